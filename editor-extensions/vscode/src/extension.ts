@@ -1,7 +1,7 @@
 import * as path from "path";
 import * as fs from "fs";
 import * as os from "os";
-import { workspace, ExtensionContext, window, OutputChannel, commands } from "vscode";
+import { workspace, ExtensionContext, window, OutputChannel, commands, TextEditor, StatusBarAlignment, Uri, Position, Range, Selection } from "vscode";
 
 import {
   LanguageClient,
@@ -10,10 +10,36 @@ import {
   Executable,
   State,
   Trace,
+  StaticFeature,
+  ClientCapabilities,
+  FeatureState,
 } from "vscode-languageclient/node";
 
 import { GrenCompilerManager } from "./compiler-manager";
 
+/**
+ * Tells the server which VS Code commands it may reference from responses,
+ * e.g. re-opening suggestions after completing a module qualifier.
+ */
+class ClientCommandsFeature implements StaticFeature {
+  fillClientCapabilities(capabilities: ClientCapabilities): void {
+    capabilities.experimental = {
+      ...(capabilities.experimental as object | undefined),
+      commands: {
+        commands: ["editor.action.triggerSuggest", "grenLsp.showReferences", "grenLsp.manageExports"],
+      },
+    };
+  }
+
+  initialize(): void {}
+
+  getState(): FeatureState {
+    return { kind: "static" };
+  }
+
+  clear(): void {}
+}
+
 let client: LanguageClient;
 let outputChannel: OutputChannel;
 let compilerManager: GrenCompilerManager;
@@ -35,6 +61,9 @@ export function activate(context: ExtensionContext) {
   
   console.log("Gren LSP Extension: Compiler manager created");
   
+  // Subsystems the server was asked to log at trace level
+  const tracedSubsystems = new Set<string>();
+
   // Register commands
   context.subscriptions.push(
     commands.registerCommand('grenLsp.downloadCompiler', () => compilerManager.downloadCompilerCommand()),
@@ -54,6 +83,160 @@ export function activate(context: ExtensionContext) {
       } else {
         outputChannel.appendLine(`❌ Client is not running - cannot test server connection`);
       }
+    }),
+    commands.registerCommand('grenLsp.showCapabilitiesReport', async () => {
+      if (client.state !== State.Running) {
+        window.showWarningMessage('Gren language server is not running');
+        return;
+      }
+
+      const report = await client.sendRequest<{
+        features: { feature: string; status: 'active' | 'degraded' | 'disabled'; reason?: string; usage?: string }[];
+      }>('gren/capabilitiesReport');
+
+      outputChannel.appendLine('Gren language server capabilities:');
+      for (const { feature, status, reason, usage } of report.features) {
+        outputChannel.appendLine(`   ${feature}: ${status}${reason ? ` - ${reason}` : ''}`);
+        if (usage) {
+          outputChannel.appendLine(`      ${usage}`);
+        }
+      }
+      outputChannel.show(true);
+
+      const problems = report.features.filter(feature => feature.status !== 'active' && feature.reason);
+      if (problems.length > 0) {
+        window.showWarningMessage(`Gren: ${problems.map(p => `${p.feature} ${p.status}`).join(', ')}. See the output channel for details.`);
+      }
+    }),
+    commands.registerCommand('grenLsp.toggleSubsystemTrace', async () => {
+      if (client.state !== State.Running) {
+        window.showWarningMessage('Gren language server is not running');
+        return;
+      }
+
+      const picked = await window.showQuickPick(
+        ['completion', 'index', 'compile', 'rename'].map(subsystem => ({
+          label: subsystem,
+          description: tracedSubsystems.has(subsystem) ? 'traced' : undefined,
+        })),
+        { placeHolder: 'Turn trace logging of a server subsystem on or off' }
+      );
+      if (!picked) {
+        return;
+      }
+
+      const enabled = !tracedSubsystems.has(picked.label);
+      if (enabled) {
+        tracedSubsystems.add(picked.label);
+      } else {
+        tracedSubsystems.delete(picked.label);
+      }
+      await client.sendNotification('gren/setTrace', { subsystem: picked.label, enabled });
+      outputChannel.appendLine(`Trace logging of ${picked.label} ${enabled ? 'on' : 'off'}`);
+    }),
+    ...(['up', 'down'] as const).map(direction =>
+      commands.registerCommand(`grenLsp.moveDeclaration${direction === 'up' ? 'Up' : 'Down'}`, async () => {
+        const editor = window.activeTextEditor;
+        if (!editor || editor.document.languageId !== 'gren' || client.state !== State.Running) {
+          return;
+        }
+        await commands.executeCommand('gren.moveDeclaration', {
+          textDocument: { uri: editor.document.uri.toString() },
+          position: { line: editor.selection.active.line, character: editor.selection.active.character },
+          direction,
+        });
+      })
+    ),
+    commands.registerCommand('grenLsp.renameStringLiteral', async () => {
+      const editor = window.activeTextEditor;
+      if (!editor || editor.document.languageId !== 'gren' || client.state !== State.Running) {
+        return;
+      }
+      const newValue = await window.showInputBox({
+        prompt: 'Replace every string literal in the project with the same value as the one at the cursor',
+        placeHolder: 'New value, without quotes',
+      });
+      if (newValue === undefined) {
+        return;
+      }
+      await commands.executeCommand('gren.renameStringLiteral', {
+        textDocument: { uri: editor.document.uri.toString() },
+        position: { line: editor.selection.active.line, character: editor.selection.active.character },
+        newValue,
+      });
+    }),
+    commands.registerCommand('grenLsp.openModule', async () => {
+      if (client.state !== State.Running) {
+        window.showWarningMessage('Gren language server is not running');
+        return;
+      }
+
+      type ModuleMatch = { moduleName: string; location: { uri: string; range: { start: { line: number; character: number } } } };
+      const picker = window.createQuickPick<{ label: string; match: ModuleMatch }>();
+      picker.placeholder = 'Module name, e.g. D.Li for Data.List';
+      const search = async (moduleName: string) => {
+        picker.busy = true;
+        const matches = await client.sendRequest<ModuleMatch[]>('gren/openModule', { moduleName });
+        // The server's fuzzy matching decides what's shown, the picker's own filter would drop `D.Li` matches
+        picker.items = matches.map(match => ({ label: match.moduleName, alwaysShow: true, match }));
+        picker.busy = false;
+      };
+      picker.onDidChangeValue(search);
+      picker.onDidAccept(async () => {
+        const picked = picker.selectedItems[0];
+        picker.hide();
+        if (!picked) {
+          return;
+        }
+        const { uri, range } = picked.match.location;
+        const editor = await window.showTextDocument(Uri.parse(uri));
+        const position = new Position(range.start.line, range.start.character);
+        editor.selection = new Selection(position, position);
+        editor.revealRange(new Range(position, position));
+      });
+      picker.onDidHide(() => picker.dispose());
+      picker.show();
+      await search('');
+    }),
+    // Target of the "exposes N of M top-level definitions" code lens
+    commands.registerCommand('grenLsp.manageExports', async (uri: string) => {
+      if (client.state !== State.Running) {
+        window.showWarningMessage('Gren language server is not running');
+        return;
+      }
+
+      type ModuleExport = { name: string; kind: string; exposed: boolean; externalUsages: number };
+      const exports = await client.sendRequest<{ moduleName: string; declarations: ModuleExport[] } | null>(
+        'gren/moduleExports',
+        { textDocument: { uri } }
+      );
+      if (!exports) {
+        return;
+      }
+      const items = exports.declarations.map(declaration => ({
+        label: declaration.name,
+        description: declaration.kind,
+        detail: `used ${declaration.externalUsages} times in other modules`,
+        picked: declaration.exposed,
+        declaration,
+      }));
+      const picked = await window.showQuickPick(items, {
+        canPickMany: true,
+        placeHolder: `Declarations ${exports.moduleName} exposes`,
+      });
+      if (!picked) {
+        return;
+      }
+      const exposed = new Set(picked.map(item => item.declaration.name));
+      await commands.executeCommand('gren.editExposing', {
+        textDocument: { uri },
+        expose: exports.declarations.filter(d => !d.exposed && exposed.has(d.name)).map(d => d.name),
+        unexpose: exports.declarations.filter(d => d.exposed && !exposed.has(d.name)).map(d => d.name),
+      });
+    }),
+    // Target of the "Used N times" link in hovers
+    commands.registerCommand('grenLsp.showReferences', async (uri: string, position: { line: number; character: number }) => {
+      await commands.executeCommand('editor.action.findReferences', Uri.parse(uri), new Position(position.line, position.character));
     })
   );
   
@@ -209,11 +392,18 @@ export function activate(context: ExtensionContext) {
 
     // Options to control the language client
     const clientOptions: LanguageClientOptions = {
-      // Register the server for Gren documents
-      documentSelector: [{ scheme: "file", language: "gren" }],
+      // Register the server for Gren files, and for unsaved buffers, which it checks as
+      // scratch modules of their own
+      documentSelector: [
+        { scheme: "file", language: "gren" },
+        { scheme: "untitled", language: "gren" },
+      ],
       synchronize: {
-        // Notify the server about file changes to Gren files
-        fileEvents: workspace.createFileSystemWatcher("**/*.gren"),
+        // Notify the server about file changes to Gren files and the project configuration
+        fileEvents: [
+          workspace.createFileSystemWatcher("**/*.gren"),
+          workspace.createFileSystemWatcher("**/.gren-lsp.toml"),
+        ],
         // Synchronize the configuration section to the server
         configurationSection: 'grenLsp'
       },
@@ -225,7 +415,7 @@ export function activate(context: ExtensionContext) {
     };
     
     outputChannel.appendLine(`📋 Client options configured:`);
-    outputChannel.appendLine(`  - Document selector: file:gren`);
+    outputChannel.appendLine(`  - Document selector: file:gren, untitled:gren`);
     outputChannel.appendLine(`  - File watcher: **/*.gren`);
     outputChannel.appendLine(`  - Output channel: ${clientOptions.outputChannelName}`);
 
@@ -238,6 +428,9 @@ export function activate(context: ExtensionContext) {
       clientOptions
     );
 
+    // Advertise client-side commands the server may attach to completion items
+    client.registerFeature(new ClientCommandsFeature());
+
     // Enable LSP protocol tracing if verbose mode is on
     if (traceLevel === 'verbose') {
       outputChannel.appendLine(`🔍 Enabling verbose LSP protocol tracing...`);
@@ -283,6 +476,41 @@ export function activate(context: ExtensionContext) {
       outputChannel.appendLine(`📄 Check server logs at: ${logPath}`);
       outputChannel.appendLine(`🎉 Extension is now active and ready!`);
       console.log("Gren LSP Extension: LSP client started successfully in", duration, "ms");
+
+      // Tell the server which document is focused, so it's checked first when the
+      // restored tabs are all opened at once
+      const sendActiveDocument = (editor: TextEditor | undefined) => {
+        if (client && editor && editor.document.languageId === 'gren') {
+          client.sendNotification('gren/activeDocument', {
+            textDocument: { uri: editor.document.uri.toString() },
+          });
+        }
+      };
+      sendActiveDocument(window.activeTextEditor);
+      context.subscriptions.push(window.onDidChangeActiveTextEditor(sendActiveDocument));
+
+      // Show when files an edit changed haven't been re-indexed yet, as references and
+      // renames may miss what changed in them until they are
+      const indexStatus = window.createStatusBarItem(StatusBarAlignment.Left);
+      context.subscriptions.push(indexStatus);
+      let pendingFiles: string[] = [];
+      const showIndexStatus = (editor: TextEditor | undefined) => {
+        if (pendingFiles.length === 0) {
+          indexStatus.hide();
+          return;
+        }
+        const activePending = editor !== undefined && pendingFiles.includes(editor.document.uri.toString());
+        indexStatus.text = activePending
+          ? '$(sync~spin) Gren index: this file is pending'
+          : `$(sync~spin) Gren index: ${pendingFiles.length} file(s) pending`;
+        indexStatus.tooltip = `Results from the index may be incomplete until these files are re-indexed:\n${pendingFiles.join('\n')}`;
+        indexStatus.show();
+      };
+      client.onNotification('gren/indexStatus', (status: { generation: number; pendingFiles: string[] }) => {
+        pendingFiles = status.pendingFiles;
+        showIndexStatus(window.activeTextEditor);
+      });
+      context.subscriptions.push(window.onDidChangeActiveTextEditor(showIndexStatus));
       outputChannel.appendLine(`\n💡 You should now see two channels in the Output panel:`);
       outputChannel.appendLine(`  - "Gren LSP Extension" (this channel) - Extension logs`);
       outputChannel.appendLine(`  - "Gren LSP Server" - LSP communication logs`);
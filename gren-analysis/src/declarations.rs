@@ -0,0 +1,543 @@
+//! Top-level declarations of a module
+//!
+//! Finds the functions, types, constructors and module name a file declares, with their
+//! type signatures and `{-| -}` documentation comments, using tree-sitter queries over the
+//! parsed file.
+
+use crate::parser::Parser;
+use crate::text::TextRange;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use tracing::debug;
+use tree_sitter::{Node, Query, QueryCursor, Tree};
+
+/// What a declaration declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeclarationKind {
+    /// A top-level value, with or without parameters
+    Function,
+    /// A custom type or type alias
+    Type,
+    /// A variant of a custom type
+    Constructor,
+    Module,
+}
+
+/// A name declared at the top level of a module
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: DeclarationKind,
+    /// The declared name, not the whole declaration
+    pub range: TextRange,
+    /// The type a constructor belongs to
+    pub container: Option<String>,
+    /// The annotation of a function, the definition of a type, or the function type of a
+    /// constructor, with whitespace collapsed
+    pub signature: Option<String>,
+    /// The documentation comment before the declaration, without its delimiters
+    pub documentation: Option<String>,
+}
+
+/// Finds the declarations of parsed Gren modules. The queries are compiled once, so one
+/// extractor is best reused for many files.
+pub struct DeclarationExtractor {
+    function_query: Query,
+    type_query: Query,
+    constructor_query: Query,
+    module_query: Query,
+    doc_query: Query,
+}
+
+impl DeclarationExtractor {
+    pub fn new() -> Result<Self> {
+        let language = Parser::language();
+
+        // Query for function definitions and type annotations (top-level only)
+        let function_query = Query::new(
+            language,
+            r#"
+            ; Top-level function value declarations
+            (file
+                (value_declaration
+                    (function_declaration_left
+                        (lower_case_identifier) @function.name)))
+
+            ; Top-level function type annotations
+            (file
+                (type_annotation
+                    (lower_case_identifier) @function.type_name
+                    (colon)
+                    (type_expression) @function.type_sig))
+        "#,
+        )
+        .context("Failed to create function query")?;
+
+        // Query for type definitions and aliases
+        let type_query = Query::new(
+            language,
+            r#"
+            ; Type declarations (union types)
+            (type_declaration
+                (upper_case_identifier) @type.name) @type.definition
+
+            ; Type aliases
+            (type_alias_declaration
+                (upper_case_identifier) @type.name) @type.alias
+        "#,
+        )
+        .context("Failed to create type query")?;
+
+        // Query for union type constructors
+        let constructor_query = Query::new(
+            language,
+            r#"
+            ; Union type constructors with parent type
+            (type_declaration
+                (upper_case_identifier) @constructor.parent_type
+                (union_variant
+                    (upper_case_identifier) @constructor.name)) @constructor.definition
+        "#,
+        )
+        .context("Failed to create constructor query")?;
+
+        // Query for module declarations
+        let module_query = Query::new(
+            language,
+            r#"
+            ; Module declarations
+            (module_declaration
+                (upper_case_qid
+                    (upper_case_identifier) @module.name)) @module.definition
+        "#,
+        )
+        .context("Failed to create module query")?;
+
+        // Query for block comments that are documentation comments
+        let doc_query = Query::new(
+            language,
+            r#"
+            (block_comment) @doc.comment
+        "#,
+        )
+        .context("Failed to create documentation query")?;
+
+        Ok(Self {
+            function_query,
+            type_query,
+            constructor_query,
+            module_query,
+            doc_query,
+        })
+    }
+
+    /// Every top-level declaration in the tree: functions, then types, constructors and
+    /// the module
+    pub fn extract(&self, tree: &Tree, source: &str) -> Vec<Declaration> {
+        let mut declarations = Vec::new();
+        let mut cursor = QueryCursor::new();
+
+        // First, extract all documentation comments and associate them with their line positions
+        let doc_comments = self.extract_documentation_comments(tree, source);
+
+        declarations.extend(self.extract_functions(tree, source, &mut cursor, &doc_comments));
+        declarations.extend(self.extract_types(tree, source, &mut cursor, &doc_comments));
+        declarations.extend(self.extract_constructors(tree, source, &mut cursor, &doc_comments));
+        declarations.extend(self.extract_modules(tree, source, &mut cursor, &doc_comments));
+
+        debug!("Extracted {} declarations", declarations.len());
+        declarations
+    }
+
+    /// Extract documentation comments from the source tree
+    /// Returns a map of line numbers to documentation text
+    fn extract_documentation_comments(&self, tree: &Tree, source: &str) -> HashMap<u32, String> {
+        let mut doc_comments = HashMap::new();
+
+        let mut cursor = QueryCursor::new();
+        let source_bytes = source.as_bytes();
+        let matches = cursor.matches(&self.doc_query, tree.root_node(), source_bytes);
+
+        for m in matches {
+            for capture in m.captures {
+                let node = capture.node;
+                if let Ok(comment_text) = node.utf8_text(source_bytes) {
+                    // Check if this is a documentation comment (starts with {-|)
+                    if comment_text.starts_with("{-|") && comment_text.ends_with("-}") {
+                        // Extract the inner documentation text
+                        let inner_text = &comment_text[3..comment_text.len() - 2];
+                        let cleaned_doc = clean_documentation_text(inner_text);
+
+                        // Associate with the line where the comment ends
+                        let end_line = node.end_position().row as u32;
+                        let preview =
+                            cleaned_doc[..std::cmp::min(50, cleaned_doc.len())].to_string();
+                        doc_comments.insert(end_line, cleaned_doc);
+
+                        debug!(
+                            "Found documentation comment ending at line {}: {}",
+                            end_line, preview
+                        );
+                    }
+                }
+            }
+        }
+
+        debug!("Extracted {} documentation comments", doc_comments.len());
+        doc_comments
+    }
+
+    fn extract_functions(
+        &self,
+        tree: &Tree,
+        source: &str,
+        cursor: &mut QueryCursor,
+        doc_comments: &HashMap<u32, String>,
+    ) -> Vec<Declaration> {
+        let mut functions = Vec::new();
+        let source_bytes = source.as_bytes();
+
+        // First pass: collect all function definitions and type annotations
+        let matches = cursor.matches(&self.function_query, tree.root_node(), source_bytes);
+        let mut function_defs: HashMap<String, TextRange> = HashMap::new();
+        let mut type_annotations: HashMap<String, String> = HashMap::new();
+
+        for m in matches {
+            for capture in m.captures {
+                let node = capture.node;
+                if let Ok(text) = node.utf8_text(source_bytes) {
+                    let capture_name = &self.function_query.capture_names()[capture.index as usize];
+
+                    match capture_name.as_str() {
+                        "function.name" => {
+                            function_defs.insert(text.to_string(), TextRange::of_node(&node));
+                        }
+                        "function.type_name" => {
+                            // This is the name in a type annotation - we'll look for the matching type signature
+                            let func_name = text.to_string();
+
+                            // Find the type signature for this function
+                            for capture2 in m.captures {
+                                let capture2_name =
+                                    &self.function_query.capture_names()[capture2.index as usize];
+                                if capture2_name == "function.type_sig" {
+                                    if let Ok(type_text) = capture2.node.utf8_text(source_bytes) {
+                                        type_annotations.insert(
+                                            func_name.clone(),
+                                            clean_type_signature(type_text),
+                                        );
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        // Second pass: combine function definitions with their type annotations and documentation
+        for (func_name, range) in function_defs {
+            let signature = type_annotations.get(&func_name).cloned();
+            let documentation = find_documentation(range.start.line, doc_comments);
+
+            functions.push(Declaration {
+                name: func_name,
+                kind: DeclarationKind::Function,
+                range,
+                container: None,
+                signature,
+                documentation,
+            });
+        }
+
+        debug!("Extracted {} functions", functions.len());
+        functions
+    }
+
+    fn extract_types(
+        &self,
+        tree: &Tree,
+        source: &str,
+        cursor: &mut QueryCursor,
+        doc_comments: &HashMap<u32, String>,
+    ) -> Vec<Declaration> {
+        let mut types = Vec::new();
+        let source_bytes = source.as_bytes();
+
+        let matches = cursor.matches(&self.type_query, tree.root_node(), source_bytes);
+
+        for m in matches {
+            let mut type_name = None;
+            let mut type_definition = None;
+            let mut type_range = None;
+
+            // Process all captures for this match
+            for capture in m.captures {
+                let capture_name = &self.type_query.capture_names()[capture.index as usize];
+                let node = capture.node;
+
+                match capture_name.as_str() {
+                    "type.name" => {
+                        if let Ok(name) = node.utf8_text(source_bytes) {
+                            type_name = Some(name.to_string());
+                            type_range = Some(TextRange::of_node(&node));
+                        }
+                    }
+                    "type.definition" | "type.alias" => {
+                        if let Ok(def) = node.utf8_text(source_bytes) {
+                            // Clean up the type definition - remove "type " prefix to avoid duplication
+                            let cleaned_def = def.trim_start_matches("type ").trim();
+                            type_definition = Some(cleaned_def.to_string());
+                        }
+                    }
+                    _ => {} // Ignore other captures
+                }
+            }
+
+            // Create declaration if we have both name and range
+            if let (Some(name), Some(range)) = (type_name, type_range) {
+                let documentation = find_documentation(range.start.line, doc_comments);
+
+                types.push(Declaration {
+                    name,
+                    kind: DeclarationKind::Type,
+                    range,
+                    container: None,
+                    signature: type_definition,
+                    documentation,
+                });
+            }
+        }
+
+        debug!("Extracted {} types", types.len());
+        types
+    }
+
+    fn extract_constructors(
+        &self,
+        tree: &Tree,
+        source: &str,
+        cursor: &mut QueryCursor,
+        doc_comments: &HashMap<u32, String>,
+    ) -> Vec<Declaration> {
+        let mut constructors = Vec::new();
+        let source_bytes = source.as_bytes();
+
+        let matches = cursor.matches(&self.constructor_query, tree.root_node(), source_bytes);
+
+        for m in matches {
+            let mut constructor_name: Option<String> = None;
+            let mut parent_type: Option<String> = None;
+            let mut constructor_node = None;
+            let mut type_declaration_node = None;
+
+            // Collect both constructor name and parent type from the same match
+            for capture in m.captures {
+                let node = capture.node;
+                let capture_name = &self.constructor_query.capture_names()[capture.index as usize];
+
+                if let Ok(text) = node.utf8_text(source_bytes) {
+                    match capture_name.as_str() {
+                        "constructor.name" => {
+                            constructor_name = Some(text.to_string());
+                            constructor_node = Some(node);
+                        }
+                        "constructor.parent_type" => {
+                            parent_type = Some(text.to_string());
+                        }
+                        "constructor.definition" => {
+                            type_declaration_node = Some(node);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            // Only create a constructor declaration if we have both name and parent type
+            if let (Some(name), Some(parent), Some(constructor)) =
+                (constructor_name, parent_type, constructor_node)
+            {
+                let range = TextRange::of_node(&constructor);
+                let documentation = find_documentation(range.start.line, doc_comments);
+                let signature = type_declaration_node.and_then(|declaration| {
+                    constructor_signature(constructor, declaration, &parent, source)
+                });
+
+                debug!("Found constructor '{}' for type '{}'", name, parent);
+                constructors.push(Declaration {
+                    name,
+                    kind: DeclarationKind::Constructor,
+                    range,
+                    container: Some(parent),
+                    signature,
+                    documentation,
+                });
+            }
+        }
+
+        debug!("Extracted {} constructors", constructors.len());
+        constructors
+    }
+
+    fn extract_modules(
+        &self,
+        tree: &Tree,
+        source: &str,
+        cursor: &mut QueryCursor,
+        doc_comments: &HashMap<u32, String>,
+    ) -> Vec<Declaration> {
+        let mut modules = Vec::new();
+        let source_bytes = source.as_bytes();
+
+        let matches = cursor.matches(&self.module_query, tree.root_node(), source_bytes);
+
+        for m in matches {
+            let mut module_name: Option<String> = None;
+            let mut module_range: Option<TextRange> = None;
+
+            // Only capture the module name, not the full declaration
+            for capture in m.captures {
+                let node = capture.node;
+                let capture_name = &self.module_query.capture_names()[capture.index as usize];
+
+                if capture_name == "module.name" {
+                    if let Ok(text) = node.utf8_text(source_bytes) {
+                        module_name = Some(text.to_string());
+                        module_range = Some(TextRange::of_node(&node));
+                    }
+                }
+            }
+
+            // Only create a module declaration if we have a name
+            if let (Some(name), Some(range)) = (module_name, module_range) {
+                let documentation = find_documentation(range.start.line, doc_comments);
+
+                debug!("Found module '{}'", name);
+                modules.push(Declaration {
+                    name,
+                    kind: DeclarationKind::Module,
+                    range,
+                    container: None,
+                    signature: None,
+                    documentation,
+                });
+            }
+        }
+
+        debug!("Extracted {} modules", modules.len());
+        modules
+    }
+}
+
+/// Clean documentation text by removing leading/trailing whitespace and normalizing formatting
+fn clean_documentation_text(doc_text: &str) -> String {
+    doc_text
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty()) // Remove empty lines
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Find the documentation comment associated with a declaration at the given line
+/// Documentation comments should appear immediately before the declaration
+fn find_documentation(line: u32, doc_comments: &HashMap<u32, String>) -> Option<String> {
+    // Look for documentation comments ending 1-3 lines before the declaration
+    // This accounts for the fact that there might be a blank line between doc and declaration
+    for offset in 1..=3 {
+        if let Some(doc) = doc_comments.get(&(line.saturating_sub(offset))) {
+            return Some(doc.clone());
+        }
+    }
+    None
+}
+
+/// Signature of a union type constructor, e.g. `error -> Result error value` for `Err`
+/// in `type Result error value = Err error | Ok value`
+fn constructor_signature(
+    constructor_name: Node,
+    type_declaration: Node,
+    parent_type: &str,
+    source: &str,
+) -> Option<String> {
+    let variant = constructor_name.parent()?;
+    let source_bytes = source.as_bytes();
+
+    let mut cursor = variant.walk();
+    let mut parts: Vec<String> = variant
+        .named_children(&mut cursor)
+        .filter(|child| *child != constructor_name && !child.kind().ends_with("comment"))
+        .filter_map(|argument| argument.utf8_text(source_bytes).ok())
+        .map(clean_type_signature)
+        .collect();
+
+    let mut cursor = type_declaration.walk();
+    let type_variables: Vec<&str> = type_declaration
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "lower_type_name")
+        .filter_map(|variable| variable.utf8_text(source_bytes).ok())
+        .collect();
+
+    let mut result = vec![parent_type];
+    result.extend(type_variables);
+    parts.push(result.join(" "));
+
+    Some(parts.join(" -> "))
+}
+
+/// Clean up type signature text by removing extra whitespace and formatting
+fn clean_type_signature(sig: &str) -> String {
+    sig.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextPosition;
+
+    const SOURCE: &str = r#"module Shapes exposing (..)
+
+{-| A shape to draw -}
+type Shape a
+    = Circle Float
+    | Tagged a Shape
+
+
+{-| Area of a shape -}
+area : Shape a -> Float
+area shape =
+    0
+"#;
+
+    #[test]
+    fn test_declarations_with_signatures_and_docs() {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        let declarations = DeclarationExtractor::new().unwrap().extract(&tree, SOURCE);
+
+        let find = |name: &str| {
+            declarations
+                .iter()
+                .find(|declaration| declaration.name == name)
+                .unwrap()
+        };
+
+        let area = find("area");
+        assert_eq!(area.kind, DeclarationKind::Function);
+        assert_eq!(area.signature.as_deref(), Some("Shape a -> Float"));
+        assert_eq!(area.documentation.as_deref(), Some("Area of a shape"));
+        assert_eq!(area.range.start, TextPosition::new(10, 0));
+
+        let tagged = find("Tagged");
+        assert_eq!(tagged.kind, DeclarationKind::Constructor);
+        assert_eq!(tagged.container.as_deref(), Some("Shape"));
+        assert_eq!(tagged.signature.as_deref(), Some("a -> Shape -> Shape a"));
+
+        assert_eq!(find("Shape").kind, DeclarationKind::Type);
+        assert_eq!(find("Shapes").kind, DeclarationKind::Module);
+    }
+}
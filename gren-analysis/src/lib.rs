@@ -0,0 +1,48 @@
+//! Parsing and static analysis of Gren source code
+//!
+//! This crate is the analysis the Gren language server is built on, without the language
+//! server: no LSP types, no async runtime and no symbol database. Linters, formatters and
+//! documentation tools can use it to parse Gren, list what a module declares and check
+//! local scopes the same way the editor does.
+//!
+//! ```no_run
+//! use gren_analysis::{DeclarationExtractor, Parser};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let source = std::fs::read_to_string("src/Main.gren")?;
+//! let mut parser = Parser::new()?;
+//! if let Some(tree) = parser.parse(&source)? {
+//!     for declaration in DeclarationExtractor::new()?.extract(&tree, &source) {
+//!         let line = declaration.range.start.line + 1;
+//!         println!("{:?} {} at line {}", declaration.kind, declaration.name, line);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Syntax trees
+//!
+//! The syntax trees come from the [`tree_sitter`] crate and the node kinds of the
+//! tree-sitter Gren grammar, which is taken from its git repository. Code walking trees
+//! directly follows the grammar as it changes, while the declaration and scope APIs hide
+//! the node kinds.
+//!
+//! Positions are zero-based lines and byte columns, see [`text`]. [`syntax`] converts
+//! columns to the UTF-16 code units editors count.
+
+pub mod declarations;
+pub mod parser;
+pub mod references;
+pub mod scope;
+pub mod syntax;
+pub mod text;
+
+pub use declarations::{Declaration, DeclarationExtractor, DeclarationKind};
+pub use parser::{ParseError, Parser};
+pub use references::{module_references, NameReference, ReferenceKind};
+pub use scope::{
+    shadowed_bindings, unused_let_bindings, ShadowedBinding, ShadowedName, ShadowingKind,
+    UnusedBinding,
+};
+pub use text::{TextPosition, TextRange};
@@ -142,6 +142,14 @@ impl Parser {
             }
         }
 
+        // Narrow the range and explain the likely cause, since tree-sitter often wraps a
+        // whole declaration in one ERROR node
+        if !node.is_missing() {
+            let (primary, hint) = Self::diagnose_error(node);
+            context.primary_range = Some((primary.start_position(), primary.end_position()));
+            context.hint = hint;
+        }
+
         // Get sibling context
         if let Some(prev_sibling) = node.prev_sibling() {
             context.previous_sibling = Some(prev_sibling.kind().to_string());
@@ -153,6 +161,131 @@ impl Parser {
         context
     }
 
+    /// The token a diagnostic for an ERROR node should point at, with a hint about the
+    /// likely cause when the grammar makes one evident. Checked in order of how certain
+    /// the hint is: unbalanced delimiters, a `when` branch without `->`, then indentation.
+    fn diagnose_error(node: Node) -> (Node, Option<String>) {
+        if let Some(diagnosis) = Self::unbalanced_delimiter(node) {
+            return diagnosis;
+        }
+
+        let tokens = leaves(node);
+        let first = tokens.first().copied().unwrap_or(node);
+
+        let has_arrow = tokens
+            .iter()
+            .any(|leaf| matches!(leaf.kind(), "arrow" | "->"));
+        if Self::in_when_branch(node) && !has_arrow {
+            return (
+                first,
+                Some("possibly missing `->` after the pattern".to_string()),
+            );
+        }
+
+        if let Some(continuation) = Self::dedented_continuation(node, &tokens) {
+            return (
+                continuation,
+                Some(
+                    "possibly bad indentation: a line continuing an expression must be \
+                     indented more than the start of its declaration"
+                        .to_string(),
+                ),
+            );
+        }
+
+        (first, None)
+    }
+
+    /// An opening delimiter in the enclosing top-level declaration that is never closed,
+    /// or a closing one that was never opened
+    fn unbalanced_delimiter(node: Node) -> Option<(Node, Option<String>)> {
+        let mut scope = node;
+        while let Some(parent) = scope.parent() {
+            if parent.parent().is_none() {
+                break;
+            }
+            scope = parent;
+        }
+
+        let mut open: Vec<(Node, &str)> = Vec::new();
+        for leaf in leaves(scope) {
+            if leaf.is_missing() {
+                continue;
+            }
+            match delimiter(leaf.kind()) {
+                Some(Delimiter::Open(close)) => open.push((leaf, close)),
+                Some(Delimiter::Close(text)) => match open.last() {
+                    Some((_, expected)) if *expected == text => {
+                        open.pop();
+                    }
+                    Some((opening, expected)) => {
+                        return Some((*opening, Some(format!("possibly missing `{}`", expected))));
+                    }
+                    None => {
+                        return Some((leaf, Some(format!("unmatched `{}`", text))));
+                    }
+                },
+                None => {}
+            }
+        }
+
+        open.pop()
+            .map(|(opening, close)| (opening, Some(format!("possibly missing `{}`", close))))
+    }
+
+    fn in_when_branch(node: Node) -> bool {
+        let mut current = node.parent();
+        while let Some(ancestor) = current {
+            match ancestor.kind() {
+                "case_of_branch" | "case_of_expr" => return true,
+                // Stop at the first enclosing expression that isn't a branch
+                "let_in_expr" | "value_declaration" | "anonymous_function_expr" => return false,
+                _ => current = ancestor.parent(),
+            }
+        }
+        false
+    }
+
+    /// A token at the start of a line that isn't indented past its `let` block or, at
+    /// the top level, follows a token that can't end an expression such as `=` or `->`
+    fn dedented_continuation<'a>(node: Node<'a>, tokens: &[Node<'a>]) -> Option<Node<'a>> {
+        let first = tokens.first()?;
+
+        let mut current = node.parent();
+        while let Some(ancestor) = current {
+            if ancestor.kind() == "let_in_expr" {
+                let mut cursor = ancestor.walk();
+                let binding_column = ancestor
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "value_declaration")?
+                    .start_position()
+                    .column;
+                return (first.start_position().column < binding_column).then_some(*first);
+            }
+            current = ancestor.parent();
+        }
+
+        let mut previous = node
+            .prev_sibling()
+            .and_then(|sibling| leaves(sibling).last().copied());
+        for leaf in tokens {
+            if let Some(before) = previous {
+                let starts_line = leaf.start_position().row > before.end_position().row;
+                if starts_line
+                    && leaf.start_position().column == 0
+                    && matches!(
+                        before.kind(),
+                        "=" | "eq" | "->" | "arrow" | "in" | "is" | ","
+                    )
+                {
+                    return Some(*leaf);
+                }
+            }
+            previous = Some(*leaf);
+        }
+        None
+    }
+
     /// Try to infer what token was expected based on context
     fn infer_expected_token(parent: &Node, error_node: Node) -> Option<String> {
         match parent.kind() {
@@ -184,6 +317,46 @@ impl Parser {
     }
 }
 
+enum Delimiter {
+    /// An opening delimiter, with the text of the one closing it
+    Open(&'static str),
+    Close(&'static str),
+}
+
+fn delimiter(kind: &str) -> Option<Delimiter> {
+    match kind {
+        "(" | "left_parenthesis" => Some(Delimiter::Open(")")),
+        "[" | "left_square_bracket" => Some(Delimiter::Open("]")),
+        "{" | "left_brace" => Some(Delimiter::Open("}")),
+        ")" | "right_parenthesis" => Some(Delimiter::Close(")")),
+        "]" | "right_square_bracket" => Some(Delimiter::Close("]")),
+        "}" | "right_brace" => Some(Delimiter::Close("}")),
+        _ => None,
+    }
+}
+
+/// Tokens under `node` in source order, skipping comments
+fn leaves(node: Node) -> Vec<Node> {
+    let mut leaves = Vec::new();
+    collect_leaves(node, &mut leaves);
+    leaves
+}
+
+fn collect_leaves<'a>(node: Node<'a>, leaves: &mut Vec<Node<'a>>) {
+    if node.kind().ends_with("comment") {
+        return;
+    }
+    if node.child_count() == 0 {
+        leaves.push(node);
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_leaves(child, leaves);
+    }
+}
+
 /// Additional context for parse errors
 #[derive(Debug, Clone, Default)]
 pub struct ParseErrorContext {
@@ -192,6 +365,10 @@ pub struct ParseErrorContext {
     pub actual_text: Option<String>,
     pub previous_sibling: Option<String>,
     pub next_sibling: Option<String>,
+    /// The token the error most likely starts at, narrower than the ERROR node itself
+    pub primary_range: Option<(tree_sitter::Point, tree_sitter::Point)>,
+    /// Likely cause, e.g. "possibly missing `)`"
+    pub hint: Option<String>,
 }
 
 /// Represents a parse error found in the syntax tree
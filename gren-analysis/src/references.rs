@@ -0,0 +1,503 @@
+//! Names a module refers to, resolved to the module that defines them
+//!
+//! Every occurrence of a top-level value, type or constructor is resolved through the
+//! module's own declarations and its imports, so occurrences of a name can be told apart
+//! by where they point. Locals are left out: a parameter, `let` binding or pattern variable
+//! that happens to share a top-level name never resolves to it.
+
+use crate::syntax::child_of_kind;
+use crate::text::TextRange;
+use std::collections::{HashMap, HashSet};
+use tree_sitter::{Node, Tree};
+
+/// An occurrence of a top-level name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameReference {
+    pub name: String,
+    /// The module defining the name
+    pub module: String,
+    /// Just the name, without any qualifier
+    pub range: TextRange,
+    pub kind: ReferenceKind,
+    /// The top-level value, type or type alias whose definition the name occurs in, for
+    /// telling which functions call which and which types refer to which
+    pub container: Option<String>,
+}
+
+/// Where a name occurs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    /// Where a value or constructor is declared, or a value's type annotation
+    Declaration,
+    Expression,
+    /// A constructor in a pattern, e.g. a `when` branch
+    Pattern,
+    /// A type in an annotation, or where it's declared
+    Type,
+    /// An entry of the exposing list of a module declaration or an import
+    Exposing,
+}
+
+/// What a module's imports bring into scope
+#[derive(Default)]
+struct Imports {
+    /// Alias or full name of each imported module
+    modules: HashMap<String, String>,
+    /// Names listed in an import's exposing list
+    exposed: HashMap<String, String>,
+    /// Modules imported with `exposing (..)`
+    exposing_all: Vec<String>,
+    /// Types exposed with their constructors, `T(..)`, by module
+    open_types: Vec<String>,
+}
+
+/// Every reference to a top-level name in the module, definitions included. A name exposed
+/// by more than one `exposing (..)` import can't be pinned down without the other modules,
+/// so it's reported once for each of them.
+pub fn module_references(tree: &Tree, source: &str) -> Vec<NameReference> {
+    let root = tree.root_node();
+    let module = module_name(root, source).unwrap_or_default();
+    let top_level = top_level_names(root, source);
+    let imports = imports(root, source);
+
+    let mut references = Vec::new();
+    let mut cursor = root.walk();
+    for declaration in root.named_children(&mut cursor) {
+        let container = match declaration.kind() {
+            "value_declaration" | "type_declaration" | "type_alias_declaration" => {
+                definition_name(declaration).and_then(|name| text(name, source))
+            }
+            _ => None,
+        };
+        collect(
+            declaration,
+            source,
+            &module,
+            container,
+            &top_level,
+            &imports,
+            &mut references,
+        );
+    }
+    references
+}
+
+fn collect(
+    node: Node,
+    source: &str,
+    module: &str,
+    container: Option<&str>,
+    top_level: &HashSet<String>,
+    imports: &Imports,
+    references: &mut Vec<NameReference>,
+) {
+    let mut add = |name_node: Node, kind: ReferenceKind, modules: Vec<String>| {
+        if let Some(name) = text(name_node, source) {
+            for module in modules {
+                references.push(NameReference {
+                    name: name.to_string(),
+                    module,
+                    range: TextRange::of_node(&name_node),
+                    kind,
+                    container: container.map(str::to_string),
+                });
+            }
+        }
+    };
+
+    match node.kind() {
+        "module_declaration" => {
+            for item in exposed_items(node) {
+                add(item, ReferenceKind::Exposing, vec![module.to_string()]);
+            }
+            return;
+        }
+        "import_clause" => {
+            let imported = child_of_kind(&node, "upper_case_qid").and_then(|qid| text(qid, source));
+            if let Some(imported) = imported {
+                for item in exposed_items(node) {
+                    add(item, ReferenceKind::Exposing, vec![imported.to_string()]);
+                }
+            }
+            return;
+        }
+        "value_qid" | "upper_case_qid" => {
+            let count = node.named_child_count();
+            let name = match node.named_child(count.saturating_sub(1)) {
+                Some(name) => name,
+                None => return,
+            };
+            let value = text(name, source).unwrap_or_default();
+            let modules = if count > 1 {
+                let qualifier = source[node.start_byte()..name.start_byte()].trim_end_matches('.');
+                let qualified = imports
+                    .modules
+                    .get(qualifier)
+                    .cloned()
+                    .unwrap_or_else(|| qualifier.to_string());
+                vec![qualified]
+            } else if node.kind() == "value_qid" && is_local(node, value, source) {
+                Vec::new()
+            } else {
+                resolve(
+                    value,
+                    node.kind() == "upper_case_qid",
+                    module,
+                    top_level,
+                    imports,
+                )
+            };
+            let kind = match node.parent().map(|parent| parent.kind()) {
+                Some("type_ref") => ReferenceKind::Type,
+                Some("union_pattern") => ReferenceKind::Pattern,
+                _ => ReferenceKind::Expression,
+            };
+            add(name, kind, modules);
+            return;
+        }
+        "record_base_identifier" => {
+            let value = text(node, source).unwrap_or_default();
+            if !is_local(node, value, source) {
+                let modules = resolve(value, false, module, top_level, imports);
+                add(node, ReferenceKind::Expression, modules);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    if let Some(definition) = definition_name(node) {
+        let kind = match node.kind() {
+            "type_declaration" | "type_alias_declaration" => ReferenceKind::Type,
+            _ => ReferenceKind::Declaration,
+        };
+        add(definition, kind, vec![module.to_string()]);
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect(
+            child, source, module, container, top_level, imports, references,
+        );
+    }
+}
+
+/// Modules an unqualified name can come from, this one first
+fn resolve(
+    name: &str,
+    upper_case: bool,
+    module: &str,
+    top_level: &HashSet<String>,
+    imports: &Imports,
+) -> Vec<String> {
+    if top_level.contains(name) {
+        return vec![module.to_string()];
+    }
+    if let Some(imported) = imports.exposed.get(name) {
+        return vec![imported.clone()];
+    }
+    let mut modules = imports.exposing_all.clone();
+    if upper_case {
+        modules.extend(imports.open_types.iter().cloned());
+        modules.dedup();
+    }
+    modules
+}
+
+/// The name a top-level declaration, annotation or constructor defines
+fn definition_name(node: Node) -> Option<Node> {
+    let top_level = node
+        .parent()
+        .is_some_and(|parent| parent.parent().is_none());
+    match node.kind() {
+        "value_declaration" if top_level => child_of_kind(&node, "function_declaration_left")
+            .and_then(|left| child_of_kind(&left, "lower_case_identifier")),
+        "type_annotation" | "port_annotation" if top_level => {
+            child_of_kind(&node, "lower_case_identifier")
+        }
+        "type_declaration" | "type_alias_declaration" | "union_variant" => {
+            child_of_kind(&node, "upper_case_identifier")
+        }
+        _ => None,
+    }
+}
+
+/// Whether a parameter, `let` binding or pattern between `node` and the top level binds
+/// `name`
+fn is_local(node: Node, name: &str, source: &str) -> bool {
+    let binds = |pattern: Node| {
+        pattern_binders(pattern)
+            .into_iter()
+            .any(|binder| text(binder, source) == Some(name))
+    };
+
+    let mut current = node.parent();
+    while let Some(scope) = current {
+        let bound = match scope.kind() {
+            "value_declaration" => child_of_kind(&scope, "function_declaration_left")
+                .map(|left| {
+                    let mut cursor = left.walk();
+                    let parameters: Vec<Node> = left.named_children(&mut cursor).skip(1).collect();
+                    parameters.into_iter().any(binds)
+                })
+                .unwrap_or(false),
+            "let_in_expr" => {
+                let mut cursor = scope.walk();
+                let declarations: Vec<Node> = scope
+                    .named_children(&mut cursor)
+                    .filter(|child| child.kind() == "value_declaration")
+                    .collect();
+                declarations.into_iter().any(|declaration| {
+                    match child_of_kind(&declaration, "function_declaration_left") {
+                        Some(left) => child_of_kind(&left, "lower_case_identifier")
+                            .is_some_and(|binder| text(binder, source) == Some(name)),
+                        // Destructuring, e.g. `{ x, y } = point`
+                        None => declaration.named_child(0).is_some_and(binds),
+                    }
+                })
+            }
+            "case_of_branch" => scope
+                .child_by_field_name("pattern")
+                .or_else(|| scope.named_child(0))
+                .is_some_and(binds),
+            "anonymous_function_expr" => {
+                let mut cursor = scope.walk();
+                let patterns: Vec<Node> = scope
+                    .named_children(&mut cursor)
+                    .take_while(|child| child.kind() != "arrow")
+                    .collect();
+                patterns.into_iter().any(binds)
+            }
+            _ => false,
+        };
+        if bound {
+            return true;
+        }
+        current = scope.parent();
+    }
+    false
+}
+
+/// Identifiers a pattern binds
+fn pattern_binders(pattern: Node) -> Vec<Node> {
+    if pattern.kind() == "lower_case_identifier" {
+        return vec![pattern];
+    }
+
+    let mut cursor = pattern.walk();
+    let children: Vec<Node> = pattern.named_children(&mut cursor).collect();
+    children.into_iter().flat_map(pattern_binders).collect()
+}
+
+fn module_name(root: Node, source: &str) -> Option<String> {
+    let declaration = child_of_kind(&root, "module_declaration")?;
+    let name = child_of_kind(&declaration, "upper_case_qid")?;
+    text(name, source).map(str::to_string)
+}
+
+fn top_level_names(root: Node, source: &str) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        let mut definitions: Vec<Node> = definition_name(node).into_iter().collect();
+        if node.kind() == "type_declaration" {
+            let mut variant_cursor = node.walk();
+            definitions.extend(
+                node.named_children(&mut variant_cursor)
+                    .filter(|child| child.kind() == "union_variant")
+                    .filter_map(|variant| definition_name(variant)),
+            );
+        }
+        names.extend(
+            definitions
+                .into_iter()
+                .filter_map(|definition| text(definition, source))
+                .map(str::to_string),
+        );
+    }
+    names
+}
+
+fn imports(root: Node, source: &str) -> Imports {
+    let mut imports = Imports::default();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        if node.kind() != "import_clause" {
+            continue;
+        }
+        let imported =
+            match child_of_kind(&node, "upper_case_qid").and_then(|qid| text(qid, source)) {
+                Some(imported) => imported.to_string(),
+                None => continue,
+            };
+
+        let alias = child_of_kind(&node, "as_clause")
+            .and_then(|clause| child_of_kind(&clause, "upper_case_identifier"))
+            .and_then(|alias| text(alias, source));
+        imports
+            .modules
+            .insert(alias.unwrap_or(&imported).to_string(), imported.clone());
+
+        let list = match child_of_kind(&node, "exposing_list") {
+            Some(list) => list,
+            None => continue,
+        };
+        if child_of_kind(&list, "double_dot").is_some() {
+            imports.exposing_all.push(imported);
+            continue;
+        }
+        let mut list_cursor = list.walk();
+        for item in list.named_children(&mut list_cursor) {
+            if item.kind() == "exposed_type"
+                && child_of_kind(&item, "exposed_union_constructors").is_some()
+            {
+                imports.open_types.push(imported.clone());
+            }
+        }
+        for item in exposed_items(node) {
+            if let Some(name) = text(item, source) {
+                imports.exposed.insert(name.to_string(), imported.clone());
+            }
+        }
+    }
+    imports
+}
+
+/// Value and type names in the exposing list of a module declaration or import
+fn exposed_items(node: Node) -> Vec<Node> {
+    let list = match child_of_kind(&node, "exposing_list") {
+        Some(list) => list,
+        None => return Vec::new(),
+    };
+    let mut cursor = list.walk();
+    let items = list
+        .named_children(&mut cursor)
+        .filter_map(|item| match item.kind() {
+            "exposed_value" => Some(item),
+            "exposed_type" => child_of_kind(&item, "upper_case_identifier"),
+            _ => None,
+        })
+        .collect();
+    items
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::text::TextPosition;
+
+    const SOURCE: &str = r#"module Main exposing (view, Model)
+
+import Html as H exposing (text)
+import Dict exposing (..)
+
+type Model = Loaded
+
+view : Model -> H.Html msg
+view model =
+    let
+        title = "Main"
+    in
+    text title
+
+title = view Loaded
+
+isLoaded model =
+    when model is
+        Loaded -> 1
+
+type alias Page =
+    { model : Model }
+"#;
+
+    fn references() -> Vec<NameReference> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        module_references(&tree, SOURCE)
+    }
+
+    fn at(line: u32, column: u32) -> Vec<(String, String)> {
+        references()
+            .into_iter()
+            .filter(|reference| reference.range.start == TextPosition::new(line, column))
+            .map(|reference| (reference.name, reference.module))
+            .collect()
+    }
+
+    fn pair(name: &str, module: &str) -> Vec<(String, String)> {
+        vec![(name.to_string(), module.to_string())]
+    }
+
+    #[test]
+    fn test_definitions_and_exposing_lists() {
+        assert_eq!(at(0, 22), pair("view", "Main"));
+        assert_eq!(at(0, 28), pair("Model", "Main"));
+        assert_eq!(at(2, 27), pair("text", "Html"));
+        assert_eq!(at(5, 5), pair("Model", "Main"));
+        assert_eq!(at(5, 13), pair("Loaded", "Main"));
+        assert_eq!(at(7, 0), pair("view", "Main"));
+        assert_eq!(at(8, 0), pair("view", "Main"));
+    }
+
+    #[test]
+    fn test_resolution() {
+        // Through the alias
+        assert_eq!(at(7, 18), pair("Html", "Html"));
+        assert_eq!(at(7, 7), pair("Model", "Main"));
+        // Exposed by name
+        assert_eq!(at(12, 4), pair("text", "Html"));
+        assert_eq!(at(14, 8), pair("view", "Main"));
+        assert_eq!(at(14, 13), pair("Loaded", "Main"));
+        assert_eq!(at(14, 0), pair("title", "Main"));
+    }
+
+    #[test]
+    fn test_kinds() {
+        let kind = |line, column| {
+            references()
+                .into_iter()
+                .find(|reference| reference.range.start == TextPosition::new(line, column))
+                .map(|reference| reference.kind)
+        };
+        assert_eq!(kind(0, 28), Some(ReferenceKind::Exposing));
+        assert_eq!(kind(5, 5), Some(ReferenceKind::Type));
+        assert_eq!(kind(5, 13), Some(ReferenceKind::Declaration));
+        assert_eq!(kind(7, 0), Some(ReferenceKind::Declaration));
+        assert_eq!(kind(7, 7), Some(ReferenceKind::Type));
+        assert_eq!(kind(14, 13), Some(ReferenceKind::Expression));
+        assert_eq!(kind(18, 8), Some(ReferenceKind::Pattern));
+    }
+
+    #[test]
+    fn test_containers() {
+        let container = |line, column| {
+            references()
+                .into_iter()
+                .find(|reference| reference.range.start == TextPosition::new(line, column))
+                .and_then(|reference| reference.container)
+        };
+        assert_eq!(container(12, 4), Some("view".to_string()));
+        assert_eq!(container(14, 8), Some("title".to_string()));
+        assert_eq!(container(18, 8), Some("isLoaded".to_string()));
+        assert_eq!(container(5, 13), Some("Model".to_string()));
+        assert_eq!(container(21, 14), Some("Page".to_string()));
+        // Annotations and exposing lists are outside any definition
+        assert_eq!(container(7, 7), None);
+        assert_eq!(container(0, 22), None);
+    }
+
+    #[test]
+    fn test_locals_are_left_out() {
+        // The `let` binding and its use shadow nothing resolvable
+        assert!(at(12, 9).is_empty());
+        // `model` is a parameter
+        assert!(references()
+            .iter()
+            .all(|reference| reference.name != "model"));
+    }
+}
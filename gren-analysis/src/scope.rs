@@ -0,0 +1,484 @@
+//! Scopes of local names, and locals that shadow a top-level or imported name
+//!
+//! Gren doesn't allow shadowing, so a parameter or `let` binding reusing the name of a
+//! top-level value or an exposed import is a compile error. Finding them from the syntax
+//! tree reports them as soon as they're typed, without waiting for the compiler, and gives
+//! a rename everything it needs: every occurrence of the local in its scope, and names
+//! that are free there.
+
+use crate::syntax::child_of_kind;
+use crate::text::{TextPosition, TextRange};
+use std::collections::{HashMap, HashSet};
+use tree_sitter::{Node, Tree};
+
+/// How the shadowing name is bound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowingKind {
+    /// A function or lambda parameter
+    Parameter,
+    LetBinding,
+}
+
+impl ShadowingKind {
+    pub fn description(self) -> &'static str {
+        match self {
+            ShadowingKind::Parameter => "parameter",
+            ShadowingKind::LetBinding => "let binding",
+        }
+    }
+}
+
+/// Where the shadowed name comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShadowedName {
+    TopLevel,
+    /// Exposed by an import of this module
+    Imported(String),
+}
+
+/// A local name that shadows a top-level or imported one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowedBinding {
+    pub name: String,
+    pub kind: ShadowingKind,
+    pub shadows: ShadowedName,
+    /// The local name where it's bound
+    pub binding_range: TextRange,
+    /// The top-level definition or the import clause of the shadowed name
+    pub shadowed_range: TextRange,
+    /// The binding and every use of the local in its scope, everything a rename rewrites
+    pub occurrences: Vec<TextRange>,
+    /// New names for the local, free in its scope, the first being `name_`
+    pub suggestions: Vec<String>,
+}
+
+/// Report parameters and `let` bindings that reuse a top-level or imported name.
+/// `exposed_values` lists the values of a module, for imports that expose everything
+/// with `exposing (..)`.
+pub fn shadowed_bindings(
+    tree: &Tree,
+    source: &str,
+    exposed_values: impl Fn(&str) -> Vec<String>,
+) -> Vec<ShadowedBinding> {
+    let outer_names = outer_names(tree, source, exposed_values);
+    let mut bindings = Vec::new();
+    if !outer_names.is_empty() {
+        collect_bindings(tree.root_node(), source, &outer_names, &mut bindings);
+    }
+    bindings
+}
+
+/// A `let` binding nothing uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedBinding {
+    pub name: String,
+    pub binding_range: TextRange,
+    /// What to delete to remove the binding: the lines of its declaration and annotation,
+    /// or `let ... in` up to the body when it's the only binding
+    pub removal_range: TextRange,
+}
+
+/// Report `let` bindings used neither by the body nor by the other bindings of their
+/// `let`. A function only calling itself counts as unused. Destructuring bindings,
+/// `{ x, y } = point`, are left alone.
+pub fn unused_let_bindings(tree: &Tree, source: &str) -> Vec<UnusedBinding> {
+    let mut unused = Vec::new();
+    collect_unused_bindings(tree.root_node(), source, &mut unused);
+    unused
+}
+
+fn collect_unused_bindings(node: Node, source: &str, unused: &mut Vec<UnusedBinding>) {
+    if node.kind() == "let_in_expr" {
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        let declarations: Vec<Node> = children
+            .iter()
+            .filter(|child| child.kind() == "value_declaration")
+            .copied()
+            .collect();
+        let body = node.child_by_field_name("body").or_else(|| {
+            children.iter().rev().copied().find(|child| {
+                !matches!(
+                    child.kind(),
+                    "value_declaration" | "type_annotation" | "line_comment" | "block_comment"
+                )
+            })
+        });
+
+        for declaration in &declarations {
+            let binder = match child_of_kind(declaration, "function_declaration_left")
+                .and_then(|left| child_of_kind(&left, "lower_case_identifier"))
+            {
+                Some(binder) => binder,
+                None => continue,
+            };
+            let name = match text(binder, source) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let declaration_range = TextRange::of_node(declaration);
+            let mut uses = Vec::new();
+            collect_uses(node, name, source, &mut uses);
+            if uses
+                .iter()
+                .any(|range| !declaration_range.contains(range.start))
+            {
+                continue;
+            }
+
+            let removal_range = if declarations.len() == 1 {
+                match body {
+                    Some(body) => TextRange::new(
+                        TextRange::of_node(&node).start,
+                        TextRange::of_node(&body).start,
+                    ),
+                    None => continue,
+                }
+            } else {
+                let start = declaration
+                    .prev_named_sibling()
+                    .filter(|previous| previous.kind() == "type_annotation")
+                    .filter(|annotation| {
+                        child_of_kind(annotation, "lower_case_identifier")
+                            .and_then(|annotated| text(annotated, source))
+                            == Some(name)
+                    })
+                    .map_or(declaration_range.start, |annotation| {
+                        TextRange::of_node(&annotation).start
+                    });
+                TextRange::new(
+                    TextPosition::new(start.line, 0),
+                    TextPosition::new(declaration_range.end.line + 1, 0),
+                )
+            };
+            unused.push(UnusedBinding {
+                name: name.to_string(),
+                binding_range: TextRange::of_node(&binder),
+                removal_range,
+            });
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_unused_bindings(child, source, unused);
+    }
+}
+
+/// Top-level and imported value names, with where each comes from
+fn outer_names(
+    tree: &Tree,
+    source: &str,
+    exposed_values: impl Fn(&str) -> Vec<String>,
+) -> HashMap<String, (ShadowedName, TextRange)> {
+    let root = tree.root_node();
+    let mut names = HashMap::new();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        match node.kind() {
+            "value_declaration" => {
+                let name = child_of_kind(&node, "function_declaration_left")
+                    .and_then(|left| child_of_kind(&left, "lower_case_identifier"));
+                if let Some(name) = name {
+                    if let Some(value) = text(name, source) {
+                        names.insert(
+                            value.to_string(),
+                            (ShadowedName::TopLevel, TextRange::of_node(&name)),
+                        );
+                    }
+                }
+            }
+            "import_clause" => {
+                let module_name = match child_of_kind(&node, "upper_case_qid")
+                    .and_then(|name| text(name, source))
+                {
+                    Some(module_name) => module_name,
+                    None => continue,
+                };
+                let list = match child_of_kind(&node, "exposing_list") {
+                    Some(list) => list,
+                    None => continue,
+                };
+
+                let imported = ShadowedName::Imported(module_name.to_string());
+                if child_of_kind(&list, "double_dot").is_some() {
+                    for value in exposed_values(module_name) {
+                        names
+                            .entry(value)
+                            .or_insert((imported.clone(), TextRange::of_node(&node)));
+                    }
+                } else {
+                    let mut list_cursor = list.walk();
+                    for item in list.named_children(&mut list_cursor) {
+                        if item.kind() != "exposed_value" {
+                            continue;
+                        }
+                        if let Some(value) = text(item, source) {
+                            names
+                                .entry(value.to_string())
+                                .or_insert((imported.clone(), TextRange::of_node(&item)));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Walk the tree checking every parameter and `let` binding against the outer names
+fn collect_bindings(
+    node: Node,
+    source: &str,
+    outer_names: &HashMap<String, (ShadowedName, TextRange)>,
+    bindings: &mut Vec<ShadowedBinding>,
+) {
+    let mut check = |binder: Node, kind: ShadowingKind, scope: Node| {
+        let name = match text(binder, source) {
+            Some(name) => name,
+            None => return,
+        };
+        if let Some((shadows, shadowed_range)) = outer_names.get(name) {
+            bindings.push(ShadowedBinding {
+                name: name.to_string(),
+                kind,
+                shadows: shadows.clone(),
+                binding_range: TextRange::of_node(&binder),
+                shadowed_range: *shadowed_range,
+                occurrences: occurrences(binder, kind, scope, name, source),
+                suggestions: suggestions(name, kind, scope, source, outer_names),
+            });
+        }
+    };
+
+    let in_let = node
+        .parent()
+        .filter(|parent| parent.kind() == "let_in_expr");
+    match node.kind() {
+        "value_declaration" => match child_of_kind(&node, "function_declaration_left") {
+            Some(left) => {
+                let mut cursor = left.walk();
+                let mut parts = left.named_children(&mut cursor);
+                let function_name = parts.next();
+                for parameter in parts {
+                    for binder in pattern_binders(parameter) {
+                        check(binder, ShadowingKind::Parameter, node);
+                    }
+                }
+                if let (Some(let_in), Some(function_name)) = (in_let, function_name) {
+                    check(function_name, ShadowingKind::LetBinding, let_in);
+                }
+            }
+            // Destructuring, e.g. `{ x, y } = point`
+            None => {
+                if let (Some(let_in), Some(pattern)) = (in_let, node.named_child(0)) {
+                    for binder in pattern_binders(pattern) {
+                        check(binder, ShadowingKind::LetBinding, let_in);
+                    }
+                }
+            }
+        },
+        "anonymous_function_expr" => {
+            let mut cursor = node.walk();
+            let patterns: Vec<Node> = node
+                .named_children(&mut cursor)
+                .take_while(|child| child.kind() != "arrow")
+                .collect();
+            for pattern in patterns {
+                for binder in pattern_binders(pattern) {
+                    check(binder, ShadowingKind::Parameter, node);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_bindings(child, source, outer_names, bindings);
+    }
+}
+
+/// Identifiers a pattern binds
+fn pattern_binders(pattern: Node) -> Vec<Node> {
+    if pattern.kind() == "lower_case_identifier" {
+        return vec![pattern];
+    }
+
+    let mut cursor = pattern.walk();
+    let children: Vec<Node> = pattern.named_children(&mut cursor).collect();
+    children.into_iter().flat_map(pattern_binders).collect()
+}
+
+/// The binding and its uses within `scope`. A `let` function's annotation is renamed too.
+fn occurrences(
+    binder: Node,
+    kind: ShadowingKind,
+    scope: Node,
+    name: &str,
+    source: &str,
+) -> Vec<TextRange> {
+    let mut ranges = vec![TextRange::of_node(&binder)];
+
+    if kind == ShadowingKind::LetBinding {
+        let mut cursor = scope.walk();
+        let annotations: Vec<Node> = scope
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() == "type_annotation")
+            .filter_map(|annotation| child_of_kind(&annotation, "lower_case_identifier"))
+            .filter(|annotated| text(*annotated, source) == Some(name))
+            .collect();
+        ranges.extend(annotations.iter().map(TextRange::of_node));
+    }
+
+    collect_uses(scope, name, source, &mut ranges);
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+/// Unqualified references to `name`, including the base of a record update
+fn collect_uses(node: Node, name: &str, source: &str, ranges: &mut Vec<TextRange>) {
+    let is_use = match node.kind() {
+        "value_qid" => node.named_child_count() == 1,
+        "record_base_identifier" => true,
+        _ => false,
+    };
+    if is_use {
+        if text(node, source) == Some(name) {
+            ranges.push(TextRange::of_node(&node));
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_uses(child, name, source, ranges);
+    }
+}
+
+/// `name_` and a variant saying what the local is, leaving out any name already used in
+/// the scope or taken by a top-level or imported value
+fn suggestions(
+    name: &str,
+    kind: ShadowingKind,
+    scope: Node,
+    source: &str,
+    outer_names: &HashMap<String, (ShadowedName, TextRange)>,
+) -> Vec<String> {
+    let mut capitalized = name.to_string();
+    if let Some(first) = capitalized.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    let variant = match kind {
+        ShadowingKind::Parameter => format!("{}Arg", name),
+        ShadowingKind::LetBinding => format!("local{}", capitalized),
+    };
+
+    let mut used = HashSet::new();
+    collect_identifiers(scope, source, &mut used);
+    [format!("{}_", name), variant]
+        .into_iter()
+        .filter(|suggestion| {
+            !used.contains(suggestion.as_str()) && !outer_names.contains_key(suggestion)
+        })
+        .collect()
+}
+
+fn collect_identifiers<'a>(node: Node, source: &'a str, identifiers: &mut HashSet<&'a str>) {
+    if node.kind() == "lower_case_identifier" {
+        if let Some(identifier) = text(node, source) {
+            identifiers.insert(identifier);
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_identifiers(child, source, identifiers);
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    const SOURCE: &str = r#"module Main exposing (update)
+
+import Html exposing (text)
+
+update msg model =
+    let
+        text =
+            String.fromInt model.count
+    in
+    { model | label = text }
+"#;
+
+    #[test]
+    fn test_let_binding_shadowing_an_import() {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        let found = shadowed_bindings(&tree, SOURCE, |_| Vec::new());
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].shadows, ShadowedName::Imported("Html".to_string()));
+        assert_eq!(
+            found[0]
+                .occurrences
+                .iter()
+                .map(|range| range.start)
+                .collect::<Vec<_>>(),
+            vec![TextPosition::new(6, 8), TextPosition::new(9, 22)]
+        );
+        assert_eq!(found[0].suggestions, vec!["text_", "localText"]);
+    }
+
+    #[test]
+    fn test_unused_let_bindings() {
+        let source = r#"module Main exposing (view)
+
+view model =
+    let
+        label : String
+        label =
+            String.fromInt model.count
+
+        loop n =
+            loop (n - 1)
+
+        count =
+            model.count
+    in
+    label
+
+total model =
+    let
+        unused =
+            1
+    in
+    model.total
+"#;
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        let unused = unused_let_bindings(&tree, source);
+
+        let names: Vec<&str> = unused.iter().map(|binding| binding.name.as_str()).collect();
+        assert_eq!(names, vec!["loop", "count", "unused"]);
+        assert_eq!(
+            unused[0].removal_range,
+            TextRange::new(TextPosition::new(8, 0), TextPosition::new(10, 0))
+        );
+        // The only binding goes with its `let ... in`
+        assert_eq!(
+            unused[2].removal_range,
+            TextRange::new(TextPosition::new(17, 4), TextPosition::new(21, 4))
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Walking syntax trees
+//!
+//! Tree-sitter counts columns in bytes while editors count them in UTF-16 code units. The
+//! conversions here take the source the tree was parsed from, so the two agree on lines
+//! holding non-ASCII text.
+
+use tree_sitter::Node;
+
+/// The first child of `node` of the given kind
+pub fn child_of_kind<'a>(node: &Node<'a>, kind: &str) -> Option<Node<'a>> {
+    let mut cursor = node.walk();
+    let child = node
+        .children(&mut cursor)
+        .find(|child| child.kind() == kind);
+    child
+}
+
+/// The column of byte `offset` into `source` in UTF-16 code units. An offset inside a
+/// character counts as that character's start, and one past the end as the end.
+pub fn utf16_column(source: &str, offset: usize) -> u32 {
+    let offset = char_boundary(source, offset);
+    let line_start = source[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    source[line_start..offset].encode_utf16().count() as u32
+}
+
+/// The byte offset zero-based `line` starts at, `None` past the last line
+pub fn line_offset(source: &str, line: usize) -> Option<usize> {
+    if line == 0 {
+        return Some(0);
+    }
+    source
+        .match_indices('\n')
+        .nth(line - 1)
+        .map(|(newline, _)| newline + 1)
+}
+
+/// The byte offset of the UTF-16 `column` on zero-based `line`, clamped to the end of the
+/// line, or `None` past the last line
+pub fn utf16_offset(source: &str, line: usize, column: u32) -> Option<usize> {
+    let line_start = line_offset(source, line)?;
+    let rest = &source[line_start..];
+    let line_text = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let mut units = 0;
+    for (index, character) in line_text.char_indices() {
+        if units >= column as usize {
+            return Some(line_start + index);
+        }
+        units += character.len_utf16();
+    }
+    Some(line_start + line_text.len())
+}
+
+/// `offset` moved back to the start of the character it's in, and clamped to `source`
+pub fn char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_child_of_kind() {
+        let source = "module Main exposing (main)\n";
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        let module = child_of_kind(&tree.root_node(), "module_declaration").unwrap();
+        assert!(child_of_kind(&module, "exposing_list").is_some());
+        assert!(child_of_kind(&module, "import_clause").is_none());
+    }
+
+    #[test]
+    fn test_utf16_column_counts_code_units() {
+        let source = "a\n\"é😀\" x";
+        let x = source.rfind('x').unwrap();
+        // é is one code unit and two bytes, 😀 two code units and four bytes
+        assert_eq!(utf16_column(source, x), 6);
+        assert_eq!(utf16_column(source, 0), 0);
+        assert_eq!(utf16_column(source, source.len() + 3), 7);
+        // Inside é
+        assert_eq!(utf16_column(source, 4), 1);
+    }
+
+    #[test]
+    fn test_utf16_offset_round_trips() {
+        let source = "a\n\"é😀\" x\nlast";
+        let x = source.rfind('x').unwrap();
+        assert_eq!(utf16_offset(source, 1, 6), Some(x));
+        assert_eq!(
+            utf16_offset(source, 1, 99),
+            Some(source.find("\nlast").unwrap())
+        );
+        assert_eq!(utf16_offset(source, 2, 0), Some(source.len() - 4));
+        assert_eq!(utf16_offset(source, 3, 0), None);
+        assert_eq!(line_offset(source, 1), Some(2));
+    }
+}
@@ -0,0 +1,54 @@
+//! Positions in source text
+//!
+//! Lines and columns count from zero, and columns are byte offsets into the line, as
+//! tree-sitter reports them. Editors usually count columns in UTF-16 code units, so a tool
+//! talking to one converts at its own boundary.
+
+use tree_sitter::{Node, Point};
+
+/// A zero-based line and byte column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextPosition {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl TextPosition {
+    pub fn new(line: u32, column: u32) -> Self {
+        Self { line, column }
+    }
+}
+
+impl From<Point> for TextPosition {
+    fn from(point: Point) -> Self {
+        Self::new(point.row as u32, point.column as u32)
+    }
+}
+
+impl From<TextPosition> for Point {
+    fn from(position: TextPosition) -> Self {
+        Point::new(position.line as usize, position.column as usize)
+    }
+}
+
+/// The text from `start` up to, but not including, `end`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextRange {
+    pub start: TextPosition,
+    pub end: TextPosition,
+}
+
+impl TextRange {
+    pub fn new(start: TextPosition, end: TextPosition) -> Self {
+        Self { start, end }
+    }
+
+    /// The text a syntax node spans
+    pub fn of_node(node: &Node) -> Self {
+        Self::new(node.start_position().into(), node.end_position().into())
+    }
+
+    pub fn contains(&self, position: TextPosition) -> bool {
+        self.start <= position && position <= self.end
+    }
+}
@@ -0,0 +1,314 @@
+//! Type aliases that refer back to themselves
+//!
+//! An alias is replaced by its definition wherever it's used, so one that refers to itself,
+//! directly or through other aliases, would expand forever. The compiler rejects these with
+//! a long explanation once the project is built; finding the cycle in the syntax tree
+//! reports it while the alias is written, with the path through the aliases involved and a
+//! fix turning the alias into a custom type, which may be recursive. Import cycles aren't
+//! allowed either, so a cycle never spans modules and looking at one file finds them all.
+
+use crate::syntax::{child_of_kind, node_range};
+use lsp_types::*;
+use std::collections::{HashMap, VecDeque};
+use tree_sitter::{Node, Tree};
+
+/// Diagnostic code attached to recursive type aliases
+pub const RECURSIVE_ALIAS: &str = "recursive-type-alias";
+
+/// A type alias on a cycle of aliases
+#[derive(Debug, Clone, PartialEq)]
+pub struct AliasCycle {
+    pub name: String,
+    /// The aliases on the cycle in the order they refer to each other, starting and ending
+    /// with `name`
+    pub path: Vec<String>,
+    /// The alias name in its declaration
+    pub name_range: Range,
+    /// Name ranges of the other aliases on the cycle, in path order
+    pub other_ranges: Vec<(String, Range)>,
+    /// Edits turning the alias into a custom type with one constructor of the same name
+    pub wrapper_edits: Vec<TextEdit>,
+}
+
+impl AliasCycle {
+    pub fn diagnostic(&self, uri: &Url) -> Diagnostic {
+        let path = self.path.join(" -> ");
+        let message = if self.path.len() == 2 {
+            format!(
+                "The type alias `{}` refers to itself. Aliases are expanded where they're used, so it would be infinitely large; a custom type can be recursive.",
+                self.name
+            )
+        } else {
+            format!(
+                "The type alias `{}` refers to itself through other aliases: {}. Aliases are expanded where they're used, so it would be infinitely large; a custom type can be recursive.",
+                self.name, path
+            )
+        };
+
+        let related_information = (!self.other_ranges.is_empty()).then(|| {
+            self.other_ranges
+                .iter()
+                .map(|(name, range)| DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), *range),
+                    message: format!("`{}` is on the cycle {}", name, path),
+                })
+                .collect()
+        });
+
+        Diagnostic {
+            range: self.name_range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(RECURSIVE_ALIAS.to_string())),
+            code_description: None,
+            source: Some("gren-lsp".to_string()),
+            message,
+            related_information,
+            tags: None,
+            data: None,
+        }
+    }
+}
+
+/// An alias declaration and the aliases of the same module its definition refers to
+struct AliasDeclaration<'tree> {
+    declaration: Node<'tree>,
+    name: Node<'tree>,
+    references: Vec<String>,
+}
+
+/// Report every type alias that refers back to itself, with its shortest cycle
+pub fn alias_cycles(tree: &Tree, source: &str) -> Vec<AliasCycle> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut aliases: Vec<(String, AliasDeclaration)> = root
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "type_alias_declaration")
+        .filter_map(|declaration| {
+            let name = child_of_kind(&declaration, "upper_case_identifier")?;
+            let body = child_of_kind(&declaration, "type_expression")?;
+            let mut references = Vec::new();
+            collect_type_references(body, source, &mut references);
+            Some((
+                text(name, source)?.to_string(),
+                AliasDeclaration {
+                    declaration,
+                    name,
+                    references,
+                },
+            ))
+        })
+        .collect();
+
+    // Only references to this module's aliases can continue a cycle
+    let names: Vec<String> = aliases.iter().map(|(name, _)| name.clone()).collect();
+    for (_, alias) in &mut aliases {
+        alias
+            .references
+            .retain(|reference| names.contains(reference));
+    }
+    let by_name: HashMap<&str, &AliasDeclaration> = aliases
+        .iter()
+        .map(|(name, alias)| (name.as_str(), alias))
+        .collect();
+
+    let mut cycles = Vec::new();
+    for (name, alias) in &aliases {
+        let path = match shortest_cycle(name, &by_name) {
+            Some(path) => path,
+            None => continue,
+        };
+        let other_ranges = path[1..path.len() - 1]
+            .iter()
+            .filter_map(|other| {
+                Some((
+                    other.clone(),
+                    node_range(&by_name.get(other.as_str())?.name, source),
+                ))
+            })
+            .collect();
+        cycles.push(AliasCycle {
+            name: name.clone(),
+            name_range: node_range(&alias.name, source),
+            other_ranges,
+            wrapper_edits: wrapper_edits(alias, name, source),
+            path,
+        });
+    }
+    cycles
+}
+
+/// The shortest path of alias references leading from `start` back to it
+fn shortest_cycle(start: &str, aliases: &HashMap<&str, &AliasDeclaration>) -> Option<Vec<String>> {
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(current) = queue.pop_front() {
+        for next in &aliases.get(current)?.references {
+            let next = next.as_str();
+            if next == start {
+                let mut path = vec![start.to_string()];
+                let mut step = current;
+                while step != start {
+                    path.push(step.to_string());
+                    step = previous[step];
+                }
+                path.push(start.to_string());
+                path.reverse();
+                return Some(path);
+            }
+            if !previous.contains_key(next) {
+                previous.insert(next, current);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Rewrite `type alias Name a = Definition` to `type Name a = Name (Definition)`,
+/// leaving out the parentheses when the definition is a single term
+fn wrapper_edits(alias: &AliasDeclaration, name: &str, source: &str) -> Vec<TextEdit> {
+    let declaration = alias.declaration;
+    let (type_keyword, body) = match (
+        declaration.child(0),
+        child_of_kind(&declaration, "type_expression"),
+    ) {
+        (Some(type_keyword), Some(body)) => (type_keyword, body),
+        _ => return Vec::new(),
+    };
+
+    let keyword_end = node_range(&type_keyword, source).end;
+    let name_start = node_range(&alias.name, source).start;
+    let body_range = node_range(&body, source);
+    let mut edits = vec![TextEdit::new(
+        Range::new(keyword_end, name_start),
+        " ".to_string(),
+    )];
+    if is_single_term(body) {
+        edits.push(TextEdit::new(
+            Range::new(body_range.start, body_range.start),
+            format!("{} ", name),
+        ));
+    } else {
+        edits.push(TextEdit::new(
+            Range::new(body_range.start, body_range.start),
+            format!("{} (", name),
+        ));
+        edits.push(TextEdit::new(
+            Range::new(body_range.end, body_range.end),
+            ")".to_string(),
+        ));
+    }
+    edits
+}
+
+/// Whether a type expression can be a constructor argument without parentheses: a
+/// record, a type variable or a type without arguments
+fn is_single_term(body: Node) -> bool {
+    let mut cursor = body.walk();
+    let terms: Vec<Node> = body
+        .named_children(&mut cursor)
+        .filter(|node| !node.kind().ends_with("comment"))
+        .collect();
+    match terms.as_slice() {
+        [term] => match term.kind() {
+            "record_type" | "type_variable" => true,
+            "type_ref" => term.named_child_count() == 1,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Collect the unqualified type names a type expression refers to
+fn collect_type_references(node: Node, source: &str, references: &mut Vec<String>) {
+    if node.kind() == "type_ref" {
+        if let Some(name) = child_of_kind(&node, "upper_case_qid").and_then(|qid| text(qid, source))
+        {
+            if !name.contains('.') && !references.iter().any(|known| known == name) {
+                references.push(name.to_string());
+            }
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_type_references(child, source, references);
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    const SOURCE: &str = r#"module Tree exposing (..)
+
+type alias Node =
+    { value : Int
+    , children : Array Node
+    }
+
+type alias Forest =
+    Array Branch
+
+type alias Branch =
+    { label : String
+    , forest : Forest
+    }
+
+type alias Page =
+    { title : String
+    , next : Maybe Link
+    }
+
+type Link
+    = Link Page
+"#;
+
+    fn cycles() -> Vec<AliasCycle> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        alias_cycles(&tree, SOURCE)
+    }
+
+    #[test]
+    fn test_direct_and_mutual_cycles() {
+        let found = cycles();
+        let paths: Vec<String> = found.iter().map(|cycle| cycle.path.join(" -> ")).collect();
+        // Page goes through the custom type Link, which breaks the cycle
+        assert_eq!(
+            paths,
+            vec![
+                "Node -> Node",
+                "Forest -> Branch -> Forest",
+                "Branch -> Forest -> Branch"
+            ]
+        );
+        assert_eq!(found[0].name_range.start, Position::new(2, 11));
+        assert_eq!(found[1].other_ranges.len(), 1);
+        assert_eq!(found[1].other_ranges[0].0, "Branch");
+    }
+
+    #[test]
+    fn test_wrapper_fix() {
+        let found = cycles();
+        let forest = found.iter().find(|cycle| cycle.name == "Forest").unwrap();
+        let new_texts: Vec<&str> = forest
+            .wrapper_edits
+            .iter()
+            .map(|edit| edit.new_text.as_str())
+            .collect();
+        // `type alias Forest =\n    Array Branch` becomes `type Forest =\n    Forest (Array Branch)`
+        assert_eq!(new_texts, vec![" ", "Forest (", ")"]);
+        assert_eq!(forest.wrapper_edits[0].range.start, Position::new(7, 4));
+        assert_eq!(forest.wrapper_edits[1].range.start, Position::new(8, 4));
+
+        let node = found.iter().find(|cycle| cycle.name == "Node").unwrap();
+        assert_eq!(node.wrapper_edits[1].new_text, "Node ");
+        assert_eq!(node.wrapper_edits.len(), 2);
+    }
+}
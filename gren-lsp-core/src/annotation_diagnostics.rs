@@ -0,0 +1,298 @@
+use crate::syntax::{child_of_kind, node_range};
+use lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+/// Diagnostic code attached to annotation/definition arity hints
+pub const ANNOTATION_ARITY_MISMATCH: &str = "annotation-arity-mismatch";
+
+/// Custom types of the core packages, which can't stand for a function type
+const CORE_CUSTOM_TYPES: &[&str] = &[
+    "Array", "Bool", "Bytes", "Char", "Cmd", "Dict", "Float", "Int", "Maybe", "Never", "Order",
+    "Result", "Set", "String", "Sub", "Task",
+];
+
+/// Report definitions binding more argument patterns than the type annotation before them
+/// has arguments.
+///
+/// The compiler reports these as a type mismatch somewhere in the body, so the hint points
+/// at both the annotation and the definition instead. A definition binding fewer arguments
+/// may return a lambda, and the annotation's result may be a function when it's a type
+/// variable or an alias, so only results that can't be a function are compared. The counts
+/// are only compared syntactically, which is why these are published as hints rather than
+/// errors.
+pub fn annotation_arity_diagnostics(tree: &Tree, source: &str, uri: &Url) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let custom_types = custom_type_names(tree.root_node(), source);
+    collect_arity_mismatches(
+        tree.root_node(),
+        source,
+        uri,
+        &custom_types,
+        &mut diagnostics,
+    );
+    diagnostics
+}
+
+/// Names of the custom types the module declares
+fn custom_type_names<'a>(root: Node, source: &'a str) -> Vec<&'a str> {
+    let mut cursor = root.walk();
+    let names = root
+        .named_children(&mut cursor)
+        .filter(|declaration| declaration.kind() == "type_declaration")
+        .filter_map(|declaration| child_of_kind(&declaration, "upper_case_identifier"))
+        .filter_map(|name| name.utf8_text(source.as_bytes()).ok())
+        .collect();
+    names
+}
+
+/// Walk the tree looking for annotation/definition pairs, including those in `let` blocks
+fn collect_arity_mismatches(
+    node: Node,
+    source: &str,
+    uri: &Url,
+    custom_types: &[&str],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|child| !is_comment(child))
+        .collect();
+
+    for (index, child) in children.iter().enumerate() {
+        if child.kind() == "type_annotation" {
+            if let Some(definition) = children.get(index + 1) {
+                if let Some(diagnostic) = check_pair(child, definition, source, uri, custom_types) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+
+        collect_arity_mismatches(*child, source, uri, custom_types, diagnostics);
+    }
+}
+
+/// Compare an annotation with the declaration that follows it
+fn check_pair(
+    annotation: &Node,
+    definition: &Node,
+    source: &str,
+    uri: &Url,
+    custom_types: &[&str],
+) -> Option<Diagnostic> {
+    if definition.kind() != "value_declaration" {
+        return None;
+    }
+
+    let annotated_name = child_of_kind(annotation, "lower_case_identifier")?
+        .utf8_text(source.as_bytes())
+        .ok()?;
+    let definition_left = child_of_kind(definition, "function_declaration_left")?;
+    let defined_name = child_of_kind(&definition_left, "lower_case_identifier")?
+        .utf8_text(source.as_bytes())
+        .ok()?;
+
+    // An annotation for a different name is reported by the compiler, not by this check
+    if annotated_name != defined_name {
+        return None;
+    }
+
+    // Don't guess about declarations the parser couldn't make sense of
+    if annotation.has_error() || definition_left.has_error() {
+        return None;
+    }
+
+    let type_expression = child_of_kind(annotation, "type_expression")?;
+    let annotated_arity = count_children_of_kind(&type_expression, "arrow");
+    let mut cursor = definition_left.walk();
+    let defined_arity = definition_left
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() != "lower_case_identifier" && !is_comment(child))
+        .count();
+
+    if defined_arity <= annotated_arity {
+        return None;
+    }
+    let mut cursor = type_expression.walk();
+    let result = type_expression
+        .named_children(&mut cursor)
+        .filter(|child| !is_comment(child))
+        .last()?;
+    if !cannot_be_function(&result, source, custom_types) {
+        return None;
+    }
+
+    let annotation_range = node_range(annotation, source);
+    let definition_range = node_range(&definition_left, source);
+
+    Some(Diagnostic {
+        range: definition_range,
+        severity: Some(DiagnosticSeverity::HINT),
+        code: Some(NumberOrString::String(
+            ANNOTATION_ARITY_MISMATCH.to_string(),
+        )),
+        code_description: None,
+        source: Some("gren-lsp".to_string()),
+        message: format!(
+            "The type annotation for `{}` has {} but the definition binds {}",
+            defined_name,
+            plural_arguments(annotated_arity),
+            plural_arguments(defined_arity)
+        ),
+        related_information: Some(vec![
+            DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), annotation_range),
+                message: format!("Annotation has {}", plural_arguments(annotated_arity)),
+            },
+            DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), definition_range),
+                message: format!("Definition binds {}", plural_arguments(defined_arity)),
+            },
+        ]),
+        tags: None,
+        data: None,
+    })
+}
+
+/// Whether an annotation's result type can't stand for a function type: a record, unit,
+/// tuple or custom type. Type variables, aliases and types of other modules may.
+fn cannot_be_function(result: &Node, source: &str, custom_types: &[&str]) -> bool {
+    match result.kind() {
+        "record_type" | "unit_expr" | "tuple_type" => true,
+        "type_ref" => {
+            let name = match child_of_kind(result, "upper_case_qid")
+                .and_then(|qid| qid.utf8_text(source.as_bytes()).ok())
+            {
+                Some(name) => name,
+                None => return false,
+            };
+            CORE_CUSTOM_TYPES.contains(&name) || custom_types.contains(&name)
+        }
+        _ => false,
+    }
+}
+
+fn plural_arguments(count: usize) -> String {
+    if count == 1 {
+        "1 argument".to_string()
+    } else {
+        format!("{} arguments", count)
+    }
+}
+
+fn count_children_of_kind(node: &Node, kind: &str) -> usize {
+    let mut cursor = node.walk();
+    let count = node
+        .children(&mut cursor)
+        .filter(|child| child.kind() == kind)
+        .count();
+    count
+}
+
+fn is_comment(node: &Node) -> bool {
+    matches!(node.kind(), "line_comment" | "block_comment")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn diagnostics_for(source: &str) -> Vec<Diagnostic> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        let uri = Url::parse("file:///test/Main.gren").unwrap();
+        annotation_arity_diagnostics(&tree, source, &uri)
+    }
+
+    #[test]
+    fn test_annotation_arity_mismatch_reported() {
+        let source = r#"module Main exposing (..)
+
+add : Int -> Int
+add a b =
+    a + b
+"#;
+        let diagnostics = diagnostics_for(source);
+        assert_eq!(diagnostics.len(), 1);
+
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diagnostic.range.start.line, 3);
+        assert!(diagnostic.message.contains("1 argument"));
+        assert!(diagnostic.message.contains("binds 2 arguments"));
+
+        let related = diagnostic.related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 2);
+        assert_eq!(related[0].location.range.start.line, 2);
+        assert_eq!(related[1].location.range.start.line, 3);
+    }
+
+    #[test]
+    fn test_matching_annotation_not_reported() {
+        let source = r#"module Main exposing (..)
+
+add : Int -> Int -> Int
+add a b =
+    a + b
+
+increment : Int -> Int
+increment =
+    add 1
+
+value : Int
+value =
+    let
+        helper : Int -> Int
+        helper x =
+            x
+    in
+    helper 1
+"#;
+        assert!(diagnostics_for(source).is_empty());
+    }
+
+    #[test]
+    fn test_results_that_may_be_functions_not_reported() {
+        let source = r#"module Main exposing (..)
+
+type alias Handler =
+    Int -> Int
+
+add : Int -> Int -> Int
+add x =
+    \y -> x + y
+
+handler : Int -> Handler
+handler x y =
+    x + y
+
+apply : (a -> b) -> a -> b
+apply f x y =
+    f x y
+
+view : Model -> Html.Html Msg
+view model extra =
+    Html.text ""
+"#;
+        assert!(diagnostics_for(source).is_empty());
+    }
+
+    #[test]
+    fn test_let_binding_mismatch_reported() {
+        let source = r#"module Main exposing (..)
+
+value : Int
+value =
+    let
+        helper : Int -> { x : Int }
+        helper x y =
+            { x = x }
+    in
+    helper 1 2
+"#;
+        let diagnostics = diagnostics_for(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 6);
+    }
+}
@@ -0,0 +1,131 @@
+//! Plain comments of top-level declarations
+//!
+//! Doc comments are indexed as a symbol's documentation. The other comments explaining a
+//! declaration, those written just above it and those in its body, are indexed with it too
+//! so `gren/searchDocs` finds a function by what its comments say.
+
+use crate::syntax::child_of_kind;
+use tree_sitter::{Node, Tree};
+
+/// The comments belonging to a top-level declaration, without their delimiters
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclarationComments {
+    pub name: String,
+    pub text: String,
+}
+
+/// The comments of each top-level declaration that has any, in source order. Comments
+/// above a declaration belong to it unless something other than its annotation comes in
+/// between.
+pub fn declaration_comments(tree: &Tree, source: &str) -> Vec<DeclarationComments> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut declarations = Vec::new();
+    let mut above: Vec<&str> = Vec::new();
+    for node in root.named_children(&mut cursor) {
+        match node.kind() {
+            "line_comment" | "block_comment" => above.extend(comment_text(node, source)),
+            "type_annotation" => {}
+            "value_declaration"
+            | "type_declaration"
+            | "type_alias_declaration"
+            | "port_annotation" => {
+                let mut comments = std::mem::take(&mut above);
+                collect_comments(node, source, &mut comments);
+                let name = declaration_name(&node, source).filter(|_| !comments.is_empty());
+                if let Some(name) = name {
+                    declarations.push(DeclarationComments {
+                        name: name.to_string(),
+                        text: comments.join("\n"),
+                    });
+                }
+            }
+            _ => above.clear(),
+        }
+    }
+    declarations
+}
+
+fn collect_comments<'a>(node: Node, source: &'a str, comments: &mut Vec<&'a str>) {
+    if matches!(node.kind(), "line_comment" | "block_comment") {
+        comments.extend(comment_text(node, source));
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_comments(child, source, comments);
+    }
+}
+
+/// A comment's text without its delimiters, `None` for doc comments and empty comments
+fn comment_text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    let comment = node.utf8_text(source.as_bytes()).ok()?;
+    if comment.starts_with("{-|") {
+        return None;
+    }
+    let text = match comment.strip_prefix("{-") {
+        Some(block) => block.strip_suffix("-}").unwrap_or(block),
+        None => comment.strip_prefix("--").unwrap_or(comment),
+    }
+    .trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+fn declaration_name<'a>(node: &Node, source: &'a str) -> Option<&'a str> {
+    let name = match node.kind() {
+        "value_declaration" => child_of_kind(node, "function_declaration_left")
+            .and_then(|left| child_of_kind(&left, "lower_case_identifier")),
+        "port_annotation" => child_of_kind(node, "lower_case_identifier"),
+        _ => child_of_kind(node, "upper_case_identifier"),
+    }?;
+    name.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_declaration_comments() {
+        let source = r#"module Main exposing (..)
+
+import Date
+
+-- Accepts dates written as 2024-01-31
+{-| Parse a date. -}
+fromIsoString : String -> Maybe Date
+fromIsoString text =
+    {- the year comes first -}
+    Date.fromParts text
+
+-- Stray note
+
+view =
+    -- draws the calendar grid
+    42
+
+type Color
+    = Red
+"#;
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        assert_eq!(
+            declaration_comments(&tree, source),
+            vec![
+                DeclarationComments {
+                    name: "fromIsoString".to_string(),
+                    text: "Accepts dates written as 2024-01-31\nthe year comes first".to_string(),
+                },
+                DeclarationComments {
+                    name: "view".to_string(),
+                    text: "Stray note\ndraws the calendar grid".to_string(),
+                },
+            ]
+        );
+    }
+}
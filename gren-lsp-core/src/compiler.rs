@@ -1,7 +1,12 @@
+use crate::compiler_diagnostics::render_markdown;
+use crate::compiler_options::CompilerOptions;
+use crate::declaration_check::{isolate_declaration, DeclarationCheck, Isolation};
+use crate::formatter::Formatter;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::SystemTime;
 use tokio::process::Command as AsyncCommand;
 use tracing::{debug, info, warn};
@@ -24,6 +29,8 @@ pub struct GrenCompiler {
     cache: std::collections::HashMap<PathBuf, CompilationResult>,
     /// Cached project type
     project_type_cache: Option<ProjectType>,
+    /// Extra environment and flags for `gren make`
+    options: CompilerOptions,
 }
 
 /// Result of a compilation attempt
@@ -39,6 +46,10 @@ pub struct CompilationResult {
     pub timestamp: SystemTime,
     /// Hash of the source content compiled
     pub content_hash: u64,
+    /// Modules the compiler reported building, when it said
+    pub modules_compiled: Option<usize>,
+    /// Whether this result came from the cache instead of a compiler run
+    pub from_cache: bool,
 }
 
 /// A diagnostic message from the Gren compiler
@@ -54,6 +65,9 @@ pub struct CompilerDiagnostic {
     pub path: Option<PathBuf>,
     /// Line and column information
     pub location: Option<DiagnosticLocation>,
+    /// The message as Markdown, keeping the compiler's highlighting
+    #[serde(default)]
+    pub markdown: Option<String>,
 }
 
 /// Severity levels for compiler diagnostics
@@ -131,6 +145,20 @@ struct GrenPosition {
     column: u32,
 }
 
+/// Numbers the temporary project directories of in-memory compiles
+static NEXT_TEMP_PROJECT: AtomicU64 = AtomicU64::new(0);
+
+/// Temporary project directory of an in-memory compile, removed when dropped
+struct TempProject(PathBuf);
+
+impl Drop for TempProject {
+    fn drop(&mut self) {
+        if self.0.exists() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
 impl GrenCompiler {
     /// Extract module name from Gren source code using tree-sitter
     /// Parses the "module ModuleName exposing (...)" declaration
@@ -241,9 +269,34 @@ impl GrenCompiler {
             working_dir,
             cache: std::collections::HashMap::new(),
             project_type_cache: None,
+            options: CompilerOptions::default(),
         })
     }
 
+    /// Path of the gren executable used for compilation
+    pub fn gren_path(&self) -> &Path {
+        &self.gren_path
+    }
+
+    /// Environment and flags added to compiler runs
+    pub fn options(&self) -> &CompilerOptions {
+        &self.options
+    }
+
+    /// The formatter of this compiler, run with the same environment
+    pub fn formatter(&self) -> Formatter {
+        Formatter::new(&self.gren_path, self.options.clone())
+    }
+
+    /// Change the environment and flags compiler runs get. Cached results came from runs
+    /// with the old options, so they're dropped.
+    pub fn set_options(&mut self, options: CompilerOptions) {
+        if options != self.options {
+            self.options = options;
+            self.cache.clear();
+        }
+    }
+
     /// Find the gren executable using only configured or extension-downloaded compilers
     /// Never uses PATH to prevent version mismatches
     fn find_gren_executable() -> Result<PathBuf> {
@@ -374,7 +427,9 @@ impl GrenCompiler {
                     "📦 Using cached compilation result for {}",
                     file_path.display()
                 );
-                return Ok(cached.clone());
+                let mut cached = cached.clone();
+                cached.from_cache = true;
+                return Ok(cached);
             } else {
                 info!(
                     "🔄 Cache invalidated for {} (content changed)",
@@ -430,6 +485,7 @@ impl GrenCompiler {
         if project_type == ProjectType::Application {
             cmd.arg("--output=/dev/null");
         }
+        self.options.apply_to_make(&mut cmd);
 
         info!(
             "🔨 Running Gren compiler on {} (project type: {:?})",
@@ -489,6 +545,8 @@ impl GrenCompiler {
             global_errors,
             timestamp: SystemTime::now(),
             content_hash: self.calculate_content_hash(file_path)?,
+            modules_compiled: compiled_module_count(&stdout),
+            from_cache: false,
         };
         info!("✅ Compilation result created successfully");
 
@@ -581,12 +639,14 @@ impl GrenCompiler {
             .arg("--report=json")
             .current_dir(working_dir) // Use the provided working directory
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true); // A cancelled compile must not leave the compiler running
 
         // Only add --output for applications, not packages
         if project_type == ProjectType::Application {
             cmd.arg("--output=/dev/null");
         }
+        self.options.apply_to_make(&mut cmd);
 
         info!(
             "🔨 Running Gren compiler on {} (project type: {:?})",
@@ -644,6 +704,8 @@ impl GrenCompiler {
             global_errors,
             timestamp: SystemTime::now(),
             content_hash: self.calculate_content_hash(file_path)?,
+            modules_compiled: compiled_module_count(&stdout),
+            from_cache: false,
         };
         info!("✅ Compilation result created successfully");
 
@@ -747,6 +809,7 @@ impl GrenCompiler {
                             let diagnostic = CompilerDiagnostic {
                                 severity: DiagnosticSeverity::Error,
                                 title: problem.title,
+                                markdown: Some(render_markdown(&problem.message)),
                                 message: self.extract_message_text(problem.message),
                                 path: Some(PathBuf::from(error.path.clone())),
                                 location: Some(DiagnosticLocation {
@@ -793,6 +856,7 @@ impl GrenCompiler {
                         message: output.to_string(),
                         path: None,
                         location: None,
+                        markdown: None,
                     });
                 }
             }
@@ -834,6 +898,18 @@ impl GrenCompiler {
         Ok(hasher.finish())
     }
 
+    /// A copy of this compiler without its cache, for compiling in-memory content away
+    /// from the workspace that owns this one
+    pub fn detached(&self) -> Self {
+        Self {
+            gren_path: self.gren_path.clone(),
+            working_dir: self.working_dir.clone(),
+            cache: std::collections::HashMap::new(),
+            project_type_cache: self.project_type_cache.clone(),
+            options: self.options.clone(),
+        }
+    }
+
     /// Clear the compilation cache
     pub fn clear_cache(&mut self) {
         self.cache.clear();
@@ -872,6 +948,63 @@ impl GrenCompiler {
         &mut self,
         content: &str,
         original_path: &Path,
+    ) -> Result<CompilationResult> {
+        self.compile_content_in(content, original_path, "compile").await
+    }
+
+    /// Type check the declaration at zero-based `line` of in-memory content. The
+    /// declaration is compiled in a small module of its own when it can be, falling back to
+    /// compiling all of `content` when that isn't possible or its result can't be trusted.
+    /// Neither updates the compilation cache.
+    pub async fn check_declaration(
+        &mut self,
+        content: &str,
+        original_path: &Path,
+        line: u32,
+    ) -> Result<DeclarationCheck> {
+        let mut parser = crate::parser::Parser::new()?;
+        let isolation = match parser.parse(content)? {
+            Some(tree) => isolate_declaration(&tree, content, line),
+            None => Isolation::FullModule("the module could not be parsed".to_string()),
+        };
+
+        match isolation {
+            Isolation::Isolated(isolated) => {
+                let result = self
+                    .compile_content_in(&isolated.source, original_path, "declaration")
+                    .await?;
+                if let Some(result) = isolated.map_result(result) {
+                    info!("✅ Checked {} without compiling its module", isolated.name);
+                    return Ok(DeclarationCheck {
+                        result,
+                        isolated: true,
+                    });
+                }
+                info!(
+                    "🔄 Result for {} on its own may come from its stubs, compiling the module",
+                    isolated.name
+                );
+            }
+            Isolation::FullModule(reason) => {
+                info!("🔄 Declaration needs its module compiled: {}", reason);
+            }
+        }
+
+        let result = self.compile_content(content, original_path).await?;
+        Ok(DeclarationCheck {
+            result,
+            isolated: false,
+        })
+    }
+
+    /// Compile in-memory content in a temporary copy of the project named after
+    /// `temp_prefix`. Every compile gets a directory of its own, so a compile that was
+    /// cancelled and one that replaced it never write over each other's files.
+    async fn compile_content_in(
+        &mut self,
+        content: &str,
+        original_path: &Path,
+        temp_prefix: &str,
     ) -> Result<CompilationResult> {
         use tokio::fs;
         use tokio::io::AsyncWriteExt;
@@ -910,11 +1043,16 @@ impl GrenCompiler {
             }
         };
 
-        // Create temp directory structure in OS temp directory  
-        let temp_base = std::env::temp_dir()
-            .join("gren-lsp")
-            .join(format!("compile_{}", std::process::id()));
-        
+        // Create temp directory structure in OS temp directory
+        let temp_base = std::env::temp_dir().join("gren-lsp").join(format!(
+            "{}_{}_{}",
+            temp_prefix,
+            std::process::id(),
+            NEXT_TEMP_PROJECT.fetch_add(1, Ordering::Relaxed)
+        ));
+        // Removes the directory when the compile is cancelled before its own cleanup runs
+        let _temp_project = TempProject(temp_base.clone());
+
         // Create the temporary file with the correct module name
         let temp_file_name = format!("{}.gren", module_name);
         let temp_file_path = temp_base.join(&temp_file_name);
@@ -1182,11 +1320,100 @@ impl GrenCompiler {
     }
 }
 
+/// Number of modules from the compiler's `Compiled 3 modules.` summary line
+fn compiled_module_count(stdout: &str) -> Option<usize> {
+    stdout.lines().find_map(|line| {
+        let (_, rest) = line.split_once("Compiled ")?;
+        let count = rest.split_whitespace().next()?;
+        rest.split_whitespace()
+            .nth(1)
+            .filter(|unit| unit.starts_with("module"))?;
+        count.parse().ok()
+    })
+}
+
+/// Summary of one compile for the client: how long it took, what was built and how many
+/// problems the compiler found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileReport {
+    pub uri: lsp_types::Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    pub success: bool,
+    /// Wall-clock time of the compile, including cache lookups
+    pub duration_ms: u64,
+    /// Modules the compiler built, when it reported it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modules_compiled: Option<usize>,
+    /// Whether the result was served from the compile cache
+    pub cache_hit: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Indexed modules the compiled module depends on, in build order, ending with itself
+    pub build_order: Vec<String>,
+    /// When the compile finished, in milliseconds since the Unix epoch
+    pub finished_at: u64,
+}
+
+impl CompileReport {
+    pub fn new(
+        uri: lsp_types::Url,
+        module_name: Option<String>,
+        result: &CompilationResult,
+        duration: std::time::Duration,
+        build_order: Vec<String>,
+    ) -> Self {
+        let severities = result
+            .diagnostics
+            .iter()
+            .map(|diagnostic| &diagnostic.severity)
+            .chain(result.global_errors.iter().map(|error| &error.severity));
+        let (mut errors, mut warnings) = (0, 0);
+        for severity in severities {
+            match severity {
+                DiagnosticSeverity::Error => errors += 1,
+                DiagnosticSeverity::Warning => warnings += 1,
+                DiagnosticSeverity::Info => {}
+            }
+        }
+
+        Self {
+            uri,
+            module_name,
+            success: result.success,
+            duration_ms: duration.as_millis() as u64,
+            modules_compiled: result.modules_compiled,
+            cache_hit: result.from_cache,
+            errors,
+            warnings,
+            build_order,
+            finished_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_compiled_module_count() {
+        assert_eq!(
+            compiled_module_count("Compiling ...\nSuccess! Compiled 3 modules.\n"),
+            Some(3)
+        );
+        assert_eq!(
+            compiled_module_count("Success! Compiled 1 module."),
+            Some(1)
+        );
+        assert_eq!(compiled_module_count("Success!"), None);
+    }
+
     #[tokio::test]
     async fn test_compiler_creation() {
         let temp_dir = TempDir::new().unwrap();
@@ -1213,6 +1440,7 @@ mod tests {
                 working_dir: temp_dir.path().to_path_buf(),
                 cache: std::collections::HashMap::new(),
                 project_type_cache: None,
+                options: CompilerOptions::default(),
             }
         });
 
@@ -1242,6 +1470,7 @@ mod tests {
                 working_dir: temp_dir.path().to_path_buf(),
                 cache: std::collections::HashMap::new(),
                 project_type_cache: None,
+                options: CompilerOptions::default(),
             }
         });
 
@@ -1256,6 +1485,11 @@ mod tests {
         let diag = &diagnostics[0];
         assert_eq!(diag.title, "TOO MANY ARGS");
         assert!(diag.message.contains("String` type needs 0 arguments"));
+        assert!(diag
+            .markdown
+            .as_deref()
+            .unwrap()
+            .contains("```\n154| count : String Int"));
         assert_eq!(
             diag.path,
             Some(PathBuf::from(
@@ -1282,6 +1516,7 @@ mod tests {
                 working_dir: temp_dir.path().to_path_buf(),
                 cache: std::collections::HashMap::new(),
                 project_type_cache: None,
+                options: CompilerOptions::default(),
             }
         });
 
@@ -1,7 +1,11 @@
 use crate::compiler::{CompilerDiagnostic, DiagnosticSeverity};
 use lsp_types::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Where errors without a hint of their own link to
+pub const GREN_DOCS_URL: &str = "https://gren-lang.org/book/";
+
 /// Convert compiler diagnostics to LSP diagnostics
 pub fn compiler_diagnostics_to_lsp(
     compiler_diagnostics: &[CompilerDiagnostic],
@@ -42,19 +46,198 @@ fn compiler_diagnostic_to_lsp(diag: &CompilerDiagnostic, uri: &Url) -> Option<Di
         },
     });
 
+    let related_information: Vec<DiagnosticRelatedInformation> = excerpt_regions(&diag.message)
+        .into_iter()
+        .filter(|(region, _)| !ranges_overlap(region, &range))
+        .map(|(region, label)| DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), region),
+            message: label.unwrap_or_else(|| diag.title.clone()),
+        })
+        .collect();
+
     Some(Diagnostic {
         range,
         severity: lsp_severity,
-        code: None,
-        code_description: None,
+        code: error_code(&diag.title).map(NumberOrString::String),
+        code_description: docs_link(&diag.message).map(|href| CodeDescription { href }),
         source: Some("gren".to_string()),
         message: format_diagnostic_message(diag),
-        related_information: None,
+        related_information: if related_information.is_empty() {
+            None
+        } else {
+            Some(related_information)
+        },
         tags: None,
-        data: None,
+        data: hints_data(&compiler_hints(&diag.message)),
     })
 }
 
+/// The code of an error, its title in kebab case: `TYPE MISMATCH` is `type-mismatch`
+fn error_code(title: &str) -> Option<String> {
+    let words: Vec<String> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join("-"))
+    }
+}
+
+/// The hint an error links to, like `<https://github.com/gren-lang/compiler/blob/main/hints/imports.md>`,
+/// or the Gren book
+fn docs_link(message: &str) -> Option<Url> {
+    message
+        .split('<')
+        .skip(1)
+        .filter_map(|rest| rest.split('>').next())
+        .find(|link| link.starts_with("https://"))
+        .unwrap_or(GREN_DOCS_URL)
+        .parse()
+        .ok()
+}
+
+/// The regions of the code excerpts in a compiler message, each with the sentence
+/// introducing it. An excerpt is a run of numbered lines, `154| count : String Int`,
+/// narrowed down to the columns a line of carets under it points at.
+fn excerpt_regions(message: &str) -> Vec<(Range, Option<String>)> {
+    let mut regions = Vec::new();
+    let mut label = None;
+    let mut lines = message.lines().peekable();
+    while let Some(line) = lines.next() {
+        let (first, prefix) = match excerpt_line(line) {
+            Some(excerpt) => excerpt,
+            None => {
+                if !line.trim().is_empty() && !is_caret_line(line) {
+                    label = Some(line.trim().to_string());
+                }
+                continue;
+            }
+        };
+
+        let mut last = (first, line.len().saturating_sub(prefix));
+        let mut carets = None;
+        while let Some(next) = lines.peek() {
+            if let Some((number, prefix)) = excerpt_line(next) {
+                last = (number, next.len().saturating_sub(prefix));
+                carets = None;
+            } else if is_caret_line(next) {
+                let start = next.find('^').unwrap_or(0);
+                let end = next.rfind('^').map_or(start, |end| end + 1);
+                carets = Some((start.saturating_sub(prefix), end.saturating_sub(prefix)));
+            } else {
+                break;
+            }
+            lines.next();
+        }
+
+        let line = last.0.saturating_sub(1);
+        let range = match carets {
+            Some((start, end)) if first == last.0 => Range::new(
+                Position::new(line, start as u32),
+                Position::new(line, end as u32),
+            ),
+            _ => Range::new(
+                Position::new(first.saturating_sub(1), 0),
+                Position::new(line, last.1 as u32),
+            ),
+        };
+        regions.push((range, label.take()));
+    }
+    regions
+}
+
+/// The line number of an excerpt line, `154| count : String Int` or `5|>    when x is`,
+/// and the length of the prefix before the code
+fn excerpt_line(line: &str) -> Option<(u32, usize)> {
+    let indent = line.len() - line.trim_start().len();
+    let pipe = indent + line[indent..].find('|')?;
+    let number = line[indent..pipe].parse().ok()?;
+    Some((number, pipe + 2))
+}
+
+/// A line of carets pointing at the excerpt line above it
+fn is_caret_line(line: &str) -> bool {
+    line.contains('^') && line.chars().all(|c| c == '^' || c == ' ')
+}
+
+/// Render a compiler message, a list of plain and styled strings, as Markdown: the
+/// styled strings are emphasized and the code excerpts fenced
+pub fn render_markdown(message: &serde_json::Value) -> String {
+    let segments: Vec<(&str, bool)> = match message {
+        serde_json::Value::String(text) => vec![(text.as_str(), false)],
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                serde_json::Value::String(text) => Some((text.as_str(), false)),
+                serde_json::Value::Object(style) => {
+                    let styled = style.get("bold").and_then(|bold| bold.as_bool()) == Some(true)
+                        || style.get("underline").and_then(|under| under.as_bool()) == Some(true)
+                        || style.get("color").is_some_and(|color| !color.is_null());
+                    Some((style.get("string")?.as_str()?, styled))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // Split the segments into lines, keeping each piece's style
+    let mut lines: Vec<Vec<(&str, bool)>> = vec![Vec::new()];
+    for (text, styled) in segments {
+        for (index, piece) in text.split('\n').enumerate() {
+            if index > 0 {
+                lines.push(Vec::new());
+            }
+            if !piece.is_empty() {
+                lines.last_mut().unwrap().push((piece, styled));
+            }
+        }
+    }
+
+    let mut markdown = Vec::new();
+    let mut in_excerpt = false;
+    for pieces in lines {
+        let plain: String = pieces.iter().map(|(text, _)| *text).collect();
+        let code = excerpt_line(&plain).is_some() || (in_excerpt && is_caret_line(&plain));
+        if code != in_excerpt {
+            markdown.push("```".to_string());
+            in_excerpt = code;
+        }
+        if code {
+            markdown.push(plain);
+        } else {
+            markdown.push(
+                pieces
+                    .iter()
+                    .map(|(text, styled)| emphasize(text, *styled))
+                    .collect(),
+            );
+        }
+    }
+    if in_excerpt {
+        markdown.push("```".to_string());
+    }
+    markdown.join("\n")
+}
+
+/// Bold `text` when styled, keeping surrounding spaces outside the markers
+fn emphasize(text: &str, styled: bool) -> String {
+    let trimmed = text.trim();
+    if !styled || trimmed.is_empty() {
+        return text.to_string();
+    }
+    let start = text.len() - text.trim_start().len();
+    format!(
+        "{}**{}**{}",
+        &text[..start],
+        trimmed,
+        &text[start + trimmed.len()..]
+    )
+}
+
 /// Extract line/column range from diagnostic message or location
 fn extract_range_from_diagnostic(diag: &CompilerDiagnostic) -> Option<Range> {
     // If we have explicit location information, use it
@@ -130,15 +313,169 @@ fn parse_location_from_message(message: &str) -> Option<Range> {
 
 /// Format the diagnostic message for display
 fn format_diagnostic_message(diag: &CompilerDiagnostic) -> String {
+    let message = diag.markdown.as_ref().unwrap_or(&diag.message);
     if diag.title.is_empty() {
-        diag.message.clone()
-    } else if diag.message.is_empty() {
+        message.clone()
+    } else if message.is_empty() {
         diag.title.clone()
     } else {
-        format!("{}: {}", diag.title, diag.message)
+        format!("{}: {}", diag.title, message)
+    }
+}
+
+/// Title of the compiler's error for a `when` expression without a branch for every value
+pub const MISSING_PATTERNS: &str = "MISSING PATTERNS";
+
+/// The patterns a compiler `MISSING PATTERNS` diagnostic lists under "Missing
+/// possibilities include:", `None` for any other diagnostic
+pub fn missing_patterns(diagnostic: &Diagnostic) -> Option<Vec<String>> {
+    if diagnostic.source.as_deref() != Some("gren")
+        || !diagnostic.message.starts_with(MISSING_PATTERNS)
+    {
+        return None;
+    }
+
+    // The list is indented and ends at the first blank line after it
+    let patterns = diagnostic
+        .message
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("Missing possibilit"))
+        .skip(1)
+        .skip_while(|line| line.trim().is_empty())
+        .take_while(|line| !line.trim().is_empty() && line.starts_with(char::is_whitespace))
+        .map(|line| line.trim().replace("**", ""))
+        .collect();
+    Some(patterns)
+}
+
+/// A fix a compiler error suggests, kept in its diagnostic's `data` for code actions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum CompilerHint {
+    /// Write `replacement` instead of the name the error points at
+    #[serde(rename_all = "camelCase")]
+    ReplaceName { name: String, replacement: String },
+    /// Import `module`, which the file uses without importing
+    AddImport { module: String },
+    /// Annotate `name` with the type the compiler found its body to have
+    Annotate { name: String, annotation: String },
+}
+
+/// The fixes a compiler message suggests: the names it lists as close to one it can't
+/// find, the module it says isn't imported, and the type it found a definition's body to
+/// have when that disagrees with the annotation
+pub fn compiler_hints(message: &str) -> Vec<CompilerHint> {
+    let mut hints = Vec::new();
+    let lines: Vec<&str> = message.lines().collect();
+
+    // "I cannot find a `floor` variable:", followed by "These names seem close though:"
+    // or "Maybe you want one of the following?" and an indented list
+    if let Some(name) = lines
+        .first()
+        .and_then(|line| line.strip_prefix("I cannot find a `"))
+        .and_then(|rest| rest.split('`').next())
+    {
+        let suggestions = lines
+            .iter()
+            .skip_while(|line| {
+                let line = line.trim();
+                // The names listed after a missing import are modules
+                line.contains("` import")
+                    || !line.ends_with("seem close though:")
+                        && !line.ends_with("one of the following?")
+            })
+            .skip(1)
+            .skip_while(|line| line.trim().is_empty())
+            .take_while(|line| !line.trim().is_empty() && line.starts_with(char::is_whitespace))
+            .map(|line| line.trim());
+        let did_you_mean = lines.iter().filter_map(|line| {
+            line.split("Did you mean `")
+                .nth(1)
+                .and_then(|rest| rest.split('`').next())
+        });
+        for replacement in suggestions.chain(did_you_mean) {
+            let hint = CompilerHint::ReplaceName {
+                name: name.to_string(),
+                replacement: replacement.to_string(),
+            };
+            if replacement != name && !hints.contains(&hint) {
+                hints.push(hint);
+            }
+        }
+    }
+
+    // "I cannot find a `Dict` import."
+    for line in &lines {
+        let module = line
+            .split("cannot find a `")
+            .nth(1)
+            .and_then(|rest| rest.split_once("` import"));
+        if let Some((module, _)) = module {
+            hints.push(CompilerHint::AddImport {
+                module: module.to_string(),
+            });
+        }
+    }
+
+    // "The body is a:", or "This `value` value is a:" when the body is a name, the type
+    // indented below it, then "But the type annotation on `total` says it should be:"
+    for (index, line) in lines.iter().enumerate() {
+        let name = match line
+            .trim()
+            .strip_prefix("But the type annotation on `")
+            .and_then(|rest| rest.split('`').next())
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        let body = match lines[..index]
+            .iter()
+            .rposition(|line| line.trim() == "The body is a:" || is_value_type_line(line))
+        {
+            Some(body) => body,
+            None => continue,
+        };
+        let annotation: Vec<&str> = lines[body + 1..index]
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if !annotation.is_empty() {
+            hints.push(CompilerHint::Annotate {
+                name: name.to_string(),
+                // Long records continue on lines starting with a comma
+                annotation: annotation.join(" ").replace(" ,", ","),
+            });
+        }
+    }
+    hints
+}
+
+/// "This `total` value is a:"
+fn is_value_type_line(line: &str) -> bool {
+    line.trim()
+        .strip_prefix("This `")
+        .is_some_and(|rest| rest.ends_with("` value is a:"))
+}
+
+fn hints_data(hints: &[CompilerHint]) -> Option<serde_json::Value> {
+    if hints.is_empty() {
+        None
+    } else {
+        Some(serde_json::json!({ "hints": hints }))
     }
 }
 
+/// The fixes kept in a compiler diagnostic's `data`
+pub fn diagnostic_hints(diagnostic: &Diagnostic) -> Vec<CompilerHint> {
+    diagnostic
+        .data
+        .as_ref()
+        .and_then(|data| data.get("hints"))
+        .and_then(|hints| serde_json::from_value(hints.clone()).ok())
+        .unwrap_or_default()
+}
+
 /// Merge compiler diagnostics with existing syntax diagnostics
 pub fn merge_diagnostics(
     compiler_diagnostics: Vec<Diagnostic>,
@@ -210,6 +547,7 @@ mod tests {
                 end_line: Some(5),
                 end_column: Some(15),
             }),
+            markdown: None,
         };
 
         let lsp_diags = compiler_diagnostics_to_lsp(&[compiler_diag], &uri);
@@ -223,6 +561,155 @@ mod tests {
         assert_eq!(diag.range.start.character, 9); // 0-based
     }
 
+    #[test]
+    fn test_missing_patterns() {
+        let uri = Url::parse("file:///test/Main.gren").unwrap();
+        let compiler_diag = CompilerDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            title: MISSING_PATTERNS.to_string(),
+            message: "This `when` does not have branches for all possibilities:\n\n\
+                      5|>    when shape is\n6|>        Circle r ->\n7|>            r\n\n\
+                      Missing possibilities include:\n\n    Square _\n    Triangle _ _\n\n\
+                      I would have to crash if I saw one of those. Add branches for them!"
+                .to_string(),
+            path: None,
+            location: None,
+            markdown: None,
+        };
+
+        let diag = &compiler_diagnostics_to_lsp(&[compiler_diag], &uri)[0];
+        assert_eq!(
+            missing_patterns(diag),
+            Some(vec!["Square _".to_string(), "Triangle _ _".to_string()])
+        );
+
+        let other = Diagnostic {
+            message: "TYPE MISMATCH: Missing possibilities include:\n\n    Square _".to_string(),
+            ..diag.clone()
+        };
+        assert_eq!(missing_patterns(&other), None);
+    }
+
+    #[test]
+    fn test_related_information() {
+        let uri = Url::parse("file:///test/Main.gren").unwrap();
+        let compiler_diag = CompilerDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            title: "SHADOWING".to_string(),
+            message: "The name `total` is first defined here:\n\n\
+                      3| total = 0\n   ^^^^^\n\
+                      But then it is defined AGAIN over here:\n\n\
+                      8|     total = 1\n       ^^^^^\n\
+                      Think of a more helpful name for one of them and you should be all set!"
+                .to_string(),
+            path: None,
+            location: Some(DiagnosticLocation {
+                line: 8,
+                column: 5,
+                end_line: Some(8),
+                end_column: Some(10),
+            }),
+            markdown: None,
+        };
+
+        let diag = &compiler_diagnostics_to_lsp(&[compiler_diag], &uri)[0];
+        assert_eq!(
+            diag.code,
+            Some(NumberOrString::String("shadowing".to_string()))
+        );
+        assert_eq!(
+            diag.code_description
+                .as_ref()
+                .map(|description| description.href.as_str()),
+            Some(GREN_DOCS_URL)
+        );
+        let related = diag.related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(
+            related[0].message,
+            "The name `total` is first defined here:"
+        );
+        assert_eq!(
+            related[0].location.range,
+            Range::new(Position::new(2, 0), Position::new(2, 5))
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let message = serde_json::json!([
+            "The `String` type needs 0 arguments, but I see 1 instead:\n\n154| count : String Int\n             ",
+            {"bold": false, "underline": false, "color": "RED", "string": "^^^^^^^^^^"},
+            "\nWhich is the ",
+            {"bold": false, "underline": false, "color": "yellow", "string": "extra one "},
+            "? Read <https://github.com/gren-lang/compiler/blob/main/hints/parens.md>"
+        ]);
+        let markdown = render_markdown(&message);
+        assert_eq!(
+            markdown,
+            "The `String` type needs 0 arguments, but I see 1 instead:\n\n```\n\
+             154| count : String Int\n             ^^^^^^^^^^\n```\n\
+             Which is the **extra one** ? Read <https://github.com/gren-lang/compiler/blob/main/hints/parens.md>"
+        );
+        assert_eq!(
+            docs_link(&markdown).map(String::from),
+            Some("https://github.com/gren-lang/compiler/blob/main/hints/parens.md".to_string())
+        );
+    }
+
+    #[test]
+    fn test_compiler_hints() {
+        let naming = "I cannot find a `flor` variable:\n\n5|     flor 1.5\n       ^^^^\n\
+                      These names seem close though:\n\n    floor\n    Math.floor\n\n\
+                      Hint: Read <https://github.com/gren-lang/compiler/blob/main/hints/imports.md>";
+        assert_eq!(
+            compiler_hints(naming),
+            vec![
+                CompilerHint::ReplaceName {
+                    name: "flor".to_string(),
+                    replacement: "floor".to_string()
+                },
+                CompilerHint::ReplaceName {
+                    name: "flor".to_string(),
+                    replacement: "Math.floor".to_string()
+                },
+            ]
+        );
+
+        let import = "I cannot find a `Dict.empty` variable:\n\n3| x = Dict.empty\n\n\
+                      I cannot find a `Dict` import. These names seem close though:\n\n    Dict.Extra";
+        assert_eq!(
+            compiler_hints(import),
+            vec![CompilerHint::AddImport {
+                module: "Dict".to_string()
+            }]
+        );
+
+        let mismatch = "Something is off with the body of the `total` definition:\n\n\
+                        8| total = \"0\"\n           ^^^\n\
+                        The body is a:\n\n    String\n\n\
+                        But the type annotation on `total` says it should be:\n\n    Int";
+        assert_eq!(
+            compiler_hints(mismatch),
+            vec![CompilerHint::Annotate {
+                name: "total".to_string(),
+                annotation: "String".to_string()
+            }]
+        );
+
+        let uri = Url::parse("file:///test/Main.gren").unwrap();
+        let compiler_diag = CompilerDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            title: "NAMING ERROR".to_string(),
+            message: naming.to_string(),
+            path: None,
+            location: None,
+            markdown: None,
+        };
+        let diag = &compiler_diagnostics_to_lsp(&[compiler_diag], &uri)[0];
+        assert_eq!(diagnostic_hints(diag), compiler_hints(naming));
+    }
+
     #[test]
     fn test_location_parsing() {
         let message = "Something went wrong on line 10, column 5";
@@ -0,0 +1,110 @@
+//! Extra environment variables and flags for compiler runs
+//!
+//! Some projects need the compiler run in a particular environment: a custom `GREN_HOME`,
+//! proxy variables so packages can be fetched, or flags selecting what a project that runs
+//! on Node is built for. These come from the `[compiler]` table of the project's
+//! `.gren-lsp.toml` (see [`crate::project_config`]) and from the client's `compiler`
+//! settings, which take precedence:
+//!
+//! ```toml
+//! [compiler]
+//! flags = ["--optimize"]
+//!
+//! [compiler.env]
+//! GREN_HOME = "/opt/gren"
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tokio::process::Command;
+use tracing::warn;
+
+/// Flags the server passes itself and reads the results of, so they can't be overridden
+const RESERVED_FLAGS: &[&str] = &["--report", "--output"];
+
+/// Environment and flags added to every compiler run
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompilerOptions {
+    /// Environment variables set for the compiler, on top of the server's own environment
+    pub env: BTreeMap<String, String>,
+    /// Flags appended to `gren make`
+    pub flags: Vec<String>,
+}
+
+impl CompilerOptions {
+    pub fn is_empty(&self) -> bool {
+        self.env.is_empty() && self.flags.is_empty()
+    }
+
+    /// These options with `overrides` on top: its variables replace ones with the same
+    /// name and its flags come after these
+    pub fn merged_with(&self, overrides: &CompilerOptions) -> CompilerOptions {
+        let mut env = self.env.clone();
+        env.extend(overrides.env.clone());
+        let mut flags = self.flags.clone();
+        flags.extend(overrides.flags.iter().cloned());
+        CompilerOptions { env, flags }
+    }
+
+    /// Set the environment variables on `command`
+    pub fn apply_env(&self, command: &mut Command) {
+        command.envs(&self.env);
+    }
+
+    /// Set the environment variables on a `gren make` command and append the flags.
+    /// Flags that would change the output the server parses are left out.
+    pub fn apply_to_make(&self, command: &mut Command) {
+        self.apply_env(command);
+        for flag in &self.flags {
+            if is_reserved(flag) {
+                warn!(
+                    "Ignoring compiler flag `{}`, the server sets it itself",
+                    flag
+                );
+                continue;
+            }
+            command.arg(flag);
+        }
+    }
+}
+
+fn is_reserved(flag: &str) -> bool {
+    RESERVED_FLAGS.iter().any(|reserved| {
+        flag.strip_prefix(reserved)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_override_project_file() {
+        let project = CompilerOptions {
+            env: BTreeMap::from([
+                ("GREN_HOME".to_string(), "/opt/gren".to_string()),
+                ("HTTPS_PROXY".to_string(), "http://proxy:3128".to_string()),
+            ]),
+            flags: vec!["--optimize".to_string()],
+        };
+        let settings = CompilerOptions {
+            env: BTreeMap::from([("GREN_HOME".to_string(), "/home/me/gren".to_string())]),
+            flags: vec!["--verbose".to_string()],
+        };
+
+        let merged = project.merged_with(&settings);
+        assert_eq!(merged.env["GREN_HOME"], "/home/me/gren");
+        assert_eq!(merged.env["HTTPS_PROXY"], "http://proxy:3128");
+        assert_eq!(merged.flags, vec!["--optimize", "--verbose"]);
+    }
+
+    #[test]
+    fn test_reserved_flags() {
+        assert!(is_reserved("--report=json"));
+        assert!(is_reserved("--output"));
+        assert!(!is_reserved("--optimize"));
+        assert!(!is_reserved("--outputs-elsewhere"));
+    }
+}
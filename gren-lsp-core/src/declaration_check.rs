@@ -0,0 +1,438 @@
+//! Type checking one declaration without compiling its whole module
+//!
+//! Features that need the compiler's view of a single declaration, like the type of an
+//! expression or what fits a hole, can't wait for the whole module and everything it
+//! imports to compile on each keystroke. Instead the declaration is copied into a small
+//! module of its own: the module's type declarations verbatim, only the imports the copied
+//! code uses, and every other top-level value it refers to as a stub with its annotated
+//! type. Compiling that checks the declaration against the same types in a fraction of the
+//! time, and leaves the full module's cached results alone.
+//!
+//! Stubs only stand in for values with a type annotation, so a declaration using an
+//! unannotated value, or a port, can't be isolated. Errors the small module reports outside
+//! the declaration, or about names and imports, may come from the stubs rather than the
+//! code, so those results are thrown away and the full module is compiled instead.
+
+use crate::compiler::{CompilationResult, DiagnosticLocation};
+use crate::syntax::child_of_kind;
+use std::collections::{HashMap, HashSet};
+use tree_sitter::{Node, Tree};
+
+/// Compiler error titles that can be caused by what the small module leaves out
+const UNTRUSTED_TITLES: &[&str] = &["NAMING", "IMPORT", "MODULE", "AMBIGUOUS"];
+
+/// A declaration copied into a module of its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsolatedDeclaration {
+    pub name: String,
+    /// Source of the synthesized module
+    pub source: String,
+    /// First and last line of the declaration in the synthesized module, one-based as the
+    /// compiler reports them
+    pub start_line: u32,
+    pub end_line: u32,
+    /// First line of the declaration in the original module, one-based
+    pub original_start_line: u32,
+}
+
+/// How a declaration can be checked
+#[derive(Debug, Clone, PartialEq)]
+pub enum Isolation {
+    Isolated(IsolatedDeclaration),
+    /// The declaration needs the full module, for the given reason
+    FullModule(String),
+}
+
+/// Outcome of checking one declaration
+#[derive(Debug, Clone)]
+pub struct DeclarationCheck {
+    /// Diagnostics in terms of the original module
+    pub result: CompilationResult,
+    /// Whether the declaration was compiled on its own, rather than with its module
+    pub isolated: bool,
+}
+
+impl IsolatedDeclaration {
+    /// The compile result of the synthesized module, moved onto the original module's lines.
+    /// `None` when it can't be trusted and the full module should be compiled instead.
+    pub fn map_result(&self, mut result: CompilationResult) -> Option<CompilationResult> {
+        if !result.global_errors.is_empty() {
+            return None;
+        }
+
+        for diagnostic in &mut result.diagnostics {
+            if UNTRUSTED_TITLES
+                .iter()
+                .any(|title| diagnostic.title.contains(title))
+            {
+                return None;
+            }
+            let location = diagnostic.location.as_mut()?;
+            let end_line = location.end_line.unwrap_or(location.line);
+            if location.line < self.start_line || end_line > self.end_line {
+                return None;
+            }
+            self.shift(location);
+        }
+        Some(result)
+    }
+
+    fn shift(&self, location: &mut DiagnosticLocation) {
+        location.line = self.original_line(location.line);
+        location.end_line = location.end_line.map(|line| self.original_line(line));
+    }
+
+    fn original_line(&self, line: u32) -> u32 {
+        line - self.start_line + self.original_start_line
+    }
+}
+
+/// Copy the top-level declaration at zero-based `line` into a module of its own
+pub fn isolate_declaration(tree: &Tree, source: &str, line: u32) -> Isolation {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let top_level: Vec<Node> = root.named_children(&mut cursor).collect();
+    if top_level.iter().any(|node| node.is_error()) {
+        return Isolation::FullModule("the module has syntax errors".to_string());
+    }
+
+    let module_name = match top_level
+        .iter()
+        .find(|node| node.kind() == "module_declaration")
+        .and_then(|node| child_of_kind(node, "upper_case_qid"))
+        .and_then(|name| text(name, source))
+    {
+        Some(module_name) => module_name,
+        None => return Isolation::FullModule("the module has no header".to_string()),
+    };
+
+    let index = match top_level.iter().position(|node| {
+        matches!(node.kind(), "value_declaration" | "type_annotation")
+            && node.start_position().row as u32 <= line
+            && line <= node.end_position().row as u32
+    }) {
+        Some(index) => index,
+        None => return Isolation::FullModule("no value is declared there".to_string()),
+    };
+    // An annotation belongs to the value declared after it
+    let index = match top_level[index].kind() {
+        "type_annotation" => match top_level[index..]
+            .iter()
+            .position(|node| node.kind() == "value_declaration")
+        {
+            Some(offset) => index + offset,
+            None => return Isolation::FullModule("the annotation has no value".to_string()),
+        },
+        _ => index,
+    };
+    let declaration = top_level[index];
+    let name = match value_name(&declaration, source) {
+        Some(name) => name,
+        None => return Isolation::FullModule("the declaration has no name".to_string()),
+    };
+    let annotation = top_level[..index]
+        .iter()
+        .rev()
+        .take_while(|node| node.kind() != "value_declaration")
+        .find(|node| annotated_name(node, source) == Some(name))
+        .copied();
+
+    let mut annotations = HashMap::new();
+    let mut values = HashSet::new();
+    let mut ports = HashSet::new();
+    for node in &top_level {
+        match node.kind() {
+            "type_annotation" => {
+                if let (Some(annotated), Some(signature)) = (
+                    annotated_name(node, source),
+                    child_of_kind(node, "type_expression"),
+                ) {
+                    annotations.insert(annotated, signature);
+                }
+            }
+            "value_declaration" => values.extend(value_name(node, source)),
+            "port_annotation" => ports.extend(
+                child_of_kind(node, "lower_case_identifier").and_then(|port| text(port, source)),
+            ),
+            _ => {}
+        }
+    }
+
+    let mut references = References::default();
+    if let Some(annotation) = annotation {
+        references.collect(annotation, source);
+    }
+    references.collect(declaration, source);
+
+    let mut stubs = Vec::new();
+    let mut stubbed: Vec<&str> = references
+        .values
+        .iter()
+        .copied()
+        .filter(|value| *value != name && (values.contains(value) || ports.contains(value)))
+        .collect();
+    stubbed.sort_unstable();
+    for value in stubbed {
+        if ports.contains(value) {
+            return Isolation::FullModule(format!("`{}` is a port", value));
+        }
+        let signature = match annotations.get(value) {
+            Some(signature) => *signature,
+            None => return Isolation::FullModule(format!("`{}` has no type annotation", value)),
+        };
+        references.collect(signature, source);
+        stubs.push(format!(
+            "{} : {}\n{} =\n    Debug.todo \"{}\"",
+            value,
+            text(signature, source).unwrap_or_default(),
+            value,
+            value
+        ));
+    }
+
+    let mut types = Vec::new();
+    for node in &top_level {
+        if matches!(node.kind(), "type_declaration" | "type_alias_declaration") {
+            references.collect(*node, source);
+            types.extend(text(*node, source));
+        }
+    }
+
+    let mut imports: Vec<&str> = top_level
+        .iter()
+        .filter(|node| node.kind() == "import_clause" && references.needs_import(node, source))
+        .filter_map(|node| text(*node, source))
+        .collect();
+    let imports_debug = imports
+        .iter()
+        .any(|import| import.split_whitespace().nth(1) == Some("Debug"));
+    if !stubs.is_empty() && !imports_debug {
+        imports.push("import Debug");
+    }
+
+    let mut sections = vec![format!("module {} exposing (..)", module_name)];
+    if !imports.is_empty() {
+        sections.push(imports.join("\n"));
+    }
+    sections.extend(types.into_iter().map(str::to_string));
+    sections.extend(stubs);
+    let prefix = sections.join("\n\n\n") + "\n\n\n";
+
+    let start = annotation.unwrap_or(declaration);
+    let body = &source[start.start_byte()..declaration.end_byte()];
+    let start_line = prefix.matches('\n').count() as u32 + 1;
+    Isolation::Isolated(IsolatedDeclaration {
+        name: name.to_string(),
+        source: format!("{}{}\n", prefix, body),
+        start_line,
+        end_line: start_line + body.matches('\n').count() as u32,
+        original_start_line: start.start_position().row as u32 + 1,
+    })
+}
+
+/// Names the copied code refers to
+#[derive(Debug, Default)]
+struct References<'a> {
+    /// Unqualified values
+    values: HashSet<&'a str>,
+    /// Unqualified types and constructors
+    types: HashSet<&'a str>,
+    /// Module names and aliases used to qualify names
+    qualifiers: HashSet<String>,
+}
+
+impl<'a> References<'a> {
+    fn collect(&mut self, node: Node, source: &'a str) {
+        if matches!(node.kind(), "value_qid" | "upper_case_qid") {
+            let mut cursor = node.walk();
+            let parts: Vec<&str> = node
+                .named_children(&mut cursor)
+                .filter_map(|part| text(part, source))
+                .collect();
+            if let Some((last, qualifier)) = parts.split_last() {
+                if !qualifier.is_empty() {
+                    self.qualifiers.insert(qualifier.join("."));
+                } else if node.kind() == "value_qid" {
+                    self.values.insert(last);
+                } else {
+                    self.types.insert(last);
+                }
+            }
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            self.collect(child, source);
+        }
+    }
+
+    /// Whether the copied code uses something `import` brings in. Imports exposing
+    /// everything are always kept, as what they expose isn't known here.
+    fn needs_import(&self, import: &Node, source: &str) -> bool {
+        let module_name =
+            child_of_kind(import, "upper_case_qid").and_then(|name| text(name, source));
+        let alias = child_of_kind(import, "as_clause")
+            .and_then(|clause| child_of_kind(&clause, "upper_case_identifier"))
+            .and_then(|alias| text(alias, source));
+        if module_name
+            .into_iter()
+            .chain(alias)
+            .any(|qualifier| self.qualifiers.contains(qualifier))
+        {
+            return true;
+        }
+
+        let list = match child_of_kind(import, "exposing_list") {
+            Some(list) => list,
+            None => return false,
+        };
+        if child_of_kind(&list, "double_dot").is_some() {
+            return true;
+        }
+        let mut cursor = list.walk();
+        let exposes_used = list.named_children(&mut cursor).any(|item| {
+            let exposed = match item.kind() {
+                "exposed_value" | "exposed_type" => item.named_child(0).or(Some(item)),
+                _ => None,
+            };
+            exposed
+                .and_then(|exposed| text(exposed, source))
+                .is_some_and(|name| self.values.contains(name) || self.types.contains(name))
+        });
+        exposes_used
+    }
+}
+
+fn value_name<'a>(declaration: &Node, source: &'a str) -> Option<&'a str> {
+    child_of_kind(declaration, "function_declaration_left")
+        .and_then(|left| child_of_kind(&left, "lower_case_identifier"))
+        .and_then(|name| text(name, source))
+}
+
+fn annotated_name<'a>(annotation: &Node, source: &'a str) -> Option<&'a str> {
+    if annotation.kind() != "type_annotation" {
+        return None;
+    }
+    child_of_kind(annotation, "lower_case_identifier").and_then(|name| text(name, source))
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{CompilerDiagnostic, DiagnosticSeverity};
+    use crate::Parser;
+
+    const SOURCE: &str = r#"module Cart exposing (total)
+
+import Dict exposing (Dict)
+import Html
+import Money as M exposing (Money, add)
+
+
+type alias Cart =
+    { items : Dict String M.Money }
+
+
+price : String -> Money
+price item =
+    M.zero
+
+
+label =
+    Html.text "cart"
+
+
+total : Cart -> Money
+total cart =
+    Dict.foldl (\item _ sum -> add sum (price item)) M.zero cart.items
+
+
+summary cart =
+    label
+"#;
+
+    fn isolate(line: u32) -> Isolation {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        isolate_declaration(&tree, SOURCE, line)
+    }
+
+    #[test]
+    fn test_declaration_isolated_with_stubs_and_used_imports() {
+        let isolated = match isolate(22) {
+            Isolation::Isolated(isolated) => isolated,
+            other => panic!("expected an isolated declaration, got {:?}", other),
+        };
+        assert_eq!(isolated.name, "total");
+        assert_eq!(isolated.original_start_line, 21);
+        assert!(isolated.source.contains("import Dict exposing (Dict)"));
+        assert!(isolated
+            .source
+            .contains("import Money as M exposing (Money, add)"));
+        assert!(!isolated.source.contains("import Html"));
+        assert!(isolated
+            .source
+            .contains("price : String -> Money\nprice =\n    Debug.todo"));
+        assert!(!isolated.source.contains("label"));
+
+        let lines: Vec<&str> = isolated.source.lines().collect();
+        assert_eq!(
+            lines[isolated.start_line as usize - 1],
+            "total : Cart -> Money"
+        );
+        assert_eq!(isolated.end_line - isolated.start_line, 2);
+    }
+
+    #[test]
+    fn test_unannotated_reference_needs_full_module() {
+        assert_eq!(
+            isolate(26),
+            Isolation::FullModule("`label` has no type annotation".to_string())
+        );
+    }
+
+    #[test]
+    fn test_result_mapped_to_original_lines() {
+        let isolated = IsolatedDeclaration {
+            name: "total".to_string(),
+            source: String::new(),
+            start_line: 12,
+            end_line: 14,
+            original_start_line: 21,
+        };
+        let result = |title: &str, line: u32| CompilationResult {
+            success: false,
+            diagnostics: vec![CompilerDiagnostic {
+                severity: DiagnosticSeverity::Error,
+                title: title.to_string(),
+                message: String::new(),
+                path: None,
+                location: Some(DiagnosticLocation {
+                    line,
+                    column: 5,
+                    end_line: Some(line),
+                    end_column: Some(9),
+                }),
+                markdown: None,
+            }],
+            global_errors: Vec::new(),
+            timestamp: std::time::SystemTime::now(),
+            content_hash: 0,
+            modules_compiled: None,
+            from_cache: false,
+        };
+
+        let mapped = isolated.map_result(result("TYPE MISMATCH", 14)).unwrap();
+        let location = mapped.diagnostics[0].location.as_ref().unwrap();
+        assert_eq!((location.line, location.end_line), (23, Some(23)));
+
+        // Problems in the stubs, or about names, may not be the declaration's fault
+        assert!(isolated.map_result(result("TYPE MISMATCH", 8)).is_none());
+        assert!(isolated.map_result(result("NAMING ERROR", 13)).is_none());
+    }
+}
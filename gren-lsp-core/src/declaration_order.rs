@@ -0,0 +1,322 @@
+//! Rearranging a module's top-level declarations
+//!
+//! The order of top-level declarations doesn't change what a Gren module means, so they can
+//! be sorted or moved around freely. A declaration moves as a block together with its doc
+//! comment, its type annotation and any comments written above it, and a comment on the
+//! same line as the end of a declaration stays with that declaration. The text between
+//! blocks stays where it is, so the module's spacing is kept.
+
+use crate::syntax::{child_of_kind, point_to_position};
+use lsp_types::*;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+/// Which way `move_declaration` moves a declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MoveDirection {
+    Up,
+    Down,
+}
+
+/// A top-level declaration with everything that moves along with it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclarationBlock {
+    pub name: String,
+    /// From the first comment or annotation above the declaration to its end
+    pub range: Range,
+    start_byte: usize,
+    end_byte: usize,
+}
+
+/// The blocks that can be rearranged, in source order.
+///
+/// Returns `None` when something after the imports can't safely be moved: a syntax error,
+/// an annotation without its definition, or a kind of declaration that isn't understood.
+pub fn declaration_blocks(tree: &Tree, source: &str) -> Option<Vec<DeclarationBlock>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let children: Vec<Node> = root.named_children(&mut cursor).collect();
+
+    let header_end = children
+        .iter()
+        .rposition(|node| matches!(node.kind(), "module_declaration" | "import_clause"))
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let mut rest = &children[header_end..];
+
+    // Without imports, the doc comment following the module header documents the module
+    let has_imports = children[..header_end]
+        .iter()
+        .any(|node| node.kind() == "import_clause");
+    if !has_imports && header_end > 0 {
+        if let Some(first) = rest.first() {
+            if is_doc_comment(first, source) {
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    let mut blocks: Vec<DeclarationBlock> = Vec::new();
+    let mut start: Option<Node> = None;
+    let mut annotation: Option<Node> = None;
+    for node in rest {
+        if node.has_error() {
+            return None;
+        }
+
+        match node.kind() {
+            "line_comment" | "block_comment" => {
+                let trailing = blocks.last_mut().filter(|block| {
+                    start.is_none() && block.range.end.line == node.start_position().row as u32
+                });
+                match trailing {
+                    Some(block) => {
+                        block.end_byte = node.end_byte();
+                        block.range.end = point_to_position(node.end_position(), source);
+                    }
+                    None => {
+                        start.get_or_insert(*node);
+                    }
+                }
+            }
+            "type_annotation" => {
+                if annotation.is_some() {
+                    return None;
+                }
+                annotation = Some(*node);
+                start.get_or_insert(*node);
+            }
+            "value_declaration"
+            | "type_declaration"
+            | "type_alias_declaration"
+            | "port_annotation" => {
+                let name = declaration_name(node, source)?;
+                if let Some(annotation) = annotation.take() {
+                    let annotated = child_of_kind(&annotation, "lower_case_identifier")
+                        .and_then(|identifier| identifier.utf8_text(source.as_bytes()).ok());
+                    if node.kind() != "value_declaration" || annotated != Some(name) {
+                        return None;
+                    }
+                }
+
+                let first = start.take().unwrap_or(*node);
+                blocks.push(DeclarationBlock {
+                    name: name.to_string(),
+                    range: Range::new(
+                        point_to_position(first.start_position(), source),
+                        point_to_position(node.end_position(), source),
+                    ),
+                    start_byte: first.start_byte(),
+                    end_byte: node.end_byte(),
+                });
+            }
+            _ => return None,
+        }
+    }
+
+    // Comments after the last declaration aren't part of any block and stay at the end
+    if annotation.is_some() {
+        return None;
+    }
+    Some(blocks)
+}
+
+/// Edit sorting the top-level declarations alphabetically, ignoring case. `None` when they
+/// already are or can't be rearranged.
+pub fn sort_declarations(tree: &Tree, source: &str) -> Option<TextEdit> {
+    let blocks = declaration_blocks(tree, source)?;
+    let mut sorted: Vec<&DeclarationBlock> = blocks.iter().collect();
+    sorted.sort_by_key(|block| block.name.to_lowercase());
+
+    if sorted
+        .iter()
+        .zip(&blocks)
+        .all(|(sorted, block)| sorted.name == block.name)
+    {
+        return None;
+    }
+    Some(rearranged(&blocks, &sorted, source))
+}
+
+/// The block containing `position`, if it can be moved
+pub fn declaration_at(tree: &Tree, source: &str, position: Position) -> Option<DeclarationBlock> {
+    declaration_blocks(tree, source)?
+        .into_iter()
+        .find(|block| block.range.start <= position && position <= block.range.end)
+}
+
+/// Edit swapping the declaration containing `position` with the one above or below it.
+/// `None` when there's no declaration there or it's already first or last.
+pub fn move_declaration(
+    tree: &Tree,
+    source: &str,
+    position: Position,
+    direction: MoveDirection,
+) -> Option<TextEdit> {
+    let blocks = declaration_blocks(tree, source)?;
+    let index = blocks
+        .iter()
+        .position(|block| block.range.start <= position && position <= block.range.end)?;
+    let other = match direction {
+        MoveDirection::Up => index.checked_sub(1)?,
+        MoveDirection::Down => Some(index + 1).filter(|other| *other < blocks.len())?,
+    };
+
+    let (first, second) = (index.min(other), index.max(other));
+    let pair = &blocks[first..=second];
+    Some(rearranged(pair, &[&pair[1], &pair[0]], source))
+}
+
+/// Edit writing `order` into the places `blocks` occupy, keeping the text between them
+fn rearranged(blocks: &[DeclarationBlock], order: &[&DeclarationBlock], source: &str) -> TextEdit {
+    let mut text = String::new();
+    for (index, block) in order.iter().enumerate() {
+        if index > 0 {
+            text.push_str(&source[blocks[index - 1].end_byte..blocks[index].start_byte]);
+        }
+        text.push_str(&source[block.start_byte..block.end_byte]);
+    }
+
+    let first = &blocks[0];
+    let last = &blocks[blocks.len() - 1];
+    TextEdit::new(Range::new(first.range.start, last.range.end), text)
+}
+
+fn declaration_name<'a>(node: &Node, source: &'a str) -> Option<&'a str> {
+    let name = match node.kind() {
+        "value_declaration" => child_of_kind(node, "function_declaration_left")
+            .and_then(|left| child_of_kind(&left, "lower_case_identifier")),
+        "port_annotation" => child_of_kind(node, "lower_case_identifier"),
+        _ => child_of_kind(node, "upper_case_identifier"),
+    }?;
+    name.utf8_text(source.as_bytes()).ok()
+}
+
+fn is_doc_comment(node: &Node, source: &str) -> bool {
+    node.kind() == "block_comment"
+        && node
+            .utf8_text(source.as_bytes())
+            .map(|text| text.starts_with("{-|"))
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    const SOURCE: &str = r#"module Main exposing (..)
+
+import Array
+
+
+{-| Entry point
+-}
+main : Int
+main =
+    helper 1 -- not zero
+
+
+-- Unrelated note
+type alias Model =
+    { count : Int }
+
+
+helper : Int -> Int
+helper n =
+    n + 1
+"#;
+
+    fn parse(source: &str) -> Tree {
+        let mut parser = Parser::new().unwrap();
+        parser.parse(source).unwrap().unwrap()
+    }
+
+    fn apply(source: &str, edit: &TextEdit) -> String {
+        let lines: Vec<&str> = source.split('\n').collect();
+        let offset = |position: Position| {
+            lines[..position.line as usize]
+                .iter()
+                .map(|line| line.len() + 1)
+                .sum::<usize>()
+                + position.character as usize
+        };
+        format!(
+            "{}{}{}",
+            &source[..offset(edit.range.start)],
+            edit.new_text,
+            &source[offset(edit.range.end)..]
+        )
+    }
+
+    #[test]
+    fn test_blocks_keep_docs_annotations_and_comments() {
+        let tree = parse(SOURCE);
+        let blocks = declaration_blocks(&tree, SOURCE).unwrap();
+        let names: Vec<&str> = blocks.iter().map(|block| block.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "Model", "helper"]);
+        assert_eq!(blocks[0].range.start.line, 5);
+        assert_eq!(blocks[0].range.end.line, 9);
+        assert_eq!(blocks[1].range.start.line, 12);
+    }
+
+    #[test]
+    fn test_sort_declarations() {
+        let tree = parse(SOURCE);
+        let edit = sort_declarations(&tree, SOURCE).unwrap();
+        let sorted = apply(SOURCE, &edit);
+        assert_eq!(
+            sorted,
+            r#"module Main exposing (..)
+
+import Array
+
+
+helper : Int -> Int
+helper n =
+    n + 1
+
+
+{-| Entry point
+-}
+main : Int
+main =
+    helper 1 -- not zero
+
+
+-- Unrelated note
+type alias Model =
+    { count : Int }
+"#
+        );
+        assert_eq!(sort_declarations(&parse(&sorted), &sorted), None);
+    }
+
+    #[test]
+    fn test_move_declaration() {
+        let tree = parse(SOURCE);
+        let inside_model = Position::new(13, 2);
+        let edit = move_declaration(&tree, SOURCE, inside_model, MoveDirection::Up).unwrap();
+        let moved = apply(SOURCE, &edit);
+        let tree = parse(&moved);
+        let names: Vec<String> = declaration_blocks(&tree, &moved)
+            .unwrap()
+            .into_iter()
+            .map(|block| block.name)
+            .collect();
+        assert_eq!(names, vec!["Model", "main", "helper"]);
+
+        let first = Position::new(5, 0);
+        assert_eq!(
+            move_declaration(&tree, &moved, first, MoveDirection::Up),
+            None
+        );
+    }
+
+    #[test]
+    fn test_broken_modules_left_alone() {
+        let source = "module Main exposing (..)\n\nhelper : Int\n\nmain = 1\n";
+        assert_eq!(declaration_blocks(&parse(source), source), None);
+    }
+}
@@ -1,5 +1,11 @@
 use crate::parser::ParseError;
-use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentDiagnosticReport, FullDocumentDiagnosticReport,
+    Position, Range, RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport,
+    UnchangedDocumentDiagnosticReport, Url, WorkspaceDocumentDiagnosticReport,
+    WorkspaceFullDocumentDiagnosticReport, WorkspaceUnchangedDocumentDiagnosticReport,
+};
+use std::collections::HashMap;
 
 /// Converts tree-sitter parse errors to LSP diagnostics
 pub fn parse_errors_to_diagnostics(errors: Vec<ParseError>) -> Vec<Diagnostic> {
@@ -9,18 +15,20 @@ pub fn parse_errors_to_diagnostics(errors: Vec<ParseError>) -> Vec<Diagnostic> {
     deduplicated_errors
         .into_iter()
         .map(|error| {
+            // Point at the offending token rather than the whole ERROR node when known
+            let (start, end) = error
+                .context
+                .primary_range
+                .unwrap_or((error.start_position, error.end_position));
             let range = Range::new(
-                Position::new(
-                    error.start_position.row as u32,
-                    error.start_position.column as u32,
-                ),
-                Position::new(
-                    error.end_position.row as u32,
-                    error.end_position.column as u32,
-                ),
+                Position::new(start.row as u32, start.column as u32),
+                Position::new(end.row as u32, end.column as u32),
             );
 
-            let message = create_detailed_error_message(&error);
+            let message = match &error.context.hint {
+                Some(hint) => format!("{} ({})", create_detailed_error_message(&error), hint),
+                None => create_detailed_error_message(&error),
+            };
 
             Diagnostic::new(
                 range,
@@ -121,7 +129,7 @@ fn create_detailed_error_message(error: &ParseError) -> String {
         if let Some(expected) = &error.context.expected {
             format!("Missing {}", expected)
         } else {
-            format!("Missing {}", error.kind)
+            format!("Missing {}", describe_token(&error.kind))
         }
     } else {
         // Handle unexpected tokens with context
@@ -182,6 +190,148 @@ fn create_detailed_error_message(error: &ParseError) -> String {
     }
 }
 
+/// Readable name for a token kind, e.g. `->` for `arrow`
+fn describe_token(kind: &str) -> String {
+    let token = match kind {
+        "arrow" => "->",
+        "eq" => "=",
+        "colon" => ":",
+        "left_parenthesis" => "(",
+        "right_parenthesis" => ")",
+        "left_square_bracket" => "[",
+        "right_square_bracket" => "]",
+        "left_brace" => "{",
+        "right_brace" => "}",
+        "in" | "is" | "then" | "else" | "exposing" => kind,
+        // Named nodes such as `identifier` read fine as they are
+        kind if kind.chars().all(|c| c.is_ascii_lowercase() || c == '_') => {
+            return kind.to_string()
+        }
+        kind => kind,
+    };
+    format!("`{}`", token)
+}
+
+/// The diagnostics last computed for a document, for answering pull requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedDiagnostics {
+    /// Version of the document they were computed for, `None` for a file that isn't open
+    pub version: Option<i32>,
+    /// Identifies the diagnostics to the client, which sends it back to ask whether they
+    /// changed. It only changes when the diagnostics do.
+    pub result_id: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CachedDiagnostics {
+    /// The report answering `textDocument/diagnostic`, just saying nothing changed when
+    /// the client already has these diagnostics as `previous_result_id`
+    pub fn report(&self, previous_result_id: Option<&str>) -> DocumentDiagnosticReport {
+        if previous_result_id == Some(self.result_id.as_str()) {
+            return DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                related_documents: None,
+                unchanged_document_diagnostic_report: self.unchanged_report(),
+            });
+        }
+        DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+            related_documents: None,
+            full_document_diagnostic_report: self.full_report(),
+        })
+    }
+
+    /// The report on `uri` in the answer to `workspace/diagnostic`
+    pub fn workspace_report(
+        &self,
+        uri: &Url,
+        previous_result_id: Option<&str>,
+    ) -> WorkspaceDocumentDiagnosticReport {
+        let version = self.version.map(i64::from);
+        if previous_result_id == Some(self.result_id.as_str()) {
+            return WorkspaceDocumentDiagnosticReport::Unchanged(
+                WorkspaceUnchangedDocumentDiagnosticReport {
+                    uri: uri.clone(),
+                    version,
+                    unchanged_document_diagnostic_report: self.unchanged_report(),
+                },
+            );
+        }
+        WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+            uri: uri.clone(),
+            version,
+            full_document_diagnostic_report: self.full_report(),
+        })
+    }
+
+    fn full_report(&self) -> FullDocumentDiagnosticReport {
+        FullDocumentDiagnosticReport {
+            result_id: Some(self.result_id.clone()),
+            items: self.diagnostics.clone(),
+        }
+    }
+
+    fn unchanged_report(&self) -> UnchangedDocumentDiagnosticReport {
+        UnchangedDocumentDiagnosticReport {
+            result_id: self.result_id.clone(),
+        }
+    }
+}
+
+/// The diagnostics last computed for each document, by document version
+#[derive(Debug, Default)]
+pub struct DiagnosticsCache {
+    entries: HashMap<Url, CachedDiagnostics>,
+    next_result_id: u64,
+}
+
+impl DiagnosticsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the diagnostics computed for `version` of `uri`, returning their result id
+    pub fn store(&mut self, uri: &Url, version: Option<i32>, diagnostics: &[Diagnostic]) -> String {
+        if let Some(entry) = self.entries.get_mut(uri) {
+            if entry.diagnostics == diagnostics {
+                entry.version = version;
+                return entry.result_id.clone();
+            }
+        }
+
+        self.next_result_id += 1;
+        let result_id = self.next_result_id.to_string();
+        self.entries.insert(
+            uri.clone(),
+            CachedDiagnostics {
+                version,
+                result_id: result_id.clone(),
+                diagnostics: diagnostics.to_vec(),
+            },
+        );
+        result_id
+    }
+
+    /// The diagnostics of `uri`, if they were computed for `version`
+    pub fn get(&self, uri: &Url, version: Option<i32>) -> Option<&CachedDiagnostics> {
+        self.entries
+            .get(uri)
+            .filter(|entry| entry.version == version)
+    }
+
+    /// The diagnostics last computed for `uri`, whatever version they were computed for
+    pub fn latest(&self, uri: &Url) -> Option<&CachedDiagnostics> {
+        self.entries.get(uri)
+    }
+
+    /// The diagnostics of every document, whatever version they were computed for
+    pub fn entries(&self) -> impl Iterator<Item = (&Url, &CachedDiagnostics)> {
+        self.entries.iter()
+    }
+
+    pub fn remove(&mut self, uri: &Url) {
+        self.entries.remove(uri);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -189,6 +339,61 @@ mod tests {
     use crate::Parser;
     use tree_sitter::Point;
 
+    #[test]
+    fn test_diagnostics_cache() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let error = Diagnostic::new_simple(Range::default(), "Syntax error".to_string());
+        let mut cache = DiagnosticsCache::new();
+
+        let first = cache.store(&uri, Some(1), &[error.clone()]);
+        assert_eq!(
+            cache.get(&uri, Some(1)).unwrap().diagnostics,
+            vec![error.clone()]
+        );
+        assert!(cache.get(&uri, Some(2)).is_none());
+
+        // Unchanged diagnostics keep their result id
+        assert_eq!(cache.store(&uri, Some(2), &[error]), first);
+        assert!(cache.get(&uri, Some(2)).is_some());
+        assert_ne!(cache.store(&uri, Some(3), &[]), first);
+
+        cache.remove(&uri);
+        assert!(cache.get(&uri, Some(3)).is_none());
+    }
+
+    #[test]
+    fn test_cached_diagnostics_reports() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let cached = CachedDiagnostics {
+            version: Some(4),
+            result_id: "7".to_string(),
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::default(),
+                "Syntax error".to_string(),
+            )],
+        };
+
+        assert!(matches!(
+            cached.report(Some("7")),
+            DocumentDiagnosticReport::Unchanged(_)
+        ));
+        match cached.report(Some("6")) {
+            DocumentDiagnosticReport::Full(report) => {
+                let report = report.full_document_diagnostic_report;
+                assert_eq!(report.result_id.as_deref(), Some("7"));
+                assert_eq!(report.items, cached.diagnostics);
+            }
+            report => panic!("expected a full report, got {:?}", report),
+        }
+        match cached.workspace_report(&uri, None) {
+            WorkspaceDocumentDiagnosticReport::Full(report) => {
+                assert_eq!(report.uri, uri);
+                assert_eq!(report.version, Some(4));
+            }
+            report => panic!("expected a full report, got {:?}", report),
+        }
+    }
+
     #[test]
     fn test_parse_error_to_diagnostic() {
         let error = ParseError {
@@ -263,6 +468,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_primary_range_and_hint() {
+        let context = ParseErrorContext {
+            actual_text: Some("(a b".to_string()),
+            primary_range: Some((Point { row: 2, column: 9 }, Point { row: 2, column: 10 })),
+            hint: Some("possibly missing `)`".to_string()),
+            ..Default::default()
+        };
+        let error = ParseError {
+            start_byte: 20,
+            end_byte: 40,
+            start_position: Point { row: 2, column: 0 },
+            end_position: Point { row: 4, column: 3 },
+            kind: "ERROR".to_string(),
+            is_missing: false,
+            context,
+        };
+
+        let diagnostics = parse_errors_to_diagnostics(vec![error]);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 9), Position::new(2, 10))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Unexpected '(a b' (possibly missing `)`)"
+        );
+    }
+
+    #[test]
+    fn test_missing_token_named_readably() {
+        assert_eq!(describe_token("arrow"), "`->`");
+        assert_eq!(describe_token(")"), "`)`");
+        assert_eq!(describe_token("in"), "`in`");
+        assert_eq!(describe_token("identifier"), "identifier");
+    }
+
+    #[test]
+    fn test_unbalanced_parenthesis_hint() {
+        let mut parser = Parser::new().expect("Failed to create parser");
+        let source = r#"module Test exposing (..)
+
+total =
+    (1 + 2
+
+other = 3
+"#;
+        let tree = parser.parse(source).unwrap().unwrap();
+        let diagnostics =
+            parse_errors_to_diagnostics(Parser::extract_errors_with_source(&tree, source));
+
+        let diagnostic = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.message.contains("possibly missing `)`"))
+            .expect("Expected a hint about the unclosed parenthesis");
+        assert_eq!(diagnostic.range.start, Position::new(3, 4));
+    }
+
     #[test]
     fn test_parser_finds_syntax_errors() {
         let mut parser = Parser::new().expect("Failed to create parser");
@@ -1,10 +1,13 @@
+use crate::source_file::normalize_line_endings;
+use crate::telemetry::uri_hash;
 use crate::{ParseError, Parser};
 use anyhow::Result;
 use lsp_textdocument::FullTextDocument;
 use lsp_types::*;
+use std::borrow::Cow;
 use std::path::Path;
 use std::time::Instant;
-use tracing::info;
+use tracing::{debug_span, info};
 use tree_sitter::Tree;
 
 pub struct Document {
@@ -18,10 +21,14 @@ pub struct Document {
 impl Document {
     pub fn new(text_document_item: TextDocumentItem) -> Self {
         let uri = text_document_item.uri.clone();
+        let text = match normalize_line_endings(&text_document_item.text) {
+            Cow::Borrowed(_) => text_document_item.text,
+            Cow::Owned(normalized) => normalized,
+        };
         let text_document = FullTextDocument::new(
             text_document_item.language_id,
             text_document_item.version,
-            text_document_item.text,
+            text,
         );
 
         Self {
@@ -46,6 +53,16 @@ impl Document {
     }
 
     pub fn apply_changes(&mut self, changes: Vec<TextDocumentContentChangeEvent>) -> Result<()> {
+        // Swap lone `\r` line breaks as `Document::new` does
+        let changes: Vec<TextDocumentContentChangeEvent> = changes
+            .into_iter()
+            .map(|mut change| {
+                if change.text.contains('\r') {
+                    change.text = normalize_line_endings(&change.text).into_owned();
+                }
+                change
+            })
+            .collect();
         let new_version = self.version() + 1;
         self.text_document.update(&changes, new_version);
 
@@ -83,11 +100,23 @@ impl Document {
         Ok(self.parse_tree.as_ref())
     }
 
+    /// Free the parse tree until it's asked for again, for documents only kept to answer
+    /// what they expose
+    pub fn drop_parse_tree(&mut self) {
+        self.parse_tree = None;
+    }
+
     /// Force a reparse of the document
     pub fn reparse(&mut self, parser: &mut Parser) -> Result<()> {
         use tracing::info;
 
         let source = self.text();
+        let _span = debug_span!(
+            "parse",
+            uri_hash = %uri_hash(self.uri()),
+            bytes = source.len()
+        )
+        .entered();
         info!("Reparsing document, source length: {} bytes", source.len());
 
         // TEMPORARY: Always use full parsing to avoid incremental parsing issues
@@ -0,0 +1,247 @@
+//! Undo for edits the server applies itself
+//!
+//! Fixes and commands change files through `workspace/applyEdit`. The editor files such a
+//! change into the undo history of every document it touched, so once the user has moved
+//! on, reverting it means finding it again in each file's history. The journal keeps the
+//! inverse of each applied edit instead, so the last one can be reverted in one step as
+//! long as the files still hold what the edit left in them.
+
+use anyhow::{anyhow, bail, Result};
+use lsp_textdocument::FullTextDocument;
+use lsp_types::{
+    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+    Range, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+use std::collections::{HashMap, VecDeque};
+
+/// Number of server edits that can be undone
+const JOURNAL_SIZE: usize = 20;
+
+/// A server edit that was applied, with what it takes to revert it
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    /// What the edit did, for telling the user what an undo reverts
+    pub label: String,
+    /// Edit putting the files back the way they were
+    pub undo: WorkspaceEdit,
+    /// Content of each edited file right after the edit
+    after: HashMap<Url, String>,
+}
+
+impl JournalEntry {
+    /// Files the edit changed, sorted by URI
+    pub fn files(&self) -> Vec<Url> {
+        let mut files: Vec<Url> = self.after.keys().cloned().collect();
+        files.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        files
+    }
+
+    /// Fail when a file no longer holds what the edit left in it, as reverting the edit
+    /// would then overwrite changes made since. `current` maps each of `files()` to its
+    /// content now.
+    pub fn check_unchanged(&self, current: &HashMap<Url, String>) -> Result<()> {
+        for uri in self.files() {
+            if current.get(&uri) != self.after.get(&uri) {
+                bail!("{} has changed since \"{}\"", uri, self.label);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The most recent server edits, newest last
+#[derive(Debug, Default)]
+pub struct EditJournal {
+    entries: VecDeque<JournalEntry>,
+}
+
+impl EditJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `edit` was applied. `before` maps each file in `edited_files(edit)` to
+    /// its content before the edit. Edits that create, rename or delete files aren't
+    /// recorded and fail here.
+    pub fn record(
+        &mut self,
+        label: &str,
+        edit: &WorkspaceEdit,
+        before: &HashMap<Url, String>,
+    ) -> Result<()> {
+        let mut after = HashMap::new();
+        let mut undo = Vec::new();
+        for (uri, edits) in text_edits(edit)? {
+            let original = before
+                .get(&uri)
+                .ok_or_else(|| anyhow!("No content for {}", uri))?;
+            let (edited, inverse) = apply_edits(original, edits);
+            undo.push(TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: None,
+                },
+                edits: inverse.into_iter().map(OneOf::Left).collect(),
+            });
+            after.insert(uri, edited);
+        }
+
+        if self.entries.len() == JOURNAL_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(JournalEntry {
+            label: label.to_string(),
+            undo: WorkspaceEdit {
+                changes: None,
+                document_changes: Some(DocumentChanges::Edits(undo)),
+                change_annotations: None,
+            },
+            after,
+        });
+        Ok(())
+    }
+
+    /// The most recent server edit
+    pub fn last(&self) -> Option<&JournalEntry> {
+        self.entries.back()
+    }
+
+    /// Forget the most recent server edit, once it has been undone
+    pub fn pop(&mut self) -> Option<JournalEntry> {
+        self.entries.pop_back()
+    }
+}
+
+/// Files whose text `edit` changes
+pub fn edited_files(edit: &WorkspaceEdit) -> Vec<Url> {
+    text_edits(edit)
+        .map(|edits| edits.into_keys().collect())
+        .unwrap_or_default()
+}
+
+/// The text edits of a workspace edit by file, failing for file operations
+fn text_edits(edit: &WorkspaceEdit) -> Result<HashMap<Url, Vec<TextEdit>>> {
+    let mut by_file: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for (uri, edits) in edit.changes.iter().flatten() {
+        by_file
+            .entry(uri.clone())
+            .or_default()
+            .extend(edits.iter().cloned());
+    }
+
+    let document_edits: Vec<&TextDocumentEdit> = match &edit.document_changes {
+        None => Vec::new(),
+        Some(DocumentChanges::Edits(edits)) => edits.iter().collect(),
+        Some(DocumentChanges::Operations(operations)) => operations
+            .iter()
+            .map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Ok(edit),
+                DocumentChangeOperation::Op(_) => Err(anyhow!(
+                    "Edits creating, renaming or deleting files can't be undone"
+                )),
+            })
+            .collect::<Result<_>>()?,
+    };
+    for document_edit in document_edits {
+        by_file
+            .entry(document_edit.text_document.uri.clone())
+            .or_default()
+            .extend(document_edit.edits.iter().map(|edit| match edit {
+                OneOf::Left(edit) => edit.clone(),
+                OneOf::Right(annotated) => annotated.text_edit.clone(),
+            }));
+    }
+    Ok(by_file)
+}
+
+/// Apply `edits` to `text`, returning the new text and the edits turning it back
+fn apply_edits(text: &str, mut edits: Vec<TextEdit>) -> (String, Vec<TextEdit>) {
+    let original = FullTextDocument::new(String::new(), 0, text.to_string());
+    edits.sort_by_key(|edit| (edit.range.start.line, edit.range.start.character));
+
+    let mut edited = String::with_capacity(text.len());
+    let mut copied_up_to = 0;
+    // Byte ranges in the new text of each replacement, with the text it replaced
+    let mut replaced = Vec::new();
+    for edit in edits {
+        let start = (original.offset_at(edit.range.start) as usize).max(copied_up_to);
+        let end = (original.offset_at(edit.range.end) as usize).max(start);
+        edited.push_str(&text[copied_up_to..start]);
+        let new_start = edited.len();
+        edited.push_str(&edit.new_text);
+        replaced.push((new_start, edited.len(), text[start..end].to_string()));
+        copied_up_to = end;
+    }
+    edited.push_str(&text[copied_up_to..]);
+
+    let result = FullTextDocument::new(String::new(), 0, edited.clone());
+    let inverse = replaced
+        .into_iter()
+        .map(|(start, end, old_text)| TextEdit {
+            range: Range::new(
+                result.position_at(start as u32),
+                result.position_at(end as u32),
+            ),
+            new_text: old_text,
+        })
+        .collect();
+    (edited, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+
+    fn text_edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_undo_restores_the_original_text() {
+        let original = "module Page exposing (view)\n\nimport Utils\n";
+        let (edited, inverse) = apply_edits(
+            original,
+            vec![
+                text_edit((2, 7), (2, 12), "Helpers.Utils"),
+                text_edit((0, 7), (0, 11), "Pages.Home"),
+            ],
+        );
+        assert_eq!(
+            edited,
+            "module Pages.Home exposing (view)\n\nimport Helpers.Utils\n"
+        );
+
+        let (restored, _) = apply_edits(&edited, inverse);
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn test_journal_refuses_to_undo_over_later_changes() {
+        let uri = Url::parse("file:///project/src/Page.gren").unwrap();
+        let edit = WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                uri.clone(),
+                vec![text_edit((0, 7), (0, 11), "Home")],
+            )])),
+            ..Default::default()
+        };
+        let before = HashMap::from([(uri.clone(), "module Page exposing (..)\n".to_string())]);
+
+        let mut journal = EditJournal::new();
+        journal.record("Rename module", &edit, &before).unwrap();
+        let entry = journal.last().unwrap();
+        assert_eq!(entry.files(), vec![uri.clone()]);
+
+        let unchanged = HashMap::from([(uri.clone(), "module Home exposing (..)\n".to_string())]);
+        assert!(entry.check_unchanged(&unchanged).is_ok());
+        let changed = HashMap::from([(uri.clone(), "module Home exposing (main)\n".to_string())]);
+        assert!(entry.check_unchanged(&changed).is_err());
+
+        assert!(journal.pop().is_some());
+        assert!(journal.last().is_none());
+    }
+}
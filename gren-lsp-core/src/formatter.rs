@@ -0,0 +1,304 @@
+//! Formatting Gren source with `gren format`
+//!
+//! The compiler ships the formatter, so the server runs `gren format --stdin` with the
+//! same executable and environment as `gren make` and never writes files itself.
+//! `textDocument/formatting`, the workspace formatting check and `gren-lsp fmt` all go
+//! through [`Formatter::format`], so they agree on what formatted means.
+//!
+//! Editors get the lines the formatter changed rather than a replacement of the whole
+//! document, which keeps the cursor, folds and markers elsewhere where they were. Range
+//! formatting formats the whole document too, as the formatter only takes whole modules,
+//! and keeps the changes touching the range.
+//!
+//! Lines being typed don't parse yet, so on-type formatting indents them with
+//! [`crate::indentation`] instead, which follows the layout the formatter writes.
+
+use crate::compiler_options::CompilerOptions;
+use crate::source_file::{line_ending, with_line_ending};
+use anyhow::{anyhow, Result};
+use lsp_types::*;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Diagnostic code of files the formatter would change
+pub const UNFORMATTED: &str = "unformatted";
+
+/// How long one file may take to format
+const FORMAT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Runs the compiler's formatter over source text
+#[derive(Debug, Clone)]
+pub struct Formatter {
+    gren_path: PathBuf,
+    options: CompilerOptions,
+}
+
+impl Formatter {
+    pub fn new(gren_path: &Path, options: CompilerOptions) -> Self {
+        Formatter {
+            gren_path: gren_path.to_path_buf(),
+            options,
+        }
+    }
+
+    /// `source` as the formatter writes it, keeping the line ending `source` uses
+    pub async fn format(&self, source: &str) -> Result<String> {
+        let mut command = Command::new(&self.gren_path);
+        command
+            .args(["format", "--stdin"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        self.options.apply_env(&mut command);
+
+        let mut child = command.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(source.as_bytes()).await?;
+        }
+        let output = match tokio::time::timeout(FORMAT_TIMEOUT, child.wait_with_output()).await {
+            Ok(output) => output?,
+            Err(_) => {
+                return Err(anyhow!(
+                    "Formatter timed out after {} seconds",
+                    FORMAT_TIMEOUT.as_secs()
+                ))
+            }
+        };
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Formatter failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let formatted = String::from_utf8(output.stdout)?;
+        Ok(match with_line_ending(&formatted, line_ending(source)) {
+            Cow::Borrowed(_) => formatted,
+            Cow::Owned(restored) => restored,
+        })
+    }
+}
+
+/// The edit replacing all of `source` with `formatted`, or `None` when they're the same
+pub fn whole_document_edit(source: &str, formatted: &str) -> Option<TextEdit> {
+    if source == formatted {
+        return None;
+    }
+    Some(TextEdit {
+        range: Range::new(Position::new(0, 0), end_position(source)),
+        new_text: formatted.to_string(),
+    })
+}
+
+/// Edits turning `source` into `formatted`, one per run of changed lines
+pub fn minimal_edits(source: &str, formatted: &str) -> Vec<TextEdit> {
+    if source == formatted {
+        return Vec::new();
+    }
+    let old: Vec<&str> = source.split_inclusive('\n').collect();
+    let new: Vec<&str> = formatted.split_inclusive('\n').collect();
+
+    // Lines before and after the changes are left out of the diff
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    let position = |line: usize| {
+        if line < old.len() {
+            Position::new(line as u32, 0)
+        } else {
+            end_position(source)
+        }
+    };
+    let edit = |old_start: usize, old_end: usize, new_lines: &[&str]| {
+        TextEdit::new(
+            Range::new(position(prefix + old_start), position(prefix + old_end)),
+            new_lines.concat(),
+        )
+    };
+
+    if old_changed.len() * new_changed.len() > MAX_DIFF_CELLS {
+        return vec![edit(0, old_changed.len(), new_changed)];
+    }
+
+    let mut edits = Vec::new();
+    let (mut old_start, mut new_start) = (0, 0);
+    for (old_line, new_line) in common_lines(old_changed, new_changed)
+        .into_iter()
+        .chain([(old_changed.len(), new_changed.len())])
+    {
+        if old_line > old_start || new_line > new_start {
+            edits.push(edit(old_start, old_line, &new_changed[new_start..new_line]));
+        }
+        old_start = old_line + 1;
+        new_start = new_line + 1;
+    }
+    edits
+}
+
+/// The edits touching the lines of `range`
+pub fn edits_in_range(edits: Vec<TextEdit>, range: Range) -> Vec<TextEdit> {
+    edits
+        .into_iter()
+        .filter(|edit| {
+            let end_line = match edit.range.end.character {
+                // An edit ending at the start of a line doesn't change it
+                0 if edit.range.end.line > edit.range.start.line => edit.range.end.line - 1,
+                _ => edit.range.end.line,
+            };
+            edit.range.start.line <= range.end.line && end_line >= range.start.line
+        })
+        .collect()
+}
+
+/// Above this many line pairs the changed lines are replaced in one edit, rather than
+/// diffed
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Indices of the lines a longest common subsequence of `old` and `new` keeps, in order
+fn common_lines(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut common = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            common.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
+
+/// The diagnostic on the first line of a file the formatter would change
+pub fn unformatted_diagnostic(source: &str) -> Diagnostic {
+    let first_line = source.lines().next().unwrap_or_default();
+    Diagnostic {
+        range: Range::new(
+            Position::new(0, 0),
+            Position::new(0, first_line.encode_utf16().count() as u32),
+        ),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(UNFORMATTED.to_string())),
+        source: Some("gren-lsp".to_string()),
+        message: "File isn't formatted with `gren format`".to_string(),
+        ..Default::default()
+    }
+}
+
+/// The position after the last character, with the column in UTF-16 code units
+fn end_position(source: &str) -> Position {
+    let line_start = source.rfind('\n').map_or(0, |newline| newline + 1);
+    Position::new(
+        source.matches('\n').count() as u32,
+        source[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_document_edit() {
+        assert_eq!(whole_document_edit("x = 1\n", "x = 1\n"), None);
+
+        let edit = whole_document_edit("x  =  1\ny = 2", "x = 1\n\ny = 2\n").unwrap();
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(0, 0), Position::new(1, 5))
+        );
+        assert_eq!(edit.new_text, "x = 1\n\ny = 2\n");
+    }
+
+    fn apply(source: &str, edits: &[TextEdit]) -> String {
+        let mut lines: Vec<String> = source.split_inclusive('\n').map(str::to_string).collect();
+        // Edits don't overlap, so applying them last first keeps the earlier lines in place
+        for edit in edits.iter().rev() {
+            let start = edit.range.start.line as usize;
+            let end = (edit.range.end.line as usize + usize::from(edit.range.end.character > 0))
+                .min(lines.len());
+            lines.splice(start..end, [edit.new_text.clone()]);
+        }
+        lines.concat()
+    }
+
+    #[test]
+    fn test_minimal_edits() {
+        assert!(minimal_edits("x = 1\n", "x = 1\n").is_empty());
+
+        let source = "module Main exposing (..)\n\nx  =  1\ny = 2\n\n\nz = 3\n";
+        let formatted = "module Main exposing (..)\n\nx = 1\ny = 2\n\nz = 3\n";
+        let edits = minimal_edits(source, formatted);
+        assert_eq!(
+            edits,
+            vec![
+                TextEdit::new(
+                    Range::new(Position::new(2, 0), Position::new(3, 0)),
+                    "x = 1\n".to_string()
+                ),
+                TextEdit::new(
+                    Range::new(Position::new(4, 0), Position::new(5, 0)),
+                    String::new()
+                ),
+            ]
+        );
+        assert_eq!(apply(source, &edits), formatted);
+
+        // The last line has no line break to end the range at
+        let edits = minimal_edits("x = 1\ny  = 2", "x = 1\ny = 2\n");
+        assert_eq!(
+            edits,
+            vec![TextEdit::new(
+                Range::new(Position::new(1, 0), Position::new(1, 6)),
+                "y = 2\n".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_edits_in_range() {
+        let source = "x  =  1\ny = 2\nz  = 3\n";
+        let edits = minimal_edits(source, "x = 1\ny = 2\nz = 3\n");
+        assert_eq!(edits.len(), 2);
+
+        let in_range = edits_in_range(edits, Range::new(Position::new(1, 0), Position::new(2, 3)));
+        assert_eq!(in_range.len(), 1);
+        assert_eq!(in_range[0].range.start.line, 2);
+    }
+
+    #[test]
+    fn test_unformatted_diagnostic() {
+        let diagnostic = unformatted_diagnostic("module Main exposing (..)\n\nx = 1\n");
+        assert_eq!(diagnostic.range.start, Position::new(0, 0));
+        assert_eq!(diagnostic.range.end, Position::new(0, 25));
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String(UNFORMATTED.to_string()))
+        );
+    }
+}
@@ -0,0 +1,681 @@
+//! Imports that make references ambiguous
+//!
+//! Importing a module twice, importing two modules under the same qualifier
+//! (`import Json.Decode as D` next to `import Dict as D`), or exposing one name from two
+//! imports leaves references that could mean either module. The `import-conflict` lint
+//! reports these, and organize imports offers to resolve them before reordering instead
+//! of sorting the ambiguity into place. Both go through [`import_conflicts`], and the
+//! fixes through [`conflict_resolutions`].
+
+use crate::syntax::{child_of_kind, node_range};
+use crate::ModuleExports;
+use lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+/// Diagnostic code attached to conflicting imports
+pub const IMPORT_CONFLICT: &str = "import-conflict";
+
+/// An import clause as written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub module_name: String,
+    pub alias: Option<String>,
+    /// Names in the exposing list, without the constructors `Type(..)` brings in
+    pub exposed: Vec<String>,
+    /// Whether the clause is `exposing (..)`
+    pub exposes_all: bool,
+    pub range: Range,
+    pub text: String,
+    /// The exposing list items as written, in the same order as `exposed`
+    exposed_items: Vec<String>,
+    /// Byte ranges in `text` of the `as` clause and the exposing list
+    alias_span: Option<(usize, usize)>,
+    exposing_span: Option<(usize, usize)>,
+}
+
+impl Import {
+    /// What qualified references to the module start with
+    pub fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.module_name)
+    }
+
+    /// The clause with its `as` clause replaced, or removed when `qualifier` is the module
+    /// name
+    fn with_qualifier(&self, qualifier: &str) -> String {
+        let alias = if qualifier == self.module_name {
+            String::new()
+        } else {
+            format!(" as {}", qualifier)
+        };
+        match self.alias_span {
+            Some((start, end)) => {
+                let before = self.text[..start].trim_end();
+                format!("{}{}{}", before, alias, &self.text[end..])
+            }
+            None => {
+                let end = self.module_name_end();
+                format!("{}{}{}", &self.text[..end], alias, &self.text[end..])
+            }
+        }
+    }
+
+    /// The clause with its exposing list replaced by `items`, or removed when there are none
+    fn with_exposing(&self, items: &[String]) -> String {
+        let exposing = if items.is_empty() {
+            String::new()
+        } else {
+            format!(" exposing ({})", items.join(", "))
+        };
+        match self.exposing_span {
+            Some((start, end)) => {
+                let before = self.text[..start].trim_end().trim_end_matches("exposing");
+                format!("{}{}{}", before.trim_end(), exposing, &self.text[end..])
+            }
+            None => format!("{}{}", self.text, exposing),
+        }
+    }
+
+    fn module_name_end(&self) -> usize {
+        self.text
+            .find(&self.module_name)
+            .map_or(self.text.len(), |start| start + self.module_name.len())
+    }
+}
+
+/// Why two imports conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// The same module is imported twice
+    Duplicate,
+    /// Different modules are imported under the same qualifier
+    Alias,
+    /// Both imports expose `name`
+    Exposed { name: String },
+}
+
+/// Two imports that make references ambiguous, by their index in the file's imports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportConflict {
+    pub kind: ConflictKind,
+    pub first: usize,
+    pub second: usize,
+}
+
+impl ImportConflict {
+    /// Whether organizing imports settles the conflict by merging the two imports, as
+    /// they import one module under one qualifier
+    pub fn is_mergeable(&self, imports: &[Import]) -> bool {
+        self.kind == ConflictKind::Duplicate
+            && imports[self.first].qualifier() == imports[self.second].qualifier()
+    }
+
+    pub fn message(&self, imports: &[Import]) -> String {
+        let first = &imports[self.first];
+        let second = &imports[self.second];
+        match &self.kind {
+            ConflictKind::Duplicate => format!("`{}` is imported twice", first.module_name),
+            ConflictKind::Alias => format!(
+                "`{}` and `{}` are both imported as `{}`",
+                first.module_name,
+                second.module_name,
+                first.qualifier()
+            ),
+            ConflictKind::Exposed { name } => format!(
+                "`{}` is exposed by both `{}` and `{}`",
+                name, first.module_name, second.module_name
+            ),
+        }
+    }
+}
+
+/// A way to resolve a conflict
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictResolution {
+    pub title: String,
+    /// New text of import clauses by index, `None` removing the clause
+    pub rewrites: Vec<(usize, Option<String>)>,
+    /// Edits to the references outside the imports
+    pub reference_edits: Vec<TextEdit>,
+}
+
+impl ConflictResolution {
+    /// Every edit of the resolution, for applying it without reordering the imports
+    pub fn text_edits(&self, imports: &[Import]) -> Vec<TextEdit> {
+        let mut edits: Vec<TextEdit> = self
+            .rewrites
+            .iter()
+            .map(|(index, text)| {
+                let range = imports[*index].range;
+                match text {
+                    Some(text) => TextEdit::new(range, text.clone()),
+                    // Take the line break with it
+                    None => TextEdit::new(
+                        Range::new(
+                            Position::new(range.start.line, 0),
+                            Position::new(range.end.line + 1, 0),
+                        ),
+                        String::new(),
+                    ),
+                }
+            })
+            .collect();
+        edits.extend(self.reference_edits.iter().cloned());
+        edits
+    }
+}
+
+/// The import clauses of a file, in order
+pub fn imports(tree: &Tree, source: &str) -> Vec<Import> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let clauses: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "import_clause")
+        .collect();
+    clauses
+        .iter()
+        .filter_map(|clause| import(clause, source))
+        .collect()
+}
+
+fn import(clause: &Node, source: &str) -> Option<Import> {
+    let module_name = text(child_of_kind(clause, "upper_case_qid")?, source)?.to_string();
+    let start = clause.start_byte();
+    let as_clause = child_of_kind(clause, "as_clause");
+    let alias = as_clause
+        .and_then(|alias| child_of_kind(&alias, "upper_case_identifier"))
+        .and_then(|alias| text(alias, source))
+        .map(str::to_string);
+
+    let exposing_list = child_of_kind(clause, "exposing_list");
+    let mut exposed = Vec::new();
+    let mut exposed_items = Vec::new();
+    let mut exposes_all = false;
+    if let Some(list) = &exposing_list {
+        exposes_all = child_of_kind(list, "double_dot").is_some();
+        let mut cursor = list.walk();
+        for item in list.named_children(&mut cursor) {
+            let name = match item.kind() {
+                "exposed_value" | "exposed_operator" => text(item, source),
+                "exposed_type" => child_of_kind(&item, "upper_case_identifier")
+                    .and_then(|name| text(name, source)),
+                _ => None,
+            };
+            if let (Some(name), Some(item_text)) = (name, text(item, source)) {
+                exposed.push(name.to_string());
+                exposed_items.push(item_text.to_string());
+            }
+        }
+    }
+
+    Some(Import {
+        module_name,
+        alias,
+        exposed,
+        exposes_all,
+        range: node_range(clause, source),
+        text: text(*clause, source)?.trim_end().to_string(),
+        exposed_items,
+        alias_span: as_clause.map(|node| (node.start_byte() - start, node.end_byte() - start)),
+        exposing_span: exposing_list
+            .map(|node| (node.start_byte() - start, node.end_byte() - start)),
+    })
+}
+
+/// Find the imports that conflict. `exports` gives what an imported module exposes, when
+/// it's known, to tell which names `exposing (..)` brings in.
+pub fn import_conflicts(
+    imports: &[Import],
+    exports: impl Fn(&str) -> Option<ModuleExports>,
+) -> Vec<ImportConflict> {
+    let mut conflicts = Vec::new();
+    for (second, later) in imports.iter().enumerate() {
+        for (first, earlier) in imports[..second].iter().enumerate() {
+            let conflict = |kind| ImportConflict {
+                kind,
+                first,
+                second,
+            };
+            if earlier.module_name == later.module_name {
+                conflicts.push(conflict(ConflictKind::Duplicate));
+                continue;
+            }
+            if earlier.qualifier() == later.qualifier() {
+                conflicts.push(conflict(ConflictKind::Alias));
+            }
+            if let Some(name) = shared_exposed_name(earlier, later, &exports) {
+                conflicts.push(conflict(ConflictKind::Exposed { name }));
+            }
+        }
+    }
+    conflicts
+}
+
+/// A name both imports expose. Two imports exposing everything aren't compared, as that's
+/// usually deliberate and which names would clash depends on the whole index.
+fn shared_exposed_name(
+    first: &Import,
+    second: &Import,
+    exports: &impl Fn(&str) -> Option<ModuleExports>,
+) -> Option<String> {
+    let exposes = |import: &Import, name: &str| {
+        import.exposed.iter().any(|exposed| exposed == name)
+            || (import.exposes_all
+                && exports(&import.module_name).is_some_and(|exports| exports_name(&exports, name)))
+    };
+    second
+        .exposed
+        .iter()
+        .find(|name| exposes(first, name))
+        .or_else(|| first.exposed.iter().find(|name| exposes(second, name)))
+        .cloned()
+}
+
+fn exports_name(exports: &ModuleExports, name: &str) -> bool {
+    exports.values.contains(name)
+        || exports.types.contains(name)
+        || exports.constructors.contains(name)
+}
+
+/// Lint diagnostics for conflicting imports, on the later import of each pair
+pub fn import_conflict_diagnostics(
+    imports: &[Import],
+    conflicts: &[ImportConflict],
+) -> Vec<Diagnostic> {
+    conflicts
+        .iter()
+        .map(|conflict| Diagnostic {
+            range: imports[conflict.second].range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(IMPORT_CONFLICT.to_string())),
+            code_description: None,
+            source: Some("gren-lsp".to_string()),
+            message: conflict.message(imports),
+            related_information: None,
+            tags: None,
+            data: None,
+        })
+        .collect()
+}
+
+/// Ways to resolve `conflict`:
+///
+/// - an import of a module twice is merged into one, rewriting references through the
+///   dropped qualifier when the two used different ones
+/// - modules sharing a qualifier get a different one for either of them, and references
+///   to names only that module exposes are qualified with it. This needs both modules'
+///   exports, references to names both expose stay ambiguous and are left alone.
+/// - a name exposed twice is kept in one exposing list and removed from the other
+pub fn conflict_resolutions(
+    tree: &Tree,
+    source: &str,
+    imports: &[Import],
+    conflict: &ImportConflict,
+    exports: impl Fn(&str) -> Option<ModuleExports>,
+) -> Vec<ConflictResolution> {
+    let pair = [conflict.first, conflict.second];
+    match &conflict.kind {
+        ConflictKind::Duplicate => pair
+            .iter()
+            .filter_map(|&kept| {
+                let dropped = if kept == conflict.first {
+                    conflict.second
+                } else {
+                    conflict.first
+                };
+                merge_resolution(tree, source, imports, kept, dropped)
+            })
+            .collect(),
+        ConflictKind::Alias => [conflict.second, conflict.first]
+            .iter()
+            .filter_map(|&renamed| {
+                let other = if renamed == conflict.first {
+                    conflict.second
+                } else {
+                    conflict.first
+                };
+                requalify_resolution(tree, source, imports, renamed, other, &exports)
+            })
+            .collect(),
+        ConflictKind::Exposed { name } => pair
+            .iter()
+            .filter_map(|&kept| {
+                let other = if kept == conflict.first {
+                    conflict.second
+                } else {
+                    conflict.first
+                };
+                let import = &imports[other];
+                if !import.exposed.contains(name) {
+                    return None;
+                }
+                let items: Vec<String> = import
+                    .exposed
+                    .iter()
+                    .zip(&import.exposed_items)
+                    .filter(|(exposed, _)| *exposed != name)
+                    .map(|(_, item)| item.clone())
+                    .collect();
+                Some(ConflictResolution {
+                    title: format!("Use `{}` from `{}`", name, imports[kept].module_name),
+                    rewrites: vec![(other, Some(import.with_exposing(&items)))],
+                    reference_edits: Vec::new(),
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Merge the import at `dropped` into the one at `kept`
+fn merge_resolution(
+    tree: &Tree,
+    source: &str,
+    imports: &[Import],
+    kept: usize,
+    dropped: usize,
+) -> Option<ConflictResolution> {
+    let (import, other) = (&imports[kept], &imports[dropped]);
+    // Merging the same clause written twice one way or the other is the same edit
+    if kept > dropped && import.qualifier() == other.qualifier() {
+        return None;
+    }
+
+    let merged = if import.exposes_all || other.exposes_all {
+        import.with_exposing(&["..".to_string()])
+    } else {
+        let mut items = import.exposed_items.clone();
+        for (name, item) in other.exposed.iter().zip(&other.exposed_items) {
+            if !import.exposed.contains(name) {
+                items.push(item.clone());
+            }
+        }
+        import.with_exposing(&items)
+    };
+
+    let reference_edits = if import.qualifier() == other.qualifier() {
+        Vec::new()
+    } else {
+        requalify_references(tree, source, other.qualifier(), import.qualifier(), |_| {
+            true
+        })
+    };
+    let title = if import.qualifier() == other.qualifier() || import.alias.is_none() {
+        format!("Merge the imports of `{}`", import.module_name)
+    } else {
+        format!(
+            "Merge the imports of `{}` as `{}`",
+            import.module_name,
+            import.qualifier()
+        )
+    };
+    Some(ConflictResolution {
+        title,
+        rewrites: vec![(kept, Some(merged)), (dropped, None)],
+        reference_edits,
+    })
+}
+
+/// Give the import at `renamed` a qualifier no other import uses
+fn requalify_resolution(
+    tree: &Tree,
+    source: &str,
+    imports: &[Import],
+    renamed: usize,
+    other: usize,
+    exports: &impl Fn(&str) -> Option<ModuleExports>,
+) -> Option<ConflictResolution> {
+    let import = &imports[renamed];
+    let renamed_exports = exports(&import.module_name)?;
+    let other_exports = exports(&imports[other].module_name)?;
+
+    let last_segment = import
+        .module_name
+        .rsplit('.')
+        .next()
+        .unwrap_or(&import.module_name);
+    let taken = |qualifier: &str| imports.iter().any(|import| import.qualifier() == qualifier);
+    let qualifier = [last_segment, import.module_name.as_str()]
+        .into_iter()
+        .find(|qualifier| !taken(qualifier))?;
+
+    let reference_edits =
+        requalify_references(tree, source, import.qualifier(), qualifier, |name| {
+            exports_name(&renamed_exports, name) && !exports_name(&other_exports, name)
+        });
+    let title = if qualifier == import.module_name {
+        format!("Import `{}` without an alias", import.module_name)
+    } else {
+        format!("Import `{}` as `{}`", import.module_name, qualifier)
+    };
+    Some(ConflictResolution {
+        title,
+        rewrites: vec![(renamed, Some(import.with_qualifier(qualifier)))],
+        reference_edits,
+    })
+}
+
+/// Edits changing the qualifier of references through `from` to `to`, for the names
+/// `rewrite` accepts
+fn requalify_references(
+    tree: &Tree,
+    source: &str,
+    from: &str,
+    to: &str,
+    rewrite: impl Fn(&str) -> bool,
+) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    collect_requalified(tree.root_node(), source, from, to, &rewrite, &mut edits);
+    edits
+}
+
+fn collect_requalified(
+    node: Node,
+    source: &str,
+    from: &str,
+    to: &str,
+    rewrite: &impl Fn(&str) -> bool,
+    edits: &mut Vec<TextEdit>,
+) {
+    match node.kind() {
+        "module_declaration" | "import_clause" => return,
+        "value_qid" | "upper_case_qid" => {
+            if let Some((qualifier, name)) = text(node, source).and_then(|r| r.rsplit_once('.')) {
+                if qualifier == from && rewrite(name) {
+                    let start = node.start_position();
+                    edits.push(TextEdit::new(
+                        Range::new(
+                            Position::new(start.row as u32, start.column as u32),
+                            Position::new(start.row as u32, (start.column + from.len()) as u32),
+                        ),
+                        to.to_string(),
+                    ));
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_requalified(child, source, from, to, rewrite, edits);
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+    use std::collections::BTreeSet;
+
+    fn parse(source: &str) -> Tree {
+        let mut parser = Parser::new().unwrap();
+        parser.parse(source).unwrap().unwrap()
+    }
+
+    fn exports(module_name: &str) -> Option<ModuleExports> {
+        let values: &[&str] = match module_name {
+            "Json.Decode" => &["map", "string", "decodeString"],
+            "Dict" => &["map", "empty", "insert"],
+            "Html" => &["div", "text"],
+            _ => return None,
+        };
+        Some(ModuleExports {
+            values: values.iter().map(|value| value.to_string()).collect(),
+            types: BTreeSet::new(),
+            constructors: BTreeSet::new(),
+        })
+    }
+
+    #[test]
+    fn test_alias_conflict_resolution() {
+        let source = r#"module Main exposing (..)
+
+import Json.Decode as D
+import Dict as D
+
+decoder = D.map identity D.string
+
+table = D.insert 1 2 D.empty
+"#;
+        let tree = parse(source);
+        let imports = imports(&tree, source);
+        let conflicts = import_conflicts(&imports, exports);
+        assert_eq!(
+            conflicts,
+            vec![ImportConflict {
+                kind: ConflictKind::Alias,
+                first: 0,
+                second: 1,
+            }]
+        );
+        assert_eq!(
+            conflicts[0].message(&imports),
+            "`Json.Decode` and `Dict` are both imported as `D`"
+        );
+
+        let resolutions = conflict_resolutions(&tree, source, &imports, &conflicts[0], exports);
+        assert_eq!(resolutions[0].title, "Import `Dict` without an alias");
+        assert_eq!(
+            resolutions[0].rewrites,
+            vec![(1, Some("import Dict".to_string()))]
+        );
+        // `D.map` could be either module, so it's left for the author
+        let lines: Vec<u32> = resolutions[0]
+            .reference_edits
+            .iter()
+            .map(|edit| edit.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![7, 7]);
+        assert_eq!(resolutions[1].title, "Import `Json.Decode` as `Decode`");
+    }
+
+    #[test]
+    fn test_duplicate_import_resolution() {
+        let source = r#"module Main exposing (..)
+
+import Html exposing (div)
+import Html as H exposing (text)
+
+main = H.text "hi"
+"#;
+        let tree = parse(source);
+        let imports = imports(&tree, source);
+        let conflicts = import_conflicts(&imports, exports);
+        assert_eq!(conflicts[0].kind, ConflictKind::Duplicate);
+        // Under different qualifiers, merging has to rewrite references
+        assert!(!conflicts[0].is_mergeable(&imports));
+
+        let resolutions = conflict_resolutions(&tree, source, &imports, &conflicts[0], exports);
+        assert_eq!(resolutions[0].title, "Merge the imports of `Html`");
+        assert_eq!(
+            resolutions[0].rewrites,
+            vec![
+                (0, Some("import Html exposing (div, text)".to_string())),
+                (1, None)
+            ]
+        );
+        assert_eq!(resolutions[0].reference_edits[0].new_text, "Html");
+
+        let edits = resolutions[0].text_edits(&imports);
+        assert_eq!(
+            edits[1].range,
+            Range::new(Position::new(3, 0), Position::new(4, 0))
+        );
+    }
+
+    #[test]
+    fn test_exposed_conflict_resolution() {
+        let source = r#"module Main exposing (..)
+
+import Json.Decode exposing (map, string)
+import Dict exposing (map)
+
+main = map
+"#;
+        let tree = parse(source);
+        let imports = imports(&tree, source);
+        let conflicts = import_conflicts(&imports, exports);
+        assert_eq!(
+            conflicts[0].kind,
+            ConflictKind::Exposed {
+                name: "map".to_string()
+            }
+        );
+
+        let resolutions = conflict_resolutions(&tree, source, &imports, &conflicts[0], exports);
+        assert_eq!(resolutions[0].title, "Use `map` from `Json.Decode`");
+        assert_eq!(
+            resolutions[0].rewrites,
+            vec![(1, Some("import Dict".to_string()))]
+        );
+        assert_eq!(
+            resolutions[1].rewrites,
+            vec![(0, Some("import Json.Decode exposing (string)".to_string()))]
+        );
+
+        let diagnostics = import_conflict_diagnostics(&imports, &conflicts);
+        assert_eq!(diagnostics[0].range.start.line, 3);
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(IMPORT_CONFLICT.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_exposing_everything_conflicts_when_exports_known() {
+        let source = r#"module Main exposing (..)
+
+import Html exposing (..)
+import Json.Decode exposing (..)
+import Dict exposing (map)
+import Json.Decode as Decode
+
+main = 1
+"#;
+        let tree = parse(source);
+        let imports = imports(&tree, source);
+        let conflicts = import_conflicts(&imports, exports);
+        assert_eq!(
+            conflicts,
+            vec![
+                ImportConflict {
+                    kind: ConflictKind::Exposed {
+                        name: "map".to_string()
+                    },
+                    first: 1,
+                    second: 2,
+                },
+                ImportConflict {
+                    kind: ConflictKind::Duplicate,
+                    first: 1,
+                    second: 3,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,582 @@
+use crate::lints::used_names;
+use crate::syntax::{child_of_kind, point_to_position};
+use crate::ModuleExports;
+use lsp_types::*;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use tree_sitter::{Node, Tree};
+
+/// Diagnostic code attached to import style lints
+pub const IMPORT_STYLE_MISMATCH: &str = "import-style";
+
+/// Modules of `gren-lang/core`, used to group imports when the package sources haven't
+/// been indexed
+const CORE_MODULES: &[&str] = &[
+    "Array",
+    "Basics",
+    "Bitwise",
+    "Bytes",
+    "Bytes.Decode",
+    "Bytes.Encode",
+    "Char",
+    "Debug",
+    "Dict",
+    "Json.Decode",
+    "Json.Encode",
+    "Math",
+    "Maybe",
+    "Platform",
+    "Platform.Cmd",
+    "Platform.Sub",
+    "Process",
+    "Result",
+    "Set",
+    "String",
+    "Task",
+];
+
+/// How imports are ordered by organize-imports and checked by the import lint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportStyle {
+    /// A single block sorted by module name
+    #[default]
+    Alphabetical,
+    /// `gren-lang/core` modules, then other packages, then the project's own modules,
+    /// sorted by module name within each group and separated by a blank line
+    Grouped,
+}
+
+impl ImportStyle {
+    fn description(self) -> &'static str {
+        match self {
+            ImportStyle::Alphabetical => "sorted alphabetically",
+            ImportStyle::Grouped => {
+                "grouped as core, then packages, then local modules, each sorted alphabetically"
+            }
+        }
+    }
+}
+
+/// Where an imported module comes from, in the order groups are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ImportGroup {
+    Core,
+    Package,
+    Local,
+}
+
+/// The import section of a file, as written and as the style wants it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportSection {
+    /// From the start of the first import to the end of the last
+    pub range: Range,
+    pub current: String,
+    pub organized: String,
+}
+
+impl ImportSection {
+    pub fn is_organized(&self) -> bool {
+        self.current == self.organized
+    }
+
+    /// Edit rewriting the section into its organized form
+    pub fn text_edit(&self) -> TextEdit {
+        TextEdit::new(self.range, self.organized.clone())
+    }
+}
+
+/// Work out how a file's imports should be ordered. `classify` says which group an
+/// imported module belongs to and is only consulted for the grouped style.
+///
+/// Returns `None` when there are no imports, or when they can't be reordered without losing
+/// something: imports the parser couldn't read, or comments between imports.
+pub fn organize_imports(
+    tree: &Tree,
+    source: &str,
+    style: ImportStyle,
+    classify: impl Fn(&str) -> ImportGroup,
+) -> Option<ImportSection> {
+    organize_imports_with_rewrites(tree, source, style, classify, &[])
+}
+
+/// Like [`organize_imports`], with the text of some imports replaced first. `rewrites`
+/// holds the new text of imports by their index among the file's imports, `None` removing
+/// the import, as conflict resolutions give them.
+pub fn organize_imports_with_rewrites(
+    tree: &Tree,
+    source: &str,
+    style: ImportStyle,
+    classify: impl Fn(&str) -> ImportGroup,
+    rewrites: &[(usize, Option<String>)],
+) -> Option<ImportSection> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let children: Vec<Node> = root.named_children(&mut cursor).collect();
+
+    let first_index = children
+        .iter()
+        .position(|node| node.kind() == "import_clause")?;
+    let last_index = children
+        .iter()
+        .rposition(|node| node.kind() == "import_clause")?;
+    let imports = &children[first_index..=last_index];
+
+    if imports
+        .iter()
+        .any(|node| node.kind() != "import_clause" || node.has_error())
+    {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    for (index, node) in imports.iter().enumerate() {
+        let module_name = child_of_kind(node, "upper_case_qid")?
+            .utf8_text(source.as_bytes())
+            .ok()?;
+        let group = match style {
+            ImportStyle::Alphabetical => ImportGroup::Core,
+            ImportStyle::Grouped => classify(module_name),
+        };
+        let text = match rewrites.iter().find(|(rewritten, _)| *rewritten == index) {
+            Some((_, Some(text))) => text.as_str(),
+            Some((_, None)) => continue,
+            None => node.utf8_text(source.as_bytes()).ok()?.trim_end(),
+        };
+        entries.push((group, module_name, text));
+    }
+
+    entries.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let mut organized = String::new();
+    for (index, (group, _, text)) in entries.iter().enumerate() {
+        if index > 0 {
+            organized.push('\n');
+            if entries[index - 1].0 != *group {
+                organized.push('\n');
+            }
+        }
+        organized.push_str(text);
+    }
+
+    let first = imports.first()?;
+    let last = imports.last()?;
+    Some(ImportSection {
+        range: Range::new(
+            point_to_position(first.start_position(), source),
+            point_to_position(last.end_position(), source),
+        ),
+        current: source[first.start_byte()..last.end_byte()].to_string(),
+        organized,
+    })
+}
+
+/// Rewrites tidying a file's imports, for [`organize_imports_with_rewrites`]:
+///
+/// - imports of one module under one qualifier are merged into the first of them
+/// - exposing lists are sorted, and lose the names the file doesn't use. Types exposed
+///   with their constructors and operators are kept.
+/// - with `expand_exposing_all`, `exposing (..)` becomes the names the file uses, when
+///   `exports` knows what the module exposes and none of its constructors are used
+pub fn tidy_imports(
+    tree: &Tree,
+    source: &str,
+    expand_exposing_all: bool,
+    exports: impl Fn(&str) -> Option<ModuleExports>,
+) -> Vec<(usize, Option<String>)> {
+    let used = used_names(tree, source);
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let clauses: Vec<Option<ImportClause>> = root
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "import_clause")
+        .map(|node| ImportClause::read(&node, source))
+        .collect();
+
+    let mut rewrites = Vec::new();
+    for (index, clause) in clauses.iter().enumerate() {
+        let clause = match clause {
+            Some(clause) => clause,
+            None => continue,
+        };
+        let same_import = |other: &ImportClause| {
+            other.module_name == clause.module_name && other.qualifier() == clause.qualifier()
+        };
+        if clauses[..index].iter().flatten().any(same_import) {
+            rewrites.push((index, None));
+            continue;
+        }
+
+        let merged: Vec<&ImportClause> = clauses[index..]
+            .iter()
+            .flatten()
+            .filter(|other| same_import(other))
+            .collect();
+        let mut exposes_all = merged.iter().any(|clause| clause.exposes_all);
+        let mut items: Vec<&ExposedItem> = merged
+            .iter()
+            .flat_map(|clause| &clause.items)
+            .filter(|item| item.kept_by(&used))
+            .collect();
+        // A type exposed both with and without its constructors keeps them
+        items.sort_by(|a, b| (&a.name, !a.constructors).cmp(&(&b.name, !b.constructors)));
+        items.dedup_by(|a, b| a.name == b.name);
+        let mut exposed: Vec<String> = items.iter().map(|item| item.text.clone()).collect();
+
+        if exposes_all && expand_exposing_all {
+            let known = exports(&clause.module_name).filter(|exports| {
+                !exports
+                    .constructors
+                    .iter()
+                    .any(|name| used.contains(name.as_str()))
+            });
+            if let Some(known) = known {
+                exposes_all = false;
+                for name in known.types.iter().chain(&known.values) {
+                    if used.contains(name.as_str()) && !items.iter().any(|item| &item.name == name)
+                    {
+                        exposed.push(name.clone());
+                    }
+                }
+                exposed.sort();
+            }
+        }
+
+        let unchanged = merged.len() == 1
+            && exposes_all == clause.exposes_all
+            && (exposes_all || exposed == clause.written_items());
+        if !unchanged {
+            rewrites.push((index, Some(clause.render(exposes_all, &exposed))));
+        }
+    }
+    rewrites
+}
+
+/// An import clause, as far as tidying it goes
+struct ImportClause {
+    module_name: String,
+    alias: Option<String>,
+    exposes_all: bool,
+    items: Vec<ExposedItem>,
+}
+
+/// An entry of an exposing list
+struct ExposedItem {
+    name: String,
+    text: String,
+    /// A type exposed with `(..)`
+    constructors: bool,
+    /// An operator or anything else that's always kept
+    operator: bool,
+}
+
+impl ImportClause {
+    fn read(node: &Node, source: &str) -> Option<Self> {
+        let text = |node: Node| node.utf8_text(source.as_bytes()).ok();
+        let module_name = text(child_of_kind(node, "upper_case_qid")?)?.to_string();
+        let alias = child_of_kind(node, "as_clause")
+            .and_then(|alias| child_of_kind(&alias, "upper_case_identifier"))
+            .and_then(text)
+            .map(str::to_string);
+
+        let mut exposes_all = false;
+        let mut items = Vec::new();
+        if let Some(list) = child_of_kind(node, "exposing_list") {
+            exposes_all = child_of_kind(&list, "double_dot").is_some();
+            let mut cursor = list.walk();
+            for item in list.named_children(&mut cursor) {
+                let name = match item.kind() {
+                    "exposed_type" => child_of_kind(&item, "upper_case_identifier").and_then(text),
+                    "double_dot" => continue,
+                    _ => text(item),
+                };
+                items.push(ExposedItem {
+                    name: name?.to_string(),
+                    text: text(item)?.split_whitespace().collect(),
+                    constructors: item.kind() == "exposed_type" && item.named_child_count() > 1,
+                    operator: !matches!(item.kind(), "exposed_value" | "exposed_type"),
+                });
+            }
+        }
+
+        Some(Self {
+            module_name,
+            alias,
+            exposes_all,
+            items,
+        })
+    }
+
+    fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.module_name)
+    }
+
+    fn written_items(&self) -> Vec<String> {
+        self.items.iter().map(|item| item.text.clone()).collect()
+    }
+
+    fn render(&self, exposes_all: bool, exposed: &[String]) -> String {
+        let mut text = format!("import {}", self.module_name);
+        if self.qualifier() != self.module_name {
+            text.push_str(&format!(" as {}", self.qualifier()));
+        }
+        if exposes_all {
+            text.push_str(" exposing (..)");
+        } else if !exposed.is_empty() {
+            text.push_str(&format!(" exposing ({})", exposed.join(", ")));
+        }
+        text
+    }
+}
+
+impl ExposedItem {
+    fn kept_by(&self, used: &HashSet<&str>) -> bool {
+        self.constructors || self.operator || used.contains(self.name.as_str())
+    }
+}
+
+/// Lint diagnostic for an import section that doesn't follow the style
+pub fn import_style_diagnostic(section: &ImportSection, style: ImportStyle) -> Option<Diagnostic> {
+    if section.is_organized() {
+        return None;
+    }
+
+    Some(Diagnostic {
+        range: section.range,
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String(IMPORT_STYLE_MISMATCH.to_string())),
+        code_description: None,
+        source: Some("gren-lsp".to_string()),
+        message: format!("Imports should be {}", style.description()),
+        related_information: None,
+        tags: None,
+        data: None,
+    })
+}
+
+/// Group for a module defined in `path`: files in the package cache
+/// (`packages/<author>/<name>/...`) belong to a package, anything else to the project
+pub fn import_group_for_path(path: &Path) -> ImportGroup {
+    let components: Vec<&str> = path.iter().filter_map(|c| c.to_str()).collect();
+    let package = components
+        .iter()
+        .rposition(|c| *c == "packages")
+        .and_then(|index| Some((components.get(index + 1)?, components.get(index + 2)?)));
+
+    match package {
+        Some((&"gren-lang", &"core")) => ImportGroup::Core,
+        Some(_) => ImportGroup::Package,
+        None => ImportGroup::Local,
+    }
+}
+
+/// Group for a module that isn't in the symbol index
+pub fn import_group_for_unindexed(module_name: &str) -> ImportGroup {
+    if CORE_MODULES.contains(&module_name) {
+        ImportGroup::Core
+    } else {
+        ImportGroup::Package
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    const SOURCE: &str = r#"module Main exposing (..)
+
+import Utils.Format
+import Json.Decode as Decode
+import Html exposing (Html)
+import Array
+
+main = 42
+"#;
+
+    fn classify(module_name: &str) -> ImportGroup {
+        match module_name {
+            "Html" => ImportGroup::Package,
+            "Utils.Format" => ImportGroup::Local,
+            other => import_group_for_unindexed(other),
+        }
+    }
+
+    fn section_for(source: &str, style: ImportStyle) -> Option<ImportSection> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        organize_imports(&tree, source, style, classify)
+    }
+
+    #[test]
+    fn test_alphabetical_imports() {
+        let section = section_for(SOURCE, ImportStyle::Alphabetical).unwrap();
+        assert_eq!(
+            section.organized,
+            "import Array\nimport Html exposing (Html)\nimport Json.Decode as Decode\nimport Utils.Format"
+        );
+        assert_eq!(section.range.start.line, 2);
+        assert_eq!(section.range.end.line, 5);
+    }
+
+    #[test]
+    fn test_grouped_imports() {
+        let section = section_for(SOURCE, ImportStyle::Grouped).unwrap();
+        assert_eq!(
+            section.organized,
+            "import Array\nimport Json.Decode as Decode\n\nimport Html exposing (Html)\n\nimport Utils.Format"
+        );
+
+        let diagnostic = import_style_diagnostic(&section, ImportStyle::Grouped).unwrap();
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String(IMPORT_STYLE_MISMATCH.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_organized_imports_not_reported() {
+        let source = r#"module Main exposing (..)
+
+import Array
+import Json.Decode as Decode
+
+import Html exposing (Html)
+
+import Utils.Format
+
+main = 42
+"#;
+        let section = section_for(source, ImportStyle::Grouped).unwrap();
+        assert!(section.is_organized());
+        assert_eq!(
+            import_style_diagnostic(&section, ImportStyle::Grouped),
+            None
+        );
+    }
+
+    #[test]
+    fn test_organize_imports_with_rewrites() {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        let rewrites = [(1, Some("import Json.Decode as D".to_string())), (3, None)];
+        let section = organize_imports_with_rewrites(
+            &tree,
+            SOURCE,
+            ImportStyle::Alphabetical,
+            classify,
+            &rewrites,
+        )
+        .unwrap();
+        assert_eq!(
+            section.organized,
+            "import Html exposing (Html)\nimport Json.Decode as D\nimport Utils.Format"
+        );
+    }
+
+    #[test]
+    fn test_tidy_imports() {
+        let source = r#"module Main exposing (..)
+
+import Html exposing (text, div, span)
+import Dict exposing (Dict, empty)
+import Html exposing (Attribute, div)
+import Maybe exposing (Maybe(..), withDefault)
+
+view : Dict String Int -> Html.Html msg
+view _ =
+    div [] [ text "hi" ]
+"#;
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        let rewrites = tidy_imports(&tree, source, false, |_| None);
+        assert_eq!(
+            rewrites,
+            vec![
+                (0, Some("import Html exposing (div, text)".to_string())),
+                (1, Some("import Dict exposing (Dict)".to_string())),
+                (2, None),
+                (3, Some("import Maybe exposing (Maybe(..))".to_string())),
+            ]
+        );
+
+        let section = organize_imports_with_rewrites(
+            &tree,
+            source,
+            ImportStyle::Alphabetical,
+            classify,
+            &rewrites,
+        )
+        .unwrap();
+        assert_eq!(
+            section.organized,
+            "import Dict exposing (Dict)\nimport Html exposing (div, text)\nimport Maybe exposing (Maybe(..))"
+        );
+    }
+
+    #[test]
+    fn test_tidy_imports_expands_exposing_all() {
+        let source = r#"module Main exposing (..)
+
+import Dict exposing (..)
+import Maybe exposing (..)
+
+main = insert 1 (withDefault 0 (Just 2)) empty
+"#;
+        let exports = |module_name: &str| {
+            let (values, constructors): (&[&str], &[&str]) = match module_name {
+                "Dict" => (&["empty", "insert", "remove"], &[]),
+                "Maybe" => (&["withDefault"], &["Just", "Nothing"]),
+                _ => return None,
+            };
+            Some(ModuleExports {
+                values: values.iter().map(|name| name.to_string()).collect(),
+                types: [module_name.to_string()].into_iter().collect(),
+                constructors: constructors.iter().map(|name| name.to_string()).collect(),
+            })
+        };
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        assert!(tidy_imports(&tree, source, false, exports).is_empty());
+        // Maybe's constructors could be in use, so it keeps `(..)`
+        assert_eq!(
+            tidy_imports(&tree, source, true, exports),
+            vec![(0, Some("import Dict exposing (empty, insert)".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_imports_with_comments_left_alone() {
+        let source = r#"module Main exposing (..)
+
+import Json.Decode
+-- Needed for rendering
+import Array
+
+main = 42
+"#;
+        assert_eq!(section_for(source, ImportStyle::Alphabetical), None);
+    }
+
+    #[test]
+    fn test_import_group_for_path() {
+        assert_eq!(
+            import_group_for_path(Path::new(
+                "/home/user/.cache/gren/packages/gren-lang/core/7.0.0/src/Array.gren"
+            )),
+            ImportGroup::Core
+        );
+        assert_eq!(
+            import_group_for_path(Path::new(
+                "/project/.gren/packages/gren-lang/browser/src/Html.gren"
+            )),
+            ImportGroup::Package
+        );
+        assert_eq!(
+            import_group_for_path(Path::new("/project/src/Utils/Format.gren")),
+            ImportGroup::Local
+        );
+    }
+}
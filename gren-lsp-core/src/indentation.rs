@@ -0,0 +1,276 @@
+//! Indentation the way `gren format` lays code out, for lines being typed
+//!
+//! The formatter only takes whole modules that parse, so it can't place the cursor while a
+//! `when` or `let` is half written. This works out a line's indentation from the lines
+//! above it instead: the syntax tree gives where the enclosing declaration, `when`, `let`
+//! or `if` starts, and the text of the previous line covers what doesn't parse yet.
+//! Indentation steps are four spaces, as the formatter writes them.
+
+use lsp_types::*;
+use tree_sitter::{Node, Point, Tree};
+
+/// Spaces per indentation step
+pub const INDENT_WIDTH: usize = 4;
+
+/// The indentation of a new line at `line`, going by the lines above it
+pub fn new_line_indentation(tree: &Tree, source: &str, line: usize) -> usize {
+    let lines: Vec<&str> = source.lines().collect();
+    let previous = match (0..line.min(lines.len()))
+        .rev()
+        .find(|index| !lines[*index].trim().is_empty())
+    {
+        Some(previous) => previous,
+        None => return 0,
+    };
+    let text = lines[previous].trim_end();
+    let indentation = indentation_of(text);
+
+    let (token, column) = last_token(text);
+    let node = leaf_at(tree, previous, column);
+    if node.is_some_and(|node| node.kind().ends_with("comment")) {
+        return indentation;
+    }
+
+    match token {
+        // A declaration's body, or a record field's value
+        "=" => match node.and_then(|node| ancestor(node, DEFINITIONS)) {
+            Some(declaration) => declaration.start_position().column + INDENT_WIDTH,
+            None => indentation + INDENT_WIDTH,
+        },
+        // A branch's body
+        "->" => match node.and_then(|node| ancestor(node, &["case_of_branch"])) {
+            Some(branch) => branch.start_position().column + INDENT_WIDTH,
+            None => indentation + INDENT_WIDTH,
+        },
+        // The first branch
+        "is" => match node.and_then(|node| ancestor(node, &["case_of_expr"])) {
+            Some(when) => when.start_position().column + INDENT_WIDTH,
+            None => keyword_column(text, "when").unwrap_or(indentation) + INDENT_WIDTH,
+        },
+        // The first binding
+        "let" => column + INDENT_WIDTH,
+        // The body, level with `in`
+        "in" => column,
+        "then" => match node.and_then(|node| ancestor(node, &["if_else_expr"])) {
+            Some(condition) => condition.start_position().column + INDENT_WIDTH,
+            None => indentation + INDENT_WIDTH,
+        },
+        "else" => column + INDENT_WIDTH,
+        _ => indentation,
+    }
+}
+
+/// The indentation of `line` when all it holds is a keyword closing what an earlier line
+/// opened: `in` under its `let`, and `is` under its `when` when the value it matches on
+/// takes several lines
+pub fn keyword_indentation(tree: &Tree, source: &str, line: usize) -> Option<usize> {
+    let lines: Vec<&str> = source.lines().collect();
+    let text = lines.get(line)?;
+    let keyword = text.trim();
+    let (kind, opening) = match keyword {
+        "in" => ("let_in_expr", "let"),
+        "is" => ("case_of_expr", "when"),
+        _ => return None,
+    };
+
+    let node = leaf_at(tree, line, indentation_of(text));
+    if let Some(expression) = node.and_then(|node| ancestor(node, &[kind])) {
+        if expression.start_position().row < line {
+            return Some(expression.start_position().column);
+        }
+    }
+
+    // Not parsed yet: the nearest opening keyword above that isn't closed already
+    let mut unclosed = 0;
+    for text in lines[..line].iter().rev() {
+        if text.trim() == keyword {
+            unclosed += 1;
+            continue;
+        }
+        if let Some(column) = keyword_column(text, opening) {
+            if unclosed == 0 {
+                return Some(column);
+            }
+            unclosed -= 1;
+        }
+    }
+    None
+}
+
+/// The edit giving `line` `indentation` spaces, `None` when it already has them
+pub fn indentation_edit(source: &str, line: usize, indentation: usize) -> Option<TextEdit> {
+    let text = source.lines().nth(line).unwrap_or("");
+    let current = text.len() - text.trim_start().len();
+    if current == indentation && text[..current].chars().all(|c| c == ' ') {
+        return None;
+    }
+
+    Some(TextEdit {
+        range: Range::new(
+            Position::new(line as u32, 0),
+            Position::new(line as u32, current as u32),
+        ),
+        new_text: " ".repeat(indentation),
+    })
+}
+
+/// What an `=` ending a line can be the `=` of
+const DEFINITIONS: &[&str] = &[
+    "field",
+    "value_declaration",
+    "type_declaration",
+    "type_alias_declaration",
+];
+
+fn indentation_of(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+/// The last token of a line and its column: a word, or a run of operator characters
+fn last_token(text: &str) -> (&str, usize) {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let last = match text.chars().last() {
+        Some(last) => last,
+        None => return ("", 0),
+    };
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| {
+            if is_word(last) {
+                is_word(*c)
+            } else {
+                !is_word(*c) && !c.is_whitespace() && !"()[]{},".contains(*c)
+            }
+        })
+        .last()
+        .map_or(text.len(), |(index, _)| index);
+    (&text[start..], start)
+}
+
+/// The column of the last `keyword` on a line, as a whole word
+fn keyword_column(text: &str, keyword: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(keyword)
+        .filter(|(index, _)| {
+            let before = text[..*index].chars().last();
+            let after = text[index + keyword.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+        .map(|(index, _)| index)
+        .last()
+}
+
+fn leaf_at(tree: &Tree, line: usize, column: usize) -> Option<Node> {
+    let point = Point::new(line, column);
+    tree.root_node().descendant_for_point_range(point, point)
+}
+
+fn ancestor<'a>(node: Node<'a>, kinds: &[&str]) -> Option<Node<'a>> {
+    let mut node = node;
+    loop {
+        if kinds.contains(&node.kind()) {
+            return Some(node);
+        }
+        node = node.parent()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_last_token() {
+        assert_eq!(last_token("view model ="), ("=", 11));
+        assert_eq!(last_token("    when msg is"), ("is", 13));
+        assert_eq!(last_token("        Increment ->"), ("->", 18));
+        assert_eq!(last_token("    let"), ("let", 4));
+        assert_eq!(last_token("    x == y"), ("y", 9));
+        assert_eq!(last_token(""), ("", 0));
+    }
+
+    #[test]
+    fn test_keyword_column() {
+        assert_eq!(keyword_column("    when msg is", "when"), Some(4));
+        assert_eq!(keyword_column("    whenever", "when"), None);
+        assert_eq!(keyword_column("x = let", "let"), Some(4));
+    }
+
+    #[test]
+    fn test_indentation_edit() {
+        let source = "update msg model =\n  model\n    \n";
+        let edit = indentation_edit(source, 1, 4).unwrap();
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(1, 0), Position::new(1, 2))
+        );
+        assert_eq!(edit.new_text, "    ");
+        assert_eq!(indentation_edit(source, 2, 4), None);
+        assert_eq!(indentation_edit(source, 3, 8).unwrap().new_text, "        ");
+    }
+
+    #[test]
+    fn test_new_line_indentation() {
+        let source = "module Main exposing (..)
+
+update msg model =
+    when msg is
+        Increment ->
+            let
+                next =
+                    model + 1
+            in
+            next
+
+        Reset ->
+            if model > 10 then
+                0
+
+            else
+                model
+";
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        let after = |line: usize| new_line_indentation(&tree, source, line + 1);
+        assert_eq!(after(2), 4);
+        assert_eq!(after(3), 8);
+        assert_eq!(after(4), 12);
+        assert_eq!(after(5), 16);
+        assert_eq!(after(6), 20);
+        assert_eq!(after(7), 20);
+        assert_eq!(after(8), 12);
+        assert_eq!(after(12), 16);
+        assert_eq!(after(15), 16);
+        assert_eq!(new_line_indentation(&tree, source, 0), 0);
+    }
+
+    #[test]
+    fn test_keyword_indentation() {
+        let source = "module Main exposing (..)
+
+total items =
+    let
+        sum =
+            Array.foldl (+) 0 items
+        in
+";
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        assert_eq!(keyword_indentation(&tree, source, 6), Some(4));
+        assert_eq!(keyword_indentation(&tree, source, 5), None);
+
+        let source = "module Main exposing (..)
+
+describe value =
+    when
+        value
+            |> String.trim
+        is
+";
+        let tree = parser.parse(source).unwrap().unwrap();
+        assert_eq!(keyword_indentation(&tree, source, 6), Some(4));
+    }
+}
@@ -0,0 +1,184 @@
+//! How current the symbol index is for each file
+//!
+//! Files are re-indexed as soon as the server sees their new content, but a refactor that
+//! touches files the editor doesn't have open only reaches the server once the client opens
+//! them or its file watcher reports the change. Until then, references, renames and other
+//! answers built from the index can miss occurrences in those files. Every indexing of a
+//! file gets a generation number, and files an edit is about to change are pending until
+//! they're indexed again, so responses can say when they may be incomplete.
+//!
+//! Files matching `index.signatures_only` in `.gren-lsp.toml` are indexed with only the
+//! signatures of what they expose, and are marked so responses can say that too.
+
+use lsp_types::Url;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// How long a file stays pending without being re-indexed. An edit the client declined
+/// never changes the file, which shouldn't be reported as stale forever.
+pub const PENDING_EXPIRY: Duration = Duration::from_secs(60);
+
+/// Index generation of each file, and the files waiting to be re-indexed
+#[derive(Debug, Default)]
+pub struct IndexGenerations {
+    generation: u64,
+    indexed: HashMap<Url, u64>,
+    pending: HashMap<Url, Instant>,
+    /// Whether the pending files changed since `take_changed`
+    changed: bool,
+    /// Files indexed with only the signatures of their exposed declarations
+    signatures_only: HashSet<Url>,
+}
+
+impl IndexGenerations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `uri` was just indexed, returning its new generation
+    pub fn record_indexed(&mut self, uri: &Url) -> u64 {
+        self.generation += 1;
+        self.indexed.insert(uri.clone(), self.generation);
+        if self.pending.remove(uri).is_some() {
+            self.changed = true;
+        }
+        self.generation
+    }
+
+    /// Record that `uri` is about to change on disk without the server seeing it yet
+    pub fn mark_pending(&mut self, uri: Url, now: Instant) {
+        if self.pending.insert(uri, now).is_none() {
+            self.changed = true;
+        }
+    }
+
+    /// Record whether `uri` was last indexed with only the signatures of its exposed
+    /// declarations
+    pub fn set_signatures_only(&mut self, uri: &Url, signatures_only: bool) {
+        if signatures_only {
+            self.signatures_only.insert(uri.clone());
+        } else {
+            self.signatures_only.remove(uri);
+        }
+    }
+
+    /// Whether `uri` was last indexed with only the signatures of its exposed declarations,
+    /// so its private declarations and the references in its bodies aren't in the index
+    pub fn is_signatures_only(&self, uri: &Url) -> bool {
+        self.signatures_only.contains(uri)
+    }
+
+    /// Files indexed with only their signatures, sorted
+    pub fn signatures_only_files(&self) -> Vec<Url> {
+        let mut files: Vec<Url> = self.signatures_only.iter().cloned().collect();
+        files.sort();
+        files
+    }
+
+    /// Forget a file that left the index
+    pub fn remove(&mut self, uri: &Url) {
+        self.indexed.remove(uri);
+        self.signatures_only.remove(uri);
+        if self.pending.remove(uri).is_some() {
+            self.changed = true;
+        }
+    }
+
+    /// Stop reporting files that have been pending for longer than `PENDING_EXPIRY`
+    pub fn expire(&mut self, now: Instant) {
+        let before = self.pending.len();
+        self.pending
+            .retain(|_, since| now.saturating_duration_since(*since) < PENDING_EXPIRY);
+        if self.pending.len() != before {
+            self.changed = true;
+        }
+    }
+
+    /// Generation of the most recent indexing of any file
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Generation `uri` was last indexed at, `None` if it isn't indexed
+    pub fn file_generation(&self, uri: &Url) -> Option<u64> {
+        self.indexed.get(uri).copied()
+    }
+
+    /// Whether `uri` changed since it was last indexed
+    pub fn is_pending(&self, uri: &Url) -> bool {
+        self.pending.contains_key(uri)
+    }
+
+    /// Files waiting to be re-indexed, sorted
+    pub fn pending_files(&self) -> Vec<Url> {
+        let mut files: Vec<Url> = self.pending.keys().cloned().collect();
+        files.sort();
+        files
+    }
+
+    /// Whether the pending files changed since the last call
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///project/src/{}.gren", name)).unwrap()
+    }
+
+    #[test]
+    fn test_pending_until_reindexed() {
+        let now = Instant::now();
+        let mut generations = IndexGenerations::new();
+        assert_eq!(generations.record_indexed(&uri("Main")), 1);
+        assert_eq!(generations.record_indexed(&uri("Page")), 2);
+        assert!(!generations.take_changed());
+
+        generations.mark_pending(uri("Page"), now);
+        generations.mark_pending(uri("View"), now);
+        assert!(generations.take_changed());
+        assert!(generations.is_pending(&uri("Page")));
+        assert_eq!(generations.pending_files(), vec![uri("Page"), uri("View")]);
+
+        assert_eq!(generations.record_indexed(&uri("Page")), 3);
+        assert!(generations.take_changed());
+        assert_eq!(generations.file_generation(&uri("Page")), Some(3));
+        assert_eq!(generations.file_generation(&uri("Main")), Some(1));
+        assert_eq!(generations.pending_files(), vec![uri("View")]);
+    }
+
+    #[test]
+    fn test_signatures_only_files() {
+        let mut generations = IndexGenerations::new();
+        generations.record_indexed(&uri("Generated"));
+        generations.set_signatures_only(&uri("Generated"), true);
+        generations.set_signatures_only(&uri("Api"), true);
+        assert!(generations.is_signatures_only(&uri("Generated")));
+        assert_eq!(
+            generations.signatures_only_files(),
+            vec![uri("Api"), uri("Generated")]
+        );
+
+        generations.set_signatures_only(&uri("Api"), false);
+        generations.remove(&uri("Generated"));
+        assert!(generations.signatures_only_files().is_empty());
+    }
+
+    #[test]
+    fn test_pending_files_expire() {
+        let now = Instant::now();
+        let mut generations = IndexGenerations::new();
+        generations.mark_pending(uri("Main"), now);
+        generations.take_changed();
+
+        generations.expire(now + Duration::from_secs(1));
+        assert!(!generations.take_changed());
+        generations.expire(now + PENDING_EXPIRY);
+        assert!(generations.take_changed());
+        assert!(generations.pending_files().is_empty());
+    }
+}
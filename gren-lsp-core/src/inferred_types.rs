@@ -0,0 +1,244 @@
+//! The types the compiler infers for top-level values without an annotation
+//!
+//! The compiler doesn't report the types it infers, but it does report the type of a
+//! definition's body when that disagrees with its annotation. So the module is compiled with
+//! a probe added, a definition annotated as `{}` whose body is the value asked about, and
+//! the compiler's complaint about the probe says what type the value has.
+
+use crate::compiler::CompilationResult;
+use crate::compiler_diagnostics::{compiler_hints, CompilerHint};
+use crate::syntax::child_of_kind;
+use tree_sitter::{Node, Tree};
+
+/// Name of the probe definition, numbered when the module already uses it
+const PROBE_NAME: &str = "grenLspInferredType";
+
+/// Widest annotation kept on one line
+const ANNOTATION_WIDTH: usize = 80;
+
+/// A top-level value with no type annotation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnannotatedValue {
+    pub name: String,
+    /// Zero-based line the definition starts on, where its annotation goes
+    pub line: u32,
+}
+
+/// The unannotated top-level value whose definition's head, its name and arguments, is on
+/// zero-based `line`
+pub fn unannotated_value_at(tree: &Tree, source: &str, line: u32) -> Option<UnannotatedValue> {
+    unannotated_values(tree, source)
+        .into_iter()
+        .find(|(head, _)| {
+            head.start_position().row <= line as usize && line as usize <= head.end_position().row
+        })
+        .map(|(_, value)| value)
+}
+
+/// The unannotated top-level value called `name`
+pub fn unannotated_value(tree: &Tree, source: &str, name: &str) -> Option<UnannotatedValue> {
+    unannotated_values(tree, source)
+        .into_iter()
+        .map(|(_, value)| value)
+        .find(|value| value.name == name)
+}
+
+fn unannotated_values<'a>(tree: &'a Tree, source: &str) -> Vec<(Node<'a>, UnannotatedValue)> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declarations: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|node| !matches!(node.kind(), "line_comment" | "block_comment"))
+        .collect();
+
+    let mut values = Vec::new();
+    for (index, declaration) in declarations.iter().enumerate() {
+        if declaration.kind() != "value_declaration" {
+            continue;
+        }
+        let head = match child_of_kind(declaration, "function_declaration_left") {
+            Some(head) => head,
+            None => continue,
+        };
+        let name = match child_of_kind(&head, "lower_case_identifier")
+            .and_then(|name| text(name, source))
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        let annotated = index
+            .checked_sub(1)
+            .map(|previous| declarations[previous])
+            .filter(|previous| previous.kind() == "type_annotation")
+            .and_then(|annotation| child_of_kind(&annotation, "lower_case_identifier"))
+            .and_then(|annotated| text(annotated, source))
+            == Some(name);
+        if !annotated {
+            values.push((
+                head,
+                UnannotatedValue {
+                    name: name.to_string(),
+                    line: declaration.start_position().row as u32,
+                },
+            ));
+        }
+    }
+    values
+}
+
+/// `source` with a probe for the type of the top-level value `name` added, and the probe's
+/// name to look for in the compiler's errors
+pub fn type_probe(source: &str, name: &str) -> (String, String) {
+    let mut probe = PROBE_NAME.to_string();
+    let mut number = 1;
+    while source.contains(&probe) {
+        number += 1;
+        probe = format!("{}{}", PROBE_NAME, number);
+    }
+    let separator = if source.ends_with('\n') { "\n" } else { "\n\n" };
+    let probed = format!(
+        "{}{}{} : {{}}\n{} =\n    {}\n",
+        source, separator, probe, probe, name
+    );
+    (probed, probe)
+}
+
+/// The type the compiler found the probe's body to have
+pub fn probed_type(result: &CompilationResult, probe: &str) -> Option<String> {
+    result
+        .diagnostics
+        .iter()
+        .flat_map(|diagnostic| compiler_hints(&diagnostic.message))
+        .find_map(|hint| match hint {
+            CompilerHint::Annotate { name, annotation } if name == probe => Some(annotation),
+            _ => None,
+        })
+}
+
+/// The annotation line giving `name` the type `annotation`. One too long to fit on a line
+/// gets a line per argument, the way `gren format` lays out long signatures.
+pub fn annotation_text(name: &str, annotation: &str) -> String {
+    let single = format!("{} : {}", name, annotation);
+    if single.len() <= ANNOTATION_WIDTH {
+        return single;
+    }
+    let parts = top_level_arrows(annotation);
+    if parts.len() < 2 {
+        return single;
+    }
+    let mut text = format!("{} :\n    {}", name, parts[0]);
+    for part in &parts[1..] {
+        text.push_str(&format!("\n    -> {}", part));
+    }
+    text
+}
+
+/// A function type split at the arrows outside any parentheses, records or arrays
+fn top_level_arrows(annotation: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let bytes = annotation.as_bytes();
+    for (index, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'(' | b'{' | b'[' => depth += 1,
+            b')' | b'}' | b']' => depth = depth.saturating_sub(1),
+            b'-' if depth == 0 && bytes.get(index + 1) == Some(&b'>') => {
+                parts.push(annotation[start..index].trim());
+                start = index + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(annotation[start..].trim());
+    parts
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{CompilerDiagnostic, DiagnosticSeverity};
+    use crate::Parser;
+
+    #[test]
+    fn test_unannotated_value_at() {
+        let source = "module Main exposing (..)\n\n\
+                      total : Int\ntotal =\n    1\n\n\
+                      double n =\n    n * 2\n";
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        assert_eq!(unannotated_value_at(&tree, source, 3), None);
+        assert_eq!(
+            unannotated_value_at(&tree, source, 6),
+            Some(UnannotatedValue {
+                name: "double".to_string(),
+                line: 6
+            })
+        );
+        assert_eq!(unannotated_value_at(&tree, source, 7), None);
+        assert_eq!(unannotated_value(&tree, source, "total"), None);
+    }
+
+    #[test]
+    fn test_type_probe() {
+        let (probed, probe) =
+            type_probe("module Main exposing (..)\n\ndouble n = n * 2\n", "double");
+        assert_eq!(probe, "grenLspInferredType");
+        assert!(probed.ends_with(
+            "double n = n * 2\n\ngrenLspInferredType : {}\ngrenLspInferredType =\n    double\n"
+        ));
+
+        let (_, probe) = type_probe("grenLspInferredType = 1\n", "x");
+        assert_eq!(probe, "grenLspInferredType2");
+    }
+
+    #[test]
+    fn test_probed_type() {
+        let diagnostic = CompilerDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            title: "TYPE MISMATCH".to_string(),
+            message:
+                "Something is off with the body of the `grenLspInferredType` definition:\n\n\
+                      12|     double\n\n\
+                      This `double` value is a:\n\n    number -> number\n\n\
+                      But the type annotation on `grenLspInferredType` says it should be:\n\n    {}"
+                    .to_string(),
+            path: None,
+            location: None,
+            markdown: None,
+        };
+        let result = CompilationResult {
+            success: false,
+            diagnostics: vec![diagnostic],
+            global_errors: Vec::new(),
+            timestamp: std::time::SystemTime::now(),
+            content_hash: 0,
+            modules_compiled: None,
+            from_cache: false,
+        };
+        assert_eq!(
+            probed_type(&result, "grenLspInferredType"),
+            Some("number -> number".to_string())
+        );
+        assert_eq!(probed_type(&result, "other"), None);
+    }
+
+    #[test]
+    fn test_annotation_text() {
+        assert_eq!(
+            annotation_text("double", "number -> number"),
+            "double : number -> number"
+        );
+        assert_eq!(
+            annotation_text(
+                "update",
+                "(Msg -> Model -> Model) -> { model : Model, history : Array Model } -> Cmd Msg"
+            ),
+            "update :\n    (Msg -> Model -> Model)\n    -> { model : Model, history : Array Model }\n    -> Cmd Msg"
+        );
+    }
+}
@@ -1,19 +1,89 @@
+pub mod alias_cycles;
 pub mod analysis;
+pub mod annotation_diagnostics;
+pub mod comments;
 pub mod compiler;
 pub mod compiler_diagnostics;
+pub mod compiler_options;
+pub mod declaration_check;
+pub mod declaration_order;
 pub mod diagnostics;
 pub mod document;
-pub mod parser;
+pub mod edit_journal;
+pub mod formatter;
+pub mod import_conflicts;
+pub mod imports;
+pub mod indentation;
+pub mod index_freshness;
+pub mod inferred_types;
+pub mod lints;
+pub mod manifest;
+pub mod metrics;
+pub mod module_graph;
+pub mod package_resolver;
+pub mod popular_symbols;
+pub mod project_config;
+pub mod project_graph;
+pub mod record_updates;
+pub mod reference_counts;
+pub mod runtime_log;
+pub mod scope_analysis;
+pub mod shadowing;
+pub mod source_file;
+pub mod string_literals;
 pub mod symbol;
+pub mod symbol_query;
+pub mod symbol_store;
+pub mod syntax;
+#[cfg(feature = "tantivy")]
+pub mod tantivy_store;
+pub mod telemetry;
+pub mod todos;
+pub mod type_inference;
+pub mod type_usage;
+pub mod uri;
 pub mod workspace;
 
+pub use alias_cycles::{alias_cycles, AliasCycle};
 pub use analysis::AnalysisEngine;
-pub use compiler::{CompilerDiagnostic, GrenCompiler};
+pub use annotation_diagnostics::annotation_arity_diagnostics;
+pub use comments::{declaration_comments, DeclarationComments};
+pub use compiler::{CompileReport, CompilerDiagnostic, GrenCompiler};
 pub use compiler_diagnostics::{
-    compiler_diagnostics_to_lsp, group_diagnostics_by_uri, merge_diagnostics,
+    compiler_diagnostics_to_lsp, compiler_hints, diagnostic_hints, group_diagnostics_by_uri,
+    merge_diagnostics, missing_patterns, CompilerHint, MISSING_PATTERNS,
 };
-pub use diagnostics::parse_errors_to_diagnostics;
+pub use compiler_options::CompilerOptions;
+pub use declaration_check::DeclarationCheck;
+pub use declaration_order::{DeclarationBlock, MoveDirection};
+pub use diagnostics::{parse_errors_to_diagnostics, CachedDiagnostics, DiagnosticsCache};
 pub use document::Document;
+pub use formatter::Formatter;
+pub use gren_analysis::parser;
+pub use import_conflicts::{ConflictResolution, Import, ImportConflict};
+pub use imports::{organize_imports, ImportGroup, ImportSection, ImportStyle};
+pub use index_freshness::IndexGenerations;
+pub use lints::LintProfile;
+pub use metrics::{MetricThresholds, ModuleMetrics};
+pub use module_graph::{ModuleExports, ModuleGraph, ModuleHeader};
+pub use package_resolver::PackageSource;
 pub use parser::{ParseError, Parser};
+pub use popular_symbols::PopularSymbols;
+pub use project_config::{LintCode, LintLevel, ProjectConfig, PROJECT_CONFIG_FILE};
+pub use project_graph::{Project, ProjectGraph};
+pub use reference_counts::ReferenceCounts;
+pub use runtime_log::RuntimeLog;
+pub use scope_analysis::{extract_references, ReferenceKind, SymbolReference};
+pub use shadowing::{shadowed_bindings, ShadowedBinding};
+pub use source_file::{ScanSummary, SkipReason, SkippedFile};
+pub use string_literals::StringLiteral;
 pub use symbol::{Symbol, SymbolExtractor, SymbolIndex};
-pub use workspace::{Workspace, WorkspaceStats};
+pub use symbol_query::{FileFilter, ModuleFilter, SymbolQuery};
+pub use symbol_store::{InMemorySymbolStore, IndexStats, SymbolStore, SymbolStoreBackend};
+#[cfg(feature = "tantivy")]
+pub use tantivy_store::TantivySymbolStore;
+pub use todos::{TodoItem, TodoMarker};
+pub use type_inference::{infer_types, InferredBinding, ModuleTypes, TypeEnvironment};
+pub use type_usage::TypeUsage;
+pub use uri::{DocumentKind, UriNormalizer};
+pub use workspace::{CompiledDocument, DocumentCompile, Workspace, WorkspaceStats};
@@ -0,0 +1,432 @@
+//! Lints the compiler doesn't report: imports nothing in the module uses, `let` bindings
+//! nothing uses, exposed values no other module uses, and exposed values without a type
+//! annotation
+//!
+//! They are hints by default. Teams whose CI rejects them can switch to the strict
+//! profile, which reports them as errors, and run `gren-lsp check` with the same profile
+//! in CI so the editor and the build agree on what's an error. Shadowing is an error in
+//! either profile, as the compiler rejects it too.
+
+use crate::syntax::{child_of_kind, node_range, text_range};
+use crate::ModuleHeader;
+use gren_analysis::scope::unused_let_bindings;
+use lsp_types::*;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+use tree_sitter::{Node, Tree};
+
+/// Diagnostic code attached to unused imports
+pub const UNUSED_IMPORT: &str = "unused-import";
+
+/// Diagnostic code attached to `let` bindings nothing uses
+pub const UNUSED_BINDING: &str = "unused-binding";
+
+/// Diagnostic code attached to exposed values no other module uses
+pub const UNUSED_EXPOSED: &str = "unused-exposed";
+
+/// Diagnostic code attached to exposed values without a type annotation
+pub const MISSING_ANNOTATION: &str = "missing-annotation";
+
+/// How strictly lints are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LintProfile {
+    /// Lints are hints
+    #[default]
+    Default,
+    /// Lints are errors
+    Strict,
+}
+
+impl LintProfile {
+    /// Severity lint diagnostics are reported with
+    pub fn severity(self) -> DiagnosticSeverity {
+        match self {
+            LintProfile::Default => DiagnosticSeverity::HINT,
+            LintProfile::Strict => DiagnosticSeverity::ERROR,
+        }
+    }
+}
+
+impl FromStr for LintProfile {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "default" => Ok(LintProfile::Default),
+            "strict" => Ok(LintProfile::Strict),
+            other => Err(format!(
+                "unknown lint profile `{}`, expected `default` or `strict`",
+                other
+            )),
+        }
+    }
+}
+
+/// Report imports whose module is never used through its qualifier and whose exposed
+/// names are never used either. Imports exposing everything with `(..)`, or a type's
+/// constructors, count as used, as which names they bring in can't be told from this
+/// file alone.
+pub fn unused_import_diagnostics(
+    tree: &Tree,
+    source: &str,
+    profile: LintProfile,
+) -> Vec<Diagnostic> {
+    let root = tree.root_node();
+    let mut qualifiers = HashSet::new();
+    let mut names = HashSet::new();
+    collect_references(root, source, &mut qualifiers, &mut names);
+
+    let mut diagnostics = Vec::new();
+    let mut cursor = root.walk();
+    for import in root
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "import_clause")
+    {
+        let module_name =
+            match child_of_kind(&import, "upper_case_qid").and_then(|name| text(name, source)) {
+                Some(module_name) => module_name,
+                None => continue,
+            };
+        let qualifier = child_of_kind(&import, "as_clause")
+            .and_then(|alias| child_of_kind(&alias, "upper_case_identifier"))
+            .and_then(|alias| text(alias, source))
+            .unwrap_or(module_name);
+
+        let exposed_used = child_of_kind(&import, "exposing_list")
+            .is_some_and(|list| exposing_list_used(&list, source, &names));
+        if qualifiers.contains(qualifier) || exposed_used {
+            continue;
+        }
+
+        let range = node_range(&import, source);
+        let lines = Range::new(
+            Position::new(range.start.line, 0),
+            Position::new(range.end.line + 1, 0),
+        );
+        diagnostics.push(Diagnostic {
+            range,
+            severity: Some(profile.severity()),
+            code: Some(NumberOrString::String(UNUSED_IMPORT.to_string())),
+            code_description: None,
+            source: Some("gren-lsp".to_string()),
+            message: format!("`{}` is imported but never used", module_name),
+            related_information: None,
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            data: removal_data(lines),
+        });
+    }
+    diagnostics
+}
+
+/// Report `let` bindings nothing uses
+pub fn unused_binding_diagnostics(
+    tree: &Tree,
+    source: &str,
+    profile: LintProfile,
+) -> Vec<Diagnostic> {
+    unused_let_bindings(tree, source)
+        .into_iter()
+        .map(|binding| Diagnostic {
+            range: text_range(binding.binding_range, source),
+            severity: Some(profile.severity()),
+            code: Some(NumberOrString::String(UNUSED_BINDING.to_string())),
+            code_description: None,
+            source: Some("gren-lsp".to_string()),
+            message: format!("`{}` is defined but never used", binding.name),
+            related_information: None,
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            data: removal_data(text_range(binding.removal_range, source)),
+        })
+        .collect()
+}
+
+/// Report the values in the module's exposing list that are in `unused`, the ones no other
+/// module uses
+pub fn unused_exposed_diagnostics(
+    tree: &Tree,
+    source: &str,
+    profile: LintProfile,
+    unused: &HashSet<String>,
+) -> Vec<Diagnostic> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let list = match root
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "module_declaration")
+        .and_then(|declaration| child_of_kind(&declaration, "exposing_list"))
+    {
+        Some(list) => list,
+        None => return Vec::new(),
+    };
+
+    let mut list_cursor = list.walk();
+    let diagnostics = list
+        .named_children(&mut list_cursor)
+        .filter(|item| item.kind() == "exposed_value")
+        .filter_map(|item| {
+            let value = text(item, source).filter(|value| unused.contains(*value))?;
+            Some(Diagnostic {
+                range: node_range(&item, source),
+                severity: Some(profile.severity()),
+                code: Some(NumberOrString::String(UNUSED_EXPOSED.to_string())),
+                code_description: None,
+                source: Some("gren-lsp".to_string()),
+                message: format!("`{}` is exposed but no other module uses it", value),
+                related_information: None,
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: None,
+            })
+        })
+        .collect();
+    diagnostics
+}
+
+/// The text a quick fix deletes to remove what a diagnostic reports
+pub fn removal_range(diagnostic: &Diagnostic) -> Option<Range> {
+    let removal = diagnostic.data.as_ref()?.get("removal")?;
+    serde_json::from_value(removal.clone()).ok()
+}
+
+fn removal_data(range: Range) -> Option<serde_json::Value> {
+    Some(serde_json::json!({ "removal": range }))
+}
+
+/// Report top-level values the module exposes that have no type annotation
+pub fn missing_annotation_diagnostics(
+    tree: &Tree,
+    source: &str,
+    profile: LintProfile,
+) -> Vec<Diagnostic> {
+    let exposed_values = ModuleHeader::from_tree(tree, source).exposed_values;
+    let is_exposed = |name: &str| match &exposed_values {
+        Some(values) => values.iter().any(|value| value == name),
+        None => true,
+    };
+
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declarations: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|node| !matches!(node.kind(), "line_comment" | "block_comment"))
+        .collect();
+
+    let mut diagnostics = Vec::new();
+    for (index, declaration) in declarations.iter().enumerate() {
+        if declaration.kind() != "value_declaration" {
+            continue;
+        }
+        let name = match child_of_kind(declaration, "function_declaration_left")
+            .and_then(|left| child_of_kind(&left, "lower_case_identifier"))
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        let value = match text(name, source) {
+            Some(value) if is_exposed(value) => value,
+            _ => continue,
+        };
+
+        let annotated = index
+            .checked_sub(1)
+            .and_then(|previous| declarations.get(previous))
+            .filter(|previous| previous.kind() == "type_annotation")
+            .and_then(|annotation| child_of_kind(annotation, "lower_case_identifier"))
+            .and_then(|annotated| text(annotated, source))
+            == Some(value);
+        if annotated {
+            continue;
+        }
+
+        diagnostics.push(Diagnostic {
+            range: node_range(&name, source),
+            severity: Some(profile.severity()),
+            code: Some(NumberOrString::String(MISSING_ANNOTATION.to_string())),
+            code_description: None,
+            source: Some("gren-lsp".to_string()),
+            message: format!("`{}` is exposed but has no type annotation", value),
+            related_information: None,
+            tags: None,
+            data: None,
+        });
+    }
+    diagnostics
+}
+
+/// Whether any name an exposing list brings in is used, counting lists that can't be
+/// checked as used
+fn exposing_list_used(list: &Node, source: &str, names: &HashSet<&str>) -> bool {
+    if child_of_kind(list, "double_dot").is_some() {
+        return true;
+    }
+
+    let mut cursor = list.walk();
+    let used = list
+        .named_children(&mut cursor)
+        .any(|item| match item.kind() {
+            "exposed_value" => text(item, source).is_some_and(|name| names.contains(name)),
+            // `Type(..)` brings in constructors that aren't listed
+            "exposed_type" if item.named_child_count() > 1 => true,
+            "exposed_type" => child_of_kind(&item, "upper_case_identifier")
+                .and_then(|name| text(name, source))
+                .is_some_and(|name| names.contains(name)),
+            _ => true,
+        });
+    used
+}
+
+/// The unqualified names referenced outside the module declaration and imports
+pub fn used_names<'a>(tree: &Tree, source: &'a str) -> HashSet<&'a str> {
+    let mut qualifiers = HashSet::new();
+    let mut names = HashSet::new();
+    collect_references(tree.root_node(), source, &mut qualifiers, &mut names);
+    names
+}
+
+/// Collect the module qualifiers and unqualified names referenced outside the module
+/// declaration and imports
+fn collect_references<'a>(
+    node: Node,
+    source: &'a str,
+    qualifiers: &mut HashSet<&'a str>,
+    names: &mut HashSet<&'a str>,
+) {
+    match node.kind() {
+        "module_declaration" | "import_clause" => return,
+        "value_qid" | "upper_case_qid" => {
+            if let Some(reference) = text(node, source) {
+                match reference.rsplit_once('.') {
+                    Some((qualifier, _)) => {
+                        qualifiers.insert(qualifier);
+                    }
+                    None => {
+                        names.insert(reference);
+                    }
+                }
+            }
+            return;
+        }
+        "lower_case_identifier" | "upper_case_identifier" => {
+            if let Some(name) = text(node, source) {
+                names.insert(name);
+            }
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_references(child, source, qualifiers, names);
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn parse(source: &str) -> Tree {
+        let mut parser = Parser::new().unwrap();
+        parser.parse(source).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_unused_imports() {
+        let source = r#"module Main exposing (main)
+
+import Array
+import Dict as D
+import Json.Decode exposing (Decoder, field)
+import Maybe exposing (Maybe(..))
+import String exposing (toUpper)
+
+main : Decoder Int
+main =
+    D.empty
+        |> Array.length
+"#;
+        let tree = parse(source);
+        let diagnostics = unused_import_diagnostics(&tree, source, LintProfile::Default);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec!["`String` is imported but never used"]);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diagnostics[0].range.start, Position::new(6, 0));
+        assert_eq!(
+            removal_range(&diagnostics[0]),
+            Some(Range::new(Position::new(6, 0), Position::new(7, 0)))
+        );
+    }
+
+    #[test]
+    fn test_unused_bindings_and_exposed_values() {
+        let source = r#"module Main exposing (main, view, helper)
+
+main =
+    let
+        unused =
+            1
+    in
+    view 2
+
+view model =
+    model
+
+helper x =
+    x
+"#;
+        let tree = parse(source);
+        let bindings = unused_binding_diagnostics(&tree, source, LintProfile::Default);
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].message, "`unused` is defined but never used");
+        assert_eq!(bindings[0].tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+        assert_eq!(
+            removal_range(&bindings[0]),
+            Some(Range::new(Position::new(3, 4), Position::new(7, 4)))
+        );
+
+        let unused = HashSet::from(["helper".to_string()]);
+        let exposed = unused_exposed_diagnostics(&tree, source, LintProfile::Default, &unused);
+        assert_eq!(exposed.len(), 1);
+        assert_eq!(
+            exposed[0].message,
+            "`helper` is exposed but no other module uses it"
+        );
+        assert_eq!(exposed[0].range.start, Position::new(0, 34));
+    }
+
+    #[test]
+    fn test_missing_annotations_on_exposed_values() {
+        let source = r#"module Main exposing (main, view)
+
+main : Int
+main =
+    1
+
+view model =
+    model
+
+helper x =
+    x
+"#;
+        let tree = parse(source);
+        let diagnostics = missing_annotation_diagnostics(&tree, source, LintProfile::Strict);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "`view` is exposed but has no type annotation"
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn test_profile_names() {
+        assert_eq!("strict".parse(), Ok(LintProfile::Strict));
+        assert_eq!("default".parse(), Ok(LintProfile::Default));
+        assert!("pedantic".parse::<LintProfile>().is_err());
+    }
+}
@@ -0,0 +1,563 @@
+//! Adding and removing dependencies in `gren.json`
+//!
+//! Edits are made to the text rather than by re-serializing the parsed manifest, so key
+//! order, indentation and everything outside the changed entry stay as the user wrote them.
+//! Applications list exact versions under `dependencies.direct` and
+//! `dependencies.indirect`, packages list version ranges directly under `dependencies`.
+
+use crate::compiler_options::CompilerOptions;
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command as AsyncCommand;
+use tracing::info;
+
+/// Indentation step used when the manifest has no indented line to copy it from
+const DEFAULT_INDENT: &str = "    ";
+
+/// Add `package` to the project's dependencies, or change the version it's listed with.
+/// `version` is an exact `1.2.3` version, or for packages also a `1.0.0 <= v < 2.0.0`
+/// range; an exact version in a package becomes the range up to the next major version.
+/// An application's indirect dependency becomes a direct one.
+pub fn add_dependency(manifest: &str, package: &str, version: &str) -> Result<String> {
+    check_package_name(package)?;
+    let version = version.trim();
+
+    if is_package(manifest)? {
+        let constraint = if is_version(version) {
+            let major: u64 = version.split('.').next().unwrap_or_default().parse()?;
+            format!("{} <= v < {}.0.0", version, major + 1)
+        } else if is_constraint(version) {
+            version.to_string()
+        } else {
+            bail!("`{}` isn't a version or a version range", version);
+        };
+        return set_member(manifest, &["dependencies"], package, &constraint);
+    }
+
+    if !is_version(version) {
+        bail!(
+            "Applications depend on exact versions like 1.2.3, not `{}`",
+            version
+        );
+    }
+    let manifest = remove_member(manifest, &["dependencies", "indirect"], package)?
+        .unwrap_or_else(|| manifest.to_string());
+    set_member(&manifest, &["dependencies", "direct"], package, version)
+}
+
+/// Remove `package` from the project's direct dependencies. Fails when it isn't one.
+pub fn remove_dependency(manifest: &str, package: &str) -> Result<String> {
+    check_package_name(package)?;
+    let path: &[&str] = if is_package(manifest)? {
+        &["dependencies"]
+    } else {
+        &["dependencies", "direct"]
+    };
+    remove_member(manifest, path, package)?
+        .ok_or_else(|| anyhow!("`{}` isn't a direct dependency", package))
+}
+
+/// Run `gren package install` in the project directory, so the compiler resolves and
+/// downloads what `gren.json` now lists, in the environment compiler runs get. Returns the
+/// compiler's error output on failure.
+pub async fn fetch_packages(
+    compiler: &Path,
+    project_dir: &Path,
+    options: &CompilerOptions,
+) -> Result<()> {
+    info!("📦 Fetching packages in {}", project_dir.display());
+    let mut command = AsyncCommand::new(compiler);
+    options.apply_env(&mut command);
+    let output = command
+        .arg("package")
+        .arg("install")
+        .current_dir(project_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let message = if stderr.trim().is_empty() {
+            stdout
+        } else {
+            stderr
+        };
+        bail!("`gren package install` failed: {}", message.trim())
+    }
+}
+
+/// Packages the project depends on directly, as `author/name`
+pub fn direct_dependencies(manifest: &str) -> Result<Vec<String>> {
+    let json: serde_json::Value =
+        serde_json::from_str(manifest).map_err(|e| anyhow!("gren.json isn't valid JSON: {}", e))?;
+    let dependencies = json.get("dependencies");
+    let direct = if json.get("type").and_then(|value| value.as_str()) == Some("package") {
+        dependencies
+    } else {
+        dependencies.and_then(|dependencies| dependencies.get("direct"))
+    };
+    Ok(direct
+        .and_then(|direct| direct.as_object())
+        .map(|direct| direct.keys().cloned().collect())
+        .unwrap_or_default())
+}
+
+/// Every package the manifest lists with its version: an application's direct and
+/// indirect dependencies with exact versions, a package's dependencies with ranges
+pub fn dependency_versions(manifest: &str) -> Result<Vec<(String, String)>> {
+    let json: serde_json::Value =
+        serde_json::from_str(manifest).map_err(|e| anyhow!("gren.json isn't valid JSON: {}", e))?;
+    let dependencies = json.get("dependencies");
+    let groups = if json.get("type").and_then(|value| value.as_str()) == Some("package") {
+        vec![dependencies]
+    } else {
+        vec![
+            dependencies.and_then(|dependencies| dependencies.get("direct")),
+            dependencies.and_then(|dependencies| dependencies.get("indirect")),
+        ]
+    };
+    Ok(groups
+        .into_iter()
+        .flatten()
+        .filter_map(|group| group.as_object())
+        .flatten()
+        .filter_map(|(package, version)| Some((package.clone(), version.as_str()?.to_string())))
+        .collect())
+}
+
+/// Byte ranges of the entries naming `module` in a package's `exposed-modules`, without
+/// their quotes. The list may be grouped under headings, which are left alone.
+pub fn exposed_module_entries(manifest: &str, module: &str) -> Vec<std::ops::Range<usize>> {
+    let bytes = manifest.as_bytes();
+    let exposed = match object_at(manifest, &[]).and_then(|root| {
+        root.members
+            .into_iter()
+            .find(|member| member.key == "exposed-modules")
+    }) {
+        Some(exposed) => exposed,
+        None => return Vec::new(),
+    };
+
+    let mut entries = Vec::new();
+    let mut index = exposed.value_start;
+    while index < exposed.value_end {
+        if bytes[index] != b'"' {
+            index += 1;
+            continue;
+        }
+        let (contents, end) = match parse_string(bytes, index) {
+            Some(string) => string,
+            None => break,
+        };
+        let is_heading = bytes.get(skip_whitespace(bytes, end)) == Some(&b':');
+        if !is_heading && contents == module {
+            entries.push(index + 1..end - 1);
+        }
+        index = end;
+    }
+    entries
+}
+
+fn is_package(manifest: &str) -> Result<bool> {
+    let json: serde_json::Value =
+        serde_json::from_str(manifest).map_err(|e| anyhow!("gren.json isn't valid JSON: {}", e))?;
+    Ok(json.get("type").and_then(|value| value.as_str()) == Some("package"))
+}
+
+fn check_package_name(package: &str) -> Result<()> {
+    let valid = package.split_once('/').is_some_and(|(author, name)| {
+        !author.is_empty() && !name.is_empty() && !name.contains('/')
+    });
+    if valid && !package.contains(['"', '\\']) {
+        Ok(())
+    } else {
+        bail!("`{}` isn't a package name like author/name", package)
+    }
+}
+
+/// Whether a string is an exact `major.minor.patch` version
+pub fn is_version(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('.').collect();
+    parts.len() == 3
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Whether a string is a `1.0.0 <= v < 2.0.0` range
+fn is_constraint(value: &str) -> bool {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    matches!(
+        parts.as_slice(),
+        [lower, "<=" | "<", "v", "<=" | "<", upper] if is_version(lower) && is_version(upper)
+    )
+}
+
+/// Set `key` to a string `value` in the object at `path`, keeping entries sorted by key
+fn set_member(manifest: &str, path: &[&str], key: &str, value: &str) -> Result<String> {
+    let object = object_at(manifest, path)
+        .ok_or_else(|| anyhow!("gren.json has no `{}` object", path.join(".")))?;
+    let entry = format!("\"{}\": \"{}\"", key, value);
+    let mut result = manifest.to_string();
+
+    if let Some(member) = object.members.iter().find(|member| member.key == key) {
+        result.replace_range(
+            member.value_start..member.value_end,
+            &format!("\"{}\"", value),
+        );
+        return Ok(result);
+    }
+
+    let multiline = manifest[object.open + 1..object.close].contains('\n');
+    let line_indent = line_indentation(manifest, object.open);
+
+    match object.members.first() {
+        None => {
+            let replacement = format!(
+                "\n{}{}{}\n{}",
+                line_indent,
+                indent_step(manifest),
+                entry,
+                line_indent
+            );
+            result.replace_range(object.open + 1..object.close, &replacement);
+        }
+        Some(first) => {
+            let separator = if multiline {
+                format!(",\n{}", line_indentation(manifest, first.key_start))
+            } else {
+                ", ".to_string()
+            };
+            match object
+                .members
+                .iter()
+                .find(|member| member.key.as_str() > key)
+            {
+                Some(next) => result.insert_str(next.key_start, &format!("{}{}", entry, separator)),
+                None => {
+                    let last = object.members.last().unwrap_or(first);
+                    result.insert_str(last.value_end, &format!("{}{}", separator, entry));
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Remove `key` from the object at `path`, or `None` when the object doesn't have it
+fn remove_member(manifest: &str, path: &[&str], key: &str) -> Result<Option<String>> {
+    let object = object_at(manifest, path)
+        .ok_or_else(|| anyhow!("gren.json has no `{}` object", path.join(".")))?;
+    let index = match object.members.iter().position(|member| member.key == key) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+
+    let member = &object.members[index];
+    let removed = match (index.checked_sub(1), object.members.get(index + 1)) {
+        // Take the entry and everything up to the next key, so the next entry keeps the
+        // removed one's place and indentation
+        (_, Some(next)) => member.key_start..next.key_start,
+        (Some(previous), None) => object.members[previous].value_end..member.value_end,
+        (None, None) => object.open + 1..object.close,
+    };
+    let mut result = manifest.to_string();
+    result.replace_range(removed, "");
+    Ok(Some(result))
+}
+
+/// The manifest's indentation step, taken from its first indented line
+fn indent_step(text: &str) -> &str {
+    text.lines()
+        .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+        .find(|indentation| !indentation.is_empty())
+        .unwrap_or(DEFAULT_INDENT)
+}
+
+/// Leading whitespace of the line containing `offset`
+fn line_indentation(text: &str, offset: usize) -> &str {
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line = &text[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// An object in the manifest, by byte offsets
+#[derive(Debug)]
+struct ObjectSpan {
+    open: usize,
+    close: usize,
+    members: Vec<MemberSpan>,
+}
+
+#[derive(Debug)]
+struct MemberSpan {
+    key: String,
+    key_start: usize,
+    value_start: usize,
+    value_end: usize,
+}
+
+/// The object reached by following `path` from the top-level object
+fn object_at(text: &str, path: &[&str]) -> Option<ObjectSpan> {
+    let bytes = text.as_bytes();
+    let mut object = parse_object(bytes, skip_whitespace(bytes, 0))?;
+    for key in path {
+        let member = object.members.iter().find(|member| member.key == *key)?;
+        object = parse_object(bytes, member.value_start)?;
+    }
+    Some(object)
+}
+
+fn parse_object(bytes: &[u8], open: usize) -> Option<ObjectSpan> {
+    if bytes.get(open) != Some(&b'{') {
+        return None;
+    }
+
+    let mut members = Vec::new();
+    let mut index = skip_whitespace(bytes, open + 1);
+    if bytes.get(index) == Some(&b'}') {
+        return Some(ObjectSpan {
+            open,
+            close: index,
+            members,
+        });
+    }
+
+    loop {
+        let key_start = index;
+        let (key, key_end) = parse_string(bytes, key_start)?;
+        index = skip_whitespace(bytes, key_end);
+        if bytes.get(index) != Some(&b':') {
+            return None;
+        }
+        let value_start = skip_whitespace(bytes, index + 1);
+        let value_end = skip_value(bytes, value_start)?;
+        members.push(MemberSpan {
+            key,
+            key_start,
+            value_start,
+            value_end,
+        });
+
+        index = skip_whitespace(bytes, value_end);
+        match bytes.get(index)? {
+            b',' => index = skip_whitespace(bytes, index + 1),
+            b'}' => {
+                return Some(ObjectSpan {
+                    open,
+                    close: index,
+                    members,
+                })
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// A string starting at `start`, returning its contents and the offset after its closing
+/// quote. Escapes are kept as written, manifest keys don't need them.
+fn parse_string(bytes: &[u8], start: usize) -> Option<(String, usize)> {
+    if bytes.get(start) != Some(&b'"') {
+        return None;
+    }
+    let mut index = start + 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            b'"' => {
+                let contents = std::str::from_utf8(&bytes[start + 1..index]).ok()?;
+                return Some((contents.to_string(), index + 1));
+            }
+            _ => index += 1,
+        }
+    }
+    None
+}
+
+/// The offset just after the value starting at `start`
+fn skip_value(bytes: &[u8], start: usize) -> Option<usize> {
+    match bytes.get(start)? {
+        b'"' => parse_string(bytes, start).map(|(_, end)| end),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut index = start;
+            while index < bytes.len() {
+                match bytes[index] {
+                    b'"' => {
+                        index = parse_string(bytes, index)?.1;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(index + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                index += 1;
+            }
+            None
+        }
+        _ => {
+            let length = bytes[start..]
+                .iter()
+                .position(|byte| matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace())
+                .unwrap_or(bytes.len() - start);
+            Some(start + length)
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut index: usize) -> usize {
+    while bytes
+        .get(index)
+        .is_some_and(|byte| byte.is_ascii_whitespace())
+    {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APPLICATION: &str = r#"{
+    "type": "application",
+    "source-directories": [
+        "src"
+    ],
+    "gren-version": "0.5.0",
+    "dependencies": {
+        "direct": {
+            "gren-lang/browser": "5.0.0",
+            "gren-lang/core": "6.0.0"
+        },
+        "indirect": {
+            "gren-lang/url": "5.0.0"
+        }
+    }
+}
+"#;
+
+    #[test]
+    fn test_dependency_versions() {
+        assert_eq!(
+            dependency_versions(APPLICATION).unwrap(),
+            vec![
+                ("gren-lang/browser".to_string(), "5.0.0".to_string()),
+                ("gren-lang/core".to_string(), "6.0.0".to_string()),
+                ("gren-lang/url".to_string(), "5.0.0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_keeps_order_and_formatting() {
+        let added = add_dependency(APPLICATION, "gren-lang/node", "4.0.0").unwrap();
+        assert_eq!(
+            added,
+            APPLICATION.replace(
+                "\"gren-lang/core\": \"6.0.0\"\n",
+                "\"gren-lang/core\": \"6.0.0\",\n            \"gren-lang/node\": \"4.0.0\"\n"
+            )
+        );
+
+        let first = add_dependency(APPLICATION, "elm/json", "1.0.0").unwrap();
+        assert!(first.contains(
+            "\"direct\": {\n            \"elm/json\": \"1.0.0\",\n            \"gren-lang/browser\""
+        ));
+    }
+
+    #[test]
+    fn test_add_promotes_indirect_and_updates_existing() {
+        let promoted = add_dependency(APPLICATION, "gren-lang/url", "5.0.0").unwrap();
+        assert!(promoted.contains("\"indirect\": {}"));
+        assert!(promoted.contains(
+            "\"gren-lang/core\": \"6.0.0\",\n            \"gren-lang/url\": \"5.0.0\"\n"
+        ));
+
+        let updated = add_dependency(APPLICATION, "gren-lang/core", "6.1.0").unwrap();
+        assert_eq!(updated, APPLICATION.replace("6.0.0", "6.1.0"));
+
+        assert!(add_dependency(APPLICATION, "gren-lang/core", "6.0.0 <= v < 7.0.0").is_err());
+        assert!(add_dependency(APPLICATION, "core", "6.0.0").is_err());
+    }
+
+    #[test]
+    fn test_remove_direct_dependency() {
+        let removed = remove_dependency(APPLICATION, "gren-lang/core").unwrap();
+        assert!(removed
+            .contains("\"direct\": {\n            \"gren-lang/browser\": \"5.0.0\"\n        },"));
+
+        let removed = remove_dependency(APPLICATION, "gren-lang/browser").unwrap();
+        assert!(removed
+            .contains("\"direct\": {\n            \"gren-lang/core\": \"6.0.0\"\n        },"));
+
+        assert!(remove_dependency(APPLICATION, "gren-lang/url").is_err());
+    }
+
+    #[test]
+    fn test_direct_dependencies() {
+        assert_eq!(
+            direct_dependencies(APPLICATION).unwrap(),
+            vec!["gren-lang/browser", "gren-lang/core"]
+        );
+        let package =
+            r#"{ "type": "package", "dependencies": { "gren-lang/core": "6.0.0 <= v < 7.0.0" } }"#;
+        assert_eq!(
+            direct_dependencies(package).unwrap(),
+            vec!["gren-lang/core"]
+        );
+        assert!(direct_dependencies("{").is_err());
+    }
+
+    #[test]
+    fn test_exposed_module_entries() {
+        let listed = r#"{ "type": "package", "exposed-modules": ["Shapes", "Shapes.Circle"] }"#;
+        let entries = exposed_module_entries(listed, "Shapes");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(&listed[entries[0].clone()], "Shapes");
+        assert_eq!(entries[0].start, 42);
+
+        let grouped = r#"{
+    "type": "package",
+    "exposed-modules": {
+        "Shapes": ["Shapes", "Shapes.Circle"],
+        "Other": ["Shapes.Circle"]
+    }
+}"#;
+        let entries = exposed_module_entries(grouped, "Shapes.Circle");
+        assert_eq!(entries.len(), 2);
+        assert!(entries
+            .iter()
+            .all(|entry| &grouped[entry.clone()] == "Shapes.Circle"));
+        // The `Shapes` heading isn't a module
+        assert_eq!(exposed_module_entries(grouped, "Shapes").len(), 1);
+        assert!(exposed_module_entries(APPLICATION, "Main").is_empty());
+    }
+
+    #[test]
+    fn test_package_dependencies_are_ranges() {
+        let package = "{\n  \"type\": \"package\",\n  \"dependencies\": {}\n}\n";
+        let added = add_dependency(package, "gren-lang/core", "6.1.0").unwrap();
+        assert_eq!(
+            added,
+            "{\n  \"type\": \"package\",\n  \"dependencies\": {\n    \"gren-lang/core\": \"6.1.0 <= v < 7.0.0\"\n  }\n}\n"
+        );
+        assert_eq!(
+            remove_dependency(&added, "gren-lang/core").unwrap(),
+            package
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use crate::module_graph::ModuleHeader;
+use crate::syntax::{child_of_kind, node_range};
+use lsp_types::*;
+use std::collections::BTreeSet;
+use tree_sitter::{Node, Tree};
+
+/// Diagnostic code attached to hints for functions longer than the configured limit
+pub const FUNCTION_TOO_LONG: &str = "function-length";
+
+/// Diagnostic code attached to hints for `when` expressions nested deeper than the limit
+pub const CASE_NESTING_TOO_DEEP: &str = "case-nesting";
+
+/// Limits above which metric hints are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricThresholds {
+    pub max_function_lines: usize,
+    pub max_case_depth: usize,
+}
+
+/// Size and shape of a top-level function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionMetrics {
+    pub name: String,
+    /// Range of the function's name in its definition
+    pub name_range: Range,
+    /// Lines spanned by the definition, not counting its type annotation
+    pub lines: usize,
+    /// Deepest nesting of `when ... is` expressions in the body
+    pub case_depth: usize,
+}
+
+/// Complexity metrics for a single module
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModuleMetrics {
+    pub module_name: Option<String>,
+    /// Lines containing code, excluding blank lines and lines holding only comments
+    pub lines_of_code: usize,
+    pub exported_symbols: usize,
+    pub functions: Vec<FunctionMetrics>,
+    /// Modules this module imports, sorted and without duplicates
+    pub imports: Vec<String>,
+    /// Number of indexed files importing this module, filled in by the workspace
+    pub fan_in: usize,
+}
+
+impl ModuleMetrics {
+    /// The longest top-level function, if there are any
+    pub fn longest_function(&self) -> Option<&FunctionMetrics> {
+        self.functions.iter().max_by_key(|function| function.lines)
+    }
+
+    pub fn max_case_depth(&self) -> usize {
+        self.functions
+            .iter()
+            .map(|function| function.case_depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Number of distinct modules this module imports
+    pub fn fan_out(&self) -> usize {
+        self.imports.len()
+    }
+}
+
+/// Measure a module from its syntax tree. Declarations the parser couldn't read are left
+/// out of the per-function metrics rather than measured from a guess.
+pub fn module_metrics(tree: &Tree, source: &str) -> ModuleMetrics {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declarations: Vec<Node> = root.named_children(&mut cursor).collect();
+
+    let header = ModuleHeader::from_tree(tree, source);
+
+    ModuleMetrics {
+        module_name: header.name,
+        lines_of_code: count_code_lines(root),
+        exported_symbols: count_exported_symbols(&declarations),
+        functions: declarations
+            .iter()
+            .filter(|node| node.kind() == "value_declaration")
+            .filter_map(|node| function_metrics(node, source))
+            .collect(),
+        imports: header.imports,
+        fan_in: 0,
+    }
+}
+
+/// Hints for functions that go over the configured limits
+pub fn metric_diagnostics(
+    metrics: &ModuleMetrics,
+    thresholds: MetricThresholds,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for function in &metrics.functions {
+        if function.lines > thresholds.max_function_lines {
+            diagnostics.push(metric_hint(
+                function,
+                FUNCTION_TOO_LONG,
+                format!(
+                    "`{}` is {} lines long, more than the configured limit of {}",
+                    function.name, function.lines, thresholds.max_function_lines
+                ),
+            ));
+        }
+
+        if function.case_depth > thresholds.max_case_depth {
+            diagnostics.push(metric_hint(
+                function,
+                CASE_NESTING_TOO_DEEP,
+                format!(
+                    "`{}` nests `when` expressions {} deep, more than the configured limit of {}",
+                    function.name, function.case_depth, thresholds.max_case_depth
+                ),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+fn metric_hint(function: &FunctionMetrics, code: &str, message: String) -> Diagnostic {
+    Diagnostic {
+        range: function.name_range,
+        severity: Some(DiagnosticSeverity::HINT),
+        code: Some(NumberOrString::String(code.to_string())),
+        code_description: None,
+        source: Some("gren-lsp".to_string()),
+        message,
+        related_information: None,
+        tags: None,
+        data: None,
+    }
+}
+
+fn function_metrics(declaration: &Node, source: &str) -> Option<FunctionMetrics> {
+    if declaration.has_error() {
+        return None;
+    }
+
+    let left = child_of_kind(declaration, "function_declaration_left")?;
+    let name = child_of_kind(&left, "lower_case_identifier")?;
+
+    Some(FunctionMetrics {
+        name: name.utf8_text(source.as_bytes()).ok()?.to_string(),
+        name_range: node_range(&name, source),
+        lines: declaration.end_position().row - declaration.start_position().row + 1,
+        case_depth: case_depth(*declaration),
+    })
+}
+
+/// Deepest chain of `when` expressions nested inside one another under `node`
+fn case_depth(node: Node) -> usize {
+    let mut cursor = node.walk();
+    let deepest_child = node
+        .named_children(&mut cursor)
+        .map(case_depth)
+        .max()
+        .unwrap_or(0);
+
+    if node.kind() == "case_of_expr" {
+        deepest_child + 1
+    } else {
+        deepest_child
+    }
+}
+
+/// Count the symbols a module exposes. `exposing (..)` exposes every top-level
+/// declaration.
+fn count_exported_symbols(declarations: &[Node]) -> usize {
+    let exposing = declarations
+        .iter()
+        .find(|node| node.kind() == "module_declaration")
+        .and_then(|module| child_of_kind(module, "exposing_list"));
+    let exposing = match exposing {
+        Some(exposing) => exposing,
+        None => return 0,
+    };
+
+    if child_of_kind(&exposing, "double_dot").is_some() {
+        return declarations
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node.kind(),
+                    "value_declaration"
+                        | "type_declaration"
+                        | "type_alias_declaration"
+                        | "port_annotation"
+                )
+            })
+            .count();
+    }
+
+    let mut cursor = exposing.walk();
+    let count = exposing
+        .named_children(&mut cursor)
+        .filter(|child| {
+            matches!(
+                child.kind(),
+                "exposed_value" | "exposed_type" | "exposed_operator"
+            )
+        })
+        .count();
+    count
+}
+
+/// Count the lines holding at least one token that isn't part of a comment
+fn count_code_lines(root: Node) -> usize {
+    let mut rows = BTreeSet::new();
+    collect_code_rows(root, &mut rows);
+    rows.len()
+}
+
+fn collect_code_rows(node: Node, rows: &mut BTreeSet<usize>) {
+    if is_comment(&node) || node.start_byte() == node.end_byte() {
+        return;
+    }
+
+    if node.child_count() == 0 {
+        rows.extend(node.start_position().row..=node.end_position().row);
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_code_rows(child, rows);
+    }
+}
+
+fn is_comment(node: &Node) -> bool {
+    matches!(node.kind(), "line_comment" | "block_comment")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    const SOURCE: &str = r#"module Shapes exposing (Shape, area)
+
+import Math
+import Array
+import Math
+
+-- A shape
+type Shape
+    = Circle Float
+    | Square Float
+
+
+area : Shape -> Float
+area shape =
+    when shape is
+        Circle radius ->
+            Math.pi * radius * radius
+
+        Square side ->
+            side * side
+
+
+describe : Maybe Shape -> String
+describe maybeShape =
+    when maybeShape is
+        Just shape ->
+            when shape is
+                Circle _ ->
+                    "circle"
+
+                Square _ ->
+                    "square"
+
+        Nothing ->
+            "nothing"
+"#;
+
+    fn metrics_for(source: &str) -> ModuleMetrics {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        module_metrics(&tree, source)
+    }
+
+    #[test]
+    fn test_module_metrics() {
+        let metrics = metrics_for(SOURCE);
+        assert_eq!(metrics.module_name.as_deref(), Some("Shapes"));
+        assert_eq!(metrics.exported_symbols, 2);
+        assert_eq!(metrics.imports, vec!["Array", "Math"]);
+        assert_eq!(metrics.fan_out(), 2);
+        // Blank lines and the comment aren't counted
+        assert_eq!(metrics.lines_of_code, 25);
+
+        let longest = metrics.longest_function().unwrap();
+        assert_eq!(longest.name, "describe");
+        assert_eq!(longest.lines, 12);
+        assert_eq!(metrics.max_case_depth(), 2);
+    }
+
+    #[test]
+    fn test_metric_hints_respect_thresholds() {
+        let metrics = metrics_for(SOURCE);
+
+        let relaxed = MetricThresholds {
+            max_function_lines: 20,
+            max_case_depth: 2,
+        };
+        assert!(metric_diagnostics(&metrics, relaxed).is_empty());
+
+        let strict = MetricThresholds {
+            max_function_lines: 10,
+            max_case_depth: 1,
+        };
+        let diagnostics = metric_diagnostics(&metrics, strict);
+        let codes: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.code.clone())
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                Some(NumberOrString::String(FUNCTION_TOO_LONG.to_string())),
+                Some(NumberOrString::String(CASE_NESTING_TOO_DEEP.to_string())),
+            ]
+        );
+        assert_eq!(diagnostics[0].range.start.line, 23);
+    }
+
+    #[test]
+    fn test_exposing_everything_counts_declarations() {
+        let metrics = metrics_for(
+            r#"module Main exposing (..)
+
+type alias Model = { count : Int }
+
+main = 42
+
+helper x = x
+"#,
+        );
+        assert_eq!(metrics.exported_symbols, 3);
+        assert_eq!(metrics.fan_out(), 0);
+    }
+}
@@ -0,0 +1,579 @@
+use crate::syntax::child_of_kind;
+use lsp_types::{SymbolKind, Url};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use tree_sitter::{Node, Tree};
+
+/// The module a file declares and the modules it imports
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModuleHeader {
+    pub name: Option<String>,
+    /// Imported modules, sorted and without duplicates
+    pub imports: Vec<String>,
+    /// Import clauses as written, with their whitespace collapsed, in source order
+    pub import_clauses: Vec<String>,
+    /// Values the module declaration exposes, or `None` for `exposing (..)`
+    pub exposed_values: Option<Vec<String>>,
+    /// Everything importers can refer to, with `exposing (..)` expanded
+    pub exports: ModuleExports,
+}
+
+impl ModuleHeader {
+    /// Read the module declaration and import clauses from a file's syntax tree
+    pub fn from_tree(tree: &Tree, source: &str) -> Self {
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let mut header = ModuleHeader::default();
+        let mut imports = BTreeSet::new();
+        let mut declaration = None;
+
+        for node in root.named_children(&mut cursor) {
+            let name = child_of_kind(&node, "upper_case_qid")
+                .and_then(|name| name.utf8_text(source.as_bytes()).ok());
+            match (node.kind(), name) {
+                ("module_declaration", Some(name)) => {
+                    header.name = Some(name.to_string());
+                    header.exposed_values = exposed_values(&node, source);
+                    declaration = Some(node);
+                }
+                ("import_clause", Some(name)) => {
+                    imports.insert(name.to_string());
+                    if let Ok(clause) = node.utf8_text(source.as_bytes()) {
+                        let words: Vec<&str> = clause.split_whitespace().collect();
+                        header.import_clauses.push(words.join(" "));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        header.imports = imports.into_iter().collect();
+        if let Some(declaration) = declaration {
+            header.exports = ModuleExports::from_declarations(&root, &declaration, source);
+        }
+        header
+    }
+}
+
+/// Names a module makes available to the modules importing it
+///
+/// Private functions live in the same file as exposed ones, so the file a symbol is in
+/// doesn't say whether another module may use it; this does.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModuleExports {
+    /// Top-level values
+    pub values: BTreeSet<String>,
+    /// Custom types and type aliases
+    pub types: BTreeSet<String>,
+    /// Constructors of the custom types exposed with `Type(..)`
+    pub constructors: BTreeSet<String>,
+}
+
+impl ModuleExports {
+    /// Read the exposing list of a module declaration, expanding `exposing (..)` to every
+    /// top-level declaration of the module
+    fn from_declarations(root: &Node, declaration: &Node, source: &str) -> Self {
+        let mut defined = ModuleExports::default();
+        let mut type_constructors: HashMap<&str, Vec<String>> = HashMap::new();
+        let mut cursor = root.walk();
+        for node in root.named_children(&mut cursor) {
+            match node.kind() {
+                "value_declaration" => {
+                    if let Some(name) = child_of_kind(&node, "function_declaration_left")
+                        .and_then(|left| child_of_kind(&left, "lower_case_identifier"))
+                        .and_then(|name| text(name, source))
+                    {
+                        defined.values.insert(name.to_string());
+                    }
+                }
+                "type_declaration" => {
+                    let name = match child_of_kind(&node, "upper_case_identifier")
+                        .and_then(|name| text(name, source))
+                    {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    let mut variant_cursor = node.walk();
+                    let constructors: Vec<String> = node
+                        .named_children(&mut variant_cursor)
+                        .filter(|child| child.kind() == "union_variant")
+                        .filter_map(|variant| child_of_kind(&variant, "upper_case_identifier"))
+                        .filter_map(|constructor| text(constructor, source))
+                        .map(str::to_string)
+                        .collect();
+                    defined.types.insert(name.to_string());
+                    defined.constructors.extend(constructors.iter().cloned());
+                    type_constructors.insert(name, constructors);
+                }
+                "type_alias_declaration" => {
+                    if let Some(name) = child_of_kind(&node, "upper_case_identifier")
+                        .and_then(|name| text(name, source))
+                    {
+                        defined.types.insert(name.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let list = match child_of_kind(declaration, "exposing_list") {
+            Some(list) => list,
+            None => return ModuleExports::default(),
+        };
+        if child_of_kind(&list, "double_dot").is_some() {
+            return defined;
+        }
+
+        let mut exports = ModuleExports::default();
+        let mut cursor = list.walk();
+        for item in list.named_children(&mut cursor) {
+            match item.kind() {
+                "exposed_value" | "exposed_operator" => {
+                    if let Some(name) = text(item, source) {
+                        exports.values.insert(name.to_string());
+                    }
+                }
+                "exposed_type" => {
+                    let name = match child_of_kind(&item, "upper_case_identifier")
+                        .and_then(|name| text(name, source))
+                    {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    exports.types.insert(name.to_string());
+                    // `Type(..)` exposes the constructors too
+                    if item.named_child_count() > 1 {
+                        if let Some(constructors) = type_constructors.get(name) {
+                            exports.constructors.extend(constructors.iter().cloned());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        exports
+    }
+
+    /// Whether importers can refer to the indexed symbol `name` of kind `kind`
+    pub fn exposes(&self, name: &str, kind: SymbolKind) -> bool {
+        match kind {
+            SymbolKind::MODULE => true,
+            SymbolKind::CONSTRUCTOR => self.constructors.contains(name),
+            SymbolKind::CLASS => self.types.contains(name),
+            _ => self.values.contains(name),
+        }
+    }
+}
+
+/// Which indexed file imports which module
+///
+/// Kept up to date as files are indexed, like the symbol index, so it also covers files
+/// that have since been evicted from the document cache. Answers "who imports this
+/// module" without re-parsing every file in the workspace.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    files: HashMap<Url, ModuleHeader>,
+}
+
+impl ModuleGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the header of a freshly indexed file, replacing what was known about it
+    pub fn update_file(&mut self, uri: Url, header: ModuleHeader) {
+        self.files.insert(uri, header);
+    }
+
+    pub fn remove_file(&mut self, uri: &Url) {
+        self.files.remove(uri);
+    }
+
+    /// Every indexed file, in no particular order
+    pub fn files(&self) -> impl Iterator<Item = &Url> {
+        self.files.keys()
+    }
+
+    pub fn header(&self, uri: &Url) -> Option<&ModuleHeader> {
+        self.files.get(uri)
+    }
+
+    /// The indexed file declaring `module_name`, with its header
+    pub fn module_file(&self, module_name: &str) -> Option<(&Url, &ModuleHeader)> {
+        self.files
+            .iter()
+            .find(|(_, header)| header.name.as_deref() == Some(module_name))
+    }
+
+    /// Names of all indexed modules, sorted
+    pub fn module_names(&self) -> Vec<String> {
+        let names: BTreeSet<&str> = self
+            .files
+            .values()
+            .filter_map(|header| header.name.as_deref())
+            .collect();
+        names.into_iter().map(str::to_string).collect()
+    }
+
+    /// Files importing `module_name`, sorted by URI
+    pub fn importers_of(&self, module_name: &str) -> Vec<Url> {
+        let mut importers: Vec<Url> = self
+            .files
+            .iter()
+            .filter(|(_, header)| header.imports.iter().any(|import| import == module_name))
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        importers.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        importers
+    }
+
+    /// Every way the indexed files write an import clause, with how many files write it
+    /// that way, most common first
+    pub fn import_clauses(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for header in self.files.values() {
+            let clauses: BTreeSet<&str> =
+                header.import_clauses.iter().map(String::as_str).collect();
+            for clause in clauses {
+                *counts.entry(clause).or_default() += 1;
+            }
+        }
+        let mut clauses: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(clause, count)| (clause.to_string(), count))
+            .collect();
+        clauses.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        clauses
+    }
+
+    /// Indexed modules `module_name` depends on, directly or not, in the order they're
+    /// built: every module comes after the modules it imports, and `module_name` is last.
+    /// Imports of modules no indexed file declares, such as packages, are left out.
+    pub fn build_order(&self, module_name: &str) -> Vec<String> {
+        let headers: HashMap<&str, &ModuleHeader> = self
+            .files
+            .values()
+            .filter_map(|header| Some((header.name.as_deref()?, header)))
+            .collect();
+
+        let mut order = Vec::new();
+        let mut visited = BTreeSet::new();
+        visit_imports(module_name, &headers, &mut visited, &mut order);
+        order
+    }
+
+    /// The shortest chain of imports leading from `from` to `to`, both included, through
+    /// indexed modules. `None` when `from` doesn't depend on `to`.
+    pub fn import_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let headers: HashMap<&str, &ModuleHeader> = self
+            .files
+            .values()
+            .filter_map(|header| Some((header.name.as_deref()?, header)))
+            .collect();
+
+        // Breadth-first, remembering which module each one was first reached from
+        let mut reached_from: HashMap<&str, &str> = HashMap::new();
+        let mut pending = VecDeque::from([from]);
+        while let Some(module_name) = pending.pop_front() {
+            if module_name == to {
+                let mut path = vec![to.to_string()];
+                let mut current = to;
+                while current != from {
+                    current = reached_from[current];
+                    path.push(current.to_string());
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let header = match headers.get(module_name) {
+                Some(header) => header,
+                None => continue,
+            };
+            for import in &header.imports {
+                if import != from && !reached_from.contains_key(import.as_str()) {
+                    reached_from.insert(import, module_name);
+                    pending.push_back(import);
+                }
+            }
+        }
+        None
+    }
+
+    /// Files depending on `module_name`, directly or not, in the order they're rebuilt
+    /// after it changes: every file comes after the dependents it imports. The file
+    /// declaring `module_name` isn't included.
+    pub fn dependents_order(&self, module_name: &str) -> Vec<Url> {
+        let mut dependents: HashMap<&str, (&Url, &ModuleHeader)> = HashMap::new();
+        let mut pending = vec![module_name];
+        while let Some(imported) = pending.pop() {
+            for (uri, header) in &self.files {
+                let name = match header.name.as_deref() {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if name != module_name
+                    && !dependents.contains_key(name)
+                    && header.imports.iter().any(|import| import == imported)
+                {
+                    dependents.insert(name, (uri, header));
+                    pending.push(name);
+                }
+            }
+        }
+
+        let headers: HashMap<&str, &ModuleHeader> = dependents
+            .iter()
+            .map(|(name, (_, header))| (*name, *header))
+            .collect();
+        let mut names: Vec<&str> = headers.keys().copied().collect();
+        names.sort();
+        let mut order = Vec::new();
+        let mut visited = BTreeSet::new();
+        for name in names {
+            visit_imports(name, &headers, &mut visited, &mut order);
+        }
+        order
+            .iter()
+            .map(|name| dependents[name.as_str()].0.clone())
+            .collect()
+    }
+}
+
+/// Depth-first walk adding each module after its imports. Import cycles are compile
+/// errors, the walk just doesn't revisit a module.
+fn visit_imports(
+    module_name: &str,
+    headers: &HashMap<&str, &ModuleHeader>,
+    visited: &mut BTreeSet<String>,
+    order: &mut Vec<String>,
+) {
+    let header = match headers.get(module_name) {
+        Some(header) => header,
+        None => return,
+    };
+    if !visited.insert(module_name.to_string()) {
+        return;
+    }
+
+    for import in &header.imports {
+        visit_imports(import, headers, visited, order);
+    }
+    order.push(module_name.to_string());
+}
+
+/// Values in a module declaration's exposing list, `None` when it exposes everything
+fn exposed_values(declaration: &Node, source: &str) -> Option<Vec<String>> {
+    let list = match child_of_kind(declaration, "exposing_list") {
+        Some(list) => list,
+        None => return Some(Vec::new()),
+    };
+    if child_of_kind(&list, "double_dot").is_some() {
+        return None;
+    }
+
+    let mut cursor = list.walk();
+    let values = list
+        .named_children(&mut cursor)
+        .filter(|item| item.kind() == "exposed_value")
+        .filter_map(|item| item.utf8_text(source.as_bytes()).ok())
+        .map(str::to_string)
+        .collect();
+    Some(values)
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, imports: &[&str]) -> ModuleHeader {
+        ModuleHeader {
+            name: Some(name.to_string()),
+            imports: imports.iter().map(|import| import.to_string()).collect(),
+            import_clauses: imports
+                .iter()
+                .map(|import| format!("import {}", import))
+                .collect(),
+            exposed_values: None,
+            exports: ModuleExports::default(),
+        }
+    }
+
+    #[test]
+    fn test_importers_follow_updates() {
+        let main = Url::parse("file:///project/src/Main.gren").unwrap();
+        let page = Url::parse("file:///project/src/Page.gren").unwrap();
+
+        let mut graph = ModuleGraph::new();
+        graph.update_file(main.clone(), header("Main", &["Page", "Utils"]));
+        graph.update_file(page.clone(), header("Page", &["Utils"]));
+        assert_eq!(
+            graph.importers_of("Utils"),
+            vec![main.clone(), page.clone()]
+        );
+        assert_eq!(graph.importers_of("Page"), vec![main.clone()]);
+        assert_eq!(graph.module_names(), vec!["Main", "Page"]);
+
+        graph.update_file(page.clone(), header("Page", &[]));
+        assert_eq!(graph.importers_of("Utils"), vec![main.clone()]);
+
+        graph.remove_file(&main);
+        assert!(graph.importers_of("Page").is_empty());
+        assert_eq!(graph.header(&page).unwrap().name.as_deref(), Some("Page"));
+    }
+
+    #[test]
+    fn test_import_clauses_counted_per_file() {
+        let mut graph = ModuleGraph::new();
+        for (name, clauses) in [
+            (
+                "Main",
+                &["import Json.Decode as Decode", "import Utils"][..],
+            ),
+            (
+                "Page",
+                &["import Json.Decode as Decode", "import Utils exposing (..)"][..],
+            ),
+            (
+                "View",
+                &["import Json.Decode as D", "import Json.Decode as D"][..],
+            ),
+        ] {
+            let uri = Url::parse(&format!("file:///project/src/{}.gren", name)).unwrap();
+            let mut header = header(name, &[]);
+            header.import_clauses = clauses.iter().map(|clause| clause.to_string()).collect();
+            graph.update_file(uri, header);
+        }
+
+        assert_eq!(
+            graph.import_clauses(),
+            vec![
+                ("import Json.Decode as Decode".to_string(), 2),
+                ("import Json.Decode as D".to_string(), 1),
+                ("import Utils".to_string(), 1),
+                ("import Utils exposing (..)".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_order_puts_imports_first() {
+        let mut graph = ModuleGraph::new();
+        for (path, name, imports) in [
+            ("Main", "Main", &["Page", "Utils", "Json.Decode"][..]),
+            ("Page", "Page", &["Utils"][..]),
+            ("Utils", "Utils", &[][..]),
+            ("Unrelated", "Unrelated", &["Utils"][..]),
+        ] {
+            let uri = Url::parse(&format!("file:///project/src/{}.gren", path)).unwrap();
+            graph.update_file(uri, header(name, imports));
+        }
+
+        assert_eq!(graph.build_order("Main"), vec!["Utils", "Page", "Main"]);
+        assert_eq!(graph.build_order("Utils"), vec!["Utils"]);
+        assert!(graph.build_order("Json.Decode").is_empty());
+    }
+
+    #[test]
+    fn test_dependents_order_puts_imports_first() {
+        let mut graph = ModuleGraph::new();
+        for (name, imports) in [
+            ("Main", &["Page", "View"][..]),
+            ("Page", &["View", "Utils"][..]),
+            ("View", &["Utils"][..]),
+            ("Utils", &[][..]),
+            ("Unrelated", &[][..]),
+        ] {
+            let uri = Url::parse(&format!("file:///project/src/{}.gren", name)).unwrap();
+            graph.update_file(uri, header(name, imports));
+        }
+        let names = |uris: Vec<Url>| -> Vec<String> {
+            uris.iter()
+                .map(|uri| graph.header(uri).unwrap().name.clone().unwrap())
+                .collect()
+        };
+
+        assert_eq!(
+            names(graph.dependents_order("Utils")),
+            vec!["View", "Page", "Main"]
+        );
+        assert_eq!(names(graph.dependents_order("Page")), vec!["Main"]);
+        assert!(graph.dependents_order("Main").is_empty());
+    }
+
+    #[test]
+    fn test_import_path() {
+        let mut graph = ModuleGraph::new();
+        for (name, imports) in [
+            ("Main", &["Page", "Utils"][..]),
+            ("Page", &["View"][..]),
+            ("View", &["Utils", "Html"][..]),
+            ("Utils", &[][..]),
+        ] {
+            let uri = Url::parse(&format!("file:///project/src/{}.gren", name)).unwrap();
+            graph.update_file(uri, header(name, imports));
+        }
+
+        assert_eq!(
+            graph.import_path("Page", "Utils"),
+            Some(vec![
+                "Page".to_string(),
+                "View".to_string(),
+                "Utils".to_string()
+            ])
+        );
+        assert_eq!(
+            graph.import_path("Main", "Utils"),
+            Some(vec!["Main".to_string(), "Utils".to_string()])
+        );
+        assert_eq!(graph.import_path("Utils", "Main"), None);
+        assert_eq!(
+            graph.import_path("View", "Html").map(|path| path.len()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_exports_follow_the_exposing_list() {
+        let source = r#"module Shapes exposing (Shape(..), Color, area)
+
+type Shape
+    = Circle Float
+    | Square Float
+
+type Color
+    = Red
+    | Blue
+
+type alias Point =
+    { x : Float, y : Float }
+
+area : Shape -> Float
+area shape =
+    0
+
+perimeter : Shape -> Float
+perimeter shape =
+    0
+"#;
+        let mut parser = crate::Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        let exports = ModuleHeader::from_tree(&tree, source).exports;
+
+        assert!(exports.exposes("area", SymbolKind::FUNCTION));
+        assert!(!exports.exposes("perimeter", SymbolKind::FUNCTION));
+        assert!(exports.exposes("Circle", SymbolKind::CONSTRUCTOR));
+        assert!(exports.exposes("Color", SymbolKind::CLASS));
+        assert!(!exports.exposes("Red", SymbolKind::CONSTRUCTOR));
+        assert!(!exports.exposes("Point", SymbolKind::CLASS));
+
+        let everything = source.replace("exposing (Shape(..), Color, area)", "exposing (..)");
+        let tree = parser.parse(&everything).unwrap().unwrap();
+        let exports = ModuleHeader::from_tree(&tree, &everything).exports;
+        assert!(exports.exposes("perimeter", SymbolKind::FUNCTION));
+        assert!(exports.exposes("Red", SymbolKind::CONSTRUCTOR));
+        assert!(exports.exposes("Point", SymbolKind::CLASS));
+    }
+}
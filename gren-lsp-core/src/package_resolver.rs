@@ -0,0 +1,310 @@
+//! Finding the sources of the packages a project depends on
+//!
+//! The compiler keeps the packages it downloads in its cache, `GREN_HOME` or `gren` in the
+//! user's cache folder, as `packages/<author>/<name>/<version>`, either directly or in a
+//! folder per compiler version. The project's `gren.json` says which packages and versions
+//! to look for, each package's own `gren.json` which modules it exposes and what else it
+//! depends on. A package project only lists version ranges, so the highest installed
+//! version in range is used.
+
+use crate::manifest::dependency_versions;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// A dependency installed in the package cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSource {
+    /// `author/name`
+    pub name: String,
+    pub version: String,
+    /// The package's folder, holding its `gren.json` and `src`
+    pub root: PathBuf,
+}
+
+impl PackageSource {
+    /// Source files of the modules the package exposes, or of every module in `src` when
+    /// its manifest doesn't say
+    pub fn module_files(&self) -> Vec<PathBuf> {
+        let src = self.root.join("src");
+        let exposed = std::fs::read_to_string(self.root.join("gren.json"))
+            .ok()
+            .map(|manifest| exposed_modules(&manifest))
+            .unwrap_or_default();
+        if exposed.is_empty() {
+            let mut files = Vec::new();
+            gren_files(&src, &mut files);
+            files.sort();
+            return files;
+        }
+        exposed
+            .iter()
+            .map(|module| src.join(format!("{}.gren", module.replace('.', "/"))))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+}
+
+/// The compiler's cache folder in the environment compiler runs get, `env` overriding
+/// the server's own
+pub fn cache_root(env: &BTreeMap<String, String>) -> Option<PathBuf> {
+    cache_root_from(|name| env.get(name).cloned().or_else(|| std::env::var(name).ok()))
+}
+
+fn cache_root_from(lookup: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    let set = |name: &str| lookup(name).filter(|value| !value.is_empty());
+    if let Some(gren_home) = set("GREN_HOME") {
+        return Some(PathBuf::from(gren_home));
+    }
+    if let Some(cache) = set("XDG_CACHE_HOME") {
+        return Some(Path::new(&cache).join("gren"));
+    }
+    set("HOME").map(|home| Path::new(&home).join(".cache").join("gren"))
+}
+
+/// Every package the project's manifest needs, including what its dependencies depend
+/// on, that's installed under `cache_root`. Packages that aren't installed are left out.
+pub fn resolve_packages(manifest: &str, cache_root: &Path) -> anyhow::Result<Vec<PackageSource>> {
+    let mut pending = dependency_versions(manifest)?;
+    let mut seen: HashSet<String> = pending.iter().map(|(name, _)| name.clone()).collect();
+    let mut packages = Vec::new();
+
+    while let Some((name, version)) = pending.pop() {
+        let package = match find_installed(cache_root, &name, &version) {
+            Some(package) => package,
+            None => {
+                debug!("{} {} isn't installed", name, version);
+                continue;
+            }
+        };
+        let dependencies = std::fs::read_to_string(package.root.join("gren.json"))
+            .map_err(anyhow::Error::from)
+            .and_then(|manifest| dependency_versions(&manifest));
+        match dependencies {
+            Ok(dependencies) => pending.extend(
+                dependencies
+                    .into_iter()
+                    .filter(|(name, _)| seen.insert(name.clone())),
+            ),
+            Err(e) => warn!("Failed to read the dependencies of {}: {}", name, e),
+        }
+        packages.push(package);
+    }
+
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(packages)
+}
+
+/// The installed version of `name` an exact version or a range asks for, the highest in
+/// range, looking in the newest compiler's folder first
+fn find_installed(cache_root: &Path, name: &str, version: &str) -> Option<PackageSource> {
+    let mut package_dirs: Vec<(Option<Version>, PathBuf)> = std::fs::read_dir(cache_root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .map(|path| {
+            let compiler_version = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_version);
+            (compiler_version, path.join("packages").join(name))
+        })
+        .collect();
+    package_dirs.sort_by(|a, b| b.0.cmp(&a.0));
+    package_dirs.insert(0, (None, cache_root.join("packages").join(name)));
+
+    package_dirs.iter().find_map(|(_, package_dir)| {
+        let mut installed: Vec<(Version, String)> = std::fs::read_dir(package_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter_map(|installed| Some((parse_version(&installed)?, installed)))
+            .filter(|(_, installed)| installed == version || in_range(installed, version))
+            .collect();
+        installed.sort();
+        let (_, version) = installed.pop()?;
+        Some(PackageSource {
+            name: name.to_string(),
+            root: package_dir.join(&version),
+            version,
+        })
+    })
+}
+
+type Version = (u64, u64, u64);
+
+fn parse_version(version: &str) -> Option<Version> {
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    match parts.next() {
+        Some(_) => None,
+        None => Some(version),
+    }
+}
+
+/// Whether `version` is within a `1.0.0 <= v < 2.0.0` range
+fn in_range(version: &str, range: &str) -> bool {
+    let version = match parse_version(version) {
+        Some(version) => version,
+        None => return false,
+    };
+    let parts: Vec<&str> = range.split_whitespace().collect();
+    match parts.as_slice() {
+        [lower, lower_op, "v", upper_op, upper] => {
+            let (lower, upper) = match (parse_version(lower), parse_version(upper)) {
+                (Some(lower), Some(upper)) => (lower, upper),
+                _ => return false,
+            };
+            let above = match *lower_op {
+                "<=" => lower <= version,
+                "<" => lower < version,
+                _ => false,
+            };
+            let below = match *upper_op {
+                "<=" => version <= upper,
+                "<" => version < upper,
+                _ => false,
+            };
+            above && below
+        }
+        _ => false,
+    }
+}
+
+/// Modules a package's manifest exposes, listed directly or grouped under headings
+fn exposed_modules(manifest: &str) -> Vec<String> {
+    let json: serde_json::Value = match serde_json::from_str(manifest) {
+        Ok(json) => json,
+        Err(_) => return Vec::new(),
+    };
+    let module_names = |list: &serde_json::Value| -> Vec<String> {
+        list.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|module| module.as_str().map(str::to_string))
+            .collect()
+    };
+    match json.get("exposed-modules") {
+        Some(serde_json::Value::Object(groups)) => groups.values().flat_map(module_names).collect(),
+        Some(list) => module_names(list),
+        None => Vec::new(),
+    }
+}
+
+fn gren_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            gren_files(&path, files);
+        } else if path.extension().and_then(|extension| extension.to_str()) == Some("gren") {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn install(cache: &Path, folder: &str, name: &str, version: &str, manifest: &str) -> PathBuf {
+        let root = cache.join(folder).join(name).join(version);
+        std::fs::create_dir_all(root.join("src/Json")).unwrap();
+        std::fs::write(root.join("gren.json"), manifest).unwrap();
+        std::fs::write(root.join("src/Json/Decode.gren"), "module Json.Decode").unwrap();
+        std::fs::write(root.join("src/Internal.gren"), "module Internal").unwrap();
+        root
+    }
+
+    #[test]
+    fn test_resolve_packages() {
+        let cache = TempDir::new().unwrap();
+        let core = r#"{ "type": "package", "exposed-modules": { "JSON": ["Json.Decode"] } }"#;
+        let json = r#"{
+            "type": "package",
+            "exposed-modules": ["Json.Decode"],
+            "dependencies": { "gren-lang/core": "6.0.0 <= v < 7.0.0" }
+        }"#;
+        let compiler_dir = "0.5.0/packages";
+        for version in ["6.0.0", "7.0.0"] {
+            install(cache.path(), compiler_dir, "gren-lang/core", version, core);
+        }
+        let newest_core = install(cache.path(), compiler_dir, "gren-lang/core", "6.1.2", core);
+        let json = install(cache.path(), "packages", "gren-lang/json", "2.0.0", json);
+
+        let manifest = r#"{
+            "type": "package",
+            "dependencies": { "gren-lang/json": "2.0.0 <= v < 3.0.0", "gren-lang/missing": "1.0.0 <= v < 2.0.0" }
+        }"#;
+        let packages = resolve_packages(manifest, cache.path()).unwrap();
+        let resolved: Vec<(&str, &str, &Path)> = packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.version.as_str(), p.root.as_path()))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                ("gren-lang/core", "6.1.2", newest_core.as_path()),
+                ("gren-lang/json", "2.0.0", json.as_path()),
+            ]
+        );
+        assert_eq!(
+            packages[0].module_files(),
+            vec![newest_core.join("src/Json/Decode.gren")]
+        );
+    }
+
+    #[test]
+    fn test_module_files_without_exposed_modules() {
+        let cache = TempDir::new().unwrap();
+        let root = install(cache.path(), "packages", "me/lib", "1.0.0", "{}");
+        let package = PackageSource {
+            name: "me/lib".to_string(),
+            version: "1.0.0".to_string(),
+            root: root.clone(),
+        };
+        assert_eq!(
+            package.module_files(),
+            vec![
+                root.join("src/Internal.gren"),
+                root.join("src/Json/Decode.gren")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_root() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            cache_root_from(env(&[("GREN_HOME", "/opt/gren"), ("HOME", "/home/me")])),
+            Some(PathBuf::from("/opt/gren"))
+        );
+        assert_eq!(
+            cache_root_from(env(&[("XDG_CACHE_HOME", "/cache"), ("HOME", "/home/me")])),
+            Some(PathBuf::from("/cache/gren"))
+        );
+        assert_eq!(
+            cache_root_from(env(&[("GREN_HOME", ""), ("HOME", "/home/me")])),
+            Some(PathBuf::from("/home/me/.cache/gren"))
+        );
+        assert_eq!(cache_root_from(env(&[])), None);
+    }
+
+    #[test]
+    fn test_in_range() {
+        assert!(in_range("6.1.2", "6.0.0 <= v < 7.0.0"));
+        assert!(!in_range("7.0.0", "6.0.0 <= v < 7.0.0"));
+        assert!(in_range("7.0.0", "6.0.0 <= v <= 7.0.0"));
+        assert!(!in_range("6.0.0", "6.0.0 < v < 7.0.0"));
+        assert!(!in_range("6.1.2", "6.1.2"));
+    }
+}
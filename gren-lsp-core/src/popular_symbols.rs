@@ -0,0 +1,186 @@
+//! How often each module's values, types and constructors are used across the workspace
+//!
+//! Completing an empty or one-character word can't build an item for every indexed symbol
+//! without making the first keystroke slow in a large project. Instead it offers each
+//! imported module's most used names. Uses are counted per referencing file from the
+//! references indexed for it, and the files' counts are replaced whenever they're
+//! re-indexed. Each module's ranking is recomputed when a count of one of its names
+//! changes, so completion only reads it.
+
+use crate::{ReferenceKind, SymbolReference};
+use lsp_types::Url;
+use std::collections::{HashMap, HashSet};
+
+/// Names kept in each module's ranking
+pub const POPULAR_PER_MODULE: usize = 50;
+
+#[derive(Debug, Default)]
+pub struct PopularSymbols {
+    /// How often each file uses each name, by the module defining it
+    by_file: HashMap<Url, HashMap<(String, String), usize>>,
+    /// Uses of each name across the workspace, by the module defining it
+    totals: HashMap<String, HashMap<String, usize>>,
+    /// Each module's most used names, most used first
+    rankings: HashMap<String, Vec<(String, usize)>>,
+}
+
+impl PopularSymbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count the names `references` use in place of what `uri` used before
+    pub fn replace_file(&mut self, uri: &Url, references: &[SymbolReference]) {
+        let mut uses: HashMap<(String, String), usize> = HashMap::new();
+        // Declarations and exposing lists name a symbol without using it
+        let used = references.iter().filter(|reference| {
+            !matches!(
+                reference.kind,
+                ReferenceKind::Declaration | ReferenceKind::Exposing
+            )
+        });
+        for reference in used {
+            *uses
+                .entry((reference.module.clone(), reference.name.clone()))
+                .or_default() += 1;
+        }
+
+        let mut changed = self.subtract(uri);
+        for ((module, name), count) in &uses {
+            *self
+                .totals
+                .entry(module.clone())
+                .or_default()
+                .entry(name.clone())
+                .or_default() += count;
+            changed.insert(module.clone());
+        }
+        if !uses.is_empty() {
+            self.by_file.insert(uri.clone(), uses);
+        }
+        self.rank(changed);
+    }
+
+    /// Stop counting what `uri` uses
+    pub fn remove_file(&mut self, uri: &Url) {
+        let changed = self.subtract(uri);
+        self.rank(changed);
+    }
+
+    /// The most used names of `module`, most used first, with how often they're used
+    pub fn top(&self, module: &str) -> &[(String, usize)] {
+        self.rankings.get(module).map_or(&[], Vec::as_slice)
+    }
+
+    /// How often `name` from `module` is used across the workspace
+    pub fn uses(&self, module: &str, name: &str) -> usize {
+        self.totals
+            .get(module)
+            .and_then(|names| names.get(name))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Take the uses of `uri` out of the totals, returning the modules they were of
+    fn subtract(&mut self, uri: &Url) -> HashSet<String> {
+        let mut changed = HashSet::new();
+        for ((module, name), count) in self.by_file.remove(uri).unwrap_or_default() {
+            if let Some(names) = self.totals.get_mut(&module) {
+                if let Some(total) = names.get_mut(&name) {
+                    *total = total.saturating_sub(count);
+                    if *total == 0 {
+                        names.remove(&name);
+                    }
+                }
+                if names.is_empty() {
+                    self.totals.remove(&module);
+                }
+            }
+            changed.insert(module);
+        }
+        changed
+    }
+
+    fn rank(&mut self, modules: HashSet<String>) {
+        for module in modules {
+            let mut ranking: Vec<(String, usize)> = match self.totals.get(&module) {
+                Some(names) => names
+                    .iter()
+                    .map(|(name, count)| (name.clone(), *count))
+                    .collect(),
+                None => {
+                    self.rankings.remove(&module);
+                    continue;
+                }
+            };
+            ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            ranking.truncate(POPULAR_PER_MODULE);
+            self.rankings.insert(module, ranking);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Location, Position, Range};
+
+    fn reference(uri: &Url, module: &str, name: &str) -> SymbolReference {
+        SymbolReference {
+            name: name.to_string(),
+            module: module.to_string(),
+            location: Location::new(
+                uri.clone(),
+                Range::new(Position::new(0, 0), Position::new(0, name.len() as u32)),
+            ),
+            kind: ReferenceKind::Expression,
+            container: None,
+        }
+    }
+
+    #[test]
+    fn test_ranking_follows_reindexed_files() {
+        let main = Url::parse("file:///project/src/Main.gren").unwrap();
+        let view = Url::parse("file:///project/src/View.gren").unwrap();
+
+        let mut popular = PopularSymbols::new();
+        popular.replace_file(
+            &main,
+            &[
+                reference(&main, "Array", "map"),
+                reference(&main, "Array", "map"),
+                reference(&main, "Array", "foldl"),
+                SymbolReference {
+                    kind: ReferenceKind::Exposing,
+                    ..reference(&main, "Array", "length")
+                },
+            ],
+        );
+        popular.replace_file(
+            &view,
+            &[
+                reference(&view, "Array", "foldl"),
+                reference(&view, "Array", "length"),
+            ],
+        );
+        let names = |popular: &PopularSymbols| -> Vec<String> {
+            popular
+                .top("Array")
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+        assert_eq!(names(&popular), vec!["foldl", "map", "length"]);
+        assert_eq!(popular.uses("Array", "map"), 2);
+
+        // Main stops using `map`
+        popular.replace_file(&main, &[reference(&main, "Array", "foldl")]);
+        assert_eq!(names(&popular), vec!["foldl", "length"]);
+        assert_eq!(popular.uses("Array", "map"), 0);
+
+        popular.remove_file(&view);
+        popular.remove_file(&main);
+        assert!(popular.top("Array").is_empty());
+        assert!(popular.totals.is_empty());
+    }
+}
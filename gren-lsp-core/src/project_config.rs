@@ -0,0 +1,347 @@
+//! Team-shared configuration checked in as `.gren-lsp.toml` in the workspace root
+//!
+//! ```toml
+//! # Files that aren't indexed or linted, as globs relative to the root
+//! exclude = ["generated/**"]
+//!
+//! [lints]
+//! profile = "strict"
+//!
+//! [lints.severity]
+//! unused-import = "warning"
+//! function-length = "off"
+//!
+//! [imports]
+//! style = "grouped"
+//! lint = true
+//!
+//! [compiler]
+//! flags = ["--optimize"]
+//!
+//! # Huge generated modules indexed with only the signatures of what they expose
+//! [index]
+//! signatures_only = ["src/Generated/**"]
+//! ```
+//!
+//! Editor settings belong to one user and the file to the whole team, so:
+//!
+//! - `lints.profile`, `imports.style` and `imports.lint` set in the file win over the
+//!   editor's settings. Whatever the file leaves out falls back to them.
+//! - `exclude`, `lints.severity` and `index` only come from the file.
+//! - The editor's compiler environment and flags go on top of the file's, as they're
+//!   usually about one machine (see [`crate::compiler_options`]).
+//!
+//! A file with unknown keys or invalid values is ignored as a whole, and the problem is
+//! reported as a diagnostic on the file itself.
+
+use crate::alias_cycles::RECURSIVE_ALIAS;
+use crate::annotation_diagnostics::ANNOTATION_ARITY_MISMATCH;
+use crate::import_conflicts::IMPORT_CONFLICT;
+use crate::imports::IMPORT_STYLE_MISMATCH;
+use crate::lints::{MISSING_ANNOTATION, UNUSED_BINDING, UNUSED_EXPOSED, UNUSED_IMPORT};
+use crate::metrics::{CASE_NESTING_TOO_DEEP, FUNCTION_TOO_LONG};
+use crate::shadowing::SHADOWED_NAME;
+use crate::symbol_query::FileFilter;
+use crate::syntax::offset_to_position;
+use crate::{CompilerOptions, ImportStyle, LintProfile};
+use lsp_types::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Project configuration file looked for in the workspace root
+pub const PROJECT_CONFIG_FILE: &str = ".gren-lsp.toml";
+
+/// Diagnostic code of problems in the project configuration file
+pub const INVALID_PROJECT_CONFIG: &str = "invalid-config";
+
+/// The contents of a `.gren-lsp.toml`
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Globs, relative to the workspace root, of files that aren't indexed or linted
+    pub exclude: Vec<String>,
+    pub lints: LintConfig,
+    pub imports: ImportConfig,
+    pub compiler: CompilerOptions,
+    pub index: IndexConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    pub profile: Option<LintProfile>,
+    /// Severities overriding the profile's, by diagnostic code
+    pub severity: BTreeMap<LintCode, LintLevel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImportConfig {
+    pub style: Option<ImportStyle>,
+    pub lint: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexConfig {
+    /// Globs, relative to the workspace root, of modules indexed with only the signatures
+    /// of the declarations they expose. Their bodies aren't analyzed, which keeps huge
+    /// generated modules from filling memory and the index, while other modules can still
+    /// resolve what they import from them.
+    pub signatures_only: Vec<String>,
+}
+
+/// Codes of the diagnostics the server reports itself, whose severity can be configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum LintCode {
+    #[serde(rename = "unused-import")]
+    UnusedImport,
+    #[serde(rename = "unused-binding")]
+    UnusedBinding,
+    #[serde(rename = "unused-exposed")]
+    UnusedExposed,
+    #[serde(rename = "missing-annotation")]
+    MissingAnnotation,
+    #[serde(rename = "import-style")]
+    ImportStyle,
+    #[serde(rename = "import-conflict")]
+    ImportConflict,
+    #[serde(rename = "shadowed-name")]
+    ShadowedName,
+    #[serde(rename = "recursive-type-alias")]
+    RecursiveAlias,
+    #[serde(rename = "annotation-arity-mismatch")]
+    AnnotationArityMismatch,
+    #[serde(rename = "function-length")]
+    FunctionLength,
+    #[serde(rename = "case-nesting")]
+    CaseNesting,
+}
+
+impl LintCode {
+    /// The code diagnostics carry
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LintCode::UnusedImport => UNUSED_IMPORT,
+            LintCode::UnusedBinding => UNUSED_BINDING,
+            LintCode::UnusedExposed => UNUSED_EXPOSED,
+            LintCode::MissingAnnotation => MISSING_ANNOTATION,
+            LintCode::ImportStyle => IMPORT_STYLE_MISMATCH,
+            LintCode::ImportConflict => IMPORT_CONFLICT,
+            LintCode::ShadowedName => SHADOWED_NAME,
+            LintCode::RecursiveAlias => RECURSIVE_ALIAS,
+            LintCode::AnnotationArityMismatch => ANNOTATION_ARITY_MISMATCH,
+            LintCode::FunctionLength => FUNCTION_TOO_LONG,
+            LintCode::CaseNesting => CASE_NESTING_TOO_DEEP,
+        }
+    }
+}
+
+/// Severity a lint is reported with, or `off` to not report it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Error,
+    Warning,
+    Info,
+    Hint,
+    Off,
+}
+
+impl LintLevel {
+    pub fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            LintLevel::Error => Some(DiagnosticSeverity::ERROR),
+            LintLevel::Warning => Some(DiagnosticSeverity::WARNING),
+            LintLevel::Info => Some(DiagnosticSeverity::INFORMATION),
+            LintLevel::Hint => Some(DiagnosticSeverity::HINT),
+            LintLevel::Off => None,
+        }
+    }
+}
+
+/// Why a configuration file can't be used, and where in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub message: String,
+    pub range: Range,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.range.start.line + 1, self.message)
+    }
+}
+
+impl ConfigError {
+    fn from_toml(content: &str, error: &toml::de::Error) -> Self {
+        let range = match error.span() {
+            Some(span) => Range::new(
+                offset_to_position(content, span.start),
+                offset_to_position(content, span.end),
+            ),
+            None => Range::default(),
+        };
+        ConfigError {
+            message: error.message().trim().to_string(),
+            range,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            range: self.range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(INVALID_PROJECT_CONFIG.to_string())),
+            source: Some("gren-lsp".to_string()),
+            message: format!("{}, the file is ignored", self.message),
+            ..Default::default()
+        }
+    }
+}
+
+impl ProjectConfig {
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        toml::from_str(content).map_err(|e| ConfigError::from_toml(content, &e))
+    }
+
+    /// Globs of `exclude` that files are matched against
+    pub fn exclude_filters(&self) -> Vec<FileFilter> {
+        self.exclude
+            .iter()
+            .filter_map(|pattern| FileFilter::parse(pattern))
+            .collect()
+    }
+
+    /// Globs of `index.signatures_only` that files are matched against
+    pub fn signatures_only_filters(&self) -> Vec<FileFilter> {
+        self.index
+            .signatures_only
+            .iter()
+            .filter_map(|pattern| FileFilter::parse(pattern))
+            .collect()
+    }
+
+    /// Give lint diagnostics the configured severities, dropping the ones turned off
+    pub fn apply_severities(&self, diagnostics: &mut Vec<Diagnostic>) {
+        if self.lints.severity.is_empty() {
+            return;
+        }
+        diagnostics.retain_mut(|diagnostic| {
+            let code = match &diagnostic.code {
+                Some(NumberOrString::String(code)) => code.as_str(),
+                _ => return true,
+            };
+            let level = self
+                .lints
+                .severity
+                .iter()
+                .find(|(lint, _)| lint.as_str() == code)
+                .map(|(_, level)| *level);
+            match level.map(LintLevel::severity) {
+                Some(Some(severity)) => {
+                    diagnostic.severity = Some(severity);
+                    true
+                }
+                Some(None) => false,
+                None => true,
+            }
+        });
+    }
+}
+
+/// Diagnostics for the contents of a `.gren-lsp.toml`, empty when it's valid
+pub fn project_config_diagnostics(content: &str) -> Vec<Diagnostic> {
+    match ProjectConfig::parse(content) {
+        Ok(_) => Vec::new(),
+        Err(e) => vec![e.to_diagnostic()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_project_config() {
+        let config = ProjectConfig::parse(
+            r#"
+exclude = ["generated/**"]
+
+[lints]
+profile = "strict"
+
+[lints.severity]
+unused-import = "warning"
+function-length = "off"
+
+[imports]
+style = "grouped"
+
+[compiler]
+flags = ["--optimize"]
+
+[compiler.env]
+GREN_HOME = "/opt/gren"
+
+[index]
+signatures_only = ["src/Generated/**"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.exclude, vec!["generated/**"]);
+        assert_eq!(config.lints.profile, Some(LintProfile::Strict));
+        assert_eq!(
+            config.lints.severity[&LintCode::UnusedImport],
+            LintLevel::Warning
+        );
+        assert_eq!(config.imports.style, Some(ImportStyle::Grouped));
+        assert_eq!(config.imports.lint, None);
+        assert_eq!(config.compiler.flags, vec!["--optimize"]);
+        assert_eq!(config.compiler.env["GREN_HOME"], "/opt/gren");
+        assert_eq!(config.index.signatures_only, vec!["src/Generated/**"]);
+
+        assert_eq!(ProjectConfig::parse("").unwrap(), ProjectConfig::default());
+        assert!(ProjectConfig::parse("[compiler]\nflags = \"--optimize\"").is_err());
+    }
+
+    #[test]
+    fn test_invalid_config_diagnostics() {
+        let diagnostics =
+            project_config_diagnostics("[lints]\nprofile = \"strict\"\nstrickt = true\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(2, 0));
+        assert!(diagnostics[0].message.contains("strickt"));
+
+        let diagnostics =
+            project_config_diagnostics("[lints.severity]\nunused-imports = \"off\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+        assert!(diagnostics[0].message.contains("unused-import"));
+
+        assert!(project_config_diagnostics("[lints]\nprofile = \"strict\"\n").is_empty());
+    }
+
+    #[test]
+    fn test_apply_severities() {
+        let config = ProjectConfig::parse(
+            "[lints.severity]\nunused-import = \"warning\"\nfunction-length = \"off\"\n",
+        )
+        .unwrap();
+        let lint = |code: &str| Diagnostic {
+            severity: Some(DiagnosticSeverity::HINT),
+            code: Some(NumberOrString::String(code.to_string())),
+            ..Default::default()
+        };
+        let mut diagnostics = vec![
+            lint(UNUSED_IMPORT),
+            lint(FUNCTION_TOO_LONG),
+            lint(SHADOWED_NAME),
+        ];
+        config.apply_severities(&mut diagnostics);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::HINT));
+    }
+}
@@ -0,0 +1,373 @@
+//! The Gren projects in a workspace
+//!
+//! A repository can hold several projects, each a folder with its own `gren.json`. A file
+//! belongs to the innermost project containing it and is compiled from that project's
+//! folder. Projects in one repository depend on each other through `local:` dependencies,
+//! `"example/ui": "local:../ui"`, and a module is only visible to its own project and the
+//! projects depending on it.
+//!
+//! A project's modules are in the `source-directories` its manifest lists, `src` for a
+//! package. Those are what gets scanned when the workspace is indexed, so build output and
+//! scripts elsewhere in the project folder stay out of the index.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Folders never searched for projects
+const SKIPPED_FOLDERS: &[&str] = &["node_modules", "target"];
+
+/// A folder with a `gren.json`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    pub root: PathBuf,
+    /// Roots of the projects it lists as `local:` dependencies
+    pub local_dependencies: Vec<PathBuf>,
+    /// Folders holding its modules, empty when the manifest doesn't say
+    pub source_directories: Vec<PathBuf>,
+}
+
+impl Project {
+    /// The project in `root` with the manifest `manifest`
+    pub fn from_manifest(root: &Path, manifest: &str) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            local_dependencies: local_dependency_paths(manifest)
+                .into_iter()
+                .map(|path| normalize(&root.join(path)))
+                .collect(),
+            source_directories: source_directory_paths(manifest)
+                .into_iter()
+                .map(|path| normalize(&root.join(path)))
+                .collect(),
+        }
+    }
+
+    /// Folders to scan for its modules: its source directories, or the whole project when
+    /// the manifest doesn't list them
+    pub fn source_roots(&self) -> Vec<PathBuf> {
+        if self.source_directories.is_empty() {
+            vec![self.root.clone()]
+        } else {
+            self.source_directories.clone()
+        }
+    }
+}
+
+/// Every project under a set of folders, and the `local:` dependencies between them
+#[derive(Debug, Clone, Default)]
+pub struct ProjectGraph {
+    projects: Vec<Project>,
+}
+
+impl ProjectGraph {
+    pub fn new(projects: Vec<Project>) -> Self {
+        Self { projects }
+    }
+
+    /// Find the projects in `folders` and the folders below them, skipping hidden ones
+    pub fn discover(folders: &[PathBuf]) -> Self {
+        let mut projects = Vec::new();
+        for folder in folders {
+            find_projects(folder, &mut projects);
+        }
+        projects.sort_by(|a, b| a.root.cmp(&b.root));
+        projects.dedup_by(|a, b| a.root == b.root);
+        debug!("Found {} Gren projects", projects.len());
+        Self { projects }
+    }
+
+    pub fn projects(&self) -> &[Project] {
+        &self.projects
+    }
+
+    /// Folders to scan for the modules of the projects in `folder`, or `folder` itself
+    /// when it holds no project. Source directories inside others are left out, as
+    /// scanning the outer one finds their modules.
+    pub fn source_roots(&self, folder: &Path) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = self
+            .projects
+            .iter()
+            .filter(|project| project.root.starts_with(folder))
+            .flat_map(Project::source_roots)
+            .collect();
+        if roots.is_empty() {
+            return vec![folder.to_path_buf()];
+        }
+
+        roots.sort();
+        roots.dedup();
+        let nested: Vec<bool> = roots
+            .iter()
+            .map(|root| {
+                roots
+                    .iter()
+                    .any(|other| other != root && root.starts_with(other))
+            })
+            .collect();
+        roots
+            .into_iter()
+            .zip(nested)
+            .filter(|(_, nested)| !nested)
+            .map(|(root, _)| root)
+            .collect()
+    }
+
+    /// The project a file belongs to, the innermost one when projects are nested
+    pub fn project_of(&self, path: &Path) -> Option<&Project> {
+        self.projects
+            .iter()
+            .filter(|project| path.starts_with(&project.root))
+            .max_by_key(|project| project.root.components().count())
+    }
+
+    /// Whether code in `from` can use a module defined in `to`: both are in one project,
+    /// `to` is in a project `from`'s project depends on, or `to` isn't in any project,
+    /// like an installed package
+    pub fn is_visible(&self, from: &Path, to: &Path) -> bool {
+        let target = match self.project_of(to) {
+            Some(target) => target,
+            None => return true,
+        };
+        let source = match self.project_of(from) {
+            Some(source) => source,
+            None => return true,
+        };
+
+        let mut seen = HashSet::new();
+        let mut pending = vec![source];
+        while let Some(project) = pending.pop() {
+            if project.root == target.root {
+                return true;
+            }
+            if !seen.insert(&project.root) {
+                continue;
+            }
+            pending.extend(project.local_dependencies.iter().filter_map(|root| {
+                self.projects
+                    .iter()
+                    .find(|dependency| &dependency.root == root)
+            }));
+        }
+        false
+    }
+}
+
+fn find_projects(folder: &Path, projects: &mut Vec<Project>) {
+    if let Ok(manifest) = std::fs::read_to_string(folder.join("gren.json")) {
+        projects.push(Project::from_manifest(folder, &manifest));
+    }
+    for entry in std::fs::read_dir(folder).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() && !name.starts_with('.') && !SKIPPED_FOLDERS.contains(&name.as_ref()) {
+            find_projects(&path, projects);
+        }
+    }
+}
+
+/// The paths of the `local:` dependencies a manifest lists, relative to its folder
+fn local_dependency_paths(manifest: &str) -> Vec<String> {
+    let json: serde_json::Value = match serde_json::from_str(manifest) {
+        Ok(json) => json,
+        Err(_) => return Vec::new(),
+    };
+    let dependencies = match json.get("dependencies") {
+        Some(dependencies) => dependencies,
+        None => return Vec::new(),
+    };
+    let groups = if json.get("type").and_then(|value| value.as_str()) == Some("package") {
+        vec![dependencies]
+    } else {
+        ["direct", "indirect"]
+            .iter()
+            .filter_map(|group| dependencies.get(group))
+            .collect()
+    };
+    groups
+        .into_iter()
+        .filter_map(|group| group.as_object())
+        .flat_map(|group| group.values())
+        .filter_map(|value| value.as_str()?.strip_prefix("local:"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The source directories a manifest lists, relative to its folder. A package's modules
+/// are always in `src`.
+fn source_directory_paths(manifest: &str) -> Vec<String> {
+    let json: serde_json::Value = match serde_json::from_str(manifest) {
+        Ok(json) => json,
+        Err(_) => return Vec::new(),
+    };
+    if json.get("type").and_then(|value| value.as_str()) == Some("package") {
+        return vec!["src".to_string()];
+    }
+    json.get("source-directories")
+        .and_then(|directories| directories.as_array())
+        .map(|directories| {
+            directories
+                .iter()
+                .filter_map(|directory| directory.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Resolve `.` and `..` without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const APP: &str = r#"{
+        "type": "application",
+        "source-directories": ["src", "../shared/src"],
+        "dependencies": {
+            "direct": { "gren-lang/core": "6.0.0", "example/ui": "local:../ui" },
+            "indirect": {}
+        }
+    }"#;
+    const UI: &str = r#"{
+        "type": "package",
+        "dependencies": { "example/theme": "local:./theme" }
+    }"#;
+
+    fn graph() -> ProjectGraph {
+        ProjectGraph::new(vec![
+            Project::from_manifest(Path::new("/repo/app"), APP),
+            Project::from_manifest(Path::new("/repo/ui"), UI),
+            Project::from_manifest(Path::new("/repo/ui/theme"), "{}"),
+            Project::from_manifest(Path::new("/repo/tools"), "{}"),
+        ])
+    }
+
+    #[test]
+    fn test_local_dependencies() {
+        let app = Project::from_manifest(Path::new("/repo/app"), APP);
+        assert_eq!(app.local_dependencies, vec![PathBuf::from("/repo/ui")]);
+        let ui = Project::from_manifest(Path::new("/repo/ui"), UI);
+        assert_eq!(ui.local_dependencies, vec![PathBuf::from("/repo/ui/theme")]);
+    }
+
+    #[test]
+    fn test_source_directories() {
+        let app = Project::from_manifest(Path::new("/repo/app"), APP);
+        assert_eq!(
+            app.source_directories,
+            vec![
+                PathBuf::from("/repo/app/src"),
+                PathBuf::from("/repo/shared/src")
+            ]
+        );
+        let ui = Project::from_manifest(Path::new("/repo/ui"), UI);
+        assert_eq!(ui.source_roots(), vec![PathBuf::from("/repo/ui/src")]);
+        let tools = Project::from_manifest(Path::new("/repo/tools"), "{}");
+        assert_eq!(tools.source_roots(), vec![PathBuf::from("/repo/tools")]);
+    }
+
+    #[test]
+    fn test_source_roots() {
+        let graph = graph();
+        assert_eq!(
+            graph.source_roots(Path::new("/repo")),
+            vec![
+                PathBuf::from("/repo/app/src"),
+                PathBuf::from("/repo/shared/src"),
+                PathBuf::from("/repo/tools"),
+                PathBuf::from("/repo/ui/src"),
+                PathBuf::from("/repo/ui/theme"),
+            ]
+        );
+        assert_eq!(
+            graph.source_roots(Path::new("/repo/ui")),
+            vec![
+                PathBuf::from("/repo/ui/src"),
+                PathBuf::from("/repo/ui/theme")
+            ]
+        );
+        // A folder without projects is scanned whole
+        assert_eq!(
+            graph.source_roots(Path::new("/elsewhere")),
+            vec![PathBuf::from("/elsewhere")]
+        );
+    }
+
+    #[test]
+    fn test_project_of() {
+        let graph = graph();
+        let root = |path: &str| {
+            graph
+                .project_of(Path::new(path))
+                .map(|project| project.root.clone())
+        };
+        assert_eq!(
+            root("/repo/ui/src/Button.gren"),
+            Some(PathBuf::from("/repo/ui"))
+        );
+        assert_eq!(
+            root("/repo/ui/theme/src/Colors.gren"),
+            Some(PathBuf::from("/repo/ui/theme"))
+        );
+        assert_eq!(root("/repo/README.md"), None);
+    }
+
+    #[test]
+    fn test_is_visible() {
+        let graph = graph();
+        let repo = Path::new("/repo");
+        let visible = |from: &str, to: &str| graph.is_visible(&repo.join(from), &repo.join(to));
+        assert!(visible("app/src/Main.gren", "app/src/Page.gren"));
+        assert!(visible("app/src/Main.gren", "ui/src/Button.gren"));
+        // Through the ui package
+        assert!(visible("app/src/Main.gren", "ui/theme/src/Colors.gren"));
+        assert!(!visible("ui/src/Button.gren", "app/src/Main.gren"));
+        assert!(!visible("app/src/Main.gren", "tools/src/Build.gren"));
+        // Installed packages aren't in any project
+        assert!(graph.is_visible(
+            &repo.join("app/src/Main.gren"),
+            Path::new("/cache/packages/core/src/Array.gren")
+        ));
+    }
+
+    #[test]
+    fn test_discover() {
+        let repo = TempDir::new().unwrap();
+        for (folder, manifest) in [("app", APP), ("ui", UI), ("ui/theme", "{}")] {
+            std::fs::create_dir_all(repo.path().join(folder)).unwrap();
+            std::fs::write(repo.path().join(folder).join("gren.json"), manifest).unwrap();
+        }
+        std::fs::create_dir_all(repo.path().join(".gren/packages")).unwrap();
+        std::fs::write(repo.path().join(".gren/packages/gren.json"), "{}").unwrap();
+
+        let graph = ProjectGraph::discover(&[repo.path().to_path_buf()]);
+        let roots: Vec<PathBuf> = graph
+            .projects()
+            .iter()
+            .map(|project| project.root.clone())
+            .collect();
+        assert_eq!(
+            roots,
+            vec![
+                repo.path().join("app"),
+                repo.path().join("ui"),
+                repo.path().join("ui/theme"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+//! Record updates setting fields the record doesn't have
+//!
+//! `{ model | count = 1 }` can only set fields `model` already has. When `model` is a
+//! parameter of an annotated function, or an annotated top-level value, and its type is a
+//! record alias, the alias's definition lists those fields, so a misspelled one is reported
+//! while it's typed instead of when the project is compiled. Gren doesn't allow shadowing,
+//! so a name in a function's body that is one of its parameters always means the
+//! parameter. Aliases are looked up in the index, the module's own first, then the one its
+//! imports bring in.
+
+use crate::syntax::{child_of_kind, node_range};
+use crate::type_usage::top_level_parts;
+use lsp_types::*;
+use std::collections::HashMap;
+use tree_sitter::{Node, Tree};
+
+/// Diagnostic code attached to updates of fields a record doesn't have
+pub const UNKNOWN_FIELD: &str = "unknown-record-field";
+
+/// A `{ record | field = value }` expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordUpdate {
+    /// The name of the updated record
+    pub record: String,
+    /// The name of the record's type, when an annotation gives it
+    pub type_name: Option<String>,
+    /// The fields set, with their names' ranges
+    pub fields: Vec<(String, Range)>,
+}
+
+impl RecordUpdate {
+    /// Hints for the fields set that aren't among `known_fields`, the fields of the
+    /// record's type
+    pub fn unknown_field_diagnostics(&self, known_fields: &[String]) -> Vec<Diagnostic> {
+        let type_name = self.type_name.as_deref().unwrap_or("its type");
+        self.fields
+            .iter()
+            .filter(|(field, _)| !known_fields.contains(field))
+            .map(|(field, range)| Diagnostic {
+                range: *range,
+                severity: Some(DiagnosticSeverity::HINT),
+                code: Some(NumberOrString::String(UNKNOWN_FIELD.to_string())),
+                code_description: None,
+                source: Some("gren-lsp".to_string()),
+                message: format!(
+                    "`{}` is a `{}`, which has no field `{}`. Its fields are {}.",
+                    self.record,
+                    type_name,
+                    field,
+                    known_fields.join(", ")
+                ),
+                related_information: None,
+                tags: None,
+                data: None,
+            })
+            .collect()
+    }
+}
+
+/// The record updates of a module, with the types of the records annotations give
+pub fn record_updates(tree: &Tree, source: &str) -> Vec<RecordUpdate> {
+    let root = tree.root_node();
+    let declarations = named_children(root);
+
+    let annotations: HashMap<&str, String> = declarations
+        .iter()
+        .filter(|node| node.kind() == "type_annotation")
+        .filter_map(|annotation| {
+            let name = text(child_of_kind(annotation, "lower_case_identifier")?, source)?;
+            let signature = text(child_of_kind(annotation, "type_expression")?, source)?;
+            let signature: Vec<&str> = signature.split_whitespace().collect();
+            Some((name, signature.join(" ")))
+        })
+        .collect();
+
+    // Annotated top-level values, `init : Model`
+    let mut top_level: HashMap<String, String> = HashMap::new();
+    for (name, signature) in &annotations {
+        if let [part] = top_level_parts(signature).as_slice() {
+            if let Some(type_name) = type_name(part) {
+                top_level.insert(name.to_string(), type_name);
+            }
+        }
+    }
+
+    let mut updates = Vec::new();
+    for declaration in declarations
+        .iter()
+        .filter(|node| node.kind() == "value_declaration")
+    {
+        let mut types = top_level.clone();
+        if let Some(left) = child_of_kind(declaration, "function_declaration_left") {
+            let name = child_of_kind(&left, "lower_case_identifier").and_then(|n| text(n, source));
+            if let Some(signature) = name.and_then(|name| annotations.get(name)) {
+                let parts = top_level_parts(signature);
+                let parameters = named_children(left).into_iter().skip(1);
+                for (parameter, part) in parameters.zip(&parts[..parts.len() - 1]) {
+                    if let (Some(parameter), Some(type_name)) =
+                        (parameter_name(parameter, source), type_name(part))
+                    {
+                        types.insert(parameter.to_string(), type_name);
+                    }
+                }
+            }
+        }
+        collect_updates(*declaration, source, &types, &mut updates);
+    }
+    updates
+}
+
+fn collect_updates(
+    node: Node,
+    source: &str,
+    types: &HashMap<String, String>,
+    updates: &mut Vec<RecordUpdate>,
+) {
+    if node.kind() == "record_expr" {
+        let record = child_of_kind(&node, "record_base_identifier").and_then(|n| text(n, source));
+        if let Some(record) = record {
+            let fields = named_children(node)
+                .into_iter()
+                .filter(|child| child.kind() == "field")
+                .filter_map(|field| {
+                    let name = child_of_kind(&field, "lower_case_identifier")?;
+                    Some((text(name, source)?.to_string(), node_range(&name, source)))
+                })
+                .collect();
+            updates.push(RecordUpdate {
+                record: record.to_string(),
+                type_name: types.get(record).cloned(),
+                fields,
+            });
+        }
+    }
+    for child in named_children(node) {
+        collect_updates(child, source, types, updates);
+    }
+}
+
+/// Field names of the record a type alias stands for, from its indexed signature such as
+/// `alias Model = { count : Int, name : String }`. `None` when it isn't a record.
+pub fn alias_fields(signature: &str) -> Option<Vec<String>> {
+    let (_, definition) = signature.split_once('=')?;
+    let fields = definition.trim().strip_prefix('{')?.strip_suffix('}')?;
+    // An extensible record's fields follow `r |`
+    let fields = match top_level_split(fields, '|').as_slice() {
+        [_, fields] => *fields,
+        _ => fields,
+    };
+
+    let names: Vec<String> = top_level_split(fields, ',')
+        .into_iter()
+        .filter_map(|field| {
+            let (name, _) = field.split_once(':')?;
+            let name = name.trim();
+            is_lower_name(name).then(|| name.to_string())
+        })
+        .collect();
+    if names.is_empty() {
+        None
+    } else {
+        Some(names)
+    }
+}
+
+/// The update a half-written `{ record | ... }` ending before byte `offset` is, when the
+/// cursor is where a field name goes: the record's name and the fields already set. Read
+/// from the tree's tokens, as the update is unfinished and the parser may not have made a
+/// record expression of it yet.
+pub fn field_position<'a>(
+    tree: &Tree,
+    source: &'a str,
+    offset: usize,
+) -> Option<(&'a str, Vec<&'a str>)> {
+    let mut token = token_before(tree, source, offset)?;
+    // The field name being typed
+    if token.kind() == "lower_case_identifier" && token.end_byte() == offset {
+        token = token_before(tree, source, token.start_byte())?;
+    }
+    if token.kind() != "|" && token.kind() != "," {
+        return None;
+    }
+
+    // Back to the brace the update opens, skipping over nested brackets. A field is set
+    // where a top-level `|` or `,` is followed by `name =`.
+    let mut set = Vec::new();
+    let mut depth = 0;
+    let (mut next, mut after): (Option<Node>, Option<Node>) = (None, None);
+    loop {
+        match token.kind() {
+            ")" | "]" | "}" => depth += 1,
+            "(" | "[" | "{" if depth > 0 => depth -= 1,
+            "{" => break,
+            "(" | "[" => return None,
+            "|" | "," if depth == 0 => {
+                if let (Some(name), Some(equals)) = (next, after) {
+                    if name.kind() == "lower_case_identifier" && equals.kind() == "=" {
+                        set.push(text(name, source)?);
+                    }
+                }
+            }
+            _ => {}
+        }
+        if !token.kind().ends_with("comment") {
+            after = next;
+            next = Some(token);
+        }
+        token = token_before(tree, source, token.start_byte())?;
+    }
+
+    let record = next.filter(|name| name.kind() == "lower_case_identifier")?;
+    after.filter(|bar| bar.kind() == "|")?;
+    set.reverse();
+    Some((text(record, source)?, set))
+}
+
+/// The name of the type a part of a signature refers to, qualified as written, `None` for
+/// type variables, records and functions
+fn type_name(part: &str) -> Option<String> {
+    let mut part = part.trim();
+    while let Some(inner) = part.strip_prefix('(').and_then(|p| p.strip_suffix(')')) {
+        part = inner.trim();
+    }
+    let head = part.split_whitespace().next()?;
+    let is_name = head
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+    let name = head.rsplit('.').next().unwrap_or(head);
+    if is_name && name.starts_with(|c: char| c.is_uppercase()) {
+        Some(head.to_string())
+    } else {
+        None
+    }
+}
+
+fn parameter_name<'a>(parameter: Node, source: &'a str) -> Option<&'a str> {
+    match parameter.kind() {
+        "lower_case_identifier" => text(parameter, source),
+        "lower_pattern" => text(child_of_kind(&parameter, "lower_case_identifier")?, source),
+        _ => None,
+    }
+}
+
+fn is_lower_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_lowercase())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Parts of `text` separated by `separator` outside any parentheses, braces or brackets
+fn top_level_split(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            _ if c == separator && depth == 0 => {
+                parts.push(text[start..index].trim());
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts
+}
+
+fn named_children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect();
+    children
+}
+
+/// The token ending at the last non-whitespace byte before `offset`
+fn token_before<'t>(tree: &'t Tree, source: &str, offset: usize) -> Option<Node<'t>> {
+    let end = source[..offset].trim_end().len();
+    if end == 0 {
+        return None;
+    }
+    tree.root_node()
+        .descendant_for_byte_range(end - 1, end)
+        .filter(|token| token.child_count() == 0)
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_alias_fields() {
+        let signature = "alias Model =\n    { count : Int\n    , names : Array { first : String, last : String }\n    }";
+        assert_eq!(
+            alias_fields(signature),
+            Some(vec!["count".to_string(), "names".to_string()])
+        );
+        assert_eq!(
+            alias_fields("alias Named r = { r | name : String }"),
+            Some(vec!["name".to_string()])
+        );
+        assert_eq!(alias_fields("alias Id = String"), None);
+    }
+
+    /// The update and fields set at the end of `body`, the body of a function of `model`
+    fn field_position_at(body: &str) -> Option<(String, Vec<String>)> {
+        let source = format!("module Main exposing (..)\n\nupdate model =\n{}", body);
+        let tree = Parser::new().unwrap().parse(&source).unwrap().unwrap();
+        field_position(&tree, &source, source.len()).map(|(record, set)| {
+            (
+                record.to_string(),
+                set.into_iter().map(str::to_string).collect(),
+            )
+        })
+    }
+
+    #[test]
+    fn test_field_position() {
+        assert_eq!(
+            field_position_at("    { model | "),
+            Some(("model".to_string(), vec![]))
+        );
+        assert_eq!(
+            field_position_at("    { model\n        | count = model.count + 1\n        , na"),
+            Some(("model".to_string(), vec!["count".to_string()]))
+        );
+        assert_eq!(
+            field_position_at("    { model | items = Array.map (\\{ id } -> id) items, "),
+            Some(("model".to_string(), vec!["items".to_string()]))
+        );
+        assert_eq!(
+            field_position_at("    { model | name = \"{ a | \", "),
+            Some(("model".to_string(), vec!["name".to_string()]))
+        );
+        assert_eq!(field_position_at("    { model | count = "), None);
+        assert_eq!(field_position_at("    { count = 1, "), None);
+        assert_eq!(field_position_at("    ( a, "), None);
+    }
+
+    #[test]
+    fn test_unknown_field_diagnostics() {
+        let update = RecordUpdate {
+            record: "model".to_string(),
+            type_name: Some("Model".to_string()),
+            fields: vec![
+                (
+                    "count".to_string(),
+                    Range::new(Position::new(4, 14), Position::new(4, 19)),
+                ),
+                (
+                    "nmae".to_string(),
+                    Range::new(Position::new(4, 31), Position::new(4, 35)),
+                ),
+            ],
+        };
+        let diagnostics =
+            update.unknown_field_diagnostics(&["count".to_string(), "name".to_string()]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(4, 31));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(
+            diagnostics[0].message,
+            "`model` is a `Model`, which has no field `nmae`. Its fields are count, name."
+        );
+    }
+
+    #[test]
+    fn test_record_updates() {
+        let source = "module Main exposing (..)
+
+type alias Model =
+    { count : Int, name : String }
+
+init : Model
+init =
+    { count = 0, name = \"\" }
+
+update : Msg -> Model -> Model
+update msg model =
+    { model | count = model.count + 1, nmae = \"\" }
+
+reset =
+    { init | count = 0 }
+
+rename other =
+    { other | name = \"\" }
+";
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        let updates = record_updates(&tree, source);
+
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].record, "model");
+        assert_eq!(updates[0].type_name.as_deref(), Some("Model"));
+        assert_eq!(
+            updates[0].fields,
+            vec![
+                (
+                    "count".to_string(),
+                    Range::new(Position::new(11, 14), Position::new(11, 19))
+                ),
+                (
+                    "nmae".to_string(),
+                    Range::new(Position::new(11, 39), Position::new(11, 43))
+                ),
+            ]
+        );
+        assert_eq!(updates[1].type_name.as_deref(), Some("Model"));
+        assert_eq!(updates[2].type_name, None);
+    }
+}
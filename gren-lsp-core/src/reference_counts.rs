@@ -0,0 +1,72 @@
+//! How often each top-level symbol is used, for the hover footer
+//!
+//! Counting the uses of a symbol means searching every file that can see it, which is too
+//! slow to repeat on every hover. A count only changes when the file defining the symbol,
+//! or a file importing its module, is re-indexed. When a file is indexed the workspace
+//! drops the counts of its own symbols and of the modules it imported before and after
+//! the change, going by the module graph.
+
+use lsp_types::Url;
+use std::collections::HashMap;
+
+/// Cached use counts, by the file defining the symbol and the symbol's name
+#[derive(Debug, Default)]
+pub struct ReferenceCounts {
+    counts: HashMap<Url, HashMap<String, usize>>,
+}
+
+impl ReferenceCounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached count for `name` defined in `definition`
+    pub fn get(&self, definition: &Url, name: &str) -> Option<usize> {
+        self.counts.get(definition)?.get(name).copied()
+    }
+
+    pub fn insert(&mut self, definition: Url, name: &str, count: usize) {
+        self.counts
+            .entry(definition)
+            .or_default()
+            .insert(name.to_string(), count);
+    }
+
+    /// Drop the counts of every symbol `definition` defines
+    pub fn invalidate(&mut self, definition: &Url) {
+        self.counts.remove(definition);
+    }
+
+    /// Number of cached counts
+    pub fn len(&self) -> usize {
+        self.counts.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_dropped_per_defining_file() {
+        let main = Url::parse("file:///project/src/Main.gren").unwrap();
+        let utils = Url::parse("file:///project/src/Utils.gren").unwrap();
+
+        let mut counts = ReferenceCounts::new();
+        counts.insert(main.clone(), "main", 0);
+        counts.insert(utils.clone(), "helper", 17);
+        counts.insert(utils.clone(), "format", 2);
+        assert_eq!(counts.get(&utils, "helper"), Some(17));
+        assert_eq!(counts.get(&main, "helper"), None);
+        assert_eq!(counts.len(), 3);
+
+        counts.invalidate(&utils);
+        assert_eq!(counts.get(&utils, "helper"), None);
+        assert_eq!(counts.get(&main, "main"), Some(0));
+        assert_eq!(counts.len(), 1);
+    }
+}
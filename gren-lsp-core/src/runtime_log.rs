@@ -0,0 +1,57 @@
+//! Values logged by a running program, for showing `Debug.log` output next to the code
+//!
+//! A runtime harness watching the program forwards each `Debug.log` line to the server as a
+//! `gren/runtimeLog` notification. Only the last few values per tag are kept, enough to see
+//! how a value changed without holding on to a long-running program's whole output.
+
+use std::collections::{HashMap, VecDeque};
+
+/// Number of values kept per tag
+const VALUES_PER_TAG: usize = 3;
+
+/// Recent values by `Debug.log` tag
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeLog {
+    values: HashMap<String, VecDeque<String>>,
+}
+
+impl RuntimeLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a value logged under `tag`, dropping the oldest one for that tag if it's full
+    pub fn record(&mut self, tag: &str, value: String) {
+        let values = self.values.entry(tag.to_string()).or_default();
+        if values.len() == VALUES_PER_TAG {
+            values.pop_front();
+        }
+        values.push_back(value);
+    }
+
+    /// Values logged under `tag`, most recent first
+    pub fn recent(&self, tag: &str) -> Vec<&str> {
+        self.values
+            .get(tag)
+            .map(|values| values.iter().rev().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_values_newest_first_and_capped() {
+        let mut log = RuntimeLog::new();
+        for count in 1..=4 {
+            log.record("count", count.to_string());
+        }
+        log.record("model", "{ count = 4 }".to_string());
+
+        assert_eq!(log.recent("count"), vec!["4", "3", "2"]);
+        assert_eq!(log.recent("model").len(), 1);
+        assert!(log.recent("missing").is_empty());
+    }
+}
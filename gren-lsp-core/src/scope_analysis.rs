@@ -0,0 +1,79 @@
+//! Resolved references to top-level names, for rename and find-references
+//!
+//! Each occurrence of a top-level value, type or constructor is resolved to the module
+//! defining it when its file is indexed, so renaming `Main.view` touches the occurrences
+//! that resolve to it and not a local or another module's `view`. The resolution itself
+//! lives in `gren_analysis::references`; this module puts its results in LSP terms.
+
+use crate::syntax::text_range;
+use gren_analysis::references::module_references;
+use lsp_types::*;
+use tree_sitter::Tree;
+
+pub use gren_analysis::references::ReferenceKind;
+
+/// An occurrence of `module`'s top-level `name`, its definition included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolReference {
+    pub name: String,
+    /// The module defining the name
+    pub module: String,
+    /// Just the name, without any qualifier
+    pub location: Location,
+    pub kind: ReferenceKind,
+    /// The top-level value, type or type alias whose definition the name occurs in
+    pub container: Option<String>,
+}
+
+impl SymbolReference {
+    /// Whether the occurrence names a type rather than a value or constructor. A type and
+    /// one of its constructors can share a name, `type Id = Id String`, and are renamed
+    /// separately.
+    pub fn names_type(&self) -> bool {
+        match self.kind {
+            ReferenceKind::Type => true,
+            // Constructors are never listed by name
+            ReferenceKind::Exposing => self.name.starts_with(char::is_uppercase),
+            _ => false,
+        }
+    }
+}
+
+/// Resolve every occurrence of a top-level name in a file
+pub fn extract_references(tree: &Tree, source: &str, uri: &Url) -> Vec<SymbolReference> {
+    module_references(tree, source)
+        .into_iter()
+        .map(|reference| SymbolReference {
+            name: reference.name,
+            module: reference.module,
+            location: Location::new(uri.clone(), text_range(reference.range, source)),
+            kind: reference.kind,
+            container: reference.container,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(name: &str, kind: ReferenceKind) -> SymbolReference {
+        SymbolReference {
+            name: name.to_string(),
+            module: "Main".to_string(),
+            location: Location::new(Url::parse("file:///Main.gren").unwrap(), Range::default()),
+            kind,
+            container: None,
+        }
+    }
+
+    #[test]
+    fn test_names_type() {
+        assert!(reference("Id", ReferenceKind::Type).names_type());
+        assert!(reference("Id", ReferenceKind::Exposing).names_type());
+        assert!(!reference("id", ReferenceKind::Exposing).names_type());
+        assert!(!reference("Id", ReferenceKind::Pattern).names_type());
+        assert!(!reference("Id", ReferenceKind::Expression).names_type());
+        assert!(!reference("Id", ReferenceKind::Declaration).names_type());
+    }
+}
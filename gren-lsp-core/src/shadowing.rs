@@ -0,0 +1,174 @@
+//! Local names that shadow a top-level or imported name
+//!
+//! Gren doesn't allow shadowing, so a parameter or `let` binding reusing the name of a
+//! top-level value or an exposed import is a compile error. Finding them from the syntax
+//! tree reports them as soon as they're typed, without waiting for the compiler, and gives
+//! the quick fix everything it needs to rename the local. The analysis itself lives in
+//! `gren_analysis::scope`; this module puts its results in LSP terms.
+
+use crate::syntax::text_range;
+use gren_analysis::scope;
+use lsp_types::*;
+use tree_sitter::Tree;
+
+pub use gren_analysis::scope::{ShadowedName, ShadowingKind};
+
+/// Diagnostic code attached to shadowing errors
+pub const SHADOWED_NAME: &str = "shadowed-name";
+
+/// A local name that shadows a top-level or imported one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowedBinding {
+    pub name: String,
+    pub kind: ShadowingKind,
+    pub shadows: ShadowedName,
+    /// The local name where it's bound
+    pub binding_range: Range,
+    /// The top-level definition or the import clause of the shadowed name
+    pub shadowed_range: Range,
+    /// The binding and every use of the local in its scope, everything a rename rewrites
+    pub occurrences: Vec<Range>,
+    /// New names for the local, free in its scope, the first being `name_`
+    pub suggestions: Vec<String>,
+}
+
+impl ShadowedBinding {
+    pub fn diagnostic(&self, uri: &Url) -> Diagnostic {
+        let (shadowed, related) = match &self.shadows {
+            ShadowedName::TopLevel => (
+                format!("the top-level `{}`", self.name),
+                format!("`{}` is defined here", self.name),
+            ),
+            ShadowedName::Imported(module_name) => (
+                format!("`{}` imported from `{}`", self.name, module_name),
+                format!("`{}` is imported here", self.name),
+            ),
+        };
+
+        Diagnostic {
+            range: self.binding_range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(SHADOWED_NAME.to_string())),
+            code_description: None,
+            source: Some("gren-lsp".to_string()),
+            message: format!(
+                "The {} `{}` shadows {}. Gren doesn't allow shadowing, so the {} needs a different name.",
+                self.kind.description(),
+                self.name,
+                shadowed,
+                self.kind.description()
+            ),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), self.shadowed_range),
+                message: related,
+            }]),
+            tags: None,
+            data: None,
+        }
+    }
+}
+
+/// Report parameters and `let` bindings that reuse a top-level or imported name.
+/// `exposed_values` lists the values of a module, for imports that expose everything
+/// with `exposing (..)`.
+pub fn shadowed_bindings(
+    tree: &Tree,
+    source: &str,
+    exposed_values: impl Fn(&str) -> Vec<String>,
+) -> Vec<ShadowedBinding> {
+    scope::shadowed_bindings(tree, source, exposed_values)
+        .into_iter()
+        .map(|binding| ShadowedBinding {
+            name: binding.name,
+            kind: binding.kind,
+            shadows: binding.shadows,
+            binding_range: text_range(binding.binding_range, source),
+            shadowed_range: text_range(binding.shadowed_range, source),
+            occurrences: binding
+                .occurrences
+                .into_iter()
+                .map(|range| text_range(range, source))
+                .collect(),
+            suggestions: binding.suggestions,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    const SOURCE: &str = r#"module Main exposing (update)
+
+import Html exposing (text)
+import Model exposing (..)
+
+update : Msg -> Model -> Model
+update msg model =
+    let
+        text =
+            String.fromInt model.count
+    in
+    { model | label = text }
+
+view model =
+    (\update -> update) model
+"#;
+
+    fn bindings() -> Vec<ShadowedBinding> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        shadowed_bindings(&tree, SOURCE, |module_name| {
+            if module_name == "Model" {
+                vec!["init".to_string()]
+            } else {
+                Vec::new()
+            }
+        })
+    }
+
+    #[test]
+    fn test_let_binding_shadowing_an_import() {
+        let found = bindings();
+        let text = found.iter().find(|binding| binding.name == "text").unwrap();
+        assert_eq!(text.kind, ShadowingKind::LetBinding);
+        assert_eq!(text.shadows, ShadowedName::Imported("Html".to_string()));
+        assert_eq!(text.binding_range.start, Position::new(8, 8));
+        assert_eq!(
+            text.occurrences
+                .iter()
+                .map(|range| range.start)
+                .collect::<Vec<_>>(),
+            vec![Position::new(8, 8), Position::new(11, 22)]
+        );
+        assert_eq!(text.suggestions, vec!["text_", "localText"]);
+    }
+
+    #[test]
+    fn test_parameters_shadowing_top_level_names() {
+        let found = bindings();
+        let names: Vec<(&str, ShadowingKind)> = found
+            .iter()
+            .map(|binding| (binding.name.as_str(), binding.kind))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("text", ShadowingKind::LetBinding),
+                ("update", ShadowingKind::Parameter),
+            ]
+        );
+
+        let update = &found[1];
+        assert_eq!(update.shadows, ShadowedName::TopLevel);
+        assert_eq!(update.shadowed_range.start, Position::new(6, 0));
+        assert_eq!(update.occurrences.len(), 2);
+        assert_eq!(
+            update
+                .diagnostic(&Url::parse("file:///src/Main.gren").unwrap())
+                .code,
+            Some(NumberOrString::String(SHADOWED_NAME.to_string()))
+        );
+    }
+}
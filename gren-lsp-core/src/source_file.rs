@@ -0,0 +1,258 @@
+//! Reading Gren sources from disk
+//!
+//! Positions sent over LSP count `\r\n` and a lone `\r` as line breaks, while tree-sitter
+//! only starts a new row at `\n`. A `\r\n` ends the line at the same row for both, so
+//! sources keep the client's line endings and only a lone `\r` is swapped for `\n`, which
+//! moves no position. The server writes `\n` in the edits it makes, and gives them the
+//! file's line ending before they go out. A file that can't be read, or isn't UTF-8, is
+//! skipped with a reason rather than failing the whole workspace scan.
+
+use lsp_types::{DocumentChangeOperation, DocumentChanges, OneOf, TextEdit, Url, WorkspaceEdit};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Why a file was left out of the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    PermissionDenied,
+    NotUtf8,
+    /// Any other error reading or indexing the file
+    Unreadable(String),
+}
+
+impl SkipReason {
+    pub fn from_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => SkipReason::PermissionDenied,
+            io::ErrorKind::InvalidData => SkipReason::NotUtf8,
+            _ => SkipReason::Unreadable(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::PermissionDenied => write!(f, "permission denied"),
+            SkipReason::NotUtf8 => write!(f, "not valid UTF-8"),
+            SkipReason::Unreadable(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// A file or directory the workspace scan couldn't take in
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// What a workspace scan indexed and what it had to leave out
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanSummary {
+    pub indexed: usize,
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl ScanSummary {
+    pub fn skip(&mut self, path: impl Into<PathBuf>, reason: SkipReason) {
+        self.skipped.push(SkippedFile {
+            path: path.into(),
+            reason,
+        });
+    }
+
+    /// Add what another scan indexed and skipped, for one summary of several folders
+    pub fn merge(&mut self, other: ScanSummary) {
+        self.indexed += other.indexed;
+        self.skipped.extend(other.skipped);
+    }
+
+    /// One line for the end of the progress report, e.g. `Indexed 12 files, skipped 1`
+    pub fn message(&self) -> String {
+        let files = if self.indexed == 1 { "file" } else { "files" };
+        if self.skipped.is_empty() {
+            format!("Indexed {} {}", self.indexed, files)
+        } else {
+            format!(
+                "Indexed {} {}, skipped {}",
+                self.indexed,
+                files,
+                self.skipped.len()
+            )
+        }
+    }
+}
+
+/// Replace lone `\r` line breaks with `\n`, keeping `\r\n`. Each is one byte, so no
+/// position moves.
+pub fn normalize_line_endings(text: &str) -> Cow<'_, str> {
+    let bytes = text.as_bytes();
+    let lone = |i: usize| bytes[i] == b'\r' && bytes.get(i + 1) != Some(&b'\n');
+    if !(0..bytes.len()).any(lone) {
+        return Cow::Borrowed(text);
+    }
+    let normalized: Vec<u8> = (0..bytes.len())
+        .map(|i| if lone(i) { b'\n' } else { bytes[i] })
+        .collect();
+    // Only ASCII bytes were swapped
+    Cow::Owned(String::from_utf8(normalized).unwrap_or_else(|_| text.to_string()))
+}
+
+/// The line ending `text` uses, going by its first line break
+pub fn line_ending(text: &str) -> &'static str {
+    match text.find('\n') {
+        Some(end) if text[..end].ends_with('\r') => "\r\n",
+        _ => "\n",
+    }
+}
+
+/// `text` with its `\n` line breaks written as `ending`
+pub fn with_line_ending<'a>(text: &'a str, ending: &str) -> Cow<'a, str> {
+    if ending == "\n" || !text.contains('\n') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.replace("\r\n", "\n").replace('\n', ending))
+}
+
+/// Give the text of `edit` the line ending of the file it changes
+pub fn restore_text_edit_ending(edit: &mut TextEdit, ending: &str) {
+    if let Cow::Owned(text) = with_line_ending(&edit.new_text, ending) {
+        edit.new_text = text;
+    }
+}
+
+/// Give the text of every edit in `edit` the line ending of the file it changes
+pub fn restore_line_endings(edit: &mut WorkspaceEdit, ending_of: impl Fn(&Url) -> &'static str) {
+    if let Some(changes) = &mut edit.changes {
+        for (uri, edits) in changes.iter_mut() {
+            let ending = ending_of(uri);
+            for edit in edits {
+                restore_text_edit_ending(edit, ending);
+            }
+        }
+    }
+    let document_edits: Vec<_> = match &mut edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.iter_mut().collect(),
+        Some(DocumentChanges::Operations(operations)) => operations
+            .iter_mut()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    for document_edit in document_edits {
+        let ending = ending_of(&document_edit.text_document.uri);
+        for edit in &mut document_edit.edits {
+            match edit {
+                OneOf::Left(edit) => restore_text_edit_ending(edit, ending),
+                OneOf::Right(annotated) => {
+                    restore_text_edit_ending(&mut annotated.text_edit, ending)
+                }
+            }
+        }
+    }
+}
+
+/// Turn a file's bytes into source text: UTF-8 without a byte order mark, with line
+/// endings normalized as `normalize_line_endings` does
+pub fn decode_source(bytes: Vec<u8>) -> Result<String, SkipReason> {
+    let text = String::from_utf8(bytes).map_err(|_| SkipReason::NotUtf8)?;
+    let text = match text.strip_prefix('\u{feff}') {
+        Some(stripped) => stripped.to_string(),
+        None => text,
+    };
+    Ok(match normalize_line_endings(&text) {
+        Cow::Borrowed(_) => text,
+        Cow::Owned(normalized) => normalized,
+    })
+}
+
+/// Read a source file from disk, normalized as `decode_source` does
+pub fn read_source(path: &Path) -> Result<String, SkipReason> {
+    let bytes = std::fs::read(path).map_err(|e| SkipReason::from_io(&e))?;
+    decode_source(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_endings_normalized() {
+        assert!(matches!(
+            normalize_line_endings("main =\n    1\n"),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            normalize_line_endings("main =\r\n    1\r\rx = 2\r\n"),
+            "main =\r\n    1\n\nx = 2\r\n"
+        );
+    }
+
+    #[test]
+    fn test_edits_get_the_file_line_ending() {
+        assert_eq!(line_ending("main =\r\n    1\r\n"), "\r\n");
+        assert_eq!(line_ending("main =\n    1\r\n"), "\n");
+        assert_eq!(line_ending("main = 1"), "\n");
+
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let insert = |text: &str| TextEdit {
+            range: lsp_types::Range::default(),
+            new_text: text.to_string(),
+        };
+        let mut edit = WorkspaceEdit {
+            changes: Some(
+                [(
+                    uri.clone(),
+                    vec![insert("import Dict\n"), insert("x\r\ny\n")],
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            ..Default::default()
+        };
+        restore_line_endings(&mut edit, |_| "\r\n");
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].new_text, "import Dict\r\n");
+        assert_eq!(edits[1].new_text, "x\r\ny\r\n");
+    }
+
+    #[test]
+    fn test_decode_source() {
+        assert_eq!(
+            decode_source(b"\xef\xbb\xbfmodule Main exposing (..)\r\n".to_vec()),
+            Ok("module Main exposing (..)\r\n".to_string())
+        );
+        assert_eq!(
+            decode_source(b"main = \"\xff\"".to_vec()),
+            Err(SkipReason::NotUtf8)
+        );
+    }
+
+    #[test]
+    fn test_scan_summary_message() {
+        let mut summary = ScanSummary {
+            indexed: 1,
+            ..Default::default()
+        };
+        assert_eq!(summary.message(), "Indexed 1 file");
+
+        summary.indexed = 12;
+        summary.skip("/project/src/Secret.gren", SkipReason::PermissionDenied);
+        assert_eq!(summary.message(), "Indexed 12 files, skipped 1");
+        assert_eq!(summary.skipped[0].reason.to_string(), "permission denied");
+
+        let mut other = ScanSummary {
+            indexed: 3,
+            ..Default::default()
+        };
+        other.skip("/library/src/Latin1.gren", SkipReason::NotUtf8);
+        summary.merge(other);
+        assert_eq!(summary.message(), "Indexed 15 files, skipped 2");
+    }
+}
@@ -0,0 +1,212 @@
+//! Finding and rewriting string literals
+//!
+//! Gren apps often keep translation keys and route paths in plain strings, so renaming one
+//! means changing every literal spelling the same value. Literals are compared by the
+//! string they produce, so `"a\u{0022}"` and `"a\""` are the same key, and a replacement is
+//! escaped for the quotes of each literal it's written into. Only string literal nodes are
+//! looked at, never comments or identifiers that happen to contain the text.
+
+use crate::syntax::{child_of_kind, point_to_position, position_to_point};
+use lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+/// A string literal in a module
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringLiteral {
+    /// The string the literal produces, with escapes resolved
+    pub value: String,
+    /// Between the quotes
+    pub contents: Range,
+    /// Written with `"""`
+    pub multiline: bool,
+}
+
+/// The literal containing `position`. `None` when there's none there, or its escapes are
+/// invalid so its value isn't known.
+pub fn string_literal_at(tree: &Tree, source: &str, position: Position) -> Option<StringLiteral> {
+    let point = position_to_point(position, source);
+    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    while node.kind() != "string_constant_expr" {
+        node = node.parent()?;
+    }
+    string_literal(&node, source)
+}
+
+/// Every literal in the module whose escapes are valid, in source order
+pub fn string_literals(tree: &Tree, source: &str) -> Vec<StringLiteral> {
+    let mut literals = Vec::new();
+    collect_literals(tree.root_node(), source, &mut literals);
+    literals
+}
+
+/// Edits replacing every literal producing `value` with one producing `new_value`
+pub fn rename_string_literals(
+    tree: &Tree,
+    source: &str,
+    value: &str,
+    new_value: &str,
+) -> Vec<TextEdit> {
+    string_literals(tree, source)
+        .into_iter()
+        .filter(|literal| literal.value == value)
+        .map(|literal| {
+            TextEdit::new(
+                literal.contents,
+                escape_contents(new_value, literal.multiline),
+            )
+        })
+        .collect()
+}
+
+/// Spell `value` out for the inside of a literal
+pub fn escape_contents(value: &str, multiline: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' if !multiline => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' if !multiline => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn collect_literals(node: Node, source: &str, literals: &mut Vec<StringLiteral>) {
+    if node.kind() == "string_constant_expr" {
+        literals.extend(string_literal(&node, source));
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_literals(child, source, literals);
+    }
+}
+
+fn string_literal(node: &Node, source: &str) -> Option<StringLiteral> {
+    let open = child_of_kind(node, "open_quote")?;
+    let close = child_of_kind(node, "close_quote")?;
+
+    let mut value = String::new();
+    let mut cursor = node.walk();
+    for part in node.named_children(&mut cursor) {
+        let text = part.utf8_text(source.as_bytes()).ok()?;
+        match part.kind() {
+            "regular_string_part" => value.push_str(text),
+            "string_escape" => value.push(unescape(text)?),
+            "open_quote" | "close_quote" => {}
+            _ => return None,
+        }
+    }
+
+    Some(StringLiteral {
+        value,
+        contents: Range::new(
+            point_to_position(open.end_position(), source),
+            point_to_position(close.start_position(), source),
+        ),
+        multiline: open.end_byte() - open.start_byte() == 3,
+    })
+}
+
+/// The character an escape sequence such as `\n` or `\u{1F600}` stands for
+fn unescape(escape: &str) -> Option<char> {
+    let sequence = escape.strip_prefix('\\')?;
+    match sequence {
+        "n" => Some('\n'),
+        "r" => Some('\r'),
+        "t" => Some('\t'),
+        "\"" => Some('"'),
+        "'" => Some('\''),
+        "\\" => Some('\\'),
+        _ => {
+            let hex = sequence.strip_prefix("u{")?.strip_suffix('}')?;
+            char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    const SOURCE: &str = r#"module Routes exposing (..)
+
+-- "home" is the landing page
+home =
+    "home"
+
+
+title =
+    Translations.get "home" "Home page"
+
+
+escaped =
+    "ho\u{006D}e"
+
+
+quoted =
+    """home"""
+"#;
+
+    fn parse(source: &str) -> Tree {
+        let mut parser = Parser::new().unwrap();
+        parser.parse(source).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_literal_at_position() {
+        let tree = parse(SOURCE);
+        let literal = string_literal_at(&tree, SOURCE, Position::new(4, 6)).unwrap();
+        assert_eq!(literal.value, "home");
+        assert_eq!(
+            literal.contents,
+            Range::new(Position::new(4, 5), Position::new(4, 9))
+        );
+        assert!(!literal.multiline);
+
+        // Comments aren't literals
+        assert_eq!(string_literal_at(&tree, SOURCE, Position::new(2, 5)), None);
+        assert_eq!(
+            string_literal_at(&tree, SOURCE, Position::new(12, 8))
+                .unwrap()
+                .value,
+            "home"
+        );
+    }
+
+    #[test]
+    fn test_literal_after_non_ascii_text() {
+        let source = "module Routes exposing (..)\n\ngreeting =\n    Translations.get \"héllo 😀\" \"home\"\n";
+        let tree = parse(source);
+        // Positions count UTF-16 code units: é is one and 😀 two, though they take six bytes
+        let literal = string_literal_at(&tree, source, Position::new(3, 34)).unwrap();
+        assert_eq!(literal.value, "home");
+        assert_eq!(
+            literal.contents,
+            Range::new(Position::new(3, 33), Position::new(3, 37))
+        );
+    }
+
+    #[test]
+    fn test_rename_matches_values_not_spelling() {
+        let tree = parse(SOURCE);
+        let edits = rename_string_literals(&tree, SOURCE, "home", "start \"page\"");
+        let lines: Vec<u32> = edits.iter().map(|edit| edit.range.start.line).collect();
+        assert_eq!(lines, vec![4, 8, 12, 16]);
+        assert_eq!(edits[0].new_text, "start \\\"page\\\"");
+        assert!(rename_string_literals(&tree, SOURCE, "Home", "x").is_empty());
+    }
+
+    #[test]
+    fn test_escape_contents() {
+        assert_eq!(escape_contents("a\\b\n\"c\"", false), "a\\\\b\\n\\\"c\\\"");
+        assert_eq!(escape_contents("line\n\tnext", true), "line\n\tnext");
+        assert_eq!(unescape("\\u{1F600}"), Some('😀'));
+        assert_eq!(unescape("\\x"), None);
+    }
+}
@@ -1,11 +1,19 @@
-use anyhow::{Context, Result};
+use crate::comments::DeclarationComments;
+use crate::scope_analysis::{ReferenceKind, SymbolReference};
+use crate::symbol_query::{ModuleFilter, SymbolQuery};
+use crate::symbol_store::IndexStats;
+use crate::syntax::text_range;
+use crate::todos::{TodoItem, TodoMarker};
+use crate::type_usage::{type_usage, TypeUsage};
+use anyhow::Result;
+use gren_analysis::declarations::{Declaration, DeclarationExtractor, DeclarationKind};
 use lsp_types::*;
-use rusqlite::{Connection, Result as SqlResult};
+use rusqlite::{Connection, DatabaseName, OptionalExtension, Result as SqlResult};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use tracing::debug;
-use tree_sitter::{Query, QueryCursor, Tree};
+use tracing::{debug, info};
+use tree_sitter::{Node, Tree};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Symbol {
@@ -17,6 +25,58 @@ pub struct Symbol {
     pub documentation: Option<String>,
 }
 
+impl Symbol {
+    /// The deprecation note from a `{-| @deprecated use newThing instead -}` doc comment.
+    /// Empty when the symbol is deprecated without saying what to use instead.
+    pub fn deprecation(&self) -> Option<&str> {
+        deprecation_message(self.documentation.as_deref()?)
+    }
+
+    /// How the signature of a function or value uses the type `type_name`, `None` when it
+    /// doesn't mention it
+    pub fn type_usage(&self, type_name: &str) -> Option<TypeUsage> {
+        if self.kind != SymbolKind::FUNCTION {
+            return None;
+        }
+        type_usage(self.type_signature.as_deref()?, type_name)
+    }
+}
+
+/// Text following the `@deprecated` tag of a documentation comment, if it has one
+pub fn deprecation_message(documentation: &str) -> Option<&str> {
+    documentation.lines().find_map(|line| {
+        let message = line.trim().strip_prefix("@deprecated")?;
+        // `@deprecatedFoo` is a different tag
+        if message.starts_with(|c: char| !c.is_whitespace()) {
+            return None;
+        }
+        Some(message.trim())
+    })
+}
+
+/// Version of the database layout `open` creates. Version 0 is the original layout, which
+/// repeated the file URI and every range coordinate in each row; version 2 added
+/// `symbol_references`, version 3 the kind of each reference, version 4 the top-level
+/// declaration each reference is in, and version 5 the comments of declarations in
+/// `symbol_docs`.
+const SCHEMA_VERSION: i64 = 5;
+
+/// Prepared statements kept for reuse, enough for every query the index runs
+const STATEMENT_CACHE_CAPACITY: usize = 32;
+
+/// Bits of a packed position holding the character, the line taking the bits above them
+const CHARACTER_BITS: u32 = 20;
+const MAX_CHARACTER: u32 = (1 << CHARACTER_BITS) - 1;
+
+/// Columns of a symbol row, in the order `row_to_symbol` reads them
+const SELECT_SYMBOLS: &str = "SELECT s.name, s.kind, f.uri, s.range_start, s.range_end,
+     s.container_name, s.type_signature, s.documentation
+     FROM symbols s JOIN files f ON f.id = s.file_id";
+
+/// Symbols stored in sqlite. File URIs and module names are stored once in tables of
+/// their own and referred to by id, ranges are packed into two integers, and absent
+/// fields are `NULL`, which keeps the database small for monorepos with hundreds of
+/// thousands of symbols.
 pub struct SymbolIndex {
     connection: Arc<Mutex<Connection>>,
 }
@@ -24,124 +84,108 @@ pub struct SymbolIndex {
 impl SymbolIndex {
     pub fn new() -> Result<Self> {
         // TODO: Use proper data directory
-        let db_path = PathBuf::from("gren-lsp-symbols.db");
-        let connection = Connection::open(db_path)?;
-
-        // Create tables
-        connection.execute(
-            "CREATE TABLE IF NOT EXISTS symbols (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                kind TEXT NOT NULL,
-                file_uri TEXT NOT NULL,
-                start_line INTEGER NOT NULL,
-                start_character INTEGER NOT NULL,
-                end_line INTEGER NOT NULL,
-                end_character INTEGER NOT NULL,
-                container_name TEXT,
-                type_signature TEXT,
-                documentation TEXT
-            )",
-            [],
-        )?;
+        Self::open(Path::new("gren-lsp-symbols.db"))
+    }
 
-        connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_symbols_name ON symbols(name)",
-            [],
-        )?;
+    /// Open the database at `path`, creating it or converting it from an older layout
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut connection = Connection::open(path)?;
+        connection.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        migrate(&mut connection)?;
 
         Ok(Self {
             connection: Arc::new(Mutex::new(connection)),
         })
     }
 
+    /// Whether the database was opened read-only, e.g. because its file isn't writable
+    pub fn is_read_only(&self) -> bool {
+        let connection = self.connection.lock().unwrap();
+        connection.is_readonly(DatabaseName::Main).unwrap_or(false)
+    }
+
     pub fn index_symbol(&self, symbol: &Symbol) -> SqlResult<()> {
         let connection = self.connection.lock().unwrap();
-        connection.execute(
-            "INSERT OR REPLACE INTO symbols 
-            (name, kind, file_uri, start_line, start_character, end_line, end_character, 
-             container_name, type_signature, documentation)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            [
+        let file_id = file_id(&connection, symbol.location.uri.as_str())?;
+        connection
+            .prepare_cached(
+                "INSERT INTO symbols
+                (name, kind, file_id, range_start, range_end, container_name, type_signature,
+                 documentation)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?
+            .execute(rusqlite::params![
                 &symbol.name,
-                &format!("{:?}", symbol.kind),
-                &symbol.location.uri.to_string(),
-                &symbol.location.range.start.line.to_string(),
-                &symbol.location.range.start.character.to_string(),
-                &symbol.location.range.end.line.to_string(),
-                &symbol.location.range.end.character.to_string(),
-                symbol.container_name.as_ref().unwrap_or(&String::new()),
-                symbol.type_signature.as_ref().unwrap_or(&String::new()),
-                symbol.documentation.as_ref().unwrap_or(&String::new()),
-            ],
-        )?;
+                kind_code(symbol.kind),
+                file_id,
+                pack_position(symbol.location.range.start),
+                pack_position(symbol.location.range.end),
+                symbol.container_name.as_ref().filter(|c| !c.is_empty()),
+                symbol.type_signature.as_ref().filter(|s| !s.is_empty()),
+                symbol.documentation.as_ref().filter(|d| !d.is_empty()),
+            ])?;
+
+        if let Some(documentation) = symbol.documentation.as_ref().filter(|d| !d.is_empty()) {
+            connection
+                .prepare_cached(
+                    "INSERT INTO symbol_docs (rowid, name, documentation) VALUES (?1, ?2, ?3)",
+                )?
+                .execute(rusqlite::params![
+                    connection.last_insert_rowid(),
+                    &symbol.name,
+                    documentation
+                ])?;
+        }
+
+        // The module a file declares is recorded with the file
+        if symbol.kind == SymbolKind::MODULE {
+            connection
+                .prepare_cached("INSERT OR IGNORE INTO modules (name) VALUES (?1)")?
+                .execute([&symbol.name])?;
+            connection
+                .prepare_cached(
+                    "UPDATE files SET module_id = (SELECT id FROM modules WHERE name = ?1)
+                     WHERE id = ?2",
+                )?
+                .execute(rusqlite::params![&symbol.name, file_id])?;
+        }
         Ok(())
     }
 
     pub fn find_symbol(&self, name: &str) -> SqlResult<Vec<Symbol>> {
         let connection = self.connection.lock().unwrap();
-        let mut stmt = connection.prepare(
-            "SELECT name, kind, file_uri, start_line, start_character, end_line, end_character, 
-             container_name, type_signature, documentation
-             FROM symbols WHERE name LIKE ?1",
-        )?;
+        let mut stmt =
+            connection.prepare_cached(&format!("{} WHERE s.name LIKE ?1", SELECT_SYMBOLS))?;
 
-        let symbol_iter = stmt.query_map([format!("%{}%", name)], |row| {
-            let uri = Url::parse(&row.get::<_, String>(2)?).map_err(|e| {
-                rusqlite::Error::FromSqlConversionFailure(
-                    2,
-                    rusqlite::types::Type::Text,
-                    Box::new(e),
-                )
-            })?;
+        let symbol_iter = stmt.query_map([format!("%{}%", name)], Self::row_to_symbol)?;
 
-            let kind_str: String = row.get(1)?;
-            let kind = match kind_str.as_str() {
-                "Function" => SymbolKind::FUNCTION,
-                "Constructor" => SymbolKind::CONSTRUCTOR,
-                "Module" => SymbolKind::MODULE,
-                "Class" => SymbolKind::CLASS,
-                "Variable" => SymbolKind::VARIABLE,
-                "Field" => SymbolKind::FIELD,
-                _ => SymbolKind::VARIABLE,
-            };
+        let mut symbols = Vec::new();
+        for symbol in symbol_iter {
+            symbols.push(symbol?);
+        }
+        Ok(symbols)
+    }
 
-            Ok(Symbol {
-                name: row.get(0)?,
-                kind,
-                location: Location::new(
-                    uri,
-                    Range::new(
-                        Position::new(row.get(3)?, row.get(4)?),
-                        Position::new(row.get(5)?, row.get(6)?),
-                    ),
-                ),
-                container_name: {
-                    let container: String = row.get(7)?;
-                    if container.is_empty() {
-                        None
-                    } else {
-                        Some(container)
-                    }
-                },
-                type_signature: {
-                    let sig: String = row.get(8)?;
-                    if sig.is_empty() {
-                        None
-                    } else {
-                        Some(sig)
-                    }
-                },
-                documentation: {
-                    let doc: String = row.get(9)?;
-                    if doc.is_empty() {
-                        None
-                    } else {
-                        Some(doc)
-                    }
-                },
-            })
-        })?;
+    /// Like `find_symbol`, but returns one page of `limit` results after the first `offset`
+    /// so huge result sets for short queries never have to be materialized at once
+    pub fn find_symbol_limited(
+        &self,
+        name: &str,
+        offset: usize,
+        limit: usize,
+    ) -> SqlResult<Vec<Symbol>> {
+        let connection = self.connection.lock().unwrap();
+        let mut stmt = connection.prepare_cached(&format!(
+            "{} WHERE s.name LIKE ?1
+             ORDER BY length(s.name), s.name, s.id
+             LIMIT ?2 OFFSET ?3",
+            SELECT_SYMBOLS
+        ))?;
+
+        let symbol_iter = stmt.query_map(
+            rusqlite::params![format!("%{}%", name), limit as i64, offset as i64],
+            Self::row_to_symbol,
+        )?;
 
         let mut symbols = Vec::new();
         for symbol in symbol_iter {
@@ -150,70 +194,66 @@ impl SymbolIndex {
         Ok(symbols)
     }
 
-    pub fn find_exact_symbol(&self, name: &str) -> SqlResult<Vec<Symbol>> {
+    /// Like `find_symbol_limited`, restricted to the symbol kinds of `query` and to files
+    /// whose URI could belong to its modules and file glob. Callers check the module each
+    /// result file declares and its path.
+    pub fn find_symbol_filtered(
+        &self,
+        query: &SymbolQuery,
+        offset: usize,
+        limit: usize,
+    ) -> SqlResult<Vec<Symbol>> {
+        let mut conditions = vec!["s.name LIKE ?".to_string()];
+        let mut params: Vec<rusqlite::types::Value> = vec![format!("%{}%", query.name).into()];
+        if let Some(modules) = &query.modules {
+            conditions.push("f.uri LIKE ?".to_string());
+            params.push(
+                match modules {
+                    ModuleFilter::Module(_) => format!("%{}", modules.path_fragment()),
+                    ModuleFilter::Subtree(_) => format!("%{}%", modules.path_fragment()),
+                }
+                .into(),
+            );
+        }
+        if let Some(files) = &query.files {
+            conditions.push("f.uri LIKE ?".to_string());
+            params.push(format!("%{}%", files.path_fragment()).into());
+        }
+        if !query.kinds.is_empty() {
+            let placeholders = vec!["?"; query.kinds.len()].join(", ");
+            conditions.push(format!("s.kind IN ({})", placeholders));
+            params.extend(query.kinds.iter().map(|kind| kind_code(*kind).into()));
+        }
+
         let connection = self.connection.lock().unwrap();
-        let mut stmt = connection.prepare(
-            "SELECT name, kind, file_uri, start_line, start_character, end_line, end_character, 
-             container_name, type_signature, documentation
-             FROM symbols WHERE name = ?1",
+        let mut stmt = connection.prepare_cached(&format!(
+            "{} WHERE {}
+             ORDER BY length(s.name), s.name, s.id
+             LIMIT ? OFFSET ?",
+            SELECT_SYMBOLS,
+            conditions.join(" AND ")
+        ))?;
+        params.push((limit as i64).into());
+        params.push((offset as i64).into());
+
+        let symbol_iter = stmt.query_map(
+            rusqlite::params_from_iter(params.iter()),
+            Self::row_to_symbol,
         )?;
 
-        let symbol_iter = stmt.query_map([name], |row| {
-            let uri = Url::parse(&row.get::<_, String>(2)?).map_err(|e| {
-                rusqlite::Error::FromSqlConversionFailure(
-                    2,
-                    rusqlite::types::Type::Text,
-                    Box::new(e),
-                )
-            })?;
+        let mut symbols = Vec::new();
+        for symbol in symbol_iter {
+            symbols.push(symbol?);
+        }
+        Ok(symbols)
+    }
 
-            let kind_str: String = row.get(1)?;
-            let kind = match kind_str.as_str() {
-                "Function" => SymbolKind::FUNCTION,
-                "Constructor" => SymbolKind::CONSTRUCTOR,
-                "Module" => SymbolKind::MODULE,
-                "Class" => SymbolKind::CLASS,
-                "Variable" => SymbolKind::VARIABLE,
-                "Field" => SymbolKind::FIELD,
-                _ => SymbolKind::VARIABLE,
-            };
+    pub fn find_exact_symbol(&self, name: &str) -> SqlResult<Vec<Symbol>> {
+        let connection = self.connection.lock().unwrap();
+        let mut stmt =
+            connection.prepare_cached(&format!("{} WHERE s.name = ?1", SELECT_SYMBOLS))?;
 
-            Ok(Symbol {
-                name: row.get(0)?,
-                kind,
-                location: Location::new(
-                    uri,
-                    Range::new(
-                        Position::new(row.get(3)?, row.get(4)?),
-                        Position::new(row.get(5)?, row.get(6)?),
-                    ),
-                ),
-                container_name: {
-                    let container: String = row.get(7)?;
-                    if container.is_empty() {
-                        None
-                    } else {
-                        Some(container)
-                    }
-                },
-                type_signature: {
-                    let sig: String = row.get(8)?;
-                    if sig.is_empty() {
-                        None
-                    } else {
-                        Some(sig)
-                    }
-                },
-                documentation: {
-                    let doc: String = row.get(9)?;
-                    if doc.is_empty() {
-                        None
-                    } else {
-                        Some(doc)
-                    }
-                },
-            })
-        })?;
+        let symbol_iter = stmt.query_map([name], Self::row_to_symbol)?;
 
         let mut symbols = Vec::new();
         for symbol in symbol_iter {
@@ -222,532 +262,852 @@ impl SymbolIndex {
         Ok(symbols)
     }
 
-    pub fn clear_file_symbols(&self, file_uri: &str) -> SqlResult<()> {
+    /// Record the comments of a file's declarations in the documentation index, next to
+    /// the documentation of the symbol each declaration defines. The file's symbols must
+    /// already be indexed.
+    pub fn index_comments(
+        &self,
+        file_uri: &str,
+        comments: &[DeclarationComments],
+    ) -> SqlResult<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for declaration in comments {
+            // A name defined at the top level and in a type, like a record field, is the
+            // top-level declaration's
+            let symbol_id: Option<i64> = transaction
+                .prepare_cached(
+                    "SELECT s.id FROM symbols s JOIN files f ON f.id = s.file_id
+                     WHERE f.uri = ?1 AND s.name = ?2
+                     ORDER BY s.container_name IS NOT NULL, s.id
+                     LIMIT 1",
+                )?
+                .query_row([file_uri, declaration.name.as_str()], |row| row.get(0))
+                .optional()?;
+            let symbol_id = match symbol_id {
+                Some(symbol_id) => symbol_id,
+                None => continue,
+            };
+
+            let updated = transaction
+                .prepare_cached("UPDATE symbol_docs SET comments = ?2 WHERE rowid = ?1")?
+                .execute(rusqlite::params![symbol_id, &declaration.text])?;
+            if updated == 0 {
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO symbol_docs (rowid, name, comments) VALUES (?1, ?2, ?3)",
+                    )?
+                    .execute(rusqlite::params![
+                        symbol_id,
+                        &declaration.name,
+                        &declaration.text
+                    ])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Find symbols whose documentation or comments match a natural-language query, best
+    /// matches first
+    pub fn search_documentation(&self, query: &str, limit: usize) -> SqlResult<Vec<Symbol>> {
+        let match_expression = match fts_match_expression(query) {
+            Some(expression) => expression,
+            None => return Ok(Vec::new()),
+        };
+
         let connection = self.connection.lock().unwrap();
-        connection.execute("DELETE FROM symbols WHERE file_uri = ?1", [file_uri])?;
-        Ok(())
+        let mut stmt = connection.prepare_cached(&format!(
+            "{} JOIN symbol_docs ON s.id = symbol_docs.rowid
+             WHERE symbol_docs MATCH ?1
+             ORDER BY bm25(symbol_docs)
+             LIMIT ?2",
+            SELECT_SYMBOLS
+        ))?;
+
+        let symbol_iter = stmt.query_map(
+            rusqlite::params![match_expression, limit as i64],
+            Self::row_to_symbol,
+        )?;
+
+        let mut symbols = Vec::new();
+        for symbol in symbol_iter {
+            symbols.push(symbol?);
+        }
+        Ok(symbols)
     }
-}
 
-/// Extracts symbols from a parsed Gren syntax tree
-pub struct SymbolExtractor {
-    function_query: Query,
-    type_query: Query,
-    constructor_query: Query,
-    module_query: Query,
-    #[allow(dead_code)]
-    import_query: Query,
-}
+    /// Convert a row selected with `SELECT_SYMBOLS` back into a Symbol
+    fn row_to_symbol(row: &rusqlite::Row) -> SqlResult<Symbol> {
+        let uri = Url::parse(&row.get::<_, String>(2)?).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+        let text = |index: usize| -> SqlResult<Option<String>> {
+            Ok(row
+                .get::<_, Option<String>>(index)?
+                .filter(|text| !text.is_empty()))
+        };
 
-impl SymbolExtractor {
-    pub fn new() -> Result<Self> {
-        let language = crate::parser::Parser::language();
-
-        // Query for function definitions and type annotations (top-level only)
-        let function_query = Query::new(
-            language,
-            r#"
-            ; Top-level function value declarations
-            (file 
-                (value_declaration 
-                    (function_declaration_left 
-                        (lower_case_identifier) @function.name)))
-            
-            ; Top-level function type annotations  
-            (file
-                (type_annotation 
-                    (lower_case_identifier) @function.type_name
-                    (colon)
-                    (type_expression) @function.type_sig))
-        "#,
-        )
-        .context("Failed to create function query")?;
-
-        // Query for type definitions and aliases
-        let type_query = Query::new(
-            language,
-            r#"
-            ; Type declarations (union types)
-            (type_declaration 
-                (upper_case_identifier) @type.name) @type.definition
-            
-            ; Type aliases
-            (type_alias_declaration 
-                (upper_case_identifier) @type.name) @type.alias
-        "#,
-        )
-        .context("Failed to create type query")?;
-
-        // Query for union type constructors
-        let constructor_query = Query::new(
-            language,
-            r#"
-            ; Union type constructors with parent type
-            (type_declaration 
-                (upper_case_identifier) @constructor.parent_type
-                (union_variant 
-                    (upper_case_identifier) @constructor.name)) @constructor.definition
-        "#,
-        )
-        .context("Failed to create constructor query")?;
-
-        // Query for module declarations
-        let module_query = Query::new(
-            language,
-            r#"
-            ; Module declarations
-            (module_declaration 
-                (upper_case_qid 
-                    (upper_case_identifier) @module.name)) @module.definition
-        "#,
-        )
-        .context("Failed to create module query")?;
-
-        // Query for import statements
-        let import_query = Query::new(
-            language,
-            r#"
-            ; Import statements
-            (import_clause 
-                (upper_case_qid 
-                    (upper_case_identifier) @import.name)) @import.reference
-        "#,
-        )
-        .context("Failed to create import query")?;
+        Ok(Symbol {
+            name: row.get(0)?,
+            kind: kind_from_code(row.get(1)?),
+            location: Location::new(
+                uri,
+                Range::new(unpack_position(row.get(3)?), unpack_position(row.get(4)?)),
+            ),
+            container_name: text(5)?,
+            type_signature: text(6)?,
+            documentation: text(7)?,
+        })
+    }
 
-        Ok(Self {
-            function_query,
-            type_query,
-            constructor_query,
-            module_query,
-            import_query,
+    /// Read a reference row: file URI, module, name, range, kind and container
+    fn row_to_reference(row: &rusqlite::Row) -> SqlResult<SymbolReference> {
+        let uri = Url::parse(&row.get::<_, String>(0)?).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+        Ok(SymbolReference {
+            module: row.get(1)?,
+            name: row.get(2)?,
+            location: Location::new(
+                uri,
+                Range::new(unpack_position(row.get(3)?), unpack_position(row.get(4)?)),
+            ),
+            kind: reference_kind_from_code(row.get(5)?),
+            container: row.get(6)?,
         })
     }
 
-    /// Extract all symbols from a parsed tree
-    pub fn extract_symbols(
-        &self,
-        tree: &Tree,
-        source: &str,
-        file_uri: &Url,
-    ) -> Result<Vec<Symbol>> {
-        let mut symbols = Vec::new();
-        let mut cursor = QueryCursor::new();
-
-        // First, extract all documentation comments and associate them with their line positions
-        let doc_comments = self.extract_documentation_comments(tree, source)?;
-
-        // Extract functions with documentation
-        symbols.extend(self.extract_functions(
-            tree,
-            source,
-            file_uri,
-            &mut cursor,
-            &doc_comments,
-        )?);
-
-        // Extract types with documentation
-        symbols.extend(self.extract_types(tree, source, file_uri, &mut cursor, &doc_comments)?);
-
-        // Extract constructors with documentation
-        symbols.extend(self.extract_constructors(
-            tree,
-            source,
-            file_uri,
-            &mut cursor,
-            &doc_comments,
-        )?);
-
-        // Extract modules with documentation
-        symbols.extend(self.extract_modules(tree, source, file_uri, &mut cursor, &doc_comments)?);
+    /// Symbols whose documentation carries an `@deprecated` tag, ordered by file and position
+    pub fn deprecated_symbols(&self) -> SqlResult<Vec<Symbol>> {
+        let connection = self.connection.lock().unwrap();
+        let mut stmt = connection.prepare_cached(&format!(
+            "{} WHERE s.documentation LIKE '%@deprecated%'
+             ORDER BY f.uri, s.range_start",
+            SELECT_SYMBOLS
+        ))?;
 
-        debug!("Extracted {} symbols from {}", symbols.len(), file_uri);
+        let symbol_iter = stmt.query_map([], Self::row_to_symbol)?;
+
+        let mut symbols = Vec::new();
+        for symbol in symbol_iter {
+            let symbol = symbol?;
+            // `LIKE` also matches the tag in the middle of a sentence
+            if symbol.deprecation().is_some() {
+                symbols.push(symbol);
+            }
+        }
         Ok(symbols)
     }
 
-    /// Extract documentation comments from the source tree
-    /// Returns a map of line numbers to documentation text
-    fn extract_documentation_comments(
-        &self,
-        tree: &Tree,
-        source: &str,
-    ) -> Result<std::collections::HashMap<u32, String>> {
-        let mut doc_comments = std::collections::HashMap::new();
-        let language = crate::parser::Parser::language();
-
-        // Query for block comments that are documentation comments
-        let doc_query = Query::new(
-            language,
-            r#"
-            (block_comment) @doc.comment
-        "#,
-        )
-        .context("Failed to create documentation query")?;
-
-        let mut cursor = QueryCursor::new();
-        let source_bytes = source.as_bytes();
-        let matches = cursor.matches(&doc_query, tree.root_node(), source_bytes);
-
-        for m in matches {
-            for capture in m.captures {
-                let node = capture.node;
-                if let Ok(comment_text) = node.utf8_text(source_bytes) {
-                    // Check if this is a documentation comment (starts with {-|)
-                    if comment_text.starts_with("{-|") && comment_text.ends_with("-}") {
-                        // Extract the inner documentation text
-                        let inner_text = &comment_text[3..comment_text.len() - 2];
-                        let cleaned_doc = Self::clean_documentation_text(inner_text);
-
-                        // Associate with the line where the comment ends
-                        let end_line = node.end_position().row as u32;
-                        let preview =
-                            cleaned_doc[..std::cmp::min(50, cleaned_doc.len())].to_string();
-                        doc_comments.insert(end_line, cleaned_doc);
-
-                        debug!(
-                            "Found documentation comment ending at line {}: {}",
-                            end_line, preview
-                        );
-                    }
-                }
+    /// Functions and values whose signature mentions `type_name`, ordered by file and
+    /// position
+    pub fn symbols_using_type(&self, type_name: &str) -> SqlResult<Vec<Symbol>> {
+        let connection = self.connection.lock().unwrap();
+        let mut stmt = connection.prepare_cached(&format!(
+            "{} WHERE s.type_signature LIKE '%' || ?1 || '%'
+             ORDER BY f.uri, s.range_start",
+            SELECT_SYMBOLS
+        ))?;
+        let unqualified = type_name.rsplit('.').next().unwrap_or(type_name);
+        let symbol_iter = stmt.query_map([unqualified], Self::row_to_symbol)?;
+
+        let mut symbols = Vec::new();
+        for symbol in symbol_iter {
+            let symbol = symbol?;
+            // `LIKE` also matches longer names and names in other modules
+            if symbol.type_usage(type_name).is_some() {
+                symbols.push(symbol);
             }
         }
-
-        debug!("Extracted {} documentation comments", doc_comments.len());
-        Ok(doc_comments)
+        Ok(symbols)
     }
 
-    /// Clean documentation text by removing leading/trailing whitespace and normalizing formatting
-    fn clean_documentation_text(doc_text: &str) -> String {
-        doc_text
-            .lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.is_empty()) // Remove empty lines
-            .collect::<Vec<_>>()
-            .join("\n")
-            .trim()
-            .to_string()
+    pub fn clear_file_symbols(&self, file_uri: &str) -> SqlResult<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .prepare_cached(
+                "DELETE FROM symbol_docs WHERE rowid IN (
+                     SELECT s.id FROM symbols s JOIN files f ON f.id = s.file_id WHERE f.uri = ?1
+                 )",
+            )?
+            .execute([file_uri])?;
+        connection
+            .prepare_cached(
+                "DELETE FROM symbols WHERE file_id = (SELECT id FROM files WHERE uri = ?1)",
+            )?
+            .execute([file_uri])?;
+        forget_unused_file(&connection, file_uri)
     }
 
-    /// Find the documentation comment associated with a symbol at the given line
-    /// Documentation comments should appear immediately before the symbol definition
-    fn find_documentation_for_symbol(
-        &self,
-        symbol_line: u32,
-        doc_comments: &std::collections::HashMap<u32, String>,
-    ) -> Option<String> {
-        // Look for documentation comments ending 1-2 lines before the symbol
-        // This accounts for the fact that there might be a blank line between doc and symbol
-        for offset in 1..=3 {
-            if let Some(doc) = doc_comments.get(&(symbol_line.saturating_sub(offset))) {
-                return Some(doc.clone());
+    /// Replace the task markers recorded for a file
+    pub fn replace_file_todos(&self, file_uri: &str, todos: &[TodoItem]) -> SqlResult<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction
+            .prepare_cached(
+                "DELETE FROM todos WHERE file_id = (SELECT id FROM files WHERE uri = ?1)",
+            )?
+            .execute([file_uri])?;
+        if todos.is_empty() {
+            forget_unused_file(&transaction, file_uri)?;
+        } else {
+            let file_id = file_id(&transaction, file_uri)?;
+            for todo in todos {
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO todos (file_id, marker, text, range_start, range_end)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                    )?
+                    .execute(rusqlite::params![
+                        file_id,
+                        todo.marker.as_str(),
+                        &todo.text,
+                        pack_position(todo.location.range.start),
+                        pack_position(todo.location.range.end),
+                    ])?;
             }
         }
-        None
+        transaction.commit()
     }
 
-    fn extract_functions(
+    /// Replace the resolved references to top-level names recorded for a file
+    pub fn replace_file_references(
         &self,
-        tree: &Tree,
-        source: &str,
-        file_uri: &Url,
-        cursor: &mut QueryCursor,
-        doc_comments: &std::collections::HashMap<u32, String>,
-    ) -> Result<Vec<Symbol>> {
-        let mut functions = Vec::new();
-        let source_bytes = source.as_bytes();
-
-        // First pass: collect all function definitions and type annotations
-        let matches = cursor.matches(&self.function_query, tree.root_node(), source_bytes);
-        let mut function_defs: std::collections::HashMap<String, (Range, Option<String>)> =
-            std::collections::HashMap::new();
-        let mut type_annotations: std::collections::HashMap<String, String> =
-            std::collections::HashMap::new();
-
-        for m in matches {
-            for capture in m.captures {
-                let node = capture.node;
-                if let Ok(text) = node.utf8_text(source_bytes) {
-                    let capture_name = &self.function_query.capture_names()[capture.index as usize];
-
-                    match capture_name.as_str() {
-                        "function.name" => {
-                            // This is a function definition
-                            let range = Range::new(
-                                Position::new(
-                                    node.start_position().row as u32,
-                                    node.start_position().column as u32,
-                                ),
-                                Position::new(
-                                    node.end_position().row as u32,
-                                    node.end_position().column as u32,
-                                ),
-                            );
-                            function_defs.insert(text.to_string(), (range, None));
-                        }
-                        "function.type_name" => {
-                            // This is the name in a type annotation - we'll look for the matching type signature
-                            let func_name = text.to_string();
-
-                            // Find the type signature for this function
-                            for capture2 in m.captures {
-                                let capture2_name =
-                                    &self.function_query.capture_names()[capture2.index as usize];
-                                if capture2_name == "function.type_sig" {
-                                    if let Ok(type_text) = capture2.node.utf8_text(source_bytes) {
-                                        type_annotations.insert(
-                                            func_name.clone(),
-                                            Self::clean_type_signature(type_text),
-                                        );
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+        file_uri: &str,
+        references: &[SymbolReference],
+    ) -> SqlResult<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction
+            .prepare_cached(
+                "DELETE FROM symbol_references
+                 WHERE file_id = (SELECT id FROM files WHERE uri = ?1)",
+            )?
+            .execute([file_uri])?;
+        if references.is_empty() {
+            forget_unused_file(&transaction, file_uri)?;
+        } else {
+            let file_id = file_id(&transaction, file_uri)?;
+            for reference in references {
+                transaction
+                    .prepare_cached("INSERT OR IGNORE INTO modules (name) VALUES (?1)")?
+                    .execute([&reference.module])?;
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO symbol_references
+                         (file_id, module_id, name, kind, range_start, range_end, container)
+                         SELECT ?1, id, ?3, ?4, ?5, ?6, ?7 FROM modules WHERE name = ?2",
+                    )?
+                    .execute(rusqlite::params![
+                        file_id,
+                        &reference.module,
+                        &reference.name,
+                        reference_kind_code(reference.kind),
+                        pack_position(reference.location.range.start),
+                        pack_position(reference.location.range.end),
+                        &reference.container,
+                    ])?;
             }
         }
+        transaction.commit()
+    }
 
-        // Second pass: combine function definitions with their type annotations and documentation
-        for (func_name, (range, _)) in function_defs {
-            let type_signature = type_annotations.get(&func_name).cloned();
-            let documentation = self.find_documentation_for_symbol(range.start.line, doc_comments);
-
-            functions.push(Symbol {
-                name: func_name,
-                kind: SymbolKind::FUNCTION,
-                location: Location::new(file_uri.clone(), range),
-                container_name: None, // TODO: Extract containing module
-                type_signature,
-                documentation,
-            });
-        }
-
-        debug!("Extracted {} functions", functions.len());
-        Ok(functions)
+    /// Every recorded occurrence of `module`'s top-level `name`, ordered by file and
+    /// position
+    pub fn find_references(&self, module: &str, name: &str) -> SqlResult<Vec<SymbolReference>> {
+        let connection = self.connection.lock().unwrap();
+        let mut stmt = connection.prepare_cached(
+            "SELECT DISTINCT f.uri, m.name, r.name, r.range_start, r.range_end, r.kind,
+                 r.container
+             FROM symbol_references r
+             JOIN files f ON f.id = r.file_id
+             JOIN modules m ON m.id = r.module_id
+             WHERE m.name = ?1 AND r.name = ?2
+             ORDER BY f.uri, r.range_start",
+        )?;
+        let rows = stmt.query_map([module, name], Self::row_to_reference)?;
+        rows.collect()
     }
 
-    fn extract_types(
+    /// The references recorded in the definition of the top-level declaration `container`
+    /// of a file, ordered by position
+    pub fn find_references_from(
         &self,
-        tree: &Tree,
-        source: &str,
-        file_uri: &Url,
-        cursor: &mut QueryCursor,
-        doc_comments: &std::collections::HashMap<u32, String>,
-    ) -> Result<Vec<Symbol>> {
-        let mut types = Vec::new();
-        let source_bytes = source.as_bytes();
-
-        let matches = cursor.matches(&self.type_query, tree.root_node(), source_bytes);
-
-        for m in matches {
-            let mut type_name = None;
-            let mut type_definition = None;
-            let mut type_range = None;
-
-            // Process all captures for this match
-            for capture in m.captures {
-                let capture_name = &self.type_query.capture_names()[capture.index as usize];
-                let node = capture.node;
-
-                match capture_name.as_str() {
-                    "type.name" => {
-                        if let Ok(name) = node.utf8_text(source_bytes) {
-                            type_name = Some(name.to_string());
-                            type_range = Some(Range::new(
-                                Position::new(
-                                    node.start_position().row as u32,
-                                    node.start_position().column as u32,
-                                ),
-                                Position::new(
-                                    node.end_position().row as u32,
-                                    node.end_position().column as u32,
-                                ),
-                            ));
-                        }
-                    }
-                    "type.definition" | "type.alias" => {
-                        if let Ok(def) = node.utf8_text(source_bytes) {
-                            // Clean up the type definition - remove "type " prefix to avoid duplication
-                            let cleaned_def = def.trim_start_matches("type ").trim();
-                            type_definition = Some(cleaned_def.to_string());
-                        }
-                    }
-                    _ => {} // Ignore other captures
-                }
-            }
+        file_uri: &str,
+        container: &str,
+    ) -> SqlResult<Vec<SymbolReference>> {
+        let connection = self.connection.lock().unwrap();
+        let mut stmt = connection.prepare_cached(
+            "SELECT f.uri, m.name, r.name, r.range_start, r.range_end, r.kind, r.container
+             FROM symbol_references r
+             JOIN files f ON f.id = r.file_id
+             JOIN modules m ON m.id = r.module_id
+             WHERE f.uri = ?1 AND r.container = ?2
+             ORDER BY r.range_start",
+        )?;
+        let rows = stmt.query_map([file_uri, container], Self::row_to_reference)?;
+        rows.collect()
+    }
+
+    /// Every recorded task marker, ordered by file and position
+    pub fn all_todos(&self) -> SqlResult<Vec<TodoItem>> {
+        let connection = self.connection.lock().unwrap();
+        let mut stmt = connection.prepare_cached(
+            "SELECT f.uri, t.marker, t.text, t.range_start, t.range_end
+             FROM todos t JOIN files f ON f.id = t.file_id
+             ORDER BY f.uri, t.range_start",
+        )?;
 
-            // Create symbol if we have both name and range
-            if let (Some(name), Some(range)) = (type_name, type_range) {
-                let documentation =
-                    self.find_documentation_for_symbol(range.start.line, doc_comments);
-
-                types.push(Symbol {
-                    name,
-                    kind: SymbolKind::CLASS, // Using CLASS for type definitions
-                    location: Location::new(file_uri.clone(), range),
-                    container_name: None,
-                    type_signature: type_definition,
-                    documentation,
+        let rows = stmt.query_map([], |row| {
+            let uri = Url::parse(&row.get::<_, String>(0)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
+            let location = Location::new(
+                uri,
+                Range::new(unpack_position(row.get(3)?), unpack_position(row.get(4)?)),
+            );
+            Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?, location))
+        })?;
+
+        let mut todos = Vec::new();
+        for row in rows {
+            let (marker, text, location) = row?;
+            // Rows written by a version that knew other markers are skipped
+            if let Some(marker) = TodoMarker::parse(&marker) {
+                todos.push(TodoItem {
+                    marker,
+                    text,
+                    location,
                 });
             }
         }
+        Ok(todos)
+    }
 
-        debug!("Extracted {} types", types.len());
-        Ok(types)
+    /// How much the index holds and the size of its database
+    pub fn stats(&self) -> SqlResult<IndexStats> {
+        let connection = self.connection.lock().unwrap();
+        let count = |table: &str| -> SqlResult<usize> {
+            connection.query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+                row.get::<_, i64>(0).map(|count| count as usize)
+            })
+        };
+        let meta = |key: &str| -> SqlResult<Option<u64>> {
+            connection
+                .query_row(
+                    "SELECT value FROM index_meta WHERE key = ?1",
+                    [key],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()
+                .map(|value| value.map(|value| value as u64))
+        };
+        Ok(IndexStats {
+            symbols: count("symbols")?,
+            files: count("files")?,
+            // Modules only referred to, e.g. from packages, aren't counted
+            modules: count("modules WHERE id IN (SELECT module_id FROM files)")?,
+            database_bytes: Some(database_bytes(&connection)?),
+            bytes_before_migration: meta(BYTES_BEFORE_MIGRATION)?,
+            bytes_after_migration: meta(BYTES_AFTER_MIGRATION)?,
+        })
     }
+}
 
-    fn extract_constructors(
-        &self,
-        tree: &Tree,
-        source: &str,
-        file_uri: &Url,
-        cursor: &mut QueryCursor,
-        doc_comments: &std::collections::HashMap<u32, String>,
-    ) -> Result<Vec<Symbol>> {
-        let mut constructors = Vec::new();
-        let source_bytes = source.as_bytes();
-
-        let matches = cursor.matches(&self.constructor_query, tree.root_node(), source_bytes);
-
-        for m in matches {
-            let mut constructor_name: Option<String> = None;
-            let mut parent_type: Option<String> = None;
-            let mut constructor_range: Option<Range> = None;
-
-            // Collect both constructor name and parent type from the same match
-            for capture in m.captures {
-                let node = capture.node;
-                let capture_name = &self.constructor_query.capture_names()[capture.index as usize];
-
-                if let Ok(text) = node.utf8_text(source_bytes) {
-                    match capture_name.as_str() {
-                        "constructor.name" => {
-                            constructor_name = Some(text.to_string());
-
-                            let range = Range::new(
-                                Position::new(
-                                    node.start_position().row as u32,
-                                    node.start_position().column as u32,
-                                ),
-                                Position::new(
-                                    node.end_position().row as u32,
-                                    node.end_position().column as u32,
-                                ),
-                            );
-                            constructor_range = Some(range);
-                        }
-                        "constructor.parent_type" => {
-                            parent_type = Some(text.to_string());
-                        }
-                        _ => {}
-                    }
-                }
-            }
+/// Keys of `index_meta` recording the size of the database around the conversion from the
+/// original layout
+const BYTES_BEFORE_MIGRATION: &str = "bytes_before_migration";
+const BYTES_AFTER_MIGRATION: &str = "bytes_after_migration";
+
+/// Bring the database to `SCHEMA_VERSION`. A database in the original layout has its rows
+/// copied into the compact tables, keeping symbol ids, and is vacuumed; its size before and
+/// after is recorded for `stats`.
+fn migrate(connection: &mut Connection) -> SqlResult<()> {
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
 
-            // Only create constructor symbol if we have both name and parent type
-            if let (Some(name), Some(parent), Some(range)) =
-                (constructor_name, parent_type, constructor_range)
-            {
-                let documentation =
-                    self.find_documentation_for_symbol(range.start.line, doc_comments);
-
-                constructors.push(Symbol {
-                    name: name.clone(),
-                    kind: SymbolKind::CONSTRUCTOR,
-                    location: Location::new(file_uri.clone(), range),
-                    container_name: Some(parent.clone()),
-                    type_signature: None,
-                    documentation,
-                });
+    let legacy = connection
+        .prepare("SELECT file_uri FROM symbols LIMIT 0")
+        .is_ok();
+    let bytes_before = database_bytes(connection)?;
+
+    let transaction = connection.transaction()?;
+    // References are rebuilt whenever a file is indexed, so older ones are dropped rather
+    // than converted
+    transaction.execute_batch("DROP TABLE IF EXISTS symbol_references")?;
+    if legacy {
+        transaction.execute_batch(
+            "CREATE TABLE IF NOT EXISTS todos (
+                 id INTEGER PRIMARY KEY,
+                 file_uri TEXT NOT NULL,
+                 marker TEXT NOT NULL,
+                 text TEXT NOT NULL,
+                 start_line INTEGER NOT NULL,
+                 start_character INTEGER NOT NULL,
+                 end_line INTEGER NOT NULL,
+                 end_character INTEGER NOT NULL
+             );
+             DROP INDEX IF EXISTS idx_symbols_name;
+             DROP INDEX IF EXISTS idx_todos_file_uri;
+             ALTER TABLE symbols RENAME TO legacy_symbols;
+             ALTER TABLE todos RENAME TO legacy_todos;",
+        )?;
+    }
+    // Older documentation indexes lack the comments column, and the original layout may
+    // predate the index entirely; either way it's rebuilt from the symbols' documentation
+    // below, and comments are added as files are indexed again
+    transaction.execute_batch("DROP TABLE IF EXISTS symbol_docs")?;
+    create_tables(&transaction)?;
+    if legacy {
+        let kinds: Vec<String> = [
+            SymbolKind::FUNCTION,
+            SymbolKind::CONSTRUCTOR,
+            SymbolKind::MODULE,
+            SymbolKind::CLASS,
+            SymbolKind::VARIABLE,
+            SymbolKind::FIELD,
+        ]
+        .iter()
+        .map(|kind| format!("WHEN '{:?}' THEN {}", kind, kind_code(*kind)))
+        .collect();
+        transaction.execute_batch(&format!(
+            "INSERT OR IGNORE INTO files (uri)
+                 SELECT file_uri FROM legacy_symbols UNION SELECT file_uri FROM legacy_todos;
+             INSERT OR IGNORE INTO modules (name)
+                 SELECT name FROM legacy_symbols WHERE kind = '{module:?}';
+             UPDATE files SET module_id = (
+                 SELECT m.id FROM legacy_symbols l JOIN modules m ON m.name = l.name
+                 WHERE l.file_uri = files.uri AND l.kind = '{module:?}'
+             );
+             INSERT INTO symbols
+                 (id, name, kind, file_id, range_start, range_end, container_name, type_signature,
+                  documentation)
+                 SELECT l.id, l.name, CASE l.kind {kinds} ELSE {variable} END, f.id,
+                     (l.start_line << {bits}) | min(l.start_character, {max}),
+                     (l.end_line << {bits}) | min(l.end_character, {max}),
+                     NULLIF(l.container_name, ''), NULLIF(l.type_signature, ''),
+                     NULLIF(l.documentation, '')
+                 FROM legacy_symbols l JOIN files f ON f.uri = l.file_uri;
+             INSERT INTO todos (file_id, marker, text, range_start, range_end)
+                 SELECT f.id, l.marker, l.text,
+                     (l.start_line << {bits}) | min(l.start_character, {max}),
+                     (l.end_line << {bits}) | min(l.end_character, {max})
+                 FROM legacy_todos l JOIN files f ON f.uri = l.file_uri;
+             DROP TABLE legacy_symbols;
+             DROP TABLE legacy_todos;",
+            module = SymbolKind::MODULE,
+            kinds = kinds.join(" "),
+            variable = kind_code(SymbolKind::VARIABLE),
+            bits = CHARACTER_BITS,
+            max = MAX_CHARACTER,
+        ))?;
+    }
+    transaction.execute_batch(
+        "INSERT INTO symbol_docs (rowid, name, documentation)
+             SELECT id, name, documentation FROM symbols WHERE documentation IS NOT NULL",
+    )?;
+    transaction.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    transaction.commit()?;
+
+    if legacy {
+        connection.execute_batch("VACUUM")?;
+        let bytes_after = database_bytes(connection)?;
+        info!(
+            "Converted the symbol index to the compact layout: {} bytes before, {} after",
+            bytes_before, bytes_after
+        );
+        connection.execute(
+            "INSERT OR REPLACE INTO index_meta (key, value) VALUES (?1, ?2), (?3, ?4)",
+            rusqlite::params![
+                BYTES_BEFORE_MIGRATION,
+                bytes_before as i64,
+                BYTES_AFTER_MIGRATION,
+                bytes_after as i64
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn create_tables(connection: &Connection) -> SqlResult<()> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS modules (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL UNIQUE
+         );
+
+         CREATE TABLE IF NOT EXISTS files (
+             id INTEGER PRIMARY KEY,
+             uri TEXT NOT NULL UNIQUE,
+             module_id INTEGER REFERENCES modules(id)
+         );
+
+         -- Ranges are packed positions, see `pack_position`
+         CREATE TABLE IF NOT EXISTS symbols (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             kind INTEGER NOT NULL,
+             file_id INTEGER NOT NULL REFERENCES files(id),
+             range_start INTEGER NOT NULL,
+             range_end INTEGER NOT NULL,
+             container_name TEXT,
+             type_signature TEXT,
+             documentation TEXT
+         );
+         CREATE INDEX IF NOT EXISTS idx_symbols_name ON symbols(name);
+         CREATE INDEX IF NOT EXISTS idx_symbols_file ON symbols(file_id);
+
+         -- Full-text index over documentation and the comments of declarations, keyed by
+         -- the symbol's row id
+         CREATE VIRTUAL TABLE IF NOT EXISTS symbol_docs USING fts5(
+             name,
+             documentation,
+             comments,
+             tokenize = 'porter unicode61'
+         );
+
+         -- Task markers found in comments, replaced per file whenever it's re-indexed
+         CREATE TABLE IF NOT EXISTS todos (
+             id INTEGER PRIMARY KEY,
+             file_id INTEGER NOT NULL REFERENCES files(id),
+             marker TEXT NOT NULL,
+             text TEXT NOT NULL,
+             range_start INTEGER NOT NULL,
+             range_end INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_todos_file ON todos(file_id);
+
+         -- Occurrences of top-level names, resolved to the defining module, replaced per
+         -- file whenever it's re-indexed
+         CREATE TABLE IF NOT EXISTS symbol_references (
+             id INTEGER PRIMARY KEY,
+             file_id INTEGER NOT NULL REFERENCES files(id),
+             module_id INTEGER NOT NULL REFERENCES modules(id),
+             name TEXT NOT NULL,
+             kind INTEGER NOT NULL,
+             range_start INTEGER NOT NULL,
+             range_end INTEGER NOT NULL,
+             container TEXT
+         );
+         CREATE INDEX IF NOT EXISTS idx_symbol_references_name
+             ON symbol_references(name, module_id);
+         CREATE INDEX IF NOT EXISTS idx_symbol_references_file ON symbol_references(file_id);
+
+         CREATE TABLE IF NOT EXISTS index_meta (
+             key TEXT PRIMARY KEY,
+             value INTEGER NOT NULL
+         );",
+    )
+}
+
+/// Id of a file's URI, recording the URI the first time it's seen
+fn file_id(connection: &Connection, uri: &str) -> SqlResult<i64> {
+    connection
+        .prepare_cached("INSERT OR IGNORE INTO files (uri) VALUES (?1)")?
+        .execute([uri])?;
+    connection
+        .prepare_cached("SELECT id FROM files WHERE uri = ?1")?
+        .query_row([uri], |row| row.get(0))
+}
+
+/// Drop a file's URI once no symbol, task marker or reference refers to it
+fn forget_unused_file(connection: &Connection, uri: &str) -> SqlResult<()> {
+    connection
+        .prepare_cached(
+            "DELETE FROM files WHERE uri = ?1
+             AND NOT EXISTS (SELECT 1 FROM symbols WHERE file_id = files.id)
+             AND NOT EXISTS (SELECT 1 FROM todos WHERE file_id = files.id)
+             AND NOT EXISTS (SELECT 1 FROM symbol_references WHERE file_id = files.id)",
+        )?
+        .execute([uri])?;
+    Ok(())
+}
+
+fn database_bytes(connection: &Connection) -> SqlResult<u64> {
+    let pages: i64 = connection.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let page_size: i64 = connection.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    Ok((pages * page_size) as u64)
+}
+
+/// A position as one integer, ordering like the position does
+fn pack_position(position: Position) -> i64 {
+    ((position.line as i64) << CHARACTER_BITS) | position.character.min(MAX_CHARACTER) as i64
+}
+
+fn unpack_position(packed: i64) -> Position {
+    Position::new(
+        (packed >> CHARACTER_BITS) as u32,
+        (packed & MAX_CHARACTER as i64) as u32,
+    )
+}
+
+/// The kind's number in the LSP specification
+fn kind_code(kind: SymbolKind) -> i64 {
+    match kind {
+        SymbolKind::MODULE => 2,
+        SymbolKind::CLASS => 5,
+        SymbolKind::FIELD => 8,
+        SymbolKind::CONSTRUCTOR => 9,
+        SymbolKind::FUNCTION => 12,
+        _ => 13,
+    }
+}
+
+fn kind_from_code(code: i64) -> SymbolKind {
+    match code {
+        2 => SymbolKind::MODULE,
+        5 => SymbolKind::CLASS,
+        8 => SymbolKind::FIELD,
+        9 => SymbolKind::CONSTRUCTOR,
+        12 => SymbolKind::FUNCTION,
+        _ => SymbolKind::VARIABLE,
+    }
+}
+
+fn reference_kind_code(kind: ReferenceKind) -> i64 {
+    match kind {
+        ReferenceKind::Declaration => 0,
+        ReferenceKind::Expression => 1,
+        ReferenceKind::Pattern => 2,
+        ReferenceKind::Type => 3,
+        ReferenceKind::Exposing => 4,
+    }
+}
+
+fn reference_kind_from_code(code: i64) -> ReferenceKind {
+    match code {
+        0 => ReferenceKind::Declaration,
+        2 => ReferenceKind::Pattern,
+        3 => ReferenceKind::Type,
+        4 => ReferenceKind::Exposing,
+        _ => ReferenceKind::Expression,
+    }
+}
+
+/// Turn a free-form query into an FTS5 expression matching any of its words.
+/// Each word is quoted so punctuation in the query can't be read as FTS syntax.
+fn fts_match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"", word))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" OR "))
+    }
+}
 
-                debug!("Found constructor '{}' for type '{}'", name, parent);
+/// Full source range of each top-level declaration, keyed by name. A function's range
+/// starts at its type annotation when the annotation directly precedes the definition, so
+/// outlines can show the pair as one symbol.
+pub fn declaration_ranges(tree: &Tree, source: &str) -> std::collections::HashMap<String, Range> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declarations: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|node| !node.kind().ends_with("comment"))
+        .collect();
+
+    let mut ranges = std::collections::HashMap::new();
+    for (index, declaration) in declarations.iter().enumerate() {
+        let name = match declaration.kind() {
+            "value_declaration" => first_child_of_kind(declaration, "function_declaration_left")
+                .and_then(|left| first_child_of_kind(&left, "lower_case_identifier")),
+            "type_declaration" | "type_alias_declaration" => {
+                first_child_of_kind(declaration, "upper_case_identifier")
             }
+            _ => None,
+        };
+        let name = match name.and_then(|name| name.utf8_text(source.as_bytes()).ok()) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let annotation = index
+            .checked_sub(1)
+            .map(|previous| declarations[previous])
+            .filter(|previous| {
+                declaration.kind() == "value_declaration"
+                    && previous.kind() == "type_annotation"
+                    && first_child_of_kind(previous, "lower_case_identifier")
+                        .and_then(|annotated| annotated.utf8_text(source.as_bytes()).ok())
+                        == Some(name)
+            });
+        let start = annotation.unwrap_or(*declaration).start_position();
+        let end = declaration.end_position();
+
+        ranges.insert(
+            name.to_string(),
+            Range::new(
+                Position::new(start.row as u32, start.column as u32),
+                Position::new(end.row as u32, end.column as u32),
+            ),
+        );
+    }
+    ranges
+}
+
+/// Where a top-level function's name appears in its definition and in its type
+/// annotation. The two are one symbol: renaming either must rename both.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FunctionNameRanges {
+    pub definition: Option<Range>,
+    pub annotation: Option<Range>,
+}
+
+impl FunctionNameRanges {
+    pub fn contains(&self, range: &Range) -> bool {
+        self.definition.as_ref() == Some(range) || self.annotation.as_ref() == Some(range)
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = Range> {
+        self.definition.into_iter().chain(self.annotation)
+    }
+}
+
+/// Pair each top-level function definition with its type annotation, keyed by name.
+/// Top-level names are unique within a module, so the pairing is by name rather than by
+/// position; an annotation whose definition is missing is still reported.
+pub fn function_name_ranges(
+    tree: &Tree,
+    source: &str,
+) -> std::collections::HashMap<String, FunctionNameRanges> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut functions: std::collections::HashMap<String, FunctionNameRanges> =
+        std::collections::HashMap::new();
+
+    for declaration in root.named_children(&mut cursor) {
+        let name = match declaration.kind() {
+            "value_declaration" => first_child_of_kind(&declaration, "function_declaration_left")
+                .and_then(|left| first_child_of_kind(&left, "lower_case_identifier")),
+            "type_annotation" => first_child_of_kind(&declaration, "lower_case_identifier"),
+            _ => None,
+        };
+        let name = match name {
+            Some(name) => name,
+            None => continue,
+        };
+        let text = match name.utf8_text(source.as_bytes()) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+
+        let range = Range::new(
+            Position::new(
+                name.start_position().row as u32,
+                name.start_position().column as u32,
+            ),
+            Position::new(
+                name.end_position().row as u32,
+                name.end_position().column as u32,
+            ),
+        );
+        let entry = functions.entry(text.to_string()).or_default();
+        if declaration.kind() == "type_annotation" {
+            entry.annotation = Some(range);
+        } else {
+            entry.definition = Some(range);
         }
+    }
+    functions
+}
 
-        debug!("Extracted {} constructors", constructors.len());
-        Ok(constructors)
+/// The name of a top-level function's definition when `position` is in its type
+/// annotation, or the name in its annotation when `position` is in its definition
+pub fn alternate_function_range(tree: &Tree, source: &str, position: Position) -> Option<Range> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+    let declaration = root
+        .named_children(&mut cursor)
+        .find(|node| node.start_position() <= point && point <= node.end_position())?;
+
+    let (name, in_annotation) = match declaration.kind() {
+        "type_annotation" => (
+            first_child_of_kind(&declaration, "lower_case_identifier")?,
+            true,
+        ),
+        "value_declaration" => (
+            first_child_of_kind(&declaration, "function_declaration_left")
+                .and_then(|left| first_child_of_kind(&left, "lower_case_identifier"))?,
+            false,
+        ),
+        _ => return None,
+    };
+    let name = name.utf8_text(source.as_bytes()).ok()?;
+    let ranges = function_name_ranges(tree, source).remove(name)?;
+    if in_annotation {
+        ranges.definition
+    } else {
+        ranges.annotation
     }
+}
 
-    fn extract_modules(
+fn first_child_of_kind<'a>(node: &Node<'a>, kind: &str) -> Option<Node<'a>> {
+    let mut cursor = node.walk();
+    let child = node
+        .children(&mut cursor)
+        .find(|child| child.kind() == kind);
+    child
+}
+
+/// Extracts symbols from a parsed Gren syntax tree
+pub struct SymbolExtractor {
+    declarations: DeclarationExtractor,
+}
+
+impl SymbolExtractor {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            declarations: DeclarationExtractor::new()?,
+        })
+    }
+
+    /// Extract all symbols from a parsed tree
+    pub fn extract_symbols(
         &self,
         tree: &Tree,
         source: &str,
         file_uri: &Url,
-        cursor: &mut QueryCursor,
-        doc_comments: &std::collections::HashMap<u32, String>,
     ) -> Result<Vec<Symbol>> {
-        let mut modules = Vec::new();
-        let source_bytes = source.as_bytes();
-
-        let matches = cursor.matches(&self.module_query, tree.root_node(), source_bytes);
-
-        for m in matches {
-            let mut module_name: Option<String> = None;
-            let mut module_range: Option<Range> = None;
-
-            // Only capture the module name, not the full declaration
-            for capture in m.captures {
-                let node = capture.node;
-                let capture_name = &self.module_query.capture_names()[capture.index as usize];
-
-                if let Ok(text) = node.utf8_text(source_bytes) {
-                    match capture_name.as_str() {
-                        "module.name" => {
-                            module_name = Some(text.to_string());
-
-                            let range = Range::new(
-                                Position::new(
-                                    node.start_position().row as u32,
-                                    node.start_position().column as u32,
-                                ),
-                                Position::new(
-                                    node.end_position().row as u32,
-                                    node.end_position().column as u32,
-                                ),
-                            );
-                            module_range = Some(range);
-                        }
-                        _ => {
-                            // Ignore module.definition capture - we only want the name
-                        }
-                    }
-                }
-            }
-
-            // Only create module symbol if we have a name
-            if let (Some(name), Some(range)) = (module_name, module_range) {
-                let documentation =
-                    self.find_documentation_for_symbol(range.start.line, doc_comments);
-
-                modules.push(Symbol {
-                    name: name.clone(),
-                    kind: SymbolKind::MODULE,
-                    location: Location::new(file_uri.clone(), range),
-                    container_name: None,
-                    type_signature: None,
-                    documentation,
-                });
-
-                debug!("Found module '{}'", name);
-            }
-        }
+        let symbols: Vec<Symbol> = self
+            .declarations
+            .extract(tree, source)
+            .into_iter()
+            .map(|declaration| declaration_to_symbol(declaration, source, file_uri))
+            .collect();
 
-        debug!("Extracted {} modules", modules.len());
-        Ok(modules)
+        debug!("Extracted {} symbols from {}", symbols.len(), file_uri);
+        Ok(symbols)
     }
+}
 
-    /// Clean up type signature text by removing extra whitespace and formatting
-    fn clean_type_signature(sig: &str) -> String {
-        sig.lines()
-            .map(|line| line.trim())
-            .collect::<Vec<_>>()
-            .join(" ")
-            .split_whitespace()
-            .collect::<Vec<_>>()
-            .join(" ")
+fn declaration_to_symbol(declaration: Declaration, source: &str, file_uri: &Url) -> Symbol {
+    let kind = match declaration.kind {
+        DeclarationKind::Function => SymbolKind::FUNCTION,
+        // Using CLASS for type definitions
+        DeclarationKind::Type => SymbolKind::CLASS,
+        DeclarationKind::Constructor => SymbolKind::CONSTRUCTOR,
+        DeclarationKind::Module => SymbolKind::MODULE,
+    };
+
+    Symbol {
+        name: declaration.name,
+        kind,
+        location: Location::new(file_uri.clone(), text_range(declaration.range, source)),
+        container_name: declaration.container,
+        type_signature: declaration.signature,
+        documentation: declaration.documentation,
     }
 }
 
@@ -756,6 +1116,98 @@ mod tests {
     use super::*;
     use crate::Parser;
 
+    #[test]
+    fn test_function_name_ranges_pair_annotation_and_definition() {
+        let source = r#"module Shapes exposing (..)
+
+area : Float -> Float
+area r =
+    r
+
+perimeter r =
+    r
+"#;
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        let names = function_name_ranges(&tree, source);
+        assert_eq!(
+            names["area"].annotation,
+            Some(Range::new(Position::new(2, 0), Position::new(2, 4)))
+        );
+        assert_eq!(
+            names["area"].definition,
+            Some(Range::new(Position::new(3, 0), Position::new(3, 4)))
+        );
+        assert_eq!(names["perimeter"].annotation, None);
+        assert_eq!(names["perimeter"].ranges().count(), 1);
+    }
+
+    #[test]
+    fn test_alternate_function_range() {
+        let source = r#"module Shapes exposing (..)
+
+area : Float -> Float
+area r =
+    r
+
+perimeter r =
+    r
+"#;
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        let annotation = Range::new(Position::new(2, 0), Position::new(2, 4));
+        let definition = Range::new(Position::new(3, 0), Position::new(3, 4));
+        assert_eq!(
+            alternate_function_range(&tree, source, Position::new(2, 10)),
+            Some(definition)
+        );
+        assert_eq!(
+            alternate_function_range(&tree, source, Position::new(4, 4)),
+            Some(annotation)
+        );
+        assert_eq!(
+            alternate_function_range(&tree, source, Position::new(6, 2)),
+            None
+        );
+        assert_eq!(
+            alternate_function_range(&tree, source, Position::new(0, 2)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_declaration_ranges_include_annotations() {
+        let source = r#"module Shapes exposing (..)
+
+type Shape
+    = Circle Float
+
+area : Shape -> Float
+area shape =
+    0
+
+-- Not annotated
+perimeter shape =
+    0
+"#;
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        let ranges = declaration_ranges(&tree, source);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(
+            ranges["Shape"],
+            Range::new(Position::new(2, 0), Position::new(3, 18))
+        );
+        assert_eq!(
+            ranges["area"],
+            Range::new(Position::new(5, 0), Position::new(7, 5))
+        );
+        assert_eq!(ranges["perimeter"].start, Position::new(10, 0));
+    }
+
     #[test]
     fn test_symbol_extraction_comprehensive() {
         let gren_source = r#"
@@ -912,6 +1364,16 @@ simple x = x + 1
             "Should find Triangle constructor"
         );
 
+        // Constructors carry their argument types and the type they build
+        let rectangle = constructors
+            .iter()
+            .find(|c| c.name == "Rectangle")
+            .expect("Should find Rectangle constructor");
+        assert_eq!(
+            rectangle.type_signature,
+            Some("Float -> Float -> Point -> Shape".to_string())
+        );
+
         // Test module extraction
         assert!(
             modules.iter().any(|m| m.name == "Utils"),
@@ -1064,6 +1526,410 @@ main = Browser.sandbox { init = init, update = update, view = view }
         );
     }
 
+    #[test]
+    fn test_migrate_original_layout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("symbols.db");
+        {
+            let connection = Connection::open(&path).unwrap();
+            connection
+                .execute_batch(
+                    "CREATE TABLE symbols (
+                        id INTEGER PRIMARY KEY, name TEXT NOT NULL, kind TEXT NOT NULL,
+                        file_uri TEXT NOT NULL, start_line INTEGER NOT NULL,
+                        start_character INTEGER NOT NULL, end_line INTEGER NOT NULL,
+                        end_character INTEGER NOT NULL, container_name TEXT,
+                        type_signature TEXT, documentation TEXT
+                    );
+                    CREATE INDEX idx_symbols_name ON symbols(name);
+                    CREATE VIRTUAL TABLE symbol_docs USING fts5(
+                        name, documentation, tokenize = 'porter unicode61'
+                    );
+                    CREATE TABLE todos (
+                        id INTEGER PRIMARY KEY, file_uri TEXT NOT NULL, marker TEXT NOT NULL,
+                        text TEXT NOT NULL, start_line INTEGER NOT NULL,
+                        start_character INTEGER NOT NULL, end_line INTEGER NOT NULL,
+                        end_character INTEGER NOT NULL
+                    );
+                    INSERT INTO symbols VALUES
+                        (1, 'Shapes', 'Module', 'file:///Shapes.gren', 0, 7, 0, 13, '', '', ''),
+                        (2, 'area', 'Function', 'file:///Shapes.gren', 4, 0, 4, 4, '',
+                         'Shape -> Float', 'Area of a shape'),
+                        (3, 'Circle', 'Constructor', 'file:///Shapes.gren', 2, 6, 2, 12,
+                         'Shape', 'Float -> Shape', '');
+                    INSERT INTO symbol_docs (rowid, name, documentation)
+                        VALUES (2, 'area', 'Area of a shape');
+                    INSERT INTO todos VALUES
+                        (1, 'file:///Shapes.gren', 'TODO', 'handle squares', 6, 3, 6, 22);",
+                )
+                .unwrap();
+        }
+
+        let index = SymbolIndex::open(&path).unwrap();
+        let area = index.find_exact_symbol("area").unwrap();
+        assert_eq!(area.len(), 1);
+        assert_eq!(area[0].kind, SymbolKind::FUNCTION);
+        assert_eq!(
+            area[0].location.range,
+            Range::new(Position::new(4, 0), Position::new(4, 4))
+        );
+        assert_eq!(area[0].type_signature.as_deref(), Some("Shape -> Float"));
+        assert_eq!(area[0].container_name, None);
+        let circle = index.find_exact_symbol("Circle").unwrap();
+        assert_eq!(circle[0].container_name.as_deref(), Some("Shape"));
+        assert_eq!(
+            index.search_documentation("shape area", 10).unwrap()[0].name,
+            "area"
+        );
+        assert_eq!(index.all_todos().unwrap()[0].text, "handle squares");
+
+        let stats = index.stats().unwrap();
+        assert_eq!((stats.symbols, stats.files, stats.modules), (3, 1, 1));
+        assert!(stats.bytes_before_migration.is_some());
+        assert!(stats.bytes_after_migration.is_some());
+
+        // Opening it again leaves the converted database alone
+        drop(index);
+        let index = SymbolIndex::open(&path).unwrap();
+        assert_eq!(index.find_symbol("area").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stats_and_file_interning() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let index = SymbolIndex::open(&dir.path().join("symbols.db")).unwrap();
+        let uri = Url::parse("file:///Main.gren").unwrap();
+        for (name, kind, line) in [
+            ("Main", SymbolKind::MODULE, 0),
+            ("main", SymbolKind::FUNCTION, 2_000_000),
+        ] {
+            let symbol = Symbol {
+                name: name.to_string(),
+                kind,
+                location: Location::new(
+                    uri.clone(),
+                    Range::new(Position::new(line, 3), Position::new(line, 900_000)),
+                ),
+                container_name: None,
+                type_signature: None,
+                documentation: None,
+            };
+            index.index_symbol(&symbol).unwrap();
+        }
+
+        let main = index.find_exact_symbol("main").unwrap();
+        assert_eq!(
+            main[0].location.range,
+            Range::new(
+                Position::new(2_000_000, 3),
+                Position::new(2_000_000, 900_000)
+            )
+        );
+        let stats = index.stats().unwrap();
+        assert_eq!((stats.symbols, stats.files, stats.modules), (2, 1, 1));
+        assert_eq!(stats.bytes_before_migration, None);
+
+        index.clear_file_symbols(uri.as_str()).unwrap();
+        let stats = index.stats().unwrap();
+        assert_eq!((stats.symbols, stats.files), (0, 0));
+    }
+
+    #[test]
+    fn test_references_replaced_per_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let index = SymbolIndex::open(&dir.path().join("symbols.db")).unwrap();
+        let reference = |uri: &str, module: &str, line: u32| SymbolReference {
+            name: "view".to_string(),
+            module: module.to_string(),
+            location: Location::new(
+                Url::parse(uri).unwrap(),
+                Range::new(Position::new(line, 4), Position::new(line, 8)),
+            ),
+            kind: ReferenceKind::Expression,
+            container: Some("main".to_string()),
+        };
+
+        index
+            .replace_file_references(
+                "file:///Main.gren",
+                &[
+                    reference("file:///Main.gren", "Main", 7),
+                    reference("file:///Main.gren", "Html", 9),
+                ],
+            )
+            .unwrap();
+        index
+            .replace_file_references(
+                "file:///App.gren",
+                &[reference("file:///App.gren", "Main", 2)],
+            )
+            .unwrap();
+        index
+            .replace_file_references(
+                "file:///Main.gren",
+                &[reference("file:///Main.gren", "Main", 3)],
+            )
+            .unwrap();
+
+        let found = index.find_references("Main", "view").unwrap();
+        assert_eq!(
+            found,
+            vec![
+                reference("file:///App.gren", "Main", 2),
+                reference("file:///Main.gren", "Main", 3),
+            ]
+        );
+        assert!(index.find_references("Html", "view").unwrap().is_empty());
+        assert_eq!(
+            index
+                .find_references_from("file:///App.gren", "main")
+                .unwrap(),
+            vec![reference("file:///App.gren", "Main", 2)]
+        );
+        assert!(index
+            .find_references_from("file:///App.gren", "view")
+            .unwrap()
+            .is_empty());
+        // Modules that are only referred to aren't counted as indexed
+        assert_eq!(index.stats().unwrap().modules, 0);
+
+        index
+            .replace_file_references("file:///App.gren", &[])
+            .unwrap();
+        index
+            .replace_file_references("file:///Main.gren", &[])
+            .unwrap();
+        assert_eq!(index.stats().unwrap().files, 0);
+    }
+
+    #[test]
+    fn test_find_symbol_limited() {
+        let index = SymbolIndex::new().expect("Failed to create symbol index");
+
+        let file_uri = Url::parse("file:///limited_test.gren").expect("Invalid URI");
+        let _ = index.clear_file_symbols(file_uri.as_str());
+
+        for i in 0..5 {
+            let symbol = Symbol {
+                name: format!("limitedSymbol{}", i),
+                kind: SymbolKind::FUNCTION,
+                location: Location::new(
+                    file_uri.clone(),
+                    Range::new(Position::new(i, 0), Position::new(i, 14)),
+                ),
+                container_name: None,
+                type_signature: None,
+                documentation: None,
+            };
+            index.index_symbol(&symbol).expect("Failed to index symbol");
+        }
+
+        let limited = index
+            .find_symbol_limited("limitedSymbol", 0, 3)
+            .expect("Failed to search");
+        assert_eq!(limited.len(), 3, "Should stop at the limit");
+
+        let next_page = index
+            .find_symbol_limited("limitedSymbol", 3, 3)
+            .expect("Failed to search");
+        let names: Vec<&str> = next_page
+            .iter()
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["limitedSymbol3", "limitedSymbol4"]);
+
+        let all = index
+            .find_symbol_limited("limitedSymbol", 0, 100)
+            .expect("Failed to search");
+        assert_eq!(all.len(), 5);
+
+        index
+            .clear_file_symbols(file_uri.as_str())
+            .expect("Failed to clear symbols");
+    }
+
+    #[test]
+    fn test_find_symbol_filtered() {
+        let index = SymbolIndex::new().expect("Failed to create symbol index");
+
+        let page_uri = Url::parse("file:///filtered/src/Pages/Home.gren").expect("Invalid URI");
+        let main_uri = Url::parse("file:///filtered/src/Main.gren").expect("Invalid URI");
+        for uri in [&page_uri, &main_uri] {
+            let _ = index.clear_file_symbols(uri.as_str());
+        }
+
+        for (name, kind, uri) in [
+            ("filteredModel", SymbolKind::CLASS, &page_uri),
+            ("filteredView", SymbolKind::FUNCTION, &page_uri),
+            ("filteredMain", SymbolKind::FUNCTION, &main_uri),
+        ] {
+            let symbol = Symbol {
+                name: name.to_string(),
+                kind,
+                location: Location::new(
+                    uri.clone(),
+                    Range::new(Position::new(0, 0), Position::new(0, name.len() as u32)),
+                ),
+                container_name: None,
+                type_signature: None,
+                documentation: None,
+            };
+            index.index_symbol(&symbol).expect("Failed to index symbol");
+        }
+
+        let names = |query: &str| -> Vec<String> {
+            index
+                .find_symbol_filtered(&SymbolQuery::parse(query), 0, 10)
+                .expect("Failed to search")
+                .into_iter()
+                .map(|symbol| symbol.name)
+                .collect()
+        };
+        assert_eq!(names("kind:type filtered"), vec!["filteredModel"]);
+        assert_eq!(
+            names("file:src/Pages/** filtered"),
+            vec!["filteredView", "filteredModel"]
+        );
+        assert_eq!(
+            names("kind:function file:src/Pages/** filtered"),
+            vec!["filteredView"]
+        );
+
+        for uri in [&page_uri, &main_uri] {
+            index
+                .clear_file_symbols(uri.as_str())
+                .expect("Failed to clear symbols");
+        }
+    }
+
+    #[test]
+    fn test_search_documentation() {
+        let index = SymbolIndex::new().expect("Failed to create symbol index");
+
+        let file_uri = Url::parse("file:///doc_search_test.gren").expect("Invalid URI");
+        let _ = index.clear_file_symbols(file_uri.as_str());
+
+        let documented = [
+            ("fromIsoString", "Parse a date from an ISO 8601 string"),
+            ("toUpper", "Convert a string to upper case"),
+        ];
+        for (line, (name, doc)) in documented.iter().enumerate() {
+            let symbol = Symbol {
+                name: name.to_string(),
+                kind: SymbolKind::FUNCTION,
+                location: Location::new(
+                    file_uri.clone(),
+                    Range::new(
+                        Position::new(line as u32, 0),
+                        Position::new(line as u32, name.len() as u32),
+                    ),
+                ),
+                container_name: None,
+                type_signature: None,
+                documentation: Some(doc.to_string()),
+            };
+            index.index_symbol(&symbol).expect("Failed to index symbol");
+        }
+
+        let results = index
+            .search_documentation("find the function that parses dates", 10)
+            .expect("Failed to search documentation");
+        assert_eq!(
+            results.first().map(|s| s.name.as_str()),
+            Some("fromIsoString")
+        );
+
+        assert!(index
+            .search_documentation("  ?! ", 10)
+            .expect("Failed to search documentation")
+            .is_empty());
+
+        let slugify = Symbol {
+            name: "slugify".to_string(),
+            kind: SymbolKind::FUNCTION,
+            location: Location::new(
+                file_uri.clone(),
+                Range::new(Position::new(2, 0), Position::new(2, 7)),
+            ),
+            container_name: None,
+            type_signature: None,
+            documentation: None,
+        };
+        index
+            .index_symbol(&slugify)
+            .expect("Failed to index symbol");
+        index
+            .index_comments(
+                file_uri.as_str(),
+                &[
+                    DeclarationComments {
+                        name: "toUpper".to_string(),
+                        text: "keeps accented letters".to_string(),
+                    },
+                    DeclarationComments {
+                        name: "slugify".to_string(),
+                        text: "dashes between the words of a title".to_string(),
+                    },
+                ],
+            )
+            .expect("Failed to index comments");
+        let names = |query: &str| -> Vec<String> {
+            index
+                .search_documentation(query, 10)
+                .expect("Failed to search documentation")
+                .into_iter()
+                .filter(|symbol| symbol.location.uri == file_uri)
+                .map(|symbol| symbol.name)
+                .collect()
+        };
+        assert_eq!(names("accented"), vec!["toUpper"]);
+        assert_eq!(names("upper case"), vec!["toUpper"]);
+        assert_eq!(names("title dashes"), vec!["slugify"]);
+
+        index
+            .clear_file_symbols(file_uri.as_str())
+            .expect("Failed to clear symbols");
+        let after_clear = index
+            .search_documentation("parse date", 10)
+            .expect("Failed to search documentation");
+        assert!(after_clear.iter().all(|s| s.location.uri != file_uri));
+    }
+
+    #[test]
+    fn test_migration_backfills_documentation_index() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("symbols.db");
+        {
+            let index = SymbolIndex::open(&path).unwrap();
+            let symbol = Symbol {
+                name: "area".to_string(),
+                kind: SymbolKind::FUNCTION,
+                location: Location::new(
+                    Url::parse("file:///Shapes.gren").unwrap(),
+                    Range::new(Position::new(4, 0), Position::new(4, 4)),
+                ),
+                container_name: None,
+                type_signature: None,
+                documentation: Some("Area of a shape".to_string()),
+            };
+            index.index_symbol(&symbol).unwrap();
+        }
+        // A database from before the documentation index had comments, or existed at all
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "DROP TABLE symbol_docs;
+                 CREATE VIRTUAL TABLE symbol_docs USING fts5(name, documentation);
+                 PRAGMA user_version = 4;",
+            )
+            .unwrap();
+
+        let index = SymbolIndex::open(&path).unwrap();
+        let found = index.search_documentation("shape area", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "area");
+    }
+
     #[test]
     fn test_sum_type_extraction_with_signature() {
         use crate::Document;
@@ -0,0 +1,344 @@
+//! Workspace symbol queries constrained to a module or module subtree, symbol kinds or
+//! files
+//!
+//! Besides a plain name, `workspace/symbol` accepts `Data.List:map` for symbols named like
+//! `map` in `Data.List`, and `module:Data.*` for every symbol in the modules under `Data`,
+//! optionally followed by a name (`module:Data.* map`). `Data.*:map` works too. Queries
+//! whose module part isn't a module name are searched for as plain names.
+//!
+//! Anywhere in the query, `kind:type` keeps only symbols of that kind (`function`, `type`,
+//! `constructor` or `module`, several separated by commas) and `file:src/Pages/**` only
+//! those in files matching a glob relative to the workspace root. Modifiers that don't
+//! parse are searched for as part of the name.
+
+use lsp_types::SymbolKind;
+
+/// Modules a symbol query is restricted to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleFilter {
+    /// Exactly this module
+    Module(String),
+    /// Every module below this prefix, e.g. `Data.List` and `Data.Dict.Extra` for `Data`
+    Subtree(String),
+}
+
+impl ModuleFilter {
+    /// Parse `Data.List` or `Data.*`
+    fn parse(pattern: &str) -> Option<Self> {
+        let (name, subtree) = match pattern.strip_suffix(".*") {
+            Some(prefix) => (prefix, true),
+            None => (pattern, false),
+        };
+        if !is_module_name(name) {
+            return None;
+        }
+        Some(if subtree {
+            ModuleFilter::Subtree(name.to_string())
+        } else {
+            ModuleFilter::Module(name.to_string())
+        })
+    }
+
+    /// Whether `module_name` is one of the filtered modules
+    pub fn matches(&self, module_name: &str) -> bool {
+        match self {
+            ModuleFilter::Module(name) => module_name == name,
+            ModuleFilter::Subtree(prefix) => module_name
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.starts_with('.')),
+        }
+    }
+
+    /// Path fragment the files of the filtered modules contain, `/Data/List.gren` for
+    /// `Data.List` and `/Data/` for `Data.*`. A file can contain it without being one of
+    /// the modules (`Vendor/Data/List.gren` declaring `Vendor.Data.List`), so it narrows
+    /// a search down but `matches` has the final say.
+    pub fn path_fragment(&self) -> String {
+        match self {
+            ModuleFilter::Module(name) => format!("/{}.gren", name.replace('.', "/")),
+            ModuleFilter::Subtree(prefix) => format!("/{}/", prefix.replace('.', "/")),
+        }
+    }
+}
+
+/// Files a symbol query is restricted to, by a glob relative to the workspace root
+///
+/// `*` and `?` match within one path segment and `**` any number of segments, so
+/// `src/Pages/**` covers every file below `src/Pages` and `**/Main.gren` every `Main.gren`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFilter {
+    segments: Vec<String>,
+}
+
+impl FileFilter {
+    /// Parse a glob, `None` when it's empty
+    pub fn parse(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim_start_matches("./").trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+        Some(FileFilter {
+            segments: pattern
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+
+    /// Whether `path`, relative to the workspace root and separated by `/`, matches
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<&str> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        glob_segments(&self.segments, &path)
+    }
+
+    /// Whether any trailing run of whole segments of `path` matches, for files whose
+    /// path relative to the workspace root isn't known
+    pub fn matches_tail(&self, path: &str) -> bool {
+        let path: Vec<&str> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        (0..path.len()).any(|start| glob_segments(&self.segments, &path[start..]))
+    }
+
+    /// Path fragment the matching files contain: the segments before the first wildcard,
+    /// `/src/Pages/` for `src/Pages/**`. Like `ModuleFilter::path_fragment` it narrows a
+    /// search down and `matches` has the final say.
+    pub fn path_fragment(&self) -> String {
+        let literal: Vec<&str> = self
+            .segments
+            .iter()
+            .take_while(|segment| !segment.contains(['*', '?']))
+            .map(String::as_str)
+            .collect();
+        if literal.len() == self.segments.len() {
+            format!("/{}", literal.join("/"))
+        } else if literal.is_empty() {
+            "/".to_string()
+        } else {
+            format!("/{}/", literal.join("/"))
+        }
+    }
+}
+
+fn glob_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skipped| glob_segments(rest, &path[skipped..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => {
+                glob_segment(first.as_bytes(), segment.as_bytes()) && glob_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn glob_segment(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skipped| glob_segment(rest, &text[skipped..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_segment(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_segment(rest, &text[1..]),
+    }
+}
+
+/// A parsed `workspace/symbol` query
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SymbolQuery {
+    /// Text symbol names must contain
+    pub name: String,
+    /// Modules the symbols must be defined in, `None` for anywhere
+    pub modules: Option<ModuleFilter>,
+    /// Kinds the symbols must have, empty for any
+    pub kinds: Vec<SymbolKind>,
+    /// Files the symbols must be defined in, `None` for anywhere
+    pub files: Option<FileFilter>,
+}
+
+impl SymbolQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut kinds = Vec::new();
+        let mut files = None;
+        let mut rest = Vec::new();
+        for token in query.split_whitespace() {
+            if let Some(parsed) = token.strip_prefix("kind:").and_then(parse_kinds) {
+                kinds.extend(parsed);
+            } else if let Some(filter) = token.strip_prefix("file:").and_then(FileFilter::parse) {
+                files = Some(filter);
+            } else {
+                rest.push(token);
+            }
+        }
+
+        SymbolQuery {
+            kinds,
+            files,
+            ..Self::parse_modules(&rest.join(" "))
+        }
+    }
+
+    /// Whether the query is just a name, with nothing narrowing where or what it finds
+    pub fn is_plain(&self) -> bool {
+        self.modules.is_none() && self.kinds.is_empty() && self.files.is_none()
+    }
+
+    fn parse_modules(query: &str) -> Self {
+        let query = query.trim();
+        let plain = || SymbolQuery {
+            name: query.to_string(),
+            ..SymbolQuery::default()
+        };
+
+        let (pattern, name) = if let Some(rest) = query.strip_prefix("module:") {
+            let rest = rest.trim_start();
+            match rest.split_once(char::is_whitespace) {
+                Some((pattern, name)) => (pattern, name.trim()),
+                None => (rest, ""),
+            }
+        } else if let Some((pattern, name)) = query.split_once(':') {
+            (pattern, name.trim())
+        } else {
+            return plain();
+        };
+
+        match ModuleFilter::parse(pattern) {
+            Some(modules) => SymbolQuery {
+                name: name.to_string(),
+                modules: Some(modules),
+                ..SymbolQuery::default()
+            },
+            None => plain(),
+        }
+    }
+}
+
+/// `type,function` as symbol kinds, `None` if any of them isn't a Gren symbol kind
+fn parse_kinds(kinds: &str) -> Option<Vec<SymbolKind>> {
+    kinds
+        .split(',')
+        .map(|kind| match kind.to_ascii_lowercase().as_str() {
+            "function" | "value" => Some(SymbolKind::FUNCTION),
+            "type" => Some(SymbolKind::CLASS),
+            "constructor" => Some(SymbolKind::CONSTRUCTOR),
+            "module" => Some(SymbolKind::MODULE),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `name` is a dotted sequence of capitalized identifiers
+fn is_module_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|segment| {
+            segment.starts_with(|c: char| c.is_ascii_uppercase())
+                && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str, modules: Option<ModuleFilter>) -> SymbolQuery {
+        SymbolQuery {
+            name: name.to_string(),
+            modules,
+            ..SymbolQuery::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_queries() {
+        let module = |name: &str| Some(ModuleFilter::Module(name.to_string()));
+        let subtree = |name: &str| Some(ModuleFilter::Subtree(name.to_string()));
+
+        assert_eq!(SymbolQuery::parse("map"), query("map", None));
+        assert_eq!(
+            SymbolQuery::parse("Data.List:map"),
+            query("map", module("Data.List"))
+        );
+        assert_eq!(
+            SymbolQuery::parse("module:Data.*"),
+            query("", subtree("Data"))
+        );
+        assert_eq!(
+            SymbolQuery::parse("module:Data.* map"),
+            query("map", subtree("Data"))
+        );
+        assert_eq!(
+            SymbolQuery::parse("Data.*:map"),
+            query("map", subtree("Data"))
+        );
+        // Not a module name, so searched for as written
+        assert_eq!(SymbolQuery::parse("data:map"), query("data:map", None));
+        assert_eq!(SymbolQuery::parse("module:*"), query("module:*", None));
+    }
+
+    #[test]
+    fn test_module_filters() {
+        let subtree = ModuleFilter::Subtree("Data".to_string());
+        assert!(subtree.matches("Data.List"));
+        assert!(subtree.matches("Data.Dict.Extra"));
+        assert!(!subtree.matches("Data"));
+        assert!(!subtree.matches("DataStore.Cache"));
+        assert_eq!(subtree.path_fragment(), "/Data/");
+
+        let module = ModuleFilter::Module("Data.List".to_string());
+        assert!(module.matches("Data.List"));
+        assert!(!module.matches("Data.List.Extra"));
+        assert_eq!(module.path_fragment(), "/Data/List.gren");
+    }
+
+    #[test]
+    fn test_kind_and_file_modifiers() {
+        let parsed = SymbolQuery::parse("kind:type foo");
+        assert_eq!(parsed.name, "foo");
+        assert_eq!(parsed.kinds, vec![SymbolKind::CLASS]);
+
+        let parsed =
+            SymbolQuery::parse("file:src/Pages/** module:Data.* bar kind:function,constructor");
+        assert_eq!(parsed.name, "bar");
+        assert_eq!(
+            parsed.modules,
+            Some(ModuleFilter::Subtree("Data".to_string()))
+        );
+        assert_eq!(
+            parsed.kinds,
+            vec![SymbolKind::FUNCTION, SymbolKind::CONSTRUCTOR]
+        );
+        assert!(!parsed.is_plain());
+
+        // Unknown kinds aren't modifiers
+        let parsed = SymbolQuery::parse("kind:widget");
+        assert_eq!(parsed.name, "kind:widget");
+        assert!(parsed.is_plain());
+    }
+
+    #[test]
+    fn test_file_globs() {
+        let pages = FileFilter::parse("src/Pages/**").unwrap();
+        assert!(pages.matches("src/Pages/Home.gren"));
+        assert!(pages.matches("src/Pages/Admin/Users.gren"));
+        assert!(!pages.matches("src/Main.gren"));
+        assert!(!pages.matches("lib/src/Pages/Home.gren"));
+        assert!(pages.matches_tail("/home/me/app/src/Pages/Home.gren"));
+        assert_eq!(pages.path_fragment(), "/src/Pages/");
+
+        let mains = FileFilter::parse("**/Main*.gren").unwrap();
+        assert!(mains.matches("Main.gren"));
+        assert!(mains.matches("src/MainView.gren"));
+        assert!(!mains.matches("src/Page.gren"));
+        assert_eq!(mains.path_fragment(), "/");
+
+        let exact = FileFilter::parse("./src/Main.gren").unwrap();
+        assert!(exact.matches("src/Main.gren"));
+        assert_eq!(exact.path_fragment(), "/src/Main.gren");
+    }
+}
@@ -0,0 +1,818 @@
+use crate::comments::DeclarationComments;
+use crate::scope_analysis::SymbolReference;
+use crate::symbol::{Symbol, SymbolIndex};
+use crate::symbol_query::{ModuleFilter, SymbolQuery};
+use crate::todos::TodoItem;
+use anyhow::Result;
+use lsp_types::SymbolKind;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+/// Storage backend for indexed symbols
+///
+/// The workspace only talks to symbols through this trait so the backing store can be
+/// swapped: sqlite is the default, the in-memory store needs no filesystem (tests, wasm)
+/// and the tantivy store searches documentation with a full-text index.
+///
+/// The methods are synchronous, like the `Workspace` queries built on them: every backend
+/// answers from memory or a local database, and the server already runs request handlers
+/// on blocking threads.
+pub trait SymbolStore: Send + Sync {
+    /// Add a symbol to the store
+    fn add_symbol(&self, symbol: &Symbol) -> Result<()>;
+
+    /// Find symbols whose name contains `query` (case-insensitive)
+    fn find_symbols(&self, query: &str) -> Result<Vec<Symbol>>;
+
+    /// Like `find_symbols`, but returns the page of at most `limit` results after the first
+    /// `offset`, shortest names first
+    fn find_symbols_limited(&self, query: &str, offset: usize, limit: usize)
+        -> Result<Vec<Symbol>>;
+
+    /// Like `find_symbols_limited` for `query.name`, restricted to `query.kinds` and to
+    /// files whose path could belong to `query.modules` and `query.files` (see their
+    /// `path_fragment`)
+    fn find_symbols_filtered(
+        &self,
+        query: &SymbolQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Symbol>>;
+
+    /// Find symbols whose name is exactly `name`
+    fn find_exact_symbols(&self, name: &str) -> Result<Vec<Symbol>>;
+
+    /// Record the comments of the given file's declarations for `search_documentation`,
+    /// with the top-level symbol each declaration defines. They go when the file's symbols
+    /// are removed.
+    fn index_comments(&self, file_uri: &str, comments: &[DeclarationComments]) -> Result<()>;
+
+    /// Find symbols whose documentation or comments match a natural-language query, best
+    /// matches first
+    fn search_documentation(&self, query: &str, limit: usize) -> Result<Vec<Symbol>>;
+
+    /// Symbols documented as `@deprecated`, ordered by file and position
+    fn deprecated_symbols(&self) -> Result<Vec<Symbol>>;
+
+    /// Functions and values whose signature mentions the type `type_name`, ordered by file
+    /// and position
+    fn symbols_using_type(&self, type_name: &str) -> Result<Vec<Symbol>>;
+
+    /// Remove every symbol defined in the given file
+    fn remove_file_symbols(&self, file_uri: &str) -> Result<()>;
+
+    /// Replace the task markers recorded for the given file
+    fn replace_file_todos(&self, file_uri: &str, todos: &[TodoItem]) -> Result<()>;
+
+    /// Every recorded task marker, ordered by file and position
+    fn all_todos(&self) -> Result<Vec<TodoItem>>;
+
+    /// Replace the resolved references to top-level names recorded for the given file
+    fn replace_file_references(&self, file_uri: &str, references: &[SymbolReference])
+        -> Result<()>;
+
+    /// Every recorded occurrence of `module`'s top-level `name`, ordered by file and
+    /// position
+    fn find_references(&self, module: &str, name: &str) -> Result<Vec<SymbolReference>>;
+
+    /// The references recorded in the definition of the top-level declaration `container`
+    /// of the given file, ordered by position
+    fn find_references_from(&self, file_uri: &str, container: &str)
+        -> Result<Vec<SymbolReference>>;
+
+    /// How much the store holds, and for a database how big it is
+    fn stats(&self) -> Result<IndexStats>;
+
+    /// Whether the store rejects writes, so newly parsed symbols can't be indexed
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/// Size of a symbol store
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStats {
+    pub symbols: usize,
+    /// Files with symbols or task markers
+    pub files: usize,
+    /// Modules declared by those files
+    pub modules: usize,
+    /// Size of the database, `None` for a store kept in memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_bytes: Option<u64>,
+    /// Size of the database before it was converted from the original layout, which
+    /// stored every file URI and range coordinate in each row
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_before_migration: Option<u64>,
+    /// Size right after that conversion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_after_migration: Option<u64>,
+}
+
+/// Which `SymbolStore` implementation to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SymbolStoreBackend {
+    /// Persistent sqlite database (default)
+    #[default]
+    Sqlite,
+    /// Process-local store, nothing is written to disk
+    InMemory,
+    /// Process-local store with a tantivy full-text index for documentation search, in
+    /// builds with the `tantivy` feature
+    Tantivy,
+}
+
+impl SymbolStoreBackend {
+    /// Create a new store for this backend
+    pub fn create(self) -> Result<Arc<dyn SymbolStore>> {
+        match self {
+            SymbolStoreBackend::Sqlite => Ok(Arc::new(SymbolIndex::new()?)),
+            SymbolStoreBackend::InMemory => Ok(Arc::new(InMemorySymbolStore::new())),
+            #[cfg(feature = "tantivy")]
+            SymbolStoreBackend::Tantivy => {
+                Ok(Arc::new(crate::tantivy_store::TantivySymbolStore::new()?))
+            }
+            #[cfg(not(feature = "tantivy"))]
+            SymbolStoreBackend::Tantivy => {
+                anyhow::bail!("this build has no tantivy support, see the `tantivy` feature")
+            }
+        }
+    }
+}
+
+impl SymbolStore for SymbolIndex {
+    fn add_symbol(&self, symbol: &Symbol) -> Result<()> {
+        Ok(self.index_symbol(symbol)?)
+    }
+
+    fn find_symbols(&self, query: &str) -> Result<Vec<Symbol>> {
+        Ok(self.find_symbol(query)?)
+    }
+
+    fn find_symbols_limited(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        Ok(self.find_symbol_limited(query, offset, limit)?)
+    }
+
+    fn find_symbols_filtered(
+        &self,
+        query: &SymbolQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        Ok(self.find_symbol_filtered(query, offset, limit)?)
+    }
+
+    fn find_exact_symbols(&self, name: &str) -> Result<Vec<Symbol>> {
+        Ok(self.find_exact_symbol(name)?)
+    }
+
+    fn index_comments(&self, file_uri: &str, comments: &[DeclarationComments]) -> Result<()> {
+        Ok(SymbolIndex::index_comments(self, file_uri, comments)?)
+    }
+
+    fn search_documentation(&self, query: &str, limit: usize) -> Result<Vec<Symbol>> {
+        Ok(SymbolIndex::search_documentation(self, query, limit)?)
+    }
+
+    fn deprecated_symbols(&self) -> Result<Vec<Symbol>> {
+        Ok(SymbolIndex::deprecated_symbols(self)?)
+    }
+
+    fn symbols_using_type(&self, type_name: &str) -> Result<Vec<Symbol>> {
+        Ok(SymbolIndex::symbols_using_type(self, type_name)?)
+    }
+
+    fn remove_file_symbols(&self, file_uri: &str) -> Result<()> {
+        Ok(self.clear_file_symbols(file_uri)?)
+    }
+
+    fn replace_file_todos(&self, file_uri: &str, todos: &[TodoItem]) -> Result<()> {
+        Ok(SymbolIndex::replace_file_todos(self, file_uri, todos)?)
+    }
+
+    fn all_todos(&self) -> Result<Vec<TodoItem>> {
+        Ok(SymbolIndex::all_todos(self)?)
+    }
+
+    fn replace_file_references(
+        &self,
+        file_uri: &str,
+        references: &[SymbolReference],
+    ) -> Result<()> {
+        Ok(SymbolIndex::replace_file_references(
+            self, file_uri, references,
+        )?)
+    }
+
+    fn find_references(&self, module: &str, name: &str) -> Result<Vec<SymbolReference>> {
+        Ok(SymbolIndex::find_references(self, module, name)?)
+    }
+
+    fn find_references_from(
+        &self,
+        file_uri: &str,
+        container: &str,
+    ) -> Result<Vec<SymbolReference>> {
+        Ok(SymbolIndex::find_references_from(
+            self, file_uri, container,
+        )?)
+    }
+
+    fn stats(&self) -> Result<IndexStats> {
+        Ok(SymbolIndex::stats(self)?)
+    }
+
+    fn is_read_only(&self) -> bool {
+        SymbolIndex::is_read_only(self)
+    }
+}
+
+/// Symbol store kept entirely in memory
+///
+/// Matches the sqlite backend's semantics: results come back in insertion order and
+/// substring matching ignores ASCII case, like sqlite's `LIKE`. Documentation search is
+/// approximate: symbols are ranked by how many query words prefix-match a word in their docs.
+#[derive(Default)]
+pub struct InMemorySymbolStore {
+    symbols: RwLock<Vec<Symbol>>,
+    /// Comments of declarations by the URI of their file
+    comments: RwLock<Vec<(String, DeclarationComments)>>,
+    todos: RwLock<Vec<TodoItem>>,
+    references: RwLock<Vec<SymbolReference>>,
+}
+
+impl InMemorySymbolStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn matching(&self, query: &str) -> Vec<Symbol> {
+        let query = query.to_ascii_lowercase();
+        let symbols = self.symbols.read().unwrap();
+        symbols
+            .iter()
+            .filter(|symbol| symbol.name.to_ascii_lowercase().contains(&query))
+            .cloned()
+            .collect()
+    }
+}
+
+impl SymbolStore for InMemorySymbolStore {
+    fn add_symbol(&self, symbol: &Symbol) -> Result<()> {
+        self.symbols.write().unwrap().push(symbol.clone());
+        Ok(())
+    }
+
+    fn find_symbols(&self, query: &str) -> Result<Vec<Symbol>> {
+        Ok(self.matching(query))
+    }
+
+    fn find_symbols_limited(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        let mut symbols = self.matching(query);
+        symbols.sort_by(|a, b| a.name.len().cmp(&b.name.len()).then(a.name.cmp(&b.name)));
+        Ok(symbols.into_iter().skip(offset).take(limit).collect())
+    }
+
+    fn find_symbols_filtered(
+        &self,
+        query: &SymbolQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        let mut symbols = self.matching(&query.name);
+        symbols.retain(|symbol| {
+            let uri = symbol.location.uri.as_str();
+            let in_modules = match &query.modules {
+                Some(modules @ ModuleFilter::Module(_)) => uri.ends_with(&modules.path_fragment()),
+                Some(modules @ ModuleFilter::Subtree(_)) => uri.contains(&modules.path_fragment()),
+                None => true,
+            };
+            let in_files = query
+                .files
+                .as_ref()
+                .map_or(true, |files| uri.contains(&files.path_fragment()));
+            in_modules && in_files && (query.kinds.is_empty() || query.kinds.contains(&symbol.kind))
+        });
+        symbols.sort_by(|a, b| a.name.len().cmp(&b.name.len()).then(a.name.cmp(&b.name)));
+        Ok(symbols.into_iter().skip(offset).take(limit).collect())
+    }
+
+    fn find_exact_symbols(&self, name: &str) -> Result<Vec<Symbol>> {
+        let symbols = self.symbols.read().unwrap();
+        Ok(symbols
+            .iter()
+            .filter(|symbol| symbol.name == name)
+            .cloned()
+            .collect())
+    }
+
+    fn search_documentation(&self, query: &str, limit: usize) -> Result<Vec<Symbol>> {
+        let terms = words(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let symbols = self.symbols.read().unwrap();
+        let comments = self.comments.read().unwrap();
+        let mut scored: Vec<(usize, &Symbol)> = symbols
+            .iter()
+            .filter_map(|symbol| {
+                let commented = comments.iter().find(|(uri, declaration)| {
+                    symbol.container_name.is_none()
+                        && uri == symbol.location.uri.as_str()
+                        && declaration.name == symbol.name
+                });
+                let text = match (&symbol.documentation, commented) {
+                    (Some(documentation), Some((_, declaration))) => {
+                        format!("{}\n{}", documentation, declaration.text)
+                    }
+                    (Some(documentation), None) => documentation.clone(),
+                    (None, Some((_, declaration))) => declaration.text.clone(),
+                    (None, None) => return None,
+                };
+                let doc_words = words(&text);
+                let score = terms
+                    .iter()
+                    .filter(|term| {
+                        doc_words
+                            .iter()
+                            .any(|word| word.starts_with(term.as_str()) || is_stem_of(word, term))
+                    })
+                    .count();
+                (score > 0).then_some((score, symbol))
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.name.cmp(&b.1.name)));
+        Ok(scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, symbol)| symbol.clone())
+            .collect())
+    }
+
+    fn deprecated_symbols(&self) -> Result<Vec<Symbol>> {
+        let mut symbols: Vec<Symbol> = self
+            .symbols
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|symbol| symbol.deprecation().is_some())
+            .cloned()
+            .collect();
+        sort_by_position(&mut symbols);
+        Ok(symbols)
+    }
+
+    fn symbols_using_type(&self, type_name: &str) -> Result<Vec<Symbol>> {
+        let mut symbols: Vec<Symbol> = self
+            .symbols
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|symbol| symbol.type_usage(type_name).is_some())
+            .cloned()
+            .collect();
+        sort_by_position(&mut symbols);
+        Ok(symbols)
+    }
+
+    fn remove_file_symbols(&self, file_uri: &str) -> Result<()> {
+        self.symbols
+            .write()
+            .unwrap()
+            .retain(|symbol| symbol.location.uri.as_str() != file_uri);
+        self.comments
+            .write()
+            .unwrap()
+            .retain(|(uri, _)| uri != file_uri);
+        Ok(())
+    }
+
+    fn index_comments(&self, file_uri: &str, comments: &[DeclarationComments]) -> Result<()> {
+        let mut stored = self.comments.write().unwrap();
+        stored.extend(
+            comments
+                .iter()
+                .map(|declaration| (file_uri.to_string(), declaration.clone())),
+        );
+        Ok(())
+    }
+
+    fn replace_file_todos(&self, file_uri: &str, todos: &[TodoItem]) -> Result<()> {
+        let mut stored = self.todos.write().unwrap();
+        stored.retain(|todo| todo.location.uri.as_str() != file_uri);
+        stored.extend_from_slice(todos);
+        Ok(())
+    }
+
+    fn all_todos(&self) -> Result<Vec<TodoItem>> {
+        let mut todos = self.todos.read().unwrap().clone();
+        todos.sort_by(|a, b| {
+            let (a, b) = (&a.location, &b.location);
+            (a.uri.as_str(), a.range.start.line, a.range.start.character).cmp(&(
+                b.uri.as_str(),
+                b.range.start.line,
+                b.range.start.character,
+            ))
+        });
+        Ok(todos)
+    }
+
+    fn replace_file_references(
+        &self,
+        file_uri: &str,
+        references: &[SymbolReference],
+    ) -> Result<()> {
+        let mut stored = self.references.write().unwrap();
+        stored.retain(|reference| reference.location.uri.as_str() != file_uri);
+        stored.extend_from_slice(references);
+        Ok(())
+    }
+
+    fn find_references(&self, module: &str, name: &str) -> Result<Vec<SymbolReference>> {
+        let mut references: Vec<SymbolReference> = self
+            .references
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|reference| reference.module == module && reference.name == name)
+            .cloned()
+            .collect();
+        references.sort_by(|a, b| {
+            let (a, b) = (&a.location, &b.location);
+            (a.uri.as_str(), a.range.start.line, a.range.start.character).cmp(&(
+                b.uri.as_str(),
+                b.range.start.line,
+                b.range.start.character,
+            ))
+        });
+        references.dedup();
+        Ok(references)
+    }
+
+    fn find_references_from(
+        &self,
+        file_uri: &str,
+        container: &str,
+    ) -> Result<Vec<SymbolReference>> {
+        let mut references: Vec<SymbolReference> = self
+            .references
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|reference| {
+                reference.location.uri.as_str() == file_uri
+                    && reference.container.as_deref() == Some(container)
+            })
+            .cloned()
+            .collect();
+        references.sort_by_key(|reference| {
+            let start = reference.location.range.start;
+            (start.line, start.character)
+        });
+        Ok(references)
+    }
+
+    fn stats(&self) -> Result<IndexStats> {
+        let symbols = self.symbols.read().unwrap();
+        let todos = self.todos.read().unwrap();
+        let files: HashSet<&str> = symbols
+            .iter()
+            .map(|symbol| symbol.location.uri.as_str())
+            .chain(todos.iter().map(|todo| todo.location.uri.as_str()))
+            .collect();
+        let modules: HashSet<&str> = symbols
+            .iter()
+            .filter(|symbol| symbol.kind == SymbolKind::MODULE)
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        Ok(IndexStats {
+            symbols: symbols.len(),
+            files: files.len(),
+            modules: modules.len(),
+            ..IndexStats::default()
+        })
+    }
+}
+
+/// Order symbols by file and position, like the sqlite backend's queries
+fn sort_by_position(symbols: &mut [Symbol]) {
+    symbols.sort_by(|a, b| {
+        let (a, b) = (&a.location, &b.location);
+        (a.uri.as_str(), a.range.start.line, a.range.start.character).cmp(&(
+            b.uri.as_str(),
+            b.range.start.line,
+            b.range.start.character,
+        ))
+    });
+}
+
+/// Lower-cased alphanumeric words of a text
+pub(crate) fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Whether `word` is `term` without an inflection, e.g. `parse` for `parses`
+fn is_stem_of(word: &str, term: &str) -> bool {
+    word.len() >= 3 && term.starts_with(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scope_analysis::ReferenceKind;
+    use crate::todos::TodoMarker;
+    use lsp_types::*;
+
+    fn symbol(name: &str, uri: &str) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            location: Location::new(
+                Url::parse(uri).unwrap(),
+                Range::new(Position::new(0, 0), Position::new(0, name.len() as u32)),
+            ),
+            container_name: None,
+            type_signature: None,
+            documentation: None,
+        }
+    }
+
+    #[test]
+    fn test_in_memory_store_queries() {
+        let store = InMemorySymbolStore::new();
+        store
+            .add_symbol(&symbol("parseValue", "file:///A.gren"))
+            .unwrap();
+        store
+            .add_symbol(&symbol("parse", "file:///B.gren"))
+            .unwrap();
+        store
+            .add_symbol(&symbol("render", "file:///B.gren"))
+            .unwrap();
+
+        let found = store.find_symbols("PARSE").unwrap();
+        assert_eq!(found.len(), 2);
+
+        let limited = store.find_symbols_limited("parse", 0, 1).unwrap();
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].name, "parse");
+        let next_page = store.find_symbols_limited("parse", 1, 1).unwrap();
+        assert_eq!(next_page[0].name, "parseValue");
+        assert!(store
+            .find_symbols_limited("parse", 2, 1)
+            .unwrap()
+            .is_empty());
+
+        let exact = store.find_exact_symbols("parse").unwrap();
+        assert_eq!(exact.len(), 1);
+
+        store.remove_file_symbols("file:///B.gren").unwrap();
+        let remaining = store.find_symbols("").unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name, "parseValue");
+    }
+
+    #[test]
+    fn test_in_memory_module_filter() {
+        let store = InMemorySymbolStore::new();
+        for (name, uri) in [
+            ("map", "file:///src/Data/List.gren"),
+            ("map", "file:///src/Data/Dict/Extra.gren"),
+            ("map", "file:///src/Main.gren"),
+            ("mapError", "file:///src/Data/List.gren"),
+        ] {
+            store.add_symbol(&symbol(name, uri)).unwrap();
+        }
+
+        let found = store
+            .find_symbols_filtered(&SymbolQuery::parse("module:Data.* map"), 0, 10)
+            .unwrap();
+        assert_eq!(found.len(), 3);
+
+        let found = store
+            .find_symbols_filtered(&SymbolQuery::parse("Data.List:map"), 0, 1)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "map");
+        assert_eq!(found[0].location.uri.path(), "/src/Data/List.gren");
+
+        let found = store
+            .find_symbols_filtered(&SymbolQuery::parse("file:src/Data/** map"), 0, 10)
+            .unwrap();
+        assert_eq!(found.len(), 3);
+        let found = store
+            .find_symbols_filtered(&SymbolQuery::parse("kind:type map"), 0, 10)
+            .unwrap();
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_in_memory_documentation_search() {
+        let store = InMemorySymbolStore::new();
+        let mut parse_date = symbol("fromIsoString", "file:///Date.gren");
+        parse_date.documentation = Some("Parse a date from an ISO 8601 string".to_string());
+        let mut upper = symbol("toUpper", "file:///String.gren");
+        upper.documentation = Some("Convert a string to upper case".to_string());
+        store.add_symbol(&parse_date).unwrap();
+        store.add_symbol(&upper).unwrap();
+        store
+            .add_symbol(&symbol("undocumented", "file:///String.gren"))
+            .unwrap();
+
+        let results = store
+            .search_documentation("function that parses dates", 10)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "fromIsoString");
+
+        store
+            .index_comments(
+                "file:///String.gren",
+                &[DeclarationComments {
+                    name: "undocumented".to_string(),
+                    text: "Strips the accents off letters".to_string(),
+                }],
+            )
+            .unwrap();
+        let results = store.search_documentation("strip accents", 10).unwrap();
+        assert_eq!(results[0].name, "undocumented");
+
+        store.remove_file_symbols("file:///String.gren").unwrap();
+        assert!(store
+            .search_documentation("strip accents", 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_in_memory_deprecated_symbols() {
+        let store = InMemorySymbolStore::new();
+        let mut old = symbol("oldThing", "file:///B.gren");
+        old.documentation = Some("Does the thing.\n@deprecated use newThing instead".to_string());
+        let mut bare = symbol("legacy", "file:///A.gren");
+        bare.documentation = Some("@deprecated".to_string());
+        let mut mentioned = symbol("newThing", "file:///B.gren");
+        mentioned.documentation = Some("Replaces the @deprecated oldThing".to_string());
+        for symbol in [&old, &bare, &mentioned] {
+            store.add_symbol(symbol).unwrap();
+        }
+
+        let deprecated = store.deprecated_symbols().unwrap();
+        let names: Vec<_> = deprecated
+            .iter()
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["legacy", "oldThing"]);
+        assert_eq!(deprecated[0].deprecation(), Some(""));
+        assert_eq!(deprecated[1].deprecation(), Some("use newThing instead"));
+    }
+
+    #[test]
+    fn test_in_memory_symbols_using_type() {
+        let store = InMemorySymbolStore::new();
+        let mut decode = symbol("decoder", "file:///B.gren");
+        decode.type_signature = Some("Decoder User".to_string());
+        let mut name = symbol("name", "file:///A.gren");
+        name.type_signature = Some("User -> String".to_string());
+        let mut id = symbol("id", "file:///A.gren");
+        id.type_signature = Some("UserId -> Int".to_string());
+        let mut user_type = symbol("User", "file:///A.gren");
+        user_type.kind = SymbolKind::CLASS;
+        user_type.type_signature = Some("User".to_string());
+        for symbol in [&decode, &name, &id, &user_type] {
+            store.add_symbol(symbol).unwrap();
+        }
+
+        let symbols = store.symbols_using_type("User").unwrap();
+        let names: Vec<_> = symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, vec!["name", "decoder"]);
+    }
+
+    #[test]
+    fn test_in_memory_todos_replaced_per_file() {
+        let store = InMemorySymbolStore::new();
+        let todo = |uri: &str, line: u32, text: &str| TodoItem {
+            marker: TodoMarker::Todo,
+            text: text.to_string(),
+            location: Location::new(
+                Url::parse(uri).unwrap(),
+                Range::new(Position::new(line, 3), Position::new(line, 20)),
+            ),
+        };
+
+        store
+            .replace_file_todos(
+                "file:///B.gren",
+                &[
+                    todo("file:///B.gren", 4, "later"),
+                    todo("file:///B.gren", 1, "first"),
+                ],
+            )
+            .unwrap();
+        store
+            .replace_file_todos("file:///A.gren", &[todo("file:///A.gren", 9, "a")])
+            .unwrap();
+        store
+            .replace_file_todos("file:///B.gren", &[todo("file:///B.gren", 2, "edited")])
+            .unwrap();
+
+        let texts: Vec<String> = store
+            .all_todos()
+            .unwrap()
+            .into_iter()
+            .map(|todo| todo.text)
+            .collect();
+        assert_eq!(texts, vec!["a", "edited"]);
+    }
+
+    #[test]
+    fn test_in_memory_references_replaced_per_file() {
+        let store = InMemorySymbolStore::new();
+        let reference = |uri: &str, module: &str, line: u32| SymbolReference {
+            name: "view".to_string(),
+            module: module.to_string(),
+            location: Location::new(
+                Url::parse(uri).unwrap(),
+                Range::new(Position::new(line, 0), Position::new(line, 4)),
+            ),
+            kind: ReferenceKind::Expression,
+            container: Some("main".to_string()),
+        };
+
+        store
+            .replace_file_references(
+                "file:///Main.gren",
+                &[
+                    reference("file:///Main.gren", "Main", 7),
+                    reference("file:///Main.gren", "Main", 2),
+                    reference("file:///Main.gren", "Page", 9),
+                ],
+            )
+            .unwrap();
+        store
+            .replace_file_references(
+                "file:///App.gren",
+                &[reference("file:///App.gren", "Main", 5)],
+            )
+            .unwrap();
+        store
+            .replace_file_references(
+                "file:///Main.gren",
+                &[reference("file:///Main.gren", "Main", 3)],
+            )
+            .unwrap();
+
+        let found: Vec<(String, u32)> = store
+            .find_references("Main", "view")
+            .unwrap()
+            .into_iter()
+            .map(|reference| {
+                let location = reference.location;
+                (location.uri.to_string(), location.range.start.line)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("file:///App.gren".to_string(), 5),
+                ("file:///Main.gren".to_string(), 3)
+            ]
+        );
+        assert!(store.find_references("Page", "view").unwrap().is_empty());
+
+        let from: Vec<u32> = store
+            .find_references_from("file:///Main.gren", "main")
+            .unwrap()
+            .into_iter()
+            .map(|reference| reference.location.range.start.line)
+            .collect();
+        assert_eq!(from, vec![3]);
+    }
+
+    #[test]
+    fn test_backend_from_config() {
+        let backend: SymbolStoreBackend = serde_json::from_str("\"inMemory\"").unwrap();
+        assert_eq!(backend, SymbolStoreBackend::InMemory);
+        let backend: SymbolStoreBackend = serde_json::from_str("\"tantivy\"").unwrap();
+        assert_eq!(backend, SymbolStoreBackend::Tantivy);
+        assert_eq!(SymbolStoreBackend::default(), SymbolStoreBackend::Sqlite);
+    }
+}
@@ -0,0 +1,118 @@
+//! Syntax tree positions in LSP terms
+//!
+//! Tree-sitter counts columns in bytes and LSP in UTF-16 code units, so every conversion
+//! between a tree and an LSP position takes the source the tree was parsed from.
+
+use gren_analysis::syntax::{char_boundary, line_offset, utf16_column, utf16_offset};
+use gren_analysis::text::TextRange;
+use lsp_types::{Position, Range};
+use tree_sitter::{Node, Point};
+
+pub use gren_analysis::syntax::child_of_kind;
+
+/// The range a syntax node spans
+pub fn node_range(node: &Node, source: &str) -> Range {
+    Range::new(
+        Position::new(
+            node.start_position().row as u32,
+            utf16_column(source, node.start_byte()),
+        ),
+        Position::new(
+            node.end_position().row as u32,
+            utf16_column(source, node.end_byte()),
+        ),
+    )
+}
+
+/// The LSP position of a point in a tree parsed from `source`
+pub fn point_to_position(point: Point, source: &str) -> Position {
+    let column = match line_offset(source, point.row) {
+        Some(line_start) => utf16_column(source, line_start + point.column),
+        None => point.column as u32,
+    };
+    Position::new(point.row as u32, column)
+}
+
+/// The LSP range of a range gren-analysis found in `source`
+pub fn text_range(range: TextRange, source: &str) -> Range {
+    Range::new(
+        point_to_position(range.start.into(), source),
+        point_to_position(range.end.into(), source),
+    )
+}
+
+/// The tree point of an LSP position in `source`, its column clamped to the end of its line
+pub fn position_to_point(position: Position, source: &str) -> Point {
+    let line = position.line as usize;
+    match (
+        line_offset(source, line),
+        utf16_offset(source, line, position.character),
+    ) {
+        (Some(line_start), Some(offset)) => Point::new(line, offset - line_start),
+        _ => Point::new(line, position.character as usize),
+    }
+}
+
+/// The LSP position of byte `offset` into `source`. An offset inside a character counts as
+/// that character's start, and one past the end as the end.
+pub fn offset_to_position(source: &str, offset: usize) -> Position {
+    let offset = char_boundary(source, offset);
+    Position::new(
+        source[..offset].matches('\n').count() as u32,
+        utf16_column(source, offset),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_positions_count_utf16_code_units() {
+        let source = "module Main exposing (..)\n\ngreeting =\n    \"héllo 😀\" ++ name\n";
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        let name = source.rfind("name").unwrap();
+        let node = tree
+            .root_node()
+            .descendant_for_byte_range(name, name + 4)
+            .unwrap();
+
+        // é takes two bytes and one code unit, 😀 four bytes and two code units
+        let range = node_range(&node, source);
+        assert_eq!(range.start, Position::new(3, 18));
+        assert_eq!(range.end, Position::new(3, 22));
+        assert_eq!(
+            point_to_position(node.start_position(), source),
+            range.start
+        );
+        assert_eq!(
+            position_to_point(range.start, source),
+            node.start_position()
+        );
+    }
+
+    #[test]
+    fn test_offset_to_position() {
+        let source = "a\nbé😀c";
+        assert_eq!(offset_to_position(source, 0), Position::new(0, 0));
+        assert_eq!(offset_to_position(source, 2), Position::new(1, 0));
+        assert_eq!(offset_to_position(source, 5), Position::new(1, 2));
+        // Inside 😀, and past the end
+        assert_eq!(offset_to_position(source, 7), Position::new(1, 2));
+        assert_eq!(offset_to_position(source, 40), Position::new(1, 5));
+    }
+
+    #[test]
+    fn test_position_past_line_end_is_clamped() {
+        let source = "a\nbé\nc";
+        assert_eq!(
+            position_to_point(Position::new(1, 40), source),
+            Point::new(1, 3)
+        );
+        assert_eq!(
+            position_to_point(Position::new(7, 2), source),
+            Point::new(7, 2)
+        );
+    }
+}
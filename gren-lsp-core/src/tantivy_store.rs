@@ -0,0 +1,327 @@
+use crate::comments::DeclarationComments;
+use crate::scope_analysis::SymbolReference;
+use crate::symbol::Symbol;
+use crate::symbol_query::SymbolQuery;
+use crate::symbol_store::{words, InMemorySymbolStore, IndexStats, SymbolStore};
+use crate::todos::TodoItem;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, STORED, STRING,
+};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+/// Memory the tantivy writer may buffer before flushing a segment; tantivy's minimum
+const WRITER_MEMORY_BYTES: usize = 15_000_000;
+
+/// Symbol store whose documentation search runs on a tantivy full-text index
+///
+/// Names, task markers and references are answered by an `InMemorySymbolStore`. Documented
+/// and commented symbols also go into a tantivy index kept in RAM, which ranks matches with
+/// BM25 over the English-stemmed words of each symbol's name, documentation and comments,
+/// like sqlite's FTS table. Writes become searchable on the first search after them.
+pub struct TantivySymbolStore {
+    symbols: InMemorySymbolStore,
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: DocFields,
+    /// Documented symbols by the id stored in their tantivy document
+    documented: RwLock<HashMap<u64, Symbol>>,
+    next_id: AtomicU64,
+    uncommitted: AtomicBool,
+}
+
+/// Fields of a symbol's tantivy document
+struct DocFields {
+    id: Field,
+    /// The file URI, untokenized so a file's documents can be deleted by term
+    file: Field,
+    name: Field,
+    documentation: Field,
+    comments: Field,
+}
+
+impl TantivySymbolStore {
+    pub fn new() -> Result<Self> {
+        let text = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer("en_stem")
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+        let mut schema = Schema::builder();
+        let fields = DocFields {
+            id: schema.add_u64_field("id", STORED),
+            file: schema.add_text_field("file", STRING),
+            name: schema.add_text_field("name", text.clone()),
+            documentation: schema.add_text_field("documentation", text.clone()),
+            comments: schema.add_text_field("comments", text),
+        };
+
+        let index = Index::create_in_ram(schema.build());
+        let writer = index.writer_with_num_threads(1, WRITER_MEMORY_BYTES)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+
+        Ok(Self {
+            symbols: InMemorySymbolStore::new(),
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+            documented: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            uncommitted: AtomicBool::new(false),
+        })
+    }
+
+    /// Commit the writes made since the last search and point the reader at them
+    fn commit(&self) -> Result<()> {
+        if self.uncommitted.swap(false, Ordering::SeqCst) {
+            self.writer.lock().unwrap().commit()?;
+            self.reader.reload()?;
+        }
+        Ok(())
+    }
+}
+
+impl SymbolStore for TantivySymbolStore {
+    fn add_symbol(&self, symbol: &Symbol) -> Result<()> {
+        self.symbols.add_symbol(symbol)?;
+
+        if let Some(documentation) = &symbol.documentation {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            self.writer.lock().unwrap().add_document(doc!(
+                self.fields.id => id,
+                self.fields.file => symbol.location.uri.as_str(),
+                self.fields.name => symbol.name.as_str(),
+                self.fields.documentation => documentation.as_str(),
+            ))?;
+            self.documented.write().unwrap().insert(id, symbol.clone());
+            self.uncommitted.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    fn find_symbols(&self, query: &str) -> Result<Vec<Symbol>> {
+        self.symbols.find_symbols(query)
+    }
+
+    fn find_symbols_limited(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        self.symbols.find_symbols_limited(query, offset, limit)
+    }
+
+    fn find_symbols_filtered(
+        &self,
+        query: &SymbolQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        self.symbols.find_symbols_filtered(query, offset, limit)
+    }
+
+    fn find_exact_symbols(&self, name: &str) -> Result<Vec<Symbol>> {
+        self.symbols.find_exact_symbols(name)
+    }
+
+    fn index_comments(&self, file_uri: &str, comments: &[DeclarationComments]) -> Result<()> {
+        let writer = self.writer.lock().unwrap();
+        let mut documented = self.documented.write().unwrap();
+        for declaration in comments {
+            let symbol = self
+                .symbols
+                .find_exact_symbols(&declaration.name)?
+                .into_iter()
+                .find(|symbol| {
+                    symbol.container_name.is_none() && symbol.location.uri.as_str() == file_uri
+                });
+            let symbol = match symbol {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+
+            // A second document for a documented symbol; searches return each symbol once
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            writer.add_document(doc!(
+                self.fields.id => id,
+                self.fields.file => file_uri,
+                self.fields.name => declaration.name.as_str(),
+                self.fields.comments => declaration.text.as_str(),
+            ))?;
+            documented.insert(id, symbol);
+        }
+        self.uncommitted.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn search_documentation(&self, query: &str, limit: usize) -> Result<Vec<Symbol>> {
+        // Lower-cased words keep the parser from reading `AND`, `OR` or punctuation as
+        // query syntax, and any of them may match, like the sqlite backend's `OR` query
+        let terms = words(query);
+        if terms.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        self.commit()?;
+
+        let parser = QueryParser::for_index(
+            &self.index,
+            vec![
+                self.fields.name,
+                self.fields.documentation,
+                self.fields.comments,
+            ],
+        );
+        let query = parser.parse_query(&terms.join(" "))?;
+        let searcher = self.reader.searcher();
+        let documented = self.documented.read().unwrap();
+
+        // A symbol has up to two documents, its documentation and its comments, so twice
+        // the limit holds `limit` distinct symbols
+        let top = TopDocs::with_limit(limit.saturating_mul(2));
+        let mut symbols: Vec<Symbol> = Vec::new();
+        for (_score, address) in searcher.search(&query, &top)? {
+            let document: TantivyDocument = searcher.doc(address)?;
+            let symbol = document
+                .get_first(self.fields.id)
+                .and_then(|id| id.as_u64())
+                .and_then(|id| documented.get(&id));
+            if let Some(symbol) = symbol {
+                let seen = symbols
+                    .iter()
+                    .any(|found| found.name == symbol.name && found.location == symbol.location);
+                if !seen {
+                    symbols.push(symbol.clone());
+                }
+            }
+        }
+        symbols.truncate(limit);
+        Ok(symbols)
+    }
+
+    fn deprecated_symbols(&self) -> Result<Vec<Symbol>> {
+        self.symbols.deprecated_symbols()
+    }
+
+    fn symbols_using_type(&self, type_name: &str) -> Result<Vec<Symbol>> {
+        self.symbols.symbols_using_type(type_name)
+    }
+
+    fn remove_file_symbols(&self, file_uri: &str) -> Result<()> {
+        self.symbols.remove_file_symbols(file_uri)?;
+
+        self.writer
+            .lock()
+            .unwrap()
+            .delete_term(Term::from_field_text(self.fields.file, file_uri));
+        self.documented
+            .write()
+            .unwrap()
+            .retain(|_, symbol| symbol.location.uri.as_str() != file_uri);
+        self.uncommitted.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn replace_file_todos(&self, file_uri: &str, todos: &[TodoItem]) -> Result<()> {
+        self.symbols.replace_file_todos(file_uri, todos)
+    }
+
+    fn all_todos(&self) -> Result<Vec<TodoItem>> {
+        self.symbols.all_todos()
+    }
+
+    fn replace_file_references(
+        &self,
+        file_uri: &str,
+        references: &[SymbolReference],
+    ) -> Result<()> {
+        self.symbols.replace_file_references(file_uri, references)
+    }
+
+    fn find_references(&self, module: &str, name: &str) -> Result<Vec<SymbolReference>> {
+        self.symbols.find_references(module, name)
+    }
+
+    fn find_references_from(
+        &self,
+        file_uri: &str,
+        container: &str,
+    ) -> Result<Vec<SymbolReference>> {
+        self.symbols.find_references_from(file_uri, container)
+    }
+
+    fn stats(&self) -> Result<IndexStats> {
+        self.symbols.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::*;
+
+    fn documented(name: &str, uri: &str, documentation: &str) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            location: Location::new(
+                Url::parse(uri).unwrap(),
+                Range::new(Position::new(0, 0), Position::new(0, name.len() as u32)),
+            ),
+            container_name: None,
+            type_signature: None,
+            documentation: Some(documentation.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_tantivy_store_searches_documentation() {
+        let store = TantivySymbolStore::new().unwrap();
+        store
+            .add_symbol(&documented(
+                "fromIsoString",
+                "file:///Date.gren",
+                "Parses a date written in ISO 8601",
+            ))
+            .unwrap();
+        store
+            .add_symbol(&documented("render", "file:///View.gren", "Draw the page"))
+            .unwrap();
+
+        let found = store
+            .search_documentation("find the function that parses dates", 10)
+            .unwrap();
+        assert_eq!(found[0].name, "fromIsoString");
+        assert!(store.search_documentation("  ?! ", 10).unwrap().is_empty());
+        assert_eq!(store.find_exact_symbols("render").unwrap().len(), 1);
+
+        store
+            .index_comments(
+                "file:///View.gren",
+                &[DeclarationComments {
+                    name: "render".to_string(),
+                    text: "Lays out the calendar grid".to_string(),
+                }],
+            )
+            .unwrap();
+        let found = store.search_documentation("calendar page", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "render");
+
+        store.remove_file_symbols("file:///Date.gren").unwrap();
+        assert!(store
+            .search_documentation("parse date", 10)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by the tracing spans around parsing, indexing and compiling.
+//! With the server's `otel` feature these spans are exported to an OTLP collector.
+
+use lsp_types::Url;
+
+/// Stable identifier for a document in span attributes, so traces can be correlated per
+/// file without sending file paths to the collector. The URI's 64-bit FNV-1a hash, which
+/// unlike the standard library's hasher stays the same across runs and Rust versions.
+pub fn uri_hash(uri: &Url) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let hash = uri.as_str().bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_hash_is_stable_and_opaque() {
+        let uri = Url::parse("file:///home/user/project/src/Main.gren").unwrap();
+        let other = Url::parse("file:///home/user/project/src/Other.gren").unwrap();
+
+        assert_eq!(uri_hash(&uri), "f5544687d4a1ac02");
+        assert_ne!(uri_hash(&uri), uri_hash(&other));
+        assert!(!uri_hash(&uri).contains("Main"));
+    }
+}
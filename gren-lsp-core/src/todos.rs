@@ -0,0 +1,172 @@
+use lsp_types::*;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+/// Task marker found at the start of a comment line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TodoMarker {
+    Todo,
+    Fixme,
+    Xxx,
+}
+
+impl TodoMarker {
+    const ALL: [TodoMarker; 3] = [TodoMarker::Todo, TodoMarker::Fixme, TodoMarker::Xxx];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TodoMarker::Todo => "TODO",
+            TodoMarker::Fixme => "FIXME",
+            TodoMarker::Xxx => "XXX",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|marker| marker.as_str() == value)
+    }
+}
+
+/// A marked comment line, e.g. `-- TODO: handle the empty case`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub marker: TodoMarker,
+    /// What follows the marker, e.g. `handle the empty case`
+    pub text: String,
+    /// From the marker to the end of the comment line
+    pub location: Location,
+}
+
+/// Find task markers in a file's comments. A marker only counts at the start of a comment
+/// line, so prose that merely mentions "TODO" isn't picked up.
+pub fn extract_todos(tree: &Tree, source: &str, uri: &Url) -> Vec<TodoItem> {
+    let mut todos = Vec::new();
+    collect_todos(tree.root_node(), source, uri, &mut todos);
+    todos
+}
+
+fn collect_todos(node: Node, source: &str, uri: &Url, todos: &mut Vec<TodoItem>) {
+    if matches!(node.kind(), "line_comment" | "block_comment") {
+        if let Ok(text) = node.utf8_text(source.as_bytes()) {
+            todos_in_comment(text, node.start_position(), uri, todos);
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_todos(child, source, uri, todos);
+    }
+}
+
+fn todos_in_comment(
+    comment: &str,
+    start: tree_sitter::Point,
+    uri: &Url,
+    todos: &mut Vec<TodoItem>,
+) {
+    for (index, line) in comment.lines().enumerate() {
+        // Skip the comment delimiters and any leading whitespace
+        let after_opener = if index == 0 {
+            line.strip_prefix("{-|")
+                .or_else(|| line.strip_prefix("{-"))
+                .or_else(|| line.strip_prefix("--"))
+                .unwrap_or(line)
+        } else {
+            line
+        };
+        let trimmed = after_opener.trim_start();
+        let offset = line.len() - trimmed.len();
+        let content = trimmed.strip_suffix("-}").unwrap_or(trimmed).trim_end();
+
+        let word_end = content
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(content.len());
+        let marker = match TodoMarker::parse(&content[..word_end]) {
+            Some(marker) => marker,
+            None => continue,
+        };
+
+        // `TODO(name): ...` and `TODO: ...` are both common
+        let rest = &content[word_end..];
+        let rest = match rest.strip_prefix('(') {
+            Some(after_paren) => after_paren
+                .find(')')
+                .map_or(rest, |close| &after_paren[close + 1..]),
+            None => rest,
+        };
+        let text = rest.trim_start_matches(':').trim();
+
+        let row = (start.row + index) as u32;
+        let column = if index == 0 { start.column } else { 0 } + offset;
+        todos.push(TodoItem {
+            marker,
+            text: text.to_string(),
+            location: Location::new(
+                uri.clone(),
+                Range::new(
+                    Position::new(row, column as u32),
+                    Position::new(row, (column + content.len()) as u32),
+                ),
+            ),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_extract_todos() {
+        let source = r#"module Main exposing (..)
+
+-- TODO: handle the empty case
+main = 42
+
+{-| Renders the page.
+
+FIXME(dana) escaping is wrong here
+-}
+view model =
+    -- this is not a TODO marker
+    model -- XXX
+"#;
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        let todos = extract_todos(&tree, source, &uri);
+        let summary: Vec<_> = todos
+            .iter()
+            .map(|todo| (todo.marker, todo.text.as_str(), todo.location.range.start))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    TodoMarker::Todo,
+                    "handle the empty case",
+                    Position::new(2, 3)
+                ),
+                (
+                    TodoMarker::Fixme,
+                    "escaping is wrong here",
+                    Position::new(7, 0)
+                ),
+                (TodoMarker::Xxx, "", Position::new(11, 13)),
+            ]
+        );
+        assert_eq!(todos[0].location.range.end, Position::new(2, 30));
+    }
+
+    #[test]
+    fn test_marker_needs_whole_word() {
+        assert_eq!(TodoMarker::parse("TODO"), Some(TodoMarker::Todo));
+        assert_eq!(TodoMarker::parse("TODOS"), None);
+        assert_eq!(TodoMarker::parse("todo"), None);
+    }
+}
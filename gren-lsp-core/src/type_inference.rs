@@ -0,0 +1,1924 @@
+//! Type inference for values written without a type annotation
+//!
+//! Hover and completion show the types of `let` bindings, parameters, pattern variables,
+//! lambda arguments and unannotated top-level values by inferring them from the syntax
+//! tree, Hindley-Milner style. Annotations, the module's own custom types and aliases, and
+//! the indexed signatures of imported values seed the inference.
+//!
+//! Nothing is guessed: a name with no known type or an expression form the inference
+//! doesn't cover makes every type depending on it unknown, and a definition that doesn't
+//! type check gets no types at all. The compiler reports those.
+
+use crate::syntax::{child_of_kind, node_range};
+use gren_analysis::references::module_references;
+use lsp_types::Range;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tree_sitter::{Node, Tree};
+
+/// Alias expansions allowed inside each other before giving up on a cyclic alias
+const ALIAS_DEPTH: usize = 16;
+
+/// Constructors from the modules every module imports, for when the packages aren't indexed
+const DEFAULT_CONSTRUCTORS: &[(&str, &str)] = &[
+    ("True", "Bool"),
+    ("False", "Bool"),
+    ("Just", "a -> Maybe a"),
+    ("Nothing", "Maybe a"),
+    ("Ok", "value -> Result error value"),
+    ("Err", "error -> Result error value"),
+];
+
+/// Where a module looks up the names it imports
+pub trait TypeEnvironment {
+    /// The type signature of the value or constructor `name` defined in `module`
+    fn signature(&self, module: &str, name: &str) -> Option<String>;
+
+    /// The parameters and definition of the type alias `name`, e.g. `["a"]` and
+    /// `{ value : a }`
+    fn alias(&self, name: &str) -> Option<(Vec<String>, String)>;
+}
+
+/// A name with an inferred type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferredBinding {
+    pub name: String,
+    /// The name where it's bound
+    pub range: Range,
+    pub type_text: String,
+    /// An unannotated top-level value rather than a local
+    pub top_level: bool,
+}
+
+/// The types inferred for one module
+#[derive(Debug, Clone, Default)]
+pub struct ModuleTypes {
+    bindings: Vec<InferredBinding>,
+}
+
+impl ModuleTypes {
+    /// The type of the name bound at `range`
+    pub fn binding(&self, range: Range) -> Option<&str> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.range == range)
+            .map(|binding| binding.type_text.as_str())
+    }
+
+    /// The type of the unannotated top-level value `name`
+    pub fn top_level(&self, name: &str) -> Option<&str> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.top_level && binding.name == name)
+            .map(|binding| binding.type_text.as_str())
+    }
+
+    pub fn bindings(&self) -> &[InferredBinding] {
+        &self.bindings
+    }
+}
+
+/// Infer the types of the names a module binds without annotating them
+pub fn infer_types(tree: &Tree, source: &str, environment: &dyn TypeEnvironment) -> ModuleTypes {
+    let mut inference = Inference::new(source, environment);
+    for reference in module_references(tree, source) {
+        let start = reference.range.start;
+        inference
+            .modules
+            .entry((start.line, start.column))
+            .or_default()
+            .push(reference.module);
+    }
+    inference.infer_module(tree.root_node());
+    ModuleTypes {
+        bindings: inference.results,
+    }
+}
+
+/// A type as the inference works with it
+#[derive(Debug, Clone, PartialEq)]
+enum Type {
+    Variable(u32),
+    /// A custom type, alias-free, with its arguments
+    Named(String, Vec<Type>),
+    Function(Box<Type>, Box<Type>),
+    /// Fields and, for an extensible record, the variable standing for the other fields
+    Record(BTreeMap<String, Type>, Option<u32>),
+    /// A type alias with its arguments, kept for showing the type the way it was written,
+    /// and what it stands for
+    Alias(String, Vec<Type>, Box<Type>),
+}
+
+impl Type {
+    fn named(name: &str) -> Type {
+        Type::Named(name.to_string(), Vec::new())
+    }
+
+    fn function(argument: Type, result: Type) -> Type {
+        Type::Function(Box::new(argument), Box::new(result))
+    }
+
+    fn array(element: Type) -> Type {
+        Type::Named("Array".to_string(), vec![element])
+    }
+}
+
+/// The type classes Gren's special type variables stand for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Constraint {
+    Number,
+    Comparable,
+    Appendable,
+    CompAppend,
+}
+
+impl Constraint {
+    /// The constraint a type variable's name carries, e.g. `number` or `comparable2`
+    fn of_name(name: &str) -> Option<Constraint> {
+        if name.starts_with("number") {
+            Some(Constraint::Number)
+        } else if name.starts_with("comparable") {
+            Some(Constraint::Comparable)
+        } else if name.starts_with("appendable") {
+            Some(Constraint::Appendable)
+        } else if name.starts_with("compappend") {
+            Some(Constraint::CompAppend)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Constraint::Number => "number",
+            Constraint::Comparable => "comparable",
+            Constraint::Appendable => "appendable",
+            Constraint::CompAppend => "compappend",
+        }
+    }
+
+    /// The constraint of a variable that has to meet both, if any type can
+    fn merge(self, other: Constraint) -> Option<Constraint> {
+        use Constraint::*;
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (Number, Comparable) | (Comparable, Number) => Some(Number),
+            (Comparable, Appendable)
+            | (Appendable, Comparable)
+            | (CompAppend, Comparable)
+            | (Comparable, CompAppend)
+            | (CompAppend, Appendable)
+            | (Appendable, CompAppend) => Some(CompAppend),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Variable {
+    bound: Option<Type>,
+    constraint: Option<Constraint>,
+    /// Stands for something the inference couldn't type
+    unknown: bool,
+}
+
+/// A type with the variables it's polymorphic in
+#[derive(Debug, Clone)]
+struct Scheme {
+    variables: Vec<u32>,
+    ty: Type,
+}
+
+impl Scheme {
+    fn monomorphic(ty: Type) -> Scheme {
+        Scheme {
+            variables: Vec::new(),
+            ty,
+        }
+    }
+}
+
+/// Type variables and what unification bound them to
+#[derive(Default)]
+struct Substitution {
+    variables: Vec<Variable>,
+}
+
+impl Substitution {
+    fn variable(&mut self, constraint: Option<Constraint>, unknown: bool) -> u32 {
+        self.variables.push(Variable {
+            bound: None,
+            constraint,
+            unknown,
+        });
+        self.variables.len() as u32 - 1
+    }
+
+    fn fresh(&mut self) -> Type {
+        Type::Variable(self.variable(None, false))
+    }
+
+    fn constrained(&mut self, constraint: Constraint) -> Type {
+        Type::Variable(self.variable(Some(constraint), false))
+    }
+
+    fn unknown(&mut self) -> Type {
+        Type::Variable(self.variable(None, true))
+    }
+
+    /// Follow bound variables until the outermost shape of `ty` is known, merging the
+    /// fields an extensible record's variable was bound to
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Variable(id) => match &self.variables[*id as usize].bound {
+                Some(bound) => self.resolve(bound),
+                None => ty.clone(),
+            },
+            Type::Alias(_, _, definition) => self.resolve(definition),
+            Type::Record(fields, Some(rest)) => match &self.variables[*rest as usize].bound {
+                Some(bound) => match self.resolve(bound) {
+                    Type::Record(more, rest) => {
+                        let mut fields = fields.clone();
+                        fields.extend(more);
+                        Type::Record(fields, rest)
+                    }
+                    Type::Variable(rest) => Type::Record(fields.clone(), Some(rest)),
+                    _ => ty.clone(),
+                },
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    /// Follow bound variables, keeping aliases
+    fn follow(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Variable(id) => match &self.variables[*id as usize].bound {
+                Some(bound) => self.follow(bound),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    /// `ty` with every bound variable replaced
+    fn apply(&self, ty: &Type) -> Type {
+        if let Type::Alias(name, arguments, definition) = self.follow(ty) {
+            return Type::Alias(
+                name,
+                arguments
+                    .iter()
+                    .map(|argument| self.apply(argument))
+                    .collect(),
+                Box::new(self.apply(&definition)),
+            );
+        }
+        match self.resolve(ty) {
+            Type::Named(name, arguments) => Type::Named(
+                name,
+                arguments
+                    .iter()
+                    .map(|argument| self.apply(argument))
+                    .collect(),
+            ),
+            Type::Function(argument, result) => {
+                Type::function(self.apply(&argument), self.apply(&result))
+            }
+            Type::Record(fields, rest) => Type::Record(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), self.apply(field)))
+                    .collect(),
+                rest,
+            ),
+            variable => variable,
+        }
+    }
+
+    /// Unbound variables of `ty`, in the order they appear
+    fn free_variables(&self, ty: &Type) -> Vec<u32> {
+        fn collect(ty: &Type, variables: &mut Vec<u32>) {
+            match ty {
+                Type::Variable(id) => {
+                    if !variables.contains(id) {
+                        variables.push(*id);
+                    }
+                }
+                Type::Named(_, arguments) => {
+                    for argument in arguments {
+                        collect(argument, variables);
+                    }
+                }
+                Type::Function(argument, result) => {
+                    collect(argument, variables);
+                    collect(result, variables);
+                }
+                Type::Record(fields, rest) => {
+                    if let Some(rest) = rest {
+                        collect(&Type::Variable(*rest), variables);
+                    }
+                    for field in fields.values() {
+                        collect(field, variables);
+                    }
+                }
+                Type::Alias(_, arguments, definition) => {
+                    for argument in arguments {
+                        collect(argument, variables);
+                    }
+                    collect(definition, variables);
+                }
+            }
+        }
+
+        let mut variables = Vec::new();
+        collect(&self.apply(ty), &mut variables);
+        variables
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        // A variable takes the other type as written, alias and all
+        match (self.follow(a), self.follow(b)) {
+            (Type::Variable(x), Type::Variable(y)) if x == y => return true,
+            (Type::Variable(x), other) | (other, Type::Variable(x)) => return self.bind(x, other),
+            _ => {}
+        }
+        match (self.resolve(a), self.resolve(b)) {
+            (Type::Variable(x), Type::Variable(y)) if x == y => true,
+            (Type::Variable(x), other) | (other, Type::Variable(x)) => self.bind(x, other),
+            (Type::Named(a, a_arguments), Type::Named(b, b_arguments)) => {
+                same_name(&a, &b)
+                    && a_arguments.len() == b_arguments.len()
+                    && a_arguments
+                        .iter()
+                        .zip(&b_arguments)
+                        .all(|(a, b)| self.unify(a, b))
+            }
+            (Type::Function(a_argument, a_result), Type::Function(b_argument, b_result)) => {
+                self.unify(&a_argument, &b_argument) && self.unify(&a_result, &b_result)
+            }
+            (Type::Record(a_fields, a_rest), Type::Record(b_fields, b_rest)) => {
+                self.unify_records(a_fields, a_rest, b_fields, b_rest)
+            }
+            _ => false,
+        }
+    }
+
+    fn unify_records(
+        &mut self,
+        a_fields: BTreeMap<String, Type>,
+        a_rest: Option<u32>,
+        b_fields: BTreeMap<String, Type>,
+        b_rest: Option<u32>,
+    ) -> bool {
+        for (name, field) in &a_fields {
+            if let Some(other) = b_fields.get(name) {
+                if !self.unify(field, other) {
+                    return false;
+                }
+            }
+        }
+        let only_a: BTreeMap<String, Type> = a_fields
+            .iter()
+            .filter(|(name, _)| !b_fields.contains_key(*name))
+            .map(|(name, field)| (name.clone(), field.clone()))
+            .collect();
+        let only_b: BTreeMap<String, Type> = b_fields
+            .into_iter()
+            .filter(|(name, _)| !a_fields.contains_key(name))
+            .collect();
+
+        match (a_rest, b_rest) {
+            (None, None) => only_a.is_empty() && only_b.is_empty(),
+            (Some(a_rest), None) => {
+                only_a.is_empty() && self.bind(a_rest, Type::Record(only_b, None))
+            }
+            (None, Some(b_rest)) => {
+                only_b.is_empty() && self.bind(b_rest, Type::Record(only_a, None))
+            }
+            (Some(a_rest), Some(b_rest)) if a_rest == b_rest => {
+                only_a.is_empty() && only_b.is_empty()
+            }
+            (Some(a_rest), Some(b_rest)) => {
+                let rest = self.variable(None, false);
+                self.bind(a_rest, Type::Record(only_b, Some(rest)))
+                    && self.bind(b_rest, Type::Record(only_a, Some(rest)))
+            }
+        }
+    }
+
+    fn bind(&mut self, id: u32, ty: Type) -> bool {
+        if self.free_variables(&ty).contains(&id) {
+            return false;
+        }
+        let variable = self.variables[id as usize].clone();
+        if let Some(constraint) = variable.constraint {
+            if !self.satisfies(&ty, constraint) {
+                return false;
+            }
+        }
+        // Whatever an unknown type turns out to be is unknown too
+        if variable.unknown {
+            for free in self.free_variables(&ty) {
+                self.variables[free as usize].unknown = true;
+            }
+        }
+        self.variables[id as usize].bound = Some(ty);
+        true
+    }
+
+    /// Whether `ty` can meet `constraint`, narrowing its variables so that it does
+    fn satisfies(&mut self, ty: &Type, constraint: Constraint) -> bool {
+        match self.resolve(ty) {
+            Type::Variable(id) => {
+                let merged = match self.variables[id as usize].constraint {
+                    Some(existing) => existing.merge(constraint),
+                    None => Some(constraint),
+                };
+                self.variables[id as usize].constraint = merged;
+                merged.is_some()
+            }
+            Type::Named(name, arguments) => {
+                let name = last_segment(&name);
+                match (constraint, name, arguments.as_slice()) {
+                    (Constraint::Number, "Int" | "Float", []) => true,
+                    (Constraint::Comparable, "Int" | "Float" | "String" | "Char", []) => true,
+                    (Constraint::Appendable | Constraint::CompAppend, "String", []) => true,
+                    (Constraint::Appendable, "Array", [_]) => true,
+                    (Constraint::Comparable | Constraint::CompAppend, "Array", [element]) => {
+                        self.satisfies(element, Constraint::Comparable)
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// `scheme` with fresh variables for the ones it's polymorphic in
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        if scheme.variables.is_empty() {
+            return scheme.ty.clone();
+        }
+        let fresh: HashMap<u32, u32> = scheme
+            .variables
+            .iter()
+            .map(|id| {
+                let old = self.variables[*id as usize].clone();
+                (*id, self.variable(old.constraint, old.unknown))
+            })
+            .collect();
+        rename_variables(&scheme.ty, &fresh)
+    }
+
+    /// `ty` as a scheme polymorphic in the variables the environment doesn't use
+    fn generalize(&self, ty: &Type, environment: &HashSet<u32>) -> Scheme {
+        Scheme {
+            variables: self
+                .free_variables(ty)
+                .into_iter()
+                .filter(|id| !environment.contains(id))
+                .collect(),
+            ty: self.apply(ty),
+        }
+    }
+
+    /// `ty` as Gren writes it, or `None` when part of it is unknown
+    fn render(&self, ty: &Type) -> Option<String> {
+        let ty = self.apply(ty);
+        let free = self.free_variables(&ty);
+        if free.iter().any(|id| self.variables[*id as usize].unknown) {
+            return None;
+        }
+
+        let mut names = HashMap::new();
+        let mut letters = 0;
+        let mut constrained: HashMap<Constraint, usize> = HashMap::new();
+        for id in free {
+            let name = match self.variables[id as usize].constraint {
+                Some(constraint) => {
+                    let count = constrained.entry(constraint).or_default();
+                    *count += 1;
+                    match *count {
+                        1 => constraint.name().to_string(),
+                        n => format!("{}{}", constraint.name(), n - 1),
+                    }
+                }
+                None => {
+                    letters += 1;
+                    letter_name(letters - 1)
+                }
+            };
+            names.insert(id, name);
+        }
+        Some(show(&ty, &names, Placement::Top))
+    }
+}
+
+/// `a` to `z`, then `a1` and on
+fn letter_name(index: usize) -> String {
+    let letter = (b'a' + (index % 26) as u8) as char;
+    match index / 26 {
+        0 => letter.to_string(),
+        round => format!("{}{}", letter, round),
+    }
+}
+
+/// Where a type is printed, which decides whether it needs parentheses
+#[derive(Clone, Copy, PartialEq)]
+enum Placement {
+    Top,
+    /// Left of an arrow
+    Argument,
+    /// An argument of a custom type
+    Parameter,
+}
+
+fn show(ty: &Type, names: &HashMap<u32, String>, placement: Placement) -> String {
+    let variable = |id: &u32| names.get(id).cloned().unwrap_or_else(|| "a".to_string());
+    match ty {
+        Type::Variable(id) => variable(id),
+        Type::Named(name, arguments) | Type::Alias(name, arguments, _) if arguments.is_empty() => {
+            name.clone()
+        }
+        Type::Named(name, arguments) | Type::Alias(name, arguments, _) => {
+            let mut parts = vec![name.clone()];
+            parts.extend(
+                arguments
+                    .iter()
+                    .map(|argument| show(argument, names, Placement::Parameter)),
+            );
+            let text = parts.join(" ");
+            if placement == Placement::Parameter {
+                format!("({})", text)
+            } else {
+                text
+            }
+        }
+        Type::Function(argument, result) => {
+            let text = format!(
+                "{} -> {}",
+                show(argument, names, Placement::Argument),
+                show(result, names, Placement::Top)
+            );
+            if placement == Placement::Top {
+                text
+            } else {
+                format!("({})", text)
+            }
+        }
+        Type::Record(fields, rest) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(name, field)| format!("{} : {}", name, show(field, names, Placement::Top)))
+                .collect();
+            match (rest, fields.is_empty()) {
+                (None, true) => "{}".to_string(),
+                (Some(rest), true) => variable(rest),
+                (None, false) => format!("{{ {} }}", fields.join(", ")),
+                (Some(rest), false) => format!("{{ {} | {} }}", variable(rest), fields.join(", ")),
+            }
+        }
+    }
+}
+
+fn rename_variables(ty: &Type, renamed: &HashMap<u32, u32>) -> Type {
+    match ty {
+        Type::Variable(id) => Type::Variable(*renamed.get(id).unwrap_or(id)),
+        Type::Named(name, arguments) => Type::Named(
+            name.clone(),
+            arguments
+                .iter()
+                .map(|argument| rename_variables(argument, renamed))
+                .collect(),
+        ),
+        Type::Function(argument, result) => Type::function(
+            rename_variables(argument, renamed),
+            rename_variables(result, renamed),
+        ),
+        Type::Record(fields, rest) => Type::Record(
+            fields
+                .iter()
+                .map(|(name, field)| (name.clone(), rename_variables(field, renamed)))
+                .collect(),
+            rest.map(|rest| *renamed.get(&rest).unwrap_or(&rest)),
+        ),
+        Type::Alias(name, arguments, definition) => Type::Alias(
+            name.clone(),
+            arguments
+                .iter()
+                .map(|argument| rename_variables(argument, renamed))
+                .collect(),
+            Box::new(rename_variables(definition, renamed)),
+        ),
+    }
+}
+
+/// Whether two type names are the same type, one maybe qualified
+fn same_name(a: &str, b: &str) -> bool {
+    last_segment(a) == last_segment(b)
+}
+
+fn last_segment(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// A type signature as written
+#[derive(Debug, Clone, PartialEq)]
+enum TypeExpression {
+    Variable(String),
+    Named(String, Vec<TypeExpression>),
+    Function(Box<TypeExpression>, Box<TypeExpression>),
+    Record(Vec<(String, TypeExpression)>, Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Upper(String),
+    Lower(String),
+    Arrow,
+    Open,
+    Close,
+    OpenBrace,
+    CloseBrace,
+    Colon,
+    Comma,
+    Bar,
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '{' => Token::OpenBrace,
+            '}' => Token::CloseBrace,
+            ':' => Token::Colon,
+            ',' => Token::Comma,
+            '|' => Token::Bar,
+            '-' if chars.next_if(|(_, next)| *next == '>').is_some() => Token::Arrow,
+            c if c.is_alphabetic() => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, next)) =
+                    chars.next_if(|(_, next)| next.is_alphanumeric() || matches!(next, '_' | '.'))
+                {
+                    end = index + next.len_utf8();
+                }
+                let word = text[start..end].to_string();
+                if c.is_uppercase() {
+                    Token::Upper(word)
+                } else {
+                    Token::Lower(word)
+                }
+            }
+            _ => return None,
+        };
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+/// Parse a type signature, e.g. `(a -> b) -> Array a -> Array b`
+fn parse_signature(text: &str) -> Option<TypeExpression> {
+    let tokens = tokenize(text)?;
+    let mut parser = SignatureParser {
+        tokens: &tokens,
+        position: 0,
+    };
+    let parsed = parser.function()?;
+    (parser.position == tokens.len()).then_some(parsed)
+}
+
+struct SignatureParser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl SignatureParser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Option<()> {
+        (self.next()? == expected).then_some(())
+    }
+
+    fn function(&mut self) -> Option<TypeExpression> {
+        let argument = self.application()?;
+        if self.peek() == Some(&Token::Arrow) {
+            self.position += 1;
+            let result = self.function()?;
+            return Some(TypeExpression::Function(
+                Box::new(argument),
+                Box::new(result),
+            ));
+        }
+        Some(argument)
+    }
+
+    fn application(&mut self) -> Option<TypeExpression> {
+        match self.peek()? {
+            Token::Upper(name) => {
+                let name = name.clone();
+                self.position += 1;
+                let mut arguments = Vec::new();
+                while matches!(
+                    self.peek(),
+                    Some(Token::Upper(_) | Token::Lower(_) | Token::Open | Token::OpenBrace)
+                ) {
+                    arguments.push(self.atom()?);
+                }
+                Some(TypeExpression::Named(name, arguments))
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Option<TypeExpression> {
+        match self.next()? {
+            Token::Upper(name) => Some(TypeExpression::Named(name, Vec::new())),
+            Token::Lower(name) => Some(TypeExpression::Variable(name)),
+            Token::Open => {
+                if self.peek() == Some(&Token::Close) {
+                    self.position += 1;
+                    return Some(TypeExpression::Record(Vec::new(), None));
+                }
+                let inner = self.function()?;
+                self.expect(Token::Close)?;
+                Some(inner)
+            }
+            Token::OpenBrace => self.record(),
+            _ => None,
+        }
+    }
+
+    fn record(&mut self) -> Option<TypeExpression> {
+        if self.peek() == Some(&Token::CloseBrace) {
+            self.position += 1;
+            return Some(TypeExpression::Record(Vec::new(), None));
+        }
+        let mut rest = None;
+        if let (Some(Token::Lower(name)), Some(Token::Bar)) =
+            (self.peek(), self.tokens.get(self.position + 1))
+        {
+            rest = Some(name.clone());
+            self.position += 2;
+        }
+        let mut fields = Vec::new();
+        loop {
+            let name = match self.next()? {
+                Token::Lower(name) => name,
+                _ => return None,
+            };
+            self.expect(Token::Colon)?;
+            fields.push((name, self.function()?));
+            match self.next()? {
+                Token::Comma => continue,
+                Token::CloseBrace => break,
+                _ => return None,
+            }
+        }
+        Some(TypeExpression::Record(fields, rest))
+    }
+}
+
+/// The operators of `Basics`: type, precedence and whether they group to the right
+fn operator(name: &str) -> Option<(&'static str, u8, bool)> {
+    Some(match name {
+        "|>" => ("a -> (a -> b) -> b", 0, false),
+        "<|" => ("(a -> b) -> a -> b", 0, true),
+        "||" => ("Bool -> Bool -> Bool", 2, true),
+        "&&" => ("Bool -> Bool -> Bool", 3, true),
+        "==" | "/=" => ("a -> a -> Bool", 4, false),
+        "<" | ">" | "<=" | ">=" => ("comparable -> comparable -> Bool", 4, false),
+        "++" => ("appendable -> appendable -> appendable", 5, true),
+        "+" | "-" => ("number -> number -> number", 6, false),
+        "*" => ("number -> number -> number", 7, false),
+        "/" => ("Float -> Float -> Float", 7, false),
+        "//" => ("Int -> Int -> Int", 7, false),
+        "^" => ("number -> number -> number", 8, true),
+        "<<" => ("(b -> c) -> (a -> b) -> a -> c", 9, true),
+        ">>" => ("(a -> b) -> (b -> c) -> a -> c", 9, false),
+        _ => return None,
+    })
+}
+
+struct Inference<'a> {
+    source: &'a str,
+    environment: &'a dyn TypeEnvironment,
+    substitution: Substitution,
+    /// The module's type aliases: parameters and definition
+    aliases: HashMap<String, (Vec<String>, String)>,
+    constructors: HashMap<String, Scheme>,
+    top_level: HashMap<String, Scheme>,
+    scopes: Vec<HashMap<String, Scheme>>,
+    /// Modules each top-level name occurrence may refer to, by where it starts
+    modules: HashMap<(u32, u32), Vec<String>>,
+    /// Names bound in the definition being inferred
+    bound: Vec<(String, Range, Type)>,
+    /// Whether the definition being inferred failed to type check
+    failed: bool,
+    alias_depth: usize,
+    results: Vec<InferredBinding>,
+}
+
+impl<'a> Inference<'a> {
+    fn new(source: &'a str, environment: &'a dyn TypeEnvironment) -> Self {
+        Self {
+            source,
+            environment,
+            substitution: Substitution::default(),
+            aliases: HashMap::new(),
+            constructors: HashMap::new(),
+            top_level: HashMap::new(),
+            scopes: Vec::new(),
+            modules: HashMap::new(),
+            bound: Vec::new(),
+            failed: false,
+            alias_depth: 0,
+            results: Vec::new(),
+        }
+    }
+
+    fn infer_module(&mut self, root: Node) {
+        let declarations = named_children(root);
+
+        for declaration in &declarations {
+            match declaration.kind() {
+                "type_alias_declaration" => self.add_alias(*declaration),
+                "type_declaration" => self.add_constructors(*declaration),
+                _ => {}
+            }
+        }
+
+        let mut annotated = HashSet::new();
+        for declaration in &declarations {
+            match declaration.kind() {
+                "type_annotation" => {
+                    let name = child_of_kind(declaration, "lower_case_identifier")
+                        .and_then(|name| self.text(name));
+                    let scheme = self.annotation_scheme(*declaration);
+                    if let (Some(name), Some(scheme)) = (name, scheme) {
+                        annotated.insert(name.to_string());
+                        self.top_level.insert(name.to_string(), scheme);
+                    }
+                }
+                "value_declaration" => {
+                    if let Some(name) = self.declared_name(*declaration) {
+                        if !self.top_level.contains_key(name) {
+                            let ty = self.substitution.fresh();
+                            self.top_level
+                                .insert(name.to_string(), Scheme::monomorphic(ty));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for declaration in declarations {
+            if declaration.kind() == "value_declaration" {
+                self.infer_top_level(declaration, &annotated);
+            }
+        }
+    }
+
+    fn infer_top_level(&mut self, declaration: Node, annotated: &HashSet<String>) {
+        let left = match child_of_kind(&declaration, "function_declaration_left") {
+            Some(left) => left,
+            None => return,
+        };
+        let name_node = match child_of_kind(&left, "lower_case_identifier") {
+            Some(name) => name,
+            None => return,
+        };
+        let name = self.text(name_node).unwrap_or_default().to_string();
+        let expected = match self.top_level.get(&name) {
+            Some(scheme) => scheme.clone(),
+            None => return,
+        };
+
+        self.bound.clear();
+        self.failed = false;
+        let ty = self.infer_definition(declaration, left);
+        let expected = self.substitution.instantiate(&expected);
+        self.expect(&ty, &expected);
+
+        if self.failed {
+            // Its uses elsewhere mustn't pick up a half-inferred type
+            for free in self.substitution.free_variables(&ty) {
+                self.substitution.variables[free as usize].unknown = true;
+            }
+            return;
+        }
+        for (local, range, ty) in std::mem::take(&mut self.bound) {
+            self.record(local, range, &ty, false);
+        }
+        if !annotated.contains(&name) {
+            let environment = self.environment_variables(Some(&name));
+            let scheme = self.substitution.generalize(&ty, &environment);
+            self.record(
+                name.clone(),
+                node_range(&name_node, self.source),
+                &scheme.ty,
+                true,
+            );
+            self.top_level.insert(name, scheme);
+        }
+    }
+
+    fn record(&mut self, name: String, range: Range, ty: &Type, top_level: bool) {
+        if let Some(type_text) = self.substitution.render(ty) {
+            self.results.push(InferredBinding {
+                name,
+                range,
+                type_text,
+                top_level,
+            });
+        }
+    }
+
+    /// The type of a function or value definition, its parameters bound while the body
+    /// is inferred
+    fn infer_definition(&mut self, declaration: Node, left: Node) -> Type {
+        self.scopes.push(HashMap::new());
+        let mut parameters = Vec::new();
+        for pattern in named_children(left).into_iter().skip(1) {
+            let ty = self.substitution.fresh();
+            self.bind_pattern(pattern, &ty);
+            parameters.push(ty);
+        }
+        let result = match definition_body(declaration) {
+            Some(body) => self.infer(body),
+            None => self.substitution.unknown(),
+        };
+        self.scopes.pop();
+        parameters
+            .into_iter()
+            .rev()
+            .fold(result, |result, parameter| {
+                Type::function(parameter, result)
+            })
+    }
+
+    fn infer(&mut self, node: Node) -> Type {
+        match node.kind() {
+            "value_expr" => match named_children(node).first() {
+                Some(name) => self.infer(*name),
+                None => self.substitution.unknown(),
+            },
+            "value_qid" => {
+                let count = node.named_child_count();
+                let name = match node.named_child(count.saturating_sub(1)) {
+                    Some(name) => name,
+                    None => return self.substitution.unknown(),
+                };
+                if count > 1 {
+                    self.imported(name)
+                } else {
+                    self.variable(name)
+                }
+            }
+            "record_base_identifier" => self.variable(node),
+            "upper_case_qid" => self.constructor(node),
+            "function_call_expr" => {
+                let children = named_children(node);
+                let mut function = match children.first() {
+                    Some(function) => self.infer(*function),
+                    None => return self.substitution.unknown(),
+                };
+                for argument in &children[1..] {
+                    let argument = self.infer(*argument);
+                    function = self.apply(&function, argument);
+                }
+                function
+            }
+            "bin_op_expr" => self.infer_operators(node),
+            "operator_as_function_expr" => match child_of_kind(&node, "operator_identifier") {
+                Some(name) => self.operator_type(name),
+                None => self.substitution.unknown(),
+            },
+            "negate_expr" => {
+                let ty = match named_children(node).last() {
+                    Some(inner) => self.infer(*inner),
+                    None => return self.substitution.unknown(),
+                };
+                let number = self.substitution.constrained(Constraint::Number);
+                self.expect(&ty, &number);
+                ty
+            }
+            "parenthesized_expr" => match named_children(node).first() {
+                Some(inner) => self.infer(*inner),
+                None => self.substitution.unknown(),
+            },
+            "if_else_expr" => self.infer_if(node),
+            "case_of_expr" => self.infer_when(node),
+            "let_in_expr" => self.infer_let(node),
+            "anonymous_function_expr" => self.infer_lambda(node),
+            "record_expr" => self.infer_record(node),
+            "field_access_expr" => {
+                let children = named_children(node);
+                let mut ty = match children.first() {
+                    Some(target) => self.infer(*target),
+                    None => return self.substitution.unknown(),
+                };
+                for field in &children[1..] {
+                    if field.kind() == "lower_case_identifier" {
+                        ty = self.access(&ty, *field);
+                    }
+                }
+                ty
+            }
+            "field_accessor_function_expr" => match child_of_kind(&node, "lower_case_identifier") {
+                Some(field) => {
+                    let record = self.substitution.fresh();
+                    let ty = self.access(&record, field);
+                    Type::function(record, ty)
+                }
+                None => self.substitution.unknown(),
+            },
+            "list_expr" | "array_expr" => {
+                let element = self.substitution.fresh();
+                for item in named_children(node) {
+                    let ty = self.infer(item);
+                    self.expect(&ty, &element);
+                }
+                Type::array(element)
+            }
+            "string_constant_expr" => Type::named("String"),
+            "char_constant_expr" => Type::named("Char"),
+            "number_constant_expr" => self.number(node),
+            "unit_expr" => Type::Record(BTreeMap::new(), None),
+            _ => self.substitution.unknown(),
+        }
+    }
+
+    /// The result of calling a function of type `function` with an argument
+    fn apply(&mut self, function: &Type, argument: Type) -> Type {
+        let result = self.substitution.fresh();
+        self.expect(function, &Type::function(argument, result.clone()));
+        result
+    }
+
+    /// Unify, marking the definition failed when the types don't fit
+    fn expect(&mut self, actual: &Type, expected: &Type) {
+        if !self.substitution.unify(actual, expected) {
+            self.failed = true;
+        }
+    }
+
+    fn number(&mut self, node: Node) -> Type {
+        let text = self.text(node).unwrap_or_default();
+        let hexadecimal = text.starts_with("0x");
+        if !hexadecimal && text.contains(['.', 'e', 'E']) {
+            Type::named("Float")
+        } else {
+            self.substitution.constrained(Constraint::Number)
+        }
+    }
+
+    /// The type of the field `field` of a record of type `record`
+    fn access(&mut self, record: &Type, field: Node) -> Type {
+        let name = self.text(field).unwrap_or_default().to_string();
+        let ty = self.substitution.fresh();
+        let rest = self.substitution.variable(None, false);
+        let expected = Type::Record(BTreeMap::from([(name, ty.clone())]), Some(rest));
+        self.expect(record, &expected);
+        ty
+    }
+
+    fn infer_operators(&mut self, node: Node) -> Type {
+        let mut operands = Vec::new();
+        let mut operators = Vec::new();
+        for child in named_children(node) {
+            match child.kind() {
+                "operator" => match child_of_kind(&child, "operator_identifier") {
+                    Some(name) => operators.push(name),
+                    None => return self.substitution.unknown(),
+                },
+                "operator_identifier" => operators.push(child),
+                _ => operands.push(self.infer(child)),
+            }
+        }
+        if operands.len() != operators.len() + 1 {
+            return self.substitution.unknown();
+        }
+        let mut position = 0;
+        self.climb(&operands, &operators, &mut position, 0)
+    }
+
+    /// Group operands by precedence, from operand `position` on, taking operators binding
+    /// at least as tightly as `minimum`
+    fn climb(
+        &mut self,
+        operands: &[Type],
+        operators: &[Node],
+        position: &mut usize,
+        minimum: u8,
+    ) -> Type {
+        let mut left = operands[*position].clone();
+        while let Some(node) = operators.get(*position) {
+            let name = self.text(*node).unwrap_or_default();
+            let (precedence, right_associative) = match operator(name) {
+                Some((_, precedence, right_associative)) => (precedence, right_associative),
+                None => (9, false),
+            };
+            if precedence < minimum {
+                break;
+            }
+            *position += 1;
+            let next = if right_associative {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let right = self.climb(operands, operators, position, next);
+            let operator = self.operator_type(*node);
+            let partial = self.apply(&operator, left);
+            left = self.apply(&partial, right);
+        }
+        left
+    }
+
+    fn operator_type(&mut self, node: Node) -> Type {
+        let signature = self
+            .text(node)
+            .and_then(operator)
+            .and_then(|(signature, _, _)| parse_signature(signature));
+        match signature {
+            Some(signature) => self.convert(&signature, &mut HashMap::new()),
+            None => self.substitution.unknown(),
+        }
+    }
+
+    fn infer_if(&mut self, node: Node) -> Type {
+        let children = named_children(node);
+        if children.len() < 3 || children.len() % 2 == 0 {
+            return self.substitution.unknown();
+        }
+        let result = self.substitution.fresh();
+        for pair in children[..children.len() - 1].chunks(2) {
+            let condition = self.infer(pair[0]);
+            self.expect(&condition, &Type::named("Bool"));
+            let branch = self.infer(pair[1]);
+            self.expect(&branch, &result);
+        }
+        let otherwise = self.infer(children[children.len() - 1]);
+        self.expect(&otherwise, &result);
+        result
+    }
+
+    fn infer_when(&mut self, node: Node) -> Type {
+        let children = named_children(node);
+        let subject = match children
+            .iter()
+            .find(|child| child.kind() != "case_of_branch")
+        {
+            Some(subject) => self.infer(*subject),
+            None => return self.substitution.unknown(),
+        };
+        let result = self.substitution.fresh();
+        for branch in children
+            .iter()
+            .filter(|child| child.kind() == "case_of_branch")
+        {
+            let pattern = branch
+                .child_by_field_name("pattern")
+                .or_else(|| branch.named_child(0));
+            let body = branch
+                .child_by_field_name("expr")
+                .or_else(|| named_children(*branch).last().copied());
+            let (pattern, body) = match (pattern, body) {
+                (Some(pattern), Some(body)) => (pattern, body),
+                _ => {
+                    self.failed = true;
+                    continue;
+                }
+            };
+            self.scopes.push(HashMap::new());
+            self.bind_pattern(pattern, &subject);
+            let ty = self.infer(body);
+            self.expect(&ty, &result);
+            self.scopes.pop();
+        }
+        result
+    }
+
+    fn infer_let(&mut self, node: Node) -> Type {
+        let children = named_children(node);
+        self.scopes.push(HashMap::new());
+
+        // Bound before any is inferred, so they can refer to each other and themselves
+        for declaration in children
+            .iter()
+            .filter(|child| child.kind() == "value_declaration")
+        {
+            if let Some(name) = self.declared_name(*declaration) {
+                let ty = self.substitution.fresh();
+                self.bind(name.to_string(), Scheme::monomorphic(ty));
+            }
+        }
+
+        let mut annotation = None;
+        for child in &children {
+            match child.kind() {
+                "type_annotation" => annotation = Some(*child),
+                "value_declaration" => {
+                    self.infer_let_declaration(*child, annotation.take());
+                }
+                _ => {}
+            }
+        }
+
+        let body = node.child_by_field_name("body").or_else(|| {
+            children
+                .iter()
+                .rev()
+                .find(|child| !matches!(child.kind(), "value_declaration" | "type_annotation"))
+                .copied()
+        });
+        let result = match body {
+            Some(body) => self.infer(body),
+            None => self.substitution.unknown(),
+        };
+        self.scopes.pop();
+        result
+    }
+
+    fn infer_let_declaration(&mut self, declaration: Node, annotation: Option<Node>) {
+        let left = match child_of_kind(&declaration, "function_declaration_left") {
+            Some(left) => left,
+            None => {
+                // Destructuring, e.g. `{ x, y } = point`
+                let ty = match definition_body(declaration) {
+                    Some(body) => self.infer(body),
+                    None => self.substitution.unknown(),
+                };
+                if let Some(pattern) = declaration.named_child(0) {
+                    self.bind_pattern(pattern, &ty);
+                }
+                return;
+            }
+        };
+        let name_node = match child_of_kind(&left, "lower_case_identifier") {
+            Some(name) => name,
+            None => return,
+        };
+        let name = self.text(name_node).unwrap_or_default().to_string();
+
+        let ty = self.infer_definition(declaration, left);
+        if let Some(own) = self.lookup(&name) {
+            let own = own.ty.clone();
+            self.expect(&ty, &own);
+        }
+        let annotation = annotation.filter(|annotation| {
+            child_of_kind(annotation, "lower_case_identifier").and_then(|n| self.text(n))
+                == Some(name.as_str())
+        });
+        let scheme = match annotation.and_then(|annotation| self.annotation_scheme(annotation)) {
+            Some(scheme) => {
+                let expected = self.substitution.instantiate(&scheme);
+                self.expect(&ty, &expected);
+                scheme
+            }
+            None => {
+                let environment = self.environment_variables(Some(&name));
+                self.substitution.generalize(&ty, &environment)
+            }
+        };
+        self.bound.push((
+            name.clone(),
+            node_range(&name_node, self.source),
+            scheme.ty.clone(),
+        ));
+        self.bind(name, scheme);
+    }
+
+    fn infer_lambda(&mut self, node: Node) -> Type {
+        let children = named_children(node);
+        let patterns: Vec<Node> = children
+            .iter()
+            .take_while(|child| child.kind() != "arrow")
+            .filter(|child| child.kind() != "backslash")
+            .copied()
+            .collect();
+        let body = node
+            .child_by_field_name("expr")
+            .or_else(|| children.last().copied());
+        let body = match body {
+            Some(body) if patterns.len() < children.len() => body,
+            _ => return self.substitution.unknown(),
+        };
+
+        self.scopes.push(HashMap::new());
+        let mut parameters = Vec::new();
+        for pattern in patterns {
+            let ty = self.substitution.fresh();
+            self.bind_pattern(pattern, &ty);
+            parameters.push(ty);
+        }
+        let result = self.infer(body);
+        self.scopes.pop();
+        parameters
+            .into_iter()
+            .rev()
+            .fold(result, |result, parameter| {
+                Type::function(parameter, result)
+            })
+    }
+
+    fn infer_record(&mut self, node: Node) -> Type {
+        let mut fields = BTreeMap::new();
+        let mut base = None;
+        for child in named_children(node) {
+            match child.kind() {
+                "record_base_identifier" => base = Some(self.variable(child)),
+                "field" => {
+                    let name = child_of_kind(&child, "lower_case_identifier")
+                        .and_then(|name| self.text(name))
+                        .map(str::to_string);
+                    let value = named_children(child).into_iter().nth(1);
+                    let ty = match value {
+                        Some(value) => self.infer(value),
+                        None => self.substitution.unknown(),
+                    };
+                    if let Some(name) = name {
+                        fields.insert(name, ty);
+                    }
+                }
+                _ => {}
+            }
+        }
+        match base {
+            // An update keeps the record's type, with the fields it sets among its own
+            Some(base) => {
+                let rest = self.substitution.variable(None, false);
+                self.expect(&base, &Type::Record(fields, Some(rest)));
+                base
+            }
+            None => Type::Record(fields, None),
+        }
+    }
+
+    /// Bind the names a pattern matching a value of type `ty` introduces
+    fn bind_pattern(&mut self, pattern: Node, ty: &Type) {
+        match pattern.kind() {
+            "lower_pattern" | "lower_case_identifier" => {
+                let name = child_of_kind(&pattern, "lower_case_identifier").unwrap_or(pattern);
+                self.bind_local(name, ty.clone());
+            }
+            "anything_pattern" => {}
+            "union_pattern" | "nullary_constructor_argument_pattern" => {
+                let children = named_children(pattern);
+                let mut constructor = match children.first() {
+                    Some(name) if name.kind() == "upper_case_qid" => self.constructor(*name),
+                    _ => return self.bind_unknown(pattern),
+                };
+                for argument in &children[1..] {
+                    let argument_type = self.substitution.fresh();
+                    let result = self.substitution.fresh();
+                    let expected = Type::function(argument_type.clone(), result.clone());
+                    self.expect(&constructor, &expected);
+                    self.bind_pattern(*argument, &argument_type);
+                    constructor = result;
+                }
+                self.expect(ty, &constructor);
+            }
+            "record_pattern" => {
+                let mut fields = BTreeMap::new();
+                for entry in named_children(pattern) {
+                    let field = self.substitution.fresh();
+                    match entry.kind() {
+                        "lower_pattern" | "lower_case_identifier" => {
+                            self.bind_pattern(entry, &field);
+                            let name = self.text(entry).unwrap_or_default().to_string();
+                            fields.insert(name, field);
+                        }
+                        // `{ count = n }`
+                        _ => {
+                            let label = entry
+                                .named_child(0)
+                                .filter(|label| label.kind() == "lower_case_identifier");
+                            let inner = named_children(entry).into_iter().nth(1);
+                            match (label, inner) {
+                                (Some(label), Some(inner)) => {
+                                    self.bind_pattern(inner, &field);
+                                    let name = self.text(label).unwrap_or_default().to_string();
+                                    fields.insert(name, field);
+                                }
+                                _ => self.bind_unknown(entry),
+                            }
+                        }
+                    }
+                }
+                let rest = self.substitution.variable(None, false);
+                self.expect(ty, &Type::Record(fields, Some(rest)));
+            }
+            "list_pattern" | "array_pattern" => {
+                let element = self.substitution.fresh();
+                for item in named_children(pattern) {
+                    self.bind_pattern(item, &element);
+                }
+                self.expect(ty, &Type::array(element));
+            }
+            "string_constant_expr" => self.expect(ty, &Type::named("String")),
+            "char_constant_expr" => self.expect(ty, &Type::named("Char")),
+            "number_constant_expr" => {
+                let number = self.number(pattern);
+                self.expect(ty, &number);
+            }
+            _ => {
+                let children = named_children(pattern);
+                let mut cursor = pattern.walk();
+                let aliased = pattern
+                    .children(&mut cursor)
+                    .any(|child| child.kind() == "as");
+                match children.as_slice() {
+                    // `( x, y ) as point`
+                    [inner, alias] if aliased => {
+                        self.bind_pattern(*inner, ty);
+                        self.bind_pattern(*alias, ty);
+                    }
+                    [inner] => self.bind_pattern(*inner, ty),
+                    _ => self.bind_unknown(pattern),
+                }
+            }
+        }
+    }
+
+    /// Bind every name in a pattern the inference doesn't understand to an unknown type,
+    /// so they still shadow outer names
+    fn bind_unknown(&mut self, pattern: Node) {
+        if matches!(pattern.kind(), "lower_pattern" | "lower_case_identifier") {
+            let ty = self.substitution.unknown();
+            self.bind_pattern(pattern, &ty);
+            return;
+        }
+        for child in named_children(pattern) {
+            self.bind_unknown(child);
+        }
+    }
+
+    fn bind_local(&mut self, name: Node, ty: Type) {
+        let text = self.text(name).unwrap_or_default().to_string();
+        self.bound
+            .push((text.clone(), node_range(&name, self.source), ty.clone()));
+        self.bind(text, Scheme::monomorphic(ty));
+    }
+
+    fn bind(&mut self, name: String, scheme: Scheme) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, scheme);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Scheme> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.top_level.get(name))
+    }
+
+    /// The type of an unqualified value name
+    fn variable(&mut self, name: Node) -> Type {
+        let text = self.text(name).unwrap_or_default();
+        match self.lookup(text) {
+            Some(scheme) => {
+                let scheme = scheme.clone();
+                self.substitution.instantiate(&scheme)
+            }
+            None => self.imported(name),
+        }
+    }
+
+    fn constructor(&mut self, qid: Node) -> Type {
+        let count = qid.named_child_count();
+        let name = match qid.named_child(count.saturating_sub(1)) {
+            Some(name) => name,
+            None => return self.substitution.unknown(),
+        };
+        let own = (count == 1)
+            .then(|| self.text(name).and_then(|text| self.constructors.get(text)))
+            .flatten()
+            .cloned();
+        match own {
+            Some(scheme) => self.substitution.instantiate(&scheme),
+            None => self.imported(name),
+        }
+    }
+
+    /// The type of a name another module defines, from its indexed signature
+    fn imported(&mut self, name: Node) -> Type {
+        let text = self.text(name).unwrap_or_default();
+        let start = name.start_position();
+        let modules = self
+            .modules
+            .get(&(start.row as u32, start.column as u32))
+            .cloned()
+            .unwrap_or_default();
+        let signature = modules
+            .iter()
+            .map(String::as_str)
+            // Every module imports `Basics` exposing everything
+            .chain(modules.is_empty().then_some("Basics"))
+            .find_map(|module| self.environment.signature(module, text))
+            .or_else(|| {
+                DEFAULT_CONSTRUCTORS
+                    .iter()
+                    .find(|(constructor, _)| *constructor == text)
+                    .map(|(_, signature)| signature.to_string())
+            });
+        match signature.as_deref().and_then(parse_signature) {
+            Some(signature) => self.convert(&signature, &mut HashMap::new()),
+            None => self.substitution.unknown(),
+        }
+    }
+
+    /// The scheme of an annotation, polymorphic in all its variables
+    fn annotation_scheme(&mut self, annotation: Node) -> Option<Scheme> {
+        let text = self.text(child_of_kind(&annotation, "type_expression")?)?;
+        let signature = parse_signature(text)?;
+        let ty = self.convert(&signature, &mut HashMap::new());
+        Some(self.substitution.generalize(&ty, &HashSet::new()))
+    }
+
+    /// A type from a signature, with `variables` standing for its type variables and
+    /// aliases expanded
+    fn convert(
+        &mut self,
+        expression: &TypeExpression,
+        variables: &mut HashMap<String, Type>,
+    ) -> Type {
+        match expression {
+            TypeExpression::Variable(name) => {
+                if let Some(ty) = variables.get(name) {
+                    return ty.clone();
+                }
+                let ty =
+                    Type::Variable(self.substitution.variable(Constraint::of_name(name), false));
+                variables.insert(name.clone(), ty.clone());
+                ty
+            }
+            TypeExpression::Named(name, arguments) => {
+                let arguments: Vec<Type> = arguments
+                    .iter()
+                    .map(|argument| self.convert(argument, variables))
+                    .collect();
+                self.expand_alias(name, &arguments)
+                    .unwrap_or_else(|| Type::Named(name.clone(), arguments))
+            }
+            TypeExpression::Function(argument, result) => Type::function(
+                self.convert(argument, variables),
+                self.convert(result, variables),
+            ),
+            TypeExpression::Record(fields, rest) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), self.convert(field, variables)))
+                    .collect();
+                let rest = rest.as_ref().map(|rest| {
+                    match self.convert(&TypeExpression::Variable(rest.clone()), variables) {
+                        Type::Variable(id) => id,
+                        _ => self.substitution.variable(None, true),
+                    }
+                });
+                Type::Record(fields, rest)
+            }
+        }
+    }
+
+    /// The type an alias with these arguments stands for
+    fn expand_alias(&mut self, name: &str, arguments: &[Type]) -> Option<Type> {
+        let short = last_segment(name);
+        if self.constructors_type(short) {
+            return None;
+        }
+        let (parameters, definition) = match self.aliases.get(short) {
+            Some(alias) => alias.clone(),
+            None => self.environment.alias(short)?,
+        };
+        if parameters.len() != arguments.len() || self.alias_depth >= ALIAS_DEPTH {
+            return None;
+        }
+        let definition = parse_signature(&definition)?;
+        let mut variables: HashMap<String, Type> = parameters
+            .into_iter()
+            .zip(arguments.iter().cloned())
+            .collect();
+        self.alias_depth += 1;
+        let ty = self.convert(&definition, &mut variables);
+        self.alias_depth -= 1;
+        Some(Type::Alias(
+            name.to_string(),
+            arguments.to_vec(),
+            Box::new(ty),
+        ))
+    }
+
+    /// Whether the module declares a custom type called `name`
+    fn constructors_type(&self, name: &str) -> bool {
+        self.constructors.values().any(|scheme| {
+            let mut result = &scheme.ty;
+            while let Type::Function(_, next) = result {
+                result = next;
+            }
+            matches!(result, Type::Named(own, _) if own == name)
+        })
+    }
+
+    fn add_alias(&mut self, declaration: Node) {
+        let name = child_of_kind(&declaration, "upper_case_identifier").and_then(|n| self.text(n));
+        let definition = child_of_kind(&declaration, "type_expression").and_then(|n| self.text(n));
+        if let (Some(name), Some(definition)) = (name, definition) {
+            let parameters = self.type_parameters(declaration);
+            self.aliases
+                .insert(name.to_string(), (parameters, definition.to_string()));
+        }
+    }
+
+    fn add_constructors(&mut self, declaration: Node) {
+        let name = match child_of_kind(&declaration, "upper_case_identifier")
+            .and_then(|name| self.text(name))
+        {
+            Some(name) => name.to_string(),
+            None => return,
+        };
+        let mut result = vec![name];
+        result.extend(self.type_parameters(declaration));
+        let result = result.join(" ");
+
+        for variant in named_children(declaration)
+            .into_iter()
+            .filter(|child| child.kind() == "union_variant")
+        {
+            let children = named_children(variant);
+            let constructor = match children.first().and_then(|name| self.text(*name)) {
+                Some(constructor) => constructor.to_string(),
+                None => continue,
+            };
+            let mut parts: Vec<String> = children[1..]
+                .iter()
+                .filter_map(|argument| self.text(*argument))
+                .map(|argument| format!("({})", argument))
+                .collect();
+            parts.push(result.clone());
+            if let Some(signature) = parse_signature(&parts.join(" -> ")) {
+                let ty = self.convert(&signature, &mut HashMap::new());
+                let scheme = self.substitution.generalize(&ty, &HashSet::new());
+                self.constructors.insert(constructor, scheme);
+            }
+        }
+    }
+
+    fn type_parameters(&self, declaration: Node) -> Vec<String> {
+        named_children(declaration)
+            .into_iter()
+            .filter(|child| child.kind() == "lower_type_name")
+            .filter_map(|parameter| self.text(parameter))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Variables in use by the names in scope, which a new binding can't generalize.
+    /// `except` leaves out the innermost binding of that name, the one being generalized.
+    fn environment_variables(&self, except: Option<&str>) -> HashSet<u32> {
+        let innermost = self.scopes.len().checked_sub(1);
+        let scoped = self.scopes.iter().enumerate().flat_map(|(index, scope)| {
+            scope
+                .iter()
+                .filter(move |(name, _)| Some(index) != innermost || Some(name.as_str()) != except)
+                .map(|(_, scheme)| scheme)
+        });
+        // At the top level the binding being generalized is a top-level one
+        let at_top_level = self.scopes.is_empty();
+        let top_level = self
+            .top_level
+            .iter()
+            .filter(move |(name, _)| !at_top_level || Some(name.as_str()) != except)
+            .map(|(_, scheme)| scheme);
+
+        let mut variables = HashSet::new();
+        for scheme in scoped.chain(top_level) {
+            for free in self.substitution.free_variables(&scheme.ty) {
+                if !scheme.variables.contains(&free) {
+                    variables.insert(free);
+                }
+            }
+        }
+        variables
+    }
+
+    fn declared_name(&self, declaration: Node) -> Option<&'a str> {
+        let left = child_of_kind(&declaration, "function_declaration_left")?;
+        self.text(child_of_kind(&left, "lower_case_identifier")?)
+    }
+
+    fn text(&self, node: Node) -> Option<&'a str> {
+        node.utf8_text(self.source.as_bytes()).ok()
+    }
+}
+
+/// The expression a value declaration defines its name as
+fn definition_body(declaration: Node) -> Option<Node> {
+    declaration
+        .child_by_field_name("body")
+        .or_else(|| named_children(declaration).into_iter().skip(1).last())
+}
+
+/// Named children without comments
+fn named_children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    let children = node
+        .named_children(&mut cursor)
+        .filter(|child| !child.kind().ends_with("comment"))
+        .collect();
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+    use lsp_types::Position;
+
+    struct Packages;
+
+    impl TypeEnvironment for Packages {
+        fn signature(&self, module: &str, name: &str) -> Option<String> {
+            match (module, name) {
+                ("Array", "map") => Some("(a -> b) -> Array a -> Array b".to_string()),
+                ("Basics", "toFloat") => Some("Int -> Float".to_string()),
+                _ => None,
+            }
+        }
+
+        fn alias(&self, name: &str) -> Option<(Vec<String>, String)> {
+            (name == "Point").then(|| (Vec::new(), "{ x : Float, y : Float }".to_string()))
+        }
+    }
+
+    /// The type a signature stands for, shown again
+    fn shown(signature: &str) -> Option<String> {
+        let mut inference = Inference::new("", &Packages);
+        let ty = inference.convert(&parse_signature(signature)?, &mut HashMap::new());
+        inference.substitution.render(&ty)
+    }
+
+    #[test]
+    fn test_parse_signature() {
+        use TypeExpression::*;
+        let variable = |name: &str| Variable(name.to_string());
+        let array = |element| Named("Array".to_string(), vec![element]);
+        assert_eq!(
+            parse_signature("(a -> b) -> Array a -> Array b"),
+            Some(Function(
+                Box::new(Function(Box::new(variable("a")), Box::new(variable("b")))),
+                Box::new(Function(
+                    Box::new(array(variable("a"))),
+                    Box::new(array(variable("b")))
+                ))
+            ))
+        );
+        assert_eq!(
+            parse_signature("{ r | name : String }"),
+            Some(Record(
+                vec![("name".to_string(), Named("String".to_string(), Vec::new()))],
+                Some("r".to_string())
+            ))
+        );
+        assert_eq!(parse_signature("Shape a = Circle"), None);
+        assert_eq!(parse_signature("(Int"), None);
+    }
+
+    #[test]
+    fn test_render_names_variables_in_order() {
+        assert_eq!(
+            shown("comparable -> { r | name : String } -> (x -> number) -> Maybe x").as_deref(),
+            Some("comparable -> { a | name : String } -> (b -> number) -> Maybe b")
+        );
+        assert_eq!(
+            shown("Dict.Dict String (Array (Maybe a))").as_deref(),
+            Some("Dict.Dict String (Array (Maybe a))")
+        );
+        // Aliases are shown by name, and work as what they stand for
+        assert_eq!(shown("Point -> Float").as_deref(), Some("Point -> Float"));
+    }
+
+    #[test]
+    fn test_unify_records() {
+        let mut inference = Inference::new("", &Packages);
+        let mut convert = |signature: &str| {
+            let parsed = parse_signature(signature).unwrap();
+            inference.convert(&parsed, &mut HashMap::new())
+        };
+        let extensible = convert("{ r | x : Float }");
+        let point = convert("Point");
+        let closed = convert("{ x : Float }");
+        let substitution = &mut inference.substitution;
+
+        assert!(substitution.unify(&extensible, &point));
+        assert_eq!(
+            substitution.render(&extensible).as_deref(),
+            Some("{ x : Float, y : Float }")
+        );
+        assert!(!substitution.unify(&point, &closed));
+    }
+
+    #[test]
+    fn test_constraints() {
+        let mut substitution = Substitution::default();
+        let number = substitution.constrained(Constraint::Number);
+        assert!(!substitution.unify(&number, &Type::named("String")));
+        assert!(substitution.unify(&number, &Type::named("Int")));
+
+        let comparable = substitution.constrained(Constraint::Comparable);
+        let appendable = substitution.constrained(Constraint::Appendable);
+        assert!(substitution.unify(&comparable, &appendable));
+        assert_eq!(
+            substitution.render(&comparable).as_deref(),
+            Some("compappend")
+        );
+        let number = substitution.constrained(Constraint::Number);
+        assert!(!substitution.unify(&number, &appendable));
+
+        // Arrays of comparable elements compare
+        let comparable = substitution.constrained(Constraint::Comparable);
+        let element = substitution.fresh();
+        assert!(substitution.unify(&comparable, &Type::array(element.clone())));
+        assert_eq!(substitution.render(&element).as_deref(), Some("comparable"));
+    }
+
+    #[test]
+    fn test_unknown_types_are_not_shown() {
+        let mut substitution = Substitution::default();
+        let unknown = substitution.unknown();
+        let result = substitution.fresh();
+        let function = Type::function(Type::named("Int"), result.clone());
+        assert!(substitution.unify(&unknown, &function));
+        assert_eq!(substitution.render(&result), None);
+        assert_eq!(
+            substitution.render(&Type::named("Int")).as_deref(),
+            Some("Int")
+        );
+    }
+
+    const SOURCE: &str = r#"module Main exposing (..)
+
+type Shape
+    = Circle Float
+    | Square Float
+
+area shape =
+    when shape is
+        Circle radius ->
+            radius * radius * 3.14
+
+        Square side ->
+            side * side
+
+double x =
+    x * 2
+
+names people =
+    let
+        name person =
+            person.name
+    in
+    Array.map name people
+
+broken =
+    "text" + 1
+"#;
+
+    #[test]
+    fn test_infer_module() {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        let types = infer_types(&tree, SOURCE, &Packages);
+
+        assert_eq!(types.top_level("area"), Some("Shape -> Float"));
+        assert_eq!(types.top_level("double"), Some("number -> number"));
+        assert_eq!(
+            types.top_level("names"),
+            Some("Array { a | name : b } -> Array b")
+        );
+        // A definition that doesn't type check gets no type
+        assert_eq!(types.top_level("broken"), None);
+
+        // `radius` in its branch pattern
+        let radius = Range::new(Position::new(8, 15), Position::new(8, 21));
+        assert_eq!(types.binding(radius), Some("Float"));
+        // The `let` function `name`
+        let name = Range::new(Position::new(19, 8), Position::new(19, 12));
+        assert_eq!(types.binding(name), Some("{ a | name : b } -> b"));
+    }
+}
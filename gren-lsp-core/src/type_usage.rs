@@ -0,0 +1,131 @@
+//! How a function's type signature uses a type
+//!
+//! Signatures are indexed with their whitespace collapsed. The arrows outside any
+//! parentheses, records or arrays split one into the parameters and the result, and the
+//! type is looked for among the capitalized names of each part, so `Dict` is found in
+//! `Dict.Dict String a -> Maybe a` but not in `DictLike -> Int`. A qualified type name
+//! only matches names with the same qualifier or none, since a module can refer to its
+//! own types unqualified.
+
+use serde::{Deserialize, Serialize};
+
+/// Where a signature mentions a type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TypeUsage {
+    /// In a parameter, so the function consumes values of the type
+    pub consumes: bool,
+    /// In the result, so the function or value produces values of the type
+    pub produces: bool,
+}
+
+/// How `signature` uses `type_name`, `None` when it doesn't mention it
+pub fn type_usage(signature: &str, type_name: &str) -> Option<TypeUsage> {
+    let mut parts = top_level_parts(signature);
+    let result = parts.pop()?;
+    let usage = TypeUsage {
+        consumes: parts.iter().any(|part| mentions(part, type_name)),
+        produces: mentions(result, type_name),
+    };
+    if usage.consumes || usage.produces {
+        Some(usage)
+    } else {
+        None
+    }
+}
+
+/// The parameters and the result of a signature, split at the arrows that aren't nested
+pub fn top_level_parts(signature: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = signature.as_bytes();
+    for (index, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'(' | b'{' | b'[' => depth += 1,
+            b')' | b'}' | b']' => depth -= 1,
+            b'-' if depth == 0 && bytes.get(index + 1) == Some(&b'>') => {
+                parts.push(signature[start..index].trim());
+                start = index + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(signature[start..].trim());
+    parts
+}
+
+/// Whether a part of a signature names the type anywhere in it
+fn mentions(part: &str, type_name: &str) -> bool {
+    let (qualifier, name) = split_qualified(type_name);
+    part.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .filter(|token| token.starts_with(|c: char| c.is_uppercase()))
+        .any(|token| {
+            let (token_qualifier, token_name) = split_qualified(token);
+            token_name == name
+                && (qualifier.is_none()
+                    || token_qualifier.is_none()
+                    || token_qualifier == qualifier)
+        })
+}
+
+fn split_qualified(name: &str) -> (Option<&str>, &str) {
+    match name.rsplit_once('.') {
+        Some((qualifier, name)) => (Some(qualifier), name),
+        None => (None, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(consumes: bool, produces: bool) -> Option<TypeUsage> {
+        Some(TypeUsage { consumes, produces })
+    }
+
+    #[test]
+    fn test_type_usage() {
+        assert_eq!(
+            type_usage("String -> Dict.Dict String a -> Maybe a", "Dict"),
+            usage(true, false)
+        );
+        assert_eq!(type_usage("Decoder User", "User"), usage(false, true));
+        assert_eq!(type_usage("User -> User", "User"), usage(true, true));
+        assert_eq!(
+            type_usage("(User -> Bool) -> Array User -> Array User", "User"),
+            usage(true, true)
+        );
+        assert_eq!(
+            type_usage("{ user : User } -> Html msg", "User"),
+            usage(true, false)
+        );
+        assert_eq!(type_usage("UserId -> Int", "User"), None);
+        assert_eq!(type_usage("user -> Int", "User"), None);
+    }
+
+    #[test]
+    fn test_qualified_type_name() {
+        assert_eq!(
+            type_usage("Dict.Dict k v -> Int", "Dict.Dict"),
+            usage(true, false)
+        );
+        assert_eq!(
+            type_usage("Dict k v -> Int", "Dict.Dict"),
+            usage(true, false)
+        );
+        assert_eq!(type_usage("Other.Dict k v -> Int", "Dict.Dict"), None);
+        assert_eq!(
+            type_usage("Other.Dict k v -> Int", "Dict"),
+            usage(true, false)
+        );
+    }
+
+    #[test]
+    fn test_top_level_parts() {
+        assert_eq!(
+            top_level_parts("(a -> b) -> { f : a -> b } -> b"),
+            vec!["(a -> b)", "{ f : a -> b }", "b"]
+        );
+        assert_eq!(top_level_parts("Int"), vec!["Int"]);
+    }
+}
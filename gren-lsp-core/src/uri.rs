@@ -0,0 +1,222 @@
+//! One spelling per file for document URIs
+//!
+//! The same file can reach the server under several URIs: VS Code sends
+//! `file:///c%3A/project/Main.gren` where other clients send `file:///C:/project/Main.gren`,
+//! and on case-insensitive file systems `src/Main.gren` and `src/main.gren` are one file.
+//! Everything in the workspace is keyed by URI, so each of those spellings would otherwise
+//! get its own document, its own index rows and its own diagnostics.
+//!
+//! Not every document is a file either. Unsaved `untitled:` buffers are checked on their
+//! own, as single-module scratch files, and other schemes like notebook cells are turned
+//! away, since everything that locates modules on disk needs a path.
+
+use lsp_types::Url;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// How the server handles a document, decided by its URI scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    /// A file on disk, part of the project
+    File,
+    /// An unsaved buffer (`untitled:`): parsed, indexed and compiled on its own, without
+    /// other modules seeing its symbols
+    Scratch,
+    /// Any other scheme, such as notebook cells or files from version control
+    Unsupported,
+}
+
+impl DocumentKind {
+    pub fn of(uri: &Url) -> Self {
+        match uri.scheme() {
+            "file" => DocumentKind::File,
+            "untitled" => DocumentKind::Scratch,
+            _ => DocumentKind::Unsupported,
+        }
+    }
+}
+
+/// Where the compiler is told a scratch document lives. Nothing is written there, it only
+/// gives the compiler's diagnostics a path to be matched back to the buffer by.
+pub fn scratch_path(uri: &Url) -> PathBuf {
+    let name: String = uri
+        .path()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    std::env::temp_dir()
+        .join("gren-lsp")
+        .join("untitled")
+        .join(format!("{}.gren", name))
+}
+
+/// Maps every spelling of a file's URI to the one the workspace uses for it, which is
+/// the spelling the file was most recently opened with, so diagnostics are published
+/// under a URI the client knows.
+#[derive(Debug, Clone)]
+pub struct UriNormalizer {
+    case_insensitive: bool,
+    spellings: HashMap<String, Url>,
+}
+
+impl Default for UriNormalizer {
+    fn default() -> Self {
+        Self::new(host_is_case_insensitive())
+    }
+}
+
+impl UriNormalizer {
+    pub fn new(case_insensitive: bool) -> Self {
+        Self {
+            case_insensitive,
+            spellings: HashMap::new(),
+        }
+    }
+
+    /// The spelling in use for the file `uri` refers to, or `uri` itself for a file the
+    /// workspace hasn't seen
+    pub fn normalize(&self, uri: &Url) -> Url {
+        self.spellings
+            .get(&uri_key(uri, self.case_insensitive))
+            .cloned()
+            .unwrap_or_else(|| uri.clone())
+    }
+
+    /// Make `uri` the spelling for its file, returning the spelling it replaces if that
+    /// was a different one
+    pub fn adopt(&mut self, uri: &Url) -> Option<Url> {
+        self.spellings
+            .insert(uri_key(uri, self.case_insensitive), uri.clone())
+            .filter(|previous| previous != uri)
+    }
+
+    /// Stop tracking the file `uri` refers to
+    pub fn forget(&mut self, uri: &Url) {
+        self.spellings.remove(&uri_key(uri, self.case_insensitive));
+    }
+}
+
+/// Whether the host's file systems ignore case by default, as on macOS and Windows
+pub fn host_is_case_insensitive() -> bool {
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+/// Comparison key for a URI: `file` URIs are percent-decoded, get a lower case drive
+/// letter and, on case-insensitive file systems, a lower case path. Other schemes are
+/// compared as written.
+pub fn uri_key(uri: &Url, case_insensitive: bool) -> String {
+    if uri.scheme() != "file" {
+        return uri.as_str().to_string();
+    }
+
+    let mut path = percent_decode(uri.path());
+    if case_insensitive {
+        path = path.to_lowercase();
+    } else if is_drive_path(&path) {
+        // Drive letters are case-insensitive everywhere
+        path[1..2].make_ascii_lowercase();
+    }
+    format!("file://{}{}", uri.host_str().unwrap_or_default(), path)
+}
+
+/// Whether a decoded URI path starts with a Windows drive, e.g. `/C:/`
+fn is_drive_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':'
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(text: &str) -> Url {
+        Url::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_percent_encoding_and_drive_letters_share_a_key() {
+        let encoded = url("file:///c%3A/Project/src/Main.gren");
+        let plain = url("file:///C:/Project/src/Main.gren");
+        assert_eq!(uri_key(&encoded, false), uri_key(&plain, false));
+        assert_eq!(
+            uri_key(&url("file:///home/me/My%20App/Main.gren"), false),
+            "file:///home/me/My App/Main.gren"
+        );
+    }
+
+    #[test]
+    fn test_case_only_matters_on_case_sensitive_file_systems() {
+        let upper = url("file:///Users/me/Project/src/Main.gren");
+        let lower = url("file:///users/me/project/src/main.gren");
+        assert_eq!(uri_key(&upper, true), uri_key(&lower, true));
+        assert_ne!(uri_key(&upper, false), uri_key(&lower, false));
+    }
+
+    #[test]
+    fn test_most_recently_adopted_spelling_wins() {
+        let mut uris = UriNormalizer::new(true);
+        let indexed = url("file:///Users/me/project/src/Main.gren");
+        let opened = url("file:///Users/me/Project/src/Main.gren");
+
+        assert_eq!(uris.normalize(&opened), opened);
+        assert_eq!(uris.adopt(&indexed), None);
+        assert_eq!(uris.normalize(&opened), indexed);
+        assert_eq!(uris.adopt(&indexed), None);
+
+        assert_eq!(uris.adopt(&opened), Some(indexed.clone()));
+        assert_eq!(uris.normalize(&indexed), opened);
+
+        uris.forget(&indexed);
+        assert_eq!(uris.normalize(&indexed), indexed);
+    }
+
+    #[test]
+    fn test_document_kinds() {
+        assert_eq!(
+            DocumentKind::of(&url("file:///project/src/Main.gren")),
+            DocumentKind::File
+        );
+        assert_eq!(
+            DocumentKind::of(&url("untitled:Untitled-1")),
+            DocumentKind::Scratch
+        );
+        assert_eq!(
+            DocumentKind::of(&url("vscode-notebook-cell:/project/Notes.ipynb#W0sZmlsZQ")),
+            DocumentKind::Unsupported
+        );
+        assert!(scratch_path(&url("untitled:Untitled-1")).ends_with("untitled/Untitled_1.gren"));
+    }
+
+    #[test]
+    fn test_case_sensitive_spellings_stay_separate() {
+        let mut uris = UriNormalizer::new(false);
+        let upper = url("file:///project/src/Main.gren");
+        let lower = url("file:///project/src/main.gren");
+        uris.adopt(&upper);
+        assert_eq!(uris.normalize(&lower), lower);
+    }
+}
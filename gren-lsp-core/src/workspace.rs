@@ -1,86 +1,630 @@
+use crate::alias_cycles::alias_cycles;
+use crate::comments::declaration_comments;
+use crate::compiler::CompileReport;
+use crate::diagnostics::{CachedDiagnostics, DiagnosticsCache};
+use crate::edit_journal::{EditJournal, JournalEntry};
+use crate::formatter::unformatted_diagnostic;
+use crate::import_conflicts::{import_conflict_diagnostics, import_conflicts, imports};
+use crate::imports::{import_group_for_path, import_group_for_unindexed, import_style_diagnostic};
+use crate::inferred_types::{probed_type, type_probe};
+use crate::lints::{
+    missing_annotation_diagnostics, unused_binding_diagnostics, unused_exposed_diagnostics,
+    unused_import_diagnostics,
+};
+use crate::manifest::exposed_module_entries;
+use crate::metrics::{metric_diagnostics, module_metrics};
+use crate::package_resolver::{self, PackageSource};
+use crate::project_config::{ProjectConfig, PROJECT_CONFIG_FILE};
+use crate::project_graph::ProjectGraph;
+use crate::record_updates::{alias_fields, record_updates};
+use crate::scope_analysis::{extract_references, SymbolReference};
+use crate::source_file::{line_ending, read_source};
+use crate::symbol_query::{FileFilter, SymbolQuery};
+use crate::telemetry::uri_hash;
+use crate::todos::{extract_todos, TodoItem};
+use crate::type_inference::{infer_types, ModuleTypes, TypeEnvironment};
+use crate::uri::{scratch_path, DocumentKind, UriNormalizer};
 use crate::{
-    compiler_diagnostics_to_lsp, parse_errors_to_diagnostics, Document, GrenCompiler, Parser,
-    SymbolExtractor, SymbolIndex,
+    annotation_arity_diagnostics, compiler_diagnostics_to_lsp, organize_imports,
+    parse_errors_to_diagnostics, shadowed_bindings, CompilerOptions, DeclarationCheck, Document,
+    Formatter, GrenCompiler, ImportGroup, ImportStyle, InMemorySymbolStore, IndexGenerations,
+    IndexStats, LintProfile, MetricThresholds, ModuleExports, ModuleGraph, ModuleHeader,
+    ModuleMetrics, Parser, PopularSymbols, ReferenceCounts, RuntimeLog, SymbolExtractor,
+    SymbolStore, SymbolStoreBackend,
 };
 use anyhow::Result;
 use lru::LruCache;
 use lsp_types::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use tracing::{info, warn};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tracing::{debug_span, info, instrument, warn};
 
 const DEFAULT_CACHE_SIZE: usize = 100;
+/// Number of compile reports kept for `compile_history`
+const COMPILE_HISTORY_SIZE: usize = 50;
 
 pub struct Workspace {
+    /// The primary workspace folder, whose `.gren-lsp.toml` and `gren.json` are used
     root_uri: Option<Url>,
+    /// Every workspace folder, the primary one first
+    folders: Vec<Url>,
     documents: HashMap<Url, Document>,
     recently_accessed: LruCache<Url, ()>,
     parser: Parser,
-    symbol_index: SymbolIndex,
+    symbol_store: Arc<dyn SymbolStore>,
+    /// Symbols of untitled buffers, kept apart so other modules don't see them and they're
+    /// never written to disk
+    scratch_store: Arc<dyn SymbolStore>,
     symbol_extractor: SymbolExtractor,
-    compiler: Option<GrenCompiler>,
+    /// The projects in the workspace folders, each folder with a `gren.json`
+    projects: ProjectGraph,
+    /// A compiler for each workspace folder and each project compiled so far, keyed by
+    /// the folder's URI
+    compilers: HashMap<Url, GrenCompiler>,
+    /// Why the compiler couldn't be set up for the primary folder, if it couldn't
+    compiler_error: Option<String>,
+    /// Compiler environment and flags from the client's settings
+    compiler_settings: CompilerOptions,
+    /// Each workspace folder's `.gren-lsp.toml`, keyed by the folder's URI
+    folder_configs: HashMap<Url, FolderConfig>,
+    /// Failure of the latest write to the symbol store, cleared once a file indexes cleanly
+    symbol_index_error: Option<String>,
+    /// Import style to lint open documents against, if the lint is enabled
+    import_lint: Option<ImportStyle>,
+    /// Thresholds for metric hints, `None` when they're off
+    metric_hints: Option<MetricThresholds>,
+    /// Severity unused imports and missing annotations are reported with
+    lint_profile: LintProfile,
+    /// Declared module and imports of every indexed file
+    module_graph: ModuleGraph,
+    /// The spelling each file's URI is stored under
+    uris: UriNormalizer,
+    /// Most recent compiles, oldest first
+    compile_history: VecDeque<CompileReport>,
+    /// Compiles the client hasn't been told about yet
+    unsent_compile_reports: Vec<CompileReport>,
+    /// `Debug.log` values reported by a running program
+    runtime_log: RuntimeLog,
+    /// Edits the server applied, for undoing them
+    edit_journal: EditJournal,
+    /// When each file was last indexed, and which ones are waiting to be re-indexed
+    index_generations: IndexGenerations,
+    /// Use counts shown on hover, behind a lock so requests holding the workspace for
+    /// reading can fill it in
+    reference_counts: Mutex<ReferenceCounts>,
+    /// How often each module's names are used, for completing short words
+    popular_symbols: PopularSymbols,
+    /// Files the last formatting check found unformatted, until they change
+    unformatted: HashSet<Url>,
+    /// Files the client was last sent diagnostics for that weren't empty, as they were sent
+    published_diagnostics: HashSet<Url>,
+    /// The diagnostics last computed for each document, for clients pulling them
+    diagnostics_cache: DiagnosticsCache,
+    /// Whether the client pulls diagnostics with `textDocument/diagnostic` rather than
+    /// having them pushed
+    pull_diagnostics: bool,
+    /// Modules of dependency packages indexed from the package cache, which are read-only
+    package_files: HashSet<Url>,
 }
 
 impl Workspace {
     pub fn new() -> Result<Self> {
         Ok(Self {
             root_uri: None,
+            folders: Vec::new(),
             documents: HashMap::new(),
             recently_accessed: LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap()),
             parser: Parser::new()?,
-            symbol_index: SymbolIndex::new()?,
+            symbol_store: SymbolStoreBackend::default().create()?,
+            scratch_store: Arc::new(InMemorySymbolStore::new()),
             symbol_extractor: SymbolExtractor::new()?,
-            compiler: None,
+            projects: ProjectGraph::default(),
+            compilers: HashMap::new(),
+            compiler_error: None,
+            compiler_settings: CompilerOptions::default(),
+            folder_configs: HashMap::new(),
+            symbol_index_error: None,
+            import_lint: None,
+            metric_hints: None,
+            lint_profile: LintProfile::default(),
+            module_graph: ModuleGraph::new(),
+            uris: UriNormalizer::default(),
+            compile_history: VecDeque::with_capacity(COMPILE_HISTORY_SIZE),
+            unsent_compile_reports: Vec::new(),
+            runtime_log: RuntimeLog::new(),
+            edit_journal: EditJournal::new(),
+            index_generations: IndexGenerations::new(),
+            reference_counts: Mutex::new(ReferenceCounts::new()),
+            popular_symbols: PopularSymbols::new(),
+            unformatted: HashSet::new(),
+            published_diagnostics: HashSet::new(),
+            diagnostics_cache: DiagnosticsCache::new(),
+            pull_diagnostics: false,
+            package_files: HashSet::new(),
         })
     }
 
     pub fn with_capacity(capacity: usize) -> Result<Self> {
         Ok(Self {
             root_uri: None,
+            folders: Vec::new(),
             documents: HashMap::new(),
             recently_accessed: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
             parser: Parser::new()?,
-            symbol_index: SymbolIndex::new()?,
+            symbol_store: SymbolStoreBackend::default().create()?,
+            scratch_store: Arc::new(InMemorySymbolStore::new()),
             symbol_extractor: SymbolExtractor::new()?,
-            compiler: None,
+            projects: ProjectGraph::default(),
+            compilers: HashMap::new(),
+            compiler_error: None,
+            compiler_settings: CompilerOptions::default(),
+            folder_configs: HashMap::new(),
+            symbol_index_error: None,
+            import_lint: None,
+            metric_hints: None,
+            lint_profile: LintProfile::default(),
+            module_graph: ModuleGraph::new(),
+            uris: UriNormalizer::default(),
+            compile_history: VecDeque::with_capacity(COMPILE_HISTORY_SIZE),
+            unsent_compile_reports: Vec::new(),
+            runtime_log: RuntimeLog::new(),
+            edit_journal: EditJournal::new(),
+            index_generations: IndexGenerations::new(),
+            reference_counts: Mutex::new(ReferenceCounts::new()),
+            popular_symbols: PopularSymbols::new(),
+            unformatted: HashSet::new(),
+            published_diagnostics: HashSet::new(),
+            diagnostics_cache: DiagnosticsCache::new(),
+            pull_diagnostics: false,
+            package_files: HashSet::new(),
         })
     }
 
+    /// Replace the symbol store, re-indexing open documents into the new one
+    pub fn set_symbol_store(&mut self, symbol_store: Arc<dyn SymbolStore>) -> Result<()> {
+        self.symbol_store = symbol_store;
+        self.symbol_index_error = None;
+        self.reindex_all_symbols()
+    }
+
+    /// Enable or disable the import style lint
+    pub fn set_import_lint(&mut self, style: Option<ImportStyle>) {
+        self.import_lint = style;
+    }
+
+    /// Enable or disable hints for functions over the metric thresholds
+    pub fn set_metric_hints(&mut self, thresholds: Option<MetricThresholds>) {
+        self.metric_hints = thresholds;
+    }
+
+    /// Set how strictly unused imports and missing annotations are reported
+    pub fn set_lint_profile(&mut self, profile: LintProfile) {
+        self.lint_profile = profile;
+    }
+
+    /// Set the compiler environment and flags from the client's settings. They apply on
+    /// top of the ones in the project's `.gren-lsp.toml`.
+    pub fn set_compiler_settings(&mut self, options: CompilerOptions) {
+        self.compiler_settings = options;
+        self.apply_compiler_options();
+    }
+
+    /// Environment and flags compiler runs in the primary folder get
+    pub fn compiler_options(&self) -> CompilerOptions {
+        match self.root_uri.as_ref() {
+            Some(root) => self.compiler_options_for(root),
+            None => CompilerOptions::default().merged_with(&self.compiler_settings),
+        }
+    }
+
+    /// Environment and flags compiler runs for a file or project get: those of its
+    /// workspace folder's `.gren-lsp.toml`, under the client's settings
+    pub fn compiler_options_for(&self, uri: &Url) -> CompilerOptions {
+        self.folder_config(uri)
+            .compiler
+            .merged_with(&self.compiler_settings)
+    }
+
+    /// Give every compiler the options of the folder it runs in
+    fn apply_compiler_options(&mut self) {
+        let options: Vec<(Url, CompilerOptions)> = self
+            .compilers
+            .keys()
+            .map(|key| (key.clone(), self.compiler_options_for(key)))
+            .collect();
+        for (key, options) in options {
+            if let Some(compiler) = self.compilers.get_mut(&key) {
+                compiler.set_options(options);
+            }
+        }
+    }
+
+    /// The primary folder's `.gren-lsp.toml`, default when it's missing or invalid
+    pub fn project_config(&self) -> &ProjectConfig {
+        match self.root_uri.as_ref() {
+            Some(root) => self.folder_config(root),
+            None => default_project_config(),
+        }
+    }
+
+    /// Why a folder's `.gren-lsp.toml` was ignored, if one was, the primary folder's first
+    pub fn project_config_error(&self) -> Option<&str> {
+        self.folders
+            .iter()
+            .filter_map(|folder| self.folder_configs.get(folder))
+            .find_map(|folder_config| folder_config.error.as_deref())
+    }
+
+    /// Where each workspace folder's `.gren-lsp.toml` is or would be, with the problems
+    /// in it to publish on the file
+    pub fn project_config_diagnostics(&self) -> Vec<(Url, Vec<Diagnostic>)> {
+        self.folders
+            .iter()
+            .filter_map(|folder| {
+                let root = uri_to_path(folder).ok()?;
+                let uri = Url::from_file_path(root.join(PROJECT_CONFIG_FILE)).ok()?;
+                let diagnostics = self
+                    .folder_configs
+                    .get(folder)
+                    .map(|folder_config| folder_config.diagnostics.clone())
+                    .unwrap_or_default();
+                Some((uri, diagnostics))
+            })
+            .collect()
+    }
+
+    /// Read every folder's `.gren-lsp.toml` again after one changed
+    pub fn reload_project_config(&mut self) {
+        for folder in self.folders.clone() {
+            if let Ok(path) = uri_to_path(&folder) {
+                self.load_project_config(folder, &path);
+            }
+        }
+        self.apply_compiler_options();
+    }
+
+    /// Workspace folder whose `.gren-lsp.toml` applies to a file or project: the folder
+    /// itself, the one it's in, or the primary folder for what's outside every folder
+    fn config_folder(&self, uri: &Url) -> Option<&Url> {
+        self.folders
+            .iter()
+            .find(|folder| *folder == uri)
+            .or_else(|| self.folder_of(uri))
+            .or(self.root_uri.as_ref())
+    }
+
+    /// The `.gren-lsp.toml` that applies to a file, default when it's missing or invalid
+    fn folder_config(&self, uri: &Url) -> &ProjectConfig {
+        self.config_folder(uri)
+            .and_then(|folder| self.folder_configs.get(folder))
+            .map_or(default_project_config(), |folder_config| {
+                &folder_config.config
+            })
+    }
+
+    /// Whether `exclude` in the `.gren-lsp.toml` of a file's folder keeps it from being
+    /// indexed and linted
+    pub fn is_excluded(&self, uri: &Url) -> bool {
+        self.folder_config(uri)
+            .exclude_filters()
+            .iter()
+            .any(|filter| self.file_matches(filter, uri))
+    }
+
+    /// Whether `index.signatures_only` in the `.gren-lsp.toml` of a file's folder has it
+    /// indexed with only the signatures of what it exposes
+    pub fn is_signatures_only(&self, uri: &Url) -> bool {
+        self.folder_config(uri)
+            .signatures_only_filters()
+            .iter()
+            .any(|filter| self.file_matches(filter, uri))
+    }
+
+    /// Mark an indexed file as a module of a dependency package from the package cache
+    pub fn mark_package_file(&mut self, uri: &Url) {
+        self.package_files.insert(self.uris.normalize(uri));
+    }
+
+    /// Every file indexed from the package cache
+    pub fn package_files(&self) -> Vec<Url> {
+        self.package_files.iter().cloned().collect()
+    }
+
+    /// Whether a file was indexed from the package cache. Package modules are read-only:
+    /// they aren't linted, compiled or edited.
+    pub fn is_package_file(&self, uri: &Url) -> bool {
+        self.package_files.contains(&self.uris.normalize(uri))
+    }
+
+    /// The URI the workspace stores the file `uri` refers to under. Differently encoded
+    /// or, on case-insensitive file systems, differently cased spellings of an open
+    /// file's URI all map to one.
+    pub fn normalize_uri(&self, uri: &Url) -> Url {
+        self.uris.normalize(uri)
+    }
+
+    /// Shared handle to the symbol store, for querying without holding the workspace
+    pub fn symbol_store(&self) -> Arc<dyn SymbolStore> {
+        self.symbol_store.clone()
+    }
+
+    /// Make `root_uri` the only workspace folder
     pub fn set_root(&mut self, root_uri: Url) -> Result<()> {
         info!("Setting workspace root: {}", root_uri);
         self.root_uri = Some(root_uri.clone());
+        self.folders = vec![root_uri.clone()];
+        self.compilers.clear();
+        self.folder_configs.clear();
+        self.refresh_projects();
 
         // Try to initialize the compiler when root is set
-        if let Ok(path) = uri_to_path(&root_uri) {
-            match GrenCompiler::new(path) {
-                Ok(compiler) => {
-                    if compiler.is_available() {
-                        info!("Gren compiler initialized for workspace");
-                        self.compiler = Some(compiler);
-                        Ok(())
-                    } else {
-                        let error_msg = "Gren compiler not available - LSP server cannot function without a working compiler";
-                        warn!("{}", error_msg);
-                        Err(anyhow::anyhow!(error_msg))
-                    }
-                }
-                Err(e) => {
-                    let error_msg = format!("Failed to initialize Gren compiler: {}", e);
-                    warn!("{}", error_msg);
-                    Err(anyhow::anyhow!(error_msg))
-                }
-            }
+        let result = if let Ok(path) = uri_to_path(&root_uri) {
+            self.load_project_config(root_uri.clone(), &path);
+            self.start_compiler(root_uri, path)
         } else {
             let error_msg = "Invalid workspace root URI - cannot initialize compiler";
             warn!("{}", error_msg);
             Err(anyhow::anyhow!(error_msg))
+        };
+
+        self.compiler_error = result.as_ref().err().map(|e| e.to_string());
+        result
+    }
+
+    /// Add a workspace folder with a compiler of its own, making it the primary folder when
+    /// there's none yet
+    pub fn add_folder(&mut self, folder: Url) -> Result<()> {
+        if self.root_uri.is_none() {
+            return self.set_root(folder);
+        }
+        if self.folders.contains(&folder) {
+            return Ok(());
+        }
+
+        info!("Adding workspace folder: {}", folder);
+        self.folders.push(folder.clone());
+        self.refresh_projects();
+        let path = uri_to_path(&folder)?;
+        self.load_project_config(folder.clone(), &path);
+        self.start_compiler(folder, path)
+    }
+
+    /// Remove a workspace folder along with its compiler and the files indexed from it,
+    /// returning those files. The next folder becomes the primary one when the primary
+    /// folder is removed.
+    pub fn remove_folder(&mut self, folder: &Url) -> Vec<Url> {
+        if !self.folders.contains(folder) {
+            return Vec::new();
+        }
+        info!("Removing workspace folder: {}", folder);
+
+        let mut files: Vec<Url> = self
+            .module_graph
+            .files()
+            .chain(self.documents.keys())
+            .filter(|uri| self.folder_of(uri) == Some(folder))
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        files.dedup();
+        for uri in &files {
+            self.drop_file(uri);
+            self.uris.forget(uri);
+        }
+
+        self.folders.retain(|other| other != folder);
+        self.folder_configs.remove(folder);
+        self.refresh_projects();
+        let stale: Vec<Url> = self
+            .compilers
+            .keys()
+            .filter(|key| !self.folders.contains(key) && self.folder_of(key).is_none())
+            .cloned()
+            .collect();
+        for key in &stale {
+            self.compilers.remove(key);
+        }
+        if self.root_uri.as_ref() == Some(folder) {
+            self.root_uri = self.folders.first().cloned();
+            self.compiler_error = None;
+        }
+        files
+    }
+
+    /// Indexed and open files below the folder `folder`, sorted
+    pub fn files_below(&self, folder: &Url) -> Vec<Url> {
+        let prefix = format!("{}/", folder.as_str().trim_end_matches('/'));
+        let mut files: Vec<Url> = self
+            .module_graph
+            .files()
+            .chain(self.documents.keys())
+            .filter(|uri| uri.as_str().starts_with(&prefix))
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        files.dedup();
+        files
+    }
+
+    /// Every workspace folder, the primary one first
+    pub fn folders(&self) -> &[Url] {
+        &self.folders
+    }
+
+    /// The workspace folder a file belongs to, the innermost one when folders are nested
+    pub fn folder_of(&self, uri: &Url) -> Option<&Url> {
+        self.folders
+            .iter()
+            .filter(|folder| {
+                let folder = format!("{}/", folder.as_str().trim_end_matches('/'));
+                uri.as_str().starts_with(&folder)
+            })
+            .max_by_key(|folder| folder.as_str().len())
+    }
+
+    /// Folder of the `gren.json` a file is governed by: its project's root, or its
+    /// workspace folder when it's in no project
+    pub fn project_root(&self, uri: &Url) -> Option<PathBuf> {
+        let path = uri_to_path(uri).ok()?;
+        match self.projects.project_of(&path) {
+            Some(project) => Some(project.root.clone()),
+            None => self
+                .folder_of(uri)
+                .and_then(|folder| uri_to_path(folder).ok()),
+        }
+    }
+
+    /// Find the projects in the workspace folders again, after a `gren.json` was added,
+    /// changed or removed
+    pub fn refresh_projects(&mut self) {
+        let folders: Vec<PathBuf> = self
+            .folders
+            .iter()
+            .filter_map(|folder| uri_to_path(folder).ok())
+            .collect();
+        self.projects = ProjectGraph::discover(&folders);
+    }
+
+    /// The projects in the workspace folders
+    pub fn projects(&self) -> &ProjectGraph {
+        &self.projects
+    }
+
+    /// Whether code in `from` can use a module defined in `to`, which needs them to be in
+    /// one project or `to` to be in a project `from`'s depends on
+    pub fn is_visible(&self, from: &Url, to: &Url) -> bool {
+        match (uri_to_path(from), uri_to_path(to)) {
+            (Ok(from), Ok(to)) => self.projects.is_visible(&from, &to),
+            _ => true,
+        }
+    }
+
+    /// Key of the compiler for a file: its project's, or its folder's when that's where
+    /// the project is or it's in no project, or the primary folder's for files outside
+    /// every folder such as untitled buffers
+    fn compiler_key(&self, uri: &Url) -> Option<Url> {
+        let folder = self.folder_of(uri).or(self.root_uri.as_ref());
+        let project = uri_to_path(uri)
+            .ok()
+            .and_then(|path| self.projects.project_of(&path))
+            .map(|project| project.root.clone());
+        match (project, folder) {
+            (Some(root), Some(folder)) if uri_to_path(folder).ok() == Some(root.clone()) => {
+                Some(folder.clone())
+            }
+            (Some(root), _) => Url::from_directory_path(root).ok(),
+            (None, folder) => folder.cloned(),
+        }
+    }
+
+    /// Key of the compiler for a file, starting its project's compiler the first time one
+    /// of the project's files is compiled
+    fn start_compiler_for(&mut self, uri: &Url) -> Option<Url> {
+        let key = self.compiler_key(uri)?;
+        if !self.compilers.contains_key(&key) && !self.folders.contains(&key) {
+            if let Ok(path) = uri_to_path(&key) {
+                // A compiler that fails to start has already been logged
+                let _ = self.start_compiler(key.clone(), path);
+            }
+        }
+        Some(key)
+    }
+
+    /// The primary folder's compiler
+    fn primary_compiler(&self) -> Option<&GrenCompiler> {
+        self.compilers.get(self.root_uri.as_ref()?)
+    }
+
+    /// Start a compiler running in `path` for the workspace folder `folder`
+    fn start_compiler(&mut self, folder: Url, path: PathBuf) -> Result<()> {
+        match GrenCompiler::new(path) {
+            Ok(mut compiler) => {
+                if compiler.is_available() {
+                    info!("Gren compiler initialized for {}", folder);
+                    compiler.set_options(self.compiler_options_for(&folder));
+                    self.compilers.insert(folder, compiler);
+                    Ok(())
+                } else {
+                    let error_msg = "Gren compiler not available - LSP server cannot function without a working compiler";
+                    warn!("{}", error_msg);
+                    Err(anyhow::anyhow!(error_msg))
+                }
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to initialize Gren compiler: {}", e);
+                warn!("{}", error_msg);
+                Err(anyhow::anyhow!(error_msg))
+            }
+        }
+    }
+
+    /// Set the root of a project without starting the compiler, for the command line
+    /// tools that only need the syntax tree
+    pub fn set_project_root(&mut self, root: &Path) -> Result<()> {
+        let root_uri = Url::from_directory_path(root)
+            .map_err(|_| anyhow::anyhow!("Invalid project root {}", root.display()))?;
+        self.folders = vec![root_uri.clone()];
+        self.root_uri = Some(root_uri.clone());
+        self.folder_configs.clear();
+        self.load_project_config(root_uri, root);
+        Ok(())
+    }
+
+    /// Read a workspace folder's `.gren-lsp.toml`. A file that can't be used is ignored,
+    /// and the reason kept for `project_config_error` and `project_config_diagnostics`.
+    fn load_project_config(&mut self, folder: Url, project_dir: &Path) {
+        let path = project_dir.join(PROJECT_CONFIG_FILE);
+        let (config, error, diagnostics) = match std::fs::read_to_string(&path) {
+            Ok(content) => match ProjectConfig::parse(&content) {
+                Ok(config) => (config, None, Vec::new()),
+                Err(e) => (
+                    ProjectConfig::default(),
+                    Some(format!("Invalid {}: {}", path.display(), e)),
+                    vec![e.to_diagnostic()],
+                ),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                (ProjectConfig::default(), None, Vec::new())
+            }
+            Err(e) => (
+                ProjectConfig::default(),
+                Some(format!("Failed to read {}: {}", path.display(), e)),
+                Vec::new(),
+            ),
+        };
+        if let Some(error) = &error {
+            warn!("Ignoring project configuration: {}", error);
+        } else if config != ProjectConfig::default() {
+            info!("Project configuration: {:?}", config);
         }
+        self.folder_configs.insert(
+            folder,
+            FolderConfig {
+                config,
+                error,
+                diagnostics,
+            },
+        );
     }
 
     pub fn open_document(&mut self, text_document: TextDocumentItem) -> Result<()> {
         let uri = text_document.uri.clone();
         info!("Opening document: {}", uri);
+        if DocumentKind::of(&uri) == DocumentKind::Unsupported {
+            anyhow::bail!(
+                "`{}:` documents aren't supported, only files and untitled buffers",
+                uri.scheme()
+            );
+        }
+
+        // Reopening a file under another spelling of its URI replaces the old entry
+        if let Some(previous) = self.uris.adopt(&uri) {
+            info!("{} was previously known as {}", uri, previous);
+            self.drop_file(&previous);
+        }
 
         let mut document = Document::new(text_document);
 
@@ -103,7 +647,7 @@ impl Workspace {
     }
 
     pub fn update_document(&mut self, params: DidChangeTextDocumentParams) -> Result<()> {
-        let uri = params.text_document.uri.clone();
+        let uri = self.uris.normalize(&params.text_document.uri);
 
         if let Some(document) = self.documents.get_mut(&uri) {
             // Verify version matches or is newer
@@ -119,6 +663,8 @@ impl Workspace {
 
             // Apply changes
             document.apply_changes(params.content_changes)?;
+            // The last formatting check doesn't hold for the new contents
+            self.unformatted.remove(&uri);
 
             // Update access time
             self.recently_accessed.put(uri.clone(), ());
@@ -141,6 +687,7 @@ impl Workspace {
     }
 
     pub fn close_document(&mut self, uri: Url) -> Result<()> {
+        let uri = self.uris.normalize(&uri);
         info!("Closing document: {}", uri);
 
         // Do NOT remove symbols from index when closing documents
@@ -149,26 +696,83 @@ impl Workspace {
         self.documents.remove(&uri);
         self.recently_accessed.pop(&uri);
 
+        // An untitled buffer is gone for good once it's closed
+        if DocumentKind::of(&uri) == DocumentKind::Scratch {
+            self.drop_file(&uri);
+            self.uris.forget(&uri);
+        }
+
         Ok(())
     }
 
     /// Remove a file entirely (e.g., when deleted from filesystem)
     /// This removes both the document and its symbols
     pub fn remove_file(&mut self, uri: Url) -> Result<()> {
+        let uri = self.uris.normalize(&uri);
         info!("Removing file completely: {}", uri);
 
-        // Remove symbols from index for deleted files
-        if let Err(e) = self.symbol_index.clear_file_symbols(uri.as_str()) {
+        self.drop_file(&uri);
+        self.uris.forget(&uri);
+
+        Ok(())
+    }
+
+    /// Remove a file's document, index entries and module graph entry
+    fn drop_file(&mut self, uri: &Url) {
+        // Remove symbols and task markers from index for deleted files
+        let store = self.store_for(uri);
+        // A pass whose writes to the workspace index all succeed clears an earlier failure
+        if DocumentKind::of(uri) != DocumentKind::Scratch {
+            self.symbol_index_error = None;
+        }
+        if let Err(e) = store.remove_file_symbols(uri.as_str()) {
             warn!("Failed to clear symbols for {}: {}", uri, e);
         }
+        if let Err(e) = store.replace_file_todos(uri.as_str(), &[]) {
+            warn!("Failed to clear task markers for {}: {}", uri, e);
+        }
+        if let Err(e) = store.replace_file_references(uri.as_str(), &[]) {
+            warn!("Failed to clear references for {}: {}", uri, e);
+        }
+        self.invalidate_reference_counts(uri);
+        self.popular_symbols.remove_file(uri);
+        self.module_graph.remove_file(uri);
+        self.index_generations.remove(uri);
+        self.package_files.remove(uri);
+
+        self.documents.remove(uri);
+        self.recently_accessed.pop(uri);
+    }
 
-        self.documents.remove(&uri);
-        self.recently_accessed.pop(&uri);
+    /// Move a file to a new URI (e.g., after the client renamed it on disk)
+    /// The document content is carried over and its symbols are re-indexed under the new URI
+    pub fn rename_file(&mut self, old_uri: &Url, new_uri: Url) -> Result<()> {
+        let old_uri = &self.uris.normalize(old_uri);
+        info!("Renaming file: {} -> {}", old_uri, new_uri);
+
+        let document = match self.documents.get(old_uri) {
+            Some(document) => document,
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Cannot rename file that is not in the workspace: {}",
+                    old_uri
+                ))
+            }
+        };
 
-        Ok(())
+        let text_document = TextDocumentItem {
+            uri: new_uri,
+            language_id: document.language_id().to_string(),
+            version: document.version(),
+            text: document.text().to_string(),
+        };
+
+        self.remove_file(old_uri.clone())?;
+        self.open_document(text_document)
     }
 
     pub fn get_document(&mut self, uri: &Url) -> Option<&mut Document> {
+        let uri = &self.uris.normalize(uri);
         if self.documents.contains_key(uri) {
             // Update access time
             self.recently_accessed.put(uri.clone(), ());
@@ -179,6 +783,7 @@ impl Workspace {
     }
 
     pub fn get_document_readonly(&self, uri: &Url) -> Option<&Document> {
+        let uri = &self.uris.normalize(uri);
         self.documents.get(uri)
     }
 
@@ -211,11 +816,13 @@ impl Workspace {
 
     /// Check if document is open
     pub fn is_document_open(&self, uri: &Url) -> bool {
+        let uri = &self.uris.normalize(uri);
         self.documents.contains_key(uri)
     }
 
     /// Force reparse of a document
     pub fn reparse_document(&mut self, uri: &Url) -> Result<()> {
+        let uri = &self.uris.normalize(uri);
         if let Some(document) = self.documents.get_mut(uri) {
             document.reparse(&mut self.parser)?;
             self.recently_accessed.put(uri.clone(), ());
@@ -235,6 +842,7 @@ impl Workspace {
 
     /// Get diagnostics for a document (syntax only - for backward compatibility)
     pub fn get_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
         // First check if document exists
         if !self.documents.contains_key(uri) {
             warn!("Document not found for diagnostics: {}", uri);
@@ -285,9 +893,13 @@ impl Workspace {
 
     /// Extract and index symbols from a document
     fn extract_and_update_symbols_for_uri(&mut self, uri: &Url) -> Result<()> {
+        // Counts for modules this file stops importing change as well as those it imports now
+        self.invalidate_reference_counts(uri);
         // Clear existing symbols for this file first
-        if let Err(e) = self.symbol_index.clear_file_symbols(uri.as_str()) {
+        let store = self.store_for(uri);
+        if let Err(e) = store.remove_file_symbols(uri.as_str()) {
             warn!("Failed to clear symbols for {}: {}", uri, e);
+            self.symbol_index_error = Some(e.to_string());
         }
 
         // Get document content and parse tree in separate scopes to avoid borrowing conflicts
@@ -312,21 +924,30 @@ impl Workspace {
             return Ok(());
         }
 
+        // Generated modules only need what other modules can import from them
+        let signatures_only = self.is_signatures_only(uri);
+
         // Get the tree again in a separate borrow scope
         if let Some(document) = self.documents.get_mut(uri) {
             if let Ok(Some(tree)) = document.get_parse_tree(&mut self.parser) {
+                let header = ModuleHeader::from_tree(tree, &source);
+
                 // Extract symbols
                 match self.symbol_extractor.extract_symbols(tree, &source, uri) {
-                    Ok(symbols) => {
+                    Ok(mut symbols) => {
+                        if signatures_only {
+                            symbols.retain(|symbol| is_exported(&header, symbol));
+                        }
                         info!("Extracted {} symbols from {}", symbols.len(), uri);
 
                         // Index each symbol
                         for symbol in symbols {
-                            if let Err(e) = self.symbol_index.index_symbol(&symbol) {
+                            if let Err(e) = store.add_symbol(&symbol) {
                                 warn!(
                                     "Failed to index symbol '{}' from {}: {}",
                                     symbol.name, uri, e
                                 );
+                                self.symbol_index_error = Some(e.to_string());
                             }
                         }
                     }
@@ -334,15 +955,86 @@ impl Workspace {
                         warn!("Failed to extract symbols from {}: {}", uri, e);
                     }
                 }
+
+                // Comments explaining a declaration are searched along with its docs
+                if !signatures_only {
+                    let comments = declaration_comments(tree, &source);
+                    if let Err(e) = store.index_comments(uri.as_str(), &comments) {
+                        warn!("Failed to index comments from {}: {}", uri, e);
+                        self.symbol_index_error = Some(e.to_string());
+                    }
+                }
+
+                // A scratch buffer is a module of its own, nothing can import it
+                if DocumentKind::of(uri) == DocumentKind::File {
+                    self.module_graph.update_file(uri.clone(), header);
+                }
+
+                // Task markers are in comments throughout the bodies
+                let todos = if signatures_only {
+                    Vec::new()
+                } else {
+                    extract_todos(tree, &source, uri)
+                };
+                if let Err(e) = store.replace_file_todos(uri.as_str(), &todos) {
+                    warn!("Failed to index task markers from {}: {}", uri, e);
+                    self.symbol_index_error = Some(e.to_string());
+                }
+
+                // Resolved so a rename only touches what refers to the renamed definition
+                let references = if signatures_only {
+                    Vec::new()
+                } else {
+                    extract_references(tree, &source, uri)
+                };
+                if let Err(e) = store.replace_file_references(uri.as_str(), &references) {
+                    warn!("Failed to index references from {}: {}", uri, e);
+                    self.symbol_index_error = Some(e.to_string());
+                }
+                self.popular_symbols.replace_file(uri, &references);
+                self.index_generations.record_indexed(uri);
+                self.index_generations
+                    .set_signatures_only(uri, signatures_only);
+            }
+            // The tree is parsed again if a request needs it
+            if signatures_only {
+                document.drop_parse_tree();
             }
         }
+        self.invalidate_reference_counts(uri);
 
         Ok(())
     }
 
+    /// Every task marker (TODO, FIXME, XXX) recorded in the index
+    pub fn all_todos(&self) -> Result<Vec<TodoItem>> {
+        let _span = debug_span!("index.query", kind = "todos").entered();
+        self.symbol_store.all_todos()
+    }
+
     /// Search for symbols by name (fuzzy matching for workspace symbol search)
     pub fn find_symbols(&self, name: &str) -> Result<Vec<crate::Symbol>> {
-        match self.symbol_index.find_symbol(name) {
+        let _span = debug_span!("index.query", kind = "fuzzy", query = name).entered();
+        match self.symbol_store.find_symbols(name) {
+            Ok(symbols) => Ok(symbols),
+            Err(e) => {
+                warn!("Failed to search symbols for '{}': {}", name, e);
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Search for symbols by name, returning the page of at most `limit` results after the
+    /// first `offset`
+    pub fn find_symbols_limited(
+        &self,
+        name: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<crate::Symbol>> {
+        let _span =
+            debug_span!("index.query", kind = "fuzzy", query = name, offset, limit).entered();
+        match self.symbol_store.find_symbols_limited(name, offset, limit) {
             Ok(symbols) => Ok(symbols),
             Err(e) => {
                 warn!("Failed to search symbols for '{}': {}", name, e);
@@ -351,9 +1043,78 @@ impl Workspace {
         }
     }
 
+    /// Search for symbols matching a `workspace/symbol` query, returning the page of the
+    /// store's next `limit` candidates after the first `offset`. Candidates from files
+    /// outside the query's modules or file glob are dropped, so a page can come back short
+    /// while later pages still have matches.
+    pub fn find_symbols_matching(
+        &self,
+        query: &SymbolQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<crate::Symbol>> {
+        if query.is_plain() {
+            return self.find_symbols_limited(&query.name, offset, limit);
+        }
+
+        let name = query.name.as_str();
+        let _span = debug_span!(
+            "index.query",
+            kind = "filtered",
+            query = name,
+            offset,
+            limit
+        )
+        .entered();
+        match self
+            .symbol_store
+            .find_symbols_filtered(query, offset, limit)
+        {
+            // The store only matched paths; keep the files that declare a filtered module
+            // and whose path relative to the root matches the file glob
+            Ok(symbols) => Ok(symbols
+                .into_iter()
+                .filter(|symbol| {
+                    let uri = &symbol.location.uri;
+                    let in_modules = query.modules.as_ref().map_or(true, |modules| {
+                        self.module_name(uri)
+                            .map_or(true, |module_name| modules.matches(&module_name))
+                    });
+                    let in_files = query
+                        .files
+                        .as_ref()
+                        .map_or(true, |files| self.file_matches(files, uri));
+                    in_modules && in_files
+                })
+                .collect()),
+            Err(e) => {
+                warn!("Failed to search symbols for '{}': {}", query.name, e);
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Whether a file matches a glob relative to its workspace folder, or some trailing
+    /// part of its path does when it isn't below any folder
+    fn file_matches(&self, files: &FileFilter, uri: &Url) -> bool {
+        let path = match uri_to_path(uri) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        let relative = self
+            .config_folder(uri)
+            .and_then(|root| uri_to_path(root).ok())
+            .and_then(|root| path.strip_prefix(root).ok().map(Path::to_path_buf));
+        match relative {
+            Some(relative) => files.matches(&relative.to_string_lossy().replace('\\', "/")),
+            None => files.matches_tail(&path.to_string_lossy().replace('\\', "/")),
+        }
+    }
+
     /// Search for symbols by exact name match (for rename operations)
     pub fn find_exact_symbols(&self, name: &str) -> Result<Vec<crate::Symbol>> {
-        match self.symbol_index.find_exact_symbol(name) {
+        let _span = debug_span!("index.query", kind = "exact", query = name).entered();
+        match self.symbol_store.find_exact_symbols(name) {
             Ok(symbols) => Ok(symbols),
             Err(e) => {
                 warn!("Failed to search exact symbols for '{}': {}", name, e);
@@ -362,11 +1123,60 @@ impl Workspace {
         }
     }
 
+    /// Search symbol documentation with a natural-language query, best matches first
+    pub fn search_documentation(&self, query: &str, limit: usize) -> Result<Vec<crate::Symbol>> {
+        let _span = debug_span!("index.query", kind = "docs", query, limit).entered();
+        match self.symbol_store.search_documentation(query, limit) {
+            Ok(symbols) => Ok(symbols),
+            Err(e) => {
+                warn!("Failed to search documentation for '{}': {}", query, e);
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Every indexed symbol documented as `@deprecated`
+    pub fn deprecated_symbols(&self) -> Result<Vec<crate::Symbol>> {
+        let _span = debug_span!("index.query", kind = "deprecated").entered();
+        self.symbol_store.deprecated_symbols()
+    }
+
+    /// Every indexed function and value whose signature mentions the type `type_name`
+    pub fn symbols_using_type(&self, type_name: &str) -> Result<Vec<crate::Symbol>> {
+        let _span = debug_span!("index.query", kind = "type_usage").entered();
+        self.symbol_store.symbols_using_type(type_name)
+    }
+
+    /// Every indexed occurrence of `module`'s top-level `name`, its definition included,
+    /// ordered by file and position
+    pub fn references_to(&self, module: &str, name: &str) -> Result<Vec<SymbolReference>> {
+        let _span = debug_span!("index.query", kind = "references", query = name).entered();
+        self.symbol_store.find_references(module, name)
+    }
+
+    /// The indexed references in the definition of the top-level declaration `container`
+    /// of a file, ordered by position
+    pub fn references_from(&self, uri: &Url, container: &str) -> Result<Vec<SymbolReference>> {
+        let uri = &self.uris.normalize(uri);
+        let _span =
+            debug_span!("index.query", kind = "references_from", query = container).entered();
+        self.symbol_store
+            .find_references_from(uri.as_str(), container)
+    }
+
+    /// Size of the symbol index, and how much converting it to the compact layout saved
+    pub fn index_stats(&self) -> Result<IndexStats> {
+        let _span = debug_span!("index.query", kind = "stats").entered();
+        self.symbol_store.stats()
+    }
+
     /// Get all symbols for a specific file
     pub fn get_file_symbols(&self, uri: &Url) -> Result<Vec<crate::Symbol>> {
+        let uri = &self.uris.normalize(uri);
+        let _span = debug_span!("index.query", kind = "file", uri_hash = %uri_hash(uri)).entered();
         // For now, we'll do a full search and filter
         // TODO: Add a more efficient method to the symbol index
-        match self.symbol_index.find_symbol("") {
+        match self.store_for(uri).find_symbols("") {
             Ok(all_symbols) => {
                 let file_symbols = all_symbols
                     .into_iter()
@@ -381,6 +1191,14 @@ impl Workspace {
         }
     }
 
+    /// The store a file's symbols are indexed in
+    fn store_for(&self, uri: &Url) -> Arc<dyn SymbolStore> {
+        match DocumentKind::of(uri) {
+            DocumentKind::Scratch => self.scratch_store.clone(),
+            _ => self.symbol_store.clone(),
+        }
+    }
+
     /// Force re-indexing of all open documents
     pub fn reindex_all_symbols(&mut self) -> Result<()> {
         info!("Re-indexing symbols for all open documents");
@@ -434,6 +1252,7 @@ impl Workspace {
 
     /// Export parse tree for a specific document
     pub fn export_parse_tree_for_document(&self, uri: &Url, export_dir: &Path) -> Result<()> {
+        let uri = &self.uris.normalize(uri);
         if let Some(document) = self.documents.get(uri) {
             // Create the export directory if it doesn't exist
             std::fs::create_dir_all(export_dir)?;
@@ -449,39 +1268,198 @@ impl Workspace {
 
     /// Compile a document using the Gren compiler
     /// Prefers in-memory content for real-time diagnostics, falls back to disk file
+    #[instrument(name = "compile", skip_all, fields(uri_hash = %uri_hash(uri)))]
     pub async fn compile_document(
         &mut self,
         uri: &Url,
     ) -> Result<crate::compiler::CompilationResult> {
-        if let Some(ref mut compiler) = self.compiler {
-            if let Ok(path) = uri_to_path(uri) {
-                // Prefer in-memory content if document is open in the workspace
-                // This provides real-time diagnostics for unsaved changes
-                if let Some(document) = self.documents.get(uri) {
-                    info!(
-                        "💭 Compiling in-memory content for real-time diagnostics: {}",
-                        path.display()
-                    );
-                    return compiler.compile_content(document.text(), &path).await;
-                } else if path.exists() {
-                    // Fall back to disk file if not in workspace
-                    info!("🔨 Compiling disk file: {}", path.display());
-                    return compiler.compile_file(&path).await;
-                } else {
-                    info!("⚠️  No document or file found for: {}", path.display());
-                    // Return empty result - no content available
-                    return Ok(crate::compiler::CompilationResult {
-                        success: true,
-                        diagnostics: Vec::new(),
-                        global_errors: Vec::new(),
-                        timestamp: std::time::SystemTime::now(),
-                        content_hash: 0,
-                    });
-                }
+        let uri = &self.uris.normalize(uri);
+        let compiler = self
+            .start_compiler_for(uri)
+            .and_then(|key| self.compilers.get_mut(&key));
+        let (compiler, path) = match (compiler, document_path(uri)) {
+            (Some(compiler), Ok(path)) => (compiler, path),
+            _ => anyhow::bail!("Compiler not available or invalid URI"),
+        };
+
+        let started = std::time::Instant::now();
+        // Prefer in-memory content if document is open in the workspace
+        // This provides real-time diagnostics for unsaved changes
+        let result = if let Some(document) = self.documents.get(uri) {
+            info!(
+                "💭 Compiling in-memory content for real-time diagnostics: {}",
+                path.display()
+            );
+            compiler.compile_content(document.text(), &path).await?
+        } else if path.exists() {
+            // Fall back to disk file if not in workspace
+            info!("🔨 Compiling disk file: {}", path.display());
+            compiler.compile_file(&path).await?
+        } else {
+            info!("⚠️  No document or file found for: {}", path.display());
+            // Return empty result - no content available
+            return Ok(crate::compiler::CompilationResult {
+                success: true,
+                diagnostics: Vec::new(),
+                global_errors: Vec::new(),
+                timestamp: std::time::SystemTime::now(),
+                content_hash: 0,
+                modules_compiled: None,
+                from_cache: false,
+            });
+        };
+
+        self.record_compile(uri, &result, started.elapsed());
+        Ok(result)
+    }
+
+    /// Type check the declaration at zero-based `line` of an open document, on its own when
+    /// possible. Unlike `compile_document` this isn't a compile of the module, so it's kept
+    /// out of the compile history.
+    pub async fn check_declaration(&mut self, uri: &Url, line: u32) -> Result<DeclarationCheck> {
+        let uri = &self.uris.normalize(uri);
+        let compiler = self
+            .start_compiler_for(uri)
+            .and_then(|key| self.compilers.get_mut(&key));
+        let (compiler, path) = match (compiler, document_path(uri)) {
+            (Some(compiler), Ok(path)) => (compiler, path),
+            _ => anyhow::bail!("Compiler not available or invalid URI"),
+        };
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => anyhow::bail!("Document not open: {}", uri),
+        };
+
+        compiler
+            .check_declaration(document.text(), &path, line)
+            .await
+    }
+
+    /// The type the compiler infers for the top-level value `name` of an open document, or
+    /// `None` when the module doesn't compile far enough to tell. Like `check_declaration`
+    /// it's kept out of the compile history.
+    pub async fn inferred_type(&mut self, uri: &Url, name: &str) -> Result<Option<String>> {
+        let uri = &self.uris.normalize(uri);
+        let compiler = self
+            .start_compiler_for(uri)
+            .and_then(|key| self.compilers.get_mut(&key));
+        let (compiler, path) = match (compiler, document_path(uri)) {
+            (Some(compiler), Ok(path)) => (compiler, path),
+            _ => anyhow::bail!("Compiler not available or invalid URI"),
+        };
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => anyhow::bail!("Document not open: {}", uri),
+        };
+
+        let (probed, probe) = type_probe(document.text(), name);
+        let result = compiler.compile_content(&probed, &path).await?;
+        Ok(probed_type(&result, &probe))
+    }
+
+    /// Types inferred from an open document's syntax tree for the names it binds without
+    /// an annotation: `let` bindings, parameters, pattern variables and top-level values.
+    /// Imported names take their types from the index.
+    pub fn module_types(&self, uri: &Url) -> Option<ModuleTypes> {
+        let uri = &self.uris.normalize(uri);
+        let source = self.documents.get(uri)?.text();
+        let mut parser = Parser::new().ok()?;
+        let tree = parser.parse(source).ok()??;
+        Some(infer_types(
+            &tree,
+            source,
+            &IndexedTypes { workspace: self },
+        ))
+    }
+
+    /// Add a compile to the history and queue it for the client
+    fn record_compile(
+        &mut self,
+        uri: &Url,
+        result: &crate::compiler::CompilationResult,
+        duration: std::time::Duration,
+    ) {
+        let module_name = self.module_name(uri);
+        let build_order = module_name
+            .as_deref()
+            .map(|module_name| self.module_graph.build_order(module_name))
+            .unwrap_or_default();
+        let report = CompileReport::new(uri.clone(), module_name, result, duration, build_order);
+
+        if self.compile_history.len() == COMPILE_HISTORY_SIZE {
+            self.compile_history.pop_front();
+        }
+        self.compile_history.push_back(report.clone());
+        self.unsent_compile_reports.push(report);
+    }
+
+    /// The most recent compiles, oldest first
+    pub fn compile_history(&self) -> Vec<CompileReport> {
+        self.compile_history.iter().cloned().collect()
+    }
+
+    /// Compiles since the last call, for sending to the client
+    pub fn take_compile_reports(&mut self) -> Vec<CompileReport> {
+        std::mem::take(&mut self.unsent_compile_reports)
+    }
+
+    /// Record a value a running program logged with `Debug.log tag`
+    pub fn record_runtime_log(&mut self, tag: &str, value: String) {
+        self.runtime_log.record(tag, value);
+    }
+
+    /// Values logged under a `Debug.log` tag, most recent first
+    pub fn runtime_log_values(&self, tag: &str) -> Vec<&str> {
+        self.runtime_log.recent(tag)
+    }
+
+    /// Current content of a file: the document's text when it's held in the workspace,
+    /// otherwise what's on disk
+    pub fn file_contents(&self, uri: &Url) -> Option<String> {
+        if let Some(document) = self.get_document_readonly(uri) {
+            return Some(document.text().to_string());
+        }
+
+        let path = uri_to_path(uri).ok()?;
+        match read_source(&path) {
+            Ok(content) => Some(content),
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.display(), e);
+                None
             }
         }
+    }
+
+    /// The line ending a file uses, which the server's edits to it are written with
+    pub fn line_ending(&self, uri: &Url) -> &'static str {
+        match self.get_document_readonly(uri) {
+            Some(document) => line_ending(document.text()),
+            None => self
+                .file_contents(uri)
+                .map(|text| line_ending(&text))
+                .unwrap_or("\n"),
+        }
+    }
+
+    /// Record an edit the server applied, given the content of the files it changed from
+    /// before it was applied
+    pub fn record_server_edit(
+        &mut self,
+        label: &str,
+        edit: &WorkspaceEdit,
+        before: &HashMap<Url, String>,
+    ) -> Result<()> {
+        self.edit_journal.record(label, edit, before)
+    }
 
-        anyhow::bail!("Compiler not available or invalid URI")
+    /// The most recent edit the server applied that hasn't been undone
+    pub fn last_server_edit(&self) -> Option<&JournalEntry> {
+        self.edit_journal.last()
+    }
+
+    /// Drop the most recent server edit from the journal once it has been undone
+    pub fn pop_server_edit(&mut self) -> Option<JournalEntry> {
+        self.edit_journal.pop()
     }
 
     /// Get compiler diagnostics for all open documents
@@ -490,7 +1468,7 @@ impl Workspace {
     ) -> HashMap<Url, Vec<crate::compiler::CompilerDiagnostic>> {
         let mut diagnostics = HashMap::new();
 
-        if self.compiler.is_none() {
+        if self.compilers.is_empty() {
             return diagnostics;
         }
 
@@ -508,25 +1486,236 @@ impl Workspace {
         diagnostics
     }
 
-    /// Check if compiler is available
-    pub fn has_compiler(&self) -> bool {
-        self.compiler.as_ref().map_or(false, |c| c.is_available())
+    /// The compiler's formatter, when there's a compiler
+    pub fn formatter(&self) -> Option<Formatter> {
+        self.primary_compiler().map(|compiler| compiler.formatter())
     }
 
-    /// Invalidate compiler cache when project configuration changes
-    pub fn invalidate_compiler_cache(&mut self) {
-        if let Some(ref mut compiler) = self.compiler {
-            compiler.invalidate_all_cache();
-            info!("🔄 Invalidated all compiler cache due to project changes");
+    /// Record the result of a formatting check of `checked`: the files in `unformatted` get
+    /// a diagnostic, the other checked files lose theirs. Returns the files whose
+    /// diagnostics changed.
+    pub fn set_unformatted(&mut self, checked: &[Url], unformatted: &[Url]) -> Vec<Url> {
+        let unformatted: HashSet<Url> = unformatted
+            .iter()
+            .map(|uri| self.uris.normalize(uri))
+            .collect();
+        let mut changed = Vec::new();
+        for uri in checked {
+            let uri = self.uris.normalize(uri);
+            let was_unformatted = self.unformatted.remove(&uri);
+            if unformatted.contains(&uri) {
+                self.unformatted.insert(uri.clone());
+                if !was_unformatted {
+                    changed.push(uri);
+                }
+            } else if was_unformatted {
+                changed.push(uri);
+            }
         }
+        changed
     }
 
-    /// Force refresh diagnostics for a specific document
-    pub async fn force_refresh_diagnostics(&mut self, uri: &Url) -> Result<Vec<Diagnostic>> {
-        let (diagnostics, _global_errors) = self
-            .force_refresh_diagnostics_with_global_errors(uri)
-            .await?;
-        Ok(diagnostics)
+    /// Remember what was last published for `uri`, so the diagnostics can be cleared when
+    /// the file goes away and pull requests for the same document version answered
+    pub fn record_published_diagnostics(&mut self, uri: &Url, diagnostics: &[Diagnostic]) {
+        if diagnostics.is_empty() {
+            self.published_diagnostics.remove(uri);
+        } else {
+            self.published_diagnostics.insert(uri.clone());
+        }
+        let version = self.documents.get(uri).map(Document::version);
+        self.diagnostics_cache.store(uri, version, diagnostics);
+    }
+
+    /// The diagnostics last recorded for `uri`, if they're for the document's current
+    /// version
+    pub fn cached_diagnostics(&self, uri: &Url) -> Option<&CachedDiagnostics> {
+        let uri = &self.uris.normalize(uri);
+        let version = self.documents.get(uri).map(Document::version);
+        self.diagnostics_cache.get(uri, version)
+    }
+
+    /// The diagnostics last recorded for each document
+    pub fn diagnostics_cache(&self) -> &DiagnosticsCache {
+        &self.diagnostics_cache
+    }
+
+    /// Have diagnostics pulled by the client instead of pushed to it
+    pub fn set_pull_diagnostics(&mut self, pull: bool) {
+        self.pull_diagnostics = pull;
+    }
+
+    /// Whether diagnostics are pushed to the client with `textDocument/publishDiagnostics`
+    pub fn pushes_diagnostics(&self) -> bool {
+        !self.pull_diagnostics
+    }
+
+    /// Forget the diagnostics published for `uri`, or for the files in it when it's a
+    /// folder, returning the files that had some. Editors keep showing diagnostics of
+    /// renamed and deleted files until they're replaced with empty ones.
+    pub fn forget_published_diagnostics(&mut self, uri: &Url) -> Vec<Url> {
+        let targets = [uri.clone(), self.uris.normalize(uri)];
+        let folders: Vec<String> = targets
+            .iter()
+            .map(|target| format!("{}/", target.as_str().trim_end_matches('/')))
+            .collect();
+        let stale: Vec<Url> = self
+            .published_diagnostics
+            .iter()
+            .filter(|published| {
+                targets.contains(published)
+                    || folders
+                        .iter()
+                        .any(|folder| published.as_str().starts_with(folder.as_str()))
+            })
+            .cloned()
+            .collect();
+        for published in &stale {
+            self.published_diagnostics.remove(published);
+        }
+        let cached: Vec<Url> = self
+            .diagnostics_cache
+            .entries()
+            .map(|(cached, _)| cached)
+            .filter(|cached| {
+                targets.contains(cached)
+                    || folders
+                        .iter()
+                        .any(|folder| cached.as_str().starts_with(folder.as_str()))
+            })
+            .cloned()
+            .collect();
+        for cached in &cached {
+            self.diagnostics_cache.remove(cached);
+        }
+        stale
+    }
+
+    /// Check if compiler is available
+    pub fn has_compiler(&self) -> bool {
+        self.primary_compiler().map_or(false, |c| c.is_available())
+    }
+
+    /// Packages the `gren.json` of the project `uri` belongs to lists as direct
+    /// dependencies, empty when it's in no project or the manifest can't be read
+    pub fn direct_dependencies(&self, uri: &Url) -> Vec<String> {
+        let manifest = self
+            .project_root(uri)
+            .and_then(|root| std::fs::read_to_string(root.join("gren.json")).ok());
+        match manifest.map(|manifest| crate::manifest::direct_dependencies(&manifest)) {
+            Some(Ok(dependencies)) => dependencies,
+            Some(Err(e)) => {
+                warn!("Failed to read the project's dependencies: {}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Dependency packages of the workspace's projects installed in the package cache of
+    /// each project's compiler, each once. Projects whose manifest can't be read add none.
+    pub fn dependency_packages(&self) -> Vec<PackageSource> {
+        let mut packages: Vec<PackageSource> = Vec::new();
+        for project in self.projects.projects() {
+            let options = match Url::from_directory_path(&project.root) {
+                Ok(project_uri) => self.compiler_options_for(&project_uri),
+                Err(_) => self.compiler_options(),
+            };
+            let cache_root = match package_resolver::cache_root(&options.env) {
+                Some(cache_root) => cache_root,
+                None => continue,
+            };
+            let manifest = match std::fs::read_to_string(project.root.join("gren.json")) {
+                Ok(manifest) => manifest,
+                Err(_) => continue,
+            };
+            match package_resolver::resolve_packages(&manifest, &cache_root) {
+                Ok(resolved) => {
+                    for package in resolved {
+                        if !packages.iter().any(|known| known.root == package.root) {
+                            packages.push(package);
+                        }
+                    }
+                }
+                Err(e) => warn!(
+                    "Failed to resolve the packages of {}: {}",
+                    project.root.display(),
+                    e
+                ),
+            }
+        }
+        packages
+    }
+
+    /// Path of the compiler used for diagnostics, if one is available
+    pub fn compiler_path(&self) -> Option<&Path> {
+        self.primary_compiler()
+            .filter(|compiler| compiler.is_available())
+            .map(|compiler| compiler.gren_path())
+    }
+
+    /// Why compiler diagnostics aren't available, or `None` when they are
+    pub fn compiler_unavailable_reason(&self) -> Option<String> {
+        if self.has_compiler() {
+            return None;
+        }
+
+        match (&self.root_uri, &self.compiler_error) {
+            (None, _) => Some("No workspace folder is open".to_string()),
+            (Some(_), Some(error)) => Some(error.clone()),
+            (Some(_), None) => Some("Gren compiler not available".to_string()),
+        }
+    }
+
+    /// Whether the symbol store rejects writes
+    pub fn is_symbol_index_read_only(&self) -> bool {
+        self.symbol_store.is_read_only()
+    }
+
+    /// Error from the latest failed write to the symbol store, until a file indexes cleanly
+    pub fn symbol_index_error(&self) -> Option<&str> {
+        self.symbol_index_error.as_deref()
+    }
+
+    /// Mark files an edit is about to change as waiting to be re-indexed, until the
+    /// client sends their new content
+    pub fn mark_index_pending(&mut self, uris: &[Url]) {
+        let now = Instant::now();
+        for uri in uris {
+            let uri = self.uris.normalize(uri);
+            self.index_generations.mark_pending(uri, now);
+        }
+    }
+
+    /// When each file was last indexed, and which ones are waiting to be re-indexed
+    pub fn index_generations(&self) -> &IndexGenerations {
+        &self.index_generations
+    }
+
+    /// The files waiting to be re-indexed, if they changed since the last call
+    pub fn take_index_status_change(&mut self) -> Option<Vec<Url>> {
+        self.index_generations.expire(Instant::now());
+        self.index_generations
+            .take_changed()
+            .then(|| self.index_generations.pending_files())
+    }
+
+    /// Invalidate compiler cache when project configuration changes
+    pub fn invalidate_compiler_cache(&mut self) {
+        for compiler in self.compilers.values_mut() {
+            compiler.invalidate_all_cache();
+        }
+        if !self.compilers.is_empty() {
+            info!("🔄 Invalidated all compiler cache due to project changes");
+        }
+    }
+
+    /// Force refresh diagnostics for a specific document
+    pub async fn force_refresh_diagnostics(&mut self, uri: &Url) -> Result<Vec<Diagnostic>> {
+        let (diagnostics, _global_errors) = self
+            .force_refresh_diagnostics_with_global_errors(uri)
+            .await?;
+        Ok(diagnostics)
     }
 
     /// Force refresh diagnostics and global errors for a specific document
@@ -537,10 +1726,11 @@ impl Workspace {
         info!("🔄 Force refreshing diagnostics for {}", uri);
 
         // Invalidate compiler cache for this file
-        if let Some(ref mut compiler) = self.compiler {
-            if let Ok(path) = uri_to_path(uri) {
-                compiler.invalidate_cache(&path);
-            }
+        let compiler = self
+            .compiler_key(uri)
+            .and_then(|key| self.compilers.get_mut(&key));
+        if let (Some(compiler), Ok(path)) = (compiler, uri_to_path(uri)) {
+            compiler.invalidate_cache(&path);
         }
 
         // Get fresh diagnostics and global errors
@@ -573,12 +1763,34 @@ impl Workspace {
         &mut self,
         uri: &Url,
     ) -> Result<(Vec<Diagnostic>, Vec<crate::compiler::GlobalError>)> {
-        // Use only compiler diagnostics - they provide comprehensive and accurate error messages
+        let uri = &self.uris.normalize(uri);
+        if let Some(compile) = self.take_document_compile(uri) {
+            let compiled = compile.run().await;
+            return Ok(self.finish_document_compile(compiled));
+        }
+        // Problems in a dependency's sources aren't the project's to fix
+        if self.is_package_file(uri) {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        // Annotation hints, shadowing errors and lints come straight from the syntax tree
+        // and don't need the compiler
+        let syntax_hints = self.get_lint_diagnostics(uri);
+
+        // Use compiler diagnostics for errors - they provide comprehensive and accurate messages
         if self.has_compiler() {
             match self.compile_document(uri).await {
                 Ok(result) => {
-                    let compiler_diagnostics =
-                        compiler_diagnostics_to_lsp(&result.diagnostics, uri);
+                    // The compiler reports a scratch buffer's errors under its stand-in path
+                    let reported_uri = match DocumentKind::of(uri) {
+                        DocumentKind::Scratch => {
+                            Url::from_file_path(scratch_path(uri)).unwrap_or_else(|_| uri.clone())
+                        }
+                        _ => uri.clone(),
+                    };
+                    let mut compiler_diagnostics =
+                        compiler_diagnostics_to_lsp(&result.diagnostics, &reported_uri);
+                    compiler_diagnostics.extend(syntax_hints);
                     return Ok((compiler_diagnostics, result.global_errors));
                 }
                 Err(e) => {
@@ -588,9 +1800,639 @@ impl Workspace {
             }
         }
 
-        // If no compiler is available, return empty diagnostics and global errors
-        // Tree-sitter is used only for symbol navigation, not error reporting
-        Ok((Vec::new(), Vec::new()))
+        // If no compiler is available, only the syntax-based hints are reported
+        // Tree-sitter is otherwise used only for symbol navigation, not error reporting
+        Ok((syntax_hints, Vec::new()))
+    }
+
+    /// The compile of an open document that `get_document_diagnostics_with_global_errors`
+    /// would run, taken out of the workspace so the compiler can run while the workspace
+    /// lock isn't held. `None` when the document isn't open or has no compiler to run.
+    pub fn take_document_compile(&mut self, uri: &Url) -> Option<DocumentCompile> {
+        let uri = self.uris.normalize(uri);
+        if self.is_package_file(&uri) || !self.documents.contains_key(&uri) {
+            return None;
+        }
+        let key = self.start_compiler_for(&uri)?;
+        let compiler = self.compilers.get(&key)?.detached();
+        let path = document_path(&uri).ok()?;
+        let content = self.documents.get(&uri)?.text().to_string();
+        Some(DocumentCompile {
+            uri,
+            path,
+            content,
+            compiler,
+        })
+    }
+
+    /// Diagnostics and global errors of a document from a compile taken with
+    /// `take_document_compile`, together with its syntax hints. A failed compile leaves only
+    /// the syntax hints.
+    pub fn finish_document_compile(
+        &mut self,
+        compiled: CompiledDocument,
+    ) -> (Vec<Diagnostic>, Vec<crate::compiler::GlobalError>) {
+        let uri = &compiled.uri;
+        let syntax_hints = self.get_lint_diagnostics(uri);
+        match compiled.result {
+            Ok(result) => {
+                self.record_compile(uri, &result, compiled.duration);
+                // The compiler reports a scratch buffer's errors under its stand-in path
+                let reported_uri = match DocumentKind::of(uri) {
+                    DocumentKind::Scratch => {
+                        Url::from_file_path(scratch_path(uri)).unwrap_or_else(|_| uri.clone())
+                    }
+                    _ => uri.clone(),
+                };
+                let mut diagnostics =
+                    compiler_diagnostics_to_lsp(&result.diagnostics, &reported_uri);
+                diagnostics.extend(syntax_hints);
+                (diagnostics, result.global_errors)
+            }
+            Err(e) => {
+                warn!("Compilation failed for {}: {}", uri, e);
+                (syntax_hints, Vec::new())
+            }
+        }
+    }
+
+    /// Every diagnostic found from the syntax tree alone: annotation hints, shadowing and
+    /// recursive alias errors, updates of fields records don't have, and the lints. `gren-lsp check` reports exactly these.
+    pub fn get_lint_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        // Generated modules indexed from their signatures aren't linted either, nor are
+        // dependency packages
+        if self.is_excluded(uri) || self.is_signatures_only(uri) || self.is_package_file(uri) {
+            return Vec::new();
+        }
+        let mut diagnostics = self.get_annotation_diagnostics(uri);
+        diagnostics.extend(self.get_shadowing_diagnostics(uri));
+        diagnostics.extend(self.get_alias_cycle_diagnostics(uri));
+        diagnostics.extend(self.get_record_update_diagnostics(uri));
+        diagnostics.extend(self.get_unused_import_diagnostics(uri));
+        diagnostics.extend(self.get_unused_binding_diagnostics(uri));
+        diagnostics.extend(self.get_unused_exposed_diagnostics(uri));
+        diagnostics.extend(self.get_missing_annotation_diagnostics(uri));
+        diagnostics.extend(self.get_import_style_diagnostics(uri));
+        diagnostics.extend(self.get_import_conflict_diagnostics(uri));
+        diagnostics.extend(self.get_metric_diagnostics(uri));
+        if self.unformatted.contains(uri) {
+            let source = self.file_contents(uri).unwrap_or_default();
+            diagnostics.push(unformatted_diagnostic(&source));
+        }
+        self.folder_config(uri).apply_severities(&mut diagnostics);
+        diagnostics
+    }
+
+    /// Get diagnostics for imports nothing in the document uses
+    pub fn get_unused_import_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let profile = self.lint_profile;
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => unused_import_diagnostics(tree, &source, profile),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Get diagnostics for `let` bindings nothing uses
+    pub fn get_unused_binding_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let profile = self.lint_profile;
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => unused_binding_diagnostics(tree, &source, profile),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Get diagnostics for exposed values no other indexed file uses. `main` is used by
+    /// the runtime, and the modules a package lists in `exposed-modules` are its API, so
+    /// neither is reported.
+    pub fn get_unused_exposed_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let profile = self.lint_profile;
+        let header = match self.module_graph.header(uri) {
+            Some(header) => header,
+            None => return Vec::new(),
+        };
+        let (module, exposed_values) = match (&header.name, &header.exposed_values) {
+            (Some(module), Some(values)) => (module.clone(), values.clone()),
+            _ => return Vec::new(),
+        };
+        if self.is_package_api(uri, &module) {
+            return Vec::new();
+        }
+
+        let unused: HashSet<String> = exposed_values
+            .into_iter()
+            .filter(|value| value != "main")
+            .filter(|value| match self.references_to(&module, value) {
+                Ok(references) => references
+                    .iter()
+                    .all(|reference| reference.location.uri == *uri),
+                Err(_) => false,
+            })
+            .collect();
+        if unused.is_empty() {
+            return Vec::new();
+        }
+
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+        let source = document.text().to_string();
+        match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => unused_exposed_diagnostics(tree, &source, profile, &unused),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Whether `module` is listed in the `exposed-modules` of the package `uri` is in
+    fn is_package_api(&self, uri: &Url, module: &str) -> bool {
+        let root = match self.project_root(uri) {
+            Some(root) => root,
+            None => return false,
+        };
+        std::fs::read_to_string(root.join("gren.json"))
+            .map(|manifest| !exposed_module_entries(&manifest, module).is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Get diagnostics for exposed values without a type annotation
+    pub fn get_missing_annotation_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let profile = self.lint_profile;
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => missing_annotation_diagnostics(tree, &source, profile),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Get hints for type annotations whose arity doesn't match their definition
+    pub fn get_annotation_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => annotation_arity_diagnostics(tree, &source, uri),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Get errors for type aliases that refer back to themselves
+    pub fn get_alias_cycle_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => alias_cycles(tree, &source)
+                .iter()
+                .map(|cycle| cycle.diagnostic(uri))
+                .collect(),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Get errors for parameters and `let` bindings shadowing a top-level or imported name
+    pub fn get_shadowing_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        let tree = match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => tree.clone(),
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                return Vec::new();
+            }
+        };
+
+        let bindings = shadowed_bindings(&tree, &source, |module_name| {
+            self.exposed_values(module_name)
+        });
+        bindings
+            .iter()
+            .map(|binding| binding.diagnostic(uri))
+            .collect()
+    }
+
+    /// Get hints for record updates setting fields the record's alias doesn't have
+    pub fn get_record_update_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        let tree = match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => tree.clone(),
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                return Vec::new();
+            }
+        };
+
+        let mut diagnostics = Vec::new();
+        for update in record_updates(&tree, &source) {
+            let fields = update
+                .type_name
+                .as_deref()
+                .and_then(|type_name| self.record_alias_fields(uri, type_name));
+            if let Some(fields) = fields {
+                diagnostics.extend(update.unknown_field_diagnostics(&fields));
+            }
+        }
+        diagnostics
+    }
+
+    /// The fields of the record alias `type_name` as written in `uri`: the file's own alias
+    /// of that name, or the one an import brings in through its qualifier or exposing list.
+    /// `None` when the name doesn't resolve to exactly one alias.
+    pub fn record_alias_fields(&self, uri: &Url, type_name: &str) -> Option<Vec<String>> {
+        let uri = &self.uris.normalize(uri);
+        let (qualifier, name) = match type_name.rsplit_once('.') {
+            Some((qualifier, name)) => (Some(qualifier), name),
+            None => (None, type_name),
+        };
+        let aliases: Vec<crate::Symbol> = self
+            .find_exact_symbols(name)
+            .ok()?
+            .into_iter()
+            .filter(|symbol| {
+                symbol.kind == SymbolKind::CLASS
+                    && symbol
+                        .type_signature
+                        .as_deref()
+                        .is_some_and(|signature| signature.starts_with("alias "))
+            })
+            .collect();
+        let local = aliases
+            .iter()
+            .find(|symbol| qualifier.is_none() && symbol.location.uri == *uri);
+        let alias = match local {
+            Some(alias) => alias,
+            None => {
+                let source = self.file_contents(uri)?;
+                let tree = Parser::new().ok()?.parse(&source).ok()??;
+                let modules: Vec<String> = imports(&tree, &source)
+                    .into_iter()
+                    .filter(|import| match qualifier {
+                        Some(qualifier) => import.qualifier() == qualifier,
+                        None => import.exposes_all || import.exposed.iter().any(|n| n == name),
+                    })
+                    .map(|import| import.module_name)
+                    .collect();
+                let mut imported = aliases.iter().filter(|symbol| {
+                    let file = &symbol.location.uri;
+                    self.module_name(file)
+                        .is_some_and(|module| modules.contains(&module))
+                        && self
+                            .module_graph
+                            .header(file)
+                            .is_some_and(|header| header.exports.types.contains(name))
+                });
+                match (imported.next(), imported.next()) {
+                    (Some(alias), None) => alias,
+                    _ => return None,
+                }
+            }
+        };
+        alias_fields(alias.type_signature.as_deref()?)
+    }
+
+    /// Get the import style lint for a document, when the lint is enabled
+    pub fn get_import_style_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let style = match self.import_lint {
+            Some(style) => style,
+            None => return Vec::new(),
+        };
+
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        let tree = match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => tree.clone(),
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                return Vec::new();
+            }
+        };
+
+        organize_imports(&tree, &source, style, |module_name| {
+            self.import_group(module_name)
+        })
+        .and_then(|section| import_style_diagnostic(&section, style))
+        .into_iter()
+        .collect()
+    }
+
+    /// Get warnings for imports that make references ambiguous
+    pub fn get_import_conflict_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+
+        let source = document.text().to_string();
+        let tree = match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => tree.clone(),
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                return Vec::new();
+            }
+        };
+
+        let imports = imports(&tree, &source);
+        let conflicts = import_conflicts(&imports, |module_name| self.module_exports(module_name));
+        import_conflict_diagnostics(&imports, &conflicts)
+    }
+
+    /// Get hints for functions over the metric thresholds, when metric hints are enabled
+    pub fn get_metric_diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let uri = &self.uris.normalize(uri);
+        match self.metric_hints {
+            Some(thresholds) => self
+                .measure_module(uri)
+                .map(|metrics| metric_diagnostics(&metrics, thresholds))
+                .unwrap_or_default(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Complexity metrics for a loaded module. Fan-in counts the indexed files that
+    /// import it.
+    pub fn module_metrics(&mut self, uri: &Url) -> Option<ModuleMetrics> {
+        let uri = &self.uris.normalize(uri);
+        let mut metrics = self.measure_module(uri)?;
+        metrics.fan_in = self.count_importers(uri, &metrics);
+        Some(metrics)
+    }
+
+    /// Metrics for every loaded module of the project, leaving out package sources
+    pub fn project_module_metrics(&mut self) -> Vec<(Url, ModuleMetrics)> {
+        let mut uris: Vec<Url> = self
+            .documents
+            .keys()
+            .filter(|uri| {
+                uri_to_path(uri)
+                    .map(|path| import_group_for_path(&path) == ImportGroup::Local)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        uris.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        uris.into_iter()
+            .filter_map(|uri| {
+                let mut metrics = self.measure_module(&uri)?;
+                metrics.fan_in = self.count_importers(&uri, &metrics);
+                Some((uri, metrics))
+            })
+            .collect()
+    }
+
+    /// Every indexed file of the project itself, leaving out dependencies' modules, sorted
+    /// by URI
+    pub fn project_module_files(&self) -> Vec<Url> {
+        let mut uris: Vec<Url> = self
+            .module_graph
+            .files()
+            .filter(|uri| {
+                uri_to_path(uri)
+                    .map(|path| import_group_for_path(&path) == ImportGroup::Local)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        uris.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        uris
+    }
+
+    /// Metrics that can be read off a module's own syntax tree
+    fn measure_module(&mut self, uri: &Url) -> Option<ModuleMetrics> {
+        let document = self.documents.get_mut(uri)?;
+        let source = document.text().to_string();
+        match document.get_parse_tree(&mut self.parser) {
+            Ok(Some(tree)) => Some(module_metrics(tree, &source)),
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Failed to parse document {}: {}", uri, e);
+                None
+            }
+        }
+    }
+
+    /// Number of other files importing the module `metrics` were taken from
+    fn count_importers(&self, uri: &Url, metrics: &ModuleMetrics) -> usize {
+        metrics.module_name.as_ref().map_or(0, |module_name| {
+            self.module_graph
+                .importers_of(module_name)
+                .iter()
+                .filter(|importer| *importer != uri)
+                .count()
+        })
+    }
+
+    /// Indexed files importing `module_name`, sorted by URI. Covers every indexed file,
+    /// including ones no longer held in the document cache.
+    pub fn module_importers(&self, module_name: &str) -> Vec<Url> {
+        self.module_graph.importers_of(module_name)
+    }
+
+    /// Every way the indexed files write an import clause, with how many files write it
+    /// that way, most common first
+    pub fn import_clauses(&self) -> Vec<(String, usize)> {
+        self.module_graph.import_clauses()
+    }
+
+    /// How often each module's names are used across the indexed files
+    pub fn popular_symbols(&self) -> &PopularSymbols {
+        &self.popular_symbols
+    }
+
+    /// Cached number of uses of `name` defined in `definition`
+    pub fn cached_reference_count(&self, definition: &Url, name: &str) -> Option<usize> {
+        let definition = &self.uris.normalize(definition);
+        self.reference_counts.lock().ok()?.get(definition, name)
+    }
+
+    /// Remember the number of uses of `name` defined in `definition`, until that file or one
+    /// importing its module is re-indexed
+    pub fn cache_reference_count(&self, definition: &Url, name: &str, count: usize) {
+        let definition = self.uris.normalize(definition);
+        if let Ok(mut counts) = self.reference_counts.lock() {
+            counts.insert(definition, name, count);
+        }
+    }
+
+    /// Drop the cached counts a change to `uri` can affect: those of its own symbols and
+    /// of the modules it imports, going by the header currently in the module graph
+    fn invalidate_reference_counts(&self, uri: &Url) {
+        let mut counts = match self.reference_counts.lock() {
+            Ok(counts) => counts,
+            Err(_) => return,
+        };
+        counts.invalidate(uri);
+        if let Some(header) = self.module_graph.header(uri) {
+            for import in &header.imports {
+                if let Some((imported, _)) = self.module_graph.module_file(import) {
+                    counts.invalidate(imported);
+                }
+            }
+        }
+    }
+
+    /// Names of all indexed modules, sorted
+    pub fn module_names(&self) -> Vec<String> {
+        self.module_graph.module_names()
+    }
+
+    /// The indexed file declaring `module_name`
+    pub fn module_uri(&self, module_name: &str) -> Option<Url> {
+        self.module_graph
+            .module_file(module_name)
+            .map(|(uri, _)| uri.clone())
+    }
+
+    /// Name of the module an indexed file declares
+    pub fn module_name(&self, uri: &Url) -> Option<String> {
+        let uri = &self.uris.normalize(uri);
+        self.module_graph.header(uri)?.name.clone()
+    }
+
+    /// Indexed files depending on the module `uri` declares, directly or not, each after
+    /// the ones it imports
+    pub fn module_dependents(&self, uri: &Url) -> Vec<Url> {
+        match self.module_name(uri) {
+            Some(module_name) => self.module_graph.dependents_order(&module_name),
+            None => Vec::new(),
+        }
+    }
+
+    /// The shortest chain of imports from the module `from` to the module `to`, both
+    /// included, or `None` when `from` doesn't depend on `to`
+    pub fn import_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        self.module_graph.import_path(from, to)
+    }
+
+    /// Modules an indexed file imports, sorted
+    pub fn module_imports(&self, uri: &Url) -> Vec<String> {
+        let uri = &self.uris.normalize(uri);
+        self.module_graph
+            .header(uri)
+            .map(|header| header.imports.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether modules other than the one defining `symbol` may refer to it, going by the
+    /// exposing list of its module. Symbols of files the module graph doesn't know count
+    /// as exposed.
+    pub fn is_exposed(&self, symbol: &crate::Symbol) -> bool {
+        let uri = &self.uris.normalize(&symbol.location.uri);
+        self.module_graph.header(uri).map_or(true, |header| {
+            header.exports.exposes(&symbol.name, symbol.kind)
+        })
+    }
+
+    /// Values `import Module exposing (..)` brings into scope: the module's exposing list,
+    /// or every top-level value in the index when the module exposes everything
+    pub fn exposed_values(&self, module_name: &str) -> Vec<String> {
+        let (uri, header) = match self.module_graph.module_file(module_name) {
+            Some(module) => module,
+            None => return Vec::new(),
+        };
+        if let Some(values) = &header.exposed_values {
+            return values.clone();
+        }
+
+        // The index only holds top-level functions, never `let` bindings
+        self.get_file_symbols(uri)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|symbol| symbol.kind == SymbolKind::FUNCTION)
+            .map(|symbol| symbol.name)
+            .collect()
+    }
+
+    /// Everything importers of `module_name` can refer to, when the module is indexed
+    pub fn module_exports(&self, module_name: &str) -> Option<ModuleExports> {
+        self.module_graph
+            .module_file(module_name)
+            .map(|(_, header)| header.exports.clone())
+    }
+
+    /// Which import group a module belongs to, based on where the index says it's defined
+    pub fn import_group(&self, module_name: &str) -> ImportGroup {
+        let modules = self.find_exact_symbols(module_name).unwrap_or_default();
+        modules
+            .iter()
+            .filter(|symbol| symbol.kind == SymbolKind::MODULE)
+            .find_map(|symbol| uri_to_path(&symbol.location.uri).ok())
+            .map(|path| import_group_for_path(&path))
+            .unwrap_or_else(|| import_group_for_unindexed(module_name))
     }
 
     /// Get comprehensive diagnostics for all open documents
@@ -622,6 +2464,111 @@ impl Workspace {
     }
 }
 
+/// A compile of an open document's content, holding everything it needs so it runs
+/// without the workspace
+pub struct DocumentCompile {
+    uri: Url,
+    path: PathBuf,
+    content: String,
+    compiler: GrenCompiler,
+}
+
+impl DocumentCompile {
+    /// Run the compiler on the content the document had when the compile was taken
+    pub async fn run(mut self) -> CompiledDocument {
+        info!(
+            "💭 Compiling in-memory content for real-time diagnostics: {}",
+            self.path.display()
+        );
+        let started = Instant::now();
+        let result = self
+            .compiler
+            .compile_content(&self.content, &self.path)
+            .await;
+        CompiledDocument {
+            uri: self.uri,
+            result,
+            duration: started.elapsed(),
+        }
+    }
+}
+
+/// The outcome of a `DocumentCompile`, for `Workspace::finish_document_compile`
+pub struct CompiledDocument {
+    uri: Url,
+    result: Result<crate::compiler::CompilationResult>,
+    duration: std::time::Duration,
+}
+
+/// A workspace folder's `.gren-lsp.toml`
+#[derive(Default)]
+struct FolderConfig {
+    /// The configuration, default when the file is missing or invalid
+    config: ProjectConfig,
+    /// Why the file couldn't be used, if it couldn't
+    error: Option<String>,
+    /// Problems in the file, to show on the file itself
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Configuration of a folder without a usable `.gren-lsp.toml`
+fn default_project_config() -> &'static ProjectConfig {
+    static DEFAULT: OnceLock<ProjectConfig> = OnceLock::new();
+    DEFAULT.get_or_init(ProjectConfig::default)
+}
+
+/// The symbol index as the type inference looks names up in it
+struct IndexedTypes<'a> {
+    workspace: &'a Workspace,
+}
+
+impl TypeEnvironment for IndexedTypes<'_> {
+    fn signature(&self, module: &str, name: &str) -> Option<String> {
+        let symbols = self.workspace.find_exact_symbols(name).ok()?;
+        symbols
+            .into_iter()
+            .filter(|symbol| symbol.kind != SymbolKind::CLASS)
+            .find(|symbol| {
+                self.workspace.module_name(&symbol.location.uri).as_deref() == Some(module)
+            })?
+            .type_signature
+    }
+
+    fn alias(&self, name: &str) -> Option<(Vec<String>, String)> {
+        let symbols = self.workspace.find_exact_symbols(name).ok()?;
+        symbols.into_iter().find_map(|symbol| {
+            // Stored as `alias Name a = definition`
+            let signature = symbol.type_signature?;
+            let (head, definition) = signature.strip_prefix("alias ")?.split_once('=')?;
+            let parameters = head
+                .split_whitespace()
+                .skip(1)
+                .map(str::to_string)
+                .collect();
+            Some((parameters, definition.trim().to_string()))
+        })
+    }
+}
+
+/// Whether other modules can refer to `symbol`, as far as its module's exposing list says
+fn is_exported(header: &ModuleHeader, symbol: &crate::Symbol) -> bool {
+    let exports = &header.exports;
+    match symbol.kind {
+        SymbolKind::FUNCTION => exports.values.contains(&symbol.name),
+        SymbolKind::CLASS => exports.types.contains(&symbol.name),
+        SymbolKind::CONSTRUCTOR => exports.constructors.contains(&symbol.name),
+        _ => true,
+    }
+}
+
+/// The path a document is compiled as: its file, or the stand-in path of a scratch buffer
+fn document_path(uri: &Url) -> Result<PathBuf> {
+    match DocumentKind::of(uri) {
+        DocumentKind::Scratch => Ok(scratch_path(uri)),
+        _ => uri_to_path(uri),
+    }
+}
+
 /// Helper function to convert LSP URI to filesystem path
 fn uri_to_path(uri: &Url) -> Result<PathBuf> {
     uri.to_file_path()
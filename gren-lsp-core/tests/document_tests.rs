@@ -332,4 +332,35 @@ greet name =
     assert!(document.text().contains("中文"));
     assert!(document.text().contains("🚀"));
     assert!(document.text().contains("🌟"));
-}
\ No newline at end of file
+}
+
+/// Test: CRLF line endings
+/// Purpose: The client's `\r\n` is kept, and a lone `\r` becomes `\n` so lines count the
+/// same for the client and the parse tree
+#[test]
+fn test_crlf_line_endings_kept() {
+    let lsp_item = create_test_lsp_item(
+        "file:///crlf.gren",
+        "module Test exposing (..)\r\n\r\ngreet = \"Hello\"\r\n",
+        1,
+    );
+    let mut document = Document::new(lsp_item);
+    assert_eq!(
+        document.text(),
+        "module Test exposing (..)\r\n\r\ngreet = \"Hello\"\r\n"
+    );
+
+    let change = TextDocumentContentChangeEvent {
+        range: Some(Range {
+            start: Position { line: 3, character: 0 },
+            end: Position { line: 3, character: 0 },
+        }),
+        range_length: None,
+        text: "\r\nfarewell = \"Bye\"\r".to_string(),
+    };
+    document.apply_changes(vec![change]).unwrap();
+    assert_eq!(
+        document.text(),
+        "module Test exposing (..)\r\n\r\ngreet = \"Hello\"\r\n\r\nfarewell = \"Bye\"\n"
+    );
+}
@@ -1,5 +1,6 @@
-use gren_lsp_core::Workspace;
+use gren_lsp_core::{InMemorySymbolStore, Workspace};
 use lsp_types::*;
+use std::sync::Arc;
 
 /// Create a test text document item
 fn create_test_document(uri: &str, content: &str, version: i32) -> TextDocumentItem {
@@ -64,6 +65,103 @@ fn test_set_workspace_root() {
     assert_eq!(stats.root_uri, Some(root_uri));
 }
 
+#[test]
+fn test_workspace_folders() {
+    let mut workspace = Workspace::new().unwrap();
+    let app = Url::parse("file:///test/app").unwrap();
+    let package = Url::parse("file:///test/app/package").unwrap();
+
+    // There's no compiler in these folders
+    let _ = workspace.set_root(app.clone());
+    let _ = workspace.add_folder(package.clone());
+    assert_eq!(workspace.folders(), &[app.clone(), package.clone()]);
+
+    let folder_of = |uri: &str| workspace.folder_of(&Url::parse(uri).unwrap()).cloned();
+    assert_eq!(
+        folder_of("file:///test/app/src/Main.gren"),
+        Some(app.clone())
+    );
+    assert_eq!(
+        folder_of("file:///test/app/package/src/Lib.gren"),
+        Some(package.clone())
+    );
+    assert_eq!(folder_of("file:///test/application/src/Main.gren"), None);
+
+    workspace.remove_folder(&app);
+    assert_eq!(workspace.folders(), &[package.clone()]);
+    assert_eq!(workspace.stats().root_uri, Some(package));
+}
+
+#[test]
+fn test_project_root_is_the_owning_project() {
+    let folder = tempfile::TempDir::new().unwrap();
+    let package = folder.path().join("packages/ui");
+    std::fs::create_dir_all(&package).unwrap();
+    std::fs::write(folder.path().join("gren.json"), "{}").unwrap();
+    std::fs::write(package.join("gren.json"), "{}").unwrap();
+
+    let mut workspace = Workspace::new().unwrap();
+    // There's no compiler in the folder
+    let _ = workspace.set_root(Url::from_directory_path(folder.path()).unwrap());
+
+    let project_root =
+        |path: std::path::PathBuf| workspace.project_root(&Url::from_file_path(path).unwrap());
+    assert_eq!(
+        project_root(package.join("src/Button.gren")),
+        Some(package.clone())
+    );
+    assert_eq!(
+        project_root(folder.path().join("src/Main.gren")),
+        Some(folder.path().to_path_buf())
+    );
+}
+
+#[test]
+fn test_project_config_is_per_folder() {
+    let app = tempfile::TempDir::new().unwrap();
+    let library = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        app.path().join(".gren-lsp.toml"),
+        "exclude = [\"generated/**\"]\n",
+    )
+    .unwrap();
+
+    let mut workspace = Workspace::new().unwrap();
+    // There's no compiler in these folders
+    let _ = workspace.set_root(Url::from_directory_path(app.path()).unwrap());
+    let _ = workspace.add_folder(Url::from_directory_path(library.path()).unwrap());
+
+    let is_excluded =
+        |path: std::path::PathBuf| workspace.is_excluded(&Url::from_file_path(path).unwrap());
+    assert!(is_excluded(app.path().join("generated/Api.gren")));
+    assert!(!is_excluded(library.path().join("generated/Api.gren")));
+}
+
+#[test]
+fn test_files_below_folder() {
+    let mut workspace = Workspace::new().unwrap();
+    for uri in [
+        "file:///test/src/Page/Home.gren",
+        "file:///test/src/Page/Settings.gren",
+        "file:///test/src/Pages.gren",
+    ] {
+        let module = uri.rsplit('/').next().unwrap().trim_end_matches(".gren");
+        let content = format!("module {} exposing (..)", module);
+        workspace
+            .open_document(create_test_document(uri, &content, 1))
+            .unwrap();
+    }
+
+    let below = workspace.files_below(&Url::parse("file:///test/src/Page").unwrap());
+    assert_eq!(
+        below,
+        vec![
+            Url::parse("file:///test/src/Page/Home.gren").unwrap(),
+            Url::parse("file:///test/src/Page/Settings.gren").unwrap(),
+        ]
+    );
+}
+
 #[test]
 fn test_document_open() {
     let mut workspace = Workspace::new().unwrap();
@@ -256,6 +354,40 @@ fn test_symbol_persistence_after_close() {
     );
 }
 
+#[test]
+fn test_rename_file_moves_document_and_symbols() {
+    let mut workspace = Workspace::new().unwrap();
+
+    let doc = create_test_document(
+        "file:///src/OldName.gren",
+        "module OldName exposing (renamedFileFunction)\n\nrenamedFileFunction : Int\nrenamedFileFunction = 1",
+        3,
+    );
+    let old_uri = doc.uri.clone();
+    let new_uri = Url::parse("file:///src/NewName.gren").unwrap();
+
+    workspace.open_document(doc).unwrap();
+    workspace.rename_file(&old_uri, new_uri.clone()).unwrap();
+
+    assert!(!workspace.is_document_open(&old_uri));
+    assert!(workspace.is_document_open(&new_uri));
+    assert_eq!(
+        workspace.get_document_readonly(&new_uri).unwrap().version(),
+        3
+    );
+
+    let symbols = workspace.find_exact_symbols("renamedFileFunction").unwrap();
+    assert!(
+        !symbols.is_empty(),
+        "Symbols should be re-indexed after rename"
+    );
+    assert!(symbols.iter().all(|s| s.location.uri == new_uri));
+
+    // Renaming a file we don't know about is an error
+    let unknown = Url::parse("file:///src/Unknown.gren").unwrap();
+    assert!(workspace.rename_file(&unknown, new_uri).is_err());
+}
+
 #[test]
 fn test_document_access_tracking() {
     let mut workspace = Workspace::with_capacity(2).unwrap();
@@ -367,3 +499,55 @@ fn test_parse_errors_tracking() {
     // Note: This depends on tree-sitter-gren's error detection capabilities
     // The test might pass even with errors if the grammar is lenient
 }
+
+#[test]
+fn test_differently_encoded_uris_share_one_document() {
+    let mut workspace = Workspace::new().unwrap();
+    workspace
+        .set_symbol_store(Arc::new(InMemorySymbolStore::new()))
+        .unwrap();
+    let content = "module Main exposing (main)\n\nmain = 1\n";
+
+    let encoded = create_test_document("file:///c%3A/project/Main.gren", content, 1);
+    let plain = create_test_document("file:///C:/project/Main.gren", content, 2);
+    let plain_uri = plain.uri.clone();
+    workspace.open_document(encoded.clone()).unwrap();
+    workspace.open_document(plain).unwrap();
+
+    assert_eq!(workspace.stats().document_count, 1);
+    assert_eq!(workspace.normalize_uri(&encoded.uri), plain_uri);
+    assert!(workspace.get_document_readonly(&encoded.uri).is_some());
+
+    let mains: Vec<_> = workspace
+        .find_exact_symbols("main")
+        .unwrap()
+        .into_iter()
+        .filter(|symbol| symbol.kind == SymbolKind::FUNCTION)
+        .collect();
+    assert_eq!(mains.len(), 1, "the old spelling's index rows are replaced");
+    assert_eq!(mains[0].location.uri, plain_uri);
+}
+
+#[test]
+fn test_forget_published_diagnostics() {
+    let mut workspace = Workspace::new().unwrap();
+    let diagnostic = Diagnostic {
+        message: "problem".to_string(),
+        ..Default::default()
+    };
+    let main = Url::parse("file:///project/src/Main.gren").unwrap();
+    let page = Url::parse("file:///project/src/Page/Home.gren").unwrap();
+    let clean = Url::parse("file:///project/src/Page/About.gren").unwrap();
+    workspace.record_published_diagnostics(&main, &[diagnostic.clone()]);
+    workspace.record_published_diagnostics(&page, &[diagnostic.clone()]);
+    workspace.record_published_diagnostics(&clean, &[diagnostic]);
+    workspace.record_published_diagnostics(&clean, &[]);
+
+    let folder = Url::parse("file:///project/src/Page").unwrap();
+    assert_eq!(workspace.forget_published_diagnostics(&folder), vec![page]);
+    assert_eq!(
+        workspace.forget_published_diagnostics(&main),
+        vec![main.clone()]
+    );
+    assert!(workspace.forget_published_diagnostics(&main).is_empty());
+}
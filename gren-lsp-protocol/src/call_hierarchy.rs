@@ -0,0 +1,174 @@
+//! Call hierarchy from the indexed references
+//!
+//! Each indexed reference records the top-level value whose definition it's in. A call of
+//! `view` is a reference to it in an expression, so the values calling `view` are the
+//! containers of those references, and the values `view` calls are the lowercase names
+//! referred to from inside it.
+
+use gren_lsp_core::{ReferenceKind, Symbol, SymbolReference};
+use lsp_types::*;
+use std::collections::BTreeMap;
+
+/// The call hierarchy item for a top-level value defined in `module`
+pub fn call_item(symbol: &Symbol, module: &str) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: symbol.name.clone(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: Some(module.to_string()),
+        uri: symbol.location.uri.clone(),
+        range: symbol.location.range,
+        selection_range: symbol.location.range,
+        data: Some(serde_json::json!({ "module": module })),
+    }
+}
+
+/// The module a call hierarchy item was made for
+pub fn item_module(item: &CallHierarchyItem) -> Option<String> {
+    item.data
+        .as_ref()?
+        .get("module")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The values calling the target of `references`, one entry per calling value with the
+/// ranges of its calls. `caller` finds the item for a value of a file.
+pub fn incoming_calls(
+    references: &[SymbolReference],
+    caller: impl Fn(&Url, &str) -> Option<CallHierarchyItem>,
+) -> Vec<CallHierarchyIncomingCall> {
+    let mut calls: BTreeMap<(&str, &str), Vec<Range>> = BTreeMap::new();
+    for reference in references.iter().filter(|reference| is_call(reference)) {
+        if let Some(container) = &reference.container {
+            calls
+                .entry((reference.location.uri.as_str(), container))
+                .or_default()
+                .push(reference.location.range);
+        }
+    }
+    calls
+        .into_iter()
+        .filter_map(|((uri, container), from_ranges)| {
+            let uri = Url::parse(uri).ok()?;
+            Some(CallHierarchyIncomingCall {
+                from: caller(&uri, container)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+/// The values called in the references from one value's definition, one entry per called
+/// value with the ranges of its calls. `callee` finds the item for a module's value.
+pub fn outgoing_calls(
+    references: &[SymbolReference],
+    callee: impl Fn(&str, &str) -> Option<CallHierarchyItem>,
+) -> Vec<CallHierarchyOutgoingCall> {
+    let mut calls: BTreeMap<(&str, &str), Vec<Range>> = BTreeMap::new();
+    for reference in references.iter().filter(|reference| is_call(reference)) {
+        calls
+            .entry((&reference.module, &reference.name))
+            .or_default()
+            .push(reference.location.range);
+    }
+    calls
+        .into_iter()
+        .filter_map(|((module, name), from_ranges)| {
+            Some(CallHierarchyOutgoingCall {
+                to: callee(module, name)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+/// Whether a reference uses a value, rather than a constructor, a type or an import
+fn is_call(reference: &SymbolReference) -> bool {
+    reference.kind == ReferenceKind::Expression && reference.name.starts_with(char::is_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(
+        uri: &str,
+        module: &str,
+        name: &str,
+        line: u32,
+        container: &str,
+    ) -> SymbolReference {
+        SymbolReference {
+            name: name.to_string(),
+            module: module.to_string(),
+            location: Location::new(
+                Url::parse(uri).unwrap(),
+                Range::new(Position::new(line, 4), Position::new(line, 8)),
+            ),
+            kind: ReferenceKind::Expression,
+            container: Some(container.to_string()),
+        }
+    }
+
+    fn item(uri: &Url, module: &str, name: &str) -> CallHierarchyItem {
+        let symbol = Symbol {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            location: Location::new(uri.clone(), Range::default()),
+            container_name: None,
+            type_signature: None,
+            documentation: None,
+        };
+        call_item(&symbol, module)
+    }
+
+    #[test]
+    fn test_incoming_calls() {
+        // A constructor and the definition itself aren't calls
+        let constructor = reference("file:///Main.gren", "Page", "Home", 3, "main");
+        let mut declaration = reference("file:///Page.gren", "Page", "view", 1, "view");
+        declaration.kind = ReferenceKind::Declaration;
+        let references = vec![
+            reference("file:///Main.gren", "Page", "view", 5, "main"),
+            reference("file:///Main.gren", "Page", "view", 9, "main"),
+            reference("file:///Main.gren", "Page", "view", 12, "update"),
+            constructor,
+            declaration,
+        ];
+
+        let calls = incoming_calls(&references, |uri, name| Some(item(uri, "Main", name)));
+        let callers: Vec<(&str, usize)> = calls
+            .iter()
+            .map(|call| (call.from.name.as_str(), call.from_ranges.len()))
+            .collect();
+        assert_eq!(callers, vec![("main", 2), ("update", 1)]);
+        assert_eq!(item_module(&calls[0].from), Some("Main".to_string()));
+    }
+
+    #[test]
+    fn test_outgoing_calls() {
+        let references = vec![
+            reference("file:///Main.gren", "Page", "view", 5, "main"),
+            reference("file:///Main.gren", "Html", "text", 6, "main"),
+            reference("file:///Main.gren", "Page", "view", 7, "main"),
+        ];
+        let uri = Url::parse("file:///Page.gren").unwrap();
+
+        // Html isn't in the workspace
+        let calls = outgoing_calls(&references, |module, name| {
+            (module == "Page").then(|| item(&uri, module, name))
+        });
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].to.name, "view");
+        assert_eq!(calls[0].to.detail.as_deref(), Some("Page"));
+        assert_eq!(
+            calls[0]
+                .from_ranges
+                .iter()
+                .map(|range| range.start.line)
+                .collect::<Vec<_>>(),
+            vec![5, 7]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Call snippets for accepted function completions
+//!
+//! With `completion.functionInsert` set to `arguments`, accepting `map` inserts
+//! `map (\x -> $1) $2`: one tab stop per argument of the indexed signature, and a lambda
+//! with the right number of parameters for arguments that are functions themselves.
+//! `parenthesized` also wraps the call in parentheses when the function is completed as
+//! the argument of another call. After `|>` the pipe supplies the last argument, so it
+//! gets no tab stop.
+
+use gren_lsp_core::Parser;
+use tree_sitter::Node;
+
+/// Where a function is being completed, as far as the shape of its call is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallContext {
+    /// At the start of an expression
+    Head,
+    /// After a function or value it's applied to, so the call needs parentheses
+    Argument,
+    /// Right after `|>`, which passes the last argument
+    Piped,
+}
+
+const LAMBDA_PARAMETERS: [&str; 4] = ["x", "y", "z", "w"];
+
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "when", "is", "let", "in", "as", "exposing", "import", "module",
+];
+
+/// The context of a completion from the text before the word being completed on its line
+pub fn call_context(before_word: &str) -> CallContext {
+    let trimmed = before_word.trim_end();
+    // Only a space separates an argument from what it's applied to
+    if trimmed.len() == before_word.len() {
+        return CallContext::Head;
+    }
+    if trimmed.ends_with("|>") {
+        return CallContext::Piped;
+    }
+
+    let last = match trimmed.chars().last() {
+        Some(last) => last,
+        None => return CallContext::Head,
+    };
+    if matches!(last, ')' | ']' | '}' | '"' | '\'') {
+        return CallContext::Argument;
+    }
+    if !(last.is_alphanumeric() || last == '_') {
+        return CallContext::Head;
+    }
+
+    let word_start = trimmed
+        .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.')
+        .len();
+    if KEYWORDS.contains(&&trimmed[word_start..]) {
+        CallContext::Head
+    } else {
+        CallContext::Argument
+    }
+}
+
+/// How many parameters each argument of a signature takes, 0 for arguments that aren't
+/// functions. The signature is parsed as a synthetic annotation, like pattern completion
+/// does, so nested arrows come from the grammar.
+pub fn argument_arities(parser: &mut Parser, signature: &str) -> Option<Vec<usize>> {
+    let source = format!(
+        "module Signature exposing (..)\n\nsignature : {}\n",
+        signature
+    );
+    let tree = parser.parse(&source).ok()??;
+    let root = tree.root_node();
+
+    let mut cursor = root.walk();
+    let annotation = root
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "type_annotation")?;
+    let mut cursor = annotation.walk();
+    let type_expression = annotation
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "type_expression")?;
+    if type_expression.has_error() {
+        return None;
+    }
+
+    let mut cursor = type_expression.walk();
+    let mut arities: Vec<usize> = type_expression
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() != "arrow" && !node.kind().ends_with("comment"))
+        .map(function_arity)
+        .collect();
+    // The last part is the result
+    arities.pop()?;
+    Some(arities)
+}
+
+/// Parameters of a function type, looking through the parentheses around it
+fn function_arity(node: Node) -> usize {
+    if node.kind() == "type_ref" {
+        return 0;
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|child| !child.kind().ends_with("comment"))
+        .collect();
+    let arrows = children
+        .iter()
+        .filter(|child| child.kind() == "arrow")
+        .count();
+    match children.as_slice() {
+        [inner] if arrows == 0 => function_arity(*inner),
+        _ => arrows,
+    }
+}
+
+/// The snippet calling `name` with a tab stop per argument, or `None` when there's no
+/// argument left to fill in and the plain name is inserted
+pub fn call_snippet(
+    name: &str,
+    arities: &[usize],
+    context: CallContext,
+    parenthesize: bool,
+) -> Option<String> {
+    let arities = match context {
+        CallContext::Piped => arities.split_last().map_or(arities, |(_, rest)| rest),
+        _ => arities,
+    };
+    if arities.is_empty() {
+        return None;
+    }
+
+    let mut snippet = name.to_string();
+    for (index, arity) in arities.iter().enumerate() {
+        let tab_stop = index + 1;
+        if *arity == 0 {
+            snippet.push_str(&format!(" ${}", tab_stop));
+        } else {
+            let parameters: Vec<String> = (0..*arity).map(lambda_parameter).collect();
+            // Backslashes are escaped in snippets
+            snippet.push_str(&format!(" (\\\\{} -> ${})", parameters.join(" "), tab_stop));
+        }
+    }
+
+    if parenthesize && context == CallContext::Argument {
+        Some(format!("({})", snippet))
+    } else {
+        Some(snippet)
+    }
+}
+
+fn lambda_parameter(index: usize) -> String {
+    match LAMBDA_PARAMETERS.get(index) {
+        Some(name) => name.to_string(),
+        None => format!("x{}", index + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_snippet() {
+        assert_eq!(
+            call_snippet("map", &[1, 0], CallContext::Head, true),
+            Some("map (\\\\x -> $1) $2".to_string())
+        );
+        assert_eq!(
+            call_snippet("foldl", &[2, 0, 0], CallContext::Argument, true),
+            Some("(foldl (\\\\x y -> $1) $2 $3)".to_string())
+        );
+        assert_eq!(
+            call_snippet("foldl", &[2, 0, 0], CallContext::Argument, false),
+            Some("foldl (\\\\x y -> $1) $2 $3".to_string())
+        );
+        assert_eq!(
+            call_snippet("map", &[1, 0], CallContext::Piped, true),
+            Some("map (\\\\x -> $1)".to_string())
+        );
+        assert_eq!(call_snippet("negate", &[0], CallContext::Piped, true), None);
+        assert_eq!(call_snippet("main", &[], CallContext::Head, true), None);
+    }
+
+    #[test]
+    fn test_call_context() {
+        assert_eq!(call_context("    "), CallContext::Head);
+        assert_eq!(call_context("view model = "), CallContext::Head);
+        assert_eq!(call_context("    if "), CallContext::Head);
+        assert_eq!(call_context("    Array.map "), CallContext::Argument);
+        assert_eq!(call_context("    toString (x + 1) "), CallContext::Argument);
+        assert_eq!(call_context("        |> "), CallContext::Piped);
+        assert_eq!(call_context("    [ "), CallContext::Head);
+        assert_eq!(call_context("foo"), CallContext::Head);
+    }
+
+    #[test]
+    fn test_argument_arities() {
+        let mut parser = Parser::new().unwrap();
+        assert_eq!(
+            argument_arities(&mut parser, "(a -> b) -> Array a -> Array b"),
+            Some(vec![1, 0])
+        );
+        assert_eq!(
+            argument_arities(&mut parser, "(a -> b -> b) -> b -> Array a -> b"),
+            Some(vec![2, 0, 0])
+        );
+        assert_eq!(argument_arities(&mut parser, "Program"), Some(Vec::new()));
+    }
+}
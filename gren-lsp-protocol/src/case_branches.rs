@@ -0,0 +1,557 @@
+//! Branches for the constructors a `when ... is` expression doesn't match yet
+//!
+//! The expression is usually still being written when branches are asked for, and the
+//! parser recovers from a half-written one with an error node. Its `when` and `is`
+//! keywords stay siblings in either, so the expression is found from them: the subject is
+//! what's between the two, and the branches are the `case_of_branch` nodes after `is`.
+//! The cursor is in the expression when it's on one of its lines, or below it on a line
+//! that's blank or indented deeper than `when`.
+//!
+//! The subject's type comes from a constructor an existing branch matches, the enclosing
+//! function's annotation when the subject is one of its parameters, or the signature of
+//! the function the subject calls. A branch matching a constructor with nested patterns
+//! counts as covering it.
+
+use crate::pattern_completion::{parse_signature, type_shape, TypeShape};
+use gren_lsp_core::syntax::{child_of_kind, point_to_position, position_to_point};
+use gren_lsp_core::Parser;
+use lsp_types::Position;
+use tree_sitter::{Node, Tree};
+
+/// Words a generated pattern variable can't be
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "when", "is", "let", "in", "as", "type", "alias", "module", "exposing",
+    "import", "port", "effect", "where",
+];
+
+/// A `when ... is` expression found around the cursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhenExpression {
+    /// The expression between `when` and `is`
+    pub subject: String,
+    /// The function the subject calls and how many arguments it gets, when the subject is
+    /// a call like `Dict.get key users` or a plain name
+    pub call: Option<(String, usize)>,
+    /// Line of `when ... is`
+    pub header_line: u32,
+    pub header_indent: usize,
+    /// Column of the branch patterns, `None` before the first branch is written
+    pub branch_indent: Option<usize>,
+    /// Patterns of the branches written so far, in order
+    pub patterns: Vec<String>,
+    /// End of the expression's last line, where new branches go
+    pub end: Position,
+}
+
+impl WhenExpression {
+    /// Column a new branch starts at when the text before the cursor on its line is
+    /// blank or the start of a constructor name, `None` when the cursor isn't there
+    pub fn branch_start(&self, line_before_cursor: &str) -> Option<usize> {
+        let column = indentation(line_before_cursor);
+        let typed = &line_before_cursor[column..];
+        let is_constructor_prefix = typed.is_empty()
+            || (typed.starts_with(|c: char| c.is_uppercase())
+                && typed.chars().all(|c| c.is_alphanumeric() || c == '_'));
+        let at_branch_column = match self.branch_indent {
+            Some(branch_indent) => column == branch_indent,
+            None => column > self.header_indent,
+        };
+        if is_constructor_prefix && at_branch_column {
+            Some(column)
+        } else {
+            None
+        }
+    }
+
+    /// Column new branches go at
+    pub fn indent(&self) -> usize {
+        self.branch_indent.unwrap_or(self.header_indent + 4)
+    }
+
+    /// The qualifier existing branches write constructors with, e.g. `Shape` for
+    /// `Shape.Circle r ->`
+    pub fn qualifier(&self) -> Option<&str> {
+        self.patterns
+            .iter()
+            .filter_map(|pattern| pattern_constructor(pattern))
+            .find_map(|constructor| constructor.rsplit_once('.'))
+            .map(|(qualifier, _)| qualifier)
+    }
+}
+
+/// A constructor to write a branch for
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchConstructor {
+    /// As the branch writes it, qualified when the file needs it to be. A whole pattern
+    /// when the compiler says which one is missing.
+    pub name: String,
+    /// Types of its arguments
+    pub arguments: Vec<TypeShape>,
+}
+
+impl BranchConstructor {
+    /// The constructor named `name` with the arguments its indexed signature takes
+    pub fn from_signature(name: String, signature: Option<&str>) -> Self {
+        let arguments = signature
+            .and_then(|signature| parse_signature(&mut Parser::new().ok()?, signature))
+            .map(|(arguments, _)| arguments)
+            .unwrap_or_default();
+        BranchConstructor { name, arguments }
+    }
+
+    /// A branch for a pattern the compiler reports missing, written as it reports it
+    pub fn from_pattern(pattern: &str) -> Self {
+        BranchConstructor {
+            name: pattern.to_string(),
+            arguments: Vec::new(),
+        }
+    }
+}
+
+/// The `when ... is` expression the cursor is in, or on a line right after
+pub fn when_expression_at(tree: &Tree, source: &str, position: Position) -> Option<WhenExpression> {
+    let cursor = position_to_point(position, source);
+    let mut keywords = Vec::new();
+    collect_when_keywords(tree.root_node(), &mut keywords);
+
+    // Nested expressions start after the ones holding them, so the innermost comes first
+    keywords
+        .into_iter()
+        .rev()
+        .filter(|keyword| keyword.start_position().row <= cursor.row)
+        .filter_map(|keyword| when_expression(keyword, source))
+        .find(|when| {
+            let last_line = when.end.line as usize;
+            if cursor.row <= last_line {
+                return true;
+            }
+            let line_at = |row: usize| source.split('\n').nth(row).unwrap_or("");
+            let blank_between =
+                ((last_line + 1)..cursor.row).all(|row| line_at(row).trim().is_empty());
+            let cursor_line = line_at(cursor.row);
+            blank_between
+                && (cursor_line.trim().is_empty() || indentation(cursor_line) > when.header_indent)
+        })
+}
+
+/// The constructors no branch matches yet, in the order given, or `None` when a
+/// wildcard or variable branch already matches everything
+pub fn missing_constructors(patterns: &[String], constructors: &[String]) -> Option<Vec<String>> {
+    let mut covered = Vec::new();
+    for pattern in patterns {
+        match pattern_constructor(pattern) {
+            Some(constructor) => covered.push(unqualified(constructor)),
+            None if is_catch_all(pattern) => return None,
+            None => {}
+        }
+    }
+    Some(
+        constructors
+            .iter()
+            .filter(|constructor| !covered.contains(&unqualified(constructor)))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// The constructor a branch pattern starts with, qualified as written
+pub fn pattern_constructor(pattern: &str) -> Option<&str> {
+    let head = pattern
+        .trim_start_matches(|c: char| c == '(' || c.is_whitespace())
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .next()?;
+    if unqualified(head).starts_with(|c: char| c.is_uppercase()) {
+        Some(head)
+    } else {
+        None
+    }
+}
+
+/// Type of the parameter `name` of the top-level function holding `line`, as its
+/// annotation declares it
+pub fn parameter_type(tree: &Tree, source: &str, line: u32, name: &str) -> Option<String> {
+    let line = line as usize;
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declarations: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|declaration| !declaration.kind().ends_with("comment"))
+        .collect();
+    let index = declarations.iter().position(|declaration| {
+        declaration.start_position().row <= line && line <= declaration.end_position().row
+    })?;
+
+    let left = child_of_kind(&declarations[index], "function_declaration_left")?;
+    let mut cursor = left.walk();
+    let mut parts = left.named_children(&mut cursor);
+    let function = text(parts.next()?, source)?;
+    let parameter = parts.position(|parameter| {
+        matches!(parameter.kind(), "lower_pattern" | "lower_case_identifier")
+            && text(parameter, source) == Some(name)
+    })?;
+
+    let annotation = declarations[..index]
+        .last()
+        .filter(|annotation| annotation.kind() == "type_annotation")?;
+    let annotated = child_of_kind(annotation, "lower_case_identifier")?;
+    if text(annotated, source) != Some(function) {
+        return None;
+    }
+    let type_expression = child_of_kind(annotation, "type_expression")?;
+    let mut cursor = type_expression.walk();
+    let mut types: Vec<Node> = type_expression
+        .named_children(&mut cursor)
+        .filter(|part| part.kind() != "arrow" && !part.kind().ends_with("comment"))
+        .collect();
+    // The last part is the return type
+    types.pop();
+    shape_name(&type_shape(*types.get(parameter)?, source))
+}
+
+/// The type a function with `signature` returns after `applied` arguments, when that's
+/// all of them
+pub fn result_type_name(signature: &str, applied: usize) -> Option<String> {
+    let (arguments, result) = parse_signature(&mut Parser::new().ok()?, signature)?;
+    if applied == arguments.len() {
+        shape_name(&result)
+    } else {
+        None
+    }
+}
+
+/// Branches for `constructors`, each line indented to `indent` and each body indented
+/// below its pattern. With `snippet`, argument names and bodies are tab stops.
+pub fn branches_text(constructors: &[BranchConstructor], indent: usize, snippet: bool) -> String {
+    let branch_indent = " ".repeat(indent);
+    let body_indent = " ".repeat(indent + 4);
+    let mut tab_stop = 0;
+    let mut placeholder = |text: &str| {
+        if snippet {
+            tab_stop += 1;
+            format!("${{{}:{}}}", tab_stop, text)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let branches: Vec<String> = constructors
+        .iter()
+        .map(|constructor| {
+            let mut pattern = constructor.name.clone();
+            for name in argument_names(&constructor.arguments) {
+                pattern.push(' ');
+                pattern.push_str(&placeholder(&name));
+            }
+            let body = placeholder(&format!("Debug.todo \"{}\"", todo_label(&constructor.name)));
+            format!("{}{} ->\n{}{}", branch_indent, pattern, body_indent, body)
+        })
+        .collect();
+    branches.join("\n\n")
+}
+
+/// What a branch's `Debug.todo` says: its pattern without the constructor's qualifier
+fn todo_label(pattern: &str) -> String {
+    let label = match pattern.split_once(' ') {
+        Some((constructor, rest)) => format!("{} {}", unqualified(constructor), rest),
+        None => unqualified(pattern).to_string(),
+    };
+    label.replace('"', "\\\"")
+}
+
+/// Names for a constructor's arguments, from their types: `float` for `Float`, `value`
+/// for a type variable, a keyword or anything without a name, numbered when they repeat
+fn argument_names(arguments: &[TypeShape]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for argument in arguments {
+        let base = shape_name(argument)
+            .and_then(|name| {
+                let mut chars = name.chars();
+                let first = chars.next()?;
+                Some(first.to_lowercase().chain(chars).collect::<String>())
+            })
+            .filter(|name| !KEYWORDS.contains(&name.as_str()))
+            .unwrap_or_else(|| "value".to_string());
+        let mut name = base.clone();
+        let mut number = 2;
+        while names.contains(&name) {
+            name = format!("{}{}", base, number);
+            number += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Unqualified name of the type a shape refers to, `None` for type variables, records and
+/// functions
+fn shape_name(shape: &TypeShape) -> Option<String> {
+    match shape {
+        TypeShape::Named { name, .. } => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Every `when` keyword under `node`, in source order
+fn collect_when_keywords<'a>(node: Node<'a>, keywords: &mut Vec<Node<'a>>) {
+    if node.kind() == "when" && !node.is_named() {
+        keywords.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_when_keywords(child, keywords);
+    }
+}
+
+/// The expression a `when` keyword starts, `None` until its subject and `is` are written
+fn when_expression(keyword: Node, source: &str) -> Option<WhenExpression> {
+    let mut siblings = Vec::new();
+    let mut next = keyword.next_sibling();
+    while let Some(sibling) = next {
+        siblings.push(sibling);
+        next = sibling.next_sibling();
+    }
+    let is = siblings.iter().position(|sibling| sibling.kind() == "is")?;
+
+    let subject: Vec<Node> = siblings[..is]
+        .iter()
+        .copied()
+        .filter(|node| node.is_named() && !node.kind().ends_with("comment"))
+        .collect();
+    let subject_text: Vec<&str> = subject
+        .iter()
+        .filter_map(|node| text(*node, source))
+        .collect();
+    let subject_text = collapse_whitespace(&subject_text.join(" "));
+    if subject_text.is_empty() {
+        return None;
+    }
+
+    let branches: Vec<Node> = siblings[(is + 1)..]
+        .iter()
+        .copied()
+        .filter(|sibling| sibling.kind() == "case_of_branch")
+        .collect();
+    let patterns = branches
+        .iter()
+        .filter_map(|branch| {
+            branch
+                .child_by_field_name("pattern")
+                .or_else(|| branch.named_child(0))
+        })
+        .filter_map(|pattern| text(pattern, source))
+        .map(collapse_whitespace)
+        .collect();
+
+    let header_line = keyword.start_position().row;
+    let last = branches.last().copied().unwrap_or(siblings[is]);
+    Some(WhenExpression {
+        subject: subject_text,
+        call: match subject.as_slice() {
+            [subject] => subject_call(*subject, source),
+            _ => None,
+        },
+        header_line: header_line as u32,
+        header_indent: indentation(source.split('\n').nth(header_line).unwrap_or("")),
+        branch_indent: branches
+            .first()
+            .map(|branch| point_to_position(branch.start_position(), source).character as usize),
+        patterns,
+        end: point_to_position(last.end_position(), source),
+    })
+}
+
+/// The function a subject calls and how many arguments it gets, `None` when the subject
+/// is anything but a call or a plain name
+fn subject_call(subject: Node, source: &str) -> Option<(String, usize)> {
+    let mut cursor = subject.walk();
+    let children: Vec<Node> = subject
+        .named_children(&mut cursor)
+        .filter(|child| !child.kind().ends_with("comment"))
+        .collect();
+    let (function, arguments) = match subject.kind() {
+        "function_call_expr" => {
+            let (function, arguments) = children.split_first()?;
+            (*function, arguments.len())
+        }
+        _ => (subject, 0),
+    };
+    let name = text(function, source)?;
+    if function.kind() == "value_expr" && unqualified(name).starts_with(char::is_lowercase) {
+        Some((name.to_string(), arguments))
+    } else {
+        None
+    }
+}
+
+fn is_catch_all(pattern: &str) -> bool {
+    pattern == "_" || pattern.starts_with(|c: char| c.is_lowercase() || c == '_')
+}
+
+fn unqualified(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"module Shapes exposing (..)
+
+area : Bool -> Shape -> Float
+area exact shape =
+    when shape is
+        Circle radius ->
+            pi * radius * radius
+
+        Rectangle width height ->
+            width * height
+
+describe : Shape -> String
+describe shape =
+    when Shape.normalize shape is
+
+"#;
+
+    fn when_at(source: &str, line: u32, character: u32) -> Option<WhenExpression> {
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        when_expression_at(&tree, source, Position::new(line, character))
+    }
+
+    #[test]
+    fn test_when_expression_at() {
+        let when = when_at(SOURCE, 6, 12).unwrap();
+        assert_eq!(when.subject, "shape");
+        assert_eq!(when.call, Some(("shape".to_string(), 0)));
+        assert_eq!(when.header_line, 4);
+        assert_eq!(when.branch_indent, Some(8));
+        assert_eq!(
+            when.patterns,
+            vec!["Circle radius", "Rectangle width height"]
+        );
+        assert_eq!(when.end, Position::new(9, 26));
+
+        // On the blank line below a header with no branches yet
+        let when = when_at(SOURCE, 14, 8).unwrap();
+        assert_eq!(when.subject, "Shape.normalize shape");
+        assert_eq!(when.call, Some(("Shape.normalize".to_string(), 1)));
+        assert_eq!(when.branch_indent, None);
+        assert_eq!(when.branch_start("        "), Some(8));
+        assert_eq!(when.branch_start("        Tri"), Some(8));
+        assert_eq!(when.branch_start("        tri"), None);
+        assert_eq!(when.indent(), 8);
+
+        assert_eq!(when_at(SOURCE, 11, 3), None);
+        assert_eq!(when_at(SOURCE, 2, 3), None);
+    }
+
+    #[test]
+    fn test_when_expression_ignores_comments() {
+        let source = r#"module Main exposing (..)
+
+title page =
+    -- when this is
+    when Page.name
+            page is
+        Home ->
+            "home"
+
+        About ->
+            "about"
+"#;
+        let when = when_at(source, 10, 12).unwrap();
+        assert_eq!(when.subject, "Page.name page");
+        assert_eq!(when.call, Some(("Page.name".to_string(), 1)));
+        assert_eq!(when.header_line, 4);
+        assert_eq!(when.patterns, vec!["Home", "About"]);
+        assert_eq!(when.end, Position::new(10, 19));
+    }
+
+    #[test]
+    fn test_missing_constructors() {
+        let constructors: Vec<String> = ["Circle", "Rectangle", "Triangle"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let patterns = vec!["Shape.Circle r".to_string(), "(Rectangle w h)".to_string()];
+        assert_eq!(
+            missing_constructors(&patterns, &constructors),
+            Some(vec!["Triangle".to_string()])
+        );
+        assert_eq!(
+            missing_constructors(&["_".to_string()], &constructors),
+            None
+        );
+        assert_eq!(
+            missing_constructors(&["other".to_string()], &constructors),
+            None
+        );
+    }
+
+    #[test]
+    fn test_subject_type_sources() {
+        let tree = Parser::new().unwrap().parse(SOURCE).unwrap().unwrap();
+        assert_eq!(
+            parameter_type(&tree, SOURCE, 4, "shape").as_deref(),
+            Some("Shape")
+        );
+        assert_eq!(
+            parameter_type(&tree, SOURCE, 4, "exact").as_deref(),
+            Some("Bool")
+        );
+        assert_eq!(parameter_type(&tree, SOURCE, 4, "missing"), None);
+
+        assert_eq!(
+            result_type_name("k -> Dict k v -> Maybe v", 2).as_deref(),
+            Some("Maybe")
+        );
+        assert_eq!(result_type_name("k -> Dict k v -> Maybe v", 1), None);
+    }
+
+    #[test]
+    fn test_branches_text() {
+        let constructors = vec![
+            BranchConstructor::from_signature(
+                "Rectangle".to_string(),
+                Some("Float -> Float -> Shape"),
+            ),
+            BranchConstructor::from_signature("Shape.Empty".to_string(), Some("Shape")),
+        ];
+        assert_eq!(
+            branches_text(&constructors, 8, true),
+            "        Rectangle ${1:float} ${2:float2} ->\n            ${3:Debug.todo \"Rectangle\"}\n\n        Shape.Empty ->\n            ${4:Debug.todo \"Empty\"}"
+        );
+        assert_eq!(
+            branches_text(&constructors[1..], 4, false),
+            "    Shape.Empty ->\n        Debug.todo \"Empty\""
+        );
+        assert_eq!(
+            branches_text(&[BranchConstructor::from_pattern("Just (Err _)")], 4, false),
+            "    Just (Err _) ->\n        Debug.todo \"Just (Err _)\""
+        );
+    }
+
+    #[test]
+    fn test_argument_names_are_not_keywords() {
+        let constructor = BranchConstructor::from_signature(
+            "Declared".to_string(),
+            Some("Type -> Module -> Port -> Maybe a -> Declaration"),
+        );
+        assert_eq!(
+            argument_names(&constructor.arguments),
+            vec!["value", "value2", "value3", "maybe"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Quick fixes removing the unused code the lints report
+//!
+//! Unused import and unused binding diagnostics carry the text to delete, worked out when
+//! the lint ran: the import's lines, the binding's declaration and annotation, or the
+//! whole `let ... in` for a sole binding. The fixes only have to delete it.
+
+use gren_lsp_core::lints::{removal_range, UNUSED_BINDING, UNUSED_IMPORT};
+use lsp_types::*;
+use std::collections::HashMap;
+
+/// "Remove unused import" and "Remove unused binding" for the diagnostics that have them
+pub fn removal_fixes(uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeAction> {
+    diagnostics
+        .iter()
+        .filter_map(|diagnostic| {
+            let title = match &diagnostic.code {
+                Some(NumberOrString::String(code)) if code == UNUSED_IMPORT => {
+                    "Remove unused import"
+                }
+                Some(NumberOrString::String(code)) if code == UNUSED_BINDING => {
+                    "Remove unused binding"
+                }
+                _ => return None,
+            };
+            let range = removal_range(diagnostic)?;
+            Some(CodeAction {
+                title: title.to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(
+                        uri.clone(),
+                        vec![TextEdit::new(range, String::new())],
+                    )])),
+                    document_changes: None,
+                    change_annotations: None,
+                }),
+                command: None,
+                is_preferred: Some(true),
+                disabled: None,
+                data: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removal_fixes() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let removal = Range::new(Position::new(2, 0), Position::new(3, 0));
+        let diagnostic = |code: &str| Diagnostic {
+            code: Some(NumberOrString::String(code.to_string())),
+            data: Some(serde_json::json!({ "removal": removal })),
+            ..Default::default()
+        };
+
+        let fixes = removal_fixes(
+            &uri,
+            &[
+                diagnostic(UNUSED_IMPORT),
+                diagnostic(UNUSED_BINDING),
+                diagnostic("shadowed-name"),
+            ],
+        );
+        let titles: Vec<&str> = fixes.iter().map(|fix| fix.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["Remove unused import", "Remove unused binding"]
+        );
+        let changes = fixes[0].edit.as_ref().unwrap().changes.as_ref().unwrap();
+        assert_eq!(changes[&uri], vec![TextEdit::new(removal, String::new())]);
+    }
+}
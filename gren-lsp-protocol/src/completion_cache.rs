@@ -0,0 +1,116 @@
+//! The last completion result, reused while the user keeps typing the same word
+//!
+//! A client holding an incomplete result asks again on every keystroke with
+//! `TriggerForIncompleteCompletions`. While the cursor is still in the word the last
+//! request completed, filtering that result the way the client would gives the same
+//! answer as rebuilding it, without going back to the workspace index.
+
+use lsp_types::{CompletionItem, Position, Url};
+use std::sync::{Arc, Mutex};
+
+/// Completion cache shared by the handlers of successive requests
+pub type SharedCompletionCache = Arc<Mutex<Option<CompletionCache>>>;
+
+/// Items returned for the word starting at `line`:`word_start` in `uri`
+#[derive(Debug, Clone)]
+pub struct CompletionCache {
+    uri: Url,
+    line: u32,
+    word_start: u32,
+    items: Vec<CompletionItem>,
+}
+
+impl CompletionCache {
+    /// Remember `items` as the completions of `word`, which ends at `position`
+    pub fn new(uri: Url, position: Position, word: &str, items: Vec<CompletionItem>) -> Self {
+        Self {
+            uri,
+            line: position.line,
+            word_start: position
+                .character
+                .saturating_sub(word.chars().count() as u32),
+            items,
+        }
+    }
+
+    /// The remembered items matching `word`, or `None` when `word` isn't the one the
+    /// cached items were returned for
+    pub fn filter(&self, uri: &Url, position: Position, word: &str) -> Option<Vec<CompletionItem>> {
+        let word_start = position
+            .character
+            .checked_sub(word.chars().count() as u32)?;
+        if *uri != self.uri || position.line != self.line || word_start != self.word_start {
+            return None;
+        }
+
+        Some(
+            self.items
+                .iter()
+                .filter(|item| {
+                    fuzzy_matches(item.filter_text.as_deref().unwrap_or(&item.label), word)
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Whether the characters of `word` appear in `label` in order, ignoring case
+fn fuzzy_matches(label: &str, word: &str) -> bool {
+    let mut label_chars = label.chars().flat_map(char::to_lowercase);
+    word.chars()
+        .flat_map(char::to_lowercase)
+        .all(|wanted| label_chars.any(|c| c == wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str) -> CompletionItem {
+        CompletionItem {
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filters_while_typing_the_same_word() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let cache = CompletionCache::new(
+            uri.clone(),
+            Position::new(4, 6),
+            "ma",
+            vec![
+                item("map"),
+                item("mapMaybe"),
+                item("filterMap"),
+                item("main"),
+            ],
+        );
+
+        let labels = |items: Vec<CompletionItem>| -> Vec<String> {
+            items.into_iter().map(|item| item.label).collect()
+        };
+        assert_eq!(
+            labels(cache.filter(&uri, Position::new(4, 8), "mapM").unwrap()),
+            vec!["mapMaybe"]
+        );
+        assert_eq!(
+            labels(cache.filter(&uri, Position::new(4, 7), "MAP").unwrap()),
+            vec!["map", "mapMaybe", "filterMap"]
+        );
+    }
+
+    #[test]
+    fn test_other_words_miss() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let cache = CompletionCache::new(uri.clone(), Position::new(4, 6), "ma", vec![item("map")]);
+
+        // A word starting elsewhere on the line
+        assert!(cache.filter(&uri, Position::new(4, 12), "ma").is_none());
+        assert!(cache.filter(&uri, Position::new(5, 6), "ma").is_none());
+        let other = Url::parse("file:///project/src/Other.gren").unwrap();
+        assert!(cache.filter(&other, Position::new(4, 6), "ma").is_none());
+    }
+}
@@ -0,0 +1,137 @@
+//! `Debug.log` calls, for code lenses showing the values a running program logged
+//!
+//! Only calls with a literal tag can be matched to logged values, so `Debug.log label x`
+//! with a computed label is skipped.
+
+use gren_lsp_core::syntax::{child_of_kind, node_range};
+use lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+/// A `Debug.log "tag"` call
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugLogCall {
+    pub tag: String,
+    /// The call up to and including the tag
+    pub range: Range,
+}
+
+/// Every `Debug.log` call with a literal tag, through `Debug` or the alias it's imported
+/// under, in source order
+pub fn debug_log_calls(tree: &Tree, source: &str) -> Vec<DebugLogCall> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let alias = root
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "import_clause")
+        .find(|import| {
+            child_of_kind(import, "upper_case_qid").and_then(|name| text(name, source))
+                == Some("Debug")
+        })
+        .and_then(|import| child_of_kind(&import, "as_clause"))
+        .and_then(|alias| child_of_kind(&alias, "upper_case_identifier"))
+        .and_then(|alias| text(alias, source))
+        .map(|alias| format!("{}.log", alias));
+
+    let mut calls = Vec::new();
+    collect_calls(root, source, alias.as_deref(), &mut calls);
+    calls
+}
+
+fn collect_calls(node: Node, source: &str, alias: Option<&str>, calls: &mut Vec<DebugLogCall>) {
+    if node.kind() == "function_call_expr" {
+        if let Some(call) = debug_log_call(node, source, alias) {
+            calls.push(call);
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_calls(child, source, alias, calls);
+    }
+}
+
+fn debug_log_call(call: Node, source: &str, alias: Option<&str>) -> Option<DebugLogCall> {
+    let function = text(call.named_child(0)?, source)?;
+    if function != "Debug.log" && Some(function) != alias {
+        return None;
+    }
+
+    let tag = call.named_child(1)?;
+    if tag.kind() != "string_constant_expr" {
+        return None;
+    }
+    let mut cursor = tag.walk();
+    let parts: Vec<Node> = tag
+        .named_children(&mut cursor)
+        .filter(|part| part.kind() == "regular_string_part")
+        .collect();
+    let tag_text = parts
+        .into_iter()
+        .filter_map(|part| text(part, source))
+        .collect::<String>();
+
+    Some(DebugLogCall {
+        tag: tag_text,
+        range: Range::new(
+            node_range(&call, source).start,
+            node_range(&tag, source).end,
+        ),
+    })
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+    use lsp_types::Position;
+
+    fn calls(source: &str) -> Vec<DebugLogCall> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        debug_log_calls(&tree, source)
+    }
+
+    #[test]
+    fn test_literal_tags_in_calls_and_pipelines() {
+        let source = r#"module Main exposing (update)
+
+update msg model =
+    let
+        counted =
+            Debug.log "count" (model.count + 1)
+    in
+    { model | count = counted }
+        |> Debug.log "model"
+"#;
+        assert_eq!(
+            calls(source),
+            vec![
+                DebugLogCall {
+                    tag: "count".to_string(),
+                    range: Range::new(Position::new(5, 12), Position::new(5, 29)),
+                },
+                DebugLogCall {
+                    tag: "model".to_string(),
+                    range: Range::new(Position::new(8, 11), Position::new(8, 28)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alias_and_computed_tags() {
+        let source = r#"module Main exposing (main)
+
+import Debug as D
+
+main =
+    D.log "aliased" (Debug.log label 1)
+"#;
+        let found: Vec<String> = calls(source).into_iter().map(|call| call.tag).collect();
+        assert_eq!(found, vec!["aliased"]);
+    }
+}
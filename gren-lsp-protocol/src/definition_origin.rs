@@ -0,0 +1,115 @@
+//! Where a resolved definition lives: in the workspace, in an installed dependency or in
+//! the JavaScript kernel of a core package
+//!
+//! Only workspace definitions can be edited. Dependencies are read from the package cache
+//! and overwritten when they're reinstalled, and kernel code isn't Gren at all, so renames
+//! reaching either are refused with [`NOT_IN_WORKSPACE`] instead of producing an edit that
+//! touches some references but not the definition.
+
+use crate::package_docs::package_name_for_path;
+use lsp_types::Url;
+use serde_json::json;
+use tower_lsp::jsonrpc::{Error, ErrorCode};
+
+/// Error code of requests refused because their target is defined outside the workspace
+pub const NOT_IN_WORKSPACE: i64 = -32001;
+
+const KERNEL_PREFIX: &str = "Gren.Kernel.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefinitionOrigin {
+    Workspace,
+    /// An installed package, as `author/name`
+    Dependency(String),
+    /// A `Gren.Kernel.*` module, implemented in JavaScript
+    Kernel(String),
+}
+
+impl DefinitionOrigin {
+    /// Classify a definition by the file it's in and the module declaring it
+    pub fn of_definition(uri: &Url, module_name: Option<&str>) -> Self {
+        if let Some(module_name) = module_name.filter(|name| is_kernel_module(name)) {
+            return DefinitionOrigin::Kernel(module_name.to_string());
+        }
+        match uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| package_name_for_path(&path))
+        {
+            Some(package) => DefinitionOrigin::Dependency(package),
+            None => DefinitionOrigin::Workspace,
+        }
+    }
+
+    /// The origin of a name qualified with `module_name`, when the module alone decides it
+    pub fn of_module(module_name: &str) -> Option<Self> {
+        is_kernel_module(module_name).then(|| DefinitionOrigin::Kernel(module_name.to_string()))
+    }
+
+    /// The error refusing to rename `name`, defined here. None for workspace definitions.
+    pub fn rename_refusal(&self, name: &str) -> Option<Error> {
+        let (message, data) = match self {
+            DefinitionOrigin::Workspace => return None,
+            DefinitionOrigin::Dependency(package) => (
+                format!(
+                    "`{}` is defined in package {}; rename not possible",
+                    name, package
+                ),
+                json!({ "origin": "dependency", "package": package }),
+            ),
+            DefinitionOrigin::Kernel(module_name) => (
+                format!(
+                    "`{}` is defined in kernel module {}; rename not possible",
+                    name, module_name
+                ),
+                json!({ "origin": "kernel", "module": module_name }),
+            ),
+        };
+        Some(Error {
+            code: ErrorCode::ServerError(NOT_IN_WORKSPACE),
+            message: message.into(),
+            data: Some(data),
+        })
+    }
+}
+
+fn is_kernel_module(module_name: &str) -> bool {
+    module_name.starts_with(KERNEL_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_by_package_cache_path() {
+        let dependency =
+            Url::parse("file:///home/me/app/.gren/0.4.0/packages/gren-lang/core/src/Array.gren")
+                .unwrap();
+        let origin = DefinitionOrigin::of_definition(&dependency, Some("Array"));
+        assert_eq!(
+            origin,
+            DefinitionOrigin::Dependency("gren-lang/core".to_string())
+        );
+        let error = origin.rename_refusal("map").unwrap();
+        assert_eq!(error.code, ErrorCode::ServerError(NOT_IN_WORKSPACE));
+        assert_eq!(
+            error.message,
+            "`map` is defined in package gren-lang/core; rename not possible"
+        );
+
+        let local = Url::parse("file:///home/me/app/src/Main.gren").unwrap();
+        let origin = DefinitionOrigin::of_definition(&local, Some("Main"));
+        assert_eq!(origin, DefinitionOrigin::Workspace);
+        assert!(origin.rename_refusal("main").is_none());
+    }
+
+    #[test]
+    fn test_kernel_modules() {
+        assert_eq!(
+            DefinitionOrigin::of_module("Gren.Kernel.Bytes"),
+            Some(DefinitionOrigin::Kernel("Gren.Kernel.Bytes".to_string()))
+        );
+        assert_eq!(DefinitionOrigin::of_module("Bytes"), None);
+    }
+}
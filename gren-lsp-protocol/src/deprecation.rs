@@ -0,0 +1,201 @@
+//! Uses of symbols documented as `@deprecated`, for `gren/deprecatedUsages`
+//!
+//! A deprecated name can be referenced qualified through its module or alias, or
+//! unqualified when the import exposes it. Only the module's own file and files importing
+//! it can refer to it at all.
+
+use gren_lsp_core::syntax::{child_of_kind, node_range};
+use lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+/// A deprecated top-level name of a module
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeprecatedName {
+    pub name: String,
+    /// The custom type a constructor belongs to, which importers expose with `Type(..)`
+    pub parent_type: Option<String>,
+}
+
+/// References in a file to `names`, which are all defined in `module_name`, as pairs of
+/// name and range. Definitions, the module declaration and import clauses aren't
+/// references.
+pub fn deprecated_references(
+    tree: &Tree,
+    source: &str,
+    module_name: &str,
+    names: &[DeprecatedName],
+) -> Vec<(String, Range)> {
+    let root = tree.root_node();
+    let own_module = child_of_kind(&root, "module_declaration")
+        .and_then(|declaration| child_of_kind(&declaration, "upper_case_qid"))
+        .and_then(|name| text(name, source))
+        == Some(module_name);
+
+    let (qualifier, unqualified): (String, Vec<&str>) = if own_module {
+        (
+            module_name.to_string(),
+            names.iter().map(|name| name.name.as_str()).collect(),
+        )
+    } else {
+        let import = match find_import(tree, source, module_name) {
+            Some(import) => import,
+            None => return Vec::new(),
+        };
+        let qualifier = child_of_kind(&import, "as_clause")
+            .and_then(|alias| child_of_kind(&alias, "upper_case_identifier"))
+            .and_then(|alias| text(alias, source))
+            .unwrap_or(module_name);
+        let unqualified = match child_of_kind(&import, "exposing_list") {
+            Some(list) => exposed_names(&list, source, names),
+            None => Vec::new(),
+        };
+        (qualifier.to_string(), unqualified)
+    };
+
+    let mut references = Vec::new();
+    collect_references(root, source, &mut |node, reference| {
+        let name = match reference.strip_prefix(qualifier.as_str()) {
+            Some(rest) if rest.starts_with('.') => &rest[1..],
+            _ if unqualified.contains(&reference) => reference,
+            _ => return,
+        };
+        if names.iter().any(|deprecated| deprecated.name == name) {
+            references.push((name.to_string(), node_range(&node, source)));
+        }
+    });
+    references
+}
+
+/// The deprecated names an import's exposing list brings into scope unqualified
+fn exposed_names<'a>(list: &Node, source: &str, names: &'a [DeprecatedName]) -> Vec<&'a str> {
+    if child_of_kind(list, "double_dot").is_some() {
+        return names.iter().map(|name| name.name.as_str()).collect();
+    }
+
+    let mut cursor = list.walk();
+    let items: Vec<Node> = list.named_children(&mut cursor).collect();
+    let exposes = |name: &str, with_constructors: bool| {
+        items.iter().any(|item| match item.kind() {
+            "exposed_value" | "exposed_operator" => {
+                !with_constructors && text(*item, source) == Some(name)
+            }
+            "exposed_type" => {
+                child_of_kind(item, "upper_case_identifier").and_then(|n| text(n, source))
+                    == Some(name)
+                    && (!with_constructors
+                        || child_of_kind(item, "exposed_union_constructors").is_some())
+            }
+            _ => false,
+        })
+    };
+
+    names
+        .iter()
+        .filter(|deprecated| match &deprecated.parent_type {
+            Some(parent_type) => exposes(parent_type, true),
+            None => exposes(&deprecated.name, false),
+        })
+        .map(|deprecated| deprecated.name.as_str())
+        .collect()
+}
+
+/// Call `found` with every value and type reference outside imports and the module
+/// declaration
+fn collect_references<'a>(node: Node<'a>, source: &'a str, found: &mut impl FnMut(Node, &str)) {
+    match node.kind() {
+        "import_clause" | "module_declaration" => return,
+        "value_qid" | "upper_case_qid" => {
+            if let Some(reference) = text(node, source) {
+                found(node, reference);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_references(child, source, found);
+    }
+}
+
+fn find_import<'a>(tree: &'a Tree, source: &str, module_name: &str) -> Option<Node<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let import = root.named_children(&mut cursor).find(|node| {
+        node.kind() == "import_clause"
+            && child_of_kind(node, "upper_case_qid").and_then(|name| text(name, source))
+                == Some(module_name)
+    });
+    import
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+    use lsp_types::Position;
+
+    fn references(source: &str, names: &[DeprecatedName]) -> Vec<(String, Range)> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        deprecated_references(&tree, source, "Shapes", names)
+    }
+
+    fn deprecated(name: &str, parent_type: Option<&str>) -> DeprecatedName {
+        DeprecatedName {
+            name: name.to_string(),
+            parent_type: parent_type.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_qualified_and_exposed_references() {
+        let source = r#"module Main exposing (main)
+
+import Shapes as S exposing (Shape(..), oldArea)
+
+main =
+    oldArea (S.oldArea Square) (S.area Square)
+"#;
+        let found = references(
+            source,
+            &[
+                deprecated("oldArea", None),
+                deprecated("Square", Some("Shape")),
+            ],
+        );
+        let found: Vec<_> = found
+            .iter()
+            .map(|(name, range)| (name.as_str(), range.start))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("oldArea", Position::new(5, 4)),
+                ("oldArea", Position::new(5, 13)),
+                ("Square", Position::new(5, 23)),
+                ("Square", Position::new(5, 39)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unexposed_and_unimported_names_are_not_references() {
+        let exposing_nothing = r#"module Main exposing (main)
+
+import Shapes
+
+main =
+    oldArea 1
+"#;
+        assert!(references(exposing_nothing, &[deprecated("oldArea", None)]).is_empty());
+
+        let not_imported = "module Main exposing (main)\n\nmain =\n    Shapes.oldArea 1\n";
+        assert!(references(not_imported, &[deprecated("oldArea", None)]).is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+//! Reading and editing exposing lists
+//!
+//! Removing a name from `module Foo exposing (...)` breaks every module that still imports
+//! it with `import Foo exposing (name)`. These helpers find those imports and build the
+//! edits to either switch the importer to qualified access or expose the name again. They
+//! also list a module's top-level declarations and rewrite its exposing list, in bulk for
+//! the exports code lens or one declaration at a time for the expose and hide actions.
+
+use crate::lsp_ext::ExportKind;
+use gren_lsp_core::syntax::{child_of_kind, node_range, point_to_position};
+use lsp_types::{Range, TextEdit};
+use tree_sitter::{Node, Tree};
+
+/// What a module's own declaration exposes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleExposing {
+    pub module_name: String,
+    /// `exposing (..)`, which exposes every top-level declaration
+    pub everything: bool,
+    /// Names in the exposing list, with `(..)` stripped from types
+    pub names: Vec<String>,
+    /// The `exposing (...)` list, including its parentheses
+    pub list_range: Range,
+}
+
+impl ModuleExposing {
+    pub fn exposes(&self, name: &str) -> bool {
+        self.everything || self.names.iter().any(|exposed| exposed == name)
+    }
+
+    /// Edit adding `name` back to the end of the exposing list
+    pub fn expose_edit(&self, name: &str) -> Option<TextEdit> {
+        if self.everything {
+            return None;
+        }
+
+        // Insert just before the closing parenthesis
+        let mut end = self.list_range.end;
+        end.character = end.character.checked_sub(1)?;
+        let new_text = if self.names.is_empty() {
+            name.to_string()
+        } else {
+            format!(", {}", name)
+        };
+        Some(TextEdit::new(Range::new(end, end), new_text))
+    }
+}
+
+/// A name one import clause exposes from another module
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedName {
+    pub name: String,
+    /// Whether the import also exposes the type's constructors, `Name(..)`
+    pub with_constructors: bool,
+}
+
+/// Read the module declaration's exposing list
+pub fn module_exposing(tree: &Tree, source: &str) -> Option<ModuleExposing> {
+    let root = tree.root_node();
+    let declaration = child_of_kind(&root, "module_declaration")?;
+    let module_name = text(child_of_kind(&declaration, "upper_case_qid")?, source)?;
+    let list = child_of_kind(&declaration, "exposing_list")?;
+
+    Some(ModuleExposing {
+        module_name: module_name.to_string(),
+        everything: child_of_kind(&list, "double_dot").is_some(),
+        names: exposed_items(&list)
+            .into_iter()
+            .filter_map(|item| item_name(item, source))
+            .collect(),
+        list_range: node_range(&list, source),
+    })
+}
+
+/// A top-level declaration a module can expose
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopLevelDeclaration {
+    pub name: String,
+    pub kind: ExportKind,
+}
+
+/// The module's top-level declarations, in source order
+pub fn top_level_declarations(tree: &Tree, source: &str) -> Vec<TopLevelDeclaration> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declarations = root
+        .named_children(&mut cursor)
+        .filter_map(|node| top_level_declaration(node, source))
+        .collect();
+    declarations
+}
+
+/// The top-level declaration on zero-based `line`, a value's annotation counting as part
+/// of the value
+pub fn declaration_on_line(tree: &Tree, source: &str, line: u32) -> Option<TopLevelDeclaration> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let node = root.named_children(&mut cursor).find(|node| {
+        node.start_position().row <= line as usize && line as usize <= node.end_position().row
+    })?;
+    if node.kind() == "type_annotation" {
+        return Some(TopLevelDeclaration {
+            name: text(child_of_kind(&node, "lower_case_identifier")?, source)?.to_string(),
+            kind: ExportKind::Value,
+        });
+    }
+    top_level_declaration(node, source)
+}
+
+fn top_level_declaration(node: Node, source: &str) -> Option<TopLevelDeclaration> {
+    let (name, kind) = match node.kind() {
+        "value_declaration" => (
+            child_of_kind(&node, "function_declaration_left")
+                .and_then(|left| child_of_kind(&left, "lower_case_identifier")),
+            ExportKind::Value,
+        ),
+        "type_declaration" => (
+            child_of_kind(&node, "upper_case_identifier"),
+            ExportKind::Type,
+        ),
+        "type_alias_declaration" => (
+            child_of_kind(&node, "upper_case_identifier"),
+            ExportKind::Alias,
+        ),
+        "port_annotation" => (
+            child_of_kind(&node, "lower_case_identifier"),
+            ExportKind::Port,
+        ),
+        _ => return None,
+    };
+    Some(TopLevelDeclaration {
+        name: text(name?, source)?.to_string(),
+        kind,
+    })
+}
+
+/// Edit rewriting the module's exposing list to add `expose` and drop `unexpose`, given by
+/// name. `exposing (..)` becomes the explicit list of what's left of the declarations,
+/// with every type's constructors. Types already in the list keep their `(..)` or lack of
+/// it, and newly exposed types are exposed without constructors.
+///
+/// `None` when the list wouldn't change, or would be left empty, which Gren doesn't allow.
+pub fn edit_exposing(
+    tree: &Tree,
+    source: &str,
+    expose: &[String],
+    unexpose: &[String],
+) -> Option<TextEdit> {
+    let declaration = child_of_kind(&tree.root_node(), "module_declaration")?;
+    let list = child_of_kind(&declaration, "exposing_list")?;
+    let open = child_of_kind(&list, "(")?;
+    let close = child_of_kind(&list, ")")?;
+
+    let mut items: Vec<(String, String)> = if child_of_kind(&list, "double_dot").is_some() {
+        top_level_declarations(tree, source)
+            .into_iter()
+            .map(|declaration| {
+                let item = match declaration.kind {
+                    ExportKind::Type => format!("{}(..)", declaration.name),
+                    _ => declaration.name.clone(),
+                };
+                (declaration.name, item)
+            })
+            .collect()
+    } else {
+        exposed_items(&list)
+            .into_iter()
+            .filter_map(|item| Some((item_name(item, source)?, text(item, source)?.to_string())))
+            .collect()
+    };
+    items.retain(|(name, _)| !unexpose.contains(name));
+    for name in expose {
+        if !items.iter().any(|(exposed, _)| exposed == name) {
+            items.push((name.clone(), name.clone()));
+        }
+    }
+    if items.is_empty() {
+        return None;
+    }
+
+    let new_text = format!(
+        "({})",
+        items
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let range = Range::new(
+        point_to_position(open.start_position(), source),
+        point_to_position(close.end_position(), source),
+    );
+    if source.get(open.start_byte()..close.end_byte()) == Some(new_text.as_str()) {
+        return None;
+    }
+    Some(TextEdit::new(range, new_text))
+}
+
+/// Names the file imports from `module_name` through an exposing list
+pub fn imported_names(tree: &Tree, source: &str, module_name: &str) -> Vec<ImportedName> {
+    let import = match find_import(tree, source, module_name) {
+        Some(import) => import,
+        None => return Vec::new(),
+    };
+    let list = match child_of_kind(&import, "exposing_list") {
+        Some(list) => list,
+        None => return Vec::new(),
+    };
+
+    exposed_items(&list)
+        .into_iter()
+        .filter_map(|item| {
+            Some(ImportedName {
+                name: item_name(item, source)?,
+                with_constructors: child_of_kind(&item, "exposed_union_constructors").is_some(),
+            })
+        })
+        .collect()
+}
+
+/// Edits switching a file from `import Module exposing (name)` to qualified access: the
+/// name leaves the import's exposing list and every unqualified use gets the module's
+/// alias, or its full name, as a prefix. `constructors` are qualified along with it when
+/// the import exposed them through `Name(..)`.
+pub fn qualify_imported_name(
+    tree: &Tree,
+    source: &str,
+    module_name: &str,
+    name: &str,
+    constructors: &[String],
+) -> Vec<TextEdit> {
+    let import = match find_import(tree, source, module_name) {
+        Some(import) => import,
+        None => return Vec::new(),
+    };
+    let list = match child_of_kind(&import, "exposing_list") {
+        Some(list) => list,
+        None => return Vec::new(),
+    };
+
+    let items = exposed_items(&list);
+    let index = match items
+        .iter()
+        .position(|item| item_name(*item, source).as_deref() == Some(name))
+    {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+
+    let removal = if items.len() == 1 {
+        // Drop ` exposing (name)` entirely
+        let previous = list.prev_sibling().unwrap_or(import);
+        Range::new(
+            point_to_position(previous.end_position(), source),
+            point_to_position(list.end_position(), source),
+        )
+    } else if let Some(next) = items.get(index + 1) {
+        Range::new(
+            point_to_position(items[index].start_position(), source),
+            point_to_position(next.start_position(), source),
+        )
+    } else {
+        Range::new(
+            point_to_position(items[index - 1].end_position(), source),
+            point_to_position(items[index].end_position(), source),
+        )
+    };
+
+    let qualifier = child_of_kind(&import, "as_clause")
+        .and_then(|alias| child_of_kind(&alias, "upper_case_identifier"))
+        .and_then(|alias| text(alias, source))
+        .unwrap_or(module_name);
+
+    let mut edits = vec![TextEdit::new(removal, String::new())];
+    collect_unqualified_uses(tree.root_node(), source, name, constructors, &mut |node| {
+        let start = point_to_position(node.start_position(), source);
+        edits.push(TextEdit::new(
+            Range::new(start, start),
+            format!("{}.", qualifier),
+        ));
+    });
+    edits
+}
+
+/// Constructors of the custom type `type_name` declared in this module
+pub fn type_constructors(tree: &Tree, source: &str, type_name: &str) -> Vec<String> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declaration = root.named_children(&mut cursor).find(|node| {
+        node.kind() == "type_declaration"
+            && child_of_kind(node, "upper_case_identifier").and_then(|name| text(name, source))
+                == Some(type_name)
+    });
+    let declaration = match declaration {
+        Some(declaration) => declaration,
+        None => return Vec::new(),
+    };
+
+    let mut cursor = declaration.walk();
+    let constructors = declaration
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "union_variant")
+        .filter_map(|variant| child_of_kind(&variant, "upper_case_identifier"))
+        .filter_map(|name| text(name, source).map(str::to_string))
+        .collect();
+    constructors
+}
+
+/// Call `found` for every unqualified reference to `name` or one of `constructors`.
+/// Gren doesn't allow shadowing, so an unqualified lower case reference with the same
+/// name can only be the imported value.
+fn collect_unqualified_uses(
+    node: Node,
+    source: &str,
+    name: &str,
+    constructors: &[String],
+    found: &mut impl FnMut(Node),
+) {
+    match node.kind() {
+        "import_clause" | "module_declaration" => return,
+        "value_qid" | "upper_case_qid" => {
+            if let Some(reference) = text(node, source) {
+                if reference == name || constructors.iter().any(|c| c == reference) {
+                    found(node);
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_unqualified_uses(child, source, name, constructors, found);
+    }
+}
+
+fn find_import<'a>(tree: &'a Tree, source: &str, module_name: &str) -> Option<Node<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let import = root.named_children(&mut cursor).find(|node| {
+        node.kind() == "import_clause"
+            && child_of_kind(node, "upper_case_qid").and_then(|name| text(name, source))
+                == Some(module_name)
+    });
+    import
+}
+
+fn exposed_items<'a>(list: &Node<'a>) -> Vec<Node<'a>> {
+    let mut cursor = list.walk();
+    let items = list
+        .named_children(&mut cursor)
+        .filter(|item| {
+            matches!(
+                item.kind(),
+                "exposed_value" | "exposed_type" | "exposed_operator"
+            )
+        })
+        .collect();
+    items
+}
+
+/// Name of an exposed item, without a type's `(..)`
+fn item_name(item: Node, source: &str) -> Option<String> {
+    match item.kind() {
+        "exposed_type" => {
+            let name = child_of_kind(&item, "upper_case_identifier")?;
+            text(name, source).map(str::to_string)
+        }
+        _ => text(item, source).map(str::to_string),
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+    use lsp_types::Position;
+
+    fn parse(source: &str) -> Tree {
+        let mut parser = Parser::new().unwrap();
+        parser.parse(source).unwrap().unwrap()
+    }
+
+    /// Apply non-overlapping edits to single-line-per-edit ranges, last first
+    fn apply(source: &str, mut edits: Vec<TextEdit>) -> String {
+        let offset = |position: Position| {
+            source
+                .lines()
+                .take(position.line as usize)
+                .map(|line| line.len() + 1)
+                .sum::<usize>()
+                + position.character as usize
+        };
+        edits.sort_by_key(|edit| std::cmp::Reverse(offset(edit.range.start)));
+
+        let mut result = source.to_string();
+        for edit in edits {
+            result.replace_range(
+                offset(edit.range.start)..offset(edit.range.end),
+                &edit.new_text,
+            );
+        }
+        result
+    }
+
+    #[test]
+    fn test_module_exposing_and_expose_edit() {
+        let source = "module Shapes exposing (Shape(..), area)\n\narea s = 1\n";
+        let tree = parse(source);
+
+        let exposing = module_exposing(&tree, source).unwrap();
+        assert_eq!(exposing.module_name, "Shapes");
+        assert_eq!(exposing.names, vec!["Shape", "area"]);
+        assert!(!exposing.exposes("perimeter"));
+
+        let edit = exposing.expose_edit("perimeter").unwrap();
+        assert_eq!(
+            apply(source, vec![edit]),
+            "module Shapes exposing (Shape(..), area, perimeter)\n\narea s = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_exposing() {
+        let source = "module Shapes exposing (Shape(..), area)\n\ntype Shape = Circle Float\n\ntype alias Size = Float\n\narea s = 1\n\nperimeter s = 2\n";
+        let tree = parse(source);
+
+        assert_eq!(
+            top_level_declarations(&tree, source),
+            vec![
+                TopLevelDeclaration {
+                    name: "Shape".to_string(),
+                    kind: ExportKind::Type,
+                },
+                TopLevelDeclaration {
+                    name: "Size".to_string(),
+                    kind: ExportKind::Alias,
+                },
+                TopLevelDeclaration {
+                    name: "area".to_string(),
+                    kind: ExportKind::Value,
+                },
+                TopLevelDeclaration {
+                    name: "perimeter".to_string(),
+                    kind: ExportKind::Value,
+                },
+            ]
+        );
+
+        let edit = edit_exposing(
+            &tree,
+            source,
+            &["perimeter".to_string(), "Size".to_string()],
+            &["area".to_string()],
+        )
+        .unwrap();
+        assert!(apply(source, vec![edit])
+            .starts_with("module Shapes exposing (Shape(..), perimeter, Size)\n"));
+
+        assert_eq!(
+            edit_exposing(&tree, source, &["area".to_string()], &[]),
+            None
+        );
+        let everything = ["Shape", "area"].map(str::to_string);
+        assert_eq!(edit_exposing(&tree, source, &[], &everything), None);
+
+        let source = source.replace("(Shape(..), area)", "(..)");
+        let tree = parse(&source);
+        let edit = edit_exposing(&tree, &source, &[], &["perimeter".to_string()]).unwrap();
+        assert!(apply(&source, vec![edit])
+            .starts_with("module Shapes exposing (Shape(..), Size, area)\n"));
+    }
+
+    #[test]
+    fn test_declaration_on_line() {
+        let source = "module Shapes exposing (area)\n\ntype Shape = Circle Float\n\n\
+                      area : Shape -> Float\narea s =\n    1\n";
+        let tree = parse(source);
+        let name = |line| declaration_on_line(&tree, source, line).map(|found| found.name);
+        assert_eq!(name(0), None);
+        assert_eq!(name(2), Some("Shape".to_string()));
+        assert_eq!(name(3), None);
+        // The annotation belongs to the value
+        assert_eq!(name(4), Some("area".to_string()));
+        assert_eq!(name(6), Some("area".to_string()));
+    }
+
+    #[test]
+    fn test_qualify_imported_name() {
+        let source = r#"module Main exposing (main)
+
+import Shapes as S exposing (Shape(..), area)
+
+main =
+    area (Circle 2)
+"#;
+        let tree = parse(source);
+        assert_eq!(
+            imported_names(&tree, source, "Shapes"),
+            vec![
+                ImportedName {
+                    name: "Shape".to_string(),
+                    with_constructors: true,
+                },
+                ImportedName {
+                    name: "area".to_string(),
+                    with_constructors: false,
+                },
+            ]
+        );
+
+        let edits = qualify_imported_name(&tree, source, "Shapes", "area", &[]);
+        assert_eq!(
+            apply(source, edits),
+            r#"module Main exposing (main)
+
+import Shapes as S exposing (Shape(..))
+
+main =
+    S.area (Circle 2)
+"#
+        );
+
+        let constructors = vec!["Circle".to_string()];
+        let edits = qualify_imported_name(&tree, source, "Shapes", "Shape", &constructors);
+        assert_eq!(
+            apply(source, edits),
+            r#"module Main exposing (main)
+
+import Shapes as S exposing (area)
+
+main =
+    area (S.Circle 2)
+"#
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Renaming a record field across the workspace
+//!
+//! Without type information a field can't be tied to a single record type, so a rename
+//! changes every field of that name: in record types, record literals and updates,
+//! `.field` accesses and accessors, and record patterns. A pattern binding the field by
+//! its own name, `{ count }`, becomes `{ total = count }` so the variable keeps its name.
+
+use gren_lsp_core::syntax::{child_of_kind, node_range, position_to_point};
+use lsp_types::{Position, TextEdit};
+use tree_sitter::{Node, Tree};
+
+/// Name of the record field at `position`, if the cursor is on one
+pub fn field_at(tree: &Tree, source: &str, position: Position) -> Option<String> {
+    let node = identifier_at(tree, source, position)?;
+    if !is_field_name(node) {
+        return None;
+    }
+    text(node, source).map(str::to_string)
+}
+
+/// Edits renaming every occurrence of the field `field` in a file to `new_name`
+pub fn field_edits(tree: &Tree, source: &str, field: &str, new_name: &str) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    collect_edits(tree.root_node(), source, field, new_name, &mut edits);
+    edits
+}
+
+fn collect_edits(node: Node, source: &str, field: &str, new_name: &str, edits: &mut Vec<TextEdit>) {
+    let rename = |name: Node| {
+        (text(name, source) == Some(field))
+            .then(|| TextEdit::new(node_range(&name, source), new_name.to_string()))
+    };
+
+    match node.kind() {
+        "field_type" | "field" => {
+            edits.extend(child_of_kind(&node, "lower_case_identifier").and_then(rename));
+        }
+        "field_access_expr" | "field_accessor_function_expr" => {
+            let mut cursor = node.walk();
+            edits.extend(
+                node.named_children(&mut cursor)
+                    .filter(|child| child.kind() == "lower_case_identifier")
+                    .filter_map(rename),
+            );
+        }
+        "record_pattern" => {
+            let mut cursor = node.walk();
+            for entry in node.named_children(&mut cursor) {
+                let edit = match entry.kind() {
+                    // `{ count }` binds `count`, which keeps its name
+                    "lower_pattern" => (text(entry, source) == Some(field)).then(|| {
+                        TextEdit::new(
+                            node_range(&entry, source),
+                            format!("{} = {}", new_name, field),
+                        )
+                    }),
+                    "lower_case_identifier" => rename(entry),
+                    // `{ count = n }`
+                    _ => entry
+                        .named_child(0)
+                        .filter(|name| name.kind() == "lower_case_identifier")
+                        .and_then(rename),
+                };
+                edits.extend(edit);
+            }
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_edits(child, source, field, new_name, edits);
+    }
+}
+
+/// Whether an identifier names a field rather than a value
+fn is_field_name(node: Node) -> bool {
+    let parent = match node.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    match parent.kind() {
+        "field_type" | "field" => child_of_kind(&parent, "lower_case_identifier") == Some(node),
+        "field_access_expr" | "field_accessor_function_expr" | "record_pattern" => true,
+        // The label of `{ count = n }` in a record pattern
+        _ => {
+            parent.kind() != "lower_pattern"
+                && parent.parent().map(|grandparent| grandparent.kind()) == Some("record_pattern")
+                && parent.named_child(0) == Some(node)
+        }
+    }
+}
+
+/// The identifier at `position`, or the one ending there when the cursor is right after it
+fn identifier_at<'a>(tree: &'a Tree, source: &str, position: Position) -> Option<Node<'a>> {
+    let point = position_to_point(position, source);
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
+    if node.kind() == "lower_case_identifier" {
+        return Some(node);
+    }
+    if position.character == 0 {
+        return None;
+    }
+    let before = position_to_point(Position::new(position.line, position.character - 1), source);
+    tree.root_node()
+        .descendant_for_point_range(before, before)
+        .filter(|previous| previous.kind() == "lower_case_identifier")
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    const SOURCE: &str = r#"module Counter exposing (..)
+
+type alias Model =
+    { count : Int
+    , step : Int
+    }
+
+increment : Model -> Model
+increment model =
+    { model | count = model.count + model.step }
+
+counts : Array Model -> Array Int
+counts models =
+    Array.map .count models
+
+describe : Model -> String
+describe { count } =
+    String.fromInt count
+"#;
+
+    fn parse() -> Tree {
+        let mut parser = Parser::new().unwrap();
+        parser.parse(SOURCE).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_field_at() {
+        let tree = parse();
+        let field = |line, character| field_at(&tree, SOURCE, Position::new(line, character));
+        assert_eq!(field(3, 8), Some("count".to_string()));
+        assert_eq!(field(9, 16), Some("count".to_string()));
+        assert_eq!(field(9, 29), Some("count".to_string()));
+        assert_eq!(field(13, 15), Some("count".to_string()));
+        // Values aren't fields
+        assert_eq!(field(9, 23), None);
+        assert_eq!(field(8, 2), None);
+    }
+
+    #[test]
+    fn test_field_edits() {
+        let tree = parse();
+        let edits: Vec<(u32, u32, String)> = field_edits(&tree, SOURCE, "count", "total")
+            .into_iter()
+            .map(|edit| {
+                (
+                    edit.range.start.line,
+                    edit.range.start.character,
+                    edit.new_text,
+                )
+            })
+            .collect();
+        assert_eq!(
+            edits,
+            vec![
+                (3, 6, "total".to_string()),
+                (9, 14, "total".to_string()),
+                (9, 28, "total".to_string()),
+                (13, 15, "total".to_string()),
+                (16, 11, "total = count".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn test_fields_after_non_ascii_text() {
+        let source = "module Main exposing (..)\n\ngreet model =\n    \"héllo 😀\" ++ model.name\n";
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        // Columns count UTF-16 code units: é is one and 😀 two, though they take six bytes
+        assert_eq!(
+            field_at(&tree, source, Position::new(3, 25)),
+            Some("name".to_string())
+        );
+        let edits = field_edits(&tree, source, "name", "title");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position::new(3, 24));
+        assert_eq!(edits[0].range.end, Position::new(3, 28));
+    }
+}
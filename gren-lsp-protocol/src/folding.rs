@@ -0,0 +1,262 @@
+//! Folding ranges for declarations, imports, comments and `when ... is` expressions
+//!
+//! Every branch of a `when` gets its own range, labelled with its pattern when the client
+//! can show collapsed text, so a long `update` function folds down to one line per message.
+
+use lsp_types::{FoldingRange, FoldingRangeKind};
+use tree_sitter::{Node, Tree};
+
+/// How the client displays folding ranges
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FoldingOptions {
+    /// Label collapsed `when` expressions and branches with their subject and pattern
+    pub collapsed_text: bool,
+    /// Most ranges the client wants for one document
+    pub range_limit: Option<usize>,
+}
+
+/// Folding ranges for a document, ordered by start line. Where several ranges start on the
+/// same line only the outermost is kept, since clients can only fold one range per line.
+pub fn folding_ranges(tree: &Tree, source: &str, options: FoldingOptions) -> Vec<FoldingRange> {
+    let root = tree.root_node();
+    let mut ranges = Vec::new();
+
+    let mut cursor = root.walk();
+    let children: Vec<Node> = root.named_children(&mut cursor).collect();
+
+    if let (Some(first), Some(last)) = (
+        children.iter().find(|node| node.kind() == "import_clause"),
+        children
+            .iter()
+            .rev()
+            .find(|node| node.kind() == "import_clause"),
+    ) {
+        push_range(
+            &mut ranges,
+            first.start_position().row,
+            last.end_position().row,
+            Some(FoldingRangeKind::Imports),
+            None,
+        );
+    }
+
+    collect_comment_ranges(&children, &mut ranges);
+    for child in &children {
+        collect_ranges(*child, source, options, &mut ranges);
+    }
+
+    ranges.sort_by_key(|range| range.start_line);
+    ranges.dedup_by_key(|range| range.start_line);
+    if let Some(limit) = options.range_limit {
+        ranges.truncate(limit);
+    }
+    ranges
+}
+
+fn collect_ranges(
+    node: Node,
+    source: &str,
+    options: FoldingOptions,
+    ranges: &mut Vec<FoldingRange>,
+) {
+    let start = node.start_position().row;
+    let end = node.end_position().row;
+
+    match node.kind() {
+        "value_declaration" | "type_declaration" | "type_alias_declaration" | "let_in_expr" => {
+            push_range(ranges, start, end, None, None);
+        }
+        "case_of_expr" => {
+            let label = options
+                .collapsed_text
+                .then(|| case_label(node, source))
+                .flatten();
+            push_range(ranges, start, end, None, label);
+        }
+        "case_of_branch" => {
+            let label = options
+                .collapsed_text
+                .then(|| branch_label(node, source))
+                .flatten();
+            push_range(ranges, start, end, None, label);
+        }
+        "block_comment" => {
+            push_range(ranges, start, end, Some(FoldingRangeKind::Comment), None);
+            return;
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.named_children(&mut cursor).collect();
+    collect_comment_ranges(&children, ranges);
+    for child in children {
+        collect_ranges(child, source, options, ranges);
+    }
+}
+
+/// Runs of line comments on consecutive lines fold together
+fn collect_comment_ranges(siblings: &[Node], ranges: &mut Vec<FoldingRange>) {
+    let mut run: Option<(usize, usize)> = None;
+
+    for node in siblings {
+        if node.kind() != "line_comment" {
+            if let Some((start, end)) = run.take() {
+                push_range(ranges, start, end, Some(FoldingRangeKind::Comment), None);
+            }
+            continue;
+        }
+
+        let row = node.start_position().row;
+        run = match run {
+            Some((start, end)) if row == end + 1 => Some((start, row)),
+            Some((start, end)) => {
+                push_range(ranges, start, end, Some(FoldingRangeKind::Comment), None);
+                Some((row, row))
+            }
+            None => Some((row, row)),
+        };
+    }
+
+    if let Some((start, end)) = run {
+        push_range(ranges, start, end, Some(FoldingRangeKind::Comment), None);
+    }
+}
+
+fn push_range(
+    ranges: &mut Vec<FoldingRange>,
+    start: usize,
+    end: usize,
+    kind: Option<FoldingRangeKind>,
+    collapsed_text: Option<String>,
+) {
+    // A range on a single line has nothing to hide
+    if end <= start {
+        return;
+    }
+
+    ranges.push(FoldingRange {
+        start_line: start as u32,
+        start_character: None,
+        end_line: end as u32,
+        end_character: None,
+        kind,
+        collapsed_text,
+    });
+}
+
+/// `when msg is …` for a collapsed `when` expression
+fn case_label(node: Node, source: &str) -> Option<String> {
+    if node.has_error() {
+        return None;
+    }
+
+    let mut cursor = node.walk();
+    let subject = node
+        .named_children(&mut cursor)
+        .find(|child| child.kind() != "case_of_branch" && !is_comment(child))?;
+    Some(format!("when {} is …", node_label(subject, source)?))
+}
+
+/// `Increment -> …` for a collapsed branch
+fn branch_label(node: Node, source: &str) -> Option<String> {
+    if node.has_error() {
+        return None;
+    }
+
+    let pattern = node.child_by_field_name("pattern").or_else(|| {
+        let mut cursor = node.walk();
+        let first = node
+            .named_children(&mut cursor)
+            .find(|child| !is_comment(child));
+        first
+    })?;
+    Some(format!("{} -> …", node_label(pattern, source)?))
+}
+
+/// A node's source text on one line, with line breaks and indentation collapsed
+fn node_label(node: Node, source: &str) -> Option<String> {
+    let text = node.utf8_text(source.as_bytes()).ok()?;
+    Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn is_comment(node: &Node) -> bool {
+    matches!(node.kind(), "line_comment" | "block_comment")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    const SOURCE: &str = r#"module Main exposing (..)
+
+import Array
+import Html
+import Json.Decode
+
+-- Messages the page handles
+-- while it's open
+type Msg
+    = Increment
+    | SetName String
+
+
+update msg model =
+    when msg is
+        Increment ->
+            { model | count = model.count + 1 }
+
+        SetName
+            name ->
+            { model | name = name }
+"#;
+
+    fn ranges_for(options: FoldingOptions) -> Vec<FoldingRange> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        folding_ranges(&tree, SOURCE, options)
+    }
+
+    #[test]
+    fn test_folding_ranges_with_branch_labels() {
+        let ranges = ranges_for(FoldingOptions {
+            collapsed_text: true,
+            range_limit: None,
+        });
+        let summary: Vec<_> = ranges
+            .iter()
+            .map(|range| {
+                (
+                    range.start_line,
+                    range.end_line,
+                    range.kind.clone(),
+                    range.collapsed_text.as_deref(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (2, 4, Some(FoldingRangeKind::Imports), None),
+                (6, 7, Some(FoldingRangeKind::Comment), None),
+                (8, 10, None, None),
+                (13, 20, None, None),
+                (14, 20, None, Some("when msg is …")),
+                (15, 16, None, Some("Increment -> …")),
+                (18, 20, None, Some("SetName name -> …")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_folding_without_collapsed_text_support() {
+        let ranges = ranges_for(FoldingOptions {
+            collapsed_text: false,
+            range_limit: Some(3),
+        });
+        assert_eq!(ranges.len(), 3);
+        assert!(ranges.iter().all(|range| range.collapsed_text.is_none()));
+    }
+}
@@ -1,6 +1,77 @@
 #![allow(deprecated)]
-use gren_lsp_core::{Symbol as GrenSymbol, Workspace};
+use crate::call_hierarchy::{call_item, incoming_calls, item_module, outgoing_calls};
+use crate::call_snippets::{argument_arities, call_context, call_snippet, CallContext};
+use crate::case_branches::{
+    branches_text, missing_constructors, parameter_type, pattern_constructor, result_type_name,
+    when_expression_at, BranchConstructor, WhenExpression,
+};
+use crate::code_actions::removal_fixes;
+use crate::completion_cache::{CompletionCache, SharedCompletionCache};
+use crate::debug_log::debug_log_calls;
+use crate::definition_origin::DefinitionOrigin;
+use crate::deprecation::{deprecated_references, DeprecatedName};
+use crate::exposing::{
+    declaration_on_line, edit_exposing, imported_names, module_exposing, qualify_imported_name,
+    top_level_declarations, type_constructors, ModuleExposing,
+};
+use crate::field_rename::{field_at, field_edits};
+use crate::folding::{folding_ranges, FoldingOptions};
+use crate::hint_fixes::hint_fixes;
+use crate::inlay_hints::{
+    calls, has_confusable_parameters, parameter_hints, parameter_names, type_hint,
+    unannotated_declarations, Call,
+};
+use crate::inline_completion::{
+    branches_completion, import_line, import_prefix, record_update_at, record_update_text,
+};
+use crate::locals::{local_binding_at, local_occurrences};
+use crate::lsp_ext::{
+    CapabilitiesReportResult, DeprecatedSymbolUsages, DocSearchResult, EditExposingArguments,
+    FeatureReport, FileTodos, FindValuesOfTypeParams, InlineCompletionItem, InlineCompletionParams,
+    MetricsParams, ModuleExport, ModuleExportsParams, ModuleExportsResult, ModuleMatch,
+    ModuleMetricsReport, MoveDeclarationArguments, OpenModuleParams, SearchDocsParams, Todo,
+    ValueOfType, EDIT_EXPOSING_COMMAND, FORMAT_WORKSPACE_COMMAND, MANAGE_EXPORTS_COMMAND,
+    MOVE_DECLARATION_COMMAND, SOURCE_MOVE_DECLARATION, SOURCE_SORT_DECLARATIONS,
+};
+use crate::module_hover::{module_hover_content, qualifier_at};
+use crate::module_search::matching_modules;
+use crate::package_docs::{docs_url_for_symbol, package_name_for_path};
+use crate::pattern_completion::{expected_pattern_type, nested_pattern_path};
+use crate::qualified_completion::qualifier_before;
+use crate::rename::{rename_range, rename_rejected, REQUEST_FAILED};
+use crate::selection_range::selection_range;
+use crate::semantic_tokens::{semantic_tokens, tokens_delta, SharedSemanticTokens};
+use crate::settings::{FunctionInsert, ServerSettings};
+use crate::signature_help::{
+    application_at, signature_help as build_signature_help, signature_parameters,
+};
+use crate::type_hierarchy::{constructors_of, referring_types, type_item, type_item_module};
+use gren_lsp_core::alias_cycles::RECURSIVE_ALIAS;
+use gren_lsp_core::declaration_order::{declaration_at, move_declaration, sort_declarations};
+use gren_lsp_core::formatter::{edits_in_range, minimal_edits, UNFORMATTED};
+use gren_lsp_core::import_conflicts::{
+    conflict_resolutions, import_conflicts, imports, IMPORT_CONFLICT,
+};
+use gren_lsp_core::imports::{organize_imports_with_rewrites, tidy_imports, IMPORT_STYLE_MISMATCH};
+use gren_lsp_core::indentation::{indentation_edit, keyword_indentation, new_line_indentation};
+use gren_lsp_core::inferred_types::{
+    annotation_text, unannotated_value, unannotated_value_at, UnannotatedValue,
+};
+use gren_lsp_core::lints::UNUSED_EXPOSED;
+use gren_lsp_core::manifest::exposed_module_entries;
+use gren_lsp_core::popular_symbols::POPULAR_PER_MODULE;
+use gren_lsp_core::record_updates::field_position;
+use gren_lsp_core::shadowing::SHADOWED_NAME;
+use gren_lsp_core::source_file::read_source;
+use gren_lsp_core::symbol::{alternate_function_range, declaration_ranges, function_name_ranges};
+use gren_lsp_core::syntax::{node_range, offset_to_position};
+use gren_lsp_core::{
+    alias_cycles, missing_patterns, organize_imports, shadowed_bindings, CompileReport, Import,
+    ImportConflict, ImportSection, IndexStats, ModuleFilter, ModuleMetrics, MoveDirection,
+    Symbol as GrenSymbol, SymbolQuery, Workspace,
+};
 use lsp_types::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tower_lsp::jsonrpc::Result;
@@ -13,13 +84,99 @@ struct SymbolAtPosition {
     module_path: Option<Vec<String>>,
 }
 
+/// What a completion request completes, going by the text before the cursor
+#[derive(Debug, Clone, PartialEq)]
+enum CompletionSource {
+    /// The module name of an `import` clause
+    ImportModule,
+    /// A member after `Module.Name.`
+    ModuleMember(Vec<String>),
+    /// A field after `record.`, or of a `.field` accessor
+    RecordField,
+    /// Anything in scope
+    General,
+}
+
+/// Words up to this many characters are completed from the imported modules' most used
+/// names rather than the whole index
+const SHORT_WORD_LENGTH: usize = 1;
+
+/// Client command that re-opens the completion widget, used to chain module completions
+const TRIGGER_SUGGEST_COMMAND: &str = "editor.action.triggerSuggest";
+/// Client-side command opening the references of the symbol at a position, linked from the
+/// hover's use count
+const SHOW_REFERENCES_COMMAND: &str = "grenLsp.showReferences";
+/// How `workspace/symbol` queries can be narrowed down, for the capabilities report
+const WORKSPACE_SYMBOL_QUERY_SYNTAX: &str = "Narrow queries with `Data.List:name`, `module:Data.*`, `kind:function|type|constructor|module` (comma-separated for several) and `file:<glob relative to the workspace root>`, e.g. `kind:type file:src/Pages/** Model`";
+
+/// A module imported by a file, as written in its import clause
+#[derive(Debug, Clone, PartialEq)]
+struct ImportedModule {
+    module_name: String,
+    alias: Option<String>,
+}
+
+impl ImportedModule {
+    /// The name code in the importing file uses to qualify this module's members
+    fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.module_name)
+    }
+}
+
+/// Edit needed to bring a renamed file's module declaration (and everything importing it)
+/// back in line with the file's new location
+#[derive(Debug, Clone)]
+pub struct ModuleRenameSync {
+    pub old_module_name: String,
+    pub new_module_name: String,
+    /// Number of files other than the renamed one that the edit touches
+    pub importing_files: usize,
+    pub edit: WorkspaceEdit,
+}
+
+/// What a module rename has to look at, before any file has been scanned
+#[derive(Debug, Clone)]
+pub struct ModuleRenamePlan {
+    pub module_name: String,
+    /// Module name in the file's own `module` declaration
+    pub declaration: Location,
+    /// Files importing the module, not counting the module's own file
+    pub importers: Vec<Url>,
+}
+
 pub struct Handlers {
     workspace: Arc<RwLock<Workspace>>,
+    settings: ServerSettings,
+    completion_cache: Option<SharedCompletionCache>,
+    semantic_tokens: Option<SharedSemanticTokens>,
 }
 
 impl Handlers {
     pub fn new(workspace: Arc<RwLock<Workspace>>) -> Self {
-        Self { workspace }
+        Self::with_settings(workspace, ServerSettings::default())
+    }
+
+    pub fn with_settings(workspace: Arc<RwLock<Workspace>>, settings: ServerSettings) -> Self {
+        Self {
+            workspace,
+            settings,
+            completion_cache: None,
+            semantic_tokens: None,
+        }
+    }
+
+    /// Keep the last completion result in `cache`, so a request re-triggered for an
+    /// incomplete result can filter it instead of querying the workspace again
+    pub fn with_completion_cache(mut self, cache: SharedCompletionCache) -> Self {
+        self.completion_cache = Some(cache);
+        self
+    }
+
+    /// Remember each document's last semantic tokens in `cache`, so delta requests can
+    /// answer with what changed
+    pub fn with_semantic_tokens_cache(mut self, cache: SharedSemanticTokens) -> Self {
+        self.semantic_tokens = Some(cache);
+        self
     }
 
     pub async fn hover_with_capabilities(
@@ -36,6 +193,19 @@ impl Handlers {
         let workspace = self.workspace.read().await;
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
+        let supports_markdown = hover_supports_markdown(client_capabilities);
+
+        // Parameters and let bindings aren't indexed, so resolve them from the file itself
+        if let Some(hover) = self.local_hover(&workspace, uri, position, supports_markdown) {
+            return Ok(Some(hover));
+        }
+
+        // On the module part of a qualified name, describe the module
+        if let Some(hover) =
+            self.module_qualifier_hover(&workspace, uri, position, supports_markdown)
+        {
+            return Ok(Some(hover));
+        }
 
         // Find the symbol at the cursor position
         match self
@@ -59,16 +229,9 @@ impl Handlers {
                 match search_results {
                     Ok(symbols) => {
                         if let Some(symbol) = symbols.first() {
-                            // Check if client supports markdown format
-                            let supports_markdown = client_capabilities
-                                .and_then(|caps| caps.text_document.as_ref())
-                                .and_then(|text_doc| text_doc.hover.as_ref())
-                                .and_then(|hover| hover.content_format.as_ref())
-                                .map(|formats| formats.contains(&MarkupKind::Markdown))
-                                .unwrap_or(true); // Default to true for better compatibility
-
+                            let symbol = &with_inferred_signature(&workspace, symbol);
                             // Build hover content from the symbol
-                            let hover_content = self
+                            let mut hover_content = self
                                 .build_hover_content(
                                     symbol,
                                     &symbol_info,
@@ -77,6 +240,24 @@ impl Handlers {
                                 )
                                 .await;
 
+                            if !hover_content.is_empty() && self.settings.hover.reference_count {
+                                if let Some(footer) = self
+                                    .reference_count_footer(
+                                        &workspace,
+                                        symbol,
+                                        &symbol_info,
+                                        uri,
+                                        position,
+                                        client_capabilities,
+                                    )
+                                    .await
+                                {
+                                    let separator = if supports_markdown { "\n\n" } else { "\n" };
+                                    hover_content.push_str(separator);
+                                    hover_content.push_str(&footer);
+                                }
+                            }
+
                             if !hover_content.is_empty() {
                                 info!(
                                     "Generated hover content for '{}' (markdown support: {})",
@@ -118,7 +299,68 @@ impl Handlers {
         }
     }
 
+    /// Hover for the module qualifier of a qualified name, resolving import aliases
+    fn module_qualifier_hover(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        position: Position,
+        supports_markdown: bool,
+    ) -> Option<Hover> {
+        let document = workspace.get_document_readonly(uri)?;
+        let source = document.text();
+        let offset = document.position_to_offset(position)?;
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(source).ok()??;
+        let qualifier = qualifier_at(&tree, source, offset)?;
+
+        let module_name = imports(&tree, source)
+            .into_iter()
+            .find(|import| import.qualifier() == qualifier)
+            .map(|import| import.module_name)
+            .unwrap_or(qualifier);
+
+        let module = workspace
+            .find_exact_symbols(&module_name)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|symbol| symbol.kind == SymbolKind::MODULE);
+        let exports = workspace.module_exports(&module_name);
+        if module.is_none() && exports.is_none() {
+            return None;
+        }
+
+        let value = module_hover_content(
+            &module_name,
+            module
+                .as_ref()
+                .and_then(|module| module.documentation.as_deref()),
+            exports.as_ref(),
+            supports_markdown,
+        );
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: if supports_markdown {
+                    MarkupKind::Markdown
+                } else {
+                    MarkupKind::PlainText
+                },
+                value,
+            }),
+            range: None,
+        })
+    }
+
+    // Backward compatibility method - assumes no optional client commands are available
     pub async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        self.completion_with_capabilities(params, None).await
+    }
+
+    pub async fn completion_with_capabilities(
+        &self,
+        params: CompletionParams,
+        client_capabilities: Option<&ClientCapabilities>,
+    ) -> Result<Option<CompletionResponse>> {
         info!(
             "Completion requested at position {}:{}",
             params.text_document_position.position.line,
@@ -132,15 +374,259 @@ impl Handlers {
         let position = params.text_document_position.position;
 
         // Check if the document is open - don't provide completions for non-existent documents
-        if !workspace.is_document_open(uri) {
-            info!("Completion requested for non-open document: {}", uri);
-            return Ok(None);
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => {
+                info!("Completion requested for non-open document: {}", uri);
+                return Ok(None);
+            }
+        };
+
+        // Only chain completions for module names if the client can run the suggest command
+        let supports_trigger_suggest = client_capabilities
+            .map(|caps| self.client_supports_command(caps, TRIGGER_SUGGEST_COMMAND))
+            .unwrap_or(false);
+
+        // Clients that don't declare context support may still send a context; don't rely
+        // on it then
+        let supports_context = client_capabilities
+            .map(|caps| {
+                caps.text_document
+                    .as_ref()
+                    .and_then(|text_document| text_document.completion.as_ref())
+                    .and_then(|completion| completion.context_support)
+                    .unwrap_or(false)
+            })
+            .unwrap_or(true);
+        let context = params.context.as_ref().filter(|_| supports_context);
+
+        // The position's character counts UTF-16 code units; the document converts it
+        let text = document.text();
+        let offset = document
+            .position_to_offset(position)
+            .unwrap_or(text.len())
+            .min(text.len());
+        let line_start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_prefix = &text[line_start..offset];
+        let word_start = line_prefix
+            .trim_end_matches(|c| self.is_identifier_char(c))
+            .len();
+        let typed_word = &line_prefix[word_start..];
+
+        // Still typing the word of the previous result: filter that instead of querying
+        // the workspace again
+        if context.map(|context| context.trigger_kind)
+            == Some(CompletionTriggerKind::TRIGGER_FOR_INCOMPLETE_COMPLETIONS)
+        {
+            if let Some(items) = self.cached_completions(uri, position, typed_word) {
+                info!(
+                    "Returning {} completion items from the last result",
+                    items.len()
+                );
+                return Ok(Some(self.completion_response(items, false)));
+            }
+        }
+
+        let supports_snippets = client_capabilities
+            .and_then(|caps| caps.text_document.as_ref())
+            .and_then(|text_document| text_document.completion.as_ref())
+            .and_then(|completion| completion.completion_item.as_ref())
+            .and_then(|item| item.snippet_support)
+            .unwrap_or(false);
+
+        let trigger_character = context.and_then(|context| context.trigger_character.as_deref());
+        let module_path = gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(text).ok().flatten())
+            .and_then(|tree| qualifier_before(&tree, text, offset));
+        let source = self.completion_source(line_prefix, module_path, trigger_character);
+        // Only a call in general completion can be wrapped in parentheses; a qualified
+        // name's qualifier is already typed
+        let call = match source {
+            Some(CompletionSource::General) => call_context(&line_prefix[..word_start]),
+            _ => CallContext::Head,
+        };
+        // Too short a word to narrow the workspace down: only the popular names of the
+        // imported modules are offered, until the client asks again with a longer word
+        let short_word = matches!(source, Some(CompletionSource::General))
+            && typed_word.chars().count() <= SHORT_WORD_LENGTH;
+
+        let mut completion_items = match source {
+            Some(CompletionSource::ImportModule) => {
+                info!("Import completion for module names");
+                self.import_module_completions(&workspace, uri)
+            }
+            Some(CompletionSource::ModuleMember(module_path)) => {
+                info!(
+                    "Qualified completion for module path '{}'",
+                    module_path.join(".")
+                );
+                self.qualified_module_completions(
+                    &workspace,
+                    &params,
+                    document.text(),
+                    &module_path,
+                    supports_trigger_suggest,
+                )
+            }
+            Some(CompletionSource::RecordField) => {
+                info!("Record field completion");
+                self.record_field_completions(document.text())
+            }
+            Some(CompletionSource::General) => {
+                if let Some(items) = self.updated_field_completions(
+                    &workspace,
+                    uri,
+                    document.text(),
+                    position,
+                    offset,
+                ) {
+                    info!("Record update field completion");
+                    return Ok(Some(self.completion_response(items, false)));
+                }
+                let mut items = self.general_completions(
+                    &workspace,
+                    &params,
+                    supports_trigger_suggest,
+                    short_word.then_some(typed_word),
+                );
+                if let Some(item) = self.missing_branches_completion(
+                    &workspace,
+                    uri,
+                    document.text(),
+                    position,
+                    line_prefix,
+                    supports_snippets,
+                ) {
+                    items.insert(0, item);
+                }
+                items
+            }
+            None => {
+                info!("Nothing to complete after trigger character");
+                Vec::new()
+            }
+        };
+
+        if supports_snippets {
+            self.insert_call_snippets(&mut completion_items, call);
+        }
+
+        // The popular names of a short word can't be filtered down to a longer word's
+        // completions, so those are never reused
+        if let Some(cache) = &self.completion_cache {
+            if let Ok(mut cache) = cache.lock() {
+                *cache = (!short_word).then(|| {
+                    CompletionCache::new(
+                        uri.clone(),
+                        position,
+                        typed_word,
+                        completion_items.clone(),
+                    )
+                });
+            }
+        }
+
+        info!("Returning {} completion items", completion_items.len());
+        Ok(Some(self.completion_response(completion_items, short_word)))
+    }
+
+    /// `items` as a response, cut to the `completion.maxItems` first in the order clients
+    /// sort them. A cut or otherwise `incomplete` list makes the client ask again as the
+    /// word grows instead of filtering what it has.
+    fn completion_response(
+        &self,
+        mut items: Vec<CompletionItem>,
+        incomplete: bool,
+    ) -> CompletionResponse {
+        let max_items = self.settings.completion.max_items;
+        let cut = items.len() > max_items;
+        if cut {
+            items.sort_by(|a, b| {
+                let key = |item: &CompletionItem| {
+                    item.sort_text.clone().unwrap_or_else(|| item.label.clone())
+                };
+                key(a).cmp(&key(b)).then_with(|| a.label.cmp(&b.label))
+            });
+            items.truncate(max_items);
+            debug!("Completion cut to {} items", max_items);
+        }
+
+        if cut || incomplete {
+            CompletionResponse::List(CompletionList {
+                is_incomplete: true,
+                items,
+            })
+        } else {
+            CompletionResponse::Array(items)
+        }
+    }
+
+    /// Make function completions insert a call with a tab stop per argument, as
+    /// `completion.functionInsert` asks. The signature is the item's detail.
+    fn insert_call_snippets(&self, items: &mut [CompletionItem], call: CallContext) {
+        let parenthesize = match self.settings.completion.function_insert {
+            FunctionInsert::Name => return,
+            FunctionInsert::Arguments => false,
+            FunctionInsert::Parenthesized => true,
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for call snippets: {}", e);
+                return;
+            }
+        };
+
+        for item in items
+            .iter_mut()
+            .filter(|item| item.kind == Some(CompletionItemKind::FUNCTION))
+        {
+            let arities = match item
+                .detail
+                .as_deref()
+                .and_then(|signature| argument_arities(&mut parser, signature))
+            {
+                Some(arities) => arities,
+                None => continue,
+            };
+            if let Some(snippet) = call_snippet(&item.label, &arities, call, parenthesize) {
+                item.insert_text = Some(snippet);
+                item.insert_text_format = Some(InsertTextFormat::SNIPPET);
+            }
+        }
+    }
+
+    /// Everything in scope: the constructors fitting a nested pattern when the cursor is
+    /// in one, otherwise the file's and the workspace's symbols, imported module
+    /// qualifiers and keywords. A `short_word` only gets the popular names of the imported
+    /// modules rather than every indexed symbol.
+    fn general_completions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CompletionParams,
+        supports_trigger_suggest: bool,
+        short_word: Option<&str>,
+    ) -> Vec<CompletionItem> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        // In a constructor argument of a branch pattern only the constructors of the
+        // argument's type fit
+        if let Some(document) = workspace.get_document_readonly(uri) {
+            if let Some(items) =
+                self.nested_pattern_completions(workspace, uri, document.text(), position)
+            {
+                info!("Returning {} pattern constructor completions", items.len());
+                return items;
+            }
         }
 
         // Get symbols from current file
         let mut completion_items = Vec::new();
 
-        // Add local symbols from current file
+        // Add local symbols from current file, unannotated values with their inferred types
+        let module_types = workspace.module_types(uri).unwrap_or_default();
         match workspace.get_file_symbols(uri) {
             Ok(symbols) => {
                 for symbol in symbols {
@@ -150,14 +636,17 @@ impl Handlers {
                     }
 
                     // Apply basic context filtering
-                    if !self.should_include_symbol_in_completion(&symbol, &params) {
+                    if !self.should_include_symbol_in_completion(&symbol, params) {
                         continue;
                     }
 
                     let completion_item = CompletionItem {
                         label: symbol.name.clone(),
                         kind: Some(self.symbol_kind_to_completion_kind(symbol.kind)),
-                        detail: symbol.type_signature.clone(),
+                        detail: symbol
+                            .type_signature
+                            .clone()
+                            .or_else(|| module_types.top_level(&symbol.name).map(str::to_string)),
                         documentation: symbol
                             .documentation
                             .as_ref()
@@ -167,7 +656,7 @@ impl Handlers {
                         ..Default::default()
                     };
 
-                    completion_items.push(completion_item);
+                    completion_items.push(mark_deprecated(completion_item, &symbol));
                 }
             }
             Err(e) => {
@@ -176,7 +665,11 @@ impl Handlers {
         }
 
         // Add symbols from workspace (other files)
-        match workspace.find_symbols("") {
+        let workspace_symbols = match short_word {
+            Some(word) => Ok(self.popular_symbols(workspace, uri, word)),
+            None => workspace.find_symbols(""),
+        };
+        match workspace_symbols {
             Ok(workspace_symbols) => {
                 for symbol in workspace_symbols {
                     // Skip symbols from the current file (already added above)
@@ -189,8 +682,13 @@ impl Handlers {
                         continue;
                     }
 
+                    // Other modules can't use what their module doesn't expose
+                    if !workspace.is_exposed(&symbol) {
+                        continue;
+                    }
+
                     // Apply basic context filtering
-                    if !self.should_include_symbol_in_completion(&symbol, &params) {
+                    if !self.should_include_symbol_in_completion(&symbol, params) {
                         continue;
                     }
 
@@ -207,7 +705,7 @@ impl Handlers {
                         ..Default::default()
                     };
 
-                    completion_items.push(completion_item);
+                    completion_items.push(mark_deprecated(completion_item, &symbol));
                 }
             }
             Err(e) => {
@@ -215,6 +713,24 @@ impl Handlers {
             }
         }
 
+        // Add qualifiers of imported modules (e.g. `Json` for `import Json.Decode`)
+        if let Some(document) = workspace.get_document_readonly(uri) {
+            let mut seen_qualifiers = std::collections::HashSet::new();
+            for import in self.parse_imported_modules(document.text()) {
+                let first_segment = import.qualifier().split('.').next().unwrap_or("");
+                if first_segment.is_empty() || !seen_qualifiers.insert(first_segment.to_string()) {
+                    continue;
+                }
+                let mut item = self.module_segment_completion(
+                    first_segment,
+                    &import.module_name,
+                    supports_trigger_suggest,
+                );
+                item.sort_text = Some(format!("1_{}", first_segment));
+                completion_items.push(item);
+            }
+        }
+
         // Add Gren keywords
         let mut keyword_completions = self.get_keyword_completions();
         for keyword in &mut keyword_completions {
@@ -222,8 +738,7 @@ impl Handlers {
         }
         completion_items.extend(keyword_completions);
 
-        info!("Returning {} completion items", completion_items.len());
-        Ok(Some(CompletionResponse::Array(completion_items)))
+        completion_items
     }
 
     pub async fn goto_definition(
@@ -277,6 +792,16 @@ impl Handlers {
                             .map(|symbol| symbol.location)
                             .collect();
 
+                        // Projects in one repository can define modules of the same name;
+                        // only the ones this file's project can import are candidates
+                        if definitions
+                            .iter()
+                            .any(|definition| workspace.is_visible(uri, &definition.uri))
+                        {
+                            definitions
+                                .retain(|definition| workspace.is_visible(uri, &definition.uri));
+                        }
+
                         // Sort definitions to prefer the most likely ones
                         // For now, this is basic but could be enhanced with module awareness
                         definitions.sort_by(|a, b| {
@@ -376,7 +901,7 @@ impl Handlers {
 
         // Find all references to this symbol across the workspace
         let references = match self
-            .find_all_symbol_references(&workspace, &symbol_info, uri)
+            .find_all_symbol_references(&workspace, &symbol_info, uri, Some(position))
             .await
         {
             Ok(refs) => refs,
@@ -418,6 +943,201 @@ impl Handlers {
         Ok(Some(filtered_references))
     }
 
+    /// The top-level values named at the cursor, for a call hierarchy to start from
+    pub async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let workspace = self.workspace.read().await;
+
+        let symbol_info = match self
+            .find_symbol_at_position(&workspace, uri, position)
+            .await
+        {
+            Some(symbol) => symbol,
+            None => return Ok(None),
+        };
+        let name = &symbol_info.function_name;
+        let definitions = match &symbol_info.module_path {
+            Some(module_path) => {
+                self.find_qualified_symbol(&workspace, name, module_path)
+                    .await
+            }
+            None => self.find_unqualified_symbol(&workspace, uri, name).await,
+        };
+        let definitions = match definitions {
+            Ok(definitions) => definitions,
+            Err(e) => {
+                warn!("Failed to find the definition of '{}': {}", name, e);
+                return Ok(None);
+            }
+        };
+
+        let items: Vec<CallHierarchyItem> = definitions
+            .iter()
+            .filter(|symbol| symbol.kind == SymbolKind::FUNCTION)
+            .filter_map(|symbol| {
+                let module = workspace.module_name(&symbol.location.uri)?;
+                Some(call_item(symbol, &module))
+            })
+            .collect();
+        Ok(if items.is_empty() { None } else { Some(items) })
+    }
+
+    /// The top-level values whose definitions call the item's value
+    pub async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let item = &params.item;
+        let workspace = self.workspace.read().await;
+        let module = match item_module(item).or_else(|| workspace.module_name(&item.uri)) {
+            Some(module) => module,
+            None => return Ok(None),
+        };
+
+        let references = match workspace.references_to(&module, &item.name) {
+            Ok(references) => references,
+            Err(e) => {
+                warn!("Failed to find the callers of '{}': {}", item.name, e);
+                return Ok(None);
+            }
+        };
+        Ok(Some(incoming_calls(&references, |uri, name| {
+            value_call_item(&workspace, uri, name)
+        })))
+    }
+
+    /// The top-level values the item's definition calls
+    pub async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let item = &params.item;
+        let workspace = self.workspace.read().await;
+
+        let references = match workspace.references_from(&item.uri, &item.name) {
+            Ok(references) => references,
+            Err(e) => {
+                warn!("Failed to find the calls made by '{}': {}", item.name, e);
+                return Ok(None);
+            }
+        };
+        Ok(Some(outgoing_calls(&references, |module, name| {
+            let uri = workspace.module_uri(module)?;
+            value_call_item(&workspace, &uri, name)
+        })))
+    }
+
+    /// The custom types, type aliases and constructors named at the cursor, for a type
+    /// hierarchy to start from
+    pub async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let workspace = self.workspace.read().await;
+
+        let symbol_info = match self
+            .find_symbol_at_position(&workspace, uri, position)
+            .await
+        {
+            Some(symbol) => symbol,
+            None => return Ok(None),
+        };
+        let name = &symbol_info.function_name;
+        let definitions = match &symbol_info.module_path {
+            Some(module_path) => {
+                self.find_qualified_symbol(&workspace, name, module_path)
+                    .await
+            }
+            None => self.find_unqualified_symbol(&workspace, uri, name).await,
+        };
+        let definitions = match definitions {
+            Ok(definitions) => definitions,
+            Err(e) => {
+                warn!("Failed to find the definition of '{}': {}", name, e);
+                return Ok(None);
+            }
+        };
+
+        let items: Vec<TypeHierarchyItem> = definitions
+            .iter()
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::CLASS | SymbolKind::CONSTRUCTOR))
+            .filter_map(|symbol| {
+                let module = workspace.module_name(&symbol.location.uri)?;
+                Some(type_item(symbol, &module))
+            })
+            .collect();
+        Ok(if items.is_empty() { None } else { Some(items) })
+    }
+
+    /// The types and type aliases referring to the item's type, or a constructor's type
+    pub async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let item = &params.item;
+        let workspace = self.workspace.read().await;
+
+        if item.kind == SymbolKind::ENUM_MEMBER {
+            let symbols = workspace.get_file_symbols(&item.uri).unwrap_or_default();
+            let parent = symbols
+                .iter()
+                .find(|symbol| symbol.kind == SymbolKind::CONSTRUCTOR && symbol.name == item.name)
+                .and_then(|constructor| constructor.container_name.as_deref())
+                .and_then(|parent| type_symbol_item(&workspace, &item.uri, parent));
+            return Ok(Some(parent.into_iter().collect()));
+        }
+
+        let module = match type_item_module(item).or_else(|| workspace.module_name(&item.uri)) {
+            Some(module) => module,
+            None => return Ok(None),
+        };
+        let references = match workspace.references_to(&module, &item.name) {
+            Ok(references) => references,
+            Err(e) => {
+                warn!(
+                    "Failed to find the types referring to '{}': {}",
+                    item.name, e
+                );
+                return Ok(None);
+            }
+        };
+        Ok(Some(
+            referring_types(&references, &item.name)
+                .into_iter()
+                .filter_map(|(uri, name)| type_symbol_item(&workspace, &uri, &name))
+                .collect(),
+        ))
+    }
+
+    /// The constructors of the item's custom type
+    pub async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let item = &params.item;
+        if item.kind != SymbolKind::ENUM {
+            return Ok(Some(Vec::new()));
+        }
+        let workspace = self.workspace.read().await;
+        let module = match type_item_module(item).or_else(|| workspace.module_name(&item.uri)) {
+            Some(module) => module,
+            None => return Ok(None),
+        };
+        let symbols = workspace.get_file_symbols(&item.uri).unwrap_or_default();
+        Ok(Some(
+            constructors_of(&symbols, &item.name)
+                .into_iter()
+                .map(|constructor| type_item(constructor, &module))
+                .collect(),
+        ))
+    }
+
     pub async fn document_symbols(
         &self,
         params: DocumentSymbolParams,
@@ -447,7 +1167,14 @@ impl Handlers {
                 );
 
                 // Convert to LSP document symbols with hierarchy
-                let document_symbols = self.convert_to_document_symbols(symbols);
+                let full_ranges = indexed_source(&workspace, &params.text_document.uri)
+                    .and_then(|content| {
+                        let mut parser = gren_lsp_core::Parser::new().ok()?;
+                        let tree = parser.parse(&content).ok()??;
+                        Some(declaration_ranges(&tree, &content))
+                    })
+                    .unwrap_or_default();
+                let document_symbols = self.convert_to_document_symbols(symbols, &full_ranges);
 
                 Ok(Some(DocumentSymbolResponse::Nested(document_symbols)))
             }
@@ -461,1251 +1188,3369 @@ impl Handlers {
         }
     }
 
-    pub async fn workspace_symbols(
+    /// Signature help for the function applied where the cursor is, highlighting the
+    /// parameter of the argument being typed
+    pub async fn signature_help(
         &self,
-        params: WorkspaceSymbolParams,
-    ) -> Result<Option<Vec<SymbolInformation>>> {
-        info!("Workspace symbols requested with query: '{}'", params.query);
-
+        params: SignatureHelpParams,
+    ) -> Result<Option<SignatureHelp>> {
         let workspace = self.workspace.read().await;
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
 
-        // Search for symbols matching the query
-        match workspace.find_symbols(&params.query) {
-            Ok(symbols) => {
-                if symbols.is_empty() {
-                    debug!("No symbols found for query: '{}'", params.query);
-                    return Ok(Some(Vec::new()));
-                }
-
-                info!(
-                    "Found {} symbols for query: '{}'",
-                    symbols.len(),
-                    params.query
-                );
-
-                // Convert to LSP symbol information
-                let symbol_information = symbols
-                    .into_iter()
-                    .map(|symbol| self.convert_to_symbol_information(symbol))
-                    .collect();
-
-                Ok(Some(symbol_information))
+        let application = {
+            let document = match workspace.get_document_readonly(uri) {
+                Some(document) => document,
+                None => return Ok(None),
+            };
+            let offset = match document.position_to_offset(position) {
+                Some(offset) => offset,
+                None => return Ok(None),
+            };
+            match application_at(&document.text()[..offset]) {
+                Some(application) => application,
+                None => return Ok(None),
             }
+        };
+
+        let (module_path, name) = application.qualified_name();
+        let symbols = if module_path.is_empty() {
+            self.find_unqualified_symbol(&workspace, uri, name).await
+        } else {
+            self.find_qualified_symbol(&workspace, name, &module_path)
+                .await
+        };
+        let symbol = match symbols {
+            Ok(symbols) => symbols.into_iter().find(|symbol| {
+                symbol.kind == SymbolKind::FUNCTION && symbol.type_signature.is_some()
+            }),
             Err(e) => {
-                warn!(
-                    "Failed to search symbols for query '{}': {}",
-                    params.query, e
+                debug!(
+                    "Failed to resolve {} for signature help: {}",
+                    application.function, e
                 );
-                Ok(Some(Vec::new()))
+                None
             }
-        }
-    }
+        };
+        let (symbol, signature) = match symbol {
+            Some(symbol) => {
+                let signature = symbol.type_signature.clone().unwrap_or_default();
+                (symbol, signature)
+            }
+            None => return Ok(None),
+        };
 
-    pub async fn code_action(
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(_) => return Ok(None),
+        };
+        let parameters = match signature_parameters(&mut parser, &signature) {
+            Some(parameters) => parameters,
+            None => return Ok(None),
+        };
+        Ok(build_signature_help(
+            &symbol.name,
+            &signature,
+            &parameters,
+            application.active_argument,
+            symbol.documentation.as_deref(),
+        ))
+    }
+
+    /// Formatting a document changes the lines `gren format` writes differently. The
+    /// formatter refuses source that doesn't parse, which leaves the document alone.
+    pub async fn formatting(
         &self,
-        params: CodeActionParams,
-    ) -> Result<Option<CodeActionResponse>> {
-        info!(
-            "Code action requested for range {}:{}-{}:{} in file {}",
-            params.range.start.line,
-            params.range.start.character,
-            params.range.end.line,
-            params.range.end.character,
-            params.text_document.uri
-        );
+        params: DocumentFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        self.format_document(&params.text_document.uri).await
+    }
 
-        let mut actions = Vec::new();
-        let workspace = self.workspace.read().await;
+    /// Formatting a range formats the whole document, as the formatter only takes whole
+    /// modules, and keeps the changes to the lines of the range
+    pub async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let edits = self.format_document(&params.text_document.uri).await?;
+        Ok(edits.map(|edits| edits_in_range(edits, params.range)))
+    }
 
-        // Handle quick fix actions (import suggestions for unresolved symbols)
-        if params.context.only.is_none()
-            || params
-                .context
-                .only
-                .as_ref()
-                .unwrap()
-                .contains(&CodeActionKind::QUICKFIX)
-        {
-            if let Some(quickfix_actions) = self
-                .generate_import_quickfix_actions(&workspace, &params)
-                .await
-            {
-                actions.extend(quickfix_actions);
+    /// Indentation while typing: a new line is indented for what the line above opens,
+    /// and `in` or `is` on a line of its own moves under its `let` or `when`
+    pub async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let workspace = self.workspace.read().await;
+        let uri = &params.text_document_position.text_document.uri;
+        let line = params.text_document_position.position.line as usize;
+        let source = match workspace.get_document_readonly(uri) {
+            Some(document) => document.text().to_string(),
+            None => return Ok(None),
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for on-type formatting: {}", e);
+                return Ok(None);
             }
-        }
+        };
+        let tree = match parser.parse(&source) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
 
-        // Handle source organize imports actions
-        if params.context.only.is_none()
-            || params
-                .context
-                .only
-                .as_ref()
-                .unwrap()
-                .contains(&CodeActionKind::SOURCE_ORGANIZE_IMPORTS)
-        {
-            if let Some(organize_action) = self
-                .generate_organize_imports_action(&workspace, &params.text_document.uri)
-                .await
-            {
-                actions.push(CodeActionOrCommand::CodeAction(organize_action));
+        let indentation = match params.ch.as_str() {
+            "\n" => Some(new_line_indentation(&tree, &source, line)),
+            _ => keyword_indentation(&tree, &source, line),
+        };
+        Ok(indentation
+            .and_then(|indentation| indentation_edit(&source, line, indentation))
+            .map(|edit| vec![edit]))
+    }
+
+    async fn format_document(&self, uri: &Url) -> Result<Option<Vec<TextEdit>>> {
+        let (formatter, source) = {
+            let workspace = self.workspace.read().await;
+            match (workspace.formatter(), indexed_source(&workspace, uri)) {
+                (Some(formatter), Some(source)) => (formatter, source),
+                _ => return Ok(None),
             }
-        }
+        };
 
-        if actions.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(actions))
+        match formatter.format(&source).await {
+            Ok(formatted) => Ok(Some(minimal_edits(&source, &formatted))),
+            Err(e) => {
+                warn!("Failed to format {}: {}", uri, e);
+                Ok(None)
+            }
         }
     }
 
-    pub async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
-        info!(
-            "Rename requested at position {}:{} with new name '{}'",
-            params.text_document_position.position.line,
-            params.text_document_position.position.character,
-            params.new_name
-        );
-
+    pub async fn folding_range(
+        &self,
+        params: FoldingRangeParams,
+        client_capabilities: Option<&ClientCapabilities>,
+    ) -> Result<Option<Vec<FoldingRange>>> {
         let workspace = self.workspace.read().await;
-        let uri = &params.text_document_position.text_document.uri;
-        let position = params.text_document_position.position;
-        let new_name = &params.new_name;
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Ok(None),
+        };
 
-        // Validate the new name is a valid Gren identifier
-        if !self.is_valid_gren_identifier(new_name) {
-            warn!("Invalid identifier name for rename: '{}'", new_name);
-            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
-                "'{}' is not a valid Gren identifier",
-                new_name
-            )));
-        }
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for folding ranges: {}", e);
+                return Ok(None);
+            }
+        };
+        let tree = match parser.parse(document.text()) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
 
-        // Find the symbol at the cursor position
-        match self
-            .find_symbol_at_position(&workspace, uri, position)
-            .await
-        {
-            Some(symbol_info) => {
-                info!("Found symbol for rename: {:?}", symbol_info);
+        let folding = client_capabilities
+            .and_then(|caps| caps.text_document.as_ref())
+            .and_then(|text_document| text_document.folding_range.as_ref());
+        let options = FoldingOptions {
+            collapsed_text: folding
+                .and_then(|folding| folding.folding_range.as_ref())
+                .and_then(|folding_range| folding_range.collapsed_text)
+                .unwrap_or(false),
+            range_limit: folding
+                .and_then(|folding| folding.range_limit)
+                .map(|limit| limit as usize),
+        };
 
-                // Find all references to this symbol
-                let references = match self
-                    .find_all_symbol_references(&workspace, &symbol_info, uri)
-                    .await
-                {
-                    Ok(refs) => refs,
-                    Err(e) => {
-                        warn!(
-                            "Failed to find references for symbol '{}': {}",
-                            symbol_info.function_name, e
-                        );
-                        return Ok(None);
-                    }
-                };
+        let ranges = folding_ranges(&tree, document.text(), options);
+        debug!(
+            "Found {} folding ranges for {}",
+            ranges.len(),
+            params.text_document.uri
+        );
+        Ok(Some(ranges))
+    }
 
-                if references.is_empty() {
-                    info!(
-                        "No references found for symbol '{}'",
-                        symbol_info.function_name
-                    );
-                    return Ok(None);
-                }
+    /// Ranges to grow the selection through at each position, following the syntax tree
+    pub async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let workspace = self.workspace.read().await;
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Ok(None),
+        };
 
-                // Generate workspace edit for all references
-                let workspace_edit = self.generate_workspace_edit_for_rename(references, new_name);
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for selection ranges: {}", e);
+                return Ok(None);
+            }
+        };
+        let tree = match parser.parse(document.text()) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
 
-                info!(
-                    "Generated workspace edit for rename of '{}' to '{}' with {} changes",
-                    symbol_info.function_name,
-                    new_name,
-                    workspace_edit
-                        .document_changes
-                        .as_ref()
-                        .map(|changes| {
-                            match changes {
-                                DocumentChanges::Edits(edits) => edits.len(),
-                                DocumentChanges::Operations(ops) => ops.len(),
-                            }
-                        })
-                        .unwrap_or(0)
-                );
+        // The response has one range per position, so a position outside the tree selects
+        // just itself
+        let ranges = params
+            .positions
+            .iter()
+            .map(|&position| {
+                selection_range(&tree, document.text(), position).unwrap_or(SelectionRange {
+                    range: Range::new(position, position),
+                    parent: None,
+                })
+            })
+            .collect();
+        Ok(Some(ranges))
+    }
 
-                Ok(Some(workspace_edit))
-            }
-            None => {
-                info!("No symbol found at rename position");
-                Ok(None)
-            }
-        }
+    /// Highlighting for a whole document
+    pub async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let uri = params.text_document.uri;
+        let data = match self.document_semantic_tokens(&uri).await {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let result_id = self.remember_semantic_tokens(&uri, &data);
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id,
+            data,
+        })))
     }
 
-    // Helper methods for rename functionality
+    /// Highlighting for a whole document, as the changes since the client's last result
+    /// when it's still the latest one
+    pub async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let uri = params.text_document.uri;
+        let data = match self.document_semantic_tokens(&uri).await {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let previous = self.semantic_tokens.as_ref().and_then(|cache| {
+            let cache = cache.lock().ok()?;
+            cache
+                .previous(&uri, &params.previous_result_id)
+                .map(<[_]>::to_vec)
+        });
+        let result_id = self.remember_semantic_tokens(&uri, &data);
 
-    /// Validate that a string is a valid Gren identifier
-    fn is_valid_gren_identifier(&self, name: &str) -> bool {
-        if name.is_empty() {
-            return false;
-        }
+        Ok(Some(match previous {
+            Some(previous) => {
+                let edits = tokens_delta(&previous, &data);
+                debug!("Semantic tokens of {}: {} edits", uri, edits.len());
+                SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta { result_id, edits })
+            }
+            None => SemanticTokensFullDeltaResult::Tokens(SemanticTokens { result_id, data }),
+        }))
+    }
 
-        // Check if it's a reserved keyword
-        if self.is_keyword(name) {
-            return false;
+    /// Inferred types after unannotated declarations and parameter names before the
+    /// arguments of functions taking several parameters of the same type
+    pub async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let settings = &self.settings.inlay_hints;
+        if !settings.types && !settings.parameter_names {
+            return Ok(None);
         }
 
-        let chars: Vec<char> = name.chars().collect();
+        let workspace = self.workspace.read().await;
+        let uri = &params.text_document.uri;
+        let source = match workspace.get_document_readonly(uri) {
+            Some(document) => document.text().to_string(),
+            None => return Ok(None),
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for inlay hints: {}", e);
+                return Ok(None);
+            }
+        };
+        let tree = match parser.parse(&source) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
 
-        // First character must be letter or underscore, but not uppercase (unless it's a type)
-        let first_char = chars[0];
-        if !first_char.is_alphabetic() && first_char != '_' {
-            return false;
+        let mut hints = Vec::new();
+        if settings.types {
+            if let Some(types) = workspace.module_types(uri) {
+                for declaration in unannotated_declarations(&tree, &source, params.range) {
+                    if let Some(type_text) = types.binding(declaration.name_range) {
+                        hints.push(type_hint(&declaration, type_text));
+                    }
+                }
+            }
         }
 
-        // Rest of characters must be alphanumeric or underscore
-        for &c in &chars[1..] {
-            if !c.is_alphanumeric() && c != '_' {
-                return false;
+        if settings.parameter_names {
+            // The same function is usually called many times in a document
+            let mut parameters: HashMap<String, Option<Vec<Option<String>>>> = HashMap::new();
+            for call in calls(&tree, &source, params.range) {
+                if !parameters.contains_key(&call.function) {
+                    let names = self
+                        .confusable_parameter_names(&workspace, &mut parser, uri, &call)
+                        .await;
+                    parameters.insert(call.function.clone(), names);
+                }
+                if let Some(Some(names)) = parameters.get(&call.function) {
+                    hints.extend(parameter_hints(&call, names));
+                }
             }
         }
 
-        true
+        hints.sort_by_key(|hint| hint.position);
+        Ok(Some(hints))
     }
 
-    /// Find all references to a symbol in the workspace
-    async fn find_all_symbol_references(
+    /// The parameter names of the function `call` applies, when two or more of its
+    /// parameters have the same type
+    async fn confusable_parameter_names(
         &self,
-        workspace: &gren_lsp_core::Workspace,
-        symbol_info: &SymbolAtPosition,
-        current_uri: &Url,
-    ) -> anyhow::Result<Vec<Location>> {
-        let mut all_references = Vec::new();
-
-        // Search for all occurrences of the symbol name
-        let search_results = if let Some(module_path) = &symbol_info.module_path {
-            // For qualified symbols, search both qualified and unqualified forms
-            let qualified_symbols = self
-                .find_qualified_symbol(workspace, &symbol_info.function_name, module_path)
-                .await?;
-            let unqualified_symbols = workspace.find_exact_symbols(&symbol_info.function_name)?;
-
-            // Combine results and deduplicate
-            let mut all_symbols = qualified_symbols;
-            for symbol in unqualified_symbols {
-                if !all_symbols
-                    .iter()
-                    .any(|existing| existing.location == symbol.location)
-                {
-                    all_symbols.push(symbol);
-                }
-            }
-            all_symbols
+        workspace: &Workspace,
+        parser: &mut gren_lsp_core::Parser,
+        uri: &Url,
+        call: &Call,
+    ) -> Option<Vec<Option<String>>> {
+        let (module_path, name) = call.qualified_name();
+        let symbols = if module_path.is_empty() {
+            self.find_unqualified_symbol(workspace, uri, name).await
         } else {
-            // For unqualified symbols, use import-aware search
-            self.find_unqualified_symbol(workspace, current_uri, &symbol_info.function_name)
-                .await?
+            self.find_qualified_symbol(workspace, name, &module_path)
+                .await
         };
+        let symbol = symbols.ok()?.into_iter().find(|symbol| {
+            symbol.kind == SymbolKind::FUNCTION && symbol.type_signature.is_some()
+        })?;
 
-        // Extract locations from symbols (these are definition locations)
-        for symbol in search_results {
-            all_references.push(symbol.location);
+        let signature = symbol.type_signature.as_deref()?;
+        let parameter_types: Vec<&str> = signature_parameters(parser, signature)?
+            .into_iter()
+            .map(|(start, end)| signature[start..end].trim())
+            .collect();
+        if !has_confusable_parameters(&parameter_types) {
+            return None;
         }
 
-        // Also search for textual references in all documents
-        // This catches usage locations that might not be indexed as symbols
-        let textual_references = self
-            .find_textual_references(workspace, &symbol_info.function_name)
-            .await?;
-        all_references.extend(textual_references);
-
-        // Remove duplicates by location
-        all_references.sort_by(|a, b| {
-            let uri_cmp = a.uri.cmp(&b.uri);
-            if uri_cmp != std::cmp::Ordering::Equal {
-                return uri_cmp;
-            }
-            let line_cmp = a.range.start.line.cmp(&b.range.start.line);
-            if line_cmp != std::cmp::Ordering::Equal {
-                return line_cmp;
-            }
-            a.range.start.character.cmp(&b.range.start.character)
-        });
-        all_references.dedup_by(|a, b| a.uri == b.uri && a.range == b.range);
-
-        Ok(all_references)
+        let source = indexed_source(workspace, &symbol.location.uri)?;
+        let tree = parser.parse(&source).ok()??;
+        parameter_names(&tree, &source, &symbol.name)
     }
 
-    /// Find textual references to a symbol name across all open documents
-    async fn find_textual_references(
+    /// Ghost text finishing the line the cursor ends: the rest of an import as other files
+    /// write it, the fields of a record update, or a `when` expression's missing branches
+    pub async fn inline_completion(
         &self,
-        workspace: &gren_lsp_core::Workspace,
-        symbol_name: &str,
-    ) -> anyhow::Result<Vec<Location>> {
-        let mut references = Vec::new();
-
-        // Get all documents in the workspace
-        // Note: This is a simplified approach - in a real implementation we'd want to
-        // search through all files in the project, not just open documents
-        for uri in workspace.get_open_document_uris() {
-            if let Some(document) = workspace.get_document_readonly(&uri) {
-                let text = document.text();
-                let lines: Vec<&str> = text.lines().collect();
-
-                for (line_idx, line) in lines.iter().enumerate() {
-                    let mut char_idx = 0;
-                    while let Some(match_start) = line[char_idx..].find(symbol_name) {
-                        let absolute_start = char_idx + match_start;
-                        let absolute_end = absolute_start + symbol_name.len();
-
-                        // Check if this is a complete word match (not part of another identifier or module qualifier)
-                        let is_complete_word = {
-                            let before_ok = absolute_start == 0
-                                || !self.is_identifier_char(
-                                    line.chars().nth(absolute_start - 1).unwrap_or(' '),
-                                );
-                            let after_char = line.chars().nth(absolute_end).unwrap_or(' ');
-                            let after_ok = absolute_end >= line.len()
-                                || (!self.is_identifier_char(after_char) && after_char != '.');
-                            before_ok && after_ok
-                        };
-
-                        // Check if this match is inside a comment
-                        let is_in_comment = self.is_position_in_comment(line, absolute_start);
-
-                        // Check if this match is inside an import statement
-                        let is_in_import = self.is_position_in_import(line, absolute_start);
-
-                        // Check if this match is inside a module declaration
-                        let is_in_module_declaration = self
-                            .is_position_in_module_declaration(
-                                &uri,
-                                line_idx as u32,
-                                absolute_start as u32,
-                            )
-                            .await;
-
-                        if is_complete_word
-                            && !is_in_comment
-                            && !is_in_import
-                            && !is_in_module_declaration
-                        {
-                            references.push(Location {
-                                uri: uri.clone(),
-                                range: Range {
-                                    start: Position {
-                                        line: line_idx as u32,
-                                        character: absolute_start as u32,
-                                    },
-                                    end: Position {
-                                        line: line_idx as u32,
-                                        character: absolute_end as u32,
-                                    },
-                                },
-                            });
-                        }
+        params: InlineCompletionParams,
+    ) -> Result<Option<Vec<InlineCompletionItem>>> {
+        // The editor previews the selected completion item instead
+        if params.context.selected_completion_info.is_some() {
+            return Ok(None);
+        }
 
-                        char_idx = absolute_start + 1; // Move past this match to find more
-                    }
-                }
-            }
+        let workspace = self.workspace.read().await;
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Ok(None),
+        };
+        let content = document.text();
+        let offset = match document.position_to_offset(position) {
+            Some(offset) if offset <= content.len() => offset,
+            _ => return Ok(None),
+        };
+        let line_end = content[offset..]
+            .find('\n')
+            .map_or(content.len(), |end| offset + end);
+        if !content[offset..line_end].trim().is_empty() {
+            return Ok(None);
         }
+        let line_start = content[..offset]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let line_prefix = &content[line_start..offset];
+        let tree = match gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(content).ok().flatten())
+        {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
 
-        Ok(references)
+        let item = self
+            .inline_import(&workspace, uri, &tree, content, offset, position)
+            .or_else(|| {
+                self.inline_record_update(&workspace, uri, &tree, content, offset, position)
+            })
+            .or_else(|| {
+                self.inline_branches(&workspace, uri, &tree, content, position, line_prefix)
+            });
+        Ok(item.map(|item| vec![item]))
     }
 
-    /// Filter out declarations from references based on symbol information
-    async fn filter_out_declarations(
+    /// The rest of an `import` line, as most files import a module starting with what's
+    /// written that the file doesn't import yet
+    fn inline_import(
         &self,
-        references: Vec<Location>,
-        symbol_info: &SymbolAtPosition,
-    ) -> Vec<Location> {
-        let mut filtered_references = Vec::new();
+        workspace: &Workspace,
+        uri: &Url,
+        tree: &tree_sitter::Tree,
+        content: &str,
+        offset: usize,
+        position: Position,
+    ) -> Option<InlineCompletionItem> {
+        let typed = import_prefix(tree, content, offset)?;
+        let imported: Vec<String> = imports(tree, content)
+            .into_iter()
+            .filter(|import| import.range.start.line != position.line)
+            .map(|import| import.module_name)
+            .chain(workspace.module_name(uri))
+            .collect();
 
-        for location in references {
-            // Check if this location is a declaration by examining the AST context
-            if !self.is_symbol_declaration(&location, symbol_info).await {
-                filtered_references.push(location);
-            }
+        let clauses = workspace.import_clauses();
+        let clause = import_line(&clauses, typed, &imported)?;
+        let line_start = content[..offset]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let line_prefix = &content[line_start..offset];
+        if !clause.starts_with(line_prefix) || clause.len() == line_prefix.len() {
+            return None;
         }
+        Some(InlineCompletionItem {
+            insert_text: clause.to_string(),
+            range: Some(Range::new(Position::new(position.line, 0), position)),
+        })
+    }
 
-        filtered_references
+    /// Every field of the record `{ record |` updates, set to its current value, when the
+    /// record is a parameter annotated with a record alias
+    fn inline_record_update(
+        &self,
+        workspace: &Workspace,
+        uri: &Url,
+        tree: &tree_sitter::Tree,
+        content: &str,
+        offset: usize,
+        position: Position,
+    ) -> Option<InlineCompletionItem> {
+        let record = record_update_at(tree, content, offset)?;
+        let type_name = parameter_type(tree, content, position.line, record)?;
+        let fields = workspace.record_alias_fields(uri, &type_name)?;
+        Some(InlineCompletionItem {
+            insert_text: record_update_text(&content[..offset], record, &fields),
+            range: Some(Range::new(position, position)),
+        })
     }
 
-    /// Check if a location represents a symbol declaration rather than a usage
-    /// For now, this uses a simple heuristic approach
-    async fn is_symbol_declaration(
+    /// The branches the `when` expression around the cursor is missing, when the cursor
+    /// is where the next branch starts
+    fn inline_branches(
         &self,
-        location: &Location,
-        symbol_info: &SymbolAtPosition,
-    ) -> bool {
-        let workspace = self.workspace.read().await;
+        workspace: &Workspace,
+        uri: &Url,
+        tree: &tree_sitter::Tree,
+        content: &str,
+        position: Position,
+        line_prefix: &str,
+    ) -> Option<InlineCompletionItem> {
+        let when = when_expression_at(tree, content, position)?;
+        let column = when.branch_start(line_prefix)?;
+        let constructors = self.missing_when_branches(workspace, uri, tree, content, &when)?;
+        let text = branches_completion(constructors, &line_prefix[column..], column)?;
+        Some(InlineCompletionItem {
+            insert_text: text,
+            range: Some(Range::new(
+                Position::new(position.line, column as u32),
+                position,
+            )),
+        })
+    }
 
-        // Get the document for this location
-        let document = match workspace.get_document_readonly(&location.uri) {
-            Some(doc) => doc,
-            None => return false,
+    async fn document_semantic_tokens(&self, uri: &Url) -> Option<Vec<SemanticToken>> {
+        let workspace = self.workspace.read().await;
+        let document = workspace.get_document_readonly(uri)?;
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for semantic tokens: {}", e);
+                return None;
+            }
         };
+        let tree = parser.parse(document.text()).ok()??;
+        Some(semantic_tokens(&tree, document.text(), uri))
+    }
 
-        let source = document.text();
-        let lines: Vec<&str> = source.lines().collect();
+    /// Keep `data` as the latest result for `uri`, giving it a result id when there's a
+    /// cache to keep it in
+    fn remember_semantic_tokens(&self, uri: &Url, data: &[SemanticToken]) -> Option<String> {
+        let cache = self.semantic_tokens.as_ref()?;
+        let mut cache = cache.lock().ok()?;
+        Some(cache.store(uri, data.to_vec()))
+    }
 
-        // Get the line containing this location
-        let line_index = location.range.start.line as usize;
-        if line_index >= lines.len() {
-            return false;
+    // Backward compatibility method - assumes no optional client commands are available
+    pub async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        self.code_lens_with_capabilities(params, None).await
+    }
+
+    /// Lenses above the module header listing its exports, and above `Debug.log` calls
+    /// showing the values the running program last logged under their tags
+    pub async fn code_lens_with_capabilities(
+        &self,
+        params: CodeLensParams,
+        client_capabilities: Option<&ClientCapabilities>,
+    ) -> Result<Option<Vec<CodeLens>>> {
+        if !self.settings.runtime_log.code_lens && !self.settings.exports.code_lens {
+            return Ok(None);
         }
 
-        let line = lines[line_index];
+        let workspace = self.workspace.read().await;
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Ok(None),
+        };
 
-        // Simple heuristic: check if this line looks like a declaration
-        // Function declarations: "functionName : Type" or "functionName ="
-        if line.trim_start().starts_with(&symbol_info.function_name) {
-            // Check if it's followed by " : " (type annotation) or " =" (definition)
-            let pattern = format!("{} :", symbol_info.function_name);
-            let pattern2 = format!("{} =", symbol_info.function_name);
-            if line.contains(&pattern) || line.contains(&pattern2) {
-                return true;
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for code lenses: {}", e);
+                return Ok(None);
             }
-        }
+        };
+        let tree = match parser.parse(document.text()) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
 
-        // Type declarations typically start with "type" keyword
-        if line.trim_start().starts_with("type ") && line.contains(&symbol_info.function_name) {
-            return true;
+        let mut lenses = Vec::new();
+        if self.settings.exports.code_lens {
+            let manage_exports = client_capabilities
+                .is_some_and(|caps| self.client_supports_command(caps, MANAGE_EXPORTS_COMMAND));
+            lenses.extend(exports_lenses(
+                &tree,
+                document.text(),
+                &params.text_document.uri,
+                manage_exports,
+            ));
         }
-
-        false
+        if self.settings.runtime_log.code_lens {
+            let log_lenses: Vec<CodeLens> = debug_log_calls(&tree, document.text())
+                .into_iter()
+                .map(|call| CodeLens {
+                    range: call.range,
+                    command: Some(Command {
+                        title: runtime_log_title(
+                            &call.tag,
+                            &workspace.runtime_log_values(&call.tag),
+                        ),
+                        command: String::new(),
+                        arguments: None,
+                    }),
+                    data: None,
+                })
+                .collect();
+            debug!(
+                "Found {} Debug.log lenses for {}",
+                log_lenses.len(),
+                params.text_document.uri
+            );
+            lenses.extend(log_lenses);
+        }
+        Ok(Some(lenses))
     }
 
-    /// Generate a workspace edit for renaming all occurrences
-    fn generate_workspace_edit_for_rename(
+    /// Every top-level declaration of a module with whether it's exposed and how often the
+    /// modules importing it use it. `None` for a file without a module declaration.
+    pub async fn module_exports(
         &self,
-        references: Vec<Location>,
-        new_name: &str,
-    ) -> WorkspaceEdit {
-        use std::collections::HashMap;
-
-        let mut changes_by_file: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        params: ModuleExportsParams,
+    ) -> Result<Option<ModuleExportsResult>> {
+        let uri = &params.text_document.uri;
+        let workspace = self.workspace.read().await;
+        let source = match workspace.file_contents(uri) {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for module exports: {}", e);
+                return Ok(None);
+            }
+        };
+        let tree = match parser.parse(&source) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
+        let exposing = match module_exposing(&tree, &source) {
+            Some(exposing) => exposing,
+            None => return Ok(None),
+        };
 
-        // Group references by file
-        for location in references {
-            let edit = TextEdit {
-                range: location.range,
-                new_text: new_name.to_string(),
+        let symbols = workspace.get_file_symbols(uri).unwrap_or_default();
+        let importers = workspace.module_importers(&exposing.module_name);
+        let mut declarations = Vec::new();
+        for declaration in top_level_declarations(&tree, &source) {
+            let symbol_info = SymbolAtPosition {
+                function_name: declaration.name.clone(),
+                module_path: None,
             };
-
-            changes_by_file.entry(location.uri).or_default().push(edit);
-        }
-
-        // Sort edits within each file by position (reverse order for safe application)
-        for edits in changes_by_file.values_mut() {
-            edits.sort_by(|a, b| {
-                let line_cmp = b.range.start.line.cmp(&a.range.start.line);
-                if line_cmp != std::cmp::Ordering::Equal {
-                    return line_cmp;
-                }
-                b.range.start.character.cmp(&a.range.start.character)
+            let usages = match symbols
+                .iter()
+                .find(|symbol| symbol.name == declaration.name && symbol.container_name.is_none())
+            {
+                Some(_) => match self
+                    .find_all_symbol_references(&workspace, &symbol_info, uri, None)
+                    .await
+                {
+                    Ok(references) => self.filter_out_declarations(references, &symbol_info).await,
+                    Err(e) => {
+                        warn!("Failed to find uses of {}: {}", declaration.name, e);
+                        Vec::new()
+                    }
+                },
+                None => Vec::new(),
+            };
+            declarations.push(ModuleExport {
+                exposed: exposing.exposes(&declaration.name),
+                external_usages: usages
+                    .iter()
+                    .filter(|location| importers.contains(&location.uri))
+                    .count(),
+                name: declaration.name,
+                kind: declaration.kind,
             });
         }
 
-        // Convert to DocumentChanges format for better atomicity
-        let document_changes: Vec<TextDocumentEdit> = changes_by_file
-            .into_iter()
-            .map(|(uri, edits)| TextDocumentEdit {
-                text_document: OptionalVersionedTextDocumentIdentifier {
-                    uri,
-                    version: None, // Let the client handle versioning
-                },
-                edits: edits.into_iter().map(OneOf::Left).collect(),
-            })
-            .collect();
-
-        WorkspaceEdit {
-            changes: None, // Use document_changes instead for better atomicity
-            document_changes: Some(DocumentChanges::Edits(document_changes)),
-            change_annotations: None,
-        }
+        Ok(Some(ModuleExportsResult {
+            module_name: exposing.module_name,
+            exposes_everything: exposing.everything,
+            declarations,
+        }))
     }
 
-    // Helper methods for symbol conversion
+    pub async fn search_docs(&self, params: SearchDocsParams) -> Result<Vec<DocSearchResult>> {
+        info!(
+            "Documentation search requested with query: '{}'",
+            params.query
+        );
 
-    /// Convert internal symbols to LSP DocumentSymbol format with hierarchy
-    fn convert_to_document_symbols(&self, symbols: Vec<GrenSymbol>) -> Vec<DocumentSymbol> {
-        let mut document_symbols = Vec::new();
-        let mut processed_modules = std::collections::HashSet::new();
-        let mut processed_types = std::collections::HashSet::new();
+        let limit = params
+            .limit
+            .unwrap_or(self.settings.workspace_symbols.max_results);
+        let workspace = self.workspace.read().await;
 
-        // Group symbols by type for hierarchical organization
-        let mut modules = Vec::new();
-        let mut types = Vec::new();
-        let mut functions = Vec::new();
-        let mut constructors = Vec::new();
+        let symbols = match workspace.search_documentation(&params.query, limit) {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                warn!(
+                    "Failed to search documentation for query '{}': {}",
+                    params.query, e
+                );
+                return Ok(Vec::new());
+            }
+        };
 
-        for symbol in &symbols {
-            match symbol.kind {
-                SymbolKind::MODULE => modules.push(symbol.clone()),
-                SymbolKind::CLASS => types.push(symbol.clone()),
-                SymbolKind::FUNCTION => functions.push(symbol.clone()),
-                SymbolKind::CONSTRUCTOR => constructors.push(symbol.clone()),
-                _ => {}
+        info!(
+            "Found {} symbols for query: '{}'",
+            symbols.len(),
+            params.query
+        );
+
+        Ok(symbols
+            .into_iter()
+            .map(|symbol| DocSearchResult {
+                documentation: symbol.documentation.unwrap_or_default(),
+                name: symbol.name,
+                kind: symbol.kind,
+                location: symbol.location,
+                container_name: symbol.container_name,
+                type_signature: symbol.type_signature,
+            })
+            .collect())
+    }
+
+    /// Files declaring the modules whose names match `params.module_name`, going by the
+    /// module graph rather than file paths
+    pub async fn open_module(&self, params: OpenModuleParams) -> Result<Vec<ModuleMatch>> {
+        let limit = params
+            .limit
+            .unwrap_or(self.settings.workspace_symbols.max_results);
+        let workspace = self.workspace.read().await;
+        let module_names = workspace.module_names();
+
+        let matches: Vec<ModuleMatch> = matching_modules(&params.module_name, &module_names)
+            .into_iter()
+            .filter_map(|module_name| {
+                let uri = workspace.module_uri(module_name)?;
+                let range = workspace
+                    .find_exact_symbols(module_name)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|symbol| symbol.kind == SymbolKind::MODULE && symbol.location.uri == uri)
+                    .map(|symbol| symbol.location.range)
+                    .unwrap_or_default();
+                Some(ModuleMatch {
+                    module_name: module_name.to_string(),
+                    location: Location::new(uri, range),
+                })
+            })
+            .take(limit)
+            .collect();
+
+        info!(
+            "Found {} modules matching '{}'",
+            matches.len(),
+            params.module_name
+        );
+        Ok(matches)
+    }
+
+    /// Where a toggle jumps from the position: from a constructor to the type declaring
+    /// it, from a function's type annotation to its definition, and back
+    pub async fn alternate_position(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<Location>> {
+        let uri = &params.text_document.uri;
+        let position = params.position;
+        let workspace = self.workspace.read().await;
+
+        if let Some(symbol_info) = self
+            .find_symbol_at_position(&workspace, uri, position)
+            .await
+            .filter(|symbol_info| symbol_info.function_name.starts_with(char::is_uppercase))
+        {
+            let definitions = self
+                .find_symbol_definitions(&workspace, &symbol_info, uri)
+                .await
+                .unwrap_or_default();
+            let type_declaration = definitions
+                .iter()
+                .filter(|symbol| symbol.kind == SymbolKind::CONSTRUCTOR)
+                .find_map(|constructor| {
+                    let type_name = constructor.container_name.as_ref()?;
+                    workspace
+                        .get_file_symbols(&constructor.location.uri)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .find(|symbol| {
+                            symbol.kind == SymbolKind::CLASS && &symbol.name == type_name
+                        })
+                });
+            if let Some(type_declaration) = type_declaration {
+                return Ok(Some(type_declaration.location));
             }
         }
 
-        // Add modules first (top-level)
-        for module in modules {
-            // Skip modules that are just file names or duplicates
-            if module.name.contains(" exposing ") || processed_modules.contains(&module.name) {
-                continue;
+        let source = match workspace.file_contents(uri) {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for alternate position: {}", e);
+                return Ok(None);
             }
+        };
+        let tree = match parser.parse(&source) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
+        Ok(alternate_function_range(&tree, &source, position)
+            .map(|range| Location::new(uri.clone(), range)))
+    }
 
-            let doc_symbol = DocumentSymbol {
-                name: module.name.clone(),
-                detail: None,
-                kind: module.kind,
-                range: module.location.range,
-                selection_range: module.location.range,
-                children: None,
-                tags: None,
-                #[allow(deprecated)]
-                deprecated: Some(false),
+    /// Top-level functions and values whose signatures mention a type, in index order.
+    /// Private declarations of dependency packages are left out, nothing can use them.
+    pub async fn find_values_of_type(
+        &self,
+        params: FindValuesOfTypeParams,
+    ) -> Result<Vec<ValueOfType>> {
+        let limit = params
+            .limit
+            .unwrap_or(self.settings.workspace_symbols.max_results);
+        let type_name = params.type_name.trim();
+        let workspace = self.workspace.read().await;
+        let symbols = workspace.symbols_using_type(type_name).map_err(|e| {
+            warn!(
+                "Failed to search the index for values of {}: {}",
+                type_name, e
+            );
+            tower_lsp::jsonrpc::Error {
+                code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                message: format!("Failed to search the index: {}", e).into(),
+                data: None,
+            }
+        })?;
+
+        let values: Vec<ValueOfType> = symbols
+            .into_iter()
+            .filter(|symbol| {
+                !workspace.is_package_file(&symbol.location.uri) || workspace.is_exposed(symbol)
+            })
+            .filter_map(|symbol| {
+                let usage = symbol.type_usage(type_name)?;
+                Some(ValueOfType {
+                    module_name: workspace.module_name(&symbol.location.uri),
+                    signature: symbol.type_signature?,
+                    name: symbol.name,
+                    location: symbol.location,
+                    consumes: usage.consumes,
+                    produces: usage.produces,
+                })
+            })
+            .take(limit)
+            .collect();
+
+        info!("Found {} values of type '{}'", values.len(), type_name);
+        Ok(values)
+    }
+
+    /// Size of the symbol index
+    pub async fn index_stats(&self) -> Result<IndexStats> {
+        let workspace = self.workspace.read().await;
+        workspace.index_stats().map_err(|e| {
+            warn!("Failed to read the index size: {}", e);
+            tower_lsp::jsonrpc::Error {
+                code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                message: format!("Failed to read the index size: {}", e).into(),
+                data: None,
+            }
+        })
+    }
+
+    /// Task markers from the index, grouped by file
+    pub async fn todos(&self) -> Result<Vec<FileTodos>> {
+        let workspace = self.workspace.read().await;
+        let todos = workspace.all_todos().map_err(|e| {
+            warn!("Failed to read task markers from the index: {}", e);
+            tower_lsp::jsonrpc::Error {
+                code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                message: format!("Failed to read task markers from the index: {}", e).into(),
+                data: None,
+            }
+        })?;
+
+        // The index returns markers ordered by file, so each file's markers are contiguous
+        let mut files: Vec<FileTodos> = Vec::new();
+        for todo in todos {
+            let entry = Todo {
+                marker: todo.marker,
+                text: todo.text,
+                range: todo.location.range,
             };
-            document_symbols.push(doc_symbol);
-            processed_modules.insert(module.name.clone());
+            match files.last_mut() {
+                Some(file) if file.uri == todo.location.uri => file.todos.push(entry),
+                _ => files.push(FileTodos {
+                    index_pending: workspace.index_generations().is_pending(&todo.location.uri),
+                    uri: todo.location.uri,
+                    todos: vec![entry],
+                }),
+            }
         }
 
-        // Sort types to process simple names first, then verbose ones
-        types.sort_by(|a, b| {
-            let a_is_verbose = a.name.starts_with("type ");
-            let b_is_verbose = b.name.starts_with("type ");
-            a_is_verbose.cmp(&b_is_verbose)
-        });
+        info!("Found task markers in {} files", files.len());
+        Ok(files)
+    }
 
-        // Process types with smart deduplication
-        for typ in types {
-            // Extract clean type name
-            let type_name = if typ.name.starts_with("type ") {
-                // Extract type name from "type Foo = ..." or "type alias Foo = ..."
-                if let Some(name_part) = typ.name.split_whitespace().nth(1) {
-                    name_part
-                        .split('=')
-                        .next()
-                        .unwrap_or(name_part)
-                        .trim()
-                        .to_string()
-                } else {
-                    continue;
-                }
-            } else {
-                typ.name.clone()
-            };
+    /// The most recent compile reports, oldest first
+    pub async fn compile_history(&self) -> Result<Vec<CompileReport>> {
+        let workspace = self.workspace.read().await;
+        Ok(workspace.compile_history())
+    }
 
-            // Skip if we already processed this type name
-            if processed_types.contains(&type_name) {
-                continue;
+    /// Deprecated symbols from the index with every place still using them
+    pub async fn deprecated_usages(&self) -> Result<Vec<DeprecatedSymbolUsages>> {
+        let workspace = self.workspace.read().await;
+        let symbols = workspace.deprecated_symbols().map_err(|e| {
+            warn!("Failed to read deprecated symbols from the index: {}", e);
+            tower_lsp::jsonrpc::Error {
+                code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                message: format!("Failed to read deprecated symbols from the index: {}", e).into(),
+                data: None,
             }
+        })?;
 
-            // Find constructors that belong to this type using container_name
-            let type_constructors: Vec<DocumentSymbol> = constructors
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for deprecated usages: {}", e);
+                return Ok(Vec::new());
+            }
+        };
+
+        let mut results = Vec::new();
+        // The index orders symbols by file, so each defining file's symbols are contiguous
+        let mut start = 0;
+        while start < symbols.len() {
+            let uri = symbols[start].location.uri.clone();
+            let end = start
+                + symbols[start..]
+                    .iter()
+                    .take_while(|symbol| symbol.location.uri == uri)
+                    .count();
+            let defined = &symbols[start..end];
+            start = end;
+
+            let module_name = workspace.module_name(&uri);
+            let names: Vec<DeprecatedName> = defined
                 .iter()
-                .filter(|c| {
-                    // Use the container_name field to properly associate constructors with their parent type
-                    c.container_name
-                        .as_ref()
-                        .map(|container| container == &type_name)
-                        .unwrap_or(false)
-                })
-                .map(|c| DocumentSymbol {
-                    name: c.name.clone(),
-                    detail: c.type_signature.clone(),
-                    kind: c.kind,
-                    range: c.location.range,
-                    selection_range: c.location.range,
-                    children: None,
-                    tags: None,
-                    #[allow(deprecated)]
-                    deprecated: Some(false),
+                .map(|symbol| DeprecatedName {
+                    name: symbol.name.clone(),
+                    parent_type: symbol
+                        .container_name
+                        .clone()
+                        .filter(|_| symbol.kind == SymbolKind::CONSTRUCTOR),
                 })
                 .collect();
 
-            let doc_symbol = DocumentSymbol {
-                name: type_name.clone(),
-                detail: typ.type_signature.clone(),
-                kind: typ.kind,
-                range: typ.location.range,
-                selection_range: typ.location.range,
-                children: if type_constructors.is_empty() {
-                    None
-                } else {
-                    Some(type_constructors)
-                },
-                tags: None,
-                #[allow(deprecated)]
-                deprecated: Some(false),
+            // Only the defining module and its importers can refer to its symbols
+            let files = match &module_name {
+                Some(module_name) => std::iter::once(uri.clone())
+                    .chain(
+                        workspace
+                            .module_importers(module_name)
+                            .into_iter()
+                            .filter(|importer| *importer != uri),
+                    )
+                    .collect(),
+                None => Vec::new(),
             };
 
-            document_symbols.push(doc_symbol);
-            processed_types.insert(type_name);
-        }
+            let mut usages: Vec<Vec<Location>> = vec![Vec::new(); defined.len()];
+            for file in files {
+                let source = match indexed_source(&workspace, &file) {
+                    Some(source) => source,
+                    None => continue,
+                };
+                let tree = match parser.parse(&source) {
+                    Ok(Some(tree)) => tree,
+                    _ => continue,
+                };
+                let module_name = module_name.as_deref().unwrap_or_default();
+                for (name, range) in deprecated_references(&tree, &source, module_name, &names) {
+                    for (index, symbol) in defined.iter().enumerate() {
+                        if symbol.name == name {
+                            usages[index].push(Location::new(file.clone(), range));
+                        }
+                    }
+                }
+            }
 
-        // Add functions (no deduplication needed as they're typically unique)
-        for function in functions {
-            let doc_symbol = DocumentSymbol {
-                name: function.name.clone(),
-                detail: function.type_signature.clone(),
-                kind: function.kind,
-                range: function.location.range,
-                selection_range: function.location.range,
-                children: None,
-                tags: None,
-                #[allow(deprecated)]
-                deprecated: Some(false),
-            };
-            document_symbols.push(doc_symbol);
+            for (symbol, usages) in defined.iter().zip(usages) {
+                results.push(DeprecatedSymbolUsages {
+                    name: symbol.name.clone(),
+                    kind: symbol.kind,
+                    location: symbol.location.clone(),
+                    module_name: module_name.clone(),
+                    message: symbol
+                        .deprecation()
+                        .filter(|message| !message.is_empty())
+                        .map(str::to_string),
+                    usages,
+                });
+            }
         }
 
-        // Sort by line number for consistent ordering
-        document_symbols.sort_by(|a, b| a.range.start.line.cmp(&b.range.start.line));
+        info!("Found {} deprecated symbols", results.len());
+        Ok(results)
+    }
 
-        debug!(
-            "Converted {} internal symbols to {} document symbols",
-            symbols.len(),
-            document_symbols.len()
-        );
+    /// Complexity metrics for the requested module, or for all loaded project modules
+    pub async fn metrics(&self, params: MetricsParams) -> Result<Vec<ModuleMetricsReport>> {
+        let mut workspace = self.workspace.write().await;
 
-        document_symbols
+        let metrics = match params.text_document {
+            Some(document) => workspace
+                .module_metrics(&document.uri)
+                .map(|metrics| (document.uri, metrics))
+                .into_iter()
+                .collect(),
+            None => workspace.project_module_metrics(),
+        };
+
+        info!("Computed metrics for {} modules", metrics.len());
+        Ok(metrics
+            .into_iter()
+            .map(|(uri, metrics)| metrics_report(uri, &metrics))
+            .collect())
     }
 
-    /// Convert internal symbol to LSP SymbolInformation format
-    fn convert_to_symbol_information(&self, symbol: GrenSymbol) -> SymbolInformation {
-        let container_name = symbol.container_name.clone();
+    /// Top-level functions and types of a file that nothing uses: not exposed by their
+    /// module and not referred to in it or in its importers. `main` is the entry point of
+    /// a program and never unused.
+    pub async fn unused_declarations(&self, uri: &Url) -> Vec<GrenSymbol> {
+        let workspace = self.workspace.read().await;
+        let symbols = match workspace.get_file_symbols(uri) {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                warn!("Failed to read symbols of {}: {}", uri, e);
+                return Vec::new();
+            }
+        };
 
-        SymbolInformation {
-            name: symbol.name,
-            kind: symbol.kind,
-            location: symbol.location,
-            container_name,
-            tags: None,
-            #[allow(deprecated)]
-            deprecated: Some(false),
+        let mut unused: Vec<GrenSymbol> = Vec::new();
+        for symbol in symbols {
+            let candidate = matches!(symbol.kind, SymbolKind::FUNCTION | SymbolKind::CLASS)
+                && symbol.name != "main"
+                && !workspace.is_exposed(&symbol)
+                && !unused.iter().any(|existing| existing.name == symbol.name);
+            if !candidate {
+                continue;
+            }
+            let symbol_info = SymbolAtPosition {
+                function_name: symbol.name.clone(),
+                module_path: None,
+            };
+            let count = self
+                .symbol_reference_count(&workspace, &symbol, &symbol_info, uri)
+                .await;
+            if count == Some(0) {
+                unused.push(symbol);
+            }
         }
-    }
 
-    /// Convert SymbolKind to CompletionItemKind
-    fn symbol_kind_to_completion_kind(&self, symbol_kind: SymbolKind) -> CompletionItemKind {
-        match symbol_kind {
-            SymbolKind::FUNCTION => CompletionItemKind::FUNCTION,
-            SymbolKind::VARIABLE => CompletionItemKind::VARIABLE,
-            SymbolKind::CLASS => CompletionItemKind::CLASS,
-            SymbolKind::INTERFACE => CompletionItemKind::INTERFACE,
-            SymbolKind::MODULE => CompletionItemKind::MODULE,
-            SymbolKind::PROPERTY => CompletionItemKind::PROPERTY,
-            SymbolKind::ENUM => CompletionItemKind::ENUM,
-            SymbolKind::CONSTRUCTOR => CompletionItemKind::CONSTRUCTOR,
-            SymbolKind::CONSTANT => CompletionItemKind::CONSTANT,
-            _ => CompletionItemKind::TEXT,
-        }
+        debug!("Found {} unused declarations in {}", unused.len(), uri);
+        unused
     }
 
-    /// Apply basic context filtering to determine if a symbol should be included in completion
-    fn should_include_symbol_in_completion(
-        &self,
-        symbol: &GrenSymbol,
-        _params: &CompletionParams,
-    ) -> bool {
-        // For now, implement basic filtering - can be enhanced later with more sophisticated context analysis
+    pub async fn capabilities_report(&self) -> Result<CapabilitiesReportResult> {
+        let workspace = self.workspace.read().await;
+        let mut features = Vec::new();
+
+        features.push(match workspace.compiler_unavailable_reason() {
+            None => FeatureReport::active("diagnostics"),
+            Some(reason) => FeatureReport::degraded(
+                "diagnostics",
+                format!(
+                    "{}. Only syntax-based hints are reported; type and compile errors need the Gren compiler",
+                    reason
+                ),
+            ),
+        });
 
-        // Skip very long or complex symbol names that are likely noise
-        if symbol.name.len() > 100 {
-            return false;
-        }
+        // Everything that looks symbols up goes through the index, so a broken index
+        // degrades all of these together
+        let index_problem = if workspace.is_symbol_index_read_only() {
+            Some("The symbol index is read-only".to_string())
+        } else {
+            workspace
+                .symbol_index_error()
+                .map(|error| format!("Writing to the symbol index failed: {}", error))
+        };
 
-        // Skip symbols that look like internal compiler artifacts
-        if symbol.name.starts_with("_") || symbol.name.contains("$") {
-            return false;
-        }
-
-        // Skip module declarations with exposing clauses (verbose forms)
-        if symbol.kind == SymbolKind::MODULE && symbol.name.contains(" exposing ") {
-            return false;
-        }
-
-        // Skip verbose type definitions in favor of clean type names
-        if symbol.kind == SymbolKind::CLASS
-            && symbol.name.starts_with("type ")
-            && symbol.name.contains(" = ")
-        {
-            return false;
+        for feature in [
+            "symbolIndex",
+            "completion",
+            "definition",
+            "references",
+            "workspaceSymbols",
+            "searchDocs",
+        ] {
+            let report = match &index_problem {
+                None => FeatureReport::active(feature),
+                Some(problem) => FeatureReport::degraded(
+                    feature,
+                    format!(
+                        "{}, so symbols from changed files aren't indexed and results may be missing or out of date",
+                        problem
+                    ),
+                ),
+            };
+            features.push(if feature == "workspaceSymbols" {
+                report.with_usage(WORKSPACE_SYMBOL_QUERY_SYNTAX)
+            } else {
+                report
+            });
         }
 
-        true
-    }
-
-    /// Find the symbol name at the given cursor position
-    async fn find_symbol_at_position(
-        &self,
-        workspace: &gren_lsp_core::Workspace,
-        uri: &Url,
-        position: Position,
-    ) -> Option<SymbolAtPosition> {
-        // Get the document text
-        let document = workspace.get_document_readonly(uri)?;
-        let lines: Vec<&str> = document.text().lines().collect();
+        features.push(match workspace.compiler_unavailable_reason() {
+            None => FeatureReport::active("formatting"),
+            Some(reason) => FeatureReport::degraded(
+                "formatting",
+                format!("{}. Formatting runs the compiler's `gren format`", reason),
+            ),
+        });
 
-        // Check if position is valid
-        if position.line as usize >= lines.len() {
-            return None;
+        // Generated modules indexed from their signatures leave their private declarations
+        // and the references in their bodies out of the index
+        let signatures_only = workspace.index_generations().signatures_only_files();
+        if !signatures_only.is_empty() {
+            features.push(FeatureReport::degraded(
+                "signaturesOnlyIndex",
+                format!(
+                    "{} modules are indexed from the signatures of what they expose, as `index.signatures_only` in {} asks. Their private declarations and the references in their bodies aren't found",
+                    signatures_only.len(),
+                    gren_lsp_core::PROJECT_CONFIG_FILE
+                ),
+            ));
         }
 
-        let line = lines[position.line as usize];
-        let char_pos = position.character as usize;
-
-        if char_pos >= line.len() {
-            return None;
-        }
+        features.push(if self.settings.hover.docs_links {
+            FeatureReport::active("hoverDocsLinks")
+        } else {
+            FeatureReport::disabled("hoverDocsLinks", "Disabled by the hover.docsLinks setting")
+        });
 
-        // Find word boundaries around the cursor position
-        let chars: Vec<char> = line.chars().collect();
+        features.push(if self.settings.hover.reference_count {
+            FeatureReport::active("hoverReferenceCount")
+        } else {
+            FeatureReport::disabled(
+                "hoverReferenceCount",
+                "Disabled by the hover.referenceCount setting",
+            )
+        });
 
-        // Check if we're on a valid identifier character
-        if char_pos >= chars.len() || !self.is_identifier_char(chars[char_pos]) {
-            return None;
-        }
+        features.push(if self.settings.metrics.hints {
+            FeatureReport::active("metricHints")
+        } else {
+            FeatureReport::disabled("metricHints", "Disabled by the metrics.hints setting")
+        });
 
-        // Find the start of the identifier
-        let mut start = char_pos;
-        while start > 0 && self.is_identifier_char(chars[start - 1]) {
-            start -= 1;
-        }
+        features.push(if self.settings.exports.code_lens {
+            FeatureReport::active("exportsCodeLens")
+        } else {
+            FeatureReport::disabled(
+                "exportsCodeLens",
+                "Disabled by the exports.codeLens setting",
+            )
+        });
 
-        // Find the end of the identifier
-        let mut end = char_pos;
-        while end < chars.len() && self.is_identifier_char(chars[end]) {
-            end += 1;
-        }
+        features.push(if self.settings.runtime_log.code_lens {
+            FeatureReport::active("runtimeLogCodeLens")
+        } else {
+            FeatureReport::disabled(
+                "runtimeLogCodeLens",
+                "Disabled by the runtimeLog.codeLens setting",
+            )
+        });
 
-        // Extract the identifier
-        let identifier: String = chars[start..end].iter().collect();
+        features.push(if self.settings.inlay_hints.types {
+            FeatureReport::active("typeInlayHints")
+        } else {
+            FeatureReport::disabled("typeInlayHints", "Disabled by the inlayHints.types setting")
+        });
 
-        // Filter out keywords and single characters
-        if identifier.len() < 2 || self.is_keyword(&identifier) {
-            return None;
-        }
+        features.push(if self.settings.inlay_hints.parameter_names {
+            FeatureReport::active("parameterNameInlayHints")
+        } else {
+            FeatureReport::disabled(
+                "parameterNameInlayHints",
+                "Disabled by the inlayHints.parameterNames setting",
+            )
+        });
 
-        // Check if this is a qualified name like "Utils.isEmpty" or "Gren.Kernel.Bytes.flatten"
-        // Look backwards to find the full qualified path
-        let mut module_path = Vec::new();
-        let mut search_start = start;
+        Ok(CapabilitiesReportResult { features })
+    }
 
-        // Keep looking backwards for module qualifiers
-        while search_start > 0 && chars[search_start - 1] == '.' {
-            // Find the module name before this dot
-            let mut module_start = search_start - 1;
-            while module_start > 0 && self.is_identifier_char(chars[module_start - 1]) {
-                module_start -= 1;
-            }
+    pub async fn workspace_symbols(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        self.workspace_symbols_with_capabilities(params, None).await
+    }
 
-            if module_start < search_start - 1 {
-                let module_name: String = chars[module_start..search_start - 1].iter().collect();
-                module_path.insert(0, module_name); // Insert at beginning to maintain order
-                search_start = module_start;
-            } else {
-                break;
-            }
-        }
+    pub async fn workspace_symbols_with_capabilities(
+        &self,
+        params: WorkspaceSymbolParams,
+        client_capabilities: Option<&ClientCapabilities>,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        info!("Workspace symbols requested with query: '{}'", params.query);
 
-        let symbol_info = if module_path.is_empty() {
-            SymbolAtPosition {
-                function_name: identifier,
-                module_path: None,
-            }
+        // Capped so that empty or single-character queries don't pull the entire index
+        // into one response
+        let max_results = self.settings.workspace_symbols.max_results;
+        let (symbols, _) = self
+            .workspace_symbols_page(&params.query, client_capabilities, 0, max_results)
+            .await;
+        if symbols.is_empty() {
+            debug!("No symbols found for query: '{}'", params.query);
         } else {
             info!(
-                "Found qualified reference: {}.{}",
-                module_path.join("."),
-                identifier
+                "Found {} symbols for query: '{}' (limit {})",
+                symbols.len(),
+                params.query,
+                max_results
             );
-            SymbolAtPosition {
-                function_name: identifier,
-                module_path: Some(module_path),
+        }
+        Ok(Some(symbols))
+    }
+
+    /// One page of `workspace/symbol` results: the matches among the store's `limit`
+    /// candidates after the first `offset`, and whether the store may have more after them.
+    /// `Data.List:map`, `module:Data.*`, `kind:type` and `file:src/Pages/**` narrow the
+    /// search down.
+    pub async fn workspace_symbols_page(
+        &self,
+        query: &str,
+        client_capabilities: Option<&ClientCapabilities>,
+        offset: usize,
+        limit: usize,
+    ) -> (Vec<SymbolInformation>, bool) {
+        let workspace = self.workspace.read().await;
+        let query = SymbolQuery::parse(query);
+        let mut symbols = match workspace.find_symbols_matching(&query, offset, limit) {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                warn!("Failed to search symbols for query '{}': {}", query.name, e);
+                return (Vec::new(), false);
             }
         };
+        let more = limit > 0 && symbols.len() == limit;
+
+        // Clients listing the symbol kinds they support only get those
+        if let Some(supported) = client_capabilities
+            .and_then(|caps| caps.workspace.as_ref())
+            .and_then(|workspace| workspace.symbol.as_ref())
+            .and_then(|symbol| symbol.symbol_kind.as_ref())
+            .and_then(|kinds| kinds.value_set.as_ref())
+        {
+            symbols.retain(|symbol| supported.contains(&symbol.kind));
+        }
 
-        Some(symbol_info)
-    }
-
-    /// Check if a character is part of an identifier
-    fn is_identifier_char(&self, c: char) -> bool {
-        c.is_alphanumeric() || c == '_'
+        let symbol_information = symbols
+            .into_iter()
+            .map(|symbol| self.convert_to_symbol_information(symbol))
+            .collect();
+        (symbol_information, more)
     }
 
-    /// Check if a string is a Gren keyword
-    fn is_keyword(&self, word: &str) -> bool {
-        matches!(
-            word,
-            "if" | "then"
-                | "else"
-                | "case"
-                | "of"
-                | "let"
-                | "in"
-                | "type"
-                | "alias"
-                | "module"
-                | "import"
-                | "exposing"
-                | "as"
-                | "where"
-        )
+    pub async fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> Result<Option<CodeActionResponse>> {
+        self.code_action_with_capabilities(params, None).await
     }
 
-    /// Find a symbol in a specific module context
-    async fn find_qualified_symbol(
+    pub async fn code_action_with_capabilities(
         &self,
-        workspace: &gren_lsp_core::Workspace,
-        function_name: &str,
-        module_path: &[String],
-    ) -> anyhow::Result<Vec<gren_lsp_core::Symbol>> {
+        params: CodeActionParams,
+        client_capabilities: Option<&ClientCapabilities>,
+    ) -> Result<Option<CodeActionResponse>> {
         info!(
-            "Searching for '{}' in module '{}'",
-            function_name,
-            module_path.join(".")
+            "Code action requested for range {}:{}-{}:{} in file {}",
+            params.range.start.line,
+            params.range.start.character,
+            params.range.end.line,
+            params.range.end.character,
+            params.text_document.uri
         );
 
-        // Check if this is a Gren.Kernel.* module - these are built-in and don't have user files
-        if module_path.len() >= 2 && module_path[0] == "Gren" && module_path[1] == "Kernel" {
-            info!("Detected Gren.Kernel.* module '{}' - these are built-in functions with no user-defined files", 
-                  module_path.join("."));
-            return Ok(Vec::new());
+        let mut actions = Vec::new();
+        let workspace = self.workspace.read().await;
+
+        // Handle quick fix actions (import suggestions for unresolved symbols)
+        if params.context.only.is_none()
+            || params
+                .context
+                .only
+                .as_ref()
+                .unwrap()
+                .contains(&CodeActionKind::QUICKFIX)
+        {
+            // Clients showing disabled actions are told why an import isn't offered
+            let disabled_support = client_capabilities
+                .and_then(|caps| caps.text_document.as_ref())
+                .and_then(|text_document| text_document.code_action.as_ref())
+                .and_then(|code_action| code_action.disabled_support)
+                .unwrap_or(false);
+            if let Some(quickfix_actions) = self
+                .generate_import_quickfix_actions(&workspace, &params, disabled_support)
+                .await
+            {
+                actions.extend(quickfix_actions);
+            }
+            actions.extend(self.generate_import_style_fixes(&workspace, &params));
+            actions.extend(self.generate_import_conflict_fixes(&workspace, &params));
+            actions.extend(self.generate_shadowing_fixes(&workspace, &params));
+            // The compiler's list of missing patterns is exact, so the branches worked out
+            // from the index are only offered without it
+            let missing_pattern_fixes = self.generate_missing_pattern_fixes(&workspace, &params);
+            if missing_pattern_fixes.is_empty() {
+                actions.extend(self.generate_missing_branches_action(&workspace, &params));
+            } else {
+                actions.extend(missing_pattern_fixes);
+            }
+            actions.extend(self.generate_alias_cycle_fixes(&workspace, &params));
+            actions.extend(self.generate_compiler_hint_fixes(&workspace, &params));
+            actions.extend(
+                removal_fixes(&params.text_document.uri, &params.context.diagnostics)
+                    .into_iter()
+                    .map(CodeActionOrCommand::CodeAction),
+            );
+            actions.extend(self.generate_unexposed_import_fixes(&workspace, &params));
+            actions.extend(self.generate_unused_exposed_fixes(&workspace, &params));
+            actions.extend(generate_formatting_fixes(&params));
         }
 
-        // First, try to find symbols with the function name
-        let all_symbols = workspace.find_exact_symbols(function_name)?;
+        // Handle source organize imports actions
+        if params.context.only.is_none()
+            || params
+                .context
+                .only
+                .as_ref()
+                .unwrap()
+                .contains(&CodeActionKind::SOURCE_ORGANIZE_IMPORTS)
+        {
+            if let Some(organize_action) = self
+                .generate_organize_imports_action(&workspace, &params.text_document.uri)
+                .await
+            {
+                actions.push(CodeActionOrCommand::CodeAction(organize_action));
+            }
+            actions.extend(
+                self.generate_conflict_organize_actions(&workspace, &params.text_document.uri)
+                    .into_iter()
+                    .map(CodeActionOrCommand::CodeAction),
+            );
+        }
 
-        // Filter symbols that match the specified module path
-        let qualified_symbols: Vec<_> = all_symbols
-            .into_iter()
-            .filter(|symbol| self.symbol_matches_module_path(symbol, module_path))
-            .collect();
+        // Handle sorting and moving top-level declarations
+        let only = params.context.only.as_deref();
+        let sort = kind_requested(only, &SOURCE_SORT_DECLARATIONS);
+        let moves = kind_requested(only, &SOURCE_MOVE_DECLARATION);
+        if sort || moves {
+            actions
+                .extend(self.generate_declaration_order_actions(&workspace, &params, sort, moves));
+        }
 
-        // For qualified calls, we only return results if we have high confidence
-        // Better to show nothing than the wrong thing
-        if !qualified_symbols.is_empty() {
-            info!(
-                "Found {} qualified matches for '{}' in module '{}'",
-                qualified_symbols.len(),
-                function_name,
-                module_path.join(".")
-            );
-            Ok(qualified_symbols)
+        if kind_requested(only, &CodeActionKind::REFACTOR_REWRITE) {
+            actions.extend(self.generate_exposing_actions(&workspace, &params));
+        }
+
+        // Annotating a value needs a compile, left until the action is picked when the
+        // client resolves edits
+        let unannotated = if kind_requested(only, &CodeActionKind::REFACTOR_REWRITE) {
+            self.unannotated_value_in(&workspace, &params)
         } else {
-            info!("No qualified matches found for '{}' in module '{}' - returning empty to avoid incorrect results", 
-                  function_name, module_path.join("."));
-            Ok(Vec::new())
+            None
+        };
+        drop(workspace);
+        if let Some(value) = unannotated {
+            let action = add_annotation_action(&params.text_document.uri, &value);
+            if resolves_code_action_edits(client_capabilities) {
+                actions.push(CodeActionOrCommand::CodeAction(action));
+            } else if let Ok(action) = self.code_action_resolve(action).await {
+                actions.push(CodeActionOrCommand::CodeAction(action));
+            }
+        }
+
+        if actions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(actions))
         }
     }
 
-    /// Check if a symbol likely belongs to the specified module path
-    /// This is highly conservative - we only return true when we have very strong confidence
-    fn symbol_matches_module_path(
-        &self,
-        symbol: &gren_lsp_core::Symbol,
-        module_path: &[String],
-    ) -> bool {
-        let file_path = symbol.location.uri.path();
+    /// Fill in the edit of a code action offered without one. Only "Add type annotation"
+    /// is, as the type comes from a compile of the module.
+    pub async fn code_action_resolve(&self, mut action: CodeAction) -> Result<CodeAction> {
+        let data = match action
+            .data
+            .as_ref()
+            .and_then(|data| data.get("addAnnotation"))
+        {
+            Some(data) => data,
+            None => return Ok(action),
+        };
+        let uri = data
+            .get("uri")
+            .and_then(|uri| uri.as_str())
+            .and_then(|uri| Url::parse(uri).ok());
+        let name = data.get("name").and_then(|name| name.as_str());
+        let (uri, name) = match (uri, name) {
+            (Some(uri), Some(name)) => (uri, name.to_string()),
+            _ => return Ok(action),
+        };
 
-        info!(
-            "🔍 Checking if symbol '{}' in file '{}' matches module path '{}'",
-            symbol.name,
-            file_path,
-            module_path.join(".")
+        let mut workspace = self.workspace.write().await;
+        let value = workspace.get_document_readonly(&uri).and_then(|document| {
+            let content = document.text();
+            let mut parser = gren_lsp_core::Parser::new().ok()?;
+            let tree = parser.parse(content).ok()??;
+            unannotated_value(&tree, content, &name)
+        });
+        let value = match value {
+            Some(value) => value,
+            None => {
+                return Err(request_failed(format!(
+                    "`{}` has a type annotation or is gone",
+                    name
+                )))
+            }
+        };
+        let annotation = match workspace.inferred_type(&uri, &name).await {
+            Ok(Some(annotation)) => annotation,
+            Ok(None) => {
+                return Err(request_failed(format!(
+                    "The type of `{}` can't be worked out until the module compiles",
+                    name
+                )))
+            }
+            Err(e) => {
+                warn!("Failed to infer the type of {}: {}", name, e);
+                return Err(request_failed(format!(
+                    "The type of `{}` can't be worked out: {}",
+                    name, e
+                )));
+            }
+        };
+        info!("Inferred {} : {}", name, annotation);
+
+        let position = Position::new(value.line, 0);
+        let edit = TextEdit::new(
+            Range::new(position, position),
+            format!("{}\n", annotation_text(&name, &annotation)),
         );
+        action.edit = Some(WorkspaceEdit::new(
+            [(uri, vec![edit])].into_iter().collect(),
+        ));
+        Ok(action)
+    }
 
-        // For qualified calls, we demand very high confidence in the match
-        // It's better to show no results than incorrect ones
+    /// "Add `name` to the exposing list" or "Remove `name` from the exposing list" for the
+    /// top-level declaration at the cursor. Removing a name from `exposing (..)` lists
+    /// everything else instead.
+    fn generate_exposing_actions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+        let source = document.text();
+        let tree = match gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(source).ok().flatten())
+        {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let (exposing, declaration) = match (
+            module_exposing(&tree, source),
+            declaration_on_line(&tree, source, params.range.start.line),
+        ) {
+            (Some(exposing), Some(declaration)) => (exposing, declaration),
+            _ => return Vec::new(),
+        };
 
-        if module_path.is_empty() {
-            info!("❌ Module path is empty");
-            return false;
+        let name = declaration.name;
+        let (title, edit) = if exposing.exposes(&name) {
+            (
+                format!("Remove `{}` from the exposing list", name),
+                edit_exposing(&tree, source, &[], &[name.clone()]),
+            )
+        } else {
+            (
+                format!("Add `{}` to the exposing list", name),
+                edit_exposing(&tree, source, &[name.clone()], &[]),
+            )
+        };
+        match edit {
+            Some(edit) => vec![CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit::new(
+                    [(uri.clone(), vec![edit])].into_iter().collect(),
+                )),
+                ..Default::default()
+            })],
+            None => Vec::new(),
         }
+    }
 
-        let empty_string = String::new();
-        let module_name = module_path.last().unwrap_or(&empty_string);
-
-        if module_name.is_empty() {
-            info!("❌ Module name is empty");
-            return false;
+    /// "Remove `name` from the exposing list" for exposed values no other module uses
+    fn generate_unused_exposed_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let unused: Vec<&Diagnostic> = params
+            .context
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.code == Some(NumberOrString::String(UNUSED_EXPOSED.to_string()))
+            })
+            .collect();
+        if unused.is_empty() {
+            return Vec::new();
         }
 
-        // Pattern 1: Exact module file match - /path/to/ModuleName.gren
-        let pattern1 = format!("/{}.gren", module_name);
-        if file_path.ends_with(&pattern1) {
-            info!("✅ Symbol matches exact module file pattern: {}", pattern1);
-            return true;
-        } else {
-            info!(
-                "❌ Pattern 1 failed: '{}' does not end with '{}'",
-                file_path, pattern1
-            );
-        }
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+        let source = document.text();
+        let tree = match gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(source).ok().flatten())
+        {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
 
-        // Pattern 2: Full hierarchical path - /path/to/Gren/Kernel/Bytes.gren
-        let full_path = module_path.join("/");
-        let pattern2 = format!("/{}.gren", full_path);
-        if file_path.ends_with(&pattern2) {
-            info!("✅ Symbol matches full hierarchical path: {}", pattern2);
-            return true;
-        } else {
-            info!(
-                "❌ Pattern 2 failed: '{}' does not end with '{}'",
-                file_path, pattern2
-            );
-        }
+        unused
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let range = diagnostic.range;
+                let name = source
+                    .lines()
+                    .nth(range.start.line as usize)?
+                    .get(range.start.character as usize..range.end.character as usize)?;
+                let edit = edit_exposing(&tree, source, &[], &[name.to_string()])?;
+                Some(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Remove `{}` from the exposing list", name),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(
+                        [(uri.clone(), vec![edit])].into_iter().collect(),
+                    )),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }))
+            })
+            .collect()
+    }
 
-        // Pattern 3: Check if the file path contains the complete module hierarchy
-        // For Gren.Kernel.Bytes, we want files containing /Gren/Kernel/Bytes.gren
-        if module_path.len() > 1 {
-            let hierarchical_pattern = format!("/{}.gren", module_path.join("/"));
-            if file_path.contains(&hierarchical_pattern) {
-                info!(
-                    "✅ Symbol matches hierarchical module pattern: {}",
-                    hierarchical_pattern
-                );
-                return true;
-            } else {
-                info!(
-                    "❌ Pattern 3 failed: '{}' does not contain '{}'",
-                    file_path, hierarchical_pattern
-                );
+    /// The unannotated top-level value whose definition starts where the code actions are
+    /// asked for
+    fn unannotated_value_in(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Option<UnannotatedValue> {
+        let document = workspace.get_document_readonly(&params.text_document.uri)?;
+        let content = document.text();
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(content).ok()??;
+        unannotated_value_at(&tree, content, params.range.start.line)
+    }
+
+    /// The name a rename at the position would change, or an error saying why it can't
+    pub async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let workspace = self.workspace.read().await;
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Ok(None),
+        };
+        let content = document.text();
+        let mut parser = gren_lsp_core::Parser::new()
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+        let tree = match parser.parse(content) {
+            Ok(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
+
+        let range = rename_range(&tree, content, params.position).map_err(|message| {
+            info!("Refusing to prepare rename: {}", message);
+            rename_rejected(message)
+        })?;
+        let placeholder = content
+            .lines()
+            .nth(range.start.line as usize)
+            .and_then(|line| line.get(range.start.character as usize..range.end.character as usize))
+            .unwrap_or_default()
+            .to_string();
+
+        // Locals are always in this file; anything else is checked for where it's defined
+        if self
+            .local_rename_ranges(&workspace, uri, range.start)
+            .is_none()
+        {
+            if let Some(symbol_info) = self
+                .find_symbol_at_position(&workspace, uri, range.start)
+                .await
+            {
+                if let Some(error) = self.rename_refusal(&workspace, &symbol_info, uri).await {
+                    info!("Refusing to prepare rename: {}", error.message);
+                    return Err(error);
+                }
             }
         }
 
-        // No match found - be conservative and reject
-        info!(
-            "❌ Symbol in '{}' does not match required module path '{}'",
-            file_path,
-            module_path.join(".")
-        );
-        false
+        Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range,
+            placeholder,
+        }))
     }
 
-    /// Find an unqualified symbol by checking local symbols first, then imports only
-    /// No workspace fallback - if it's not local or imported, it's a compile error
-    async fn find_unqualified_symbol(
-        &self,
-        workspace: &gren_lsp_core::Workspace,
-        file_uri: &Url,
-        symbol_name: &str,
-    ) -> anyhow::Result<Vec<gren_lsp_core::Symbol>> {
+    pub async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
         info!(
-            "Finding unqualified symbol '{}' in file '{}'",
-            symbol_name, file_uri
+            "Rename requested at position {}:{} with new name '{}'",
+            params.text_document_position.position.line,
+            params.text_document_position.position.character,
+            params.new_name
         );
 
-        // First, check for local symbols in the same file
-        let local_symbols = workspace.get_file_symbols(file_uri)?;
-        let local_matches: Vec<_> = local_symbols
-            .into_iter()
-            .filter(|symbol| {
-                symbol.name == symbol_name &&
-                // Prefer function definitions over other types for go-to-definition
-                (symbol.kind == SymbolKind::FUNCTION ||
-                 symbol.kind == SymbolKind::CLASS ||
-                 symbol.kind == SymbolKind::CONSTRUCTOR)
-            })
-            .collect();
+        let workspace = self.workspace.read().await;
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let new_name = &params.new_name;
 
-        if !local_matches.is_empty() {
-            info!(
-                "Found {} local symbols for '{}' in same file - using local definition",
-                local_matches.len(),
-                symbol_name
-            );
-            return Ok(local_matches);
+        // Validate the new name is a valid Gren identifier
+        if !self.is_valid_gren_identifier(new_name) {
+            warn!("Invalid identifier name for rename: '{}'", new_name);
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "'{}' is not a valid Gren identifier",
+                new_name
+            )));
         }
 
-        // Next, parse imports from the current file
-        let import_map = self.parse_imports(workspace, file_uri).await;
-
-        // Check if this symbol is explicitly imported from a specific module
-        if let Some(module_name) = import_map.get(symbol_name) {
+        // Locals are renamed within the scope binding them, never by the global search,
+        // which would also rename same-named locals in other branches and functions
+        if let Some(ranges) = self.local_rename_ranges(&workspace, uri, position) {
+            if !new_name.starts_with(|c: char| c.is_lowercase()) {
+                return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                    "'{}' is not a valid name for a local",
+                    new_name
+                )));
+            }
             info!(
-                "Symbol '{}' is imported from module '{}' - searching there only",
-                symbol_name, module_name
+                "Renaming local at {}:{} in its scope, {} occurrences",
+                position.line,
+                position.character,
+                ranges.len()
             );
-
-            // Search for the symbol in the specific imported module
-            let all_symbols = workspace.find_exact_symbols(symbol_name)?;
-            let module_symbols: Vec<_> = all_symbols
+            let references = ranges
                 .into_iter()
-                .filter(|symbol| {
-                    // Check if the symbol is from the imported module
-                    self.symbol_is_from_module(symbol, module_name)
-                })
+                .map(|range| Location::new(uri.clone(), range))
                 .collect();
+            return Ok(Some(
+                self.generate_workspace_edit_for_rename(references, new_name),
+            ));
+        }
 
-            if !module_symbols.is_empty() {
-                info!(
-                    "Found {} symbols from imported module '{}'",
-                    module_symbols.len(),
-                    module_name
-                );
-                return Ok(module_symbols);
-            } else {
-                info!(
-                    "No symbols found in imported module '{}' - this might be a built-in",
-                    module_name
-                );
-                return Ok(Vec::new());
+        // Without types a field can't be tied to one record, so every field of that name
+        // is renamed, in every module
+        if let Some(field) = self.field_at_position(&workspace, uri, position) {
+            if !new_name.starts_with(|c: char| c.is_lowercase()) {
+                return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                    "'{}' is not a valid field name",
+                    new_name
+                )));
             }
+            info!("Renaming record field '{}' to '{}'", field, new_name);
+            return Ok(Some(self.field_rename_edit(&workspace, &field, new_name)));
         }
 
-        // If not found locally or in imports, return empty (don't do workspace search)
-        // This would be a compile error in valid Gren code
-        info!(
-            "Symbol '{}' not found locally or in imports - would be compile error",
-            symbol_name
-        );
-        Ok(Vec::new())
-    }
-
-    /// Parse import statements from a file to build a symbol -> module mapping
-    async fn parse_imports(
-        &self,
-        workspace: &gren_lsp_core::Workspace,
-        file_uri: &Url,
-    ) -> std::collections::HashMap<String, String> {
-        let mut import_map = std::collections::HashMap::new();
+        // Find the symbol at the cursor position
+        match self
+            .find_symbol_at_position(&workspace, uri, position)
+            .await
+        {
+            Some(symbol_info) => {
+                info!("Found symbol for rename: {:?}", symbol_info);
 
-        // Get the document content
-        if let Some(document) = workspace.get_document_readonly(file_uri) {
-            let content = document.text();
+                // Definitions outside the workspace can't be edited, and renaming only
+                // their uses here would break the code
+                if let Some(error) = self.rename_refusal(&workspace, &symbol_info, uri).await {
+                    warn!("Refusing rename: {}", error.message);
+                    return Err(error);
+                }
 
-            // Parse import statements line by line
-            for line in content.lines() {
-                let line = line.trim();
+                // Types and constructors are capitalized, values aren't
+                let capitalized = |name: &str| name.starts_with(|c: char| c.is_uppercase());
+                if capitalized(&symbol_info.function_name) != capitalized(new_name) {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "'{}' can't rename '{}': types and constructors start with a capital \
+                         letter, values don't",
+                        new_name, symbol_info.function_name
+                    )));
+                }
 
-                // Look for import statements with exposing clause
-                if line.starts_with("import ") && line.contains(" exposing ") {
-                    if let Some((module_part, exposing_part)) = line.split_once(" exposing ") {
-                        // Extract the actual module name, handling aliases
-                        // e.g., "import Dedris.Motion as Motion" -> "Dedris.Motion"
-                        let module_declaration = module_part.trim_start_matches("import ").trim();
-                        let module_name = if module_declaration.contains(" as ") {
-                            // Split on " as " and take the first part (actual module name)
-                            module_declaration.split(" as ").next().unwrap().trim()
-                        } else {
-                            module_declaration
-                        };
+                // Find all references to this symbol
+                let references = match self
+                    .find_all_symbol_references(&workspace, &symbol_info, uri, Some(position))
+                    .await
+                {
+                    Ok(refs) => refs,
+                    Err(e) => {
+                        warn!(
+                            "Failed to find references for symbol '{}': {}",
+                            symbol_info.function_name, e
+                        );
+                        return Ok(None);
+                    }
+                };
 
-                        // Parse the exposing clause
-                        let exposing_content = exposing_part.trim();
+                if references.is_empty() {
+                    info!(
+                        "No references found for symbol '{}'",
+                        symbol_info.function_name
+                    );
+                    return Ok(None);
+                }
 
-                        // Handle different exposing formats
-                        if exposing_content == "(..)" {
-                            // exposing (..) - exposes everything, we can't track specific symbols
-                            info!("Found exposing (..) for module '{}' - cannot track specific imports", module_name);
-                        } else if exposing_content.starts_with('(')
-                            && exposing_content.ends_with(')')
-                        {
-                            // exposing (symbol1, symbol2, ...)
-                            let symbols_str = &exposing_content[1..exposing_content.len() - 1];
-                            for symbol in symbols_str.split(',') {
-                                let symbol = symbol.trim();
-                                if !symbol.is_empty() {
-                                    info!(
-                                        "Found import: '{}' from module '{}'",
-                                        symbol, module_name
-                                    );
-                                    import_map.insert(symbol.to_string(), module_name.to_string());
-                                }
+                // A definition and its type annotation are renamed together
+                let references =
+                    paired_function_names(&workspace, &symbol_info.function_name, references);
+
+                // Generate workspace edit for all references
+                let workspace_edit = self.generate_workspace_edit_for_rename(references, new_name);
+
+                info!(
+                    "Generated workspace edit for rename of '{}' to '{}' with {} changes",
+                    symbol_info.function_name,
+                    new_name,
+                    workspace_edit
+                        .document_changes
+                        .as_ref()
+                        .map(|changes| {
+                            match changes {
+                                DocumentChanges::Edits(edits) => edits.len(),
+                                DocumentChanges::Operations(ops) => ops.len(),
                             }
-                        }
-                    }
-                }
+                        })
+                        .unwrap_or(0)
+                );
+
+                Ok(Some(workspace_edit))
+            }
+            None => {
+                info!("No symbol found at rename position");
+                Ok(None)
             }
         }
-
-        import_map
     }
 
-    /// Check if a symbol is from a specific module based on file path
-    fn symbol_is_from_module(&self, symbol: &gren_lsp_core::Symbol, module_name: &str) -> bool {
-        let file_path = symbol.location.uri.path();
+    /// Check whether a renamed file still declares the module its new path implies and,
+    /// if not, build the edit that rewrites the module declaration and all importers.
+    /// Used for `workspace/didRenameFiles`, where the client has already moved the file.
+    pub async fn module_rename_for_file_rename(
+        &self,
+        new_uri: &Url,
+    ) -> Result<Option<ModuleRenameSync>> {
+        let workspace = self.workspace.read().await;
 
-        // Handle hierarchical module names (e.g., "Dedris.Motion" -> "/Dedris/Motion.gren")
-        let hierarchical_pattern = format!("/{}.gren", module_name.replace(".", "/"));
-        let simple_pattern = format!("/{}.gren", module_name);
+        let content = match indexed_source(&workspace, new_uri) {
+            Some(content) => content,
+            None => {
+                info!("Renamed file is not in the workspace: {}", new_uri);
+                return Ok(None);
+            }
+        };
 
-        // Try hierarchical pattern first, then fall back to simple pattern
-        let matches =
-            file_path.ends_with(&hierarchical_pattern) || file_path.ends_with(&simple_pattern);
+        let (declared_module, declaration_range) = match self.extract_declared_module_name(&content)
+        {
+            Some(declaration) => declaration,
+            None => {
+                info!("No module declaration found in renamed file: {}", new_uri);
+                return Ok(None);
+            }
+        };
 
-        if matches {
-            let matched_pattern = if file_path.ends_with(&hierarchical_pattern) {
-                &hierarchical_pattern
-            } else {
-                &simple_pattern
-            };
-            info!(
-                "✅ Symbol '{}' matches module '{}' using pattern '{}' (file: {})",
-                symbol.name, module_name, matched_pattern, file_path
+        let expected_module = match self.extract_module_name_from_path(new_uri) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+
+        if declared_module == expected_module {
+            debug!(
+                "Module declaration '{}' already matches path of {}",
+                declared_module, new_uri
             );
-        } else {
+            return Ok(None);
+        }
+
+        // Only offer the rewrite when the new path yields a legal module name,
+        // otherwise we'd be replacing one broken state with another
+        if !is_valid_module_name(&expected_module) {
             info!(
-                "❌ Symbol '{}' does not match module '{}' - tried patterns '{}' and '{}' (file: {})",
-                symbol.name, module_name, hierarchical_pattern, simple_pattern, file_path
+                "Path of {} does not map to a valid module name ('{}'), not offering rename",
+                new_uri, expected_module
             );
+            return Ok(None);
         }
 
-        matches
-    }
+        info!(
+            "📋 Module '{}' no longer matches its path, expected '{}'",
+            declared_module, expected_module
+        );
 
-    /// Build hover content in Markdown format from symbol information
-    async fn build_hover_content(
-        &self,
-        symbol: &gren_lsp_core::Symbol,
-        symbol_info: &SymbolAtPosition,
-        workspace: &gren_lsp_core::Workspace,
-        supports_markdown: bool,
-    ) -> String {
-        let mut content = Vec::new();
+        let mut locations = vec![Location {
+            uri: new_uri.clone(),
+            range: declaration_range,
+        }];
 
-        // Add symbol name and kind as header
-        let kind_name = match symbol.kind {
-            SymbolKind::FUNCTION => "function",
-            SymbolKind::CLASS => "type",
-            SymbolKind::CONSTRUCTOR => "constructor",
-            SymbolKind::MODULE => "module",
-            SymbolKind::VARIABLE => "variable",
-            SymbolKind::CONSTANT => "constant",
-            _ => "symbol",
-        };
+        // Every indexed importer, read from disk when the editor doesn't have it open
+        let mut importing_files = 0;
+        for uri in workspace.module_importers(&declared_module) {
+            if &uri == new_uri {
+                continue;
+            }
 
-        if supports_markdown {
-            // Check if this is a sum type first
-            let is_sum_type = symbol
-                .type_signature
-                .as_ref()
-                .map(|sig| sig.contains('=') && sig.contains('|'))
-                .unwrap_or(false);
+            let ranges = match indexed_source(&workspace, &uri) {
+                Some(content) => self.find_module_name_references(&content, &declared_module),
+                None => continue,
+            };
+            if !ranges.is_empty() {
+                importing_files += 1;
+                locations.extend(ranges.into_iter().map(|range| Location {
+                    uri: uri.clone(),
+                    range,
+                }));
+            }
+        }
 
-            if is_sum_type {
-                // For sum types, skip the header and use the formatted version directly
-                if let Some(type_signature) = &symbol.type_signature {
-                    let formatted_signature = self.format_sum_type(type_signature);
-                    content.push(formatted_signature);
-                }
-            } else {
-                // For non-sum types, show the normal header
-                if symbol.kind == SymbolKind::CONSTRUCTOR {
-                    // Special case for constructors: show parent type
-                    if let Some(parent_type) = &symbol.container_name {
-                        content.push(format!(
-                            "*{}* **{}** of type **{}**",
-                            kind_name, symbol.name, parent_type
-                        ));
-                    } else {
-                        content.push(format!("*{}* **{}**", kind_name, symbol.name));
-                    }
-                } else {
-                    content.push(format!("*{}* **{}**", kind_name, symbol.name));
-                }
+        let mut edit = self.generate_workspace_edit_for_rename(locations, &expected_module);
+        add_manifest_rename(
+            &mut edit,
+            &workspace,
+            new_uri,
+            &declared_module,
+            &expected_module,
+        );
 
-                // Add formatted type signature in code block
-                if let Some(type_signature) = &symbol.type_signature {
-                    let formatted_signature = self.format_type_signature(type_signature);
-                    content.push(format!("```gren\n{}\n```", formatted_signature));
-                }
-            }
+        Ok(Some(ModuleRenameSync {
+            old_module_name: declared_module,
+            new_module_name: expected_module,
+            importing_files,
+            edit,
+        }))
+    }
 
-            // Add Types section with clickable links (only for functions)
-            if symbol.kind == SymbolKind::FUNCTION {
-                if let Some(type_signature) = &symbol.type_signature {
-                    let types_section = self.create_types_section(type_signature, workspace).await;
-                    if !types_section.is_empty() {
-                        content.push(types_section);
-                    }
-                }
-            }
+    /// Work out what renaming the module declared in `uri` would touch: the declaration
+    /// and the files that import it, according to the module graph. Importers are only
+    /// listed here; `module_references_in_file` finds the occurrences in each, so callers
+    /// can report progress and stop early on large workspaces.
+    pub async fn module_rename_plan(&self, uri: &Url) -> Result<Option<ModuleRenamePlan>> {
+        let workspace = self.workspace.read().await;
 
-            // Add module information for qualified symbols
-            if let Some(module_path) = &symbol_info.module_path {
-                content.push(format!("*from module `{}`*", module_path.join(".")));
-            } else if let Some(container) = &symbol.container_name {
-                content.push(format!("*from module `{}`*", container));
+        let content = match indexed_source(&workspace, uri) {
+            Some(content) => content,
+            None => {
+                info!("File is not in the workspace: {}", uri);
+                return Ok(None);
             }
+        };
 
-            // Add documentation if available
-            if let Some(documentation) = &symbol.documentation {
-                let doc = documentation.trim();
-                if !doc.is_empty() {
-                    content.push("---".to_string());
-                    content.push(doc.to_string());
-                }
+        let (module_name, declaration_range) = match self.extract_declared_module_name(&content) {
+            Some(declaration) => declaration,
+            None => {
+                info!("No module declaration found in {}", uri);
+                return Ok(None);
             }
+        };
 
-            content.join("\n\n")
-        } else {
-            // Plain text format - similar to JavaScript LSP
-            let mut parts = Vec::new();
+        let importers = workspace
+            .module_importers(&module_name)
+            .into_iter()
+            .filter(|importer| importer != uri)
+            .collect();
 
-            // Add kind and symbol name (swapped order)
-            if symbol.kind == SymbolKind::CONSTRUCTOR {
-                // Special case for constructors: show parent type
-                if let Some(parent_type) = &symbol.container_name {
-                    parts.push(format!(
-                        "{} {} of type {}",
-                        kind_name, symbol.name, parent_type
-                    ));
-                } else {
-                    parts.push(format!("{} {}", kind_name, symbol.name));
-                }
-            } else {
-                parts.push(format!("{} {}", kind_name, symbol.name));
-            }
+        Ok(Some(ModuleRenamePlan {
+            module_name,
+            declaration: Location::new(uri.clone(), declaration_range),
+            importers,
+        }))
+    }
 
-            // Add type signature (plain, no clickable links for plaintext)
-            if let Some(type_signature) = &symbol.type_signature {
-                parts.push(type_signature.clone());
-            }
+    /// Rename the module declared in `uri` to `new_name`, rewriting its declaration, every
+    /// import and qualified reference in the files importing it, and its entry in a
+    /// package's `exposed-modules`. The file itself isn't moved; clients that want the path
+    /// to follow do that separately.
+    pub async fn module_rename(&self, uri: &Url, new_name: &str) -> Result<Option<WorkspaceEdit>> {
+        if !is_valid_module_name(new_name) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "'{}' is not a valid Gren module name",
+                new_name
+            )));
+        }
 
-            // Add module information
-            if let Some(module_path) = &symbol_info.module_path {
-                parts.push(format!("from module {}", module_path.join(".")));
-            } else if let Some(container) = &symbol.container_name {
-                parts.push(format!("from module {}", container));
-            }
+        let plan = match self.module_rename_plan(uri).await? {
+            Some(plan) => plan,
+            None => return Ok(None),
+        };
 
-            parts.join("\n")
+        let mut locations = vec![plan.declaration];
+        for importer in plan.importers {
+            let ranges = self
+                .module_references_in_file(&importer, &plan.module_name)
+                .await;
+            locations.extend(ranges.into_iter().map(|range| Location {
+                uri: importer.clone(),
+                range,
+            }));
         }
-    }
 
-    /// Create a Types section with clickable links for types found in the signature
-    async fn create_types_section(
-        &self,
-        type_signature: &str,
-        workspace: &gren_lsp_core::Workspace,
-    ) -> String {
-        let mut type_links = Vec::new();
-        let mut seen_types = std::collections::HashSet::new();
+        let mut edit = self.generate_workspace_edit_for_rename(locations, new_name);
+        let workspace = self.workspace.read().await;
+        add_manifest_rename(&mut edit, &workspace, uri, &plan.module_name, new_name);
+        Ok(Some(edit))
+    }
 
-        // Extract type names using tree-sitter parsing
-        match self
-            .extract_type_names_from_signature_tree_sitter(type_signature)
+    /// Edit organizing the imports of `uri`, or `None` when they're already organized
+    pub async fn organize_imports(&self, uri: &Url) -> Option<WorkspaceEdit> {
+        let workspace = self.workspace.read().await;
+        self.generate_organize_imports_action(&workspace, uri)
             .await
-        {
-            Ok(type_names) => {
-                for type_name in type_names {
-                    // Skip constructors that shouldn't be clickable
-                    if matches!(
-                        type_name.as_str(),
-                        "True" | "False" | "Just" | "Nothing" | "Active" | "Inactive"
-                    ) {
-                        continue;
-                    }
+            .and_then(|action| action.edit)
+    }
 
-                    // Skip if we already processed this type
-                    if seen_types.contains(&type_name) {
-                        continue;
-                    }
-                    seen_types.insert(type_name.clone());
+    /// Ranges in `uri` that mention `module_name` and would change if it were renamed
+    pub async fn module_references_in_file(&self, uri: &Url, module_name: &str) -> Vec<Range> {
+        let content = {
+            let workspace = self.workspace.read().await;
+            indexed_source(&workspace, uri)
+        };
+
+        match content {
+            Some(content) => self.find_module_name_references(&content, module_name),
+            None => Vec::new(),
+        }
+    }
+
+    // Helper methods for rename functionality
+
+    /// Validate that a string is a valid Gren identifier
+    fn is_valid_gren_identifier(&self, name: &str) -> bool {
+        if name.is_empty() {
+            return false;
+        }
+
+        // Check if it's a reserved keyword
+        if self.is_keyword(name) {
+            return false;
+        }
+
+        let chars: Vec<char> = name.chars().collect();
+
+        // First character must be letter or underscore, but not uppercase (unless it's a type)
+        let first_char = chars[0];
+        if !first_char.is_alphabetic() && first_char != '_' {
+            return false;
+        }
+
+        // Rest of characters must be alphanumeric or underscore
+        for &c in &chars[1..] {
+            if !c.is_alphanumeric() && c != '_' {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Find all references to a symbol in the workspace
+    ///
+    /// Top-level names come from the index of resolved references, so locals and other
+    /// modules' symbols of the same name are left out. Record fields, and names whose
+    /// defining module isn't known, fall back to searching the text. When a type and a
+    /// constructor share the name, the occurrence at `at` decides which one is meant.
+    async fn find_all_symbol_references(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        symbol_info: &SymbolAtPosition,
+        current_uri: &Url,
+        at: Option<Position>,
+    ) -> anyhow::Result<Vec<Location>> {
+        let name = &symbol_info.function_name;
+        let definitions = match &symbol_info.module_path {
+            Some(module_path) => {
+                self.find_qualified_symbol(workspace, name, module_path)
+                    .await?
+            }
+            None => {
+                self.find_unqualified_symbol(workspace, current_uri, name)
+                    .await?
+            }
+        };
+        let mut modules: Vec<String> = definitions
+            .iter()
+            .filter(|symbol| !matches!(symbol.kind, SymbolKind::FIELD | SymbolKind::MODULE))
+            .filter_map(|symbol| workspace.module_name(&symbol.location.uri))
+            .collect();
+        modules.sort();
+        modules.dedup();
+
+        let mut all_references = Vec::new();
+        if modules.is_empty() {
+            all_references.extend(definitions.into_iter().map(|symbol| symbol.location));
+            all_references.extend(self.find_textual_references(workspace, name).await?);
+        } else {
+            let mut references = Vec::new();
+            for module in &modules {
+                references.extend(workspace.references_to(module, name)?);
+            }
+
+            let shares_name = definitions
+                .iter()
+                .any(|symbol| symbol.kind == SymbolKind::CLASS)
+                && definitions
+                    .iter()
+                    .any(|symbol| symbol.kind == SymbolKind::CONSTRUCTOR);
+            let names_type = at
+                .filter(|_| shares_name)
+                .and_then(|at| {
+                    references.iter().find(|reference| {
+                        let range = reference.location.range;
+                        reference.location.uri == *current_uri
+                            && (range.start.line, range.start.character) <= (at.line, at.character)
+                            && (at.line, at.character) <= (range.end.line, range.end.character)
+                    })
+                })
+                .map(|reference| reference.names_type());
+            all_references.extend(
+                references
+                    .into_iter()
+                    .filter(|reference| {
+                        names_type.map_or(true, |names_type| reference.names_type() == names_type)
+                    })
+                    .map(|reference| reference.location),
+            );
+        }
+
+        // Remove duplicates by location
+        all_references.sort_by(|a, b| {
+            let uri_cmp = a.uri.cmp(&b.uri);
+            if uri_cmp != std::cmp::Ordering::Equal {
+                return uri_cmp;
+            }
+            let line_cmp = a.range.start.line.cmp(&b.range.start.line);
+            if line_cmp != std::cmp::Ordering::Equal {
+                return line_cmp;
+            }
+            a.range.start.character.cmp(&b.range.start.character)
+        });
+        all_references.dedup_by(|a, b| a.uri == b.uri && a.range == b.range);
+
+        Ok(all_references)
+    }
+
+    /// Definitions a symbol at a position may refer to
+    async fn find_symbol_definitions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        symbol_info: &SymbolAtPosition,
+        current_uri: &Url,
+    ) -> anyhow::Result<Vec<GrenSymbol>> {
+        // Search for all occurrences of the symbol name
+        let definitions = if let Some(module_path) = &symbol_info.module_path {
+            // For qualified symbols, search both qualified and unqualified forms
+            let qualified_symbols = self
+                .find_qualified_symbol(workspace, &symbol_info.function_name, module_path)
+                .await?;
+            let unqualified_symbols = workspace.find_exact_symbols(&symbol_info.function_name)?;
+
+            // Combine results and deduplicate
+            let mut all_symbols = qualified_symbols;
+            for symbol in unqualified_symbols {
+                if !all_symbols
+                    .iter()
+                    .any(|existing| existing.location == symbol.location)
+                {
+                    all_symbols.push(symbol);
+                }
+            }
+            all_symbols
+        } else {
+            // For unqualified symbols, use import-aware search
+            self.find_unqualified_symbol(workspace, current_uri, &symbol_info.function_name)
+                .await?
+        };
+        Ok(definitions)
+    }
+
+    /// The error refusing to rename a symbol whose definitions are all outside the
+    /// workspace, in an installed package or a kernel module
+    async fn rename_refusal(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        symbol_info: &SymbolAtPosition,
+        current_uri: &Url,
+    ) -> Option<tower_lsp::jsonrpc::Error> {
+        let name = &symbol_info.function_name;
+        if let Some(origin) = symbol_info
+            .module_path
+            .as_ref()
+            .and_then(|module_path| DefinitionOrigin::of_module(&module_path.join(".")))
+        {
+            return origin.rename_refusal(name);
+        }
+
+        let definitions = match self
+            .find_symbol_definitions(workspace, symbol_info, current_uri)
+            .await
+        {
+            Ok(definitions) => definitions,
+            Err(e) => {
+                warn!("Failed to resolve definitions of '{}': {}", name, e);
+                return None;
+            }
+        };
+        let origins: Vec<DefinitionOrigin> = definitions
+            .iter()
+            .map(|symbol| {
+                DefinitionOrigin::of_definition(
+                    &symbol.location.uri,
+                    symbol.container_name.as_deref(),
+                )
+            })
+            .collect();
+        if origins.contains(&DefinitionOrigin::Workspace) {
+            return None;
+        }
+        origins.first()?.rename_refusal(name)
+    }
+
+    /// Find textual references to a symbol name across all open documents
+    async fn find_textual_references(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        symbol_name: &str,
+    ) -> anyhow::Result<Vec<Location>> {
+        let mut references = Vec::new();
+
+        // Get all documents in the workspace
+        // Note: This is a simplified approach - in a real implementation we'd want to
+        // search through all files in the project, not just open documents
+        for uri in workspace.get_open_document_uris() {
+            if let Some(document) = workspace.get_document_readonly(&uri) {
+                let text = document.text();
+                let lines: Vec<&str> = text.lines().collect();
+
+                for (line_idx, line) in lines.iter().enumerate() {
+                    let mut char_idx = 0;
+                    while let Some(match_start) = line[char_idx..].find(symbol_name) {
+                        let absolute_start = char_idx + match_start;
+                        let absolute_end = absolute_start + symbol_name.len();
+
+                        // Check if this is a complete word match (not part of another identifier or module qualifier)
+                        let is_complete_word = {
+                            let before_ok = absolute_start == 0
+                                || !self.is_identifier_char(
+                                    line.chars().nth(absolute_start - 1).unwrap_or(' '),
+                                );
+                            let after_char = line.chars().nth(absolute_end).unwrap_or(' ');
+                            let after_ok = absolute_end >= line.len()
+                                || (!self.is_identifier_char(after_char) && after_char != '.');
+                            before_ok && after_ok
+                        };
+
+                        // Check if this match is inside a comment
+                        let is_in_comment = self.is_position_in_comment(line, absolute_start);
+
+                        // Check if this match is inside an import statement
+                        let is_in_import = self.is_position_in_import(line, absolute_start);
+
+                        // Check if this match is inside a module declaration
+                        let is_in_module_declaration = self
+                            .is_position_in_module_declaration(
+                                &uri,
+                                line_idx as u32,
+                                absolute_start as u32,
+                            )
+                            .await;
+
+                        if is_complete_word
+                            && !is_in_comment
+                            && !is_in_import
+                            && !is_in_module_declaration
+                        {
+                            references.push(Location {
+                                uri: uri.clone(),
+                                range: Range {
+                                    start: Position {
+                                        line: line_idx as u32,
+                                        character: absolute_start as u32,
+                                    },
+                                    end: Position {
+                                        line: line_idx as u32,
+                                        character: absolute_end as u32,
+                                    },
+                                },
+                            });
+                        }
+
+                        char_idx = absolute_start + 1; // Move past this match to find more
+                    }
+                }
+            }
+        }
+
+        Ok(references)
+    }
+
+    /// Hover footer with the use count of `symbol`, linked to its references when the client
+    /// can run `SHOW_REFERENCES_COMMAND` from markdown
+    async fn reference_count_footer(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        symbol: &gren_lsp_core::Symbol,
+        symbol_info: &SymbolAtPosition,
+        uri: &Url,
+        position: Position,
+        client_capabilities: Option<&ClientCapabilities>,
+    ) -> Option<String> {
+        let count = self
+            .symbol_reference_count(workspace, symbol, symbol_info, uri)
+            .await?;
+        let link = hover_supports_markdown(client_capabilities)
+            && client_capabilities
+                .is_some_and(|caps| self.client_supports_command(caps, SHOW_REFERENCES_COMMAND));
+        Some(if link {
+            format!(
+                "[{}]({})",
+                reference_count_text(count),
+                show_references_link(uri, position)
+            )
+        } else {
+            reference_count_text(count)
+        })
+    }
+
+    /// How many times `symbol` is used, counting the file defining it and the files
+    /// importing its module. Cached in the workspace until one of those files changes.
+    async fn symbol_reference_count(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        symbol: &gren_lsp_core::Symbol,
+        symbol_info: &SymbolAtPosition,
+        current_uri: &Url,
+    ) -> Option<usize> {
+        let definition = &symbol.location.uri;
+        if let Some(count) = workspace.cached_reference_count(definition, &symbol.name) {
+            return Some(count);
+        }
+
+        let references = self
+            .find_all_symbol_references(workspace, symbol_info, current_uri, None)
+            .await
+            .ok()?;
+        let usages = self.filter_out_declarations(references, symbol_info).await;
+
+        // Only these files are re-checked when the count is invalidated, so uses of a
+        // same-named symbol elsewhere mustn't be counted
+        let importers = workspace
+            .module_name(definition)
+            .map(|module_name| workspace.module_importers(&module_name))
+            .unwrap_or_default();
+        let count = usages
+            .iter()
+            .filter(|location| location.uri == *definition || importers.contains(&location.uri))
+            .count();
+
+        workspace.cache_reference_count(definition, &symbol.name, count);
+        Some(count)
+    }
+
+    /// Filter out declarations from references based on symbol information
+    async fn filter_out_declarations(
+        &self,
+        references: Vec<Location>,
+        symbol_info: &SymbolAtPosition,
+    ) -> Vec<Location> {
+        let mut filtered_references = Vec::new();
+
+        for location in references {
+            // Check if this location is a declaration by examining the AST context
+            if !self.is_symbol_declaration(&location, symbol_info).await {
+                filtered_references.push(location);
+            }
+        }
+
+        filtered_references
+    }
+
+    /// Check if a location represents a symbol declaration rather than a usage
+    /// For now, this uses a simple heuristic approach
+    async fn is_symbol_declaration(
+        &self,
+        location: &Location,
+        symbol_info: &SymbolAtPosition,
+    ) -> bool {
+        let workspace = self.workspace.read().await;
+
+        // Get the document for this location
+        let document = match workspace.get_document_readonly(&location.uri) {
+            Some(doc) => doc,
+            None => return false,
+        };
+
+        let source = document.text();
+        let lines: Vec<&str> = source.lines().collect();
+
+        // Get the line containing this location
+        let line_index = location.range.start.line as usize;
+        if line_index >= lines.len() {
+            return false;
+        }
+
+        let line = lines[line_index];
+
+        // A function's name in its definition or its type annotation
+        let is_function_name = gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(source).ok().flatten())
+            .and_then(|tree| {
+                function_name_ranges(&tree, source)
+                    .remove(&symbol_info.function_name)
+                    .map(|names| names.contains(&location.range))
+            })
+            .unwrap_or(false);
+        if is_function_name {
+            return true;
+        }
+
+        // Type declarations typically start with "type" keyword
+        if line.trim_start().starts_with("type ") && line.contains(&symbol_info.function_name) {
+            return true;
+        }
+
+        false
+    }
+
+    /// Generate a workspace edit for renaming all occurrences
+    fn generate_workspace_edit_for_rename(
+        &self,
+        references: Vec<Location>,
+        new_name: &str,
+    ) -> WorkspaceEdit {
+        use std::collections::HashMap;
+
+        let mut changes_by_file: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+        // Group references by file
+        for location in references {
+            let edit = TextEdit {
+                range: location.range,
+                new_text: new_name.to_string(),
+            };
+
+            changes_by_file.entry(location.uri).or_default().push(edit);
+        }
+
+        // Sort edits within each file by position (reverse order for safe application)
+        for edits in changes_by_file.values_mut() {
+            edits.sort_by(|a, b| {
+                let line_cmp = b.range.start.line.cmp(&a.range.start.line);
+                if line_cmp != std::cmp::Ordering::Equal {
+                    return line_cmp;
+                }
+                b.range.start.character.cmp(&a.range.start.character)
+            });
+        }
+
+        // Convert to DocumentChanges format for better atomicity
+        let document_changes: Vec<TextDocumentEdit> = changes_by_file
+            .into_iter()
+            .map(|(uri, edits)| TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri,
+                    version: None, // Let the client handle versioning
+                },
+                edits: edits.into_iter().map(OneOf::Left).collect(),
+            })
+            .collect();
+
+        WorkspaceEdit {
+            changes: None, // Use document_changes instead for better atomicity
+            document_changes: Some(DocumentChanges::Edits(document_changes)),
+            change_annotations: None,
+        }
+    }
+
+    // Helper methods for symbol conversion
+
+    /// Convert internal symbols to LSP DocumentSymbol format with hierarchy. `full_ranges`
+    /// holds each declaration's full extent, annotation included, which becomes the
+    /// symbol's range while the name stays its selection range.
+    fn convert_to_document_symbols(
+        &self,
+        symbols: Vec<GrenSymbol>,
+        full_ranges: &std::collections::HashMap<String, Range>,
+    ) -> Vec<DocumentSymbol> {
+        let mut document_symbols = Vec::new();
+        let mut processed_modules = std::collections::HashSet::new();
+        let mut processed_types = std::collections::HashSet::new();
+
+        // Group symbols by type for hierarchical organization
+        let mut modules = Vec::new();
+        let mut types = Vec::new();
+        let mut functions = Vec::new();
+        let mut constructors = Vec::new();
+
+        for symbol in &symbols {
+            match symbol.kind {
+                SymbolKind::MODULE => modules.push(symbol.clone()),
+                SymbolKind::CLASS => types.push(symbol.clone()),
+                SymbolKind::FUNCTION => functions.push(symbol.clone()),
+                SymbolKind::CONSTRUCTOR => constructors.push(symbol.clone()),
+                _ => {}
+            }
+        }
+
+        // Add modules first (top-level)
+        for module in modules {
+            // Skip modules that are just file names or duplicates
+            if module.name.contains(" exposing ") || processed_modules.contains(&module.name) {
+                continue;
+            }
+
+            let doc_symbol = DocumentSymbol {
+                name: module.name.clone(),
+                detail: None,
+                kind: module.kind,
+                range: module.location.range,
+                selection_range: module.location.range,
+                children: None,
+                tags: None,
+                #[allow(deprecated)]
+                deprecated: Some(false),
+            };
+            document_symbols.push(doc_symbol);
+            processed_modules.insert(module.name.clone());
+        }
+
+        // Sort types to process simple names first, then verbose ones
+        types.sort_by(|a, b| {
+            let a_is_verbose = a.name.starts_with("type ");
+            let b_is_verbose = b.name.starts_with("type ");
+            a_is_verbose.cmp(&b_is_verbose)
+        });
+
+        // Process types with smart deduplication
+        for typ in types {
+            // Extract clean type name
+            let type_name = if typ.name.starts_with("type ") {
+                // Extract type name from "type Foo = ..." or "type alias Foo = ..."
+                if let Some(name_part) = typ.name.split_whitespace().nth(1) {
+                    name_part
+                        .split('=')
+                        .next()
+                        .unwrap_or(name_part)
+                        .trim()
+                        .to_string()
+                } else {
+                    continue;
+                }
+            } else {
+                typ.name.clone()
+            };
+
+            // Skip if we already processed this type name
+            if processed_types.contains(&type_name) {
+                continue;
+            }
+
+            // Find constructors that belong to this type using container_name
+            let type_constructors: Vec<DocumentSymbol> = constructors
+                .iter()
+                .filter(|c| {
+                    // Use the container_name field to properly associate constructors with their parent type
+                    c.container_name
+                        .as_ref()
+                        .map(|container| container == &type_name)
+                        .unwrap_or(false)
+                })
+                .map(|c| DocumentSymbol {
+                    name: c.name.clone(),
+                    detail: c.type_signature.clone(),
+                    kind: c.kind,
+                    range: c.location.range,
+                    selection_range: c.location.range,
+                    children: None,
+                    tags: None,
+                    #[allow(deprecated)]
+                    deprecated: Some(false),
+                })
+                .collect();
+
+            let doc_symbol = DocumentSymbol {
+                name: type_name.clone(),
+                detail: typ.type_signature.clone(),
+                kind: typ.kind,
+                range: enclosing_range(full_ranges.get(&type_name), typ.location.range),
+                selection_range: typ.location.range,
+                children: if type_constructors.is_empty() {
+                    None
+                } else {
+                    Some(type_constructors)
+                },
+                tags: None,
+                #[allow(deprecated)]
+                deprecated: Some(false),
+            };
+
+            document_symbols.push(doc_symbol);
+            processed_types.insert(type_name);
+        }
+
+        // Add functions, one per name: the annotation and definition are a single symbol
+        let mut processed_functions = std::collections::HashSet::new();
+        for function in functions {
+            if !processed_functions.insert(function.name.clone()) {
+                continue;
+            }
+
+            let doc_symbol = DocumentSymbol {
+                name: function.name.clone(),
+                detail: function.type_signature.clone(),
+                kind: function.kind,
+                range: enclosing_range(full_ranges.get(&function.name), function.location.range),
+                selection_range: function.location.range,
+                children: None,
+                tags: None,
+                #[allow(deprecated)]
+                deprecated: Some(false),
+            };
+            document_symbols.push(doc_symbol);
+        }
+
+        // Sort by line number for consistent ordering
+        document_symbols.sort_by(|a, b| a.range.start.line.cmp(&b.range.start.line));
+
+        debug!(
+            "Converted {} internal symbols to {} document symbols",
+            symbols.len(),
+            document_symbols.len()
+        );
+
+        document_symbols
+    }
+
+    /// Convert internal symbol to LSP SymbolInformation format
+    fn convert_to_symbol_information(&self, symbol: GrenSymbol) -> SymbolInformation {
+        let container_name = symbol.container_name.clone();
+
+        SymbolInformation {
+            name: symbol.name,
+            kind: symbol.kind,
+            location: symbol.location,
+            container_name,
+            tags: None,
+            #[allow(deprecated)]
+            deprecated: Some(false),
+        }
+    }
+
+    /// Convert SymbolKind to CompletionItemKind
+    fn symbol_kind_to_completion_kind(&self, symbol_kind: SymbolKind) -> CompletionItemKind {
+        match symbol_kind {
+            SymbolKind::FUNCTION => CompletionItemKind::FUNCTION,
+            SymbolKind::VARIABLE => CompletionItemKind::VARIABLE,
+            SymbolKind::CLASS => CompletionItemKind::CLASS,
+            SymbolKind::INTERFACE => CompletionItemKind::INTERFACE,
+            SymbolKind::MODULE => CompletionItemKind::MODULE,
+            SymbolKind::PROPERTY => CompletionItemKind::PROPERTY,
+            SymbolKind::ENUM => CompletionItemKind::ENUM,
+            SymbolKind::CONSTRUCTOR => CompletionItemKind::CONSTRUCTOR,
+            SymbolKind::CONSTANT => CompletionItemKind::CONSTANT,
+            _ => CompletionItemKind::TEXT,
+        }
+    }
+
+    /// Apply basic context filtering to determine if a symbol should be included in completion
+    fn should_include_symbol_in_completion(
+        &self,
+        symbol: &GrenSymbol,
+        _params: &CompletionParams,
+    ) -> bool {
+        // For now, implement basic filtering - can be enhanced later with more sophisticated context analysis
+
+        // Skip very long or complex symbol names that are likely noise
+        if symbol.name.len() > 100 {
+            return false;
+        }
+
+        // Skip symbols that look like internal compiler artifacts
+        if symbol.name.starts_with("_") || symbol.name.contains("$") {
+            return false;
+        }
+
+        // Skip module declarations with exposing clauses (verbose forms)
+        if symbol.kind == SymbolKind::MODULE && symbol.name.contains(" exposing ") {
+            return false;
+        }
+
+        // Skip verbose type definitions in favor of clean type names
+        if symbol.kind == SymbolKind::CLASS
+            && symbol.name.starts_with("type ")
+            && symbol.name.contains(" = ")
+        {
+            return false;
+        }
+
+        true
+    }
+
+    /// Find the symbol name at the given cursor position
+    async fn find_symbol_at_position(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        position: Position,
+    ) -> Option<SymbolAtPosition> {
+        // Get the document text
+        let document = workspace.get_document_readonly(uri)?;
+        let lines: Vec<&str> = document.text().lines().collect();
+
+        // Check if position is valid
+        if position.line as usize >= lines.len() {
+            return None;
+        }
+
+        let line = lines[position.line as usize];
+        let char_pos = position.character as usize;
+
+        if char_pos >= line.len() {
+            return None;
+        }
+
+        // Find word boundaries around the cursor position
+        let chars: Vec<char> = line.chars().collect();
+
+        // Check if we're on a valid identifier character
+        if char_pos >= chars.len() || !self.is_identifier_char(chars[char_pos]) {
+            return None;
+        }
+
+        // Find the start of the identifier
+        let mut start = char_pos;
+        while start > 0 && self.is_identifier_char(chars[start - 1]) {
+            start -= 1;
+        }
+
+        // Find the end of the identifier
+        let mut end = char_pos;
+        while end < chars.len() && self.is_identifier_char(chars[end]) {
+            end += 1;
+        }
+
+        // Extract the identifier
+        let identifier: String = chars[start..end].iter().collect();
+
+        // Filter out keywords and single characters
+        if identifier.len() < 2 || self.is_keyword(&identifier) {
+            return None;
+        }
+
+        // Check if this is a qualified name like "Utils.isEmpty" or "Gren.Kernel.Bytes.flatten"
+        // Look backwards to find the full qualified path
+        let mut module_path = Vec::new();
+        let mut search_start = start;
+
+        // Keep looking backwards for module qualifiers
+        while search_start > 0 && chars[search_start - 1] == '.' {
+            // Find the module name before this dot
+            let mut module_start = search_start - 1;
+            while module_start > 0 && self.is_identifier_char(chars[module_start - 1]) {
+                module_start -= 1;
+            }
+
+            if module_start < search_start - 1 {
+                let module_name: String = chars[module_start..search_start - 1].iter().collect();
+                module_path.insert(0, module_name); // Insert at beginning to maintain order
+                search_start = module_start;
+            } else {
+                break;
+            }
+        }
+
+        let symbol_info = if module_path.is_empty() {
+            SymbolAtPosition {
+                function_name: identifier,
+                module_path: None,
+            }
+        } else {
+            info!(
+                "Found qualified reference: {}.{}",
+                module_path.join("."),
+                identifier
+            );
+            SymbolAtPosition {
+                function_name: identifier,
+                module_path: Some(module_path),
+            }
+        };
+
+        Some(symbol_info)
+    }
+
+    /// Check if a character is part of an identifier
+    fn is_identifier_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    /// Check if a string is a Gren keyword
+    fn is_keyword(&self, word: &str) -> bool {
+        matches!(
+            word,
+            "if" | "then"
+                | "else"
+                | "case"
+                | "of"
+                | "let"
+                | "in"
+                | "type"
+                | "alias"
+                | "module"
+                | "import"
+                | "exposing"
+                | "as"
+                | "where"
+        )
+    }
+
+    /// Find a symbol in a specific module context
+    async fn find_qualified_symbol(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        function_name: &str,
+        module_path: &[String],
+    ) -> anyhow::Result<Vec<gren_lsp_core::Symbol>> {
+        info!(
+            "Searching for '{}' in module '{}'",
+            function_name,
+            module_path.join(".")
+        );
+
+        // Check if this is a Gren.Kernel.* module - these are built-in and don't have user files
+        if module_path.len() >= 2 && module_path[0] == "Gren" && module_path[1] == "Kernel" {
+            info!("Detected Gren.Kernel.* module '{}' - these are built-in functions with no user-defined files", 
+                  module_path.join("."));
+            return Ok(Vec::new());
+        }
+
+        // First, try to find symbols with the function name
+        let all_symbols = workspace.find_exact_symbols(function_name)?;
+
+        // Filter symbols that match the specified module path and that the module exposes
+        let qualified_symbols: Vec<_> = all_symbols
+            .into_iter()
+            .filter(|symbol| {
+                self.symbol_matches_module_path(symbol, module_path) && workspace.is_exposed(symbol)
+            })
+            .collect();
+
+        // For qualified calls, we only return results if we have high confidence
+        // Better to show nothing than the wrong thing
+        if !qualified_symbols.is_empty() {
+            info!(
+                "Found {} qualified matches for '{}' in module '{}'",
+                qualified_symbols.len(),
+                function_name,
+                module_path.join(".")
+            );
+            Ok(qualified_symbols)
+        } else {
+            info!("No qualified matches found for '{}' in module '{}' - returning empty to avoid incorrect results", 
+                  function_name, module_path.join("."));
+            Ok(Vec::new())
+        }
+    }
+
+    /// Check if a symbol likely belongs to the specified module path
+    /// This is highly conservative - we only return true when we have very strong confidence
+    fn symbol_matches_module_path(
+        &self,
+        symbol: &gren_lsp_core::Symbol,
+        module_path: &[String],
+    ) -> bool {
+        let file_path = symbol.location.uri.path();
+
+        info!(
+            "🔍 Checking if symbol '{}' in file '{}' matches module path '{}'",
+            symbol.name,
+            file_path,
+            module_path.join(".")
+        );
+
+        // For qualified calls, we demand very high confidence in the match
+        // It's better to show no results than incorrect ones
+
+        if module_path.is_empty() {
+            info!("❌ Module path is empty");
+            return false;
+        }
+
+        let empty_string = String::new();
+        let module_name = module_path.last().unwrap_or(&empty_string);
+
+        if module_name.is_empty() {
+            info!("❌ Module name is empty");
+            return false;
+        }
+
+        // Pattern 1: Exact module file match - /path/to/ModuleName.gren
+        let pattern1 = format!("/{}.gren", module_name);
+        if file_path.ends_with(&pattern1) {
+            info!("✅ Symbol matches exact module file pattern: {}", pattern1);
+            return true;
+        } else {
+            info!(
+                "❌ Pattern 1 failed: '{}' does not end with '{}'",
+                file_path, pattern1
+            );
+        }
+
+        // Pattern 2: Full hierarchical path - /path/to/Gren/Kernel/Bytes.gren
+        let full_path = module_path.join("/");
+        let pattern2 = format!("/{}.gren", full_path);
+        if file_path.ends_with(&pattern2) {
+            info!("✅ Symbol matches full hierarchical path: {}", pattern2);
+            return true;
+        } else {
+            info!(
+                "❌ Pattern 2 failed: '{}' does not end with '{}'",
+                file_path, pattern2
+            );
+        }
+
+        // Pattern 3: Check if the file path contains the complete module hierarchy
+        // For Gren.Kernel.Bytes, we want files containing /Gren/Kernel/Bytes.gren
+        if module_path.len() > 1 {
+            let hierarchical_pattern = format!("/{}.gren", module_path.join("/"));
+            if file_path.contains(&hierarchical_pattern) {
+                info!(
+                    "✅ Symbol matches hierarchical module pattern: {}",
+                    hierarchical_pattern
+                );
+                return true;
+            } else {
+                info!(
+                    "❌ Pattern 3 failed: '{}' does not contain '{}'",
+                    file_path, hierarchical_pattern
+                );
+            }
+        }
+
+        // No match found - be conservative and reject
+        info!(
+            "❌ Symbol in '{}' does not match required module path '{}'",
+            file_path,
+            module_path.join(".")
+        );
+        false
+    }
+
+    /// Find an unqualified symbol by checking local symbols first, then imports only
+    /// No workspace fallback - if it's not local or imported, it's a compile error
+    async fn find_unqualified_symbol(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        file_uri: &Url,
+        symbol_name: &str,
+    ) -> anyhow::Result<Vec<gren_lsp_core::Symbol>> {
+        info!(
+            "Finding unqualified symbol '{}' in file '{}'",
+            symbol_name, file_uri
+        );
+
+        // First, check for local symbols in the same file
+        let local_symbols = workspace.get_file_symbols(file_uri)?;
+        let local_matches: Vec<_> = local_symbols
+            .into_iter()
+            .filter(|symbol| {
+                symbol.name == symbol_name &&
+                // Prefer function definitions over other types for go-to-definition
+                (symbol.kind == SymbolKind::FUNCTION ||
+                 symbol.kind == SymbolKind::CLASS ||
+                 symbol.kind == SymbolKind::CONSTRUCTOR)
+            })
+            .collect();
+
+        if !local_matches.is_empty() {
+            info!(
+                "Found {} local symbols for '{}' in same file - using local definition",
+                local_matches.len(),
+                symbol_name
+            );
+            return Ok(local_matches);
+        }
+
+        // Next, parse imports from the current file
+        let import_map = self.parse_imports(workspace, file_uri).await;
+
+        // Check if this symbol is explicitly imported from a specific module
+        if let Some(module_name) = import_map.get(symbol_name) {
+            info!(
+                "Symbol '{}' is imported from module '{}' - searching there only",
+                symbol_name, module_name
+            );
+
+            // Search for the symbol in the specific imported module
+            let all_symbols = workspace.find_exact_symbols(symbol_name)?;
+            let module_symbols: Vec<_> = all_symbols
+                .into_iter()
+                .filter(|symbol| {
+                    // Check if the symbol is from the imported module, which has to expose
+                    // it for the import to bring it in
+                    self.symbol_is_from_module(symbol, module_name) && workspace.is_exposed(symbol)
+                })
+                .collect();
+
+            if !module_symbols.is_empty() {
+                info!(
+                    "Found {} symbols from imported module '{}'",
+                    module_symbols.len(),
+                    module_name
+                );
+                return Ok(module_symbols);
+            } else {
+                info!(
+                    "No symbols found in imported module '{}' - this might be a built-in",
+                    module_name
+                );
+                return Ok(Vec::new());
+            }
+        }
+
+        // If not found locally or in imports, return empty (don't do workspace search)
+        // This would be a compile error in valid Gren code
+        info!(
+            "Symbol '{}' not found locally or in imports - would be compile error",
+            symbol_name
+        );
+        Ok(Vec::new())
+    }
+
+    /// Parse import statements from a file to build a symbol -> module mapping
+    async fn parse_imports(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        file_uri: &Url,
+    ) -> std::collections::HashMap<String, String> {
+        let mut import_map = std::collections::HashMap::new();
+
+        // Get the document content
+        if let Some(document) = workspace.get_document_readonly(file_uri) {
+            let content = document.text();
+
+            // Parse import statements line by line
+            for line in content.lines() {
+                let line = line.trim();
+
+                // Look for import statements with exposing clause
+                if line.starts_with("import ") && line.contains(" exposing ") {
+                    if let Some((module_part, exposing_part)) = line.split_once(" exposing ") {
+                        // Extract the actual module name, handling aliases
+                        // e.g., "import Dedris.Motion as Motion" -> "Dedris.Motion"
+                        let module_declaration = module_part.trim_start_matches("import ").trim();
+                        let module_name = if module_declaration.contains(" as ") {
+                            // Split on " as " and take the first part (actual module name)
+                            module_declaration.split(" as ").next().unwrap().trim()
+                        } else {
+                            module_declaration
+                        };
+
+                        // Parse the exposing clause
+                        let exposing_content = exposing_part.trim();
+
+                        // Handle different exposing formats
+                        if exposing_content == "(..)" {
+                            // exposing (..) - exposes everything, we can't track specific symbols
+                            info!("Found exposing (..) for module '{}' - cannot track specific imports", module_name);
+                        } else if exposing_content.starts_with('(')
+                            && exposing_content.ends_with(')')
+                        {
+                            // exposing (symbol1, symbol2, ...)
+                            let symbols_str = &exposing_content[1..exposing_content.len() - 1];
+                            for symbol in symbols_str.split(',') {
+                                let symbol = symbol.trim();
+                                if !symbol.is_empty() {
+                                    info!(
+                                        "Found import: '{}' from module '{}'",
+                                        symbol, module_name
+                                    );
+                                    import_map.insert(symbol.to_string(), module_name.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        import_map
+    }
+
+    /// Check if a symbol is from a specific module based on file path
+    fn symbol_is_from_module(&self, symbol: &gren_lsp_core::Symbol, module_name: &str) -> bool {
+        let file_path = symbol.location.uri.path();
+
+        // Handle hierarchical module names (e.g., "Dedris.Motion" -> "/Dedris/Motion.gren")
+        let hierarchical_pattern = format!("/{}.gren", module_name.replace(".", "/"));
+        let simple_pattern = format!("/{}.gren", module_name);
+
+        // Try hierarchical pattern first, then fall back to simple pattern
+        let matches =
+            file_path.ends_with(&hierarchical_pattern) || file_path.ends_with(&simple_pattern);
+
+        if matches {
+            let matched_pattern = if file_path.ends_with(&hierarchical_pattern) {
+                &hierarchical_pattern
+            } else {
+                &simple_pattern
+            };
+            info!(
+                "✅ Symbol '{}' matches module '{}' using pattern '{}' (file: {})",
+                symbol.name, module_name, matched_pattern, file_path
+            );
+        } else {
+            info!(
+                "❌ Symbol '{}' does not match module '{}' - tried patterns '{}' and '{}' (file: {})",
+                symbol.name, module_name, hierarchical_pattern, simple_pattern, file_path
+            );
+        }
+
+        matches
+    }
+
+    /// Occurrences of the local at `position`, when the name there is bound inside a
+    /// function rather than at the top level
+    fn local_rename_ranges(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Vec<Range>> {
+        let content = workspace.get_document_readonly(uri)?.text().to_string();
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(&content).ok()??;
+        local_occurrences(&tree, &content, position)
+    }
+
+    fn field_at_position(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        position: Position,
+    ) -> Option<String> {
+        let content = workspace.get_document_readonly(uri)?.text().to_string();
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(&content).ok()??;
+        field_at(&tree, &content, position)
+    }
+
+    /// Rename the field `field` in every workspace module and open document
+    fn field_rename_edit(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        field: &str,
+        new_name: &str,
+    ) -> WorkspaceEdit {
+        let mut uris: Vec<Url> = workspace
+            .module_names()
+            .iter()
+            .filter_map(|module_name| workspace.module_uri(module_name))
+            .chain(workspace.get_open_document_uris())
+            .filter(|uri| !workspace.is_package_file(uri))
+            .collect();
+        uris.sort();
+        uris.dedup();
+
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for field rename: {}", e);
+                return WorkspaceEdit::default();
+            }
+        };
+        let document_changes: Vec<TextDocumentEdit> = uris
+            .into_iter()
+            .filter_map(|uri| {
+                let source = indexed_source(workspace, &uri)?;
+                let tree = parser.parse(&source).ok()??;
+                let mut edits = field_edits(&tree, &source, field, new_name);
+                if edits.is_empty() {
+                    return None;
+                }
+                // Last first, so applying one doesn't move the next
+                edits.sort_by(|a, b| {
+                    (b.range.start.line, b.range.start.character)
+                        .cmp(&(a.range.start.line, a.range.start.character))
+                });
+                Some(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                    edits: edits.into_iter().map(OneOf::Left).collect(),
+                })
+            })
+            .collect();
+
+        WorkspaceEdit {
+            changes: None,
+            document_changes: Some(DocumentChanges::Edits(document_changes)),
+            change_annotations: None,
+        }
+    }
+
+    /// Hover for a parameter, let binding or pattern variable: its type when annotated,
+    /// where it's bound and the function it belongs to
+    fn local_hover(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        position: Position,
+        supports_markdown: bool,
+    ) -> Option<Hover> {
+        let content = workspace.get_document_readonly(uri)?.text().to_string();
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(&content).ok()??;
+        let local = local_binding_at(&tree, &content, position)?;
+        info!(
+            "Found local {} for hover: {}",
+            local.kind.description(),
+            local.name
+        );
+
+        // Without an annotation, show the type inferred for it
+        let type_annotation = local.type_annotation.clone().or_else(|| {
+            workspace
+                .module_types(uri)?
+                .binding(local.binding_range)
+                .map(str::to_string)
+        });
+
+        let line = local.binding_range.start.line + 1;
+        let value = if supports_markdown {
+            let mut parts = Vec::new();
+            parts.push(match &local.enclosing_function {
+                Some(function) => format!(
+                    "*{}* **{}** in `{}`",
+                    local.kind.description(),
+                    local.name,
+                    function
+                ),
+                None => format!("*{}* **{}**", local.kind.description(), local.name),
+            });
+            if let Some(type_annotation) = &type_annotation {
+                parts.push(format!(
+                    "```gren\n{} : {}\n```",
+                    local.name, type_annotation
+                ));
+            }
+            parts.push(format!("Bound on line {}: `{}`", line, local.binding_line));
+            parts.join("\n\n")
+        } else {
+            let mut parts = vec![format!("{} {}", local.kind.description(), local.name)];
+            if let Some(type_annotation) = &type_annotation {
+                parts.push(format!("{} : {}", local.name, type_annotation));
+            }
+            if let Some(function) = &local.enclosing_function {
+                parts.push(format!("in {}", function));
+            }
+            parts.push(format!("bound on line {}: {}", line, local.binding_line));
+            parts.join("\n")
+        };
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: if supports_markdown {
+                    MarkupKind::Markdown
+                } else {
+                    MarkupKind::PlainText
+                },
+                value,
+            }),
+            range: Some(local.reference_range),
+        })
+    }
+
+    /// Build hover content in Markdown format from symbol information
+    async fn build_hover_content(
+        &self,
+        symbol: &gren_lsp_core::Symbol,
+        symbol_info: &SymbolAtPosition,
+        workspace: &gren_lsp_core::Workspace,
+        supports_markdown: bool,
+    ) -> String {
+        let mut content = Vec::new();
+
+        // Add symbol name and kind as header
+        let kind_name = match symbol.kind {
+            SymbolKind::FUNCTION => "function",
+            SymbolKind::CLASS => "type",
+            SymbolKind::CONSTRUCTOR => "constructor",
+            SymbolKind::MODULE => "module",
+            SymbolKind::VARIABLE => "variable",
+            SymbolKind::CONSTANT => "constant",
+            _ => "symbol",
+        };
+
+        if supports_markdown {
+            // Check if this is a sum type first
+            let is_sum_type = symbol
+                .type_signature
+                .as_ref()
+                .map(|sig| sig.contains('=') && sig.contains('|'))
+                .unwrap_or(false);
+
+            if is_sum_type {
+                // For sum types, skip the header and use the formatted version directly
+                if let Some(type_signature) = &symbol.type_signature {
+                    let formatted_signature = self.format_sum_type(type_signature);
+                    content.push(formatted_signature);
+                }
+            } else {
+                // For non-sum types, show the normal header
+                if symbol.kind == SymbolKind::CONSTRUCTOR {
+                    // Special case for constructors: show parent type
+                    if let Some(parent_type) = &symbol.container_name {
+                        content.push(format!(
+                            "*{}* **{}** of type **{}**",
+                            kind_name, symbol.name, parent_type
+                        ));
+                    } else {
+                        content.push(format!("*{}* **{}**", kind_name, symbol.name));
+                    }
+                } else {
+                    content.push(format!("*{}* **{}**", kind_name, symbol.name));
+                }
+
+                // Add formatted type signature in code block
+                if let Some(type_signature) = &symbol.type_signature {
+                    let formatted_signature = self.format_type_signature(type_signature);
+                    content.push(format!("```gren\n{}\n```", formatted_signature));
+                }
+            }
+
+            if let Some(message) = symbol.deprecation() {
+                content.push(deprecation_note(message, true));
+            }
+
+            // Add Types section with clickable links (only for functions)
+            if symbol.kind == SymbolKind::FUNCTION {
+                if let Some(type_signature) = &symbol.type_signature {
+                    let types_section = self.create_types_section(type_signature, workspace).await;
+                    if !types_section.is_empty() {
+                        content.push(types_section);
+                    }
+                }
+            }
+
+            // Add module information for qualified symbols
+            if let Some(module_path) = &symbol_info.module_path {
+                content.push(format!("*from module `{}`*", module_path.join(".")));
+            } else if let Some(container) = &symbol.container_name {
+                content.push(format!("*from module `{}`*", container));
+            }
+
+            if workspace
+                .index_generations()
+                .is_signatures_only(&symbol.location.uri)
+            {
+                content.push(signatures_only_note(true));
+            }
+
+            // Add documentation if available
+            if let Some(documentation) = &symbol.documentation {
+                let doc = documentation.trim();
+                if !doc.is_empty() {
+                    content.push("---".to_string());
+                    content.push(doc.to_string());
+                }
+            }
+
+            // Link dependency symbols to their published package documentation
+            if self.settings.hover.docs_links {
+                if let Some(url) = docs_url_for_symbol(symbol) {
+                    content.push(format!("[View docs online]({})", url));
+                }
+            }
+
+            content.join("\n\n")
+        } else {
+            // Plain text format - similar to JavaScript LSP
+            let mut parts = Vec::new();
+
+            // Add kind and symbol name (swapped order)
+            if symbol.kind == SymbolKind::CONSTRUCTOR {
+                // Special case for constructors: show parent type
+                if let Some(parent_type) = &symbol.container_name {
+                    parts.push(format!(
+                        "{} {} of type {}",
+                        kind_name, symbol.name, parent_type
+                    ));
+                } else {
+                    parts.push(format!("{} {}", kind_name, symbol.name));
+                }
+            } else {
+                parts.push(format!("{} {}", kind_name, symbol.name));
+            }
+
+            // Add type signature (plain, no clickable links for plaintext)
+            if let Some(type_signature) = &symbol.type_signature {
+                parts.push(type_signature.clone());
+            }
+
+            if let Some(message) = symbol.deprecation() {
+                parts.push(deprecation_note(message, false));
+            }
+
+            // Add module information
+            if let Some(module_path) = &symbol_info.module_path {
+                parts.push(format!("from module {}", module_path.join(".")));
+            } else if let Some(container) = &symbol.container_name {
+                parts.push(format!("from module {}", container));
+            }
+
+            if workspace
+                .index_generations()
+                .is_signatures_only(&symbol.location.uri)
+            {
+                parts.push(signatures_only_note(false));
+            }
+
+            parts.join("\n")
+        }
+    }
+
+    /// Create a Types section with clickable links for types found in the signature
+    async fn create_types_section(
+        &self,
+        type_signature: &str,
+        workspace: &gren_lsp_core::Workspace,
+    ) -> String {
+        let mut type_links = Vec::new();
+        let mut seen_types = std::collections::HashSet::new();
+
+        // Extract type names using tree-sitter parsing
+        match self
+            .extract_type_names_from_signature_tree_sitter(type_signature)
+            .await
+        {
+            Ok(type_names) => {
+                for type_name in type_names {
+                    // Skip constructors that shouldn't be clickable
+                    if matches!(
+                        type_name.as_str(),
+                        "True" | "False" | "Just" | "Nothing" | "Active" | "Inactive"
+                    ) {
+                        continue;
+                    }
+
+                    // Skip if we already processed this type
+                    if seen_types.contains(&type_name) {
+                        continue;
+                    }
+                    seen_types.insert(type_name.clone());
 
                     // Try to find this type in the workspace
                     match workspace.find_exact_symbols(&type_name) {
@@ -1717,86 +4562,912 @@ impl Handlers {
                                 })
                                 .collect();
 
-                            if let Some(type_symbol) = type_symbols.first() {
-                                // Create a clickable link using the file URI with line/column info
-                                let file_uri = &type_symbol.location.uri;
-                                let start_line = type_symbol.location.range.start.line;
-                                let start_char = type_symbol.location.range.start.character;
+                            if let Some(type_symbol) = type_symbols.first() {
+                                // Create a clickable link using the file URI with line/column info
+                                let file_uri = &type_symbol.location.uri;
+                                let start_line = type_symbol.location.range.start.line;
+                                let start_char = type_symbol.location.range.start.character;
+
+                                // VS Code format: file:///path/to/file.ext#L<line>:<column>
+                                let clickable_uri =
+                                    format!("{}#L{}:{}", file_uri, start_line + 1, start_char);
+                                let clickable_link = format!("[{}]({})", type_name, clickable_uri);
+
+                                type_links.push(clickable_link);
+                            }
+                            // For built-in types or types not found, skip them
+                        }
+                        Err(_) => {
+                            // Could not search, skip this type
+                        }
+                    }
+                }
+            }
+            Err(_) => {
+                // Failed to parse type signature, return empty
+            }
+        }
+
+        if type_links.is_empty() {
+            String::new()
+        } else {
+            format!("**Types:** {}", type_links.join(", "))
+        }
+    }
+
+    /// Extract type names from a type signature using tree-sitter parsing
+    async fn extract_type_names_from_signature_tree_sitter(
+        &self,
+        type_signature: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        // Create a minimal Gren source with just the type expression to parse
+        let gren_source = format!("dummy : {}", type_signature);
+
+        // Parse using gren-lsp-core parser
+        let mut parser = gren_lsp_core::Parser::new()?;
+        if let Some(tree) = parser.parse(&gren_source)? {
+            let language = gren_lsp_core::Parser::language();
+
+            // Query to find all type references in a type expression
+            let type_ref_query = tree_sitter::Query::new(
+                language,
+                r#"
+                ; Type references (upper case identifiers in type positions)
+                (type_ref
+                    (upper_case_qid
+                        (upper_case_identifier) @type.name))
+                
+                ; Simple type references
+                (upper_case_identifier) @type.name
+            "#,
+            )?;
+
+            let mut cursor = tree_sitter::QueryCursor::new();
+            let source_bytes = gren_source.as_bytes();
+            let matches = cursor.matches(&type_ref_query, tree.root_node(), source_bytes);
+
+            let mut type_names = Vec::new();
+            for m in matches {
+                for capture in m.captures {
+                    if let Ok(text) = capture.node.utf8_text(source_bytes) {
+                        // Skip single-letter generic types (a, b, etc.)
+                        if text.len() > 1 {
+                            type_names.push(text.to_string());
+                        }
+                    }
+                }
+            }
+
+            Ok(type_names)
+        } else {
+            Err(anyhow::anyhow!("Failed to parse type signature"))
+        }
+    }
+
+    /// Check whether the client advertised support for a client-side command through the
+    /// `experimental.commands.commands` capability
+    fn client_supports_command(&self, capabilities: &ClientCapabilities, command: &str) -> bool {
+        capabilities
+            .experimental
+            .as_ref()
+            .and_then(|experimental| experimental.get("commands"))
+            .and_then(|commands| commands.get("commands"))
+            .and_then(|commands| commands.as_array())
+            .map(|commands| commands.iter().any(|c| c.as_str() == Some(command)))
+            .unwrap_or(false)
+    }
+
+    /// Pick what to complete from the text before the cursor and the module path
+    /// qualifying the name typed there. `None` means nothing fits, as after the dot of a
+    /// number or when a `.` trigger leads nowhere.
+    fn completion_source(
+        &self,
+        line_prefix: &str,
+        module_path: Option<Vec<String>>,
+        trigger_character: Option<&str>,
+    ) -> Option<CompletionSource> {
+        // `import Json.Dec`: only the module name, not members of what's typed so far
+        if let Some(module_name) = line_prefix.strip_prefix("import ") {
+            let module_name = module_name.trim_start();
+            if module_name
+                .chars()
+                .all(|c| self.is_identifier_char(c) || c == '.')
+            {
+                return Some(CompletionSource::ImportModule);
+            }
+        }
+
+        if let Some(module_path) = module_path {
+            return Some(CompletionSource::ModuleMember(module_path));
+        }
+
+        let chars: Vec<char> = line_prefix.chars().collect();
+        let mut end = chars.len();
+        while end > 0 && self.is_identifier_char(chars[end - 1]) {
+            end -= 1;
+        }
+        if end > 0 && chars[end - 1] == '.' {
+            let mut start = end - 1;
+            while start > 0 && self.is_identifier_char(chars[start - 1]) {
+                start -= 1;
+            }
+            let before_dot = &chars[start..end - 1];
+            let is_number = !before_dot.is_empty() && before_dot.iter().all(char::is_ascii_digit);
+            let is_double_dot = start == end - 1 && start > 0 && chars[start - 1] == '.';
+            return (!is_number && !is_double_dot).then_some(CompletionSource::RecordField);
+        }
+
+        if trigger_character == Some(".") {
+            return None;
+        }
+        Some(CompletionSource::General)
+    }
+
+    /// The most used symbols of the modules `uri` imports whose names start with `word`,
+    /// or the first ones by name of modules nothing uses yet
+    fn popular_symbols(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        word: &str,
+    ) -> Vec<GrenSymbol> {
+        let text = match workspace.get_document_readonly(uri) {
+            Some(document) => document.text().to_string(),
+            None => return Vec::new(),
+        };
+        let word = word.to_lowercase();
+        let starts_with_word = |name: &str| name.to_lowercase().starts_with(&word);
+        let popular = workspace.popular_symbols();
+
+        let mut symbols = Vec::new();
+        for import in self.parse_imported_modules(&text) {
+            let query = SymbolQuery {
+                modules: Some(ModuleFilter::Module(import.module_name.clone())),
+                ..SymbolQuery::default()
+            };
+            let mut module_symbols: Vec<GrenSymbol> = match workspace.find_symbols_matching(
+                &query,
+                0,
+                self.settings.workspace_symbols.max_results,
+            ) {
+                Ok(module_symbols) => module_symbols
+                    .into_iter()
+                    .filter(|symbol| starts_with_word(&symbol.name))
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to get symbols of {}: {}", import.module_name, e);
+                    continue;
+                }
+            };
+
+            let ranking = popular.top(&import.module_name);
+            if ranking.is_empty() {
+                module_symbols.sort_by(|a, b| a.name.cmp(&b.name));
+            } else {
+                module_symbols
+                    .retain(|symbol| ranking.iter().any(|(name, _)| *name == symbol.name));
+                module_symbols.sort_by_key(|symbol| {
+                    std::cmp::Reverse(popular.uses(&import.module_name, &symbol.name))
+                });
+            }
+            module_symbols.truncate(POPULAR_PER_MODULE);
+            symbols.extend(module_symbols);
+        }
+        symbols
+    }
+
+    /// The last completion result filtered down to `word`, if it was for the same word
+    fn cached_completions(
+        &self,
+        uri: &Url,
+        position: Position,
+        word: &str,
+    ) -> Option<Vec<CompletionItem>> {
+        let cache = self.completion_cache.as_ref()?.lock().ok()?;
+        cache.as_ref()?.filter(uri, position, word)
+    }
 
-                                // VS Code format: file:///path/to/file.ext#L<line>:<column>
-                                let clickable_uri =
-                                    format!("{}#L{}:{}", file_uri, start_line + 1, start_char);
-                                let clickable_link = format!("[{}]({})", type_name, clickable_uri);
+    /// Names of the workspace's modules, for the module name of an `import` clause
+    fn import_module_completions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+    ) -> Vec<CompletionItem> {
+        let own_module = workspace.module_name(uri);
+        workspace
+            .module_names()
+            .into_iter()
+            .filter(|name| Some(name) != own_module.as_ref())
+            .map(|name| CompletionItem {
+                label: name.clone(),
+                kind: Some(CompletionItemKind::MODULE),
+                insert_text: Some(name.clone()),
+                sort_text: Some(format!("0_{}", name)),
+                ..Default::default()
+            })
+            .collect()
+    }
 
-                                type_links.push(clickable_link);
-                            }
-                            // For built-in types or types not found, skip them
-                        }
-                        Err(_) => {
-                            // Could not search, skip this type
-                        }
-                    }
+    /// Field names of the record types and record literals in the file. Without type
+    /// information the record before the dot can't be narrowed down further.
+    fn record_field_completions(&self, content: &str) -> Vec<CompletionItem> {
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(e) => {
+                warn!("Failed to create parser for field completions: {}", e);
+                return Vec::new();
+            }
+        };
+        let tree = match parser.parse(content) {
+            Ok(Some(tree)) => tree,
+            _ => return Vec::new(),
+        };
+
+        let mut fields = std::collections::BTreeSet::new();
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            if matches!(node.kind(), "field_type" | "field") {
+                let mut cursor = node.walk();
+                let name = node
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "lower_case_identifier")
+                    .and_then(|name| name.utf8_text(content.as_bytes()).ok());
+                if let Some(name) = name {
+                    fields.insert(name.to_string());
                 }
             }
-            Err(_) => {
-                // Failed to parse type signature, return empty
+            let mut cursor = node.walk();
+            stack.extend(node.named_children(&mut cursor));
+        }
+
+        fields
+            .into_iter()
+            .map(|field| CompletionItem {
+                label: field.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                insert_text: Some(field.clone()),
+                sort_text: Some(format!("0_{}", field)),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// The fields a `{ record | ... }` update being written can still set, when the cursor
+    /// is where a field name goes and the record is a parameter annotated with a record
+    /// alias
+    fn updated_field_completions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        content: &str,
+        position: Position,
+        offset: usize,
+    ) -> Option<Vec<CompletionItem>> {
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(content).ok()??;
+        let (record, set) = field_position(&tree, content, offset)?;
+        let type_name = parameter_type(&tree, content, position.line, record)?;
+        let fields = workspace.record_alias_fields(uri, &type_name)?;
+
+        Some(
+            fields
+                .into_iter()
+                .filter(|field| !set.contains(&field.as_str()))
+                .map(|field| CompletionItem {
+                    label: field.clone(),
+                    kind: Some(CompletionItemKind::FIELD),
+                    detail: Some(format!("field of {}", type_name)),
+                    insert_text: Some(field.clone()),
+                    sort_text: Some(format!("0_{}", field)),
+                    ..Default::default()
+                })
+                .collect(),
+        )
+    }
+
+    /// Constructors that fit the nested pattern position at the cursor, or `None` when the
+    /// cursor isn't in one or its type can't be worked out
+    fn nested_pattern_completions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        content: &str,
+        position: Position,
+    ) -> Option<Vec<CompletionItem>> {
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(content).ok()??;
+
+        let path = nested_pattern_path(&tree, content, position)?;
+        let type_name = expected_pattern_type(&path, |constructor| {
+            self.constructor_signature(workspace, uri, constructor)
+        })?;
+        debug!("Pattern position expects a value of type '{}'", type_name);
+
+        let defining_file = self.type_defining_file(workspace, uri, content, &type_name)?;
+        let name = type_name.rsplit('.').next().unwrap_or(&type_name);
+        let symbols = workspace.find_symbols("").ok()?;
+        let items: Vec<CompletionItem> = symbols
+            .into_iter()
+            .filter(|symbol| {
+                symbol.kind == SymbolKind::CONSTRUCTOR
+                    && symbol.container_name.as_deref() == Some(name)
+                    && symbol.location.uri == defining_file
+            })
+            .map(|symbol| {
+                let item = CompletionItem {
+                    label: symbol.name.clone(),
+                    kind: Some(CompletionItemKind::CONSTRUCTOR),
+                    detail: symbol.type_signature.clone(),
+                    documentation: symbol
+                        .documentation
+                        .as_ref()
+                        .map(|doc| Documentation::String(doc.clone())),
+                    insert_text: Some(symbol.name.clone()),
+                    sort_text: Some(format!("0_{}", symbol.name)),
+                    ..Default::default()
+                };
+                mark_deprecated(item, &symbol)
+            })
+            .collect();
+
+        if items.is_empty() {
+            None
+        } else {
+            Some(items)
+        }
+    }
+
+    /// Completion writing a branch for every constructor the `when` expression around the
+    /// cursor doesn't match yet, when the cursor is where a new branch pattern starts
+    fn missing_branches_completion(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        content: &str,
+        position: Position,
+        line_prefix: &str,
+        supports_snippets: bool,
+    ) -> Option<CompletionItem> {
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(content).ok()??;
+        let when = when_expression_at(&tree, content, position)?;
+        let column = when.branch_start(line_prefix)?;
+        let constructors = self.missing_when_branches(workspace, uri, &tree, content, &when)?;
+
+        let text = branches_text(&constructors, column, supports_snippets);
+        let names: Vec<&str> = constructors
+            .iter()
+            .map(|constructor| constructor.name.as_str())
+            .collect();
+        let range = Range::new(Position::new(position.line, column as u32), position);
+        Some(CompletionItem {
+            label: "missing branches".to_string(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some(names.join(", ")),
+            // Typing the first constructor keeps the item in the list
+            filter_text: Some(names[0].to_string()),
+            sort_text: Some("0_".to_string()),
+            preselect: Some(true),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                range,
+                text[column..].to_string(),
+            ))),
+            insert_text_format: Some(if supports_snippets {
+                InsertTextFormat::SNIPPET
+            } else {
+                InsertTextFormat::PLAIN_TEXT
+            }),
+            // The text is already indented for its position
+            insert_text_mode: Some(InsertTextMode::AS_IS),
+            ..Default::default()
+        })
+    }
+
+    /// Quick fix adding a branch for every constructor the `when` expression at the
+    /// cursor doesn't match yet, after its last branch
+    fn generate_missing_branches_action(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let uri = &params.text_document.uri;
+        let content = match workspace.get_document_readonly(uri) {
+            Some(document) => document.text().to_string(),
+            None => return Vec::new(),
+        };
+        let tree = match gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(&content).ok().flatten())
+        {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let when = match when_expression_at(&tree, &content, params.range.start) {
+            Some(when) if !when.patterns.is_empty() => when,
+            _ => return Vec::new(),
+        };
+        let constructors = match self.missing_when_branches(workspace, uri, &tree, &content, &when)
+        {
+            Some(constructors) => constructors,
+            None => return Vec::new(),
+        };
+
+        let text = format!("\n\n{}", branches_text(&constructors, when.indent(), false));
+        let mut changes = std::collections::HashMap::new();
+        changes.insert(
+            uri.clone(),
+            vec![TextEdit::new(Range::new(when.end, when.end), text)],
+        );
+        let title = match constructors.as_slice() {
+            [constructor] => format!("Add missing branch `{}`", constructor.name),
+            _ => format!("Add {} missing branches", constructors.len()),
+        };
+        vec![CodeActionOrCommand::CodeAction(CodeAction {
+            title,
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: None,
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                document_changes: None,
+                change_annotations: None,
+            }),
+            command: None,
+            is_preferred: Some(true),
+            disabled: None,
+            data: None,
+        })]
+    }
+
+    /// Quick fix for the compiler's `MISSING PATTERNS` error: a branch for each pattern it
+    /// lists, after the last branch of the `when` expression it points at
+    fn generate_missing_pattern_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let uri = &params.text_document.uri;
+        let mut actions = Vec::new();
+        for diagnostic in &params.context.diagnostics {
+            let patterns = match missing_patterns(diagnostic) {
+                Some(patterns) if !patterns.is_empty() => patterns,
+                _ => continue,
+            };
+            let content = match workspace.get_document_readonly(uri) {
+                Some(document) => document.text().to_string(),
+                None => return actions,
+            };
+            let tree = match gren_lsp_core::Parser::new()
+                .ok()
+                .and_then(|mut parser| parser.parse(&content).ok().flatten())
+            {
+                Some(tree) => tree,
+                None => continue,
+            };
+            let when = match when_expression_at(&tree, &content, diagnostic.range.start) {
+                Some(when) => when,
+                None => continue,
+            };
+
+            let branches: Vec<BranchConstructor> = patterns
+                .iter()
+                .map(|pattern| BranchConstructor::from_pattern(pattern))
+                .collect();
+            let text = format!("\n\n{}", branches_text(&branches, when.indent(), false));
+            let mut changes = std::collections::HashMap::new();
+            changes.insert(
+                uri.clone(),
+                vec![TextEdit::new(Range::new(when.end, when.end), text)],
+            );
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Add missing patterns".to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    document_changes: None,
+                    change_annotations: None,
+                }),
+                command: None,
+                is_preferred: Some(true),
+                disabled: None,
+                data: None,
+            }));
+        }
+        actions
+    }
+
+    /// The constructors of the `when` subject's custom type that no branch matches yet,
+    /// named as the file has to write them. `None` when the subject's type isn't known, a
+    /// branch matches anything, or every constructor has a branch.
+    fn missing_when_branches(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        tree: &tree_sitter::Tree,
+        content: &str,
+        when: &WhenExpression,
+    ) -> Option<Vec<BranchConstructor>> {
+        let type_name = self.when_subject_type(workspace, uri, tree, content, when)?;
+        debug!(
+            "`when {}` matches values of type '{}'",
+            when.subject, type_name
+        );
+
+        let defining_file = self.type_defining_file(workspace, uri, content, &type_name)?;
+        let unqualified = type_name.rsplit('.').next().unwrap_or(&type_name);
+        let mut constructors: Vec<GrenSymbol> = workspace
+            .find_symbols("")
+            .ok()?
+            .into_iter()
+            .filter(|symbol| {
+                symbol.kind == SymbolKind::CONSTRUCTOR
+                    && symbol.container_name.as_deref() == Some(unqualified)
+                    && symbol.location.uri == defining_file
+            })
+            .collect();
+        if constructors.is_empty() {
+            return None;
+        }
+        constructors.sort_by_key(|symbol| symbol.location.range.start);
+
+        let names: Vec<String> = constructors
+            .iter()
+            .map(|symbol| symbol.name.clone())
+            .collect();
+        let missing = missing_constructors(&when.patterns, &names)?;
+        if missing.is_empty() {
+            return None;
+        }
+
+        let qualifier = match when.qualifier() {
+            Some(qualifier) => Some(qualifier.to_string()),
+            None if defining_file != *uri => {
+                self.constructor_qualifier(workspace, content, &defining_file, unqualified)
             }
+            None => None,
+        };
+        Some(
+            constructors
+                .iter()
+                .filter(|symbol| missing.contains(&symbol.name))
+                .map(|symbol| {
+                    let name = match &qualifier {
+                        Some(qualifier) => format!("{}.{}", qualifier, symbol.name),
+                        None => symbol.name.clone(),
+                    };
+                    BranchConstructor::from_signature(name, symbol.type_signature.as_deref())
+                })
+                .collect(),
+        )
+    }
+
+    /// Name of the custom type a `when` expression matches: the type of a constructor a
+    /// branch already matches, of the enclosing function's parameter the subject is, or
+    /// the result of the function the subject calls
+    fn when_subject_type(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        tree: &tree_sitter::Tree,
+        content: &str,
+        when: &WhenExpression,
+    ) -> Option<String> {
+        let branch_type = when
+            .patterns
+            .iter()
+            .filter_map(|pattern| pattern_constructor(pattern))
+            .find_map(|constructor| {
+                let name = constructor.rsplit('.').next().unwrap_or(constructor);
+                self.local_or_unique_symbol(workspace, uri, name, SymbolKind::CONSTRUCTOR)?
+                    .container_name
+            });
+        if branch_type.is_some() {
+            return branch_type;
         }
 
-        if type_links.is_empty() {
-            String::new()
+        if let Some(type_name) = parameter_type(tree, content, when.header_line, &when.subject) {
+            return Some(type_name);
+        }
+
+        let (function, arguments) = when.call.as_ref()?;
+        let name = function.rsplit('.').next().unwrap_or(function);
+        let symbol = self.local_or_unique_symbol(workspace, uri, name, SymbolKind::FUNCTION)?;
+        result_type_name(symbol.type_signature.as_deref()?, *arguments)
+    }
+
+    /// The symbol of a kind named `name`: the one in the current file, or the only one
+    fn local_or_unique_symbol(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        name: &str,
+        kind: SymbolKind,
+    ) -> Option<GrenSymbol> {
+        let mut symbols: Vec<GrenSymbol> = workspace
+            .find_exact_symbols(name)
+            .ok()?
+            .into_iter()
+            .filter(|symbol| symbol.kind == kind)
+            .collect();
+        if let Some(index) = symbols
+            .iter()
+            .position(|symbol| symbol.location.uri == *uri)
+        {
+            return Some(symbols.swap_remove(index));
+        }
+        match symbols.len() {
+            1 => symbols.pop(),
+            _ => None,
+        }
+    }
+
+    /// How the current file qualifies the constructors of `type_name` from another module:
+    /// not at all when its import exposes them with `Name(..)`, otherwise by the import's
+    /// alias or the module's name
+    fn constructor_qualifier(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        content: &str,
+        defining_file: &Url,
+        type_name: &str,
+    ) -> Option<String> {
+        let module_name = workspace.module_name(defining_file)?;
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(content).ok()??;
+        let exposes_constructors = imported_names(&tree, content, &module_name)
+            .iter()
+            .any(|name| name.name == type_name && name.with_constructors);
+        if exposes_constructors {
+            return None;
+        }
+        let qualifier = self
+            .parse_imported_modules(content)
+            .into_iter()
+            .find(|import| import.module_name == module_name)
+            .map(|import| import.qualifier().to_string());
+        Some(qualifier.unwrap_or(module_name))
+    }
+
+    /// Indexed signature of a constructor. A definition in the current file wins; otherwise
+    /// the signature is only used when every constructor with that name agrees on it.
+    fn constructor_signature(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        constructor: &str,
+    ) -> Option<String> {
+        let constructors: Vec<GrenSymbol> = workspace
+            .find_exact_symbols(constructor)
+            .ok()?
+            .into_iter()
+            .filter(|symbol| symbol.kind == SymbolKind::CONSTRUCTOR)
+            .collect();
+
+        if let Some(local) = constructors
+            .iter()
+            .find(|symbol| symbol.location.uri == *uri)
+        {
+            return local.type_signature.clone();
+        }
+
+        let mut signatures = constructors
+            .iter()
+            .map(|symbol| symbol.type_signature.clone());
+        let first = signatures.next()??;
+        if signatures.all(|signature| signature.as_deref() == Some(first.as_str())) {
+            Some(first)
         } else {
-            format!("**Types:** {}", type_links.join(", "))
+            None
         }
     }
 
-    /// Extract type names from a type signature using tree-sitter parsing
-    async fn extract_type_names_from_signature_tree_sitter(
+    /// The file declaring the custom type `type_name` as `uri` refers to it: `uri` itself,
+    /// or the module its qualifier or imports point at. `None` when that's ambiguous.
+    fn type_defining_file(
         &self,
-        type_signature: &str,
-    ) -> anyhow::Result<Vec<String>> {
-        // Create a minimal Gren source with just the type expression to parse
-        let gren_source = format!("dummy : {}", type_signature);
+        workspace: &gren_lsp_core::Workspace,
+        uri: &Url,
+        content: &str,
+        type_name: &str,
+    ) -> Option<Url> {
+        let (qualifier, name) = match type_name.rsplit_once('.') {
+            Some((qualifier, name)) => (Some(qualifier), name),
+            None => (None, type_name),
+        };
+        let mut files: Vec<Url> = workspace
+            .find_exact_symbols(name)
+            .ok()?
+            .into_iter()
+            .filter(|symbol| symbol.kind == SymbolKind::CLASS)
+            .map(|symbol| symbol.location.uri)
+            .collect();
+        files.sort();
+        files.dedup();
+        if qualifier.is_none() && files.contains(uri) {
+            return Some(uri.clone());
+        }
 
-        // Parse using gren-lsp-core parser
-        let mut parser = gren_lsp_core::Parser::new()?;
-        if let Some(tree) = parser.parse(&gren_source)? {
-            let language = gren_lsp_core::Parser::language();
+        let imported: Vec<String> = self
+            .parse_imported_modules(content)
+            .into_iter()
+            .filter(|import| qualifier.map_or(true, |qualifier| import.qualifier() == qualifier))
+            .map(|import| import.module_name)
+            .collect();
+        let from_imports: Vec<&Url> = files
+            .iter()
+            .filter(|file| {
+                workspace
+                    .module_name(file)
+                    .is_some_and(|module| imported.contains(&module))
+            })
+            .collect();
+        match (from_imports.as_slice(), files.as_slice()) {
+            ([file], _) => Some((*file).clone()),
+            ([], [file]) if qualifier.is_none() => Some(file.clone()),
+            _ => None,
+        }
+    }
 
-            // Query to find all type references in a type expression
-            let type_ref_query = tree_sitter::Query::new(
-                language,
-                r#"
-                ; Type references (upper case identifiers in type positions)
-                (type_ref
-                    (upper_case_qid
-                        (upper_case_identifier) @type.name))
-                
-                ; Simple type references
-                (upper_case_identifier) @type.name
-            "#,
-            )?;
+    /// Completions for a qualified module access: the members of the module the path
+    /// refers to, plus the next segment of any imported module nested below it
+    fn qualified_module_completions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CompletionParams,
+        content: &str,
+        module_path: &[String],
+        supports_trigger_suggest: bool,
+    ) -> Vec<CompletionItem> {
+        let typed_qualifier = module_path.join(".");
+        let imports = self.parse_imported_modules(content);
+        let mut items = Vec::new();
+
+        // Members of the module this qualifier refers to
+        let qualified: Vec<&ImportedModule> = imports
+            .iter()
+            .filter(|i| i.qualifier() == typed_qualifier)
+            .collect();
+        let symbols = if qualified.is_empty() {
+            Vec::new()
+        } else {
+            match workspace.find_symbols("") {
+                Ok(symbols) => symbols,
+                Err(e) => {
+                    warn!("Failed to get workspace symbols: {}", e);
+                    Vec::new()
+                }
+            }
+        };
+        for import in qualified {
+            for symbol in &symbols {
+                if symbol.kind == SymbolKind::MODULE
+                    || !self.symbol_is_from_module(symbol, &import.module_name)
+                    || !workspace.is_exposed(symbol)
+                    || !self.should_include_symbol_in_completion(symbol, params)
+                {
+                    continue;
+                }
+
+                let item = CompletionItem {
+                    label: symbol.name.clone(),
+                    kind: Some(self.symbol_kind_to_completion_kind(symbol.kind)),
+                    detail: symbol.type_signature.clone(),
+                    documentation: symbol
+                        .documentation
+                        .as_ref()
+                        .map(|doc| Documentation::String(doc.clone())),
+                    insert_text: Some(symbol.name.clone()),
+                    sort_text: Some(format!("0_{}", symbol.name)),
+                    ..Default::default()
+                };
+                items.push(mark_deprecated(item, symbol));
+            }
+        }
+
+        // Nested modules, e.g. `Decode` after `Json.` when `Json.Decode` is imported
+        let nested_prefix = format!("{}.", typed_qualifier);
+        let mut seen_segments = std::collections::HashSet::new();
+        for import in &imports {
+            if let Some(rest) = import.qualifier().strip_prefix(&nested_prefix) {
+                let segment = rest.split('.').next().unwrap_or("");
+                if segment.is_empty() || !seen_segments.insert(segment.to_string()) {
+                    continue;
+                }
+                let mut item = self.module_segment_completion(
+                    segment,
+                    &import.module_name,
+                    supports_trigger_suggest,
+                );
+                item.sort_text = Some(format!("1_{}", segment));
+                items.push(item);
+            }
+        }
+
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+        items.dedup_by(|a, b| a.label == b.label && a.kind == b.kind);
+        items
+    }
+
+    /// Completion item for one segment of a module qualifier. When the client can trigger
+    /// suggestions, accepting it inserts the trailing dot and immediately asks for the
+    /// module's members.
+    fn module_segment_completion(
+        &self,
+        segment: &str,
+        module_name: &str,
+        supports_trigger_suggest: bool,
+    ) -> CompletionItem {
+        let (insert_text, command) = if supports_trigger_suggest {
+            (
+                format!("{}.", segment),
+                Some(Command {
+                    title: "Complete module members".to_string(),
+                    command: TRIGGER_SUGGEST_COMMAND.to_string(),
+                    arguments: None,
+                }),
+            )
+        } else {
+            (segment.to_string(), None)
+        };
 
-            let mut cursor = tree_sitter::QueryCursor::new();
-            let source_bytes = gren_source.as_bytes();
-            let matches = cursor.matches(&type_ref_query, tree.root_node(), source_bytes);
+        CompletionItem {
+            label: segment.to_string(),
+            kind: Some(CompletionItemKind::MODULE),
+            detail: Some(format!("module {}", module_name)),
+            insert_text: Some(insert_text),
+            command,
+            ..Default::default()
+        }
+    }
 
-            let mut type_names = Vec::new();
-            for m in matches {
-                for capture in m.captures {
-                    if let Ok(text) = capture.node.utf8_text(source_bytes) {
-                        // Skip single-letter generic types (a, b, etc.)
-                        if text.len() > 1 {
-                            type_names.push(text.to_string());
-                        }
+    /// Collect the modules imported by a file, along with their aliases
+    fn parse_imported_modules(&self, content: &str) -> Vec<ImportedModule> {
+        let mut imports = Vec::new();
+
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(_) => return imports,
+        };
+        let tree = match parser.parse(content) {
+            Ok(Some(tree)) => tree,
+            _ => return imports,
+        };
+
+        let source_bytes = content.as_bytes();
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+
+        for import_clause in root
+            .children(&mut cursor)
+            .filter(|node| node.kind() == "import_clause")
+        {
+            let mut module_name = None;
+            let mut alias = None;
+
+            let mut clause_cursor = import_clause.walk();
+            for child in import_clause.children(&mut clause_cursor) {
+                match child.kind() {
+                    "upper_case_qid" => {
+                        module_name = child.utf8_text(source_bytes).ok().map(str::to_string);
+                    }
+                    "as_clause" => {
+                        let mut alias_cursor = child.walk();
+                        alias = child
+                            .children(&mut alias_cursor)
+                            .find(|n| n.kind() == "upper_case_identifier")
+                            .and_then(|n| n.utf8_text(source_bytes).ok())
+                            .map(str::to_string);
                     }
+                    _ => {}
                 }
             }
 
-            Ok(type_names)
-        } else {
-            Err(anyhow::anyhow!("Failed to parse type signature"))
+            if let Some(module_name) = module_name {
+                imports.push(ImportedModule { module_name, alias });
+            }
         }
+
+        imports
     }
 
     /// Get Gren language keyword completions
@@ -2011,27 +5682,61 @@ impl Handlers {
         false
     }
 
-    /// Generate quick fix actions for import suggestions based on unresolved symbols
+    /// Generate quick fix actions for import suggestions based on unresolved symbols.
+    /// Imports that would create an import cycle aren't offered; with `disabled_support`
+    /// they're listed as disabled actions saying why.
     async fn generate_import_quickfix_actions(
         &self,
         workspace: &gren_lsp_core::Workspace,
         params: &CodeActionParams,
+        disabled_support: bool,
     ) -> Option<Vec<CodeActionOrCommand>> {
         let mut actions = Vec::new();
+        let direct_dependencies = workspace.direct_dependencies(&params.text_document.uri);
+        let importing_module = workspace.module_name(&params.text_document.uri);
 
         // Check diagnostics for unresolved symbols
         for diagnostic in &params.context.diagnostics {
             if let Some(unresolved_symbol) = self.extract_unresolved_symbol(diagnostic) {
-                // Find available symbols with matching names
-                if let Ok(symbols) = workspace.find_symbols(&unresolved_symbol) {
-                    for symbol in symbols {
-                        if let Some(action) = self.create_import_action(
-                            &symbol,
-                            &params.text_document.uri,
-                            diagnostic,
-                        ) {
-                            actions.push(CodeActionOrCommand::CodeAction(action));
+                // Find available symbols with matching names, among those an import can
+                // bring in
+                let mut candidates: Vec<gren_lsp_core::Symbol> = workspace
+                    .find_symbols(&unresolved_symbol)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|symbol| workspace.is_exposed(symbol))
+                    .collect();
+                candidates.sort_by_cached_key(|symbol| {
+                    (
+                        import_candidate_rank(symbol, &unresolved_symbol, &direct_dependencies),
+                        self.extract_module_name_from_path(&symbol.location.uri),
+                        symbol.name.clone(),
+                    )
+                });
+
+                // Only the best candidate is preferred, so "fix all" picks one import
+                let mut preferred = true;
+                for symbol in candidates {
+                    if let Some(cycle) =
+                        import_cycle(workspace, importing_module.as_deref(), &symbol)
+                    {
+                        if disabled_support {
+                            if let Some(action) =
+                                self.create_cyclic_import_action(&symbol, &cycle, diagnostic)
+                            {
+                                actions.push(CodeActionOrCommand::CodeAction(action));
+                            }
                         }
+                        continue;
+                    }
+                    if let Some(action) = self.create_import_action(
+                        &symbol,
+                        &params.text_document.uri,
+                        diagnostic,
+                        preferred,
+                    ) {
+                        actions.push(CodeActionOrCommand::CodeAction(action));
+                        preferred = false;
                     }
                 }
             }
@@ -2063,17 +5768,27 @@ impl Handlers {
         None
     }
 
-    /// Create import code action for a symbol
+    /// Create import code action for a symbol. Functions show their type in the title, so
+    /// candidates from different modules can be told apart.
     fn create_import_action(
         &self,
         symbol: &gren_lsp_core::Symbol,
         target_uri: &lsp_types::Url,
         diagnostic: &Diagnostic,
+        preferred: bool,
     ) -> Option<CodeAction> {
         // Extract module name from symbol location
         let module_name = self.extract_module_name_from_path(&symbol.location.uri)?;
 
-        let title = format!("Import {} from {}", symbol.name, module_name);
+        let title = match symbol.type_signature.as_deref() {
+            Some(signature) if symbol.kind == SymbolKind::FUNCTION => format!(
+                "Import {} from {} : {}",
+                symbol.name,
+                module_name,
+                signature.split_whitespace().collect::<Vec<_>>().join(" ")
+            ),
+            _ => format!("Import {} from {}", symbol.name, module_name),
+        };
 
         // Generate the import statement
         let import_statement = format!("import {} exposing ({})", module_name, symbol.name);
@@ -2109,12 +5824,44 @@ impl Handlers {
             diagnostics: Some(vec![diagnostic.clone()]),
             edit: Some(workspace_edit),
             command: None,
-            is_preferred: Some(true),
+            is_preferred: Some(preferred),
             disabled: None,
             data: None,
         })
     }
 
+    /// A disabled import action for a symbol whose module already depends on the importing
+    /// one, `cycle` being the imports leading back to it
+    fn create_cyclic_import_action(
+        &self,
+        symbol: &gren_lsp_core::Symbol,
+        cycle: &[String],
+        diagnostic: &Diagnostic,
+    ) -> Option<CodeAction> {
+        let module_name = self.extract_module_name_from_path(&symbol.location.uri)?;
+        let importing_module = cycle.last()?;
+        let reason = if cycle.len() == 1 {
+            format!("`{}` is defined in {} itself", symbol.name, module_name)
+        } else {
+            format!(
+                "Importing {} would create an import cycle ({} → {}). \
+                 Move `{}` to a module {} can import instead",
+                module_name,
+                importing_module,
+                cycle.join(" → "),
+                symbol.name,
+                importing_module
+            )
+        };
+        Some(CodeAction {
+            title: format!("Import {} from {}", symbol.name, module_name),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            disabled: Some(CodeActionDisabled { reason }),
+            ..Default::default()
+        })
+    }
+
     /// Generate organize imports action
     async fn generate_organize_imports_action(
         &self,
@@ -2124,142 +5871,620 @@ impl Handlers {
         let document = workspace.get_document_readonly(uri)?;
         let content = document.text();
 
-        // Parse the document to find import statements using tree-sitter
-        let organized_imports = self.organize_imports_in_content(content).await?;
+        // Conflicting imports are organized along with a resolution instead, apart from
+        // duplicates that merging settles
+        let (tree, imports, conflicts) = self.import_conflicts_in_content(workspace, content)?;
+        if conflicts
+            .iter()
+            .any(|conflict| !conflict.is_mergeable(&imports))
+        {
+            return None;
+        }
 
-        if organized_imports == content {
+        // Merging, pruning and sorting happen in the one edit rewriting the section
+        let rewrites = tidy_imports(
+            &tree,
+            content,
+            self.settings.imports.expand_exposing_all,
+            |module_name| workspace.module_exports(module_name),
+        );
+        let section = organize_imports_with_rewrites(
+            &tree,
+            content,
+            self.settings.imports.style,
+            |module_name| workspace.import_group(module_name),
+            &rewrites,
+        )?;
+        if section.is_organized() {
             // No changes needed
             return None;
         }
 
-        let edit = TextEdit {
-            range: Range {
-                start: Position {
-                    line: 0,
-                    character: 0,
-                },
-                end: Position {
-                    line: content.lines().count() as u32,
-                    character: 0,
-                },
-            },
-            new_text: organized_imports,
+        let mut changes = std::collections::HashMap::new();
+        changes.insert(uri.clone(), vec![section.text_edit()]);
+
+        let workspace_edit = WorkspaceEdit {
+            changes: Some(changes),
+            document_changes: None,
+            change_annotations: None,
+        };
+
+        Some(CodeAction {
+            title: "Organize imports".to_string(),
+            kind: Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+            diagnostics: None,
+            edit: Some(workspace_edit),
+            command: None,
+            is_preferred: Some(false),
+            disabled: None,
+            data: None,
+        })
+    }
+
+    /// Organize imports actions for imports that conflict, one per way to resolve the first
+    /// conflict, so organizing never settles on imports that make references ambiguous
+    fn generate_conflict_organize_actions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        uri: &lsp_types::Url,
+    ) -> Vec<CodeAction> {
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+        let content = document.text();
+        let (tree, imports, conflicts) = match self.import_conflicts_in_content(workspace, content)
+        {
+            Some(found) => found,
+            None => return Vec::new(),
+        };
+        let conflict = match conflicts
+            .iter()
+            .find(|conflict| !conflict.is_mergeable(&imports))
+        {
+            Some(conflict) => conflict,
+            None => return Vec::new(),
+        };
+
+        conflict_resolutions(&tree, content, &imports, conflict, |module_name| {
+            workspace.module_exports(module_name)
+        })
+        .into_iter()
+        .filter_map(|resolution| {
+            let section = organize_imports_with_rewrites(
+                &tree,
+                content,
+                self.settings.imports.style,
+                |module_name| workspace.import_group(module_name),
+                &resolution.rewrites,
+            )?;
+            let mut edits = vec![section.text_edit()];
+            edits.extend(resolution.reference_edits);
+
+            let mut changes = std::collections::HashMap::new();
+            changes.insert(uri.clone(), edits);
+            let mut title = resolution.title;
+            title[..1].make_ascii_lowercase();
+            Some(CodeAction {
+                title: format!("Organize imports and {}", title),
+                kind: Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+                diagnostics: None,
+                edit: Some(WorkspaceEdit::new(changes)),
+                command: None,
+                is_preferred: Some(false),
+                disabled: None,
+                data: None,
+            })
+        })
+        .collect()
+    }
+
+    /// Quick fixes for import conflict lints, resolving the conflict without reordering the
+    /// imports
+    fn generate_import_conflict_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let lint_diagnostics: Vec<&Diagnostic> = params
+            .context
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.code == Some(NumberOrString::String(IMPORT_CONFLICT.to_string()))
+            })
+            .collect();
+        if lint_diagnostics.is_empty() {
+            return Vec::new();
+        }
+
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+        let content = document.text();
+        let (tree, imports, conflicts) = match self.import_conflicts_in_content(workspace, content)
+        {
+            Some(found) => found,
+            None => return Vec::new(),
+        };
+
+        let mut actions = Vec::new();
+        for diagnostic in lint_diagnostics {
+            let conflict = conflicts.iter().find(|conflict| {
+                imports[conflict.second].range == diagnostic.range
+                    && conflict.message(&imports) == diagnostic.message
+            });
+            let conflict = match conflict {
+                Some(conflict) => conflict,
+                None => continue,
+            };
+
+            let resolutions = conflict_resolutions(&tree, content, &imports, conflict, |name| {
+                workspace.module_exports(name)
+            });
+            for (index, resolution) in resolutions.iter().enumerate() {
+                let mut changes = std::collections::HashMap::new();
+                changes.insert(uri.clone(), resolution.text_edits(&imports));
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: resolution.title.clone(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(changes)),
+                    command: None,
+                    is_preferred: Some(index == 0),
+                    disabled: None,
+                    data: None,
+                }));
+            }
+        }
+        actions
+    }
+
+    /// Quick fix for import style lints. Uses the same engine as organize imports, so the
+    /// fix always produces what the lint asks for.
+    fn generate_import_style_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let lint_diagnostics: Vec<Diagnostic> = params
+            .context
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.code == Some(NumberOrString::String(IMPORT_STYLE_MISMATCH.to_string()))
+            })
+            .cloned()
+            .collect();
+
+        if lint_diagnostics.is_empty() {
+            return Vec::new();
+        }
+
+        let uri = &params.text_document.uri;
+        let section = match workspace
+            .get_document_readonly(uri)
+            .and_then(|document| self.organize_imports_in_content(workspace, document.text()))
+        {
+            Some(section) if !section.is_organized() => section,
+            _ => return Vec::new(),
         };
 
         let mut changes = std::collections::HashMap::new();
-        changes.insert(uri.clone(), vec![edit]);
+        changes.insert(uri.clone(), vec![section.text_edit()]);
+
+        vec![CodeActionOrCommand::CodeAction(CodeAction {
+            title: "Reorder imports".to_string(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(lint_diagnostics),
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                document_changes: None,
+                change_annotations: None,
+            }),
+            command: None,
+            is_preferred: Some(true),
+            disabled: None,
+            data: None,
+        })]
+    }
+
+    /// Source actions sorting the module's top-level declarations and moving the one the
+    /// range starts in. Moves run `gren.moveDeclaration`, which works the edit out against
+    /// the document as it is when the command runs and makes it undoable.
+    fn generate_declaration_order_actions(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+        sort: bool,
+        moves: bool,
+    ) -> Vec<CodeActionOrCommand> {
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+        let source = document.text();
+        let tree = match gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(source).ok().flatten())
+        {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+
+        let mut actions = Vec::new();
+        if sort {
+            if let Some(edit) = sort_declarations(&tree, source) {
+                let mut changes = std::collections::HashMap::new();
+                changes.insert(uri.clone(), vec![edit]);
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: "Sort top-level declarations alphabetically".to_string(),
+                    kind: Some(SOURCE_SORT_DECLARATIONS),
+                    edit: Some(WorkspaceEdit::new(changes)),
+                    ..Default::default()
+                }));
+            }
+        }
+
+        let block = match declaration_at(&tree, source, params.range.start) {
+            Some(block) if moves => block,
+            _ => return actions,
+        };
+        for (direction, label) in [(MoveDirection::Up, "up"), (MoveDirection::Down, "down")] {
+            if move_declaration(&tree, source, params.range.start, direction).is_none() {
+                continue;
+            }
+            let arguments = MoveDeclarationArguments {
+                text_document: params.text_document.clone(),
+                position: params.range.start,
+                direction,
+            };
+            let title = format!("Move `{}` {}", block.name, label);
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: title.clone(),
+                kind: Some(SOURCE_MOVE_DECLARATION),
+                command: Some(Command {
+                    title,
+                    command: MOVE_DECLARATION_COMMAND.to_string(),
+                    arguments: serde_json::to_value(arguments)
+                        .ok()
+                        .map(|value| vec![value]),
+                }),
+                ..Default::default()
+            }));
+        }
+        actions
+    }
+
+    /// Quick fixes renaming a local that shadows a top-level or imported name, one per
+    /// suggested name. Only the local's own scope is rewritten, so uses of the outer name
+    /// elsewhere in the file are left alone.
+    fn generate_shadowing_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let shadowing_diagnostics: Vec<&Diagnostic> = params
+            .context
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.code == Some(NumberOrString::String(SHADOWED_NAME.to_string()))
+            })
+            .collect();
+        if shadowing_diagnostics.is_empty() {
+            return Vec::new();
+        }
+
+        let uri = &params.text_document.uri;
+        let content = match workspace.get_document_readonly(uri) {
+            Some(document) => document.text().to_string(),
+            None => return Vec::new(),
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(_) => return Vec::new(),
+        };
+        let tree = match parser.parse(&content) {
+            Ok(Some(tree)) => tree,
+            _ => return Vec::new(),
+        };
+        let bindings = shadowed_bindings(&tree, &content, |module_name| {
+            workspace.exposed_values(module_name)
+        });
+
+        let mut actions = Vec::new();
+        for diagnostic in shadowing_diagnostics {
+            let binding = match bindings
+                .iter()
+                .find(|binding| binding.binding_range == diagnostic.range)
+            {
+                Some(binding) => binding,
+                None => continue,
+            };
+
+            for (index, new_name) in binding.suggestions.iter().enumerate() {
+                let edits = binding
+                    .occurrences
+                    .iter()
+                    .map(|range| TextEdit::new(*range, new_name.clone()))
+                    .collect();
+                let mut changes = std::collections::HashMap::new();
+                changes.insert(uri.clone(), edits);
+
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Rename `{}` to `{}`", binding.name, new_name),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(changes),
+                        document_changes: None,
+                        change_annotations: None,
+                    }),
+                    command: None,
+                    is_preferred: Some(index == 0),
+                    disabled: None,
+                    data: None,
+                }));
+            }
+        }
+        actions
+    }
+
+    /// Quick fixes the compiler suggests in its errors: close names, missing imports and
+    /// annotations matching the definition's body
+    fn generate_compiler_hint_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let uri = &params.text_document.uri;
+        let document = match workspace.get_document_readonly(uri) {
+            Some(document) => document,
+            None => return Vec::new(),
+        };
+        let tree = match gren_lsp_core::Parser::new()
+            .ok()
+            .and_then(|mut parser| parser.parse(document.text()).ok().flatten())
+        {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        params
+            .context
+            .diagnostics
+            .iter()
+            .flat_map(|diagnostic| {
+                hint_fixes(uri, document, &tree, diagnostic, |module| {
+                    workspace.module_uri(module).is_some()
+                })
+            })
+            .map(CodeActionOrCommand::CodeAction)
+            .collect()
+    }
+
+    /// Quick fix for a recursive type alias: turn it into a custom type with a single
+    /// constructor of the same name, which may refer to itself. Uses of the alias still
+    /// need to wrap and unwrap values through the constructor afterwards.
+    fn generate_alias_cycle_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let cycle_diagnostics: Vec<&Diagnostic> = params
+            .context
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.code == Some(NumberOrString::String(RECURSIVE_ALIAS.to_string()))
+            })
+            .collect();
+        if cycle_diagnostics.is_empty() {
+            return Vec::new();
+        }
+
+        let uri = &params.text_document.uri;
+        let content = match workspace.get_document_readonly(uri) {
+            Some(document) => document.text().to_string(),
+            None => return Vec::new(),
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(_) => return Vec::new(),
+        };
+        let tree = match parser.parse(&content) {
+            Ok(Some(tree)) => tree,
+            _ => return Vec::new(),
+        };
+        let cycles = alias_cycles(&tree, &content);
+
+        let mut actions = Vec::new();
+        for diagnostic in cycle_diagnostics {
+            let cycle = match cycles
+                .iter()
+                .find(|cycle| cycle.name_range == diagnostic.range)
+            {
+                Some(cycle) if !cycle.wrapper_edits.is_empty() => cycle,
+                _ => continue,
+            };
+
+            let mut changes = std::collections::HashMap::new();
+            changes.insert(uri.clone(), cycle.wrapper_edits.clone());
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Turn `{}` into a custom type", cycle.name),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    document_changes: None,
+                    change_annotations: None,
+                }),
+                command: None,
+                is_preferred: Some(true),
+                disabled: None,
+                data: None,
+            }));
+        }
+        actions
+    }
+
+    /// Fixes for importers left broken after a name was removed from this module's
+    /// exposing list: rewrite them to qualified access, or expose the name again. Offered
+    /// while the cursor is on the module declaration, right where the name was removed.
+    fn generate_unexposed_import_fixes(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        params: &CodeActionParams,
+    ) -> Vec<CodeActionOrCommand> {
+        let uri = &params.text_document.uri;
+        let content = match workspace.get_document_readonly(uri) {
+            Some(document) => document.text().to_string(),
+            None => return Vec::new(),
+        };
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(_) => return Vec::new(),
+        };
+        let tree = match parser.parse(&content) {
+            Ok(Some(tree)) => tree,
+            _ => return Vec::new(),
+        };
+
+        let exposing = match module_exposing(&tree, &content) {
+            Some(exposing) if !exposing.everything => exposing,
+            _ => return Vec::new(),
+        };
+        let declaration_end = exposing.list_range.end;
+        if params.range.start > declaration_end {
+            return Vec::new();
+        }
+
+        // Importers still asking for names this module no longer exposes, by name
+        let mut broken: std::collections::BTreeMap<String, Vec<(Url, Vec<TextEdit>)>> =
+            std::collections::BTreeMap::new();
+        for importer in workspace.module_importers(&exposing.module_name) {
+            if &importer == uri {
+                continue;
+            }
+            let source = match indexed_source(workspace, &importer) {
+                Some(source) => source,
+                None => continue,
+            };
+            let importer_tree = match parser.parse(&source) {
+                Ok(Some(tree)) => tree,
+                _ => continue,
+            };
+
+            for imported in imported_names(&importer_tree, &source, &exposing.module_name) {
+                if exposing.exposes(&imported.name) {
+                    continue;
+                }
+                let constructors = if imported.with_constructors {
+                    type_constructors(&tree, &content, &imported.name)
+                } else {
+                    Vec::new()
+                };
+                let edits = qualify_imported_name(
+                    &importer_tree,
+                    &source,
+                    &exposing.module_name,
+                    &imported.name,
+                    &constructors,
+                );
+                if !edits.is_empty() {
+                    broken
+                        .entry(imported.name)
+                        .or_default()
+                        .push((importer.clone(), edits));
+                }
+            }
+        }
+
+        let mut actions = Vec::new();
+        for (name, importers) in broken {
+            actions.push(self.qualify_importers_action(&exposing, &name, importers));
+            if let Some(edit) = exposing.expose_edit(&name) {
+                let mut changes = std::collections::HashMap::new();
+                changes.insert(uri.clone(), vec![edit]);
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Expose `{}` from {} again", name, exposing.module_name),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: None,
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(changes),
+                        document_changes: None,
+                        change_annotations: None,
+                    }),
+                    command: None,
+                    is_preferred: None,
+                    disabled: None,
+                    data: None,
+                }));
+            }
+        }
+        actions
+    }
 
-        let workspace_edit = WorkspaceEdit {
-            changes: Some(changes),
-            document_changes: None,
-            change_annotations: None,
+    fn qualify_importers_action(
+        &self,
+        exposing: &ModuleExposing,
+        name: &str,
+        importers: Vec<(Url, Vec<TextEdit>)>,
+    ) -> CodeActionOrCommand {
+        let modules = match importers.len() {
+            1 => "1 module".to_string(),
+            count => format!("{} modules", count),
         };
 
-        Some(CodeAction {
-            title: "Organize imports".to_string(),
-            kind: Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: format!(
+                "Use qualified `{}` in {} importing it from {}",
+                name, modules, exposing.module_name
+            ),
+            kind: Some(CodeActionKind::QUICKFIX),
             diagnostics: None,
-            edit: Some(workspace_edit),
+            edit: Some(WorkspaceEdit {
+                changes: Some(importers.into_iter().collect()),
+                document_changes: None,
+                change_annotations: None,
+            }),
             command: None,
-            is_preferred: Some(false),
+            is_preferred: Some(true),
             disabled: None,
             data: None,
         })
     }
 
-    /// Organize imports in file content using tree-sitter AST
-    async fn organize_imports_in_content(&self, content: &str) -> Option<String> {
-        // Parse the document using tree-sitter
+    /// Organize the imports of file content in the configured style
+    fn organize_imports_in_content(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        content: &str,
+    ) -> Option<ImportSection> {
         let mut parser = gren_lsp_core::Parser::new().ok()?;
         let tree = parser.parse(content).ok()??;
-        let language = gren_lsp_core::Parser::language();
-
-        // Query to find import statements
-        let import_query = tree_sitter::Query::new(
-            language,
-            r#"
-            (import_clause) @import
-            "#,
-        )
-        .ok()?;
-
-        let mut cursor = tree_sitter::QueryCursor::new();
-        let source_bytes = content.as_bytes();
-        let matches = cursor.matches(&import_query, tree.root_node(), source_bytes);
-
-        let mut import_statements = Vec::new();
-        let mut import_ranges = Vec::new();
-
-        // Extract all import statements and their ranges
-        for mat in matches {
-            for capture in mat.captures {
-                let node = capture.node;
-                let start_byte = node.start_byte();
-                let end_byte = node.end_byte();
-                let import_text = &content[start_byte..end_byte];
 
-                import_statements.push(import_text.to_string());
-                import_ranges.push((node.start_position(), node.end_position()));
-            }
-        }
-
-        if import_statements.is_empty() {
-            return None;
-        }
-
-        // Sort imports alphabetically by module name
-        import_statements.sort_by(|a, b| {
-            let module_a = self.extract_module_name_from_import(a);
-            let module_b = self.extract_module_name_from_import(b);
-            module_a.cmp(&module_b)
-        });
-
-        // Reconstruct the content with organized imports
-        let mut result = content.to_string();
-
-        // For simplicity, replace the import section with organized imports
-        // In a production implementation, this would be more sophisticated
-        let organized_import_section = import_statements.join("\n");
-
-        // Find the range of all imports to replace
-        if let (Some(first_range), Some(last_range)) = (import_ranges.first(), import_ranges.last())
-        {
-            let start_byte = first_range.0;
-            let end_byte = last_range.1;
-
-            let start_idx = content[..start_byte.row]
-                .lines()
-                .map(|l| l.len() + 1)
-                .sum::<usize>();
-            let end_idx = content[..end_byte.row]
-                .lines()
-                .map(|l| l.len() + 1)
-                .sum::<usize>()
-                + end_byte.column;
-
-            result.replace_range(
-                start_idx..end_idx.min(result.len()),
-                &organized_import_section,
-            );
-        }
-
-        Some(result)
+        organize_imports(&tree, content, self.settings.imports.style, |module_name| {
+            workspace.import_group(module_name)
+        })
     }
 
-    /// Extract module name from import statement
-    fn extract_module_name_from_import(&self, import_statement: &str) -> String {
-        // Simple extraction: "import Module.Name" -> "Module.Name"
-        let trimmed = import_statement.trim();
-        if let Some(after_import) = trimmed.strip_prefix("import ") {
-            if let Some(space_or_end) = after_import.find(|c: char| c.is_whitespace()) {
-                after_import[..space_or_end].to_string()
-            } else {
-                after_import.trim().to_string()
-            }
-        } else {
-            // Return the full string for malformed imports
-            import_statement.to_string()
-        }
+    /// The syntax tree and imports of `content`, and the conflicts between the imports
+    fn import_conflicts_in_content(
+        &self,
+        workspace: &gren_lsp_core::Workspace,
+        content: &str,
+    ) -> Option<(tree_sitter::Tree, Vec<Import>, Vec<ImportConflict>)> {
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(content).ok()??;
+        let imports = imports(&tree, content);
+        let conflicts = import_conflicts(&imports, |module_name| {
+            workspace.module_exports(module_name)
+        });
+        Some((tree, imports, conflicts))
     }
 
     /// Extract module name from file path
@@ -2295,6 +6520,576 @@ impl Handlers {
             None
         }
     }
+
+    /// Extract the module name and its range from a file's module declaration
+    fn extract_declared_module_name(&self, content: &str) -> Option<(String, Range)> {
+        let mut parser = gren_lsp_core::Parser::new().ok()?;
+        let tree = parser.parse(content).ok()??;
+        let language = gren_lsp_core::Parser::language();
+
+        let module_query = tree_sitter::Query::new(
+            language,
+            r#"
+            (module_declaration
+                (upper_case_qid) @module.name)
+            "#,
+        )
+        .ok()?;
+
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let source_bytes = content.as_bytes();
+        let mut matches = cursor.matches(&module_query, tree.root_node(), source_bytes);
+
+        let capture = matches.next()?.captures.first()?.node;
+        let name = capture.utf8_text(source_bytes).ok()?.to_string();
+        Some((name, node_range(&capture, content)))
+    }
+
+    /// Find every place a file refers to a module by name: the module name in its import
+    /// clause and the module prefix of qualified references such as `Module.Name.value`.
+    /// Returned ranges cover just the module name so they can be replaced directly.
+    fn find_module_name_references(&self, content: &str, module_name: &str) -> Vec<Range> {
+        let mut ranges = Vec::new();
+
+        let mut parser = match gren_lsp_core::Parser::new() {
+            Ok(parser) => parser,
+            Err(_) => return ranges,
+        };
+        let tree = match parser.parse(content) {
+            Ok(Some(tree)) => tree,
+            _ => return ranges,
+        };
+        let language = gren_lsp_core::Parser::language();
+
+        let reference_query = match tree_sitter::Query::new(
+            language,
+            r#"
+            (import_clause
+                (upper_case_qid) @import.module)
+
+            (value_qid) @qualified.reference
+            (upper_case_qid) @qualified.reference
+            "#,
+        ) {
+            Ok(query) => query,
+            Err(_) => return ranges,
+        };
+
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let source_bytes = content.as_bytes();
+        let matches = cursor.matches(&reference_query, tree.root_node(), source_bytes);
+        let qualified_prefix = format!("{}.", module_name);
+
+        for mat in matches {
+            for capture in mat.captures {
+                let node = capture.node;
+                let text = match node.utf8_text(source_bytes) {
+                    Ok(text) => text,
+                    Err(_) => continue,
+                };
+                let capture_name = &reference_query.capture_names()[capture.index as usize];
+
+                if capture_name == "import.module" {
+                    if text == module_name {
+                        ranges.push(node_range(&node, content));
+                    }
+                    continue;
+                }
+
+                // Skip the module's own declaration and import clauses, handled above
+                let in_header = std::iter::successors(node.parent(), |n| n.parent())
+                    .any(|n| matches!(n.kind(), "module_declaration" | "import_clause"));
+                if in_header {
+                    continue;
+                }
+
+                // Only rewrite `Module.Name.member`; if the remainder still has a dot then
+                // this is a longer module path that merely starts with the same name
+                if let Some(member) = text.strip_prefix(&qualified_prefix) {
+                    if !member.is_empty() && !member.contains('.') {
+                        let start = node_range(&node, content).start;
+                        let width = module_name.encode_utf16().count() as u32;
+                        ranges.push(Range::new(
+                            start,
+                            Position::new(start.line, start.character + width),
+                        ));
+                    }
+                }
+            }
+        }
+
+        ranges.sort_by(|a, b| {
+            a.start
+                .line
+                .cmp(&b.start.line)
+                .then(a.start.character.cmp(&b.start.character))
+        });
+        ranges.dedup();
+        ranges
+    }
+}
+
+/// Lenses on the module declaration: how many top-level declarations it exposes, opening
+/// the client's export picker when it has one, and exposing what importers use when some
+/// declarations aren't exposed
+fn exports_lenses(
+    tree: &tree_sitter::Tree,
+    source: &str,
+    uri: &Url,
+    manage_exports: bool,
+) -> Vec<CodeLens> {
+    let exposing = match module_exposing(tree, source) {
+        Some(exposing) => exposing,
+        None => return Vec::new(),
+    };
+    let declarations = top_level_declarations(tree, source);
+    if declarations.is_empty() {
+        return Vec::new();
+    }
+    let exposed = declarations
+        .iter()
+        .filter(|declaration| exposing.exposes(&declaration.name))
+        .count();
+
+    let (command, arguments) = if manage_exports {
+        (
+            MANAGE_EXPORTS_COMMAND.to_string(),
+            Some(vec![serde_json::json!(uri)]),
+        )
+    } else {
+        (String::new(), None)
+    };
+    let mut lenses = vec![CodeLens {
+        range: exposing.list_range,
+        command: Some(Command {
+            title: format!(
+                "exposes {} of {} top-level {}",
+                exposed,
+                declarations.len(),
+                if declarations.len() == 1 {
+                    "definition"
+                } else {
+                    "definitions"
+                }
+            ),
+            command,
+            arguments,
+        }),
+        data: None,
+    }];
+
+    if exposed < declarations.len() {
+        let arguments = EditExposingArguments {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            expose: Vec::new(),
+            unexpose: Vec::new(),
+            expose_used: true,
+        };
+        lenses.push(CodeLens {
+            range: exposing.list_range,
+            command: Some(Command {
+                title: "expose all used".to_string(),
+                command: EDIT_EXPOSING_COMMAND.to_string(),
+                arguments: serde_json::to_value(arguments)
+                    .ok()
+                    .map(|value| vec![value]),
+            }),
+            data: None,
+        });
+    }
+    lenses
+}
+
+/// Code lens title for a `Debug.log` tag, e.g. `count: 4 · 3 · 2`, newest value first
+fn runtime_log_title(tag: &str, values: &[&str]) -> String {
+    /// Longest a single value is shown, in characters
+    const MAX_VALUE_LENGTH: usize = 60;
+
+    if values.is_empty() {
+        return format!("{}: nothing logged yet", tag);
+    }
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| {
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            if value.chars().count() > MAX_VALUE_LENGTH {
+                let shortened: String = value.chars().take(MAX_VALUE_LENGTH).collect();
+                format!("{}…", shortened)
+            } else {
+                value
+            }
+        })
+        .collect();
+    format!("{}: {}", tag, values.join(" · "))
+}
+
+/// Whether the client renders markdown hovers. Defaults to true when it doesn't say.
+fn hover_supports_markdown(client_capabilities: Option<&ClientCapabilities>) -> bool {
+    client_capabilities
+        .and_then(|caps| caps.text_document.as_ref())
+        .and_then(|text_doc| text_doc.hover.as_ref())
+        .and_then(|hover| hover.content_format.as_ref())
+        .map(|formats| formats.contains(&MarkupKind::Markdown))
+        .unwrap_or(true)
+}
+
+/// The imports by which the module defining `symbol` already depends on
+/// `importing_module`, from the former to the latter, when importing it there would close
+/// a cycle
+fn import_cycle(
+    workspace: &gren_lsp_core::Workspace,
+    importing_module: Option<&str>,
+    symbol: &gren_lsp_core::Symbol,
+) -> Option<Vec<String>> {
+    let importing_module = importing_module?;
+    let defining_module = workspace.module_name(&symbol.location.uri)?;
+    workspace.import_path(&defining_module, importing_module)
+}
+
+/// Sort key for import candidates for an unresolved `name`, best first: exact matches
+/// before fuzzy ones, then symbols from packages the project depends on directly, from
+/// the project's own modules, and from packages it only depends on through others
+fn import_candidate_rank(
+    symbol: &gren_lsp_core::Symbol,
+    name: &str,
+    direct_dependencies: &[String],
+) -> (bool, u8) {
+    let package = symbol
+        .location
+        .uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| package_name_for_path(&path));
+    let source = match package {
+        Some(package) if direct_dependencies.contains(&package) => 0,
+        Some(_) => 2,
+        None => 1,
+    };
+    (symbol.name != name, source)
+}
+
+/// Hover footer with a symbol's use count, e.g. `Used 17 times`
+fn reference_count_text(count: usize) -> String {
+    match count {
+        0 => "Not used anywhere".to_string(),
+        1 => "Used once".to_string(),
+        count => format!("Used {} times", count),
+    }
+}
+
+/// Markdown link target running `SHOW_REFERENCES_COMMAND` for the symbol at `position`
+fn show_references_link(uri: &Url, position: Position) -> String {
+    let arguments = serde_json::json!([uri, position]);
+    format!(
+        "command:{}?{}",
+        SHOW_REFERENCES_COMMAND,
+        urlencoding::encode(&arguments.to_string())
+    )
+}
+
+/// "Add type annotation" for `value`, its edit left to `code_action_resolve`
+fn add_annotation_action(uri: &Url, value: &UnannotatedValue) -> CodeAction {
+    CodeAction {
+        title: format!("Add type annotation to `{}`", value.name),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        data: Some(serde_json::json!({
+            "addAnnotation": { "uri": uri, "name": value.name }
+        })),
+        ..Default::default()
+    }
+}
+
+/// Whether the client asks for a code action's edit once it's picked
+fn resolves_code_action_edits(client_capabilities: Option<&ClientCapabilities>) -> bool {
+    let code_action = client_capabilities
+        .and_then(|caps| caps.text_document.as_ref())
+        .and_then(|text_document| text_document.code_action.as_ref());
+    code_action.is_some_and(|code_action| {
+        code_action.data_support == Some(true)
+            && code_action
+                .resolve_support
+                .as_ref()
+                .is_some_and(|support| support.properties.iter().any(|property| property == "edit"))
+    })
+}
+
+fn request_failed(message: String) -> tower_lsp::jsonrpc::Error {
+    tower_lsp::jsonrpc::Error {
+        code: tower_lsp::jsonrpc::ErrorCode::ServerError(REQUEST_FAILED),
+        message: message.into(),
+        data: None,
+    }
+}
+
+/// Whether a code action request's `only` filter lets through actions of `kind`. A
+/// requested kind covers itself and its sub-kinds, so `source` covers `source.x`.
+fn kind_requested(only: Option<&[CodeActionKind]>, kind: &CodeActionKind) -> bool {
+    only.map_or(true, |only| {
+        only.iter().any(|requested| {
+            kind.as_str() == requested.as_str()
+                || kind
+                    .as_str()
+                    .strip_prefix(requested.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    })
+}
+
+/// Whether `name` is a legal module name, e.g. `Data.List`
+fn is_valid_module_name(name: &str) -> bool {
+    name.split('.').all(|part| {
+        part.chars().next().is_some_and(|c| c.is_uppercase())
+            && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// A document symbol's range: the declaration's full range when it's known and contains
+/// the name, since clients reject symbols whose selection range falls outside their range
+fn enclosing_range(full_range: Option<&Range>, name_range: Range) -> Range {
+    match full_range {
+        Some(full) if full.start <= name_range.start && name_range.end <= full.end => *full,
+        _ => name_range,
+    }
+}
+
+/// Add the other half of every definition/annotation pair that `references` touches, so
+/// renaming `foo =` also renames `foo :` and vice versa. The pairing comes from the syntax
+/// tree of each referenced file, which may not be open in the editor.
+fn paired_function_names(
+    workspace: &Workspace,
+    name: &str,
+    mut references: Vec<Location>,
+) -> Vec<Location> {
+    let mut uris: Vec<Url> = references
+        .iter()
+        .map(|location| location.uri.clone())
+        .collect();
+    uris.sort();
+    uris.dedup();
+
+    for uri in uris {
+        let names = indexed_source(workspace, &uri).and_then(|source| {
+            let mut parser = gren_lsp_core::Parser::new().ok()?;
+            let tree = parser.parse(&source).ok()??;
+            function_name_ranges(&tree, &source).remove(name)
+        });
+        let names = match names {
+            Some(names) => names,
+            None => continue,
+        };
+
+        let touched = references
+            .iter()
+            .any(|location| location.uri == uri && names.contains(&location.range));
+        if !touched {
+            continue;
+        }
+        for range in names.ranges() {
+            if !references
+                .iter()
+                .any(|location| location.uri == uri && location.range == range)
+            {
+                references.push(Location::new(uri.clone(), range));
+            }
+        }
+    }
+    references
+}
+
+/// The hover line for a symbol documented as `@deprecated`
+fn deprecation_note(message: &str, markdown: bool) -> String {
+    match (markdown, message.is_empty()) {
+        (true, true) => "**Deprecated**".to_string(),
+        (true, false) => format!("**Deprecated**: {}", message),
+        (false, true) => "Deprecated".to_string(),
+        (false, false) => format!("Deprecated: {}", message),
+    }
+}
+
+/// Hover note for symbols of a module indexed from its signatures only, whose bodies
+/// weren't analyzed
+fn signatures_only_note(markdown: bool) -> String {
+    let note = "Indexed from signatures only (`index.signatures_only`), uses inside its module aren't found";
+    if markdown {
+        format!("*{}*", note)
+    } else {
+        note.replace('`', "")
+    }
+}
+
+/// Strike a deprecated symbol through in the completion list and say what replaces it
+fn mark_deprecated(mut item: CompletionItem, symbol: &GrenSymbol) -> CompletionItem {
+    let message = match symbol.deprecation() {
+        Some(message) => message,
+        None => return item,
+    };
+
+    item.tags = Some(vec![CompletionItemTag::DEPRECATED]);
+    // For clients predating completion item tags
+    item.deprecated = Some(true);
+    item.label_details = Some(CompletionItemLabelDetails {
+        detail: None,
+        description: Some(if message.is_empty() {
+            "deprecated".to_string()
+        } else {
+            format!("deprecated: {}", message)
+        }),
+    });
+    item
+}
+
+/// The fix for the diagnostic of an unformatted file formats every project file, as the
+/// check reports the whole project at once
+fn generate_formatting_fixes(params: &CodeActionParams) -> Vec<CodeActionOrCommand> {
+    let diagnostics: Vec<Diagnostic> = params
+        .context
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            diagnostic.code == Some(NumberOrString::String(UNFORMATTED.to_string()))
+        })
+        .cloned()
+        .collect();
+    if diagnostics.is_empty() {
+        return Vec::new();
+    }
+
+    let title = "Format all files in the workspace".to_string();
+    vec![CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.clone(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(diagnostics),
+        command: Some(Command {
+            title,
+            command: FORMAT_WORKSPACE_COMMAND.to_string(),
+            arguments: None,
+        }),
+        ..Default::default()
+    })]
+}
+
+/// The call hierarchy item for the top-level value `name` defined in the file `uri`
+fn value_call_item(workspace: &Workspace, uri: &Url, name: &str) -> Option<CallHierarchyItem> {
+    let module = workspace.module_name(uri)?;
+    let symbols = workspace.get_file_symbols(uri).ok()?;
+    let symbol = symbols
+        .iter()
+        .find(|symbol| symbol.kind == SymbolKind::FUNCTION && symbol.name == name)?;
+    Some(call_item(symbol, &module))
+}
+
+/// The type hierarchy item for the custom type or type alias `name` defined in the file
+/// `uri`
+fn type_symbol_item(workspace: &Workspace, uri: &Url, name: &str) -> Option<TypeHierarchyItem> {
+    let module = workspace.module_name(uri)?;
+    let symbols = workspace.get_file_symbols(uri).ok()?;
+    let symbol = symbols
+        .iter()
+        .find(|symbol| symbol.kind == SymbolKind::CLASS && symbol.name == name)?;
+    Some(type_item(symbol, &module))
+}
+
+/// Add renaming `old_name` to `new_name` in the `exposed-modules` of the `gren.json` of
+/// the project holding `module_uri` to a module rename, so a package keeps exposing the
+/// module
+fn add_manifest_rename(
+    edit: &mut WorkspaceEdit,
+    workspace: &Workspace,
+    module_uri: &Url,
+    old_name: &str,
+    new_name: &str,
+) {
+    let path = match workspace.project_root(module_uri) {
+        Some(root) => root.join("gren.json"),
+        None => return,
+    };
+    let uri = match Url::from_file_path(&path) {
+        Ok(uri) => uri,
+        Err(_) => return,
+    };
+    let manifest = match indexed_source(workspace, &uri) {
+        Some(manifest) => manifest,
+        None => {
+            warn!(
+                "Couldn't read {}, its exposed-modules may still list '{}'",
+                path.display(),
+                old_name
+            );
+            return;
+        }
+    };
+
+    let entries = exposed_module_entries(&manifest, old_name);
+    if entries.is_empty() {
+        return;
+    }
+    info!(
+        "Renaming exposed module '{}' in {}",
+        old_name,
+        path.display()
+    );
+    let manifest_edit = TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+        edits: entries
+            .into_iter()
+            .rev()
+            .map(|entry| {
+                let range = Range::new(
+                    offset_to_position(&manifest, entry.start),
+                    offset_to_position(&manifest, entry.end),
+                );
+                OneOf::Left(TextEdit::new(range, new_name.to_string()))
+            })
+            .collect(),
+    };
+    match &mut edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.push(manifest_edit),
+        _ => edit.document_changes = Some(DocumentChanges::Edits(vec![manifest_edit])),
+    }
+}
+
+/// `symbol`, and when it's an unannotated top-level value of an open document, with the
+/// type inferred for it as its signature
+fn with_inferred_signature(workspace: &Workspace, symbol: &GrenSymbol) -> GrenSymbol {
+    let mut symbol = symbol.clone();
+    if symbol.kind == SymbolKind::FUNCTION && symbol.type_signature.is_none() {
+        symbol.type_signature = workspace
+            .module_types(&symbol.location.uri)
+            .and_then(|types| types.top_level(&symbol.name).map(str::to_string));
+    }
+    symbol
+}
+
+/// Current text of an indexed file: the cached document when it's loaded, otherwise the
+/// file on disk, since the module graph also covers files evicted from the cache
+fn indexed_source(workspace: &Workspace, uri: &Url) -> Option<String> {
+    if let Some(document) = workspace.get_document_readonly(uri) {
+        return Some(document.text().to_string());
+    }
+
+    let path = uri.to_file_path().ok()?;
+    match read_source(&path) {
+        Ok(content) => Some(content),
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn metrics_report(uri: Url, metrics: &ModuleMetrics) -> ModuleMetricsReport {
+    let longest_function = metrics.longest_function();
+    ModuleMetricsReport {
+        uri,
+        module_name: metrics.module_name.clone(),
+        lines_of_code: metrics.lines_of_code,
+        exported_symbols: metrics.exported_symbols,
+        max_function_length: longest_function.map_or(0, |function| function.lines),
+        longest_function: longest_function.map(|function| function.name.clone()),
+        max_case_depth: metrics.max_case_depth(),
+        fan_in: metrics.fan_in,
+        fan_out: metrics.fan_out(),
+    }
 }
 
 #[cfg(test)]
@@ -2333,24 +7128,147 @@ mod tests {
         assert!(handlers.is_valid_gren_identifier("my_function"));
         assert!(handlers.is_valid_gren_identifier("a"));
 
-        // Invalid identifiers
-        assert!(!handlers.is_valid_gren_identifier(""));
-        assert!(!handlers.is_valid_gren_identifier("123invalid"));
-        assert!(!handlers.is_valid_gren_identifier("my-function"));
-        assert!(!handlers.is_valid_gren_identifier("my function"));
-        assert!(!handlers.is_valid_gren_identifier("my@function"));
+        // Invalid identifiers
+        assert!(!handlers.is_valid_gren_identifier(""));
+        assert!(!handlers.is_valid_gren_identifier("123invalid"));
+        assert!(!handlers.is_valid_gren_identifier("my-function"));
+        assert!(!handlers.is_valid_gren_identifier("my function"));
+        assert!(!handlers.is_valid_gren_identifier("my@function"));
+
+        // Gren keywords should be invalid
+        assert!(!handlers.is_valid_gren_identifier("if"));
+        assert!(!handlers.is_valid_gren_identifier("then"));
+        assert!(!handlers.is_valid_gren_identifier("else"));
+        assert!(!handlers.is_valid_gren_identifier("case"));
+        assert!(!handlers.is_valid_gren_identifier("of"));
+        assert!(!handlers.is_valid_gren_identifier("let"));
+        assert!(!handlers.is_valid_gren_identifier("in"));
+        assert!(!handlers.is_valid_gren_identifier("type"));
+        assert!(!handlers.is_valid_gren_identifier("module"));
+        assert!(!handlers.is_valid_gren_identifier("import"));
+    }
+
+    #[test]
+    fn test_kind_requested() {
+        assert!(kind_requested(None, &SOURCE_SORT_DECLARATIONS));
+        assert!(kind_requested(
+            Some(&[CodeActionKind::SOURCE]),
+            &SOURCE_SORT_DECLARATIONS
+        ));
+        assert!(kind_requested(
+            Some(&[SOURCE_MOVE_DECLARATION]),
+            &SOURCE_MOVE_DECLARATION
+        ));
+        assert!(!kind_requested(
+            Some(&[CodeActionKind::QUICKFIX, SOURCE_MOVE_DECLARATION]),
+            &SOURCE_SORT_DECLARATIONS
+        ));
+        assert!(!kind_requested(
+            Some(&[CodeActionKind::new("source.sort")]),
+            &SOURCE_SORT_DECLARATIONS
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_annotation_action() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let value = UnannotatedValue {
+            name: "double".to_string(),
+            line: 4,
+        };
+        let action = add_annotation_action(&uri, &value);
+        assert_eq!(action.title, "Add type annotation to `double`");
+        assert_eq!(action.edit, None);
+
+        let mut capabilities = ClientCapabilities::default();
+        assert!(!resolves_code_action_edits(Some(&capabilities)));
+        capabilities.text_document = Some(TextDocumentClientCapabilities {
+            code_action: Some(CodeActionClientCapabilities {
+                data_support: Some(true),
+                resolve_support: Some(CodeActionCapabilityResolveSupport {
+                    properties: vec!["edit".to_string()],
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert!(resolves_code_action_edits(Some(&capabilities)));
+
+        // Actions offered with their edit come back as they are
+        let handlers = create_test_handlers();
+        let organize = CodeAction {
+            title: "Organize imports".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            handlers
+                .code_action_resolve(organize.clone())
+                .await
+                .unwrap(),
+            organize
+        );
+    }
+
+    #[test]
+    fn test_import_candidates_ranked() {
+        let symbol_at = |path: &str, name: &str| gren_lsp_core::Symbol {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            location: Location::new(
+                Url::from_file_path(path).unwrap(),
+                Range::new(Position::new(0, 0), Position::new(0, 1)),
+            ),
+            container_name: None,
+            type_signature: None,
+            documentation: None,
+        };
+        let direct = vec!["gren-lang/core".to_string()];
+        let mut candidates = vec![
+            symbol_at("/project/.gren/packages/gren-lang/url/src/Url.gren", "map"),
+            symbol_at("/project/src/Utils.gren", "mapper"),
+            symbol_at("/project/src/Utils.gren", "map"),
+            symbol_at(
+                "/project/.gren/packages/gren-lang/core/src/Array.gren",
+                "map",
+            ),
+        ];
+        candidates.sort_by_key(|symbol| import_candidate_rank(symbol, "map", &direct));
+
+        let order: Vec<(&str, &str)> = candidates
+            .iter()
+            .map(|symbol| {
+                let path = symbol.location.uri.path();
+                (symbol.name.as_str(), path.rsplit('/').next().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("map", "Array.gren"),
+                ("map", "Utils.gren"),
+                ("map", "Url.gren"),
+                ("mapper", "Utils.gren"),
+            ]
+        );
+    }
 
-        // Gren keywords should be invalid
-        assert!(!handlers.is_valid_gren_identifier("if"));
-        assert!(!handlers.is_valid_gren_identifier("then"));
-        assert!(!handlers.is_valid_gren_identifier("else"));
-        assert!(!handlers.is_valid_gren_identifier("case"));
-        assert!(!handlers.is_valid_gren_identifier("of"));
-        assert!(!handlers.is_valid_gren_identifier("let"));
-        assert!(!handlers.is_valid_gren_identifier("in"));
-        assert!(!handlers.is_valid_gren_identifier("type"));
-        assert!(!handlers.is_valid_gren_identifier("module"));
-        assert!(!handlers.is_valid_gren_identifier("import"));
+    #[test]
+    fn test_reference_count_footer() {
+        assert_eq!(reference_count_text(0), "Not used anywhere");
+        assert_eq!(reference_count_text(1), "Used once");
+        assert_eq!(reference_count_text(17), "Used 17 times");
+
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let link = show_references_link(&uri, Position::new(3, 4));
+        let arguments = link
+            .strip_prefix("command:grenLsp.showReferences?")
+            .unwrap();
+        assert!(!arguments.contains(' ') && !arguments.contains(')'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&urlencoding::decode(arguments).unwrap())
+                .unwrap(),
+            serde_json::json!(["file:///project/src/Main.gren", { "line": 3, "character": 4 }])
+        );
     }
 
     #[test]
@@ -3468,41 +8386,6 @@ testFunction = 42"#;
         assert_eq!(handlers.extract_unresolved_symbol(&diagnostic3), None);
     }
 
-    #[test]
-    fn test_extract_module_name_from_import() {
-        let handlers = create_test_handlers();
-
-        // Test simple import
-        assert_eq!(
-            handlers.extract_module_name_from_import("import Dict"),
-            "Dict"
-        );
-
-        // Test qualified import
-        assert_eq!(
-            handlers.extract_module_name_from_import("import Data.List"),
-            "Data.List"
-        );
-
-        // Test import with exposing
-        assert_eq!(
-            handlers.extract_module_name_from_import("import Array exposing (Array)"),
-            "Array"
-        );
-
-        // Test import with alias
-        assert_eq!(
-            handlers.extract_module_name_from_import("import Json.Decode as Decode"),
-            "Json.Decode"
-        );
-
-        // Test malformed import
-        assert_eq!(
-            handlers.extract_module_name_from_import("malformed import statement"),
-            "malformed import statement"
-        );
-    }
-
     #[test]
     fn test_extract_module_name_from_path() {
         let handlers = create_test_handlers();
@@ -3540,6 +8423,221 @@ testFunction = 42"#;
         assert_eq!(handlers.extract_module_name_from_path(&uri5), None);
     }
 
+    #[tokio::test]
+    async fn test_module_rename_for_file_rename() {
+        let workspace = Arc::new(RwLock::new(create_test_workspace()));
+        let handlers = Handlers::new(workspace.clone());
+
+        // File was moved to src/Text/Helpers.gren but still declares the old module
+        let renamed_uri = Url::parse("file:///project/src/Text/Helpers.gren").unwrap();
+        let renamed_content = r#"module Helpers exposing (shout)
+
+shout : String -> String
+shout text = text
+"#;
+        let importer_uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let importer_content = r#"module Main exposing (main)
+
+import Helpers
+import Helpers.Extra
+
+main = Helpers.shout (Helpers.Extra.whisper "hi")
+"#;
+
+        {
+            let mut ws = workspace.write().await;
+            ws.open_document(create_test_document(&renamed_uri, renamed_content))
+                .unwrap();
+            ws.open_document(create_test_document(&importer_uri, importer_content))
+                .unwrap();
+        }
+
+        let sync = handlers
+            .module_rename_for_file_rename(&renamed_uri)
+            .await
+            .unwrap()
+            .expect("Mismatched module declaration should produce an edit");
+
+        assert_eq!(sync.old_module_name, "Helpers");
+        assert_eq!(sync.new_module_name, "Text.Helpers");
+        assert_eq!(sync.importing_files, 1);
+
+        let Some(DocumentChanges::Edits(edits)) = sync.edit.document_changes else {
+            panic!("Expected document edits");
+        };
+
+        let importer_edits = edits
+            .iter()
+            .find(|e| e.text_document.uri == importer_uri)
+            .expect("Importing file should be edited");
+        // `import Helpers` and `Helpers.shout`, but not `Helpers.Extra`
+        assert_eq!(importer_edits.edits.len(), 2);
+
+        // A file whose declaration already matches its path needs nothing
+        assert!(handlers
+            .module_rename_for_file_rename(&importer_uri)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_module_rename_for_file_rename_edits_closed_importers() {
+        let workspace = Arc::new(RwLock::new(create_test_workspace()));
+        let handlers = Handlers::new(workspace.clone());
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("Text")).unwrap();
+
+        let renamed_path = src.join("Text/Helpers.gren");
+        let renamed_content = "module Helpers exposing (shout)\n\nshout text = text\n";
+        let importer_path = src.join("Main.gren");
+        let importer_content =
+            "module Main exposing (main)\n\nimport Helpers\n\nmain = Helpers.shout \"hi\"\n";
+        std::fs::write(&renamed_path, renamed_content).unwrap();
+        std::fs::write(&importer_path, importer_content).unwrap();
+        let renamed_uri = Url::from_file_path(&renamed_path).unwrap();
+        let importer_uri = Url::from_file_path(&importer_path).unwrap();
+
+        {
+            let mut ws = workspace.write().await;
+            ws.open_document(create_test_document(&renamed_uri, renamed_content))
+                .unwrap();
+            ws.open_document(create_test_document(&importer_uri, importer_content))
+                .unwrap();
+            // Indexed, but no longer held as an open document
+            ws.close_document(importer_uri.clone()).unwrap();
+        }
+
+        let sync = handlers
+            .module_rename_for_file_rename(&renamed_uri)
+            .await
+            .unwrap()
+            .expect("Mismatched module declaration should produce an edit");
+        assert_eq!(sync.importing_files, 1);
+
+        let Some(DocumentChanges::Edits(edits)) = sync.edit.document_changes else {
+            panic!("Expected document edits");
+        };
+        let importer_edits = edits
+            .iter()
+            .find(|e| e.text_document.uri == importer_uri)
+            .expect("Closed importing file should be edited");
+        // `import Helpers` and `Helpers.shout`
+        assert_eq!(importer_edits.edits.len(), 2);
+    }
+
+    #[test]
+    fn test_type_defining_file() {
+        let handlers = create_test_handlers();
+        let mut workspace = create_test_workspace();
+        let alpha = Url::parse("file:///project/src/Alpha.gren").unwrap();
+        let beta = Url::parse("file:///project/src/Beta.gren").unwrap();
+        let main = Url::parse("file:///project/src/Main.gren").unwrap();
+        workspace
+            .open_document(create_test_document(
+                &alpha,
+                "module Alpha exposing (..)\n\ntype Msg\n    = Go\n    | Stop\n",
+            ))
+            .unwrap();
+        workspace
+            .open_document(create_test_document(
+                &beta,
+                "module Beta exposing (..)\n\ntype Msg\n    = Up\n    | Down\n",
+            ))
+            .unwrap();
+
+        let content =
+            "module Main exposing (..)\n\nimport Alpha as A\nimport Beta exposing (Msg(..))\n";
+        assert_eq!(
+            handlers.type_defining_file(&workspace, &main, content, "A.Msg"),
+            Some(alpha.clone())
+        );
+        // Both imports declare a `Msg`
+        assert_eq!(
+            handlers.type_defining_file(&workspace, &main, content, "Msg"),
+            None
+        );
+
+        let content = "module Main exposing (..)\n\nimport Beta exposing (Msg(..))\n";
+        assert_eq!(
+            handlers.type_defining_file(&workspace, &main, content, "Msg"),
+            Some(beta)
+        );
+        assert_eq!(
+            handlers.type_defining_file(&workspace, &alpha, "", "Msg"),
+            Some(alpha)
+        );
+    }
+
+    #[test]
+    fn test_completion_source() {
+        let handlers = create_test_handlers();
+
+        assert_eq!(
+            handlers.completion_source("import Json.Dec", None, None),
+            Some(CompletionSource::ImportModule)
+        );
+        assert_eq!(
+            handlers.completion_source(
+                "    Json.Decode.fie",
+                Some(vec!["Json".to_string(), "Decode".to_string()]),
+                Some(".")
+            ),
+            Some(CompletionSource::ModuleMember(vec![
+                "Json".to_string(),
+                "Decode".to_string()
+            ]))
+        );
+        assert_eq!(
+            handlers.completion_source("    model.us", None, None),
+            Some(CompletionSource::RecordField)
+        );
+        assert_eq!(
+            handlers.completion_source("    |> Array.map .", None, Some(".")),
+            Some(CompletionSource::RecordField)
+        );
+        assert_eq!(
+            handlers.completion_source("import Json.Decode exposing (..", None, Some(".")),
+            None
+        );
+        assert_eq!(
+            handlers.completion_source("    x = 1.", None, Some(".")),
+            None
+        );
+        assert_eq!(
+            handlers.completion_source("    Array.map to", None, None),
+            Some(CompletionSource::General)
+        );
+    }
+
+    #[test]
+    fn test_client_supports_trigger_suggest_command() {
+        let handlers = create_test_handlers();
+
+        let without_commands = ClientCapabilities::default();
+        assert!(!handlers.client_supports_command(&without_commands, TRIGGER_SUGGEST_COMMAND));
+
+        let with_commands = ClientCapabilities {
+            experimental: Some(serde_json::json!({
+                "commands": { "commands": [TRIGGER_SUGGEST_COMMAND] }
+            })),
+            ..Default::default()
+        };
+        assert!(handlers.client_supports_command(&with_commands, TRIGGER_SUGGEST_COMMAND));
+
+        let item = handlers.module_segment_completion("Json", "Json.Decode", true);
+        assert_eq!(item.insert_text.as_deref(), Some("Json."));
+        assert_eq!(
+            item.command.map(|c| c.command),
+            Some(TRIGGER_SUGGEST_COMMAND.to_string())
+        );
+
+        let item = handlers.module_segment_completion("Json", "Json.Decode", false);
+        assert_eq!(item.insert_text.as_deref(), Some("Json"));
+        assert!(item.command.is_none());
+    }
+
     #[tokio::test]
     async fn test_code_action_import_suggestions() {
         let workspace = Arc::new(RwLock::new(create_test_workspace()));
@@ -3686,16 +8784,71 @@ import Dict exposing (Dict)
 main = 42
 "#;
 
-        let result = handlers.organize_imports_in_content(content).await;
+        let workspace = handlers.workspace.read().await;
+        let section = handlers
+            .organize_imports_in_content(&workspace, content)
+            .unwrap();
 
-        // The organize_imports_in_content should process the imports
-        // For now, we just test that it doesn't crash
-        // In a full implementation, this would reorder the imports alphabetically
-        if let Some(organized) = result {
-            assert!(!organized.is_empty());
-            assert!(organized.contains("import"));
-            assert!(organized.contains("main = 42"));
-        }
+        assert!(!section.is_organized());
+        assert_eq!(
+            section.organized,
+            "import Array\nimport Dict exposing (Dict)\nimport Json.Decode"
+        );
+        assert_eq!(section.range.start.line, 2);
+        assert_eq!(section.range.end.line, 4);
+    }
+
+    #[tokio::test]
+    async fn test_import_style_lint_quick_fix() {
+        let workspace = Arc::new(RwLock::new(create_test_workspace()));
+        let mut settings = ServerSettings::default();
+        settings.imports.style = gren_lsp_core::ImportStyle::Grouped;
+        settings.imports.lint = true;
+        let handlers = Handlers::with_settings(workspace.clone(), settings.clone());
+
+        let uri = Url::parse("file:///test/Main.gren").unwrap();
+        let content = r#"module Main exposing (..)
+
+import Json.Decode
+import Array
+
+main = 42
+"#;
+        let diagnostics = {
+            let mut workspace = workspace.write().await;
+            workspace
+                .open_document(create_test_document(&uri, content))
+                .unwrap();
+            workspace.set_import_lint(settings.imports.lint_style());
+            workspace.get_import_style_diagnostics(&uri)
+        };
+        assert_eq!(diagnostics.len(), 1);
+
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            range: diagnostics[0].range,
+            context: CodeActionContext {
+                diagnostics: diagnostics.clone(),
+                only: Some(vec![CodeActionKind::QUICKFIX]),
+                trigger_kind: None,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        let actions = handlers.code_action(params).await.unwrap().unwrap();
+        let fix = actions
+            .iter()
+            .find_map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) if action.title == "Reorder imports" => {
+                    Some(action)
+                }
+                _ => None,
+            })
+            .unwrap();
+
+        let edits = &fix.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits[0].new_text, "import Array\nimport Json.Decode");
     }
 
     #[test]
@@ -3747,7 +8900,7 @@ main = 42
             data: None,
         };
 
-        let action = handlers.create_import_action(&symbol, &target_uri, &diagnostic);
+        let action = handlers.create_import_action(&symbol, &target_uri, &diagnostic, true);
 
         assert!(action.is_some());
 
@@ -3775,4 +8928,332 @@ main = 42
             }
         }
     }
+
+    #[test]
+    fn test_cyclic_import_action_is_disabled() {
+        let handlers = create_test_handlers();
+        let symbol = gren_lsp_core::Symbol {
+            name: "formatDate".to_string(),
+            kind: SymbolKind::FUNCTION,
+            location: Location {
+                uri: Url::parse("file:///src/Page.gren").unwrap(),
+                range: Range::default(),
+            },
+            container_name: None,
+            type_signature: None,
+            documentation: None,
+        };
+        let diagnostic = Diagnostic {
+            message: "Variable `formatDate` not found".to_string(),
+            ..Default::default()
+        };
+        let cycle = vec!["Page".to_string(), "View".to_string(), "Main".to_string()];
+
+        let action = handlers
+            .create_cyclic_import_action(&symbol, &cycle, &diagnostic)
+            .unwrap();
+        assert_eq!(action.title, "Import formatDate from Page");
+        assert!(action.edit.is_none());
+        assert_eq!(
+            action.disabled.unwrap().reason,
+            "Importing Page would create an import cycle (Main → Page → View → Main). \
+             Move `formatDate` to a module Main can import instead"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_report_explains_missing_features() {
+        use crate::lsp_ext::FeatureStatus;
+
+        let workspace = Arc::new(RwLock::new(create_test_workspace()));
+        let mut settings = ServerSettings::default();
+        settings.hover.docs_links = false;
+        let handlers = Handlers::with_settings(workspace, settings);
+
+        let report = handlers.capabilities_report().await.unwrap();
+        let status_of = |name: &str| {
+            report
+                .features
+                .iter()
+                .find(|feature| feature.feature == name)
+                .unwrap()
+                .clone()
+        };
+
+        // Without a workspace root there is no compiler, so only syntax hints are reported
+        let diagnostics = status_of("diagnostics");
+        assert_eq!(diagnostics.status, FeatureStatus::Degraded);
+        assert!(diagnostics
+            .reason
+            .unwrap()
+            .contains("No workspace folder is open"));
+
+        assert_eq!(status_of("completion").status, FeatureStatus::Active);
+        assert_eq!(status_of("completion").reason, None);
+
+        let docs_links = status_of("hoverDocsLinks");
+        assert_eq!(docs_links.status, FeatureStatus::Disabled);
+        assert!(docs_links.reason.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_todos_grouped_by_file_and_updated_on_change() {
+        use gren_lsp_core::{InMemorySymbolStore, TodoMarker};
+
+        let mut workspace = create_test_workspace();
+        workspace
+            .set_symbol_store(Arc::new(InMemorySymbolStore::new()))
+            .unwrap();
+
+        let main_uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let utils_uri = Url::parse("file:///project/src/Utils.gren").unwrap();
+        workspace
+            .open_document(create_test_document(
+                &main_uri,
+                "module Main exposing (..)\n\n-- TODO: load settings\nmain = 42\n\n-- FIXME: overflows\nlimit = 99\n",
+            ))
+            .unwrap();
+        workspace
+            .open_document(create_test_document(
+                &utils_uri,
+                "module Utils exposing (..)\n\n-- XXX\nhelper x = x\n",
+            ))
+            .unwrap();
+
+        let workspace = Arc::new(RwLock::new(workspace));
+        let handlers = Handlers::new(workspace.clone());
+
+        let files = handlers.todos().await.unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].uri, main_uri);
+        let markers: Vec<_> = files[0]
+            .todos
+            .iter()
+            .map(|todo| (todo.marker, todo.text.as_str(), todo.range.start.line))
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                (TodoMarker::Todo, "load settings", 2),
+                (TodoMarker::Fixme, "overflows", 5),
+            ]
+        );
+
+        // Editing a file replaces its markers without touching other files
+        workspace
+            .write()
+            .await
+            .update_document(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: main_uri.clone(),
+                    version: 2,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: "module Main exposing (..)\n\nmain = 42\n".to_string(),
+                }],
+            })
+            .unwrap();
+
+        let files = handlers.todos().await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].uri, utils_uri);
+        assert_eq!(files[0].todos[0].marker, TodoMarker::Xxx);
+    }
+
+    #[tokio::test]
+    async fn test_module_rename_plan_uses_module_graph() {
+        use gren_lsp_core::InMemorySymbolStore;
+
+        let mut workspace = create_test_workspace();
+        workspace
+            .set_symbol_store(Arc::new(InMemorySymbolStore::new()))
+            .unwrap();
+
+        let utils_uri = Url::parse("file:///project/src/Utils.gren").unwrap();
+        let main_uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let other_uri = Url::parse("file:///project/src/Other.gren").unwrap();
+        for (uri, text) in [
+            (&utils_uri, "module Utils exposing (..)\n\nhelper x = x\n"),
+            (
+                &main_uri,
+                "module Main exposing (..)\n\nimport Utils\n\nmain = Utils.helper 42\n",
+            ),
+            (&other_uri, "module Other exposing (..)\n\nother = 1\n"),
+        ] {
+            workspace
+                .open_document(create_test_document(uri, text))
+                .unwrap();
+        }
+
+        let handlers = Handlers::new(Arc::new(RwLock::new(workspace)));
+        let plan = handlers
+            .module_rename_plan(&utils_uri)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(plan.module_name, "Utils");
+        assert_eq!(plan.declaration.range.start, Position::new(0, 7));
+        assert_eq!(plan.importers, vec![main_uri.clone()]);
+
+        let occurrences = handlers.module_references_in_file(&main_uri, "Utils").await;
+        let lines: Vec<_> = occurrences.iter().map(|range| range.start.line).collect();
+        assert!(lines.contains(&2) && lines.contains(&4));
+    }
+
+    #[tokio::test]
+    async fn test_document_symbols_merge_annotation_and_definition() {
+        use gren_lsp_core::InMemorySymbolStore;
+
+        let mut workspace = create_test_workspace();
+        workspace
+            .set_symbol_store(Arc::new(InMemorySymbolStore::new()))
+            .unwrap();
+
+        let uri = Url::parse("file:///project/src/Shapes.gren").unwrap();
+        let content = r#"module Shapes exposing (..)
+
+type Shape
+    = Circle Float
+
+area : Shape -> Float
+area shape =
+    0
+"#;
+        workspace
+            .open_document(create_test_document(&uri, content))
+            .unwrap();
+
+        let handlers = Handlers::new(Arc::new(RwLock::new(workspace)));
+        let response = handlers
+            .document_symbols(DocumentSymbolParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap();
+        let symbols = match response {
+            Some(DocumentSymbolResponse::Nested(symbols)) => symbols,
+            other => panic!("Expected nested document symbols, got {:?}", other),
+        };
+
+        let areas: Vec<_> = symbols
+            .iter()
+            .filter(|symbol| symbol.name == "area")
+            .collect();
+        assert_eq!(areas.len(), 1, "annotation and definition are one symbol");
+        assert_eq!(
+            areas[0].range,
+            Range::new(Position::new(5, 0), Position::new(7, 5))
+        );
+        assert_eq!(areas[0].selection_range.start, Position::new(6, 0));
+        assert_eq!(areas[0].detail.as_deref(), Some("Shape -> Float"));
+
+        let shape = symbols
+            .iter()
+            .find(|symbol| symbol.name == "Shape")
+            .unwrap();
+        assert_eq!(shape.range.start, Position::new(2, 0));
+        assert_eq!(shape.selection_range.start, Position::new(2, 5));
+    }
+
+    #[test]
+    fn test_rename_pairs_annotation_with_definition() {
+        let mut workspace = create_test_workspace();
+        let uri = Url::parse("file:///project/src/Shapes.gren").unwrap();
+        let content = "module Shapes exposing (area)\n\narea : Float -> Float\narea r =\n    r\n";
+        workspace
+            .open_document(create_test_document(&uri, content))
+            .unwrap();
+
+        let definition = Location::new(
+            uri.clone(),
+            Range::new(Position::new(3, 0), Position::new(3, 4)),
+        );
+        let references = paired_function_names(&workspace, "area", vec![definition.clone()]);
+        assert_eq!(references.len(), 2);
+        assert!(references.contains(&definition));
+        assert!(references.contains(&Location::new(
+            uri.clone(),
+            Range::new(Position::new(2, 0), Position::new(2, 4)),
+        )));
+
+        // Renaming from the annotation brings the definition along
+        let annotation = references[1].clone();
+        let references = paired_function_names(&workspace, "area", vec![annotation]);
+        assert!(references.contains(&definition));
+    }
+
+    #[test]
+    fn test_runtime_log_title() {
+        assert_eq!(runtime_log_title("count", &[]), "count: nothing logged yet");
+        assert_eq!(
+            runtime_log_title("model", &["{ count = 2\n}", "{ count = 1 }"]),
+            "model: { count = 2 } · { count = 1 }"
+        );
+
+        let long = "x".repeat(100);
+        let title = runtime_log_title("long", &[&long]);
+        assert_eq!(title, format!("long: {}…", "x".repeat(60)));
+    }
+
+    #[test]
+    fn test_exports_lenses() {
+        let source = "module Shapes exposing (area)\n\narea s = 1\n\nperimeter s = 2\n";
+        let tree = gren_lsp_core::Parser::new()
+            .unwrap()
+            .parse(source)
+            .unwrap()
+            .unwrap();
+        let uri = Url::parse("file:///project/src/Shapes.gren").unwrap();
+
+        let lenses = exports_lenses(&tree, source, &uri, true);
+        assert_eq!(lenses.len(), 2);
+        let summary = lenses[0].command.as_ref().unwrap();
+        assert_eq!(summary.title, "exposes 1 of 2 top-level definitions");
+        assert_eq!(summary.command, MANAGE_EXPORTS_COMMAND);
+        let expose_used = lenses[1].command.as_ref().unwrap();
+        assert_eq!(expose_used.command, EDIT_EXPOSING_COMMAND);
+        assert_eq!(
+            expose_used.arguments.as_ref().unwrap()[0]["exposeUsed"],
+            true
+        );
+
+        let lenses = exports_lenses(&tree, source, &uri, false);
+        assert_eq!(lenses[0].command.as_ref().unwrap().command, "");
+    }
+
+    #[test]
+    fn test_formatting_fixes() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let params = |diagnostics| CodeActionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            range: Range::default(),
+            context: CodeActionContext {
+                diagnostics,
+                only: None,
+                trigger_kind: None,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        assert!(generate_formatting_fixes(&params(Vec::new())).is_empty());
+
+        let diagnostic =
+            gren_lsp_core::formatter::unformatted_diagnostic("module Main exposing (..)");
+        let fixes = generate_formatting_fixes(&params(vec![diagnostic]));
+        assert_eq!(fixes.len(), 1);
+        match &fixes[0] {
+            CodeActionOrCommand::CodeAction(action) => {
+                assert_eq!(
+                    action.command.as_ref().unwrap().command,
+                    FORMAT_WORKSPACE_COMMAND
+                )
+            }
+            CodeActionOrCommand::Command(_) => panic!("expected a code action"),
+        }
+    }
 }
@@ -0,0 +1,314 @@
+//! Quick fixes for the suggestions in compiler errors
+//!
+//! Compiler diagnostics carry the fixes their messages suggest: a close name for one the
+//! compiler can't find, a module the file uses without importing it, or the type a
+//! definition's body has when it disagrees with the annotation. These turn them into edits
+//! of the file the diagnostic is in.
+
+use gren_lsp_core::import_conflicts::imports;
+use gren_lsp_core::syntax::{child_of_kind, node_range};
+use gren_lsp_core::{diagnostic_hints, CompilerHint, Document};
+use lsp_types::*;
+use std::collections::HashMap;
+use tree_sitter::{Node, Tree};
+
+/// A quick fix for each hint of `diagnostic` that still applies to `document`, whose
+/// parse tree is `tree`. `has_module` says whether a module exists to be imported.
+pub fn hint_fixes(
+    uri: &Url,
+    document: &Document,
+    tree: &Tree,
+    diagnostic: &Diagnostic,
+    has_module: impl Fn(&str) -> bool,
+) -> Vec<CodeAction> {
+    let content = document.text();
+    let hints = diagnostic_hints(diagnostic);
+    // A single close name is most likely the one meant
+    let replacements = hints
+        .iter()
+        .filter(|hint| matches!(hint, CompilerHint::ReplaceName { .. }))
+        .count();
+
+    hints
+        .into_iter()
+        .filter_map(|hint| {
+            let (title, edit, preferred) = match hint {
+                CompilerHint::ReplaceName { name, replacement } => {
+                    if text_in(document, diagnostic.range) != Some(name.as_str()) {
+                        return None;
+                    }
+                    let title = format!("Change to `{}`", replacement);
+                    (
+                        title,
+                        TextEdit::new(diagnostic.range, replacement),
+                        replacements == 1,
+                    )
+                }
+                CompilerHint::AddImport { module } => {
+                    let imported = imports(tree, content)
+                        .iter()
+                        .any(|import| import.module_name == module);
+                    if !has_module(&module) || imported {
+                        return None;
+                    }
+                    let (position, new_text) = import_insertion(tree, content, &module);
+                    let edit = TextEdit::new(Range::new(position, position), new_text);
+                    (format!("Import {}", module), edit, true)
+                }
+                CompilerHint::Annotate { name, annotation } => {
+                    let (title, edit) = annotation_edit(tree, content, &name, &annotation)?;
+                    (title, edit, false)
+                }
+            };
+            Some(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    document_changes: None,
+                    change_annotations: None,
+                }),
+                command: None,
+                is_preferred: Some(preferred),
+                disabled: None,
+                data: None,
+            })
+        })
+        .collect()
+}
+
+/// The text of a range
+fn text_in(document: &Document, range: Range) -> Option<&str> {
+    let start = document.position_to_offset(range.start)?;
+    let end = document.position_to_offset(range.end)?;
+    document.text().get(start..end)
+}
+
+/// Where to add `import module` and the text to add: after the last import, or before the
+/// first declaration when there are none
+fn import_insertion(tree: &Tree, content: &str, module: &str) -> (Position, String) {
+    if let Some(last) = imports(tree, content).last() {
+        let line = last.range.end.line + 1;
+        return (Position::new(line, 0), format!("import {}\n", module));
+    }
+
+    // Skip the module declaration and any comments, like the module's documentation
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let first_declaration = root
+        .named_children(&mut cursor)
+        .find(|node| node.kind() != "module_declaration" && !node.kind().ends_with("comment"));
+    let line = first_declaration.map_or(content.lines().count(), |declaration| {
+        declaration.start_position().row
+    });
+    (
+        Position::new(line as u32, 0),
+        format!("import {}\n\n", module),
+    )
+}
+
+/// The edit giving the top-level `name` the annotation `annotation`, replacing the one it
+/// has, and a title saying so
+fn annotation_edit(
+    tree: &Tree,
+    content: &str,
+    name: &str,
+    annotation: &str,
+) -> Option<(String, TextEdit)> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declarations: Vec<Node> = root.named_children(&mut cursor).collect();
+    let new_text = format!("{} : {}", name, annotation);
+
+    let annotated = declarations.iter().find(|declaration| {
+        declaration.kind() == "type_annotation" && declared_name(declaration, content) == Some(name)
+    });
+    if let Some(annotated) = annotated {
+        let title = format!("Change the annotation of `{}` to `{}`", name, annotation);
+        let range = node_range(annotated, content);
+        return Some((title, TextEdit::new(range, new_text)));
+    }
+
+    let defined = declarations.iter().find(|declaration| {
+        declaration.kind() == "value_declaration"
+            && child_of_kind(declaration, "function_declaration_left")
+                .is_some_and(|left| declared_name(&left, content) == Some(name))
+    })?;
+    let position = Position::new(defined.start_position().row as u32, 0);
+    let title = format!("Annotate `{}` as `{}`", name, annotation);
+    Some((
+        title,
+        TextEdit::new(Range::new(position, position), format!("{}\n", new_text)),
+    ))
+}
+
+/// The name an annotation or the left side of a definition starts with
+fn declared_name<'a>(node: &Node, content: &'a str) -> Option<&'a str> {
+    child_of_kind(node, "lower_case_identifier")?
+        .utf8_text(content.as_bytes())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    const MAIN: &str = "module Main exposing (main)\n\n\
+                        {-| The app\n-}\n\n\
+                        total : Int\ntotal =\n    flor 1.5\n";
+
+    fn uri() -> Url {
+        Url::parse("file:///project/src/Main.gren").unwrap()
+    }
+
+    fn diagnostic(range: Range, hints: &[CompilerHint]) -> Diagnostic {
+        Diagnostic {
+            range,
+            source: Some("gren".to_string()),
+            data: Some(serde_json::json!({ "hints": hints })),
+            ..Default::default()
+        }
+    }
+
+    fn fixes_for(
+        content: &str,
+        diagnostic: &Diagnostic,
+        has_module: impl Fn(&str) -> bool,
+    ) -> Vec<CodeAction> {
+        let document = Document::new(TextDocumentItem::new(
+            uri(),
+            "gren".to_string(),
+            1,
+            content.to_string(),
+        ));
+        let tree = Parser::new().unwrap().parse(content).unwrap().unwrap();
+        hint_fixes(&uri(), &document, &tree, diagnostic, has_module)
+    }
+
+    fn edits(action: &CodeAction) -> &Vec<TextEdit> {
+        &action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri()]
+    }
+
+    #[test]
+    fn test_replace_name() {
+        let range = Range::new(Position::new(7, 4), Position::new(7, 8));
+        let hint = CompilerHint::ReplaceName {
+            name: "flor".to_string(),
+            replacement: "floor".to_string(),
+        };
+        let fixes = fixes_for(MAIN, &diagnostic(range, &[hint]), |_| true);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].title, "Change to `floor`");
+        assert_eq!(fixes[0].is_preferred, Some(true));
+        assert_eq!(
+            edits(&fixes[0]),
+            &vec![TextEdit::new(range, "floor".to_string())]
+        );
+
+        // Not offered once the name is gone
+        let moved = Range::new(Position::new(6, 0), Position::new(6, 4));
+        let hint = CompilerHint::ReplaceName {
+            name: "flor".to_string(),
+            replacement: "floor".to_string(),
+        };
+        assert!(fixes_for(MAIN, &diagnostic(moved, &[hint]), |_| true).is_empty());
+    }
+
+    #[test]
+    fn test_add_import() {
+        let hint = CompilerHint::AddImport {
+            module: "Dict".to_string(),
+        };
+        let range = Range::default();
+        let fixes = fixes_for(MAIN, &diagnostic(range, &[hint.clone()]), |module| {
+            module == "Dict"
+        });
+        assert_eq!(fixes[0].title, "Import Dict");
+        assert_eq!(
+            edits(&fixes[0]),
+            &vec![TextEdit::new(
+                Range::new(Position::new(5, 0), Position::new(5, 0)),
+                "import Dict\n\n".to_string()
+            )]
+        );
+
+        let imported = "module Main exposing (main)\n\nimport Array\nimport Dict\n";
+        assert!(fixes_for(imported, &diagnostic(range, &[hint.clone()]), |_| true).is_empty());
+        assert!(fixes_for(MAIN, &diagnostic(range, &[hint]), |_| false).is_empty());
+
+        let hint = CompilerHint::AddImport {
+            module: "Set".to_string(),
+        };
+        let fixes = fixes_for(imported, &diagnostic(range, &[hint]), |_| true);
+        assert_eq!(edits(&fixes[0])[0].range.start, Position::new(4, 0));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let hint = CompilerHint::Annotate {
+            name: "total".to_string(),
+            annotation: "Float".to_string(),
+        };
+        let range = Range::default();
+        let fixes = fixes_for(MAIN, &diagnostic(range, &[hint.clone()]), |_| true);
+        assert_eq!(
+            fixes[0].title,
+            "Change the annotation of `total` to `Float`"
+        );
+        assert_eq!(
+            edits(&fixes[0]),
+            &vec![TextEdit::new(
+                Range::new(Position::new(5, 0), Position::new(5, 11)),
+                "total : Float".to_string()
+            )]
+        );
+
+        let unannotated = "module Main exposing (main)\n\ntotals = 1\n\ntotal =\n    1.5\n";
+        let fixes = fixes_for(unannotated, &diagnostic(range, &[hint]), |_| true);
+        assert_eq!(fixes[0].title, "Annotate `total` as `Float`");
+        assert_eq!(
+            edits(&fixes[0]),
+            &vec![TextEdit::new(
+                Range::new(Position::new(4, 0), Position::new(4, 0)),
+                "total : Float\n".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_fixes_after_non_ascii_text_and_comments() {
+        let source = "module Main exposing (main)\n\n\
+                      {-\nimport Dict\ntotal : Int\n-}\n\n\
+                      total =\n    \"héllo 😀\" ++ flor\n";
+        // é is one UTF-16 code unit and 😀 two, though they take six bytes
+        let range = Range::new(Position::new(8, 18), Position::new(8, 22));
+        let hints = [
+            CompilerHint::ReplaceName {
+                name: "flor".to_string(),
+                replacement: "floor".to_string(),
+            },
+            CompilerHint::AddImport {
+                module: "Dict".to_string(),
+            },
+            CompilerHint::Annotate {
+                name: "total".to_string(),
+                annotation: "String".to_string(),
+            },
+        ];
+        let fixes = fixes_for(source, &diagnostic(range, &hints), |_| true);
+        let titles: Vec<&str> = fixes.iter().map(|fix| fix.title.as_str()).collect();
+        // The commented out import and annotation don't count
+        assert_eq!(
+            titles,
+            vec![
+                "Change to `floor`",
+                "Import Dict",
+                "Annotate `total` as `String`"
+            ]
+        );
+        assert_eq!(edits(&fixes[1])[0].range.start, Position::new(7, 0));
+        assert_eq!(edits(&fixes[2])[0].range.start, Position::new(7, 0));
+    }
+}
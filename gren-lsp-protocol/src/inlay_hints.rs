@@ -0,0 +1,371 @@
+//! Inlay hints: inferred types after unannotated declarations, and parameter names before
+//! the arguments of calls whose parameters are easy to mix up
+//!
+//! Type hints follow the left-hand side of top-level and `let` declarations written
+//! without an annotation, showing what type inference found for them. Parameter names are
+//! only shown for functions taking two or more parameters of the same type, like
+//! `String.replace`, where the order of the arguments isn't told apart by their types.
+
+use gren_lsp_core::syntax::{child_of_kind, node_range};
+use lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+/// A declaration written without a type annotation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnannotatedDeclaration {
+    /// The declared name
+    pub name_range: Range,
+    /// The end of the declaration's name and parameters, where its type is shown
+    pub end: Position,
+}
+
+/// A function applied to arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// The function as written, qualified or not
+    pub function: String,
+    pub arguments: Vec<Argument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argument {
+    pub range: Range,
+    pub text: String,
+}
+
+impl Call {
+    /// The module qualifier and the name of the function, `String.replace` giving
+    /// `(["String"], "replace")`
+    pub fn qualified_name(&self) -> (Vec<String>, &str) {
+        match self.function.rsplit_once('.') {
+            Some((module, name)) => (module.split('.').map(str::to_string).collect(), name),
+            None => (Vec::new(), &self.function),
+        }
+    }
+}
+
+/// Top-level and `let` declarations without a type annotation overlapping `range`
+pub fn unannotated_declarations(
+    tree: &Tree,
+    source: &str,
+    range: Range,
+) -> Vec<UnannotatedDeclaration> {
+    let mut declarations = Vec::new();
+    collect_declarations(tree.root_node(), source, range, &mut declarations);
+    declarations
+}
+
+fn collect_declarations(
+    node: Node,
+    source: &str,
+    range: Range,
+    declarations: &mut Vec<UnannotatedDeclaration>,
+) {
+    if !overlaps(&node_range(&node, source), &range) {
+        return;
+    }
+
+    if node.kind() == "value_declaration" {
+        if let Some(declaration) = unannotated_declaration(node, source) {
+            declarations.push(declaration);
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_declarations(child, source, range, declarations);
+    }
+}
+
+fn unannotated_declaration(declaration: Node, source: &str) -> Option<UnannotatedDeclaration> {
+    // Destructuring declarations have no function_declaration_left
+    let left = child_of_kind(&declaration, "function_declaration_left")?;
+    let name = child_of_kind(&left, "lower_case_identifier")?;
+    if is_annotated(declaration, text(name, source)?, source) {
+        return None;
+    }
+
+    Some(UnannotatedDeclaration {
+        name_range: node_range(&name, source),
+        end: node_range(&left, source).end,
+    })
+}
+
+/// Whether the declaration has a type annotation for `name` directly above it
+fn is_annotated(declaration: Node, name: &str, source: &str) -> bool {
+    let mut previous = declaration.prev_named_sibling();
+    while let Some(sibling) = previous {
+        if !sibling.kind().ends_with("comment") {
+            break;
+        }
+        previous = sibling.prev_named_sibling();
+    }
+
+    previous.is_some_and(|sibling| {
+        sibling.kind() == "type_annotation"
+            && child_of_kind(&sibling, "lower_case_identifier").and_then(|n| text(n, source))
+                == Some(name)
+    })
+}
+
+/// Calls of named functions overlapping `range`, outermost first
+pub fn calls(tree: &Tree, source: &str, range: Range) -> Vec<Call> {
+    let mut calls = Vec::new();
+    collect_calls(tree.root_node(), source, range, &mut calls);
+    calls
+}
+
+fn collect_calls(node: Node, source: &str, range: Range, calls: &mut Vec<Call>) {
+    if !overlaps(&node_range(&node, source), &range) {
+        return;
+    }
+
+    if node.kind() == "function_call_expr" {
+        if let Some(call) = call(node, source) {
+            calls.push(call);
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_calls(child, source, range, calls);
+    }
+}
+
+fn call(node: Node, source: &str) -> Option<Call> {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|child| !child.kind().ends_with("comment"))
+        .collect();
+    let (function, arguments) = children.split_first()?;
+    // Only named functions have parameter names to show
+    if function.kind() != "value_expr" {
+        return None;
+    }
+
+    Some(Call {
+        function: text(*function, source)?.to_string(),
+        arguments: arguments
+            .iter()
+            .filter_map(|argument| {
+                Some(Argument {
+                    range: node_range(argument, source),
+                    text: text(*argument, source)?.to_string(),
+                })
+            })
+            .collect(),
+    })
+}
+
+/// The parameter names of the top-level function `name`, `None` for parameters that are
+/// patterns rather than names
+pub fn parameter_names(tree: &Tree, source: &str, name: &str) -> Option<Vec<Option<String>>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declaration = root
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "value_declaration")
+        .find(|declaration| {
+            child_of_kind(declaration, "function_declaration_left")
+                .and_then(|left| child_of_kind(&left, "lower_case_identifier"))
+                .and_then(|identifier| text(identifier, source))
+                == Some(name)
+        })?;
+    let left = child_of_kind(&declaration, "function_declaration_left")?;
+
+    let mut cursor = left.walk();
+    let names = left
+        .named_children(&mut cursor)
+        .skip(1)
+        .filter(|parameter| !parameter.kind().ends_with("comment"))
+        .map(|parameter| match parameter.kind() {
+            "lower_case_identifier" => text(parameter, source).map(str::to_string),
+            "lower_pattern" => child_of_kind(&parameter, "lower_case_identifier")
+                .and_then(|identifier| text(identifier, source))
+                .map(str::to_string),
+            _ => None,
+        })
+        .collect();
+    Some(names)
+}
+
+/// Whether two or more of the parameter types are the same, so arguments could be
+/// passed in the wrong order without a type error
+pub fn has_confusable_parameters(parameter_types: &[&str]) -> bool {
+    parameter_types
+        .iter()
+        .enumerate()
+        .any(|(index, ty)| parameter_types[index + 1..].contains(ty))
+}
+
+/// `: type` after a declaration's name and parameters
+pub fn type_hint(declaration: &UnannotatedDeclaration, type_text: &str) -> InlayHint {
+    InlayHint {
+        position: declaration.end,
+        label: InlayHintLabel::String(format!(": {}", type_text)),
+        kind: Some(InlayHintKind::TYPE),
+        text_edits: None,
+        tooltip: None,
+        padding_left: Some(true),
+        padding_right: Some(true),
+        data: None,
+    }
+}
+
+/// `name:` before each argument, leaving out pattern parameters and arguments that are
+/// already named after their parameter
+pub fn parameter_hints(call: &Call, names: &[Option<String>]) -> Vec<InlayHint> {
+    call.arguments
+        .iter()
+        .zip(names)
+        .filter_map(|(argument, name)| {
+            let name = name.as_ref()?;
+            if argument.text == *name || argument.text.ends_with(&format!(".{}", name)) {
+                return None;
+            }
+            Some(InlayHint {
+                position: argument.range.start,
+                label: InlayHintLabel::String(format!("{}:", name)),
+                kind: Some(InlayHintKind::PARAMETER),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: Some(true),
+                data: None,
+            })
+        })
+        .collect()
+}
+
+fn overlaps(a: &Range, b: &Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    fn everything() -> Range {
+        Range::new(Position::new(0, 0), Position::new(u32::MAX, 0))
+    }
+
+    fn argument(line: u32, start: u32, text: &str) -> Argument {
+        Argument {
+            range: Range::new(
+                Position::new(line, start),
+                Position::new(line, start + text.len() as u32),
+            ),
+            text: text.to_string(),
+        }
+    }
+
+    fn label(hint: &InlayHint) -> &str {
+        match &hint.label {
+            InlayHintLabel::String(label) => label,
+            InlayHintLabel::LabelParts(_) => "",
+        }
+    }
+
+    #[test]
+    fn test_confusable_parameters() {
+        assert!(has_confusable_parameters(&["String", "String", "String"]));
+        assert!(has_confusable_parameters(&["Int", "String", "Int"]));
+        assert!(!has_confusable_parameters(&["Int", "String"]));
+        assert!(!has_confusable_parameters(&["a -> b", "Array a"]));
+        assert!(!has_confusable_parameters(&[]));
+    }
+
+    #[test]
+    fn test_parameter_hints() {
+        let call = Call {
+            function: "String.replace".to_string(),
+            arguments: vec![
+                argument(3, 19, "\",\""),
+                argument(3, 23, "separator"),
+                argument(3, 33, "model.text"),
+            ],
+        };
+        assert_eq!(
+            call.qualified_name(),
+            (vec!["String".to_string()], "replace")
+        );
+
+        let names = [
+            Some("before".to_string()),
+            Some("after".to_string()),
+            Some("text".to_string()),
+        ];
+        let hints = parameter_hints(&call, &names);
+        assert_eq!(hints.len(), 2);
+        assert_eq!(hints[0].position, Position::new(3, 19));
+        assert_eq!(label(&hints[0]), "before:");
+        assert_eq!(label(&hints[1]), "after:");
+
+        // Pattern parameters have no name to show
+        let hints = parameter_hints(&call, &[None, Some("after".to_string())]);
+        assert_eq!(hints.len(), 1);
+        assert_eq!(hints[0].position, Position::new(3, 23));
+    }
+
+    #[test]
+    fn test_declarations_and_calls() {
+        let source = "module Main exposing (..)
+
+clamp : Int -> Int -> Int -> Int
+clamp low high value =
+    max low (min high value)
+
+double value =
+    let
+        twice = value * 2
+    in
+    twice
+
+wrap { text } prefix =
+    clamp 0 10 (String.count text)
+";
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        let declarations = unannotated_declarations(&tree, source, everything());
+        let names: Vec<Position> = declarations
+            .iter()
+            .map(|declaration| declaration.name_range.start)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Position::new(6, 0),
+                Position::new(8, 8),
+                Position::new(12, 0)
+            ]
+        );
+        assert_eq!(declarations[0].end, Position::new(6, 12));
+
+        let calls = calls(&tree, source, everything());
+        let functions: Vec<&str> = calls.iter().map(|call| call.function.as_str()).collect();
+        assert_eq!(functions, vec!["max", "min", "clamp", "String.count"]);
+        assert_eq!(calls[2].arguments.len(), 3);
+        assert_eq!(calls[2].arguments[2].text, "(String.count text)");
+
+        assert_eq!(
+            parameter_names(&tree, source, "clamp"),
+            Some(vec![
+                Some("low".to_string()),
+                Some("high".to_string()),
+                Some("value".to_string())
+            ])
+        );
+        assert_eq!(
+            parameter_names(&tree, source, "wrap"),
+            Some(vec![None, Some("prefix".to_string())])
+        );
+        assert_eq!(parameter_names(&tree, source, "missing"), None);
+    }
+}
@@ -0,0 +1,219 @@
+//! Inline completions: ghost text for continuations the code around the cursor predicts
+//!
+//! Three are offered, each only with the cursor at the end of its line: the branches a
+//! `when` expression doesn't have yet, where the next branch starts; every field of the
+//! record being updated after `{ model |`, each set to its current value; and the rest of
+//! an `import` line, written the way most files import the module. Nothing is guessed
+//! beyond that: branches and fields come from the index, imports from the import clauses
+//! of the indexed files.
+
+use crate::case_branches::{branches_text, BranchConstructor};
+use gren_lsp_core::syntax::child_of_kind;
+use tree_sitter::{Node, Tree};
+
+/// The rest of the branch being started at `column` with `typed` written so far, and the
+/// branches after it. Constructors starting with `typed` come first.
+pub fn branches_completion(
+    mut constructors: Vec<BranchConstructor>,
+    typed: &str,
+    column: usize,
+) -> Option<String> {
+    constructors.sort_by_key(|constructor| !constructor.name.starts_with(typed));
+    let text = branches_text(&constructors, column, false);
+    let text = &text[column..];
+    if text.starts_with(typed) {
+        Some(text.to_string())
+    } else {
+        None
+    }
+}
+
+/// The record an update expression ending before byte `offset` updates, `model` for
+/// `{ model |`. Read from the tree's tokens, as the update is unfinished and the parser
+/// may not have made a record expression of it yet.
+pub fn record_update_at<'a>(tree: &Tree, source: &'a str, offset: usize) -> Option<&'a str> {
+    let bar = token_before(tree, source, offset).filter(|bar| text(*bar, source) == Some("|"))?;
+    if let Some(record) = bar.parent().filter(|parent| parent.kind() == "record_expr") {
+        return child_of_kind(&record, "record_base_identifier")
+            .and_then(|name| text(name, source));
+    }
+
+    let name = token_before(tree, source, bar.start_byte())
+        .filter(|name| name.kind() == "lower_case_identifier")?;
+    token_before(tree, source, name.start_byte())
+        .filter(|brace| text(*brace, source) == Some("{"))?;
+    text(name, source)
+}
+
+/// The rest of `{ record |`: each field set to its current value, then the closing brace
+pub fn record_update_text(line_before_cursor: &str, record: &str, fields: &[String]) -> String {
+    let separator = if line_before_cursor.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        " "
+    };
+    let assignments: Vec<String> = fields
+        .iter()
+        .map(|field| format!("{} = {}.{}", field, record, field))
+        .collect();
+    format!("{}{} }}", separator, assignments.join(", "))
+}
+
+/// The module name written so far on an `import` line ending at byte `offset`, empty
+/// right after `import `. The line's tokens must be the `import` keyword at its start and
+/// then only the module name's segments and dots.
+pub fn import_prefix<'a>(tree: &Tree, source: &'a str, offset: usize) -> Option<&'a str> {
+    let mut token = token_before(tree, source, offset)?;
+    let mut typed_start = offset;
+    while token.kind() == "upper_case_identifier" || text(token, source) == Some(".") {
+        if token.end_byte() != typed_start {
+            return None;
+        }
+        typed_start = token.start_byte();
+        token = token_before(tree, source, typed_start)?;
+    }
+
+    let typed = &source[typed_start..offset];
+    let starts_line = token.start_byte() == 0 || source[..token.start_byte()].ends_with('\n');
+    let after_keyword = typed.is_empty() || token.end_byte() < typed_start;
+    if text(token, source) == Some("import")
+        && starts_line
+        && after_keyword
+        && (typed.is_empty() || typed.starts_with(|c: char| c.is_uppercase()))
+    {
+        Some(typed)
+    } else {
+        None
+    }
+}
+
+/// The most common import clause of a module starting with `typed` that isn't in
+/// `imported` yet, from clauses sorted most common first
+pub fn import_line<'a>(
+    clauses: &'a [(String, usize)],
+    typed: &str,
+    imported: &[String],
+) -> Option<&'a str> {
+    clauses
+        .iter()
+        .map(|(clause, _)| clause.as_str())
+        .find(|clause| {
+            clause.split_whitespace().nth(1).is_some_and(|module| {
+                module.starts_with(typed) && !imported.iter().any(|name| name == module)
+            })
+        })
+}
+
+/// The token ending at the last non-whitespace byte before `offset`
+fn token_before(tree: &Tree, source: &str, offset: usize) -> Option<Node> {
+    let end = source[..offset].trim_end().len();
+    if end == 0 {
+        return None;
+    }
+    tree.root_node()
+        .descendant_for_byte_range(end - 1, end)
+        .filter(|token| token.child_count() == 0)
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    /// The context read with the cursor at the end of `source`
+    fn at_end(
+        source: &str,
+        context: for<'a> fn(&Tree, &'a str, usize) -> Option<&'a str>,
+    ) -> Option<String> {
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        context(&tree, source, source.len()).map(str::to_string)
+    }
+
+    /// The record updated at the end of `line`, which ends a value's body
+    fn record_update(line: &str) -> Option<String> {
+        let source = format!("module Main exposing (..)\n\nupdate model =\n{}", line);
+        at_end(&source, record_update_at)
+    }
+
+    /// The module name typed at the end of `line`, which follows the module header
+    fn import(line: &str) -> Option<String> {
+        let source = format!("module Main exposing (..)\n\n{}", line);
+        at_end(&source, import_prefix)
+    }
+
+    #[test]
+    fn test_branches_completion() {
+        let constructors = vec![
+            BranchConstructor::from_signature("Increment".to_string(), Some("Msg")),
+            BranchConstructor::from_signature("Reset".to_string(), Some("Int -> Msg")),
+        ];
+        assert_eq!(
+            branches_completion(constructors.clone(), "", 8).as_deref(),
+            Some(
+                "Increment ->\n            Debug.todo \"Increment\"\n\n        Reset int ->\n            Debug.todo \"Reset\""
+            )
+        );
+
+        let text = branches_completion(constructors.clone(), "Re", 8).unwrap();
+        assert!(text.starts_with("Reset int ->"));
+        assert_eq!(branches_completion(constructors, "Other", 8), None);
+    }
+
+    #[test]
+    fn test_record_update_at() {
+        assert_eq!(record_update("    { model |").as_deref(), Some("model"));
+        assert_eq!(
+            record_update("        ( { model | ").as_deref(),
+            Some("model")
+        );
+        assert_eq!(record_update("    { model | count = 1,"), None);
+        assert_eq!(record_update("    a || b |"), None);
+        assert_eq!(record_update("    { Model |"), None);
+    }
+
+    #[test]
+    fn test_record_update_text() {
+        let fields = vec!["count".to_string(), "name".to_string()];
+        assert_eq!(
+            record_update_text("    { model |", "model", &fields),
+            " count = model.count, name = model.name }"
+        );
+        assert_eq!(
+            record_update_text("    { model | ", "model", &fields[..1]),
+            "count = model.count }"
+        );
+    }
+
+    #[test]
+    fn test_import_line() {
+        assert_eq!(import("import Json.De").as_deref(), Some("Json.De"));
+        assert_eq!(import("import Json.").as_deref(), Some("Json."));
+        assert_eq!(import("import ").as_deref(), Some(""));
+        assert_eq!(import("import Json.Decode as"), None);
+        assert_eq!(import("    import Json"), None);
+
+        let clauses = vec![
+            ("import Json.Decode as Decode".to_string(), 4),
+            ("import Html exposing (Html)".to_string(), 3),
+            ("import Json.Decode as D".to_string(), 1),
+            ("import Json.Encode as Encode".to_string(), 1),
+        ];
+        assert_eq!(
+            import_line(&clauses, "Json", &[]),
+            Some("import Json.Decode as Decode")
+        );
+        assert_eq!(
+            import_line(&clauses, "Json", &["Json.Decode".to_string()]),
+            Some("import Json.Encode as Encode")
+        );
+        assert_eq!(
+            import_line(&clauses, "", &[]),
+            Some("import Json.Decode as Decode")
+        );
+        assert_eq!(import_line(&clauses, "Dict", &[]), None);
+    }
+}
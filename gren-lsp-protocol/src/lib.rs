@@ -1,3 +1,31 @@
+pub mod call_hierarchy;
+pub mod call_snippets;
+pub mod case_branches;
+pub mod code_actions;
+pub mod completion_cache;
+pub mod debug_log;
+pub mod definition_origin;
+pub mod deprecation;
+pub mod exposing;
+pub mod field_rename;
+pub mod folding;
 pub mod handlers;
+pub mod hint_fixes;
+pub mod inlay_hints;
+pub mod inline_completion;
+pub mod locals;
+pub mod lsp_ext;
+pub mod module_hover;
+pub mod module_search;
+pub mod package_docs;
+pub mod pattern_completion;
+pub mod qualified_completion;
+pub mod rename;
+pub mod selection_range;
+pub mod semantic_tokens;
+pub mod settings;
+pub mod signature_help;
+pub mod type_hierarchy;
 
 pub use handlers::Handlers;
+pub use settings::ServerSettings;
@@ -0,0 +1,463 @@
+//! Scope analysis for names bound inside a function: parameters, `let` bindings, `when`
+//! branch patterns and lambda arguments. None of these are in the symbol index, so hover
+//! and rename resolve them from the syntax tree of the current file.
+
+use gren_lsp_core::syntax::{child_of_kind, node_range, position_to_point};
+use lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+/// How a local name is bound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalKind {
+    Parameter,
+    LetBinding,
+    /// Bound by the pattern of a `when` branch
+    BranchPattern,
+    LambdaParameter,
+}
+
+impl LocalKind {
+    pub fn description(self) -> &'static str {
+        match self {
+            LocalKind::Parameter => "parameter",
+            LocalKind::LetBinding => "let binding",
+            LocalKind::BranchPattern => "pattern variable",
+            LocalKind::LambdaParameter => "lambda parameter",
+        }
+    }
+}
+
+/// A local name and where it was bound
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalBinding {
+    pub name: String,
+    pub kind: LocalKind,
+    /// The name at its binding site
+    pub binding_range: Range,
+    /// Source line of the binding site, trimmed
+    pub binding_line: String,
+    /// From the binding's own annotation or, for a parameter, the matching argument of
+    /// the function's annotation
+    pub type_annotation: Option<String>,
+    /// Top-level function the binding lives in
+    pub enclosing_function: Option<String>,
+    /// Range of the name under the cursor
+    pub reference_range: Range,
+}
+
+/// Resolve the name at `position` to the local binding it refers to. Returns `None` for
+/// top-level and imported names, which the symbol index handles.
+pub fn local_binding_at(tree: &Tree, source: &str, position: Position) -> Option<LocalBinding> {
+    let node = identifier_at(tree, source, position)?;
+    let name = text(node, source)?;
+    let resolved = resolve(node, name, source)?;
+    let row = resolved.binder.start_position().row;
+    Some(LocalBinding {
+        name: name.to_string(),
+        kind: resolved.kind,
+        binding_range: node_range(&resolved.binder, source),
+        binding_line: source
+            .lines()
+            .nth(row)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        type_annotation: resolved.type_annotation,
+        enclosing_function: enclosing_function(resolved.scope, source),
+        reference_range: node_range(&node, source),
+    })
+}
+
+/// Every occurrence of the local at `position`, in source order: where it's bound, its
+/// uses and, for a `let` binding, its annotation. Each same-named identifier in the
+/// binding's scope is resolved back to its binding, so another local with the same name,
+/// in a different `when` branch, lambda or function, is never included.
+pub fn local_occurrences(tree: &Tree, source: &str, position: Position) -> Option<Vec<Range>> {
+    let node = identifier_at(tree, source, position)?;
+    let name = text(node, source)?;
+    let resolved = resolve(node, name, source)?;
+
+    let mut candidates = Vec::new();
+    collect_uses(resolved.scope, name, source, &mut candidates);
+    if resolved.kind == LocalKind::LetBinding {
+        let mut cursor = resolved.scope.walk();
+        let annotations: Vec<Node> = resolved
+            .scope
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() == "type_annotation")
+            .filter_map(|annotation| child_of_kind(&annotation, "lower_case_identifier"))
+            .filter(|annotated| text(*annotated, source) == Some(name))
+            .collect();
+        candidates.extend(annotations);
+    }
+
+    let mut ranges = vec![node_range(&resolved.binder, source)];
+    ranges.extend(
+        candidates
+            .into_iter()
+            .filter(|candidate| {
+                resolve(*candidate, name, source)
+                    .is_some_and(|other| other.binder.id() == resolved.binder.id())
+            })
+            .map(|candidate| node_range(&candidate, source)),
+    );
+    ranges.sort_by_key(|range| (range.start.line, range.start.character));
+    ranges.dedup();
+    Some(ranges)
+}
+
+/// A local name resolved to its binding
+struct Resolved<'a> {
+    binder: Node<'a>,
+    kind: LocalKind,
+    type_annotation: Option<String>,
+    /// The node whose scope the binding is in: the function, `let`, branch or lambda
+    scope: Node<'a>,
+}
+
+/// The lower case identifier at `position`, unless it's qualified like `Module.name` and
+/// so can't be local
+fn identifier_at<'a>(tree: &'a Tree, source: &str, position: Position) -> Option<Node<'a>> {
+    let point = position_to_point(position, source);
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
+    if node.kind() != "lower_case_identifier" {
+        return None;
+    }
+
+    if let Some(parent) = node.parent() {
+        if parent.kind() == "value_qid" && parent.named_child_count() > 1 {
+            return None;
+        }
+    }
+    Some(node)
+}
+
+/// Walk out from `node` to the closest scope binding `name`
+fn resolve<'a>(node: Node<'a>, name: &str, source: &str) -> Option<Resolved<'a>> {
+    let mut scope = node.parent();
+    while let Some(ancestor) = scope {
+        if let Some((binder, kind, type_annotation)) = find_binder(ancestor, name, source) {
+            return Some(Resolved {
+                binder,
+                kind,
+                type_annotation,
+                scope: ancestor,
+            });
+        }
+        scope = ancestor.parent();
+    }
+    None
+}
+
+/// Unqualified references to `name` under `node`, including the base of a record update
+fn collect_uses<'a>(node: Node<'a>, name: &str, source: &str, uses: &mut Vec<Node<'a>>) {
+    let is_use = match node.kind() {
+        "value_qid" => node.named_child_count() == 1,
+        "record_base_identifier" => true,
+        _ => false,
+    };
+    if is_use {
+        if text(node, source) == Some(name) {
+            uses.push(node);
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_uses(child, name, source, uses);
+    }
+}
+
+/// The node binding `name` in the scope `node` introduces, if any
+fn find_binder<'a>(
+    node: Node<'a>,
+    name: &str,
+    source: &str,
+) -> Option<(Node<'a>, LocalKind, Option<String>)> {
+    match node.kind() {
+        "value_declaration" => {
+            let left = child_of_kind(&node, "function_declaration_left")?;
+            let parameters = function_parameters(&left);
+            let (index, binder) = parameters.iter().enumerate().find_map(|(index, pattern)| {
+                pattern_binder(*pattern, name, source).map(|binder| (index, binder))
+            })?;
+
+            // A plain parameter takes its type from the function's annotation
+            let type_annotation = (parameters[index].kind() == "lower_pattern"
+                || parameters[index].kind() == "lower_case_identifier")
+                .then(|| {
+                    let function = text(child_of_kind(&left, "lower_case_identifier")?, source)?;
+                    let annotation = preceding_annotation(node, function, source)?;
+                    argument_types(annotation, source).into_iter().nth(index)
+                })
+                .flatten();
+            Some((binder, LocalKind::Parameter, type_annotation))
+        }
+        "let_in_expr" => {
+            let mut cursor = node.walk();
+            let declarations: Vec<Node> = node
+                .named_children(&mut cursor)
+                .filter(|child| child.kind() == "value_declaration")
+                .collect();
+
+            declarations.into_iter().find_map(|declaration| {
+                let binder = match child_of_kind(&declaration, "function_declaration_left") {
+                    Some(left) => child_of_kind(&left, "lower_case_identifier")
+                        .filter(|binder| text(*binder, source) == Some(name))?,
+                    // Destructuring, e.g. `{ x, y } = point`
+                    None => {
+                        let pattern = declaration.named_child(0)?;
+                        pattern_binder(pattern, name, source)?
+                    }
+                };
+                let type_annotation = preceding_annotation(declaration, name, source)
+                    .and_then(|annotation| annotation_type(annotation, source));
+                Some((binder, LocalKind::LetBinding, type_annotation))
+            })
+        }
+        "case_of_branch" => {
+            let pattern = node
+                .child_by_field_name("pattern")
+                .or_else(|| node.named_child(0))?;
+            let binder = pattern_binder(pattern, name, source)?;
+            Some((binder, LocalKind::BranchPattern, None))
+        }
+        "anonymous_function_expr" => {
+            let mut cursor = node.walk();
+            let patterns: Vec<Node> = node
+                .named_children(&mut cursor)
+                .take_while(|child| child.kind() != "arrow")
+                .collect();
+            let binder = patterns
+                .into_iter()
+                .find_map(|pattern| pattern_binder(pattern, name, source))?;
+            Some((binder, LocalKind::LambdaParameter, None))
+        }
+        _ => None,
+    }
+}
+
+/// Parameter patterns of a function, without the function's own name
+fn function_parameters<'a>(left: &Node<'a>) -> Vec<Node<'a>> {
+    let mut cursor = left.walk();
+    let parameters = left.named_children(&mut cursor).skip(1).collect();
+    parameters
+}
+
+/// The identifier binding `name` inside a pattern
+fn pattern_binder<'a>(pattern: Node<'a>, name: &str, source: &str) -> Option<Node<'a>> {
+    if pattern.kind() == "lower_case_identifier" {
+        return (text(pattern, source) == Some(name)).then_some(pattern);
+    }
+
+    let mut cursor = pattern.walk();
+    let children: Vec<Node> = pattern.named_children(&mut cursor).collect();
+    children
+        .into_iter()
+        .find_map(|child| pattern_binder(child, name, source))
+}
+
+/// The type annotation directly above `declaration`, if it annotates `name`
+fn preceding_annotation<'a>(declaration: Node<'a>, name: &str, source: &str) -> Option<Node<'a>> {
+    let mut previous = declaration.prev_named_sibling();
+    while let Some(sibling) = previous {
+        if !sibling.kind().ends_with("comment") {
+            break;
+        }
+        previous = sibling.prev_named_sibling();
+    }
+
+    previous.filter(|sibling| {
+        sibling.kind() == "type_annotation"
+            && child_of_kind(sibling, "lower_case_identifier").and_then(|n| text(n, source))
+                == Some(name)
+    })
+}
+
+fn annotation_type(annotation: Node, source: &str) -> Option<String> {
+    let type_expression = child_of_kind(&annotation, "type_expression")?;
+    Some(collapse_whitespace(text(type_expression, source)?))
+}
+
+/// The argument types of an annotated function, e.g. `Msg` and `Model` for
+/// `Msg -> Model -> Model`
+fn argument_types(annotation: Node, source: &str) -> Vec<String> {
+    let type_expression = match child_of_kind(&annotation, "type_expression") {
+        Some(type_expression) => type_expression,
+        None => return Vec::new(),
+    };
+
+    let mut cursor = type_expression.walk();
+    let mut parts: Vec<String> = type_expression
+        .named_children(&mut cursor)
+        .filter(|part| part.kind() != "arrow" && !part.kind().ends_with("comment"))
+        .filter_map(|part| text(part, source).map(collapse_whitespace))
+        .collect();
+    // The last part is the return type
+    parts.pop();
+    parts
+}
+
+fn enclosing_function(node: Node, source: &str) -> Option<String> {
+    let mut current = Some(node);
+    let mut function = None;
+    while let Some(ancestor) = current {
+        if ancestor.kind() == "value_declaration" {
+            function = child_of_kind(&ancestor, "function_declaration_left")
+                .and_then(|left| child_of_kind(&left, "lower_case_identifier"))
+                .and_then(|name| text(name, source))
+                .or(function);
+        }
+        current = ancestor.parent();
+    }
+    function.map(str::to_string)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    const SOURCE: &str = r#"module Main exposing (update)
+
+update : Msg -> Model -> Model
+update msg model =
+    let
+        step : Int
+        step =
+            2
+    in
+    when msg is
+        Increment amount ->
+            { model | count = model.count + amount * step }
+
+        Reset ->
+            model
+"#;
+
+    fn binding_at(line: u32, character: u32) -> Option<LocalBinding> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        local_binding_at(&tree, SOURCE, Position::new(line, character))
+    }
+
+    #[test]
+    fn test_parameter_takes_type_from_annotation() {
+        // `model` in `model.count`
+        let binding = binding_at(11, 31).unwrap();
+        assert_eq!(binding.name, "model");
+        assert_eq!(binding.kind, LocalKind::Parameter);
+        assert_eq!(binding.type_annotation.as_deref(), Some("Model"));
+        assert_eq!(binding.binding_range.start, Position::new(3, 11));
+        assert_eq!(binding.binding_line, "update msg model =");
+        assert_eq!(binding.enclosing_function.as_deref(), Some("update"));
+    }
+
+    #[test]
+    fn test_let_binding_and_branch_pattern() {
+        // `step` at the end of the record update
+        let step = binding_at(11, 55).unwrap();
+        assert_eq!(step.kind, LocalKind::LetBinding);
+        assert_eq!(step.type_annotation.as_deref(), Some("Int"));
+        assert_eq!(step.binding_range.start, Position::new(6, 8));
+
+        // `amount` in the branch body
+        let amount = binding_at(11, 46).unwrap();
+        assert_eq!(amount.kind, LocalKind::BranchPattern);
+        assert_eq!(amount.binding_range.start, Position::new(10, 18));
+        assert_eq!(amount.type_annotation, None);
+    }
+
+    const SCOPES: &str = r#"module Scopes exposing (..)
+
+total msg =
+    when msg is
+        Add amount ->
+            amount + 1
+
+        Remove amount ->
+            amount - 1
+
+
+double amount =
+    let
+        step : Int
+        step =
+            2
+    in
+    Array.map (\item -> item * step) [ amount ]
+
+
+triple items =
+    let
+        step =
+            3
+    in
+    Array.map (\item -> item * step) items
+"#;
+
+    fn occurrences_at(line: u32, character: u32) -> Vec<(u32, u32)> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SCOPES).unwrap().unwrap();
+        local_occurrences(&tree, SCOPES, Position::new(line, character))
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.start.line, range.start.character))
+            .collect()
+    }
+
+    #[test]
+    fn test_branch_local_occurrences() {
+        // `amount` in the body of the first branch
+        assert_eq!(occurrences_at(5, 12), vec![(4, 12), (5, 12)]);
+        // From the binding of the second branch
+        assert_eq!(occurrences_at(7, 15), vec![(7, 15), (8, 12)]);
+    }
+
+    #[test]
+    fn test_let_local_occurrences() {
+        // `step` in the lambda, with its annotation and binding but not `triple`'s `step`
+        assert_eq!(occurrences_at(17, 31), vec![(13, 8), (14, 8), (17, 31)]);
+        assert_eq!(occurrences_at(22, 8), vec![(22, 8), (25, 31)]);
+    }
+
+    #[test]
+    fn test_lambda_parameter_occurrences() {
+        assert_eq!(occurrences_at(17, 24), vec![(17, 16), (17, 24)]);
+        assert_eq!(occurrences_at(25, 16), vec![(25, 16), (25, 24)]);
+    }
+
+    #[test]
+    fn test_top_level_names_are_not_local() {
+        // `update` in its own definition
+        assert_eq!(binding_at(3, 2), None);
+    }
+
+    #[test]
+    fn test_locals_after_non_ascii_text() {
+        let source = "module Main exposing (..)\n\ngreet name =\n    \"héllo 😀\" ++ name\n";
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        // Columns count UTF-16 code units: é is one and 😀 two, though they take six bytes
+        let position = Position::new(3, 19);
+        let binding = local_binding_at(&tree, source, position).unwrap();
+        assert_eq!(binding.kind, LocalKind::Parameter);
+        let use_range = Range::new(Position::new(3, 18), Position::new(3, 22));
+        assert_eq!(binding.reference_range, use_range);
+        assert_eq!(
+            local_occurrences(&tree, source, position),
+            Some(vec![
+                Range::new(Position::new(2, 6), Position::new(2, 10)),
+                use_range
+            ])
+        );
+    }
+}
@@ -0,0 +1,706 @@
+//! Protocol messages that lsp-types doesn't model
+
+use gren_lsp_core::{CompileReport, IndexStats, MoveDirection, TodoMarker};
+use lsp_types::notification::Notification;
+use lsp_types::request::Request;
+use lsp_types::{
+    CodeActionKind, Location, Position, ProgressToken, Range, SymbolKind, TextDocumentIdentifier,
+    TextDocumentPositionParams, Url, WorkDoneProgressParams,
+};
+use serde::{Deserialize, Serialize};
+
+/// `$/progress` notification carrying a batch of partial results for a request that was
+/// sent with a `partialResultToken`. lsp-types only models work done progress values for
+/// `$/progress`, so partial results need their own notification type.
+pub enum PartialResult {}
+
+impl Notification for PartialResult {
+    type Params = PartialResultParams;
+    const METHOD: &'static str = "$/progress";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialResultParams {
+    pub token: ProgressToken,
+    pub value: serde_json::Value,
+}
+
+/// `gren/searchDocs` request: find symbols whose documentation or comments match a
+/// natural-language query, e.g. "function that parses dates"
+pub enum SearchDocs {}
+
+impl Request for SearchDocs {
+    type Params = SearchDocsParams;
+    type Result = Vec<DocSearchResult>;
+    const METHOD: &'static str = "gren/searchDocs";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchDocsParams {
+    pub query: String,
+    /// Maximum number of results, defaults to the server's configured limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// A symbol matching a `gren/searchDocs` query, best matches first. `documentation` is empty
+/// when only the symbol's comments matched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocSearchResult {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub location: Location,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_signature: Option<String>,
+    pub documentation: String,
+}
+
+/// `gren/capabilitiesReport` request: which features are working, and why the others
+/// aren't, so editor extensions can explain missing features instead of staying silent
+pub enum CapabilitiesReport {}
+
+impl Request for CapabilitiesReport {
+    type Params = ();
+    type Result = CapabilitiesReportResult;
+    const METHOD: &'static str = "gren/capabilitiesReport";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilitiesReportResult {
+    pub features: Vec<FeatureReport>,
+}
+
+/// Status of a single server feature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureReport {
+    /// Feature name, e.g. `diagnostics` or `completion`
+    pub feature: String,
+    pub status: FeatureStatus,
+    /// Why the feature is degraded or disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// How to use the feature beyond what the editor shows, e.g. query syntax
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FeatureStatus {
+    /// Working fully
+    Active,
+    /// Working with reduced results
+    Degraded,
+    /// Not available
+    Disabled,
+}
+
+impl FeatureReport {
+    pub fn active(feature: &str) -> Self {
+        Self {
+            feature: feature.to_string(),
+            status: FeatureStatus::Active,
+            reason: None,
+            usage: None,
+        }
+    }
+
+    pub fn degraded(feature: &str, reason: impl Into<String>) -> Self {
+        Self {
+            feature: feature.to_string(),
+            status: FeatureStatus::Degraded,
+            reason: Some(reason.into()),
+            usage: None,
+        }
+    }
+
+    pub fn disabled(feature: &str, reason: impl Into<String>) -> Self {
+        Self {
+            feature: feature.to_string(),
+            status: FeatureStatus::Disabled,
+            reason: Some(reason.into()),
+            usage: None,
+        }
+    }
+
+    pub fn with_usage(mut self, usage: impl Into<String>) -> Self {
+        self.usage = Some(usage.into());
+        self
+    }
+}
+
+/// `gren/metrics` request: complexity metrics for one module, or for every loaded module of
+/// the project when no document is given
+pub enum Metrics {}
+
+impl Request for Metrics {
+    type Params = MetricsParams;
+    type Result = Vec<ModuleMetricsReport>;
+    const METHOD: &'static str = "gren/metrics";
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_document: Option<TextDocumentIdentifier>,
+}
+
+/// Metrics for a single module
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleMetricsReport {
+    pub uri: Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    /// Lines containing code, excluding blank and comment-only lines
+    pub lines_of_code: usize,
+    pub exported_symbols: usize,
+    /// Length in lines of the longest top-level function
+    pub max_function_length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longest_function: Option<String>,
+    /// Deepest nesting of `when` expressions
+    pub max_case_depth: usize,
+    /// Number of loaded modules importing this one
+    pub fan_in: usize,
+    /// Number of modules this one imports
+    pub fan_out: usize,
+}
+
+/// `gren/todos` request: TODO, FIXME and XXX comments across the indexed workspace,
+/// grouped by file, so editor extensions can show a task tree
+pub enum Todos {}
+
+impl Request for Todos {
+    type Params = ();
+    type Result = Vec<FileTodos>;
+    const METHOD: &'static str = "gren/todos";
+}
+
+/// Task markers found in one file, in the order they appear
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTodos {
+    pub uri: Url,
+    pub todos: Vec<Todo>,
+    /// The file changed since it was last indexed, so its markers may be out of date
+    #[serde(default)]
+    pub index_pending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Todo {
+    pub marker: TodoMarker,
+    /// Comment text following the marker
+    pub text: String,
+    pub range: Range,
+}
+
+/// `gren/moduleRenameImpact` request: the files a rename of the module declared in a
+/// document would touch, without building the edit. Supports work done progress,
+/// cancellation and partial results, so large workspaces can show affected files as they
+/// are found.
+pub enum ModuleRenameImpact {}
+
+impl Request for ModuleRenameImpact {
+    type Params = ModuleRenameImpactParams;
+    type Result = Vec<AffectedFile>;
+    const METHOD: &'static str = "gren/moduleRenameImpact";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleRenameImpactParams {
+    /// File whose module would be renamed
+    pub text_document: TextDocumentIdentifier,
+    #[serde(flatten)]
+    pub work_done_progress_params: WorkDoneProgressParams,
+    #[serde(flatten)]
+    pub partial_result_params: lsp_types::PartialResultParams,
+}
+
+/// A file that mentions the module, with the ranges a rename would rewrite. The module's
+/// own file comes first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AffectedFile {
+    pub uri: Url,
+    pub occurrences: Vec<Range>,
+    /// The file changed since it was last indexed, so occurrences may be missing
+    #[serde(default)]
+    pub index_pending: bool,
+}
+
+/// `gren/deprecatedUsages` request: every symbol documented as `@deprecated` and the places
+/// still using it, for planning a migration off deprecated APIs
+pub enum DeprecatedUsages {}
+
+impl Request for DeprecatedUsages {
+    type Params = ();
+    type Result = Vec<DeprecatedSymbolUsages>;
+    const METHOD: &'static str = "gren/deprecatedUsages";
+}
+
+/// A deprecated symbol, ordered by defining file and position
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeprecatedSymbolUsages {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the symbol is defined
+    pub location: Location,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    /// Text following the `@deprecated` tag, usually what to use instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Every reference outside the definition, ordered by file and position
+    pub usages: Vec<Location>,
+}
+
+/// `gren/compileReport` notification, sent after each compile with its timing, module
+/// counts and build order
+pub enum CompileReportNotification {}
+
+impl Notification for CompileReportNotification {
+    type Params = CompileReport;
+    const METHOD: &'static str = "gren/compileReport";
+}
+
+/// `gren/compileHistory` request: the most recent compile reports, oldest first
+pub enum CompileHistory {}
+
+impl Request for CompileHistory {
+    type Params = ();
+    type Result = Vec<CompileReport>;
+    const METHOD: &'static str = "gren/compileHistory";
+}
+
+/// `gren/runtimeLog` notification from a runtime harness: a value the running program
+/// logged with `Debug.log`
+pub enum RuntimeLogNotification {}
+
+impl Notification for RuntimeLogNotification {
+    type Params = RuntimeLogParams;
+    const METHOD: &'static str = "gren/runtimeLog";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeLogParams {
+    /// The tag passed to `Debug.log`
+    pub tag: String,
+    /// The logged value as the program printed it
+    pub value: String,
+}
+
+/// `gren/activeDocument` notification from the client: the document the user is looking
+/// at, diagnosed first when many documents are opened at once
+pub enum ActiveDocumentNotification {}
+
+impl Notification for ActiveDocumentNotification {
+    type Params = ActiveDocumentParams;
+    const METHOD: &'static str = "gren/activeDocument";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveDocumentParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// `gren/indexStatus` notification, sent when files start or stop waiting to be
+/// re-indexed. While any are pending, answers built from the index, like references and
+/// renames, may miss what changed in them.
+pub enum IndexStatusNotification {}
+
+impl Notification for IndexStatusNotification {
+    type Params = IndexStatusParams;
+    const METHOD: &'static str = "gren/indexStatus";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStatusParams {
+    /// Generation of the most recent indexing of any file
+    pub generation: u64,
+    /// Files an edit changed whose new content hasn't been indexed yet
+    pub pending_files: Vec<Url>,
+}
+
+/// `gren/indexFreshness` request: how current the index is for one document
+pub enum IndexFreshness {}
+
+impl Request for IndexFreshness {
+    type Params = IndexFreshnessParams;
+    type Result = IndexFreshnessResult;
+    const METHOD: &'static str = "gren/indexFreshness";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexFreshnessParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexFreshnessResult {
+    /// Generation the document was last indexed at, `None` if it isn't indexed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation: Option<u64>,
+    /// Generation of the most recent indexing of any file
+    pub workspace_generation: u64,
+    /// The document changed since it was last indexed
+    pub pending: bool,
+    /// Every file waiting to be re-indexed
+    pub pending_files: Vec<Url>,
+    /// The document is indexed with only the signatures of what it exposes, as
+    /// `index.signatures_only` in `.gren-lsp.toml` asks, so its private declarations and
+    /// the references in its bodies aren't found
+    #[serde(default)]
+    pub signatures_only: bool,
+}
+
+/// `gren/openModule` request: the files declaring modules whose names match what the user
+/// typed, best matches first, for a "go to module" picker
+pub enum OpenModule {}
+
+impl Request for OpenModule {
+    type Params = OpenModuleParams;
+    type Result = Vec<ModuleMatch>;
+    const METHOD: &'static str = "gren/openModule";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenModuleParams {
+    /// A module name, or an abbreviation of one such as `D.Li` for `Data.List`
+    pub module_name: String,
+    /// Maximum number of matches, defaults to the server's configured limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleMatch {
+    pub module_name: String,
+    /// The name in the module declaration of the file declaring it
+    pub location: Location,
+}
+
+/// `gren/moduleExports` request: every top-level declaration of a module, whether it's
+/// exposed and how often other modules use it, for an export management picker
+pub enum ModuleExports {}
+
+impl Request for ModuleExports {
+    type Params = ModuleExportsParams;
+    type Result = Option<ModuleExportsResult>;
+    const METHOD: &'static str = "gren/moduleExports";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleExportsParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleExportsResult {
+    pub module_name: String,
+    /// The module declaration says `exposing (..)`
+    pub exposes_everything: bool,
+    /// In source order
+    pub declarations: Vec<ModuleExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleExport {
+    pub name: String,
+    pub kind: ExportKind,
+    pub exposed: bool,
+    /// Uses in the modules importing this one
+    pub external_usages: usize,
+}
+
+/// Kind of a top-level declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportKind {
+    Value,
+    Type,
+    Alias,
+    Port,
+}
+
+/// `gren/findValuesOfType` request: every top-level function and value whose signature
+/// mentions a type, for discovering the APIs that produce or consume it
+pub enum FindValuesOfType {}
+
+impl Request for FindValuesOfType {
+    type Params = FindValuesOfTypeParams;
+    type Result = Vec<ValueOfType>;
+    const METHOD: &'static str = "gren/findValuesOfType";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FindValuesOfTypeParams {
+    /// A type or alias name, qualified like `Dict.Dict` to leave out same-named types of
+    /// other modules
+    pub type_name: String,
+    /// Maximum number of values, defaults to the server's configured limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueOfType {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    pub signature: String,
+    pub location: Location,
+    /// The type is in a parameter
+    pub consumes: bool,
+    /// The type is in the result
+    pub produces: bool,
+}
+
+/// `gren/alternatePosition` request: where to jump from a function's type annotation to
+/// its definition and back, or from a constructor to its type, for a toggle keybinding
+pub enum AlternatePosition {}
+
+impl Request for AlternatePosition {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<Location>;
+    const METHOD: &'static str = "gren/alternatePosition";
+}
+
+/// `gren/indexStats` request: how big the symbol index is, and its size before and after
+/// it was converted to the compact layout
+pub enum IndexStatsRequest {}
+
+impl Request for IndexStatsRequest {
+    type Params = ();
+    type Result = IndexStats;
+    const METHOD: &'static str = "gren/indexStats";
+}
+
+/// `gren/requestStats` request: per request method, how many requests were answered in
+/// time and how many ran out of time, to find the features that are slow on a workspace
+pub enum RequestStatsRequest {}
+
+impl Request for RequestStatsRequest {
+    type Params = ();
+    type Result = Vec<RequestMethodStats>;
+    const METHOD: &'static str = "gren/requestStats";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMethodStats {
+    pub method: String,
+    /// Deadline in milliseconds, `None` when requests may take as long as they need
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Requests answered before their deadline
+    pub handled: u64,
+    pub timed_out: u64,
+    /// Longest a request answered in time took, in milliseconds
+    pub slowest_ms: u64,
+}
+
+/// `textDocument/inlineCompletion` request: text to show after the cursor as ghost text.
+/// New in LSP 3.18, so lsp-types has neither the request nor a capability advertising it,
+/// and the server registers it after `initialized`.
+pub enum InlineCompletion {}
+
+impl Request for InlineCompletion {
+    type Params = InlineCompletionParams;
+    type Result = Option<Vec<InlineCompletionItem>>;
+    const METHOD: &'static str = "textDocument/inlineCompletion";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionParams {
+    #[serde(flatten)]
+    pub text_document_position: TextDocumentPositionParams,
+    pub context: InlineCompletionContext,
+    #[serde(flatten)]
+    pub work_done_progress_params: WorkDoneProgressParams,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionContext {
+    pub trigger_kind: InlineCompletionTriggerKind,
+    /// The item selected in the completion list the editor is showing, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected_completion_info: Option<SelectedCompletionInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InlineCompletionTriggerKind(i32);
+
+impl InlineCompletionTriggerKind {
+    /// Asked for explicitly
+    pub const INVOKED: Self = Self(1);
+    /// Asked for while typing
+    pub const AUTOMATIC: Self = Self(2);
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectedCompletionInfo {
+    pub range: Range,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionItem {
+    /// Replaces `range`, and has to start with the text already in it
+    pub insert_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+}
+
+/// `gren/setTrace` notification from the client: log one subsystem verbosely, or stop,
+/// without touching how much the rest of the server logs
+pub enum SetSubsystemTraceNotification {}
+
+impl Notification for SetSubsystemTraceNotification {
+    type Params = SetSubsystemTraceParams;
+    const METHOD: &'static str = "gren/setTrace";
+}
+
+/// Part of the server whose logging can be turned up on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TraceSubsystem {
+    /// Completion requests
+    Completion,
+    /// Building and querying the symbol index
+    Index,
+    /// Compiler runs
+    Compile,
+    /// Renames, including module renames
+    Rename,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSubsystemTraceParams {
+    pub subsystem: TraceSubsystem,
+    /// Whether to log the subsystem at trace level
+    pub enabled: bool,
+}
+
+/// `workspace/executeCommand` command adding a dependency to `gren.json`
+pub const ADD_DEPENDENCY_COMMAND: &str = "gren.addDependency";
+
+/// `workspace/executeCommand` command removing a dependency from `gren.json`
+pub const REMOVE_DEPENDENCY_COMMAND: &str = "gren.removeDependency";
+
+/// `workspace/executeCommand` command reverting the last edit the server applied
+pub const UNDO_LAST_SERVER_EDIT_COMMAND: &str = "gren.undoLastServerEdit";
+
+/// `workspace/executeCommand` command swapping the top-level declaration at a position with
+/// the one above or below it
+pub const MOVE_DECLARATION_COMMAND: &str = "gren.moveDeclaration";
+
+/// `workspace/executeCommand` command replacing every string literal in the project that
+/// has the same value as the one at a position
+pub const RENAME_STRING_LITERAL_COMMAND: &str = "gren.renameStringLiteral";
+
+/// `workspace/executeCommand` command adding names to and removing names from a module's
+/// exposing list in one edit
+pub const EDIT_EXPOSING_COMMAND: &str = "gren.editExposing";
+
+/// `workspace/executeCommand` command running the formatter over every project file
+/// without writing, reporting the unformatted ones as diagnostics
+pub const CHECK_FORMATTING_COMMAND: &str = "gren.checkFormatting";
+
+/// `workspace/executeCommand` command formatting every project file in one edit
+pub const FORMAT_WORKSPACE_COMMAND: &str = "gren.formatWorkspace";
+
+/// Client command opening a picker of a module's exposed declarations. Code lenses only
+/// use it when the client lists it in `experimental.commands.commands`.
+pub const MANAGE_EXPORTS_COMMAND: &str = "grenLsp.manageExports";
+
+/// Source action sorting a module's top-level declarations alphabetically
+pub const SOURCE_SORT_DECLARATIONS: CodeActionKind = CodeActionKind::new("source.sortDeclarations");
+
+/// Source actions running `gren.moveDeclaration`
+pub const SOURCE_MOVE_DECLARATION: CodeActionKind = CodeActionKind::new("source.moveDeclaration");
+
+/// The single argument of `gren.addDependency` and `gren.removeDependency`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyCommandArguments {
+    /// `author/name` of the package
+    pub package: String,
+    /// Version to depend on, required when adding
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Whether to have the compiler fetch packages after editing `gren.json`
+    #[serde(default = "default_fetch")]
+    pub fetch: bool,
+}
+
+fn default_fetch() -> bool {
+    true
+}
+
+/// The single argument of `gren.moveDeclaration`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveDeclarationArguments {
+    pub text_document: TextDocumentIdentifier,
+    /// Anywhere in the declaration, its doc comment or its annotation
+    pub position: Position,
+    pub direction: MoveDirection,
+}
+
+/// The single argument of `gren.renameStringLiteral`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameStringLiteralArguments {
+    pub text_document: TextDocumentIdentifier,
+    /// Anywhere in the string literal
+    pub position: Position,
+    /// The string the literals should produce, without quotes or escapes
+    pub new_value: String,
+}
+
+/// The single argument of `gren.editExposing`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditExposingArguments {
+    pub text_document: TextDocumentIdentifier,
+    /// Names to add to the exposing list
+    #[serde(default)]
+    pub expose: Vec<String>,
+    /// Names to remove from the exposing list
+    #[serde(default)]
+    pub unexpose: Vec<String>,
+    /// Also expose every declaration that importing modules use but the module doesn't
+    /// expose
+    #[serde(default)]
+    pub expose_used: bool,
+}
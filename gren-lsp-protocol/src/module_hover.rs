@@ -0,0 +1,165 @@
+//! Hovers on the module qualifier of a qualified name
+//!
+//! In `Json.Decode.field` the cursor may be on `Json` or `Decode` rather than on `field`.
+//! The whole qualifier then names a module, or an import alias of one, and the hover
+//! describes the module: the first line of its documentation and how much it exposes.
+
+use gren_lsp_core::ModuleExports;
+use tree_sitter::{Node, Tree};
+
+/// The qualifier of the qualified name the cursor at byte `offset` is on, when the cursor
+/// is on one of its segments rather than on the name it qualifies
+pub fn qualifier_at(tree: &Tree, source: &str, offset: usize) -> Option<String> {
+    let segment = tree
+        .root_node()
+        .descendant_for_byte_range(offset, offset + 1)
+        .filter(|segment| segment.kind() == "upper_case_identifier")?;
+    let qid = segment
+        .parent()
+        .filter(|parent| matches!(parent.kind(), "value_qid" | "upper_case_qid"))?;
+    // Import and module declarations name whole modules, there's nothing they qualify
+    if qid
+        .parent()
+        .is_some_and(|parent| matches!(parent.kind(), "import_clause" | "module_declaration"))
+    {
+        return None;
+    }
+
+    let mut cursor = qid.walk();
+    let segments: Vec<Node> = qid
+        .named_children(&mut cursor)
+        .filter(|child| {
+            matches!(
+                child.kind(),
+                "upper_case_identifier" | "lower_case_identifier"
+            )
+        })
+        .collect();
+    // The last segment is what's qualified, the segments before it the module
+    let (_, qualifier) = segments.split_last()?;
+    if !qualifier.contains(&segment) {
+        return None;
+    }
+    let names: Option<Vec<&str>> = qualifier
+        .iter()
+        .map(|segment| segment.utf8_text(source.as_bytes()).ok())
+        .collect();
+    Some(names?.join("."))
+}
+
+/// Hover text describing a module
+pub fn module_hover_content(
+    module_name: &str,
+    documentation: Option<&str>,
+    exports: Option<&ModuleExports>,
+    supports_markdown: bool,
+) -> String {
+    let mut content = Vec::new();
+    if supports_markdown {
+        content.push(format!("*module* **{}**", module_name));
+    } else {
+        content.push(format!("module {}", module_name));
+    }
+
+    let summary = documentation.and_then(|documentation| {
+        documentation
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+    });
+    if let Some(summary) = summary {
+        content.push(summary.to_string());
+    }
+
+    if let Some(exports) = exports {
+        content.push(format!(
+            "Exposes {} and {}",
+            count(exports.values.len(), "value"),
+            count(exports.types.len(), "type")
+        ));
+    }
+
+    let separator = if supports_markdown { "\n\n" } else { "\n" };
+    content.join(separator)
+}
+
+fn count(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    /// The qualifier at the `|` marking the cursor in `line`, the last line of a value
+    fn qualifier(line: &str) -> Option<String> {
+        let source = format!("module Main exposing (..)\n\nvalue =\n{}", line);
+        let offset = source.find('|').unwrap();
+        let source = source.replace('|', "");
+        let tree = Parser::new().unwrap().parse(&source).unwrap().unwrap();
+        qualifier_at(&tree, &source, offset)
+    }
+
+    #[test]
+    fn test_qualifier_at() {
+        assert_eq!(
+            qualifier("    |Json.Decode.field \"name\" Json.Decode.string"),
+            Some("Json.Decode".to_string())
+        );
+        assert_eq!(
+            qualifier("    Json.De|code.field \"é\" Json.Decode.string"),
+            Some("Json.Decode".to_string())
+        );
+        assert_eq!(
+            qualifier("    \"é\" ++ Json.|Decode.string"),
+            Some("Json.Decode".to_string())
+        );
+        // On the function and on the dot before it
+        assert_eq!(qualifier("    Json.Decode.|field \"name\""), None);
+        assert_eq!(qualifier("    Json.Decode|.field \"name\""), None);
+
+        assert_eq!(qualifier("    |D.field"), Some("D".to_string()));
+        assert_eq!(qualifier("    Maybe|.Just 1"), None);
+        assert_eq!(qualifier("    |Maybe.Just 1"), Some("Maybe".to_string()));
+        assert_eq!(qualifier("    model|.count"), None);
+        assert_eq!(qualifier("    fi|eld"), None);
+    }
+
+    #[test]
+    fn test_qualifier_at_in_declarations() {
+        let source = "module Main exposing (..)\n\nimport Json.Decode as D\n\nview : Html.Html msg\nview =\n    D.value\n";
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        let at = |text: &str| qualifier_at(&tree, source, source.find(text).unwrap());
+        assert_eq!(at("Html.Html"), Some("Html".to_string()));
+        assert_eq!(at("Html msg"), None);
+        assert_eq!(at("Json.Decode"), None);
+        assert_eq!(at("Main"), None);
+    }
+
+    #[test]
+    fn test_module_hover_content() {
+        let exports = ModuleExports {
+            values: ["field", "string"].iter().map(|v| v.to_string()).collect(),
+            types: ["Decoder"].iter().map(|v| v.to_string()).collect(),
+            constructors: Default::default(),
+        };
+        assert_eq!(
+            module_hover_content(
+                "Json.Decode",
+                Some("Turn JSON values into Gren values.\nMore detail."),
+                Some(&exports),
+                true
+            ),
+            "*module* **Json.Decode**\n\nTurn JSON values into Gren values.\n\nExposes 2 values and 1 type"
+        );
+        assert_eq!(
+            module_hover_content("Utils", None, None, false),
+            "module Utils"
+        );
+    }
+}
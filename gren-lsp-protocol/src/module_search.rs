@@ -0,0 +1,123 @@
+//! Matching module names against what the user typed in a module picker
+//!
+//! Module names are searched by their dotted segments, so `D.Li` finds `Data.List` and
+//! `Li` finds both `Data.List` and `Html.Lazy.List`. Anything else whose letters appear in
+//! order, like `dtls` for `Data.List`, still matches, ranked below those.
+
+/// How well a module name matches a query, better matches sorting first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchQuality {
+    Exact,
+    ExactIgnoringCase,
+    /// Each segment of the query starts a segment of the name, in order, the last one
+    /// starting the name's last segment
+    SegmentPrefixes,
+    /// The letters of the query appear in the name in order
+    Subsequence,
+}
+
+/// Module names matching `query`, best first. Among equally good matches shorter names
+/// come first, then alphabetical order.
+pub fn matching_modules<'a>(query: &str, module_names: &'a [String]) -> Vec<&'a str> {
+    let query = query.trim();
+    let mut matches: Vec<(MatchQuality, &str)> = module_names
+        .iter()
+        .filter_map(|name| Some((match_quality(query, name)?, name.as_str())))
+        .collect();
+    matches.sort_by(|(a_quality, a_name), (b_quality, b_name)| {
+        a_quality
+            .cmp(b_quality)
+            .then(a_name.len().cmp(&b_name.len()))
+            .then(a_name.cmp(b_name))
+    });
+    matches.into_iter().map(|(_, name)| name).collect()
+}
+
+fn match_quality(query: &str, name: &str) -> Option<MatchQuality> {
+    if query.is_empty() {
+        return Some(MatchQuality::Subsequence);
+    }
+    if query == name {
+        return Some(MatchQuality::Exact);
+    }
+    if query.eq_ignore_ascii_case(name) {
+        return Some(MatchQuality::ExactIgnoringCase);
+    }
+    if segment_prefixes(query, name) {
+        return Some(MatchQuality::SegmentPrefixes);
+    }
+    subsequence(query, name).then_some(MatchQuality::Subsequence)
+}
+
+fn segment_prefixes(query: &str, name: &str) -> bool {
+    let query_segments: Vec<String> = query.split('.').map(str::to_lowercase).collect();
+    let name_segments: Vec<String> = name.split('.').map(str::to_lowercase).collect();
+    let (last_query, leading_query) = match query_segments.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+    let (last_name, leading_name) = match name_segments.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+    if last_query.is_empty() || !last_name.starts_with(last_query.as_str()) {
+        return false;
+    }
+
+    let mut remaining = leading_name.iter();
+    leading_query.iter().all(|segment| {
+        !segment.is_empty()
+            && remaining.any(|name_segment| name_segment.starts_with(segment.as_str()))
+    })
+}
+
+fn subsequence(query: &str, name: &str) -> bool {
+    let mut name_chars = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .filter(|c| *c != '.')
+        .flat_map(char::to_lowercase)
+        .all(|wanted| name_chars.any(|c| c == wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules() -> Vec<String> {
+        [
+            "Data.List",
+            "Data.Dict",
+            "Html.Lazy.List",
+            "List",
+            "Main",
+            "Dom.Listener",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_segment_prefixes_rank_above_subsequences() {
+        let modules = modules();
+        assert_eq!(
+            matching_modules("D.Li", &modules),
+            vec!["Data.List", "Dom.Listener"]
+        );
+        assert_eq!(
+            matching_modules("Li", &modules),
+            vec!["List", "Data.List", "Dom.Listener", "Html.Lazy.List"]
+        );
+        assert_eq!(matching_modules("dtls", &modules), vec!["Data.List"]);
+    }
+
+    #[test]
+    fn test_exact_match_first() {
+        let modules = modules();
+        assert_eq!(matching_modules("list", &modules)[0], "List");
+        assert_eq!(matching_modules("Main", &modules), vec!["Main"]);
+        assert_eq!(matching_modules("", &modules).len(), modules.len());
+        assert!(matching_modules("Xyz", &modules).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+//! Links from dependency symbols to their published documentation
+
+use gren_lsp_core::manifest::is_version;
+use gren_lsp_core::Symbol;
+use lsp_types::SymbolKind;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+const PACKAGE_SITE: &str = "https://packages.gren-lang.org/package";
+
+/// A module of a published package, as located in the package cache
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageModule {
+    pub author: String,
+    pub name: String,
+    pub version: String,
+    pub module_name: String,
+}
+
+impl PackageModule {
+    /// Documentation page for this module
+    pub fn docs_url(&self) -> String {
+        format!(
+            "{}/{}/{}/version/{}/module/{}",
+            PACKAGE_SITE, self.author, self.name, self.version, self.module_name
+        )
+    }
+}
+
+/// Documentation URL for a symbol defined in a dependency, or `None` when the symbol
+/// isn't from a package whose published version is known
+pub fn docs_url_for_symbol(symbol: &Symbol) -> Option<String> {
+    let path = symbol.location.uri.to_file_path().ok()?;
+    let module = package_module_for_path(&path)?;
+    let url = module.docs_url();
+
+    let anchor = match symbol.kind {
+        SymbolKind::MODULE => return Some(url),
+        // Constructors are documented together with their type
+        SymbolKind::CONSTRUCTOR => symbol.container_name.as_deref()?,
+        _ => symbol.name.as_str(),
+    };
+
+    Some(format!("{}#{}", url, anchor))
+}
+
+/// Work out which package module a file belongs to. Files are only considered part of a
+/// published package when they live in the package cache
+/// (`packages/<author>/<name>[/<version>]/src/...`), which is only filled from the
+/// package registry. When the cache path has no version, the exact version is read from
+/// the project's `gren.json`.
+pub fn package_module_for_path(path: &Path) -> Option<PackageModule> {
+    let components: Vec<&str> = path.iter().filter_map(|c| c.to_str()).collect();
+    let packages_index = components.iter().rposition(|c| *c == "packages")?;
+    let rest = &components[packages_index + 1..];
+
+    let author = rest.first()?;
+    let name = rest.get(1)?;
+    let (version, module_start) = match rest.get(2) {
+        Some(segment) if is_version(segment) => (Some(segment.to_string()), 3),
+        _ => (None, 2),
+    };
+
+    if rest.get(module_start) != Some(&"src") {
+        return None;
+    }
+
+    let module_segments = &rest[module_start + 1..];
+    let file_name = module_segments.last()?;
+    let last_segment = file_name.strip_suffix(".gren")?;
+    let mut module_parts: Vec<&str> = module_segments[..module_segments.len() - 1].to_vec();
+    module_parts.push(last_segment);
+
+    let version = match version {
+        Some(version) => version,
+        None => {
+            let packages_dir: PathBuf = components[..=packages_index].iter().collect();
+            dependency_version(&packages_dir, author, name)?
+        }
+    };
+
+    Some(PackageModule {
+        author: author.to_string(),
+        name: name.to_string(),
+        version,
+        module_name: module_parts.join("."),
+    })
+}
+
+/// `author/name` of the package a file in the package cache belongs to, whether or not
+/// its version is known
+pub fn package_name_for_path(path: &Path) -> Option<String> {
+    let components: Vec<&str> = path.iter().filter_map(|c| c.to_str()).collect();
+    let packages_index = components.iter().rposition(|c| *c == "packages")?;
+    let author = components.get(packages_index + 1)?;
+    let name = components.get(packages_index + 2)?;
+    Some(format!("{}/{}", author, name))
+}
+
+/// Exact dependency versions read from `gren.json`, per package cache directory
+type DependencyVersions = HashMap<PathBuf, HashMap<String, String>>;
+
+/// Manifests read so far, so resolving every symbol of a dependency doesn't read and
+/// parse the project's `gren.json` again
+fn dependency_versions() -> &'static Mutex<DependencyVersions> {
+    static VERSIONS: OnceLock<Mutex<DependencyVersions>> = OnceLock::new();
+    VERSIONS.get_or_init(Default::default)
+}
+
+/// Forget the versions read from the `gren.json` at `manifest`, after it changed
+pub fn forget_dependency_versions(manifest: &Path) {
+    let Some(project_dir) = manifest.parent() else {
+        return;
+    };
+    dependency_versions()
+        .lock()
+        .unwrap()
+        .retain(|packages_dir, _| !packages_dir.starts_with(project_dir));
+}
+
+/// Look up the exact version of a dependency in the `gren.json` of the project owning a
+/// package cache directory. Package projects declare version ranges, which don't say which
+/// version's docs apply, so only exact versions are returned.
+fn dependency_version(packages_dir: &Path, author: &str, name: &str) -> Option<String> {
+    let package = format!("{}/{}", author, name);
+    let mut versions = dependency_versions().lock().unwrap();
+    versions
+        .entry(packages_dir.to_path_buf())
+        .or_insert_with(|| read_dependency_versions(packages_dir))
+        .get(&package)
+        .cloned()
+}
+
+/// Exact versions of the dependencies in the `gren.json` nearest above `packages_dir`
+fn read_dependency_versions(packages_dir: &Path) -> HashMap<String, String> {
+    for project_dir in packages_dir.ancestors().skip(1) {
+        let gren_json = project_dir.join("gren.json");
+        let content = match std::fs::read_to_string(&gren_json) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        let manifest: serde_json::Value = match serde_json::from_str(&content) {
+            Ok(manifest) => manifest,
+            Err(_) => break,
+        };
+        let Some(dependencies) = manifest.get("dependencies") else {
+            break;
+        };
+        let mut versions = HashMap::new();
+        // A direct dependency's version wins over an indirect one
+        for group in ["indirect", "direct"] {
+            let Some(group) = dependencies.get(group).and_then(|group| group.as_object()) else {
+                continue;
+            };
+            for (package, version) in group {
+                if let Some(version) = version.as_str().filter(|version| is_version(version)) {
+                    versions.insert(package.clone(), version.to_string());
+                }
+            }
+        }
+        return versions;
+    }
+
+    HashMap::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Location, Position, Range, Url};
+
+    fn symbol_at(path: &str, name: &str, kind: SymbolKind) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            kind,
+            location: Location::new(
+                Url::from_file_path(path).unwrap(),
+                Range::new(Position::new(0, 0), Position::new(0, 1)),
+            ),
+            container_name: None,
+            type_signature: None,
+            documentation: None,
+        }
+    }
+
+    #[test]
+    fn test_docs_url_from_versioned_cache_path() {
+        let symbol = symbol_at(
+            "/home/user/.cache/gren/0.6.0/packages/gren-lang/core/7.0.0/src/Array/Extra.gren",
+            "map",
+            SymbolKind::FUNCTION,
+        );
+        assert_eq!(
+            docs_url_for_symbol(&symbol).as_deref(),
+            Some(
+                "https://packages.gren-lang.org/package/gren-lang/core/version/7.0.0/module/Array.Extra#map"
+            )
+        );
+    }
+
+    #[test]
+    fn test_docs_url_uses_application_dependency_version() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("gren.json"),
+            r#"{ "type": "application", "dependencies": { "direct": {}, "indirect": { "gren-lang/url": "6.0.0" } } }"#,
+        )
+        .unwrap();
+
+        let path = dir.path().join(".gren/packages/gren-lang/url/src/Url.gren");
+        let module = package_module_for_path(&path).unwrap();
+        assert_eq!(module.version, "6.0.0");
+        assert_eq!(module.module_name, "Url");
+    }
+
+    #[test]
+    fn test_dependency_version_read_again_after_manifest_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("gren.json");
+        let write_version = |version: &str| {
+            let content = format!(
+                r#"{{ "type": "application", "dependencies": {{ "direct": {{ "gren-lang/url": "{}" }}, "indirect": {{}} }} }}"#,
+                version
+            );
+            std::fs::write(&manifest, content).unwrap();
+        };
+        let path = dir.path().join(".gren/packages/gren-lang/url/src/Url.gren");
+
+        write_version("6.0.0");
+        assert_eq!(package_module_for_path(&path).unwrap().version, "6.0.0");
+
+        write_version("6.1.0");
+        assert_eq!(package_module_for_path(&path).unwrap().version, "6.0.0");
+
+        forget_dependency_versions(&manifest);
+        assert_eq!(package_module_for_path(&path).unwrap().version, "6.1.0");
+    }
+
+    #[test]
+    fn test_no_docs_url_for_project_files_or_unknown_versions() {
+        let project_symbol = symbol_at("/project/src/Main.gren", "main", SymbolKind::FUNCTION);
+        assert_eq!(docs_url_for_symbol(&project_symbol), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("gren.json"),
+            r#"{ "type": "package", "dependencies": { "gren-lang/core": "7.0.0 <= v < 8.0.0" } }"#,
+        )
+        .unwrap();
+        let path = dir
+            .path()
+            .join(".gren/packages/gren-lang/core/src/Array.gren");
+        assert_eq!(package_module_for_path(&path), None);
+        assert_eq!(
+            package_name_for_path(&path).as_deref(),
+            Some("gren-lang/core")
+        );
+        assert_eq!(
+            package_name_for_path(Path::new("/project/src/Main.gren")),
+            None
+        );
+    }
+}
@@ -0,0 +1,454 @@
+//! Constructor completion inside nested `when ... is` branch patterns
+//!
+//! For `Ok (Status |` the constructors that fit are those of the type of `Status`'s first
+//! argument, which in turn may depend on what `Ok` wraps. The enclosing constructors are
+//! read from the parse tree and their indexed signatures decomposed one level at a time,
+//! binding type variables along the way, until the type at the cursor is known.
+
+use gren_lsp_core::syntax::child_of_kind;
+use gren_lsp_core::Parser;
+use lsp_types::Position;
+use std::collections::HashMap;
+use tree_sitter::{Node, Tree};
+
+/// A constructor enclosing the cursor, and which of its arguments the cursor is in
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternFrame {
+    pub constructor: String,
+    pub argument: usize,
+}
+
+/// Structure of a type, as far as pattern completion needs it
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeShape {
+    Named {
+        name: String,
+        arguments: Vec<TypeShape>,
+    },
+    Variable(String),
+    /// Records, functions and anything not known
+    Other,
+}
+
+/// Constructors enclosing `position` in a `when` branch pattern, outermost first.
+///
+/// Returns `None` when the cursor isn't in a constructor argument of a branch pattern, or
+/// when the pattern uses forms this doesn't follow (records, arrays, `as`).
+pub fn nested_pattern_path(
+    tree: &Tree,
+    source: &str,
+    position: Position,
+) -> Option<Vec<PatternFrame>> {
+    let mut tokens = Vec::new();
+    collect_line_tokens(tree.root_node(), position, &mut tokens);
+
+    // A partially typed name at the cursor is what's being completed, not part of the path
+    if let Some(last) = tokens.last() {
+        let is_identifier = matches!(
+            last.kind(),
+            "upper_case_identifier" | "lower_case_identifier"
+        );
+        if is_identifier && last.end_position().column == position.character as usize {
+            tokens.pop();
+        }
+    }
+
+    let first = tokens.first()?;
+    if !is_in_when_branch(first) {
+        return None;
+    }
+
+    let source_bytes = source.as_bytes();
+    let mut frames = vec![OpenFrame::default()];
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let token = tokens[index];
+        match token.kind() {
+            "upper_case_identifier" => {
+                // `Module.Constructor` is matched by its last segment
+                let mut name = token.utf8_text(source_bytes).ok()?;
+                while index + 2 < tokens.len()
+                    && tokens[index + 1].kind() == "."
+                    && tokens[index + 2].kind() == "upper_case_identifier"
+                {
+                    index += 2;
+                    name = tokens[index].utf8_text(source_bytes).ok()?;
+                }
+
+                let frame = frames.last_mut()?;
+                if frame.head.is_none() {
+                    frame.head = Some(Some(name.to_string()));
+                } else {
+                    frame.arguments += 1;
+                }
+            }
+            "(" => {
+                let frame = frames.last()?;
+                // A parenthesised pattern is an argument of the enclosing constructor
+                frame.head.as_ref()?;
+                let parent_argument = frame.arguments;
+                frames.push(OpenFrame {
+                    parent_argument,
+                    ..OpenFrame::default()
+                });
+            }
+            ")" => {
+                if frames.len() == 1 {
+                    return None;
+                }
+                frames.pop();
+                frames.last_mut()?.arguments += 1;
+            }
+            // Past the pattern, or pattern forms that aren't followed
+            "arrow"
+            | "->"
+            | "eq"
+            | "="
+            | "operator_identifier"
+            | "["
+            | "]"
+            | "{"
+            | "}"
+            | ","
+            | "as"
+            | "|" => return None,
+            _ if token.is_named() => {
+                let frame = frames.last_mut()?;
+                if frame.head.is_none() {
+                    // A variable or literal pattern, nothing can follow it
+                    frame.head = Some(None);
+                } else {
+                    frame.arguments += 1;
+                }
+            }
+            _ => return None,
+        }
+        index += 1;
+    }
+
+    let mut path = Vec::new();
+    for (depth, frame) in frames.iter().enumerate() {
+        let argument = match frames.get(depth + 1) {
+            Some(inner) => inner.parent_argument,
+            None => frame.arguments,
+        };
+        match &frame.head {
+            Some(Some(constructor)) => path.push(PatternFrame {
+                constructor: constructor.clone(),
+                argument,
+            }),
+            // The cursor is right after `(`, so the position belongs to the outer frame
+            None if depth + 1 == frames.len() => {}
+            _ => return None,
+        }
+    }
+
+    if path.is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Name of the type expected at the end of `path`, found by decomposing the signatures of
+/// the enclosing constructors. `signature_of` gives a constructor's indexed signature.
+///
+/// Returns `None` when the type depends on something that isn't known, e.g. a type
+/// variable bound by the value being matched.
+pub fn expected_pattern_type(
+    path: &[PatternFrame],
+    signature_of: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let mut parser = Parser::new().ok()?;
+    let mut expected = TypeShape::Other;
+
+    for frame in path {
+        let signature = signature_of(&frame.constructor)?;
+        let (arguments, result) = parse_signature(&mut parser, &signature)?;
+
+        let mut bindings = HashMap::new();
+        if !unify(&result, &expected, &mut bindings) {
+            return None;
+        }
+
+        expected = substitute(arguments.get(frame.argument)?, &bindings);
+    }
+
+    match expected {
+        TypeShape::Named { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct OpenFrame {
+    /// `Some(Some(name))` for a constructor pattern, `Some(None)` for any other pattern and
+    /// `None` when nothing has been written yet
+    head: Option<Option<String>>,
+    arguments: usize,
+    /// Argument of the enclosing frame this frame fills
+    parent_argument: usize,
+}
+
+/// Leaf tokens on the cursor's line that end at or before the cursor
+fn collect_line_tokens<'a>(node: Node<'a>, position: Position, tokens: &mut Vec<Node<'a>>) {
+    let line = position.line as usize;
+    if node.start_position().row > line || node.end_position().row < line {
+        return;
+    }
+
+    if node.child_count() == 0 {
+        let on_line = node.start_position().row == line;
+        let before_cursor = node.end_position().column <= position.character as usize;
+        // Tokens the parser made up to recover from the incomplete pattern aren't in the source
+        let written = !node.is_missing() && node.start_byte() < node.end_byte();
+        if on_line && before_cursor && written && !node.kind().ends_with("comment") {
+            tokens.push(node);
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_line_tokens(child, position, tokens);
+    }
+}
+
+/// Whether a token starting a line begins a branch of a `when ... is` expression
+fn is_in_when_branch(token: &Node) -> bool {
+    let mut current = token.parent();
+    while let Some(node) = current {
+        match node.kind() {
+            "case_of_expr" | "case_of_branch" => return true,
+            // Incomplete branches often end up in an error node, which still holds the
+            // `is` of the expression header
+            "ERROR" => {
+                let mut cursor = node.walk();
+                let has_header = node.children(&mut cursor).any(|child| {
+                    child.kind() == "is" && child.end_position().row < token.start_position().row
+                });
+                if has_header {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        current = node.parent();
+    }
+    false
+}
+
+/// Split a signature into argument types and result type, using the parser on a synthetic
+/// annotation so the type structure comes from the grammar
+pub fn parse_signature(
+    parser: &mut Parser,
+    signature: &str,
+) -> Option<(Vec<TypeShape>, TypeShape)> {
+    let source = format!(
+        "module Signature exposing (..)\n\nsignature : {}\n",
+        signature
+    );
+    let tree = parser.parse(&source).ok()??;
+    let root = tree.root_node();
+
+    let mut cursor = root.walk();
+    let annotation = root
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "type_annotation")?;
+    let type_expression = child_of_kind(&annotation, "type_expression")?;
+    if type_expression.has_error() {
+        return None;
+    }
+
+    let mut cursor = type_expression.walk();
+    let mut parts: Vec<TypeShape> = type_expression
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() != "arrow")
+        .map(|node| type_shape(node, &source))
+        .collect();
+
+    let result = parts.pop()?;
+    Some((parts, result))
+}
+
+/// The structure of a type written in `source`, e.g. a part of an annotation
+pub fn type_shape(node: Node, source: &str) -> TypeShape {
+    let source_bytes = source.as_bytes();
+    match node.kind() {
+        "type_ref" => {
+            let mut cursor = node.walk();
+            let mut children = node.named_children(&mut cursor);
+            let name = match children
+                .next()
+                .and_then(|qid| qid.utf8_text(source_bytes).ok())
+            {
+                Some(name) => name.rsplit('.').next().unwrap_or(name).to_string(),
+                None => return TypeShape::Other,
+            };
+            let arguments = children.map(|child| type_shape(child, source)).collect();
+            TypeShape::Named { name, arguments }
+        }
+        "type_variable" => match node.utf8_text(source_bytes) {
+            Ok(name) => TypeShape::Variable(name.to_string()),
+            Err(_) => TypeShape::Other,
+        },
+        _ => {
+            // Parenthesised types wrap a single type expression without arrows
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            let has_arrow = children.iter().any(|child| child.kind() == "arrow");
+            match children.as_slice() {
+                [inner] if !has_arrow => type_shape(*inner, source),
+                _ => TypeShape::Other,
+            }
+        }
+    }
+}
+
+/// Bind the type variables of a constructor's result type against the expected type.
+/// Returns false when the two can't be the same type.
+fn unify(
+    pattern: &TypeShape,
+    expected: &TypeShape,
+    bindings: &mut HashMap<String, TypeShape>,
+) -> bool {
+    match (pattern, expected) {
+        (TypeShape::Variable(name), _) => {
+            if !matches!(expected, TypeShape::Other | TypeShape::Variable(_)) {
+                bindings.insert(name.clone(), expected.clone());
+            }
+            true
+        }
+        (
+            TypeShape::Named { name, arguments },
+            TypeShape::Named {
+                name: expected_name,
+                arguments: expected_arguments,
+            },
+        ) => {
+            name == expected_name
+                && arguments.len() == expected_arguments.len()
+                && arguments
+                    .iter()
+                    .zip(expected_arguments)
+                    .all(|(argument, expected)| unify(argument, expected, bindings))
+        }
+        // Nothing is known about the expected type
+        _ => true,
+    }
+}
+
+fn substitute(shape: &TypeShape, bindings: &HashMap<String, TypeShape>) -> TypeShape {
+    match shape {
+        TypeShape::Variable(name) => bindings.get(name).cloned().unwrap_or(TypeShape::Other),
+        TypeShape::Named { name, arguments } => TypeShape::Named {
+            name: name.clone(),
+            arguments: arguments
+                .iter()
+                .map(|argument| substitute(argument, bindings))
+                .collect(),
+        },
+        TypeShape::Other => TypeShape::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_at(source: &str, line: u32, character: u32) -> Option<Vec<PatternFrame>> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        nested_pattern_path(&tree, source, Position::new(line, character))
+    }
+
+    fn signatures(name: &str) -> Option<String> {
+        match name {
+            "Ok" => Some("value -> Result error value".to_string()),
+            "Just" => Some("a -> Maybe a".to_string()),
+            "Status" => Some("Code -> String -> Response".to_string()),
+            "Wrapped" => Some("Maybe Code -> Box".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_nested_pattern_path() {
+        let source = r#"module Main exposing (..)
+
+describe result =
+    when result is
+        Ok (Status
+"#;
+        let path = path_at(source, 4, 19).unwrap();
+        assert_eq!(
+            path,
+            vec![
+                PatternFrame {
+                    constructor: "Ok".to_string(),
+                    argument: 0
+                },
+                PatternFrame {
+                    constructor: "Status".to_string(),
+                    argument: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_path_outside_patterns() {
+        let source = r#"module Main exposing (..)
+
+describe result =
+    when result is
+        Ok value -> Just (Status
+"#;
+        assert_eq!(path_at(source, 4, 33), None);
+    }
+
+    #[test]
+    fn test_expected_type_from_constructor_arguments() {
+        let path = vec![
+            PatternFrame {
+                constructor: "Ok".to_string(),
+                argument: 0,
+            },
+            PatternFrame {
+                constructor: "Status".to_string(),
+                argument: 0,
+            },
+        ];
+        assert_eq!(
+            expected_pattern_type(&path, signatures).as_deref(),
+            Some("Code")
+        );
+    }
+
+    #[test]
+    fn test_expected_type_binds_type_variables() {
+        // `Wrapped (Just |`: `Just`'s argument is `a`, bound to `Code` by `Maybe Code`
+        let path = vec![
+            PatternFrame {
+                constructor: "Wrapped".to_string(),
+                argument: 0,
+            },
+            PatternFrame {
+                constructor: "Just".to_string(),
+                argument: 0,
+            },
+        ];
+        assert_eq!(
+            expected_pattern_type(&path, signatures).as_deref(),
+            Some("Code")
+        );
+
+        // With nothing known about what `Just` wraps, there's no answer
+        let unknown = vec![PatternFrame {
+            constructor: "Just".to_string(),
+            argument: 0,
+        }];
+        assert_eq!(expected_pattern_type(&unknown, signatures), None);
+    }
+}
@@ -0,0 +1,86 @@
+//! The module qualifier of a name being typed, e.g. `Json.Decode` for `Json.Decode.fie`
+//!
+//! Read from the parse tree's tokens before the cursor rather than the line's text, so it
+//! holds up while the name after the last dot is still missing and the tree has an error
+//! there. Record field access like `model.user.` has a lower-case segment and isn't a
+//! qualifier.
+
+use tree_sitter::{Node, Tree};
+
+/// The module path qualifying the name typed up to byte `offset`: the upper-case segments
+/// and dots right before the cursor, after the partially typed name itself
+pub fn qualifier_before(tree: &Tree, source: &str, offset: usize) -> Option<Vec<String>> {
+    let mut token = token_ending_at(tree, offset)?;
+    if matches!(
+        token.kind(),
+        "lower_case_identifier" | "upper_case_identifier"
+    ) {
+        token = token_ending_at(tree, token.start_byte())?;
+    }
+
+    let mut module_path = Vec::new();
+    while text(token, source) == Some(".") {
+        let segment = token_ending_at(tree, token.start_byte())
+            .filter(|segment| segment.kind() == "upper_case_identifier")?;
+        module_path.insert(0, text(segment, source)?.to_string());
+        token = match token_ending_at(tree, segment.start_byte()) {
+            Some(token) => token,
+            None => break,
+        };
+    }
+
+    if module_path.is_empty() {
+        None
+    } else {
+        Some(module_path)
+    }
+}
+
+/// The token the byte before `offset` belongs to: the one ending at `offset`, or the
+/// identifier the cursor is in. `None` when it's whitespace or there's nothing before it.
+fn token_ending_at(tree: &Tree, offset: usize) -> Option<Node> {
+    if offset == 0 {
+        return None;
+    }
+    tree.root_node()
+        .descendant_for_byte_range(offset - 1, offset)
+        .filter(|token| token.child_count() == 0)
+}
+
+fn text<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    node.utf8_text(source.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    /// The qualifier before the `|` marking the cursor
+    fn qualifier(line: &str) -> Option<Vec<String>> {
+        let source = format!("module Main exposing (..)\n\nvalue =\n{}", line);
+        let offset = source.find('|').unwrap();
+        let source = source.replace('|', "");
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(&source).unwrap().unwrap();
+        qualifier_before(&tree, &source, offset)
+    }
+
+    #[test]
+    fn test_qualifier_before() {
+        assert_eq!(qualifier("    Json.|"), Some(vec!["Json".to_string()]));
+        assert_eq!(
+            qualifier("    Json.Decode.fie|"),
+            Some(vec!["Json".to_string(), "Decode".to_string()])
+        );
+        assert_eq!(
+            qualifier("    \"é\" ++ Json.Decode.fie|"),
+            Some(vec!["Json".to_string(), "Decode".to_string()])
+        );
+        // Record field access is not a module access
+        assert_eq!(qualifier("    model.user.|"), None);
+        assert_eq!(qualifier("    Json|"), None);
+        assert_eq!(qualifier("    Json. |"), None);
+        assert_eq!(qualifier("    |"), None);
+    }
+}
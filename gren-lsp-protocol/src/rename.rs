@@ -0,0 +1,187 @@
+//! Where a rename can start, for `textDocument/prepareRename`
+//!
+//! The node at the cursor decides. Names of values, types, constructors and fields can be
+//! renamed, and the range of just the name comes back so the editor preselects it.
+//! Keywords, literals, operators and comments can't, and neither can a module name in a
+//! qualifier, a module declaration or an import: a module is renamed by moving its file.
+//! Refusals are `RequestFailed` errors whose message says why, which editors show instead
+//! of opening the rename box.
+
+use gren_lsp_core::syntax::{node_range, position_to_point};
+use lsp_types::{Position, Range};
+use tower_lsp::jsonrpc::{Error, ErrorCode};
+use tree_sitter::{Node, Tree};
+
+/// LSP's `RequestFailed`: the request was valid, but what it asks for can't be done
+pub const REQUEST_FAILED: i64 = -32803;
+
+/// Range of the name at `position` that a rename would change, or why there's nothing
+/// there to rename
+pub fn rename_range(tree: &Tree, source: &str, position: Position) -> Result<Range, String> {
+    let node = match node_at(tree, source, position) {
+        Some(node) => node,
+        None => return Err("Nothing to rename here".to_string()),
+    };
+    let text = node.utf8_text(source.as_bytes()).unwrap_or_default();
+
+    if node.kind().ends_with("comment") {
+        return Err("Names in comments can't be renamed".to_string());
+    }
+    if let Some(literal) = enclosing_literal(node) {
+        return Err(format!("{} can't be renamed", literal));
+    }
+    if node.kind() == "operator_identifier" || node.parent().map(|p| p.kind()) == Some("operator") {
+        return Err(format!("The operator `{}` can't be renamed", text));
+    }
+    if !node.is_named() {
+        if text.chars().all(char::is_alphabetic) && !text.is_empty() {
+            return Err(format!("`{}` is a keyword", text));
+        }
+        return Err("Nothing to rename here".to_string());
+    }
+    if !matches!(
+        node.kind(),
+        "lower_case_identifier" | "upper_case_identifier"
+    ) {
+        return Err("Nothing to rename here".to_string());
+    }
+
+    if let Some(parent) = node.parent() {
+        let is_module_name = parent.kind() == "upper_case_qid"
+            && matches!(
+                parent.parent().map(|grandparent| grandparent.kind()),
+                Some("module_declaration" | "import_clause")
+            );
+        if is_module_name {
+            return Err(format!(
+                "`{}` is part of a module name; rename the module's file instead",
+                text
+            ));
+        }
+        if parent.kind() == "as_clause" {
+            return Err(format!("The import alias `{}` can't be renamed", text));
+        }
+        let is_qualifier = matches!(parent.kind(), "upper_case_qid" | "value_qid")
+            && parent.named_child(parent.named_child_count().saturating_sub(1)) != Some(node);
+        if is_qualifier {
+            return Err(format!(
+                "`{}` is a module qualifier; rename the module's file instead",
+                text
+            ));
+        }
+    }
+
+    Ok(node_range(&node, source))
+}
+
+/// The error a refused rename answers with
+pub fn rename_rejected(message: String) -> Error {
+    Error {
+        code: ErrorCode::ServerError(REQUEST_FAILED),
+        message: message.into(),
+        data: None,
+    }
+}
+
+/// The leaf at `position`, or the one ending there when the cursor is right after a name
+fn node_at<'a>(tree: &'a Tree, source: &str, position: Position) -> Option<Node<'a>> {
+    let point = position_to_point(position, source);
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
+    if is_identifier(&node) || position.character == 0 {
+        return Some(node);
+    }
+    let before = position_to_point(Position::new(position.line, position.character - 1), source);
+    match tree.root_node().descendant_for_point_range(before, before) {
+        Some(previous) if is_identifier(&previous) => Some(previous),
+        _ => Some(node),
+    }
+}
+
+fn is_identifier(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        "lower_case_identifier" | "upper_case_identifier"
+    )
+}
+
+/// What kind of literal holds `node`, if one does
+fn enclosing_literal(node: Node) -> Option<&'static str> {
+    let mut current = Some(node);
+    while let Some(node) = current {
+        match node.kind() {
+            "string_constant_expr" => return Some("String literals"),
+            "char_constant_expr" => return Some("Character literals"),
+            "number_constant_expr" => return Some("Number literals"),
+            _ => {}
+        }
+        current = node.parent();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    const SOURCE: &str = r#"module Shapes.Circle exposing (area)
+
+import Dict as D
+
+-- the area of a circle
+area : Float -> Float
+area radius =
+    when radius is
+        0 ->
+            D.empty
+
+        _ ->
+            pi * radius * radius + String.length "radius"
+"#;
+
+    fn range_at(line: u32, character: u32) -> Result<Range, String> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        rename_range(&tree, SOURCE, Position::new(line, character))
+    }
+
+    #[test]
+    fn test_identifier_ranges() {
+        let area = Range::new(Position::new(6, 0), Position::new(6, 4));
+        assert_eq!(range_at(6, 2), Ok(area));
+        // Right after the name
+        assert_eq!(range_at(6, 4), Ok(area));
+        assert_eq!(
+            range_at(12, 45),
+            Ok(Range::new(Position::new(12, 42), Position::new(12, 48)))
+        );
+        assert_eq!(
+            range_at(9, 15),
+            Ok(Range::new(Position::new(9, 14), Position::new(9, 19)))
+        );
+    }
+
+    #[test]
+    fn test_ranges_after_non_ascii_text() {
+        let source = "module Main exposing (..)\n\ngreeting =\n    \"héllo 😀\" ++ name\n";
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+        // Columns count UTF-16 code units: é is one and 😀 two, though they take six bytes
+        let name = Range::new(Position::new(3, 18), Position::new(3, 22));
+        assert_eq!(rename_range(&tree, source, Position::new(3, 19)), Ok(name));
+        assert_eq!(rename_range(&tree, source, Position::new(3, 22)), Ok(name));
+    }
+
+    #[test]
+    fn test_refusals() {
+        assert_eq!(range_at(7, 5), Err("`when` is a keyword".to_string()));
+        assert!(range_at(8, 8).unwrap_err().starts_with("Number literals"));
+        assert!(range_at(12, 52).unwrap_err().starts_with("String literals"));
+        assert!(range_at(4, 6).unwrap_err().contains("comments"));
+        assert!(range_at(0, 9).unwrap_err().contains("module name"));
+        assert!(range_at(2, 8).unwrap_err().contains("module name"));
+        assert!(range_at(2, 15).unwrap_err().contains("alias"));
+        assert!(range_at(9, 12).unwrap_err().contains("qualifier"));
+        assert!(range_at(12, 37).unwrap_err().contains("qualifier"));
+    }
+}
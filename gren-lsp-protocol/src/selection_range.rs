@@ -0,0 +1,116 @@
+//! Selection ranges from the syntax tree
+//!
+//! Expanding the selection walks up from the innermost node at the cursor: an identifier,
+//! the expression around it, the `when` branch, the declaration and finally the module.
+//! Ancestors covering the same text as their child are skipped, so every step grows the
+//! selection, and a top-level value is selected together with its type annotation before
+//! the whole module.
+
+use gren_lsp_core::syntax::{node_range, position_to_point};
+use lsp_types::{Position, Range, SelectionRange};
+use tree_sitter::{Node, Tree};
+
+/// The selection range at `position`, innermost first, or `None` outside the tree
+pub fn selection_range(tree: &Tree, source: &str, position: Position) -> Option<SelectionRange> {
+    let point = position_to_point(position, source);
+    let innermost = tree
+        .root_node()
+        .named_descendant_for_point_range(point, point)?;
+
+    let mut ranges: Vec<Range> = Vec::new();
+    let mut node = Some(innermost);
+    while let Some(current) = node {
+        push_range(&mut ranges, node_range(&current, source));
+        if let Some(annotated) = with_annotation(current, source) {
+            push_range(&mut ranges, annotated);
+        }
+        node = current.parent();
+    }
+
+    ranges.into_iter().rev().fold(None, |parent, range| {
+        Some(SelectionRange {
+            range,
+            parent: parent.map(Box::new),
+        })
+    })
+}
+
+/// Add a range unless it covers no more than the last one
+fn push_range(ranges: &mut Vec<Range>, range: Range) {
+    if ranges.last() != Some(&range) {
+        ranges.push(range);
+    }
+}
+
+/// The range of a top-level value declaration and the type annotation right before it
+fn with_annotation(node: Node, source: &str) -> Option<Range> {
+    if node.kind() != "value_declaration" {
+        return None;
+    }
+    let mut previous = node.prev_named_sibling()?;
+    while matches!(previous.kind(), "line_comment" | "block_comment") {
+        previous = previous.prev_named_sibling()?;
+    }
+    if previous.kind() != "type_annotation" {
+        return None;
+    }
+    Some(Range::new(
+        node_range(&previous, source).start,
+        node_range(&node, source).end,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    const SOURCE: &str = "module Main exposing (update)\n\n\
+                          update : Msg -> Int\n\
+                          update msg =\n    \
+                          when msg is\n        \
+                          Increment ->\n            \
+                          add 1 2\n";
+
+    fn ranges(position: Position) -> Vec<Range> {
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(SOURCE).unwrap().unwrap();
+        let mut ranges = Vec::new();
+        let mut selection = selection_range(&tree, SOURCE, position);
+        while let Some(current) = selection {
+            ranges.push(current.range);
+            selection = current.parent.map(|parent| *parent);
+        }
+        ranges
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    #[test]
+    fn test_expands_to_declaration_and_module() {
+        let ranges = ranges(Position::new(6, 16));
+        // The argument, the call, then the branch
+        assert_eq!(ranges[0], range((6, 16), (6, 17)));
+        assert_eq!(ranges[1], range((6, 12), (6, 19)));
+        assert_eq!(ranges[2], range((5, 8), (6, 19)));
+        // The declaration, with its annotation, then the module
+        let declaration = ranges
+            .iter()
+            .position(|selected| *selected == range((3, 0), (6, 19)))
+            .unwrap();
+        assert_eq!(ranges[declaration + 1], range((2, 0), (6, 19)));
+        assert_eq!(ranges[declaration + 2].start, Position::new(0, 0));
+        assert_eq!(ranges.len(), declaration + 3);
+    }
+    #[test]
+    fn test_positions_after_non_ascii_text() {
+        let source = "module Main exposing (..)\n\ngreeting =\n    \"héllo 😀\" ++ name\n";
+        let tree = Parser::new().unwrap().parse(source).unwrap().unwrap();
+        // Columns count UTF-16 code units: é is one and 😀 two, though they take six bytes
+        let selection = selection_range(&tree, source, Position::new(3, 19)).unwrap();
+        assert_eq!(selection.range, range((3, 18), (3, 22)));
+        assert_eq!(selection.parent.unwrap().range, range((3, 4), (3, 22)));
+    }
+}
@@ -0,0 +1,431 @@
+//! Semantic tokens from the syntax tree
+//!
+//! Editors without a good Gren grammar, like Helix, can highlight from these instead. Each
+//! name is classified by where it occurs: types, constructors, values, operators, module
+//! qualifiers and record fields. Values that are top-level declarations, here or in an
+//! imported module, are functions, while parameters, `let` bindings and pattern variables
+//! are variables.
+//!
+//! Each full result is remembered per document, so a delta request can answer with the
+//! one stretch of tokens that changed since the result the client has.
+
+use gren_lsp_core::extract_references;
+use gren_lsp_core::syntax::child_of_kind;
+use lsp_types::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tree_sitter::{Node, Tree};
+
+/// Token types, in the order of the legend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Type,
+    Constructor,
+    Function,
+    Operator,
+    Namespace,
+    Field,
+    TypeParameter,
+    Variable,
+}
+
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::TYPE,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::TYPE_PARAMETER,
+    SemanticTokenType::VARIABLE,
+];
+
+/// Bit of the `declaration` modifier
+const DECLARATION: u32 = 1;
+
+/// The token types and modifiers the server uses
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: vec![SemanticTokenModifier::DECLARATION],
+    }
+}
+
+/// A token before encoding relative to the previous one
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    line: u32,
+    start: u32,
+    length: u32,
+    kind: TokenKind,
+    declaration: bool,
+}
+
+/// The semantic tokens of the module in `uri`, in the relative encoding of the protocol
+pub fn semantic_tokens(tree: &Tree, source: &str, uri: &Url) -> Vec<SemanticToken> {
+    // Top-level values, local ones aren't referenced
+    let functions: HashSet<(u32, u32)> = extract_references(tree, source, uri)
+        .into_iter()
+        .filter(|reference| reference.name.starts_with(char::is_lowercase))
+        .map(|reference| {
+            let start = reference.location.range.start;
+            (start.line, start.character)
+        })
+        .collect();
+
+    let mut tokens = Vec::new();
+    collect(tree.root_node(), &functions, &mut tokens);
+    tokens.sort_by_key(|token| (token.line, token.start));
+    // The first classification of a name wins, e.g. a record pattern's field over the
+    // variable it binds
+    tokens.dedup_by_key(|token| (token.line, token.start));
+
+    let mut previous = (0, 0);
+    tokens
+        .into_iter()
+        .map(|token| {
+            let delta_line = token.line - previous.0;
+            let delta_start = if delta_line == 0 {
+                token.start - previous.1
+            } else {
+                token.start
+            };
+            previous = (token.line, token.start);
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: token.length,
+                token_type: token.kind as u32,
+                token_modifiers_bitset: if token.declaration { DECLARATION } else { 0 },
+            }
+        })
+        .collect()
+}
+
+fn collect(node: Node, functions: &HashSet<(u32, u32)>, tokens: &mut Vec<Token>) {
+    let parent = node.parent().map(|parent| parent.kind());
+    match node.kind() {
+        "upper_case_qid" => {
+            if matches!(parent, Some("module_declaration" | "import_clause")) {
+                push(tokens, node, TokenKind::Namespace, false);
+                return;
+            }
+            let segments = named_children(node);
+            if let Some((name, qualifier)) = segments.split_last() {
+                for segment in qualifier {
+                    push(tokens, *segment, TokenKind::Namespace, false);
+                }
+                let kind = if parent == Some("type_ref") {
+                    TokenKind::Type
+                } else {
+                    TokenKind::Constructor
+                };
+                push(tokens, *name, kind, false);
+            }
+            return;
+        }
+        "value_qid" => {
+            let segments = named_children(node);
+            if let Some((name, qualifier)) = segments.split_last() {
+                for segment in qualifier {
+                    push(tokens, *segment, TokenKind::Namespace, false);
+                }
+                let kind = if !qualifier.is_empty() || is_function(*name, functions) {
+                    TokenKind::Function
+                } else {
+                    TokenKind::Variable
+                };
+                push(tokens, *name, kind, false);
+            }
+            return;
+        }
+        "record_base_identifier" => {
+            let kind = if is_function(node, functions) {
+                TokenKind::Function
+            } else {
+                TokenKind::Variable
+            };
+            push(tokens, node, kind, false);
+            return;
+        }
+        "as_clause" => {
+            if let Some(alias) = child_of_kind(&node, "upper_case_identifier") {
+                push(tokens, alias, TokenKind::Namespace, false);
+            }
+        }
+        "exposed_value" => push(tokens, node, TokenKind::Function, false),
+        "exposed_type" => {
+            if let Some(name) = child_of_kind(&node, "upper_case_identifier") {
+                push(tokens, name, TokenKind::Type, false);
+            }
+        }
+        "type_declaration" | "type_alias_declaration" => {
+            if let Some(name) = child_of_kind(&node, "upper_case_identifier") {
+                push(tokens, name, TokenKind::Type, true);
+            }
+        }
+        "union_variant" => {
+            if let Some(name) = child_of_kind(&node, "upper_case_identifier") {
+                push(tokens, name, TokenKind::Constructor, true);
+            }
+        }
+        "lower_type_name" | "type_variable" => {
+            push(tokens, node, TokenKind::TypeParameter, false);
+            return;
+        }
+        "type_annotation" => {
+            if let Some(name) = child_of_kind(&node, "lower_case_identifier") {
+                push(tokens, name, TokenKind::Function, false);
+            }
+        }
+        "function_declaration_left" => {
+            if let Some(name) = child_of_kind(&node, "lower_case_identifier") {
+                let top_level = node
+                    .parent()
+                    .and_then(|declaration| declaration.parent())
+                    .is_some_and(|parent| parent.parent().is_none());
+                // A `let` value without parameters is a variable
+                let kind = if top_level || node.named_child_count() > 1 {
+                    TokenKind::Function
+                } else {
+                    TokenKind::Variable
+                };
+                push(tokens, name, kind, true);
+            }
+        }
+        "lower_pattern" => {
+            push(tokens, node, TokenKind::Variable, true);
+            return;
+        }
+        "operator_identifier" => {
+            push(tokens, node, TokenKind::Operator, false);
+            return;
+        }
+        "field_type" | "field" => {
+            if let Some(name) = child_of_kind(&node, "lower_case_identifier") {
+                push(tokens, name, TokenKind::Field, false);
+            }
+        }
+        "field_access_expr" | "field_accessor_function_expr" => {
+            for name in named_children(node)
+                .into_iter()
+                .filter(|child| child.kind() == "lower_case_identifier")
+            {
+                push(tokens, name, TokenKind::Field, false);
+            }
+        }
+        "record_pattern" => {
+            for entry in named_children(node) {
+                // `{ count }`, or the label of `{ count = n }`
+                let label = match entry.kind() {
+                    "lower_pattern" | "lower_case_identifier" => Some(entry),
+                    _ => entry
+                        .named_child(0)
+                        .filter(|label| label.kind() == "lower_case_identifier"),
+                };
+                if let Some(label) = label {
+                    push(tokens, label, TokenKind::Field, false);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for child in named_children(node) {
+        collect(child, functions, tokens);
+    }
+}
+
+fn is_function(name: Node, functions: &HashSet<(u32, u32)>) -> bool {
+    let start = name.start_position();
+    functions.contains(&(start.row as u32, start.column as u32))
+}
+
+/// Add a token for a node on one line
+fn push(tokens: &mut Vec<Token>, node: Node, kind: TokenKind, declaration: bool) {
+    let start = node.start_position();
+    let end = node.end_position();
+    if start.row != end.row || end.column <= start.column {
+        return;
+    }
+    tokens.push(Token {
+        line: start.row as u32,
+        start: start.column as u32,
+        length: (end.column - start.column) as u32,
+        kind,
+        declaration,
+    });
+}
+
+/// The edits turning the tokens of an earlier result into `current`: the stretch between
+/// the tokens both start and end with, replaced. Positions count integers of the encoded
+/// data, five per token.
+pub fn tokens_delta(
+    previous: &[SemanticToken],
+    current: &[SemanticToken],
+) -> Vec<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix == previous.len() && prefix == current.len() {
+        return Vec::new();
+    }
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: ((previous.len() - prefix - suffix) * 5) as u32,
+        data: Some(current[prefix..current.len() - suffix].to_vec()),
+    }]
+}
+
+/// Semantic token results shared by the handlers of successive requests
+pub type SharedSemanticTokens = Arc<Mutex<SemanticTokensCache>>;
+
+/// The latest semantic tokens returned for each document, by result id
+#[derive(Debug, Default)]
+pub struct SemanticTokensCache {
+    next_id: u64,
+    results: HashMap<Url, (String, Vec<SemanticToken>)>,
+}
+
+impl SemanticTokensCache {
+    /// Remember `tokens` as the latest result for `uri`, returning its result id
+    pub fn store(&mut self, uri: &Url, tokens: Vec<SemanticToken>) -> String {
+        self.next_id += 1;
+        let result_id = self.next_id.to_string();
+        self.results
+            .insert(uri.clone(), (result_id.clone(), tokens));
+        result_id
+    }
+
+    /// The tokens of the result `result_id` for `uri`, if that's the latest one
+    pub fn previous(&self, uri: &Url, result_id: &str) -> Option<&[SemanticToken]> {
+        self.results
+            .get(uri)
+            .filter(|(latest, _)| latest == result_id)
+            .map(|(_, tokens)| tokens.as_slice())
+    }
+
+    pub fn forget(&mut self, uri: &Url) {
+        self.results.remove(uri);
+    }
+}
+
+/// Named children without comments
+fn named_children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    let children = node
+        .named_children(&mut cursor)
+        .filter(|child| !child.kind().ends_with("comment"))
+        .collect();
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_core::Parser;
+
+    fn token(delta_line: u32, delta_start: u32, length: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn test_tokens_delta() {
+        let previous = vec![token(0, 0, 4), token(1, 2, 3), token(0, 4, 5)];
+        assert!(tokens_delta(&previous, &previous).is_empty());
+
+        // The middle token changed and one was added after it
+        let current = vec![
+            token(0, 0, 4),
+            token(1, 2, 6),
+            token(0, 7, 1),
+            token(0, 4, 5),
+        ];
+        let edits = tokens_delta(&previous, &current);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].start, 5);
+        assert_eq!(edits[0].delete_count, 5);
+        assert_eq!(
+            edits[0].data.as_deref(),
+            Some(&[token(1, 2, 6), token(0, 7, 1)][..])
+        );
+
+        // Everything removed
+        let edits = tokens_delta(&previous, &[]);
+        assert_eq!(edits[0].start, 0);
+        assert_eq!(edits[0].delete_count, 15);
+    }
+
+    #[test]
+    fn test_cache_keeps_latest_result() {
+        let uri = Url::parse("file:///Main.gren").unwrap();
+        let mut cache = SemanticTokensCache::default();
+        let first = cache.store(&uri, vec![token(0, 0, 4)]);
+        assert_eq!(cache.previous(&uri, &first).map(<[_]>::len), Some(1));
+
+        let second = cache.store(&uri, Vec::new());
+        assert_ne!(first, second);
+        assert_eq!(cache.previous(&uri, &first), None);
+        cache.forget(&uri);
+        assert_eq!(cache.previous(&uri, &second), None);
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let source = "module Main exposing (view)\n\n\
+                      import Html\n\n\
+                      view : Model -> Html.Html msg\n\
+                      view model =\n    \
+                      Html.text model.name\n";
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(source).unwrap().unwrap();
+
+        let mut line = 0;
+        let mut start = 0;
+        let mut kinds = Vec::new();
+        let uri = Url::parse("file:///Main.gren").unwrap();
+        for token in semantic_tokens(&tree, source, &uri) {
+            line += token.delta_line;
+            start = if token.delta_line == 0 {
+                start + token.delta_start
+            } else {
+                token.delta_start
+            };
+            let kind = TOKEN_TYPES[token.token_type as usize].as_str();
+            kinds.push((line, start, kind));
+        }
+
+        for expected in [
+            (0, 7, "namespace"),
+            (2, 7, "namespace"),
+            (4, 0, "function"),
+            (4, 7, "type"),
+            (4, 16, "namespace"),
+            (4, 21, "type"),
+            (4, 26, "typeParameter"),
+            (5, 0, "function"),
+            (5, 5, "variable"),
+            (6, 4, "namespace"),
+            (6, 9, "function"),
+            (6, 14, "variable"),
+            (6, 20, "property"),
+        ] {
+            assert!(kinds.contains(&expected), "{:?} missing", expected);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use gren_lsp_core::{
+    CompilerOptions, ImportStyle, LintProfile, MetricThresholds, ProjectConfig, SymbolStoreBackend,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Client-configurable server settings
+///
+/// Settings arrive through `initializationOptions` and `workspace/didChangeConfiguration`,
+/// either as a bare object or nested under a `gren` section. Unknown keys are ignored and
+/// missing keys fall back to their defaults.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ServerSettings {
+    pub workspace_symbols: WorkspaceSymbolSettings,
+    /// Backend used to store indexed symbols (`sqlite`, `inMemory` or `tantivy`)
+    pub symbol_store: SymbolStoreBackend,
+    pub hover: HoverSettings,
+    pub completion: CompletionSettings,
+    pub imports: ImportSettings,
+    pub metrics: MetricsSettings,
+    pub runtime_log: RuntimeLogSettings,
+    pub exports: ExportSettings,
+    pub lints: LintSettings,
+    pub diagnostics: DiagnosticsSettings,
+    pub inlay_hints: InlayHintSettings,
+    /// Environment variables and `gren make` flags for compiler runs, on top of the ones
+    /// in the project's `.gren-lsp.toml`
+    pub compiler: CompilerOptions,
+    /// Deadlines in milliseconds per request method, e.g. `textDocument/completion`,
+    /// replacing the built-in ones. 0 lets the method's requests take as long as they need.
+    pub timeouts: HashMap<String, u64>,
+}
+
+/// Settings for `workspace/symbol`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorkspaceSymbolSettings {
+    /// Maximum number of symbols returned for a single query
+    pub max_results: usize,
+    /// Number of symbols sent per `$/progress` notification when streaming partial results
+    pub batch_size: usize,
+}
+
+impl Default for WorkspaceSymbolSettings {
+    fn default() -> Self {
+        Self {
+            max_results: 1000,
+            batch_size: 100,
+        }
+    }
+}
+
+/// Settings for `textDocument/hover`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HoverSettings {
+    /// Link symbols from published dependencies to their online package documentation
+    pub docs_links: bool,
+    /// Add how many times the symbol is used across the workspace, linking to its references
+    pub reference_count: bool,
+}
+
+impl Default for HoverSettings {
+    fn default() -> Self {
+        Self {
+            docs_links: true,
+            reference_count: false,
+        }
+    }
+}
+
+/// Settings for `textDocument/completion`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompletionSettings {
+    /// What accepting a function completion inserts
+    pub function_insert: FunctionInsert,
+    /// Most items returned for one request. A longer list is cut, and marked incomplete so
+    /// the client asks again as the word grows.
+    pub max_items: usize,
+}
+
+impl Default for CompletionSettings {
+    fn default() -> Self {
+        Self {
+            function_insert: FunctionInsert::default(),
+            max_items: 200,
+        }
+    }
+}
+
+/// What accepting a function completion inserts. Clients without snippet support always
+/// get the name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FunctionInsert {
+    /// Just the function name
+    #[default]
+    Name,
+    /// The name and a placeholder per argument of its signature
+    Arguments,
+    /// Like `Arguments`, wrapped in parentheses when the call is itself an argument
+    Parenthesized,
+}
+
+/// Settings for import ordering
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImportSettings {
+    /// Ordering applied by organize-imports (`alphabetical` or `grouped`)
+    pub style: ImportStyle,
+    /// Report files whose imports don't follow `style`
+    pub lint: bool,
+    /// Replace `exposing (..)` with the names the file uses when organizing imports
+    pub expand_exposing_all: bool,
+}
+
+impl ImportSettings {
+    /// Style the import lint checks against, or `None` when the lint is off
+    pub fn lint_style(&self) -> Option<ImportStyle> {
+        self.lint.then_some(self.style)
+    }
+}
+
+/// Settings for module metrics
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MetricsSettings {
+    /// Report functions that go over the thresholds below as hints
+    pub hints: bool,
+    /// Longest a top-level function may be, in lines, before it's reported
+    pub max_function_lines: usize,
+    /// Deepest `when` expressions may be nested before the function is reported
+    pub max_case_depth: usize,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        Self {
+            hints: false,
+            max_function_lines: 60,
+            max_case_depth: 3,
+        }
+    }
+}
+
+impl MetricsSettings {
+    /// Thresholds metric hints are checked against, or `None` when hints are off
+    pub fn hint_thresholds(&self) -> Option<MetricThresholds> {
+        self.hints.then_some(MetricThresholds {
+            max_function_lines: self.max_function_lines,
+            max_case_depth: self.max_case_depth,
+        })
+    }
+}
+
+/// Settings for showing values logged by a running program
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RuntimeLogSettings {
+    /// Show the latest `gren/runtimeLog` values in code lenses above `Debug.log` calls.
+    /// Turn this on when a runtime harness is forwarding the program's log output.
+    pub code_lens: bool,
+}
+
+/// Settings for a module's exposed declarations
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportSettings {
+    /// Show how many top-level declarations a module exposes in a code lens on its
+    /// module declaration
+    pub code_lens: bool,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self { code_lens: true }
+    }
+}
+
+/// Settings for the lints reported alongside compiler errors
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LintSettings {
+    /// `strict` reports unused imports and exposed values without a type annotation as
+    /// errors instead of hints. Pass the same profile to `gren-lsp check` in CI.
+    pub profile: LintProfile,
+}
+
+/// Settings for the compiles that refresh an open document's diagnostics
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiagnosticsSettings {
+    /// Whether documents are compiled as they're edited or only when they're saved
+    pub compile_on: CompileTrigger,
+    /// How long typing has to pause before an edited document is compiled, in
+    /// milliseconds
+    pub debounce_ms: u64,
+}
+
+impl Default for DiagnosticsSettings {
+    fn default() -> Self {
+        Self {
+            compile_on: CompileTrigger::Change,
+            debounce_ms: 150,
+        }
+    }
+}
+
+/// When an open document is compiled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompileTrigger {
+    /// Once typing pauses, and when it's saved
+    #[default]
+    Change,
+    /// Only when it's saved, for projects too large to compile while typing
+    Save,
+}
+
+/// Settings for `textDocument/inlayHint`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InlayHintSettings {
+    /// Show the inferred type of top-level and `let` declarations without an annotation
+    pub types: bool,
+    /// Show parameter names before the arguments of functions taking several parameters
+    /// of the same type
+    pub parameter_names: bool,
+}
+
+impl Default for InlayHintSettings {
+    fn default() -> Self {
+        Self {
+            types: true,
+            parameter_names: true,
+        }
+    }
+}
+
+impl ServerSettings {
+    /// Parse settings from a client-provided JSON value, falling back to defaults
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        Self::try_from_json(value).unwrap_or_default()
+    }
+
+    /// Parse settings from a client-provided JSON value, `None` when it holds no settings
+    /// (`null`) or they don't parse
+    pub fn try_from_json(value: &serde_json::Value) -> Option<Self> {
+        let section = value.get("gren").unwrap_or(value);
+        if section.is_null() {
+            return None;
+        }
+
+        match serde_json::from_value::<ServerSettings>(section.clone()) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                warn!("Ignoring invalid settings ({})", e);
+                None
+            }
+        }
+    }
+
+    /// These settings with what the project's `.gren-lsp.toml` sets taking precedence.
+    /// Compiler options aren't merged here, the workspace layers them itself.
+    pub fn with_project_config(&self, config: &ProjectConfig) -> Self {
+        let mut settings = self.clone();
+        if let Some(profile) = config.lints.profile {
+            settings.lints.profile = profile;
+        }
+        if let Some(style) = config.imports.style {
+            settings.imports.style = style;
+        }
+        if let Some(lint) = config.imports.lint {
+            settings.imports.lint = lint;
+        }
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_settings_defaults_when_missing() {
+        let settings = ServerSettings::from_json(&json!({}));
+        assert_eq!(settings, ServerSettings::default());
+        assert_eq!(settings.workspace_symbols.max_results, 1000);
+    }
+
+    #[test]
+    fn test_settings_none_when_null_or_invalid() {
+        assert_eq!(ServerSettings::try_from_json(&json!(null)), None);
+        assert_eq!(
+            ServerSettings::try_from_json(&json!({ "gren": null })),
+            None
+        );
+        assert_eq!(
+            ServerSettings::try_from_json(&json!({ "workspaceSymbols": { "maxResults": "all" } })),
+            None
+        );
+        assert_eq!(
+            ServerSettings::try_from_json(&json!({})),
+            Some(ServerSettings::default())
+        );
+    }
+
+    #[test]
+    fn test_settings_from_gren_section() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "workspaceSymbols": { "maxResults": 50 } }
+        }));
+        assert_eq!(settings.workspace_symbols.max_results, 50);
+        assert_eq!(settings.workspace_symbols.batch_size, 100);
+    }
+
+    #[test]
+    fn test_settings_symbol_store_backend() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "symbolStore": "inMemory" }
+        }));
+        assert_eq!(settings.symbol_store, SymbolStoreBackend::InMemory);
+    }
+
+    #[test]
+    fn test_settings_function_insert() {
+        assert_eq!(
+            ServerSettings::default().completion.function_insert,
+            FunctionInsert::Name
+        );
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "completion": { "functionInsert": "parenthesized" } }
+        }));
+        assert_eq!(
+            settings.completion.function_insert,
+            FunctionInsert::Parenthesized
+        );
+        assert_eq!(settings.completion.max_items, 200);
+    }
+
+    #[test]
+    fn test_settings_import_style() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": {
+                "imports": { "style": "grouped", "lint": true, "expandExposingAll": true }
+            }
+        }));
+        assert_eq!(settings.imports.style, ImportStyle::Grouped);
+        assert_eq!(settings.imports.lint_style(), Some(ImportStyle::Grouped));
+        assert!(settings.imports.expand_exposing_all);
+        assert_eq!(ServerSettings::default().imports.lint_style(), None);
+        assert!(!ServerSettings::default().imports.expand_exposing_all);
+    }
+
+    #[test]
+    fn test_settings_metric_thresholds() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "metrics": { "hints": true, "maxFunctionLines": 40 } }
+        }));
+        assert_eq!(
+            settings.metrics.hint_thresholds(),
+            Some(MetricThresholds {
+                max_function_lines: 40,
+                max_case_depth: 3,
+            })
+        );
+        assert_eq!(ServerSettings::default().metrics.hint_thresholds(), None);
+    }
+
+    #[test]
+    fn test_settings_compiler_options() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": {
+                "compiler": { "env": { "GREN_HOME": "/opt/gren" }, "flags": ["--optimize"] }
+            }
+        }));
+        assert_eq!(settings.compiler.env["GREN_HOME"], "/opt/gren");
+        assert_eq!(settings.compiler.flags, vec!["--optimize"]);
+    }
+
+    #[test]
+    fn test_settings_hover_reference_count() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "hover": { "referenceCount": true } }
+        }));
+        assert!(settings.hover.reference_count);
+        assert!(settings.hover.docs_links);
+        assert!(!ServerSettings::default().hover.reference_count);
+    }
+
+    #[test]
+    fn test_settings_runtime_log_code_lens() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "runtimeLog": { "codeLens": true } }
+        }));
+        assert!(settings.runtime_log.code_lens);
+        assert!(!ServerSettings::default().runtime_log.code_lens);
+    }
+
+    #[test]
+    fn test_settings_strict_lint_profile() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "lints": { "profile": "strict" } }
+        }));
+        assert_eq!(settings.lints.profile, LintProfile::Strict);
+        assert_eq!(
+            ServerSettings::default().lints.profile,
+            LintProfile::Default
+        );
+    }
+
+    #[test]
+    fn test_settings_compile_on_save() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "diagnostics": { "compileOn": "save" } }
+        }));
+        assert_eq!(settings.diagnostics.compile_on, CompileTrigger::Save);
+        assert_eq!(settings.diagnostics.debounce_ms, 150);
+        assert_eq!(
+            ServerSettings::default().diagnostics.compile_on,
+            CompileTrigger::Change
+        );
+    }
+
+    #[test]
+    fn test_settings_inlay_hints() {
+        assert!(ServerSettings::default().inlay_hints.types);
+        assert!(ServerSettings::default().inlay_hints.parameter_names);
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "inlayHints": { "parameterNames": false } }
+        }));
+        assert!(settings.inlay_hints.types);
+        assert!(!settings.inlay_hints.parameter_names);
+    }
+
+    #[test]
+    fn test_project_config_takes_precedence() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": {
+                "lints": { "profile": "strict" },
+                "imports": { "style": "grouped", "lint": true }
+            }
+        }));
+        let config = ProjectConfig::parse("[imports]\nstyle = \"alphabetical\"\n").unwrap();
+        let merged = settings.with_project_config(&config);
+        assert_eq!(merged.imports.style, ImportStyle::Alphabetical);
+        assert!(merged.imports.lint);
+        assert_eq!(merged.lints.profile, LintProfile::Strict);
+    }
+
+    #[test]
+    fn test_settings_timeouts() {
+        let settings = ServerSettings::from_json(&json!({
+            "gren": { "timeouts": { "textDocument/rename": 30000, "textDocument/hover": 0 } }
+        }));
+        assert_eq!(settings.timeouts["textDocument/rename"], 30000);
+        assert_eq!(settings.timeouts["textDocument/hover"], 0);
+        assert!(ServerSettings::default().timeouts.is_empty());
+    }
+
+    #[test]
+    fn test_settings_invalid_falls_back_to_defaults() {
+        let settings = ServerSettings::from_json(&json!({
+            "workspaceSymbols": { "maxResults": "lots" }
+        }));
+        assert_eq!(settings, ServerSettings::default());
+    }
+}
@@ -0,0 +1,325 @@
+//! Signature help for function applications
+//!
+//! While arguments are typed after a function, the text before the cursor is read back to
+//! the function being applied, skipping over arguments in parentheses, brackets, braces
+//! and strings. The argument the cursor is in picks the parameter to highlight. Reading
+//! the text rather than the syntax tree keeps this working on the half-typed
+//! applications that don't parse yet.
+//!
+//! Parameters come from the function's type signature in the index, parsed as a synthetic
+//! annotation like call snippets do, so parenthesized function types stay one parameter.
+
+use gren_lsp_core::Parser;
+use lsp_types::*;
+
+/// A function applied to arguments, read from the text before the cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Application {
+    /// The function as written, qualified or not
+    pub function: String,
+    /// Index of the argument the cursor is in
+    pub active_argument: usize,
+}
+
+impl Application {
+    /// The module qualifier and the name of the function, `Array.map` giving
+    /// `(["Array"], "map")`
+    pub fn qualified_name(&self) -> (Vec<String>, &str) {
+        match self.function.rsplit_once('.') {
+            Some((module, name)) => (module.split('.').map(str::to_string).collect(), name),
+            None => (Vec::new(), &self.function),
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "when", "is", "case", "of", "let", "in", "as", "exposing", "import",
+    "module",
+];
+
+/// The application the cursor is in, from the text of the document before it
+pub fn application_at(before_cursor: &str) -> Option<Application> {
+    let chars: Vec<char> = before_cursor.chars().collect();
+    let mut index = chars.len();
+    // Arguments and the function, last first
+    let mut atoms: Vec<String> = Vec::new();
+    let mut typing_atom = false;
+
+    while index > 0 {
+        let c = chars[index - 1];
+        if c.is_whitespace() {
+            // A line starting at the first column starts another declaration
+            if c == '\n' && chars.get(index).is_some_and(|next| !next.is_whitespace()) {
+                break;
+            }
+            index -= 1;
+            continue;
+        }
+
+        let end = index;
+        let start = match c {
+            ')' | ']' | '}' => matching_open(&chars, index - 1)?,
+            '"' | '\'' => string_start(&chars, index - 1)?,
+            c if is_atom_char(c) => {
+                let mut start = index - 1;
+                while start > 0 && is_atom_char(chars[start - 1]) {
+                    start -= 1;
+                }
+                start
+            }
+            // An operator, comma or unclosed bracket ends the application
+            _ => break,
+        };
+
+        let atom: String = chars[start..end].iter().collect();
+        if KEYWORDS.contains(&atom.as_str()) {
+            break;
+        }
+        if atoms.is_empty() && end == chars.len() {
+            typing_atom = true;
+        }
+        atoms.push(atom);
+        index = start;
+    }
+
+    let function = atoms.pop()?;
+    if !is_function_name(&function) {
+        return None;
+    }
+    let arguments = atoms.len();
+    let active_argument = if typing_atom {
+        arguments.checked_sub(1)?
+    } else {
+        arguments
+    };
+    Some(Application {
+        function,
+        active_argument,
+    })
+}
+
+/// Characters of names, qualified names, record accessors and number literals
+fn is_atom_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/// A lowercase name, possibly qualified, such as `map` or `Array.map`. Record fields like
+/// `model.count` aren't.
+fn is_function_name(atom: &str) -> bool {
+    let mut parts: Vec<&str> = atom.split('.').collect();
+    let name = parts.pop().unwrap_or_default();
+    name.starts_with(|c: char| c.is_lowercase())
+        && parts
+            .iter()
+            .all(|part| part.starts_with(|c: char| c.is_uppercase()))
+}
+
+/// Index of the bracket opening the one closing at `close`
+fn matching_open(chars: &[char], close: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = close + 1;
+    while index > 0 {
+        index -= 1;
+        match chars[index] {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            '"' | '\'' if index < close => index = string_start(chars, index)?,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Index of the quote opening the string or character literal closing at `close`
+fn string_start(chars: &[char], close: usize) -> Option<usize> {
+    let quote = chars[close];
+    let mut index = close;
+    while index > 0 {
+        index -= 1;
+        let escaped = index > 0 && chars[index - 1] == '\\';
+        if chars[index] == quote && !escaped {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Byte ranges of the parameters in a type signature, leaving out the result
+pub fn signature_parameters(parser: &mut Parser, signature: &str) -> Option<Vec<(usize, usize)>> {
+    let prefix = "module Signature exposing (..)\n\nsignature : ";
+    let source = format!("{}{}\n", prefix, signature);
+    let tree = parser.parse(&source).ok()??;
+    let root = tree.root_node();
+
+    let mut cursor = root.walk();
+    let annotation = root
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "type_annotation")?;
+    let mut cursor = annotation.walk();
+    let type_expression = annotation
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "type_expression")?;
+    if type_expression.has_error() {
+        return None;
+    }
+
+    let mut cursor = type_expression.walk();
+    let mut parameters: Vec<(usize, usize)> = type_expression
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() != "arrow" && !node.kind().ends_with("comment"))
+        .map(|node| {
+            (
+                node.start_byte() - prefix.len(),
+                node.end_byte() - prefix.len(),
+            )
+        })
+        .collect();
+    // The last part is the result
+    parameters.pop()?;
+    Some(parameters)
+}
+
+/// Signature help showing `name : signature` with the active argument's parameter
+/// highlighted, or `None` when the function takes no more arguments
+pub fn signature_help(
+    name: &str,
+    signature: &str,
+    parameters: &[(usize, usize)],
+    active_argument: usize,
+    documentation: Option<&str>,
+) -> Option<SignatureHelp> {
+    if active_argument >= parameters.len() {
+        return None;
+    }
+
+    let label = format!("{} : {}", name, signature);
+    let offset = name.len() + 3;
+    let parameters = parameters
+        .iter()
+        .map(|(start, end)| ParameterInformation {
+            label: ParameterLabel::LabelOffsets([
+                utf16_offset(&label, offset + start),
+                utf16_offset(&label, offset + end),
+            ]),
+            documentation: None,
+        })
+        .collect();
+    let documentation = documentation
+        .map(str::trim)
+        .filter(|documentation| !documentation.is_empty())
+        .map(|documentation| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: documentation.to_string(),
+            })
+        });
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label,
+            documentation,
+            parameters: Some(parameters),
+            active_parameter: Some(active_argument as u32),
+        }],
+        active_signature: Some(0),
+        active_parameter: Some(active_argument as u32),
+    })
+}
+
+/// Offset in UTF-16 code units of a byte offset into `text`
+fn utf16_offset(text: &str, byte_offset: usize) -> u32 {
+    text[..byte_offset].encode_utf16().count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn application(function: &str, active_argument: usize) -> Option<Application> {
+        Some(Application {
+            function: function.to_string(),
+            active_argument,
+        })
+    }
+
+    #[test]
+    fn test_application_at() {
+        assert_eq!(
+            application_at("view model =\n    map "),
+            application("map", 0)
+        );
+        assert_eq!(
+            application_at("    Array.map (\\x -> x + 1) "),
+            application("Array.map", 1)
+        );
+        assert_eq!(
+            application_at("    Array.foldl (+) 0 item"),
+            application("Array.foldl", 2)
+        );
+        assert_eq!(
+            application_at("    text (String.fromInt \"a)\" "),
+            application("String.fromInt", 1)
+        );
+        assert_eq!(application_at("    div [ class \"x\" ]\n        [ "), None);
+        assert_eq!(application_at("    x = 1 + "), None);
+        assert_eq!(application_at("    Just "), None);
+        assert_eq!(application_at("    model.count "), None);
+        assert_eq!(application_at("    if "), None);
+        assert_eq!(application_at("main =\n    update"), None);
+        assert_eq!(
+            application_at("    when msg is\n        Go -> step model "),
+            application("step", 1)
+        );
+    }
+
+    #[test]
+    fn test_qualified_name() {
+        let application = application_at("    Html.Events.onClick ").unwrap();
+        assert_eq!(
+            application.qualified_name(),
+            (vec!["Html".to_string(), "Events".to_string()], "onClick")
+        );
+    }
+
+    #[test]
+    fn test_signature_help() {
+        let signature = "(a -> b) -> Array a -> Array b";
+        let help = signature_help(
+            "map",
+            signature,
+            &[(0, 8), (12, 19)],
+            1,
+            Some("Apply a function"),
+        )
+        .unwrap();
+        let information = &help.signatures[0];
+        assert_eq!(information.label, "map : (a -> b) -> Array a -> Array b");
+        assert_eq!(
+            information.parameters.as_ref().unwrap()[1].label,
+            ParameterLabel::LabelOffsets([18, 25])
+        );
+        assert_eq!(help.active_parameter, Some(1));
+
+        assert_eq!(
+            signature_help("map", signature, &[(0, 8), (12, 19)], 2, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_signature_parameters() {
+        let mut parser = Parser::new().unwrap();
+        let signature = "(a -> b) -> Array a -> Array b";
+        let parameters = signature_parameters(&mut parser, signature).unwrap();
+        let texts: Vec<&str> = parameters
+            .iter()
+            .map(|(start, end)| &signature[*start..*end])
+            .collect();
+        assert_eq!(texts, vec!["(a -> b)", "Array a"]);
+    }
+}
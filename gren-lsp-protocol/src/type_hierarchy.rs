@@ -0,0 +1,162 @@
+//! Type hierarchy for custom types
+//!
+//! Gren has no subtyping, so the hierarchy shows how types are built from each other: a
+//! custom type's subtypes are its constructors, and its supertypes are the custom types
+//! and type aliases whose definitions refer to it. A constructor's supertype is its type.
+
+use gren_lsp_core::{ReferenceKind, Symbol, SymbolReference};
+use lsp_types::*;
+
+/// The type hierarchy item for a type, type alias or constructor defined in `module`
+pub fn type_item(symbol: &Symbol, module: &str) -> TypeHierarchyItem {
+    let kind = if symbol.kind == SymbolKind::CONSTRUCTOR {
+        SymbolKind::ENUM_MEMBER
+    } else if is_alias(symbol) {
+        SymbolKind::STRUCT
+    } else {
+        SymbolKind::ENUM
+    };
+    TypeHierarchyItem {
+        name: symbol.name.clone(),
+        kind,
+        tags: None,
+        detail: Some(module.to_string()),
+        uri: symbol.location.uri.clone(),
+        range: symbol.location.range,
+        selection_range: symbol.location.range,
+        data: Some(serde_json::json!({ "module": module })),
+    }
+}
+
+/// The module a type hierarchy item was made for
+pub fn type_item_module(item: &TypeHierarchyItem) -> Option<String> {
+    item.data
+        .as_ref()?
+        .get("module")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The files and names of the types and type aliases whose definitions refer to the type
+/// `name`, given the references to it, in order and without repeats
+pub fn referring_types(references: &[SymbolReference], name: &str) -> Vec<(Url, String)> {
+    let mut types: Vec<(Url, String)> = references
+        .iter()
+        .filter(|reference| reference.kind == ReferenceKind::Type)
+        .filter_map(|reference| {
+            let container = reference.container.as_ref()?;
+            // A recursive type refers to itself, and values' `let` annotations refer to types
+            if container == name || !container.starts_with(char::is_uppercase) {
+                return None;
+            }
+            Some((reference.location.uri.clone(), container.clone()))
+        })
+        .collect();
+    types.sort_by(|a, b| (a.0.as_str(), &a.1).cmp(&(b.0.as_str(), &b.1)));
+    types.dedup();
+    types
+}
+
+/// The constructors of the custom type `name` among a file's symbols
+pub fn constructors_of<'a>(symbols: &'a [Symbol], name: &str) -> Vec<&'a Symbol> {
+    symbols
+        .iter()
+        .filter(|symbol| {
+            symbol.kind == SymbolKind::CONSTRUCTOR && symbol.container_name.as_deref() == Some(name)
+        })
+        .collect()
+}
+
+/// Whether a type symbol is a type alias rather than a custom type
+fn is_alias(symbol: &Symbol) -> bool {
+    symbol
+        .type_signature
+        .as_deref()
+        .is_some_and(|signature| signature.starts_with("alias "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(path: &str) -> Url {
+        Url::parse(&format!("file:///project/src/{}", path)).unwrap()
+    }
+
+    fn symbol(name: &str, kind: SymbolKind, container: Option<&str>, signature: &str) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            kind,
+            location: Location::new(uri("Shape.gren"), Range::default()),
+            container_name: container.map(str::to_string),
+            type_signature: Some(signature.to_string()),
+            documentation: None,
+        }
+    }
+
+    fn reference(path: &str, kind: ReferenceKind, container: Option<&str>) -> SymbolReference {
+        SymbolReference {
+            name: "Shape".to_string(),
+            module: "Shape".to_string(),
+            location: Location::new(uri(path), Range::default()),
+            kind,
+            container: container.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_type_item_kinds() {
+        let kind = |symbol: Symbol| type_item(&symbol, "Shape").kind;
+        assert_eq!(
+            kind(symbol("Shape", SymbolKind::CLASS, None, "Shape = Circle")),
+            SymbolKind::ENUM
+        );
+        assert_eq!(
+            kind(symbol("Point", SymbolKind::CLASS, None, "alias Point = {}")),
+            SymbolKind::STRUCT
+        );
+        assert_eq!(
+            kind(symbol("Circle", SymbolKind::CONSTRUCTOR, Some("Shape"), "")),
+            SymbolKind::ENUM_MEMBER
+        );
+        let item = type_item(&symbol("Shape", SymbolKind::CLASS, None, ""), "Shape");
+        assert_eq!(type_item_module(&item), Some("Shape".to_string()));
+    }
+
+    #[test]
+    fn test_referring_types() {
+        let references = vec![
+            reference("Scene.gren", ReferenceKind::Type, Some("Scene")),
+            reference("Scene.gren", ReferenceKind::Type, Some("Scene")),
+            reference("Scene.gren", ReferenceKind::Type, Some("Layer")),
+            reference("Shape.gren", ReferenceKind::Type, Some("Shape")),
+            // An annotation and an exposing list aren't types
+            reference("Main.gren", ReferenceKind::Type, None),
+            reference("Main.gren", ReferenceKind::Exposing, None),
+            reference("Main.gren", ReferenceKind::Type, Some("main")),
+            reference("Main.gren", ReferenceKind::Expression, Some("main")),
+        ];
+        assert_eq!(
+            referring_types(&references, "Shape"),
+            vec![
+                (uri("Scene.gren"), "Layer".to_string()),
+                (uri("Scene.gren"), "Scene".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constructors_of() {
+        let symbols = vec![
+            symbol("Shape", SymbolKind::CLASS, None, "Shape = Circle | Square"),
+            symbol("Circle", SymbolKind::CONSTRUCTOR, Some("Shape"), ""),
+            symbol("Square", SymbolKind::CONSTRUCTOR, Some("Shape"), ""),
+            symbol("Red", SymbolKind::CONSTRUCTOR, Some("Color"), ""),
+        ];
+        let names: Vec<&str> = constructors_of(&symbols, "Shape")
+            .iter()
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Circle", "Square"]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Golden-file tests for hover and completion content
+//!
+//! Every `.gren` file under `tests/golden/hover` and `tests/golden/completion` is opened in
+//! a fresh workspace. Each `$0` in it marks a position to request hover or completion at,
+//! and is removed before the file is opened. The results for all positions are compared
+//! with the `.snap` file next to the fixture. A fixture starting with
+//! `-- golden: plaintext` is requested by a client that only renders plain text.
+//!
+//! After an intended change to the content, rewrite the snapshots with `just bless`
+//! (`GREN_LSP_BLESS=1 cargo test -p gren-lsp-protocol --test golden_tests`) and review
+//! the diff. Snapshots that don't exist yet are written on the first run, except on CI
+//! where a missing snapshot fails the test.
+
+use gren_lsp_core::Workspace;
+use gren_lsp_protocol::handlers::Handlers;
+use lsp_types::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const CURSOR: &str = "$0";
+const PLAINTEXT_DIRECTIVE: &str = "-- golden: plaintext";
+
+/// A fixture with its cursor markers taken out
+struct Fixture {
+    path: PathBuf,
+    uri: Url,
+    source: String,
+    cursors: Vec<Position>,
+    plaintext: bool,
+}
+
+impl Fixture {
+    fn load(path: &Path) -> Self {
+        let marked = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+        let mut source = String::new();
+        let mut cursors = Vec::new();
+        for (line, text) in marked.split_inclusive('\n').enumerate() {
+            let mut rest = text;
+            let mut column = 0;
+            while let Some(index) = rest.find(CURSOR) {
+                let before = &rest[..index];
+                column += before.encode_utf16().count() as u32;
+                cursors.push(Position::new(line as u32, column));
+                source.push_str(before);
+                rest = &rest[index + CURSOR.len()..];
+            }
+            source.push_str(rest);
+        }
+        assert!(
+            !cursors.is_empty(),
+            "{} has no {} marker",
+            path.display(),
+            CURSOR
+        );
+
+        Fixture {
+            path: path.to_path_buf(),
+            uri: Url::from_file_path(path).unwrap(),
+            plaintext: source.starts_with(PLAINTEXT_DIRECTIVE),
+            source,
+            cursors,
+        }
+    }
+
+    async fn handlers(&self) -> Handlers {
+        let mut workspace = Workspace::new().expect("Failed to create workspace");
+        workspace
+            .open_document(TextDocumentItem {
+                uri: self.uri.clone(),
+                language_id: "gren".to_string(),
+                version: 1,
+                text: self.source.clone(),
+            })
+            .unwrap();
+        Handlers::new(Arc::new(RwLock::new(workspace)))
+    }
+
+    fn client_capabilities(&self) -> ClientCapabilities {
+        let content_format = if self.plaintext {
+            vec![MarkupKind::PlainText]
+        } else {
+            vec![MarkupKind::Markdown, MarkupKind::PlainText]
+        };
+        ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                hover: Some(HoverClientCapabilities {
+                    dynamic_registration: None,
+                    content_format: Some(content_format),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn position_params(&self, position: Position) -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: self.uri.clone(),
+            },
+            position,
+        }
+    }
+}
+
+/// The fixtures of one kind of request, sorted by name
+fn fixtures(kind: &str) -> Vec<Fixture> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(kind);
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Failed to list {}: {}", dir.display(), e))
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "gren")
+        })
+        .collect();
+    paths.sort();
+    paths.iter().map(|path| Fixture::load(path)).collect()
+}
+
+/// Section header of one requested position, with 1-based line and column like editors
+fn section(kind: &str, position: Position) -> String {
+    format!(
+        "-- {} {}:{}\n",
+        kind,
+        position.line + 1,
+        position.character + 1
+    )
+}
+
+fn render_hover(hover: Option<Hover>) -> String {
+    let hover = match hover {
+        Some(hover) => hover,
+        None => return "(no hover)\n".to_string(),
+    };
+    let (kind, value) = match hover.contents {
+        HoverContents::Markup(markup) => (format!("{:?}", markup.kind), markup.value),
+        HoverContents::Scalar(marked) => ("MarkedString".to_string(), marked_string(marked)),
+        HoverContents::Array(marked) => (
+            "MarkedString".to_string(),
+            marked
+                .into_iter()
+                .map(marked_string)
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        ),
+    };
+    format!("[{}]\n{}\n", kind, value.trim_end())
+}
+
+fn marked_string(marked: MarkedString) -> String {
+    match marked {
+        MarkedString::String(value) => value,
+        MarkedString::LanguageString(code) => {
+            format!("```{}\n{}\n```", code.language, code.value)
+        }
+    }
+}
+
+fn render_completion(response: Option<CompletionResponse>) -> String {
+    let mut items = match response {
+        Some(CompletionResponse::Array(items)) => items,
+        Some(CompletionResponse::List(list)) => list.items,
+        None => return "(no completions)\n".to_string(),
+    };
+    // Clients order by sort text, then label
+    items.sort_by(|a, b| {
+        let key = |item: &CompletionItem| item.sort_text.clone().unwrap_or(item.label.clone());
+        key(a).cmp(&key(b)).then(a.label.cmp(&b.label))
+    });
+    items
+        .iter()
+        .map(|item| match &item.detail {
+            Some(detail) => format!("{} : {}\n", item.label, detail),
+            None => format!("{}\n", item.label),
+        })
+        .collect()
+}
+
+/// Compare `actual` with the fixture's snapshot, or write it when blessing or when there's
+/// none yet. Returns the mismatch to report.
+fn check_snapshot(fixture: &Fixture, actual: &str) -> Option<String> {
+    let snapshot = fixture.path.with_extension("snap");
+    let bless = std::env::var_os("GREN_LSP_BLESS").is_some();
+    let expected = fs::read_to_string(&snapshot).ok();
+    match expected.as_deref() {
+        Some(expected) if expected == actual => None,
+        Some(expected) if !bless => Some(format!(
+            "{} doesn't match, run `just bless` if the change is intended\n\
+             --- expected\n{}--- actual\n{}",
+            snapshot.display(),
+            expected,
+            actual
+        )),
+        None if !bless && std::env::var_os("CI").is_some() => Some(format!(
+            "{} is missing, run `just bless` and commit it",
+            snapshot.display()
+        )),
+        _ => {
+            fs::write(&snapshot, actual)
+                .unwrap_or_else(|e| panic!("Failed to write {}: {}", snapshot.display(), e));
+            eprintln!("Wrote {}", snapshot.display());
+            None
+        }
+    }
+}
+
+fn assert_no_mismatches(mismatches: Vec<String>) {
+    assert!(mismatches.is_empty(), "\n{}", mismatches.join("\n\n"));
+}
+
+#[tokio::test]
+async fn test_hover_golden_files() {
+    let mut mismatches = Vec::new();
+    for fixture in fixtures("hover") {
+        let handlers = fixture.handlers().await;
+        let capabilities = fixture.client_capabilities();
+        let mut actual = String::new();
+        for position in &fixture.cursors {
+            let hover = handlers
+                .hover_with_capabilities(
+                    HoverParams {
+                        text_document_position_params: fixture.position_params(*position),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    },
+                    Some(&capabilities),
+                )
+                .await
+                .unwrap();
+            actual.push_str(&section("hover", *position));
+            actual.push_str(&render_hover(hover));
+        }
+        mismatches.extend(check_snapshot(&fixture, &actual));
+    }
+    assert_no_mismatches(mismatches);
+}
+
+#[tokio::test]
+async fn test_completion_golden_files() {
+    let mut mismatches = Vec::new();
+    for fixture in fixtures("completion") {
+        let handlers = fixture.handlers().await;
+        let capabilities = fixture.client_capabilities();
+        let mut actual = String::new();
+        for position in &fixture.cursors {
+            let completion = handlers
+                .completion_with_capabilities(
+                    CompletionParams {
+                        text_document_position: fixture.position_params(*position),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                        partial_result_params: PartialResultParams::default(),
+                        context: None,
+                    },
+                    Some(&capabilities),
+                )
+                .await
+                .unwrap();
+            actual.push_str(&section("completion", *position));
+            actual.push_str(&render_completion(completion));
+        }
+        mismatches.extend(check_snapshot(&fixture, &actual));
+    }
+    assert_no_mismatches(mismatches);
+}
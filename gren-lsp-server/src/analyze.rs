@@ -0,0 +1,279 @@
+//! `gren-lsp analyze`: a snapshot of a workspace for dashboards and code-health tracking
+//!
+//! Indexes the project like the server does on startup, runs every lint and prints the
+//! symbols, module graph, diagnostics, unused declarations and metrics of the project's
+//! own files. `--json` prints the full report for other tools to read; without it only the
+//! totals are printed. The numbers come from the same code the editor features use, so a
+//! dashboard and the editor never disagree.
+
+use crate::check::{load_project, LoadedProject};
+use anyhow::{Context, Result};
+use clap::Args;
+use gren_lsp_core::{LintProfile, Symbol};
+use gren_lsp_protocol::handlers::Handlers;
+use gren_lsp_protocol::lsp_ext::{MetricsParams, ModuleMetricsReport};
+use lsp_types::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    /// Project root to analyze, defaults to the current directory
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    /// Lint profile, `default` or `strict`; use the one set in `gren.lints.profile`.
+    /// Defaults to `lints.profile` in `.gren-lsp.toml`, then `default`.
+    #[arg(long)]
+    pub profile: Option<LintProfile>,
+
+    /// Print the full report as JSON instead of a summary
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Everything `gren-lsp analyze --json` reports. Files are paths relative to the root,
+/// and every list is sorted by file, so reports of the same tree compare equal.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisReport {
+    pub root: PathBuf,
+    pub summary: AnalysisSummary,
+    pub modules: Vec<ModuleNode>,
+    pub symbols: Vec<SymbolEntry>,
+    pub diagnostics: Vec<FileDiagnostic>,
+    pub dead_code: Vec<SymbolEntry>,
+    pub metrics: Vec<ModuleMetricsReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisSummary {
+    /// Gren files loaded, including dependencies' sources
+    pub files: usize,
+    /// Files of the project itself, which the rest of the report covers
+    pub project_files: usize,
+    pub symbols: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub unused_declarations: usize,
+}
+
+/// A module of the module graph and its edges within the project
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleNode {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    /// Every imported module, including dependencies' modules
+    pub imports: Vec<String>,
+    /// Project modules importing this one
+    pub imported_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolEntry {
+    pub file: String,
+    pub name: String,
+    pub kind: &'static str,
+    pub range: Range,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiagnostic {
+    pub file: String,
+    #[serde(flatten)]
+    pub diagnostic: Diagnostic,
+}
+
+/// Run `gren-lsp analyze`
+pub async fn run(args: AnalyzeArgs) -> Result<()> {
+    let root = match args.root {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Project root {} doesn't exist", root.display()))?;
+
+    let report = analyze(&root, args.profile).await?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        let summary = &report.summary;
+        println!(
+            "{} modules, {} symbols",
+            summary.project_files, summary.symbols
+        );
+        println!(
+            "{} errors, {} warnings, {} unused declarations",
+            summary.errors, summary.warnings, summary.unused_declarations
+        );
+    }
+    Ok(())
+}
+
+/// Index the project under `root` and build its report
+pub async fn analyze(root: &Path, profile: Option<LintProfile>) -> Result<AnalysisReport> {
+    let LoadedProject {
+        mut workspace,
+        mut project_files,
+        file_count,
+    } = load_project(root, profile)?;
+    project_files.sort();
+
+    let mut modules = Vec::new();
+    let mut symbols = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (path, uri) in &project_files {
+        let file = relative_file(root, path);
+        let module_name = workspace.module_name(uri);
+        let imported_by = module_name
+            .as_ref()
+            .map(|module_name| {
+                workspace
+                    .module_importers(module_name)
+                    .iter()
+                    .filter(|importer| {
+                        project_files
+                            .iter()
+                            .any(|(_, project_uri)| project_uri == *importer)
+                    })
+                    .filter_map(|importer| workspace.module_name(importer))
+                    .collect()
+            })
+            .unwrap_or_default();
+        modules.push(ModuleNode {
+            file: file.clone(),
+            module_name,
+            imports: workspace.module_imports(uri),
+            imported_by,
+        });
+
+        symbols.extend(
+            workspace
+                .get_file_symbols(uri)?
+                .iter()
+                .map(|symbol| symbol_entry(&file, symbol)),
+        );
+        diagnostics.extend(
+            workspace
+                .get_lint_diagnostics(uri)
+                .into_iter()
+                .map(|diagnostic| FileDiagnostic {
+                    file: file.clone(),
+                    diagnostic,
+                }),
+        );
+    }
+
+    let handlers = Handlers::new(Arc::new(RwLock::new(workspace)));
+    let mut dead_code = Vec::new();
+    for (path, uri) in &project_files {
+        let file = relative_file(root, path);
+        dead_code.extend(
+            handlers
+                .unused_declarations(uri)
+                .await
+                .iter()
+                .map(|symbol| symbol_entry(&file, symbol)),
+        );
+    }
+    let metrics = handlers
+        .metrics(MetricsParams::default())
+        .await
+        .map_err(|error| anyhow::anyhow!("{}", error.message))?;
+
+    let count_severity = |severity| {
+        diagnostics
+            .iter()
+            .filter(|entry| entry.diagnostic.severity == Some(severity))
+            .count()
+    };
+    let summary = AnalysisSummary {
+        files: file_count,
+        project_files: project_files.len(),
+        symbols: symbols.len(),
+        errors: count_severity(DiagnosticSeverity::ERROR),
+        warnings: count_severity(DiagnosticSeverity::WARNING),
+        unused_declarations: dead_code.len(),
+    };
+
+    Ok(AnalysisReport {
+        root: root.to_path_buf(),
+        summary,
+        modules,
+        symbols,
+        diagnostics,
+        dead_code,
+        metrics,
+    })
+}
+
+fn relative_file(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn symbol_entry(file: &str, symbol: &Symbol) -> SymbolEntry {
+    SymbolEntry {
+        file: file.to_string(),
+        name: symbol.name.clone(),
+        kind: symbol_kind_name(symbol.kind),
+        range: symbol.location.range,
+        container: symbol.container_name.clone(),
+        signature: symbol.type_signature.clone(),
+    }
+}
+
+/// The kinds of Gren declarations, as the report names them
+fn symbol_kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::FUNCTION => "function",
+        SymbolKind::CLASS => "type",
+        SymbolKind::CONSTRUCTOR => "constructor",
+        SymbolKind::MODULE => "module",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_diagnostics_flatten() {
+        let entry = FileDiagnostic {
+            file: "src/Main.gren".to_string(),
+            diagnostic: Diagnostic {
+                range: Range::new(Position::new(4, 0), Position::new(4, 12)),
+                severity: Some(DiagnosticSeverity::WARNING),
+                message: "`String` is imported but never used".to_string(),
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["file"], "src/Main.gren");
+        assert_eq!(json["message"], "`String` is imported but never used");
+        assert_eq!(json["range"]["start"]["line"], 4);
+    }
+
+    #[test]
+    fn test_relative_file() {
+        assert_eq!(
+            relative_file(Path::new("/project"), Path::new("/project/src/Main.gren")),
+            "src/Main.gren"
+        );
+    }
+}
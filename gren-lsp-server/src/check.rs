@@ -0,0 +1,167 @@
+//! `gren-lsp check`: the diagnostics the server finds without the compiler, for CI
+//!
+//! Reports annotation hints, shadowing, recursive aliases and the lints for every project
+//! file, with the same lint profile the editor is configured with, and fails when any of
+//! them is an error. Run it next to `gren make` so CI and the editor agree on what's an error.
+//! The project's `.gren-lsp.toml` applies as it does in the editor.
+
+use crate::refactor::collect_gren_files;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use gren_lsp_core::{InMemorySymbolStore, LintProfile, Workspace};
+use gren_lsp_protocol::package_docs::package_module_for_path;
+use gren_lsp_protocol::settings::ServerSettings;
+use lsp_types::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// Project root to check, defaults to the current directory
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    /// Lint profile, `default` or `strict`; use the one set in `gren.lints.profile`.
+    /// Defaults to `lints.profile` in `.gren-lsp.toml`, then `default`.
+    #[arg(long)]
+    pub profile: Option<LintProfile>,
+}
+
+/// Run `gren-lsp check`, returning whether the project has no errors
+pub async fn run(args: CheckArgs) -> Result<bool> {
+    let root = match args.root {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Project root {} doesn't exist", root.display()))?;
+
+    let LoadedProject {
+        mut workspace,
+        project_files,
+        file_count,
+    } = load_project(&root, args.profile)?;
+
+    let mut errors = 0;
+    let mut others = 0;
+    for (path, uri) in project_files {
+        for diagnostic in workspace.get_lint_diagnostics(&uri) {
+            if diagnostic.severity == Some(DiagnosticSeverity::ERROR) {
+                errors += 1;
+            } else {
+                others += 1;
+            }
+            println!("{}", format_diagnostic(&root, &path, &diagnostic));
+        }
+    }
+
+    eprintln!(
+        "{} errors, {} other diagnostics in {} files",
+        errors, others, file_count
+    );
+    Ok(errors == 0)
+}
+
+/// Every Gren file under a project root loaded into a workspace
+pub struct LoadedProject {
+    pub workspace: Workspace,
+    /// The project's own files, leaving out dependencies' sources
+    pub project_files: Vec<(PathBuf, Url)>,
+    pub file_count: usize,
+}
+
+/// Load every Gren file under `root` with the project's `.gren-lsp.toml`, and `profile`
+/// over the one it sets. Dependencies' sources are loaded too, so names they expose are
+/// known, but only the project's own files are listed for reporting. Files the
+/// configuration excludes aren't loaded.
+pub fn load_project(root: &Path, profile: Option<LintProfile>) -> Result<LoadedProject> {
+    let mut files = Vec::new();
+    collect_gren_files(root, &mut files)?;
+
+    let mut workspace = Workspace::with_capacity(files.len())?;
+    workspace.set_symbol_store(Arc::new(InMemorySymbolStore::new()))?;
+    workspace.set_project_root(root)?;
+    if let Some(error) = workspace.project_config_error() {
+        bail!("{}", error);
+    }
+    let settings = ServerSettings::default().with_project_config(workspace.project_config());
+    workspace.set_import_lint(settings.imports.lint_style());
+    workspace.set_lint_profile(profile.unwrap_or(settings.lints.profile));
+    files.retain(|path| match Url::from_file_path(path) {
+        Ok(uri) => !workspace.is_excluded(&uri),
+        Err(_) => true,
+    });
+
+    let mut project_files = Vec::new();
+    for path in &files {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let uri = Url::from_file_path(path)
+            .map_err(|_| anyhow!("Invalid file path: {}", path.display()))?;
+        workspace.open_document(TextDocumentItem {
+            uri: uri.clone(),
+            language_id: "gren".to_string(),
+            version: 1,
+            text,
+        })?;
+        if package_module_for_path(path).is_none() {
+            project_files.push((path.clone(), uri));
+        }
+    }
+
+    Ok(LoadedProject {
+        workspace,
+        project_files,
+        file_count: files.len(),
+    })
+}
+
+/// `path:line:column: severity: message [code]`, with 1-based lines and columns as
+/// compilers print them
+fn format_diagnostic(root: &Path, path: &Path, diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        _ => "hint",
+    };
+    let code = match &diagnostic.code {
+        Some(NumberOrString::String(code)) => format!(" [{}]", code),
+        Some(NumberOrString::Number(code)) => format!(" [{}]", code),
+        None => String::new(),
+    };
+    format!(
+        "{}:{}:{}: {}: {}{}",
+        path.strip_prefix(root).unwrap_or(path).display(),
+        diagnostic.range.start.line + 1,
+        diagnostic.range.start.character + 1,
+        severity,
+        diagnostic.message,
+        code
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_diagnostic() {
+        let diagnostic = Diagnostic {
+            range: Range::new(Position::new(4, 0), Position::new(4, 12)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("unused-import".to_string())),
+            message: "`String` is imported but never used".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_diagnostic(
+                Path::new("/project"),
+                Path::new("/project/src/Main.gren"),
+                &diagnostic
+            ),
+            "src/Main.gren:5:1: error: `String` is imported but never used [unused-import]"
+        );
+    }
+}
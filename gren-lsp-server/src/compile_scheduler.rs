@@ -0,0 +1,133 @@
+//! Scheduling the compiles that refresh an open document's diagnostics while it's edited
+//!
+//! Each edit asks for a compile of its document, which waits for typing to pause first.
+//! An edit arriving during the wait replaces the request, so a burst of edits compiles
+//! once. An edit arriving while the compile runs cancels it, since its diagnostics would
+//! be for text that's gone, and frees the workspace lock the compile holds.
+//!
+//! Compiles are looked up by the file a URI names rather than its spelling, so a
+//! notification spelling the document differently still cancels its compile without first
+//! waiting on the workspace lock to normalize the URI.
+
+use gren_lsp_core::uri::{host_is_case_insensitive, uri_key};
+use lsp_types::Url;
+use std::collections::HashMap;
+use tokio::task::AbortHandle;
+
+/// The compile task of each document being edited
+#[derive(Debug)]
+pub struct CompileScheduler {
+    /// Keyed by `uri_key`
+    tasks: HashMap<String, (u64, AbortHandle)>,
+    next_ticket: u64,
+    case_insensitive: bool,
+}
+
+impl Default for CompileScheduler {
+    fn default() -> Self {
+        Self {
+            tasks: HashMap::new(),
+            next_ticket: 0,
+            case_insensitive: host_is_case_insensitive(),
+        }
+    }
+}
+
+impl CompileScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key(&self, uri: &Url) -> String {
+        uri_key(uri, self.case_insensitive)
+    }
+
+    /// Ask for a compile of `uri`, cancelling the one waiting or running for it. Returns
+    /// the ticket to pass to `started` and `finished`.
+    pub fn request(&mut self, uri: &Url) -> u64 {
+        self.cancel(uri);
+        self.next_ticket += 1;
+        self.next_ticket
+    }
+
+    /// Record the task compiling `uri` for `ticket`
+    pub fn started(&mut self, uri: Url, ticket: u64, task: AbortHandle) {
+        self.tasks.insert(self.key(&uri), (ticket, task));
+    }
+
+    /// Forget the task for `ticket`, unless a newer request replaced it
+    pub fn finished(&mut self, uri: &Url, ticket: u64) {
+        let key = self.key(uri);
+        if self.tasks.get(&key).map(|(current, _)| *current) == Some(ticket) {
+            self.tasks.remove(&key);
+        }
+    }
+
+    /// Cancel the compile waiting or running for `uri`, returning whether there was one
+    pub fn cancel(&mut self, uri: &Url) -> bool {
+        match self.tasks.remove(&self.key(uri)) {
+            Some((_, task)) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn uri() -> Url {
+        Url::parse("file:///project/src/Main.gren").unwrap()
+    }
+
+    fn idle_task() -> tokio::task::JoinHandle<()> {
+        tokio::spawn(tokio::time::sleep(Duration::from_secs(60)))
+    }
+
+    #[tokio::test]
+    async fn test_request_cancels_previous_compile() {
+        let mut scheduler = CompileScheduler::new();
+        let first = scheduler.request(&uri());
+        let task = idle_task();
+        scheduler.started(uri(), first, task.abort_handle());
+
+        let second = scheduler.request(&uri());
+        assert_ne!(first, second);
+        assert!(task.await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_finished_keeps_newer_request() {
+        let mut scheduler = CompileScheduler::new();
+        let first = scheduler.request(&uri());
+        let second = scheduler.request(&uri());
+        let task = idle_task();
+        scheduler.started(uri(), second, task.abort_handle());
+
+        // The cancelled compile finishing doesn't forget the newer one
+        scheduler.finished(&uri(), first);
+        assert!(scheduler.cancel(&uri()));
+        assert!(task.await.unwrap_err().is_cancelled());
+
+        let third = scheduler.request(&uri());
+        scheduler.started(uri(), third, idle_task().abort_handle());
+        scheduler.finished(&uri(), third);
+        assert!(!scheduler.cancel(&uri()));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_matches_other_spelling_of_uri() {
+        let mut scheduler = CompileScheduler::new();
+        let ticket = scheduler.request(&uri());
+        let task = idle_task();
+        scheduler.started(uri(), ticket, task.abort_handle());
+
+        let encoded = Url::parse("file:///project/src/M%61in.gren").unwrap();
+        assert!(scheduler.cancel(&encoded));
+        assert!(task.await.unwrap_err().is_cancelled());
+    }
+}
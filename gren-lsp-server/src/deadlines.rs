@@ -0,0 +1,298 @@
+//! Deadlines for language feature requests
+//!
+//! A handler that hasn't answered by its method's deadline is dropped, and the request is
+//! answered with a "timed out" error, or with an empty result where the editor asks again
+//! as the user types (completion, hover, signature help), instead of leaving the editor
+//! waiting. The engine's work is synchronous, so handlers run on a blocking thread and the
+//! deadline passes on time even while one is busy. A handler past its deadline is dropped
+//! at its next await, and the synchronous step it's in finishes in the background, still
+//! holding the request's load-shedding permit so the limits count it until it's done.
+//! Pages of `workspace/symbol` results streamed before the deadline stay with the client.
+//! Answered and timed out requests are counted per method for `gren/requestStats`.
+
+use crate::load_shedding::RequestPermit;
+use gren_lsp_protocol::lsp_ext::RequestMethodStats;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
+use tracing::warn;
+
+/// `RequestFailed` from the LSP specification
+const REQUEST_FAILED: i64 = -32803;
+
+/// Deadline in milliseconds per method. Methods not listed get `DEFAULT_TIMEOUT_MS`.
+const METHOD_TIMEOUTS: &[(&str, u64)] = &[
+    ("textDocument/completion", 2_000),
+    ("textDocument/hover", 2_000),
+    ("textDocument/signatureHelp", 2_000),
+    ("textDocument/inlayHint", 2_000),
+    ("textDocument/inlineCompletion", 2_000),
+    ("textDocument/references", 10_000),
+    ("textDocument/rename", 10_000),
+    ("textDocument/formatting", 10_000),
+    ("textDocument/rangeFormatting", 10_000),
+    ("callHierarchy/incomingCalls", 10_000),
+    ("workspace/symbol", 10_000),
+];
+
+const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+
+#[derive(Debug, Default)]
+struct MethodStats {
+    handled: u64,
+    timed_out: u64,
+    slowest: Duration,
+}
+
+#[derive(Debug, Default)]
+struct DeadlineState {
+    /// The user's deadlines in milliseconds, replacing the built-in ones
+    overrides: HashMap<String, u64>,
+    stats: HashMap<&'static str, MethodStats>,
+}
+
+/// Per-method deadlines for handling requests, and how often each was missed
+#[derive(Debug, Clone, Default)]
+pub struct RequestDeadlines {
+    state: Arc<Mutex<DeadlineState>>,
+}
+
+impl RequestDeadlines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the `gren.timeouts` deadlines over the built-in ones
+    pub fn set_overrides(&self, overrides: HashMap<String, u64>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.overrides = overrides;
+        }
+    }
+
+    /// How long a `method` request may take, `None` when it may take as long as it needs
+    pub fn timeout(&self, method: &str) -> Option<Duration> {
+        let overridden = match self.state.lock() {
+            Ok(state) => state.overrides.get(method).copied(),
+            Err(_) => None,
+        };
+        let millis = overridden.unwrap_or_else(|| {
+            METHOD_TIMEOUTS
+                .iter()
+                .find(|(limited, _)| *limited == method)
+                .map(|(_, millis)| *millis)
+                .unwrap_or(DEFAULT_TIMEOUT_MS)
+        });
+        if millis == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(millis))
+        }
+    }
+
+    /// Handle a `method` request within its deadline, `None` when the deadline passed. The
+    /// `permit` is released once the handler has stopped, not when the deadline passes.
+    pub async fn within<T: Send + 'static>(
+        &self,
+        method: &'static str,
+        permit: RequestPermit,
+        request: impl Future<Output = T> + Send + 'static,
+    ) -> Option<T> {
+        let timeout = self.timeout(method);
+        let started = Instant::now();
+
+        // Dropping `_cancel`, once the request is answered, timed out or cancelled by the
+        // client, drops the handler at its next await
+        let (_cancel, cancelled) = oneshot::channel::<()>();
+        let runtime = tokio::runtime::Handle::current();
+        let handler = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            runtime.block_on(async {
+                tokio::select! {
+                    result = request => Some(result),
+                    _ = cancelled => None,
+                }
+            })
+        });
+        let handled = async {
+            match handler.await {
+                Ok(result) => result,
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Err(_) => None,
+            }
+        };
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, handled).await.ok().flatten(),
+            None => handled.await,
+        };
+        self.record(method, started.elapsed(), result.is_some());
+        result
+    }
+
+    /// Handle a `method` request within its deadline, answering with a "timed out" error
+    /// when the deadline passed
+    pub async fn enforce<T: Send + 'static>(
+        &self,
+        method: &'static str,
+        permit: RequestPermit,
+        request: impl Future<Output = Result<T>> + Send + 'static,
+    ) -> Result<T> {
+        match self.within(method, permit, request).await {
+            Some(result) => result,
+            None => Err(timeout_error(method, self.timeout(method))),
+        }
+    }
+
+    /// Requests answered and timed out per method, the most often timed out first
+    pub fn stats(&self) -> Vec<RequestMethodStats> {
+        let methods: Vec<(&'static str, u64, u64, Duration)> = match self.state.lock() {
+            Ok(state) => state
+                .stats
+                .iter()
+                .map(|(method, stats)| (*method, stats.handled, stats.timed_out, stats.slowest))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        let mut stats: Vec<RequestMethodStats> = methods
+            .into_iter()
+            .map(|(method, handled, timed_out, slowest)| RequestMethodStats {
+                method: method.to_string(),
+                timeout_ms: self
+                    .timeout(method)
+                    .map(|timeout| timeout.as_millis() as u64),
+                handled,
+                timed_out,
+                slowest_ms: slowest.as_millis() as u64,
+            })
+            .collect();
+        stats.sort_by(|a, b| {
+            b.timed_out
+                .cmp(&a.timed_out)
+                .then_with(|| a.method.cmp(&b.method))
+        });
+        stats
+    }
+
+    fn record(&self, method: &'static str, elapsed: Duration, in_time: bool) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let stats = state.stats.entry(method).or_default();
+        if in_time {
+            stats.handled += 1;
+            stats.slowest = stats.slowest.max(elapsed);
+        } else {
+            stats.timed_out += 1;
+            warn!(
+                "{} timed out after {:?} ({} of {} requests so far)",
+                method,
+                elapsed,
+                stats.timed_out,
+                stats.handled + stats.timed_out
+            );
+        }
+    }
+}
+
+fn timeout_error(method: &str, timeout: Option<Duration>) -> Error {
+    let after = timeout
+        .map(|timeout| format!(" after {:?}", timeout))
+        .unwrap_or_default();
+    Error {
+        code: ErrorCode::ServerError(REQUEST_FAILED),
+        message: format!(
+            "{} timed out{}. Its deadline can be raised with the gren.timeouts setting",
+            method, after
+        )
+        .into(),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_shedding::RequestLimiter;
+
+    #[test]
+    fn test_timeouts_overridden_by_settings() {
+        let deadlines = RequestDeadlines::new();
+        assert_eq!(
+            deadlines.timeout("textDocument/completion"),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            deadlines.timeout("gren/metrics"),
+            Some(Duration::from_millis(DEFAULT_TIMEOUT_MS))
+        );
+
+        deadlines.set_overrides(HashMap::from([
+            ("textDocument/completion".to_string(), 3_000),
+            ("textDocument/rename".to_string(), 0),
+        ]));
+        assert_eq!(
+            deadlines.timeout("textDocument/completion"),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(deadlines.timeout("textDocument/rename"), None);
+    }
+
+    #[tokio::test]
+    async fn test_requests_past_deadline_time_out() {
+        let deadlines = RequestDeadlines::new();
+        deadlines.set_overrides(HashMap::from([("textDocument/rename".to_string(), 10)]));
+
+        let limiter = RequestLimiter::new();
+
+        let answered = deadlines
+            .enforce(
+                "textDocument/rename",
+                limiter.acquire("textDocument/rename").unwrap(),
+                async { Ok(1) },
+            )
+            .await;
+        assert_eq!(answered.unwrap(), 1);
+
+        let error = deadlines
+            .enforce(
+                "textDocument/rename",
+                limiter.acquire("textDocument/rename").unwrap(),
+                async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(2)
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::ServerError(REQUEST_FAILED));
+        assert!(error.message.contains("timed out"));
+
+        // Synchronous work doesn't hold the answer up past the deadline, but keeps its
+        // permit until it's done
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let started = Instant::now();
+        let blocked = deadlines
+            .within(
+                "textDocument/rename",
+                limiter.acquire("textDocument/rename").unwrap(),
+                async {
+                    std::thread::sleep(Duration::from_millis(500));
+                },
+            )
+            .await;
+        assert!(blocked.is_none());
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(limiter.try_acquire("textDocument/rename").is_none());
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(limiter.try_acquire("textDocument/rename").is_some());
+
+        let stats = deadlines.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].method, "textDocument/rename");
+        assert_eq!(stats[0].timeout_ms, Some(10));
+        assert_eq!((stats[0].handled, stats[0].timed_out), (1, 2));
+    }
+}
@@ -0,0 +1,83 @@
+//! `gren-lsp fmt`: format every project file the way the editor does
+//!
+//! Runs the compiler's formatter over the project's own files, leaving out dependencies'
+//! sources and what `.gren-lsp.toml` excludes, through the same integration as
+//! `textDocument/formatting`. `--check` writes nothing, lists the files that aren't
+//! formatted and fails when there are any, for CI.
+
+use crate::check::{load_project, LoadedProject};
+use anyhow::{Context, Result};
+use clap::Args;
+use gren_lsp_core::GrenCompiler;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct FmtArgs {
+    /// Project root to format, defaults to the current directory
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    /// List the files that aren't formatted instead of formatting them, failing when there
+    /// are any
+    #[arg(long)]
+    pub check: bool,
+}
+
+/// Run `gren-lsp fmt`, returning whether every file was formatted already or could be
+/// formatted
+pub async fn run(args: FmtArgs) -> Result<bool> {
+    let root = match args.root {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Project root {} doesn't exist", root.display()))?;
+
+    let LoadedProject {
+        workspace,
+        project_files,
+        ..
+    } = load_project(&root, None)?;
+    let mut compiler = GrenCompiler::new(root.clone())?;
+    compiler.set_options(workspace.compiler_options());
+    let formatter = compiler.formatter();
+
+    let mut unformatted = 0;
+    let mut failed = 0;
+    for (path, uri) in &project_files {
+        let relative = path.strip_prefix(&root).unwrap_or(path).display();
+        let source = workspace.file_contents(uri).unwrap_or_default();
+        let formatted = match formatter.format(&source).await {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}: {}", relative, e);
+                failed += 1;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        unformatted += 1;
+        println!("{}", relative);
+        if !args.check {
+            std::fs::write(path, formatted)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+    }
+
+    let verb = if args.check {
+        "aren't formatted"
+    } else {
+        "formatted"
+    };
+    eprintln!(
+        "{} of {} files {}, {} couldn't be formatted",
+        unformatted,
+        project_files.len(),
+        verb,
+        failed
+    );
+    Ok(failed == 0 && (!args.check || unformatted == 0))
+}
@@ -1,2 +1,14 @@
+pub mod analyze;
+pub mod check;
+pub mod compile_scheduler;
+pub mod deadlines;
+pub mod fmt;
+pub mod load_shedding;
+pub mod methods;
+pub mod open_burst;
+pub mod readiness;
+pub mod recompile_queue;
+pub mod refactor;
 pub mod server;
 pub mod test_utils;
+pub mod trace_control;
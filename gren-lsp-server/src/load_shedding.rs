@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tower_lsp::jsonrpc::{Error, ErrorCode};
+use tracing::{info, warn};
+
+/// `ServerCancelled` from the LSP specification: the server gave up on the request and the
+/// client may send it again
+const SERVER_CANCELLED: i64 = -32802;
+
+/// Most requests of one method handled at once. Methods not listed get
+/// `DEFAULT_METHOD_LIMIT`.
+const METHOD_LIMITS: &[(&str, usize)] = &[
+    ("textDocument/completion", 2),
+    ("textDocument/hover", 2),
+    ("textDocument/definition", 2),
+    ("textDocument/codeAction", 2),
+    ("textDocument/codeLens", 2),
+    ("textDocument/documentSymbol", 2),
+    ("textDocument/foldingRange", 2),
+    ("textDocument/references", 1),
+    ("textDocument/rename", 1),
+    ("workspace/symbol", 1),
+];
+
+const DEFAULT_METHOD_LIMIT: usize = 4;
+
+/// Most limited requests handled at once across all methods
+const TOTAL_LIMIT: usize = 8;
+
+/// Messages tower-lsp processes at once. Higher than `TOTAL_LIMIT`, so document sync
+/// notifications always get through while feature requests are at their limits.
+pub const CONCURRENCY_LEVEL: usize = TOTAL_LIMIT + 8;
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    in_flight: HashMap<&'static str, usize>,
+    total: usize,
+    /// Requests turned away per method since one was last handled
+    shed: HashMap<&'static str, usize>,
+}
+
+/// Overload protection for language feature requests
+///
+/// A misbehaving editor can send requests faster than the server answers them, e.g. a
+/// completion for every keystroke without cancelling the previous one. Each request holds
+/// a permit while it's handled, and once a method, or all methods together, are at their
+/// limit, new requests are answered straight away with an empty or "server busy" result
+/// instead of queueing behind the others. Document sync and diagnostics never take
+/// permits, so they stay responsive however many requests are waiting.
+#[derive(Debug, Clone, Default)]
+pub struct RequestLimiter {
+    state: Arc<Mutex<LimiterState>>,
+}
+
+/// Held while a request is handled, releasing its slot when dropped
+#[derive(Debug)]
+pub struct RequestPermit {
+    state: Arc<Mutex<LimiterState>>,
+    method: &'static str,
+}
+
+impl RequestLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A permit for handling a `method` request, `None` when the request should be shed
+    pub fn try_acquire(&self, method: &'static str) -> Option<RequestPermit> {
+        let mut state = self.state.lock().ok()?;
+        let in_flight = state.in_flight.get(method).copied().unwrap_or(0);
+        if in_flight >= method_limit(method) || state.total >= TOTAL_LIMIT {
+            let shed = state.shed.entry(method).or_insert(0);
+            *shed += 1;
+            if *shed == 1 {
+                warn!(
+                    "Server overloaded, answering {} requests without handling them ({} of this method and {} in total in flight)",
+                    method, in_flight, state.total
+                );
+            }
+            return None;
+        }
+
+        if let Some(shed) = state.shed.remove(method) {
+            info!(
+                "Handling {} requests again after turning away {}",
+                method, shed
+            );
+        }
+        *state.in_flight.entry(method).or_insert(0) += 1;
+        state.total += 1;
+        Some(RequestPermit {
+            state: self.state.clone(),
+            method,
+        })
+    }
+
+    /// A permit for handling a `method` request, or the "server busy" error to answer it
+    /// with
+    pub fn acquire(&self, method: &'static str) -> tower_lsp::jsonrpc::Result<RequestPermit> {
+        self.try_acquire(method).ok_or_else(|| busy_error(method))
+    }
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        state.total = state.total.saturating_sub(1);
+        if let Some(in_flight) = state.in_flight.get_mut(self.method) {
+            *in_flight = in_flight.saturating_sub(1);
+        }
+    }
+}
+
+fn method_limit(method: &str) -> usize {
+    METHOD_LIMITS
+        .iter()
+        .find(|(limited, _)| *limited == method)
+        .map(|(_, limit)| *limit)
+        .unwrap_or(DEFAULT_METHOD_LIMIT)
+}
+
+fn busy_error(method: &str) -> Error {
+    Error {
+        code: ErrorCode::ServerError(SERVER_CANCELLED),
+        message: format!(
+            "Server is busy, {} was not handled. Try again shortly",
+            method
+        )
+        .into(),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_shed_over_method_limit() {
+        let limiter = RequestLimiter::new();
+        let first = limiter.try_acquire("textDocument/references").unwrap();
+        assert!(limiter.try_acquire("textDocument/references").is_none());
+
+        let error = limiter.acquire("textDocument/references").unwrap_err();
+        assert_eq!(error.code, ErrorCode::ServerError(SERVER_CANCELLED));
+
+        // Other methods have room of their own
+        assert!(limiter.try_acquire("textDocument/hover").is_some());
+
+        drop(first);
+        assert!(limiter.try_acquire("textDocument/references").is_some());
+    }
+
+    #[test]
+    fn test_requests_shed_over_total_limit() {
+        let limiter = RequestLimiter::new();
+        let mut permits = Vec::new();
+        for method in ["gren/metrics", "gren/todos"] {
+            for _ in 0..DEFAULT_METHOD_LIMIT {
+                permits.push(limiter.try_acquire(method).unwrap());
+            }
+        }
+        assert_eq!(permits.len(), TOTAL_LIMIT);
+        assert!(limiter.try_acquire("textDocument/completion").is_none());
+
+        drop(permits);
+        assert!(limiter.try_acquire("textDocument/completion").is_some());
+    }
+}
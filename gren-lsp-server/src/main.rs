@@ -1,30 +1,113 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use gren_lsp_protocol::lsp_ext::{
+    ActiveDocumentNotification, AlternatePosition, CapabilitiesReport, CompileHistory,
+    DeprecatedUsages, FindValuesOfType, IndexFreshness, IndexStatsRequest, InlineCompletion,
+    Metrics, ModuleExports, ModuleRenameImpact, OpenModule, RequestStatsRequest,
+    RuntimeLogNotification, SearchDocs, SetSubsystemTraceNotification, Todos,
+};
+use lsp_types::notification::{Notification, SetTrace};
+use lsp_types::request::Request;
 use std::path::PathBuf;
 use tower_lsp::{LspService, Server};
 use tracing::info;
-use tracing_subscriber::EnvFilter;
-
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter};
+
+mod analyze;
+mod check;
+mod compile_scheduler;
+mod deadlines;
+mod fmt;
+mod load_shedding;
+mod methods;
+mod open_burst;
+mod readiness;
+mod recompile_queue;
+mod refactor;
 mod server;
+#[cfg(feature = "otel")]
+mod telemetry;
+mod trace_control;
 use server::GrenLanguageServer;
+use trace_control::{TraceControl, DEFAULT_DIRECTIVES};
 
 mod test_utils;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Enable debug mode to export tree-sitter parse trees
     #[arg(
         long,
         help = "Export tree-sitter parse trees to specified directory for debugging"
     )]
     debug_export_trees: Option<PathBuf>,
+
+    /// Export request, parse, index and compile spans to an OTLP collector
+    #[cfg(feature = "otel")]
+    #[arg(
+        long,
+        env = "OTEL_EXPORTER_OTLP_ENDPOINT",
+        num_args = 0..=1,
+        default_missing_value = telemetry::DEFAULT_OTLP_ENDPOINT,
+        help = "Export tracing spans over OTLP/gRPC to the given collector endpoint"
+    )]
+    otlp_endpoint: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a rename, module rename or organize imports from a JSON instruction and print
+    /// the resulting workspace edit
+    Refactor(refactor::RefactorArgs),
+    /// Report annotation hints, shadowing and lints for every project file, failing when
+    /// any is an error
+    Check(check::CheckArgs),
+    /// Format every project file with the compiler's formatter, or with `--check` list the
+    /// ones that aren't formatted, failing when there are any
+    Fmt(fmt::FmtArgs),
+    /// Index the project, run every lint and report symbols, the module graph,
+    /// diagnostics, unused declarations and metrics
+    Analyze(analyze::AnalyzeArgs),
+    /// List the LSP methods and commands this binary serves, or compare them with another
+    /// `gren-lsp` binary's
+    Methods(methods::MethodsArgs),
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
+    // Subcommands print their results to stdout, so they run without the server's logging
+    match args.command {
+        Some(Command::Refactor(refactor_args)) => return refactor::run(refactor_args).await,
+        Some(Command::Check(check_args)) => {
+            if !check::run(check_args).await? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Fmt(fmt_args)) => {
+            if !fmt::run(fmt_args).await? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Analyze(analyze_args)) => return analyze::run(analyze_args).await,
+        Some(Command::Methods(methods_args)) => {
+            if !methods::run(methods_args)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
     // Initialize logging to stderr so VS Code can capture it in the output channel
     // Also keep file logging for debugging purposes
     let log_dir = std::env::temp_dir().join("gren-lsp");
@@ -40,13 +123,34 @@ async fn main() -> Result<()> {
     let multi_writer =
         tracing_subscriber::fmt::writer::MakeWriterExt::and(stderr_writer, file_non_blocking);
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("gren_lsp=debug")),
-        )
-        .with_writer(multi_writer)
-        .with_ansi(false)
-        .init();
+    // The filter can be swapped at runtime through `$/setTrace` and `gren/setTrace`
+    let startup_directives = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|directives| EnvFilter::try_new(directives).is_ok())
+        .unwrap_or_else(|| DEFAULT_DIRECTIVES.to_string());
+    let (filter, filter_handle) = reload::Layer::new(EnvFilter::new(&startup_directives));
+    let registry = tracing_subscriber::registry().with(filter).with(
+        tracing_subscriber::fmt::layer()
+            .with_writer(multi_writer)
+            .with_ansi(false),
+    );
+
+    // Spans are only exported when an OTLP endpoint is configured; the guard flushes
+    // buffered spans when the server exits
+    #[cfg(feature = "otel")]
+    let _telemetry_guard = match &args.otlp_endpoint {
+        Some(endpoint) => {
+            let (otel_layer, guard) = telemetry::otlp_layer(endpoint)?;
+            registry.with(otel_layer).init();
+            Some(guard)
+        }
+        None => {
+            registry.init();
+            None
+        }
+    };
+    #[cfg(not(feature = "otel"))]
+    registry.init();
 
     info!("LSP server starting up");
 
@@ -72,15 +176,70 @@ async fn main() -> Result<()> {
         );
     }
 
-    // Create the language server
+    // Create the language server. Custom methods are listed in `methods::CUSTOM_METHODS`
+    // too, for `gren-lsp methods`.
     info!("Creating language server service");
-    let (service, socket) = LspService::new(move |client| {
+    let (service, socket) = LspService::build(move |client| {
         info!("Creating new language server instance");
         GrenLanguageServer::new_with_debug(client, args.debug_export_trees.clone())
-    });
+            .with_trace_control(TraceControl::new(startup_directives, filter_handle))
+    })
+    .custom_method(SearchDocs::METHOD, GrenLanguageServer::search_docs)
+    .custom_method(
+        CapabilitiesReport::METHOD,
+        GrenLanguageServer::capabilities_report,
+    )
+    .custom_method(Metrics::METHOD, GrenLanguageServer::metrics)
+    .custom_method(Todos::METHOD, GrenLanguageServer::todos)
+    .custom_method(CompileHistory::METHOD, GrenLanguageServer::compile_history)
+    .custom_method(
+        RuntimeLogNotification::METHOD,
+        GrenLanguageServer::runtime_log,
+    )
+    .custom_method(
+        DeprecatedUsages::METHOD,
+        GrenLanguageServer::deprecated_usages,
+    )
+    .custom_method(
+        ModuleRenameImpact::METHOD,
+        GrenLanguageServer::module_rename_impact,
+    )
+    .custom_method(
+        ActiveDocumentNotification::METHOD,
+        GrenLanguageServer::active_document,
+    )
+    .custom_method(IndexFreshness::METHOD, GrenLanguageServer::index_freshness)
+    .custom_method(OpenModule::METHOD, GrenLanguageServer::open_module)
+    .custom_method(ModuleExports::METHOD, GrenLanguageServer::module_exports)
+    .custom_method(
+        FindValuesOfType::METHOD,
+        GrenLanguageServer::find_values_of_type,
+    )
+    .custom_method(
+        AlternatePosition::METHOD,
+        GrenLanguageServer::alternate_position,
+    )
+    .custom_method(IndexStatsRequest::METHOD, GrenLanguageServer::index_stats)
+    .custom_method(SetTrace::METHOD, GrenLanguageServer::set_trace)
+    .custom_method(
+        SetSubsystemTraceNotification::METHOD,
+        GrenLanguageServer::set_subsystem_trace,
+    )
+    .custom_method(
+        InlineCompletion::METHOD,
+        GrenLanguageServer::inline_completion,
+    )
+    .custom_method(
+        RequestStatsRequest::METHOD,
+        GrenLanguageServer::request_stats,
+    )
+    .finish();
 
     // Run the server
-    Server::new(stdin, stdout, socket).serve(service).await;
+    Server::new(stdin, stdout, socket)
+        .concurrency_level(load_shedding::CONCURRENCY_LEVEL)
+        .serve(service)
+        .await;
 
     Ok(())
 }
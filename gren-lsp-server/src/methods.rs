@@ -0,0 +1,397 @@
+//! `gren-lsp methods`: the LSP methods and commands a server binary serves
+//!
+//! Editors launch whichever `gren-lsp` they find first, and an old install or a stale
+//! build answers a different set of requests than the current one. The list comes from
+//! the capabilities the server advertises in `initialize`, so it's what clients see.
+//! `--against` runs another binary's `methods --json` and reports what differs, failing
+//! when anything does, so a build can be checked against the one the editor uses.
+
+use crate::server::server_capabilities;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use gren_lsp_protocol::lsp_ext::{
+    ActiveDocumentNotification, AlternatePosition, CapabilitiesReport, CompileHistory,
+    DeprecatedUsages, FindValuesOfType, IndexFreshness, IndexStatsRequest, InlineCompletion,
+    Metrics, ModuleExports, ModuleRenameImpact, OpenModule, RequestStatsRequest,
+    RuntimeLogNotification, SearchDocs, SetSubsystemTraceNotification, Todos,
+};
+use lsp_types::notification::{self, Notification};
+use lsp_types::request::{self, Request};
+use lsp_types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Methods registered with `custom_method` in `main`, which keeps them in the same order
+pub const CUSTOM_METHODS: &[&str] = &[
+    SearchDocs::METHOD,
+    CapabilitiesReport::METHOD,
+    Metrics::METHOD,
+    Todos::METHOD,
+    CompileHistory::METHOD,
+    RuntimeLogNotification::METHOD,
+    DeprecatedUsages::METHOD,
+    ModuleRenameImpact::METHOD,
+    ActiveDocumentNotification::METHOD,
+    IndexFreshness::METHOD,
+    OpenModule::METHOD,
+    ModuleExports::METHOD,
+    FindValuesOfType::METHOD,
+    AlternatePosition::METHOD,
+    IndexStatsRequest::METHOD,
+    notification::SetTrace::METHOD,
+    SetSubsystemTraceNotification::METHOD,
+    InlineCompletion::METHOD,
+    RequestStatsRequest::METHOD,
+];
+
+/// Methods served whatever the capabilities say
+const LIFECYCLE_METHODS: &[&str] = &[
+    request::Initialize::METHOD,
+    notification::Initialized::METHOD,
+    request::Shutdown::METHOD,
+    notification::Exit::METHOD,
+    notification::DidChangeWatchedFiles::METHOD,
+    notification::DidChangeConfiguration::METHOD,
+];
+
+/// Methods registered with clients that support it after `initialized`, rather than
+/// advertised in the capabilities
+const REGISTERED_METHODS: &[&str] = &[
+    request::TypeHierarchyPrepare::METHOD,
+    request::TypeHierarchySupertypes::METHOD,
+    request::TypeHierarchySubtypes::METHOD,
+];
+
+#[derive(Args, Debug)]
+pub struct MethodsArgs {
+    /// Print the methods as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Another `gren-lsp` binary to compare with, failing when they serve different
+    /// methods or commands
+    #[arg(long)]
+    pub against: Option<PathBuf>,
+}
+
+/// What a binary serves, as `gren-lsp methods --json` prints it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodReport {
+    pub version: String,
+    pub methods: BTreeSet<String>,
+    /// Commands accepted by `workspace/executeCommand`
+    pub commands: BTreeSet<String>,
+}
+
+impl MethodReport {
+    /// The methods and commands of this binary
+    pub fn current() -> Self {
+        let capabilities = server_capabilities();
+        let commands = capabilities
+            .execute_command_provider
+            .as_ref()
+            .map(|options| options.commands.iter().cloned().collect())
+            .unwrap_or_default();
+        let methods = LIFECYCLE_METHODS
+            .iter()
+            .chain(advertised_methods(&capabilities).iter())
+            .chain(REGISTERED_METHODS)
+            .chain(CUSTOM_METHODS)
+            .map(|method| method.to_string())
+            .collect();
+        MethodReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            methods,
+            commands,
+        }
+    }
+
+    /// Lines describing what only one of the two reports has, empty when they agree
+    pub fn drift(&self, other: &MethodReport, other_name: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut describe = |kind: &str, ours: &BTreeSet<String>, theirs: &BTreeSet<String>| {
+            for name in ours.difference(theirs) {
+                lines.push(format!("{} {} only in this binary", kind, name));
+            }
+            for name in theirs.difference(ours) {
+                lines.push(format!("{} {} only in {}", kind, name, other_name));
+            }
+        };
+        describe("method", &self.methods, &other.methods);
+        describe("command", &self.commands, &other.commands);
+        lines
+    }
+}
+
+/// Methods the capabilities tell clients they may send
+fn advertised_methods(capabilities: &ServerCapabilities) -> Vec<&'static str> {
+    let mut methods = Vec::new();
+    if capabilities.text_document_sync.is_some() {
+        methods.extend([
+            notification::DidOpenTextDocument::METHOD,
+            notification::DidChangeTextDocument::METHOD,
+            notification::DidSaveTextDocument::METHOD,
+            notification::DidCloseTextDocument::METHOD,
+        ]);
+    }
+    let providers = [
+        (
+            capabilities.hover_provider.is_some(),
+            request::HoverRequest::METHOD,
+        ),
+        (
+            capabilities.completion_provider.is_some(),
+            request::Completion::METHOD,
+        ),
+        (
+            capabilities.signature_help_provider.is_some(),
+            request::SignatureHelpRequest::METHOD,
+        ),
+        (
+            capabilities.definition_provider.is_some(),
+            request::GotoDefinition::METHOD,
+        ),
+        (
+            capabilities.document_symbol_provider.is_some(),
+            request::DocumentSymbolRequest::METHOD,
+        ),
+        (
+            capabilities.folding_range_provider.is_some(),
+            request::FoldingRangeRequest::METHOD,
+        ),
+        (
+            capabilities.selection_range_provider.is_some(),
+            request::SelectionRangeRequest::METHOD,
+        ),
+        (
+            capabilities.inlay_hint_provider.is_some(),
+            request::InlayHintRequest::METHOD,
+        ),
+        (
+            capabilities.semantic_tokens_provider.is_some(),
+            request::SemanticTokensFullRequest::METHOD,
+        ),
+        (
+            capabilities.semantic_tokens_provider.is_some(),
+            request::SemanticTokensFullDeltaRequest::METHOD,
+        ),
+        (
+            capabilities.workspace_symbol_provider.is_some(),
+            request::WorkspaceSymbolRequest::METHOD,
+        ),
+        (
+            capabilities.references_provider.is_some(),
+            request::References::METHOD,
+        ),
+        (
+            capabilities.code_action_provider.is_some(),
+            request::CodeActionRequest::METHOD,
+        ),
+        (
+            capabilities.rename_provider.is_some(),
+            request::Rename::METHOD,
+        ),
+        (
+            capabilities.document_formatting_provider.is_some(),
+            request::Formatting::METHOD,
+        ),
+        (
+            capabilities.document_range_formatting_provider.is_some(),
+            request::RangeFormatting::METHOD,
+        ),
+        (
+            capabilities.document_on_type_formatting_provider.is_some(),
+            request::OnTypeFormatting::METHOD,
+        ),
+        (
+            capabilities.code_lens_provider.is_some(),
+            request::CodeLensRequest::METHOD,
+        ),
+        (
+            capabilities.execute_command_provider.is_some(),
+            request::ExecuteCommand::METHOD,
+        ),
+    ];
+    methods.extend(
+        providers
+            .into_iter()
+            .filter(|(advertised, _)| *advertised)
+            .map(|(_, method)| method),
+    );
+    let prepare_rename = matches!(
+        &capabilities.rename_provider,
+        Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            ..
+        }))
+    );
+    if prepare_rename {
+        methods.push(request::PrepareRenameRequest::METHOD);
+    }
+    let code_action_resolve = matches!(
+        &capabilities.code_action_provider,
+        Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            resolve_provider: Some(true),
+            ..
+        }))
+    );
+    if code_action_resolve {
+        methods.push(request::CodeActionResolveRequest::METHOD);
+    }
+    if capabilities.call_hierarchy_provider.is_some() {
+        methods.extend([
+            request::CallHierarchyPrepare::METHOD,
+            request::CallHierarchyIncomingCalls::METHOD,
+            request::CallHierarchyOutgoingCalls::METHOD,
+        ]);
+    }
+    if let Some(diagnostics) = &capabilities.diagnostic_provider {
+        methods.push(request::DocumentDiagnosticRequest::METHOD);
+        let workspace_diagnostics = match diagnostics {
+            DiagnosticServerCapabilities::Options(options) => options.workspace_diagnostics,
+            DiagnosticServerCapabilities::RegistrationOptions(options) => {
+                options.diagnostic_options.workspace_diagnostics
+            }
+        };
+        if workspace_diagnostics {
+            methods.push(request::WorkspaceDiagnosticRequest::METHOD);
+        }
+    }
+
+    if let Some(workspace) = &capabilities.workspace {
+        let folder_notifications = workspace
+            .workspace_folders
+            .as_ref()
+            .and_then(|folders| folders.change_notifications.as_ref())
+            .is_some_and(|notifications| notifications != &OneOf::Left(false));
+        if folder_notifications {
+            methods.push(notification::DidChangeWorkspaceFolders::METHOD);
+        }
+        let did_rename = workspace
+            .file_operations
+            .as_ref()
+            .is_some_and(|operations| operations.did_rename.is_some());
+        if did_rename {
+            methods.push(notification::DidRenameFiles::METHOD);
+        }
+        let did_delete = workspace
+            .file_operations
+            .as_ref()
+            .is_some_and(|operations| operations.did_delete.is_some());
+        if did_delete {
+            methods.push(notification::DidDeleteFiles::METHOD);
+        }
+    }
+    methods
+}
+
+/// Run `gren-lsp methods`, returning whether the binaries compared agree
+pub fn run(args: MethodsArgs) -> Result<bool> {
+    let report = MethodReport::current();
+
+    let other = match &args.against {
+        Some(other) => other,
+        None => {
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                report
+                    .methods
+                    .iter()
+                    .for_each(|method| println!("{}", method));
+                report
+                    .commands
+                    .iter()
+                    .for_each(|command| println!("command {}", command));
+            }
+            return Ok(true);
+        }
+    };
+
+    let output = Command::new(other)
+        .args(["methods", "--json"])
+        .output()
+        .with_context(|| format!("Failed to run {}", other.display()))?;
+    if !output.status.success() {
+        bail!(
+            "{} doesn't support `methods`, it's older than this binary:\n{}",
+            other.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let other_report: MethodReport = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Unexpected output from {}", other.display()))?;
+
+    let other_name = format!("{} ({})", other.display(), other_report.version);
+    let drift = report.drift(&other_report, &other_name);
+    drift.iter().for_each(|line| println!("{}", line));
+    eprintln!(
+        "{} differences between this binary ({}) and {}",
+        drift.len(),
+        report.version,
+        other_name
+    );
+    Ok(drift.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gren_lsp_protocol::lsp_ext::ADD_DEPENDENCY_COMMAND;
+
+    #[test]
+    fn test_current_methods() {
+        let report = MethodReport::current();
+        for method in [
+            "initialize",
+            "textDocument/didSave",
+            "textDocument/hover",
+            "textDocument/formatting",
+            "textDocument/rangeFormatting",
+            "textDocument/onTypeFormatting",
+            "textDocument/signatureHelp",
+            "textDocument/prepareRename",
+            "codeAction/resolve",
+            "textDocument/selectionRange",
+            "textDocument/prepareCallHierarchy",
+            "callHierarchy/incomingCalls",
+            "callHierarchy/outgoingCalls",
+            "typeHierarchy/subtypes",
+            "textDocument/diagnostic",
+            "workspace/diagnostic",
+            "textDocument/semanticTokens/full",
+            "textDocument/semanticTokens/full/delta",
+            "textDocument/inlayHint",
+            "textDocument/inlineCompletion",
+            "workspace/didRenameFiles",
+            "workspace/didDeleteFiles",
+            "gren/openModule",
+            "gren/requestStats",
+            "$/setTrace",
+        ] {
+            assert!(report.methods.contains(method), "{} is missing", method);
+        }
+        assert!(report.commands.contains(ADD_DEPENDENCY_COMMAND));
+    }
+
+    #[test]
+    fn test_drift() {
+        let report = MethodReport::current();
+        let mut older = report.clone();
+        older.methods.remove("gren/openModule");
+        older.methods.insert("gren/legacy".to_string());
+        older.commands.clear();
+
+        assert!(report.drift(&report.clone(), "other").is_empty());
+        let drift = report.drift(&older, "old");
+        assert!(drift.contains(&"method gren/openModule only in this binary".to_string()));
+        assert!(drift.contains(&"method gren/legacy only in old".to_string()));
+        assert_eq!(drift.len(), 2 + report.commands.len());
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<MethodReport>(&json).unwrap(), report);
+    }
+}
@@ -0,0 +1,140 @@
+//! Batching of the `didOpen` flood when an editor restores its tabs
+//!
+//! Restoring a session opens every tab at once, and diagnosing each document as it's
+//! opened means one compile per tab, all queued on the workspace lock ahead of the file the
+//! user is looking at. Opens arriving close together are treated as a burst instead: the
+//! first one is diagnosed straight away, the others are queued and diagnosed in the
+//! background once the burst is over, starting with the document the client reported as
+//! focused through `gren/activeDocument`.
+
+use lsp_types::Url;
+use std::time::{Duration, Instant};
+
+/// Opens less than this apart belong to the same burst
+pub const BURST_WINDOW: Duration = Duration::from_millis(100);
+
+/// What to do with a document that was just opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenAction {
+    /// Diagnose it now, it's not part of a burst (yet)
+    DiagnoseNow,
+    /// It was queued; start a task draining the queue when `start_drain` is set, one is
+    /// already waiting for the burst to end otherwise
+    Queued { start_drain: bool },
+}
+
+/// Documents opened during a burst, waiting to be diagnosed
+#[derive(Debug, Default)]
+pub struct OpenBurst {
+    last_open: Option<Instant>,
+    queued: Vec<Url>,
+    draining: bool,
+    active: Option<Url>,
+}
+
+impl OpenBurst {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `uri` was opened at `now`
+    pub fn record_open(&mut self, uri: Url, now: Instant) -> OpenAction {
+        let in_burst = self.draining
+            || self
+                .last_open
+                .is_some_and(|last| now.saturating_duration_since(last) < BURST_WINDOW);
+        self.last_open = Some(now);
+        if !in_burst {
+            return OpenAction::DiagnoseNow;
+        }
+
+        if !self.queued.contains(&uri) {
+            self.queued.push(uri);
+        }
+        let start_drain = !self.draining;
+        self.draining = true;
+        OpenAction::Queued { start_drain }
+    }
+
+    /// Whether no document was opened during the last `BURST_WINDOW`
+    pub fn is_settled(&self, now: Instant) -> bool {
+        self.last_open.map_or(true, |last| {
+            now.saturating_duration_since(last) >= BURST_WINDOW
+        })
+    }
+
+    /// Remember the document the user is looking at, to diagnose it first
+    pub fn set_active(&mut self, uri: Url) {
+        self.active = Some(uri);
+    }
+
+    /// Forget a queued document that was closed before it was diagnosed
+    pub fn remove(&mut self, uri: &Url) {
+        self.queued.retain(|queued| queued != uri);
+    }
+
+    /// Take the queued documents, the active one first and the others in the order they
+    /// were opened, ending the burst
+    pub fn take_queued(&mut self) -> Vec<Url> {
+        let mut queued = std::mem::take(&mut self.queued);
+        if let Some(index) = self
+            .active
+            .as_ref()
+            .and_then(|active| queued.iter().position(|uri| uri == active))
+        {
+            let active = queued.remove(index);
+            queued.insert(0, active);
+        }
+        self.draining = false;
+        queued
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///project/src/{}.gren", name)).unwrap()
+    }
+
+    #[test]
+    fn test_burst_is_queued_after_the_first_open() {
+        let start = Instant::now();
+        let mut burst = OpenBurst::new();
+        assert_eq!(
+            burst.record_open(uri("Main"), start),
+            OpenAction::DiagnoseNow
+        );
+        assert_eq!(
+            burst.record_open(uri("Page"), start + Duration::from_millis(5)),
+            OpenAction::Queued { start_drain: true }
+        );
+        assert_eq!(
+            burst.record_open(uri("View"), start + Duration::from_millis(10)),
+            OpenAction::Queued { start_drain: false }
+        );
+        assert!(!burst.is_settled(start + Duration::from_millis(50)));
+        assert!(burst.is_settled(start + Duration::from_millis(110)));
+
+        burst.set_active(uri("View"));
+        assert_eq!(burst.take_queued(), vec![uri("View"), uri("Page")]);
+
+        // Once the burst is over, a lone open is diagnosed straight away again
+        assert_eq!(
+            burst.record_open(uri("Other"), start + Duration::from_secs(1)),
+            OpenAction::DiagnoseNow
+        );
+    }
+
+    #[test]
+    fn test_closed_documents_leave_the_queue() {
+        let start = Instant::now();
+        let mut burst = OpenBurst::new();
+        burst.record_open(uri("Main"), start);
+        burst.record_open(uri("Page"), start);
+        burst.record_open(uri("View"), start);
+        burst.remove(&uri("Page"));
+        assert_eq!(burst.take_queued(), vec![uri("View")]);
+    }
+}
@@ -0,0 +1,116 @@
+use lsp_types::ClientCapabilities;
+use tokio::sync::{watch, OnceCell};
+use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndexState {
+    /// The client hasn't sent `initialized` yet
+    NotStarted,
+    Indexing,
+    Ready,
+}
+
+/// Initialization barrier for the server
+///
+/// Client capabilities are written once during `initialize` and read without locking
+/// afterwards. Requests whose answers depend on the whole workspace (references, rename,
+/// workspace symbols) fail with a "server is still indexing" error until the initial
+/// indexing pass is done, rather than returning results computed from a half-built index.
+/// They don't wait for it: a waiting request would hold one of the server's concurrent
+/// request slots for the whole pass.
+pub struct ServerReadiness {
+    client_capabilities: OnceCell<ClientCapabilities>,
+    index_state: watch::Sender<IndexState>,
+}
+
+impl Default for ServerReadiness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerReadiness {
+    pub fn new() -> Self {
+        let (index_state, _) = watch::channel(IndexState::NotStarted);
+        Self {
+            client_capabilities: OnceCell::new(),
+            index_state,
+        }
+    }
+
+    /// Record the capabilities sent with `initialize`
+    pub fn set_client_capabilities(&self, capabilities: ClientCapabilities) {
+        if self.client_capabilities.set(capabilities).is_err() {
+            warn!("Ignoring client capabilities from a repeated initialize request");
+        }
+    }
+
+    /// Capabilities sent with `initialize`, or `None` before the server is initialized
+    pub fn client_capabilities(&self) -> Option<&ClientCapabilities> {
+        self.client_capabilities.get()
+    }
+
+    pub fn indexing_started(&self) {
+        self.index_state.send_replace(IndexState::Indexing);
+    }
+
+    pub fn indexing_finished(&self) {
+        info!("Initial workspace index is ready");
+        self.index_state.send_replace(IndexState::Ready);
+    }
+
+    /// Whether the workspace is being indexed or has been, so the index has files beyond
+    /// the open documents
+    pub fn indexing_has_started(&self) -> bool {
+        *self.index_state.borrow() != IndexState::NotStarted
+    }
+
+    /// Fail with a "still indexing" error until the initial index is built. The error is
+    /// `ContentModified`, which clients don't show the user and may retry.
+    pub fn require_index(&self, method: &str) -> Result<()> {
+        if *self.index_state.borrow() == IndexState::Ready {
+            return Ok(());
+        }
+
+        info!("Rejecting {}: the workspace is still being indexed", method);
+        Err(Error {
+            code: ErrorCode::ContentModified,
+            message: "Server is still indexing the workspace, try again shortly".into(),
+            data: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_rejected_until_indexed() {
+        let readiness = ServerReadiness::new();
+        assert!(!readiness.indexing_has_started());
+        readiness.indexing_started();
+        assert!(readiness.indexing_has_started());
+
+        let error = readiness.require_index("textDocument/rename").unwrap_err();
+        assert_eq!(error.code, ErrorCode::ContentModified);
+        assert!(error.message.contains("still indexing"));
+
+        readiness.indexing_finished();
+        assert!(readiness.require_index("workspace/symbol").is_ok());
+    }
+
+    #[test]
+    fn test_client_capabilities_set_once() {
+        let readiness = ServerReadiness::new();
+        assert!(readiness.client_capabilities().is_none());
+
+        readiness.set_client_capabilities(ClientCapabilities::default());
+        readiness.set_client_capabilities(ClientCapabilities {
+            experimental: Some(serde_json::json!({ "second": true })),
+            ..Default::default()
+        });
+        assert_eq!(readiness.client_capabilities().unwrap().experimental, None);
+    }
+}
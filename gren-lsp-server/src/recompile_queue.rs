@@ -0,0 +1,177 @@
+//! Recompiling the dependents of a saved module
+//!
+//! Saving a module shared by much of the project, or changing it outside the editor, can
+//! break any module importing it, directly or not, so they're all compiled again. That's
+//! one compile each, and the ones the user has open matter most: they're compiled first,
+//! the focused one before the others, so their diagnostics refresh right away. The rest
+//! follow in the background.
+//! Within each group modules keep their dependency order, so a module is compiled after
+//! the modules it imports.
+
+use lsp_types::Url;
+use std::collections::HashSet;
+
+/// How soon a dependent is compiled, most urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RecompilePriority {
+    /// The document the client reported as focused through `gren/activeDocument`
+    Active,
+    /// A document open in the editor
+    Open,
+    /// Any other file
+    Background,
+}
+
+/// Dependents waiting to be compiled again, most urgent first
+#[derive(Debug, Default)]
+pub struct RecompileQueue {
+    queued: Vec<(RecompilePriority, Url)>,
+    draining: bool,
+    open: HashSet<Url>,
+    active: Option<Url>,
+}
+
+impl RecompileQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the editor opened `uri`
+    pub fn opened(&mut self, uri: Url) {
+        self.open.insert(uri);
+    }
+
+    /// Record that the editor closed `uri`
+    pub fn closed(&mut self, uri: &Url) {
+        self.open.remove(uri);
+    }
+
+    /// Whether the editor has `uri` open, which makes the editor's buffer its content
+    /// rather than the file on disk
+    pub fn is_open(&self, uri: &Url) -> bool {
+        self.open.contains(uri)
+    }
+
+    /// The documents the editor has open
+    pub fn open_documents(&self) -> Vec<Url> {
+        self.open.iter().cloned().collect()
+    }
+
+    /// Remember the document the user is looking at, to compile it first
+    pub fn set_active(&mut self, uri: Url) {
+        self.active = Some(uri);
+    }
+
+    fn priority(&self, uri: &Url) -> RecompilePriority {
+        if self.active.as_ref() == Some(uri) {
+            RecompilePriority::Active
+        } else if self.open.contains(uri) {
+            RecompilePriority::Open
+        } else {
+            RecompilePriority::Background
+        }
+    }
+
+    /// Queue the dependents of a saved or externally changed module, given in dependency
+    /// order. Files already queued move to their new place. Returns whether to start a
+    /// task draining the queue, as none is running yet.
+    pub fn schedule(&mut self, dependents: Vec<Url>) -> bool {
+        self.queued.retain(|(_, uri)| !dependents.contains(uri));
+        let scheduled: Vec<(RecompilePriority, Url)> = dependents
+            .into_iter()
+            .map(|uri| (self.priority(&uri), uri))
+            .collect();
+        self.queued.extend(scheduled);
+        // Stable, so dependency order holds within each priority
+        self.queued.sort_by_key(|(priority, _)| *priority);
+
+        let start_drain = !self.draining && !self.queued.is_empty();
+        self.draining |= start_drain;
+        start_drain
+    }
+
+    /// The next file to compile, ending the drain once there's none left
+    pub fn take_next(&mut self) -> Option<(RecompilePriority, Url)> {
+        if self.queued.is_empty() {
+            self.draining = false;
+            return None;
+        }
+        Some(self.queued.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///project/src/{}.gren", name)).unwrap()
+    }
+
+    fn drain(queue: &mut RecompileQueue) -> Vec<(RecompilePriority, Url)> {
+        std::iter::from_fn(|| queue.take_next()).collect()
+    }
+
+    #[test]
+    fn test_open_documents_come_first_in_dependency_order() {
+        let mut queue = RecompileQueue::new();
+        queue.opened(uri("Main"));
+        queue.opened(uri("Page"));
+        queue.opened(uri("View"));
+        queue.set_active(uri("Main"));
+
+        assert!(queue.schedule(vec![
+            uri("View"),
+            uri("Widget"),
+            uri("Page"),
+            uri("Admin"),
+            uri("Main"),
+        ]));
+        assert_eq!(
+            drain(&mut queue),
+            vec![
+                (RecompilePriority::Active, uri("Main")),
+                (RecompilePriority::Open, uri("View")),
+                (RecompilePriority::Open, uri("Page")),
+                (RecompilePriority::Background, uri("Widget")),
+                (RecompilePriority::Background, uri("Admin")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rescheduling_while_draining() {
+        let mut queue = RecompileQueue::new();
+        assert!(queue.schedule(vec![uri("Page"), uri("Main")]));
+        assert_eq!(
+            queue.take_next(),
+            Some((RecompilePriority::Background, uri("Page")))
+        );
+
+        // A drain is already running, and files already queued aren't compiled twice
+        queue.opened(uri("Main"));
+        assert!(!queue.schedule(vec![uri("Main"), uri("Admin")]));
+        assert_eq!(
+            drain(&mut queue),
+            vec![
+                (RecompilePriority::Open, uri("Main")),
+                (RecompilePriority::Background, uri("Admin")),
+            ]
+        );
+
+        assert!(!queue.schedule(Vec::new()));
+        assert!(queue.schedule(vec![uri("Main")]));
+    }
+
+    #[test]
+    fn test_open_documents() {
+        let mut queue = RecompileQueue::new();
+        queue.opened(uri("Main"));
+        queue.opened(uri("Page"));
+        queue.closed(&uri("Page"));
+
+        assert!(queue.is_open(&uri("Main")));
+        assert!(!queue.is_open(&uri("Page")));
+        assert_eq!(queue.open_documents(), vec![uri("Main")]);
+    }
+}
@@ -0,0 +1,393 @@
+//! `gren-lsp refactor`: the rename, module rename and organize imports engines driven
+//! from the command line, for scripted codemods and tools that don't speak LSP
+//!
+//! Instructions are JSON, e.g.
+//! `{"action": "rename", "file": "src/Main.gren", "line": 12, "column": 5, "newName": "view"}`.
+//! Lines and columns are 1-based, as editors and compilers print them. The resulting
+//! `WorkspaceEdit` is printed as JSON, or applied to disk with `--write`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use gren_lsp_core::{Document, InMemorySymbolStore, Workspace};
+use gren_lsp_protocol::handlers::Handlers;
+use lsp_types::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[derive(Args, Debug)]
+pub struct RefactorArgs {
+    /// JSON instruction to run, or `-` to read it from stdin
+    #[arg(default_value = "-")]
+    pub instruction: String,
+
+    /// Project root to index, defaults to the current directory
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    /// Apply the edit to the files on disk instead of printing it
+    #[arg(long)]
+    pub write: bool,
+}
+
+/// One refactoring to run. Relative paths are resolved against the project root.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum Instruction {
+    /// Rename the symbol at a position
+    #[serde(rename_all = "camelCase")]
+    Rename {
+        file: PathBuf,
+        line: u32,
+        column: u32,
+        new_name: String,
+    },
+    /// Rename the module a file declares, updating its importers
+    #[serde(rename_all = "camelCase")]
+    RenameModule { file: PathBuf, new_name: String },
+    #[serde(rename_all = "camelCase")]
+    OrganizeImports { file: PathBuf },
+}
+
+/// Run `gren-lsp refactor`
+pub async fn run(args: RefactorArgs) -> Result<()> {
+    let instruction = if args.instruction == "-" {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read the instruction from stdin")?;
+        input
+    } else {
+        args.instruction.clone()
+    };
+    let instruction: Instruction =
+        serde_json::from_str(&instruction).context("Invalid refactor instruction")?;
+
+    let root = match args.root {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Project root {} doesn't exist", root.display()))?;
+
+    let handlers = index_project(&root).await?;
+    let edit = refactor(&handlers, &root, instruction).await?;
+
+    match edit {
+        Some(edit) if args.write => {
+            let changed = apply_workspace_edit(&edit)?;
+            eprintln!("Updated {} files", changed);
+        }
+        edit => println!("{}", serde_json::to_string_pretty(&edit)?),
+    }
+    Ok(())
+}
+
+/// Compute the edit for an instruction. `None` means there was nothing to change.
+pub async fn refactor(
+    handlers: &Handlers,
+    root: &Path,
+    instruction: Instruction,
+) -> Result<Option<WorkspaceEdit>> {
+    let edit = match instruction {
+        Instruction::Rename {
+            file,
+            line,
+            column,
+            new_name,
+        } => {
+            if line == 0 || column == 0 {
+                bail!("Lines and columns are 1-based");
+            }
+            let params = RenameParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: file_uri(root, &file)?,
+                    },
+                    position: Position::new(line - 1, column - 1),
+                },
+                new_name,
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            };
+            handlers.rename(params).await.map_err(rpc_error)?
+        }
+        Instruction::RenameModule { file, new_name } => handlers
+            .module_rename(&file_uri(root, &file)?, &new_name)
+            .await
+            .map_err(rpc_error)?,
+        Instruction::OrganizeImports { file } => {
+            handlers.organize_imports(&file_uri(root, &file)?).await
+        }
+    };
+    Ok(edit)
+}
+
+/// Index every Gren file under `root`, the way the server does on startup
+async fn index_project(root: &Path) -> Result<Handlers> {
+    let mut files = Vec::new();
+    collect_gren_files(root, &mut files)?;
+
+    // Keep every file loaded, there's no editor here to reopen them
+    let mut workspace = Workspace::with_capacity(files.len())?;
+    workspace.set_symbol_store(Arc::new(InMemorySymbolStore::new()))?;
+
+    for path in files {
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let uri = Url::from_file_path(&path)
+            .map_err(|_| anyhow!("Invalid file path: {}", path.display()))?;
+        workspace.open_document(TextDocumentItem {
+            uri,
+            language_id: "gren".to_string(),
+            version: 1,
+            text,
+        })?;
+    }
+
+    Ok(Handlers::new(Arc::new(RwLock::new(workspace))))
+}
+
+/// Every Gren file under `dir`, skipping version control, editor and build folders
+pub fn collect_gren_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let ignored = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    matches!(
+                        name,
+                        ".git" | "node_modules" | "target" | ".vscode" | ".idea"
+                    )
+                });
+            if !ignored {
+                collect_gren_files(&path, files)?;
+            }
+        } else if path.extension().and_then(|extension| extension.to_str()) == Some("gren") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn file_uri(root: &Path, file: &Path) -> Result<Url> {
+    let path = root.join(file);
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("{} doesn't exist", path.display()))?;
+    Url::from_file_path(&path).map_err(|_| anyhow!("Invalid file path: {}", path.display()))
+}
+
+fn rpc_error(error: tower_lsp::jsonrpc::Error) -> anyhow::Error {
+    anyhow!("{}", error.message)
+}
+
+/// Write a workspace edit's text edits to disk, returning how many files changed. Every
+/// file's new text is worked out before any is written, so an edit that doesn't fit its
+/// file leaves them all untouched.
+pub fn apply_workspace_edit(edit: &WorkspaceEdit) -> Result<usize> {
+    let mut edits_by_file: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+    if let Some(changes) = &edit.changes {
+        for (uri, edits) in changes {
+            edits_by_file
+                .entry(uri.clone())
+                .or_default()
+                .extend(edits.iter().cloned());
+        }
+    }
+
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(document_edits)) => {
+            for document_edit in document_edits {
+                add_document_edit(&mut edits_by_file, document_edit);
+            }
+        }
+        Some(DocumentChanges::Operations(operations)) => {
+            for operation in operations {
+                match operation {
+                    DocumentChangeOperation::Edit(document_edit) => {
+                        add_document_edit(&mut edits_by_file, document_edit)
+                    }
+                    DocumentChangeOperation::Op(_) => {
+                        bail!("File create, rename and delete operations aren't supported")
+                    }
+                }
+            }
+        }
+        None => {}
+    }
+
+    let mut new_texts = Vec::new();
+    for (uri, edits) in &edits_by_file {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow!("Not a file URI: {}", uri))?;
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        new_texts.push((path, apply_text_edits(uri, &text, edits)?));
+    }
+    for (path, text) in &new_texts {
+        std::fs::write(path, text)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(new_texts.len())
+}
+
+fn add_document_edit(edits_by_file: &mut HashMap<Url, Vec<TextEdit>>, edit: &TextDocumentEdit) {
+    edits_by_file
+        .entry(edit.text_document.uri.clone())
+        .or_default()
+        .extend(edit.edits.iter().map(|edit| match edit {
+            OneOf::Left(edit) => edit.clone(),
+            OneOf::Right(annotated) => annotated.text_edit.clone(),
+        }));
+}
+
+/// Apply non-overlapping edits to `text`, converting LSP positions to byte offsets. Fails
+/// when an edit's range lies outside the text.
+fn apply_text_edits(uri: &Url, text: &str, edits: &[TextEdit]) -> Result<String> {
+    let document = Document::new(TextDocumentItem {
+        uri: uri.clone(),
+        language_id: "gren".to_string(),
+        version: 1,
+        text: text.to_string(),
+    });
+
+    let mut offsets: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let offsets = if fits(text, edit.range.start) && fits(text, edit.range.end) {
+                document
+                    .position_to_offset(edit.range.start)
+                    .zip(document.position_to_offset(edit.range.end))
+            } else {
+                None
+            };
+            match offsets {
+                Some((start, end)) if start <= end => Ok((start, end, edit.new_text.as_str())),
+                _ => Err(anyhow!(
+                    "Edit at {}:{}-{}:{} doesn't fit {}",
+                    edit.range.start.line + 1,
+                    edit.range.start.character + 1,
+                    edit.range.end.line + 1,
+                    edit.range.end.character + 1,
+                    uri
+                )),
+            }
+        })
+        .collect::<Result<_>>()?;
+
+    // Apply from the end so earlier offsets stay valid
+    offsets.sort_by(|a, b| b.0.cmp(&a.0));
+    let mut result = text.to_string();
+    for (start, end, new_text) in offsets {
+        result.replace_range(start..end, new_text);
+    }
+    Ok(result)
+}
+
+/// Whether `position` is on one of the lines of `text`, at most at the line's end
+fn fits(text: &str, position: Position) -> bool {
+    text.split('\n')
+        .nth(position.line as usize)
+        .is_some_and(|line| {
+            line.trim_end_matches('\r').encode_utf16().count() >= position.character as usize
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instructions() {
+        let rename: Instruction = serde_json::from_str(
+            r#"{"action": "rename", "file": "src/Main.gren", "line": 3, "column": 1, "newName": "view"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rename,
+            Instruction::Rename {
+                file: PathBuf::from("src/Main.gren"),
+                line: 3,
+                column: 1,
+                new_name: "view".to_string(),
+            }
+        );
+
+        let organize: Instruction =
+            serde_json::from_str(r#"{"action": "organizeImports", "file": "src/Main.gren"}"#)
+                .unwrap();
+        assert_eq!(
+            organize,
+            Instruction::OrganizeImports {
+                file: PathBuf::from("src/Main.gren"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_text_edits() {
+        let uri = Url::parse("file:///project/src/Main.gren").unwrap();
+        let text = "module Main exposing (main)\n\nmain = helper 1\n";
+        let edits = vec![
+            TextEdit::new(
+                Range::new(Position::new(2, 7), Position::new(2, 13)),
+                "compute".to_string(),
+            ),
+            TextEdit::new(
+                Range::new(Position::new(0, 7), Position::new(0, 11)),
+                "App".to_string(),
+            ),
+        ];
+
+        assert_eq!(
+            apply_text_edits(&uri, text, &edits).unwrap(),
+            "module App exposing (main)\n\nmain = compute 1\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_workspace_edit_writes_nothing_when_an_edit_doesnt_fit() {
+        let dir = std::env::temp_dir().join(format!("gren-lsp-refactor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("Main.gren");
+        let helpers = dir.join("Helpers.gren");
+        std::fs::write(&main, "module Main exposing (main)\n\nmain = helper 1\n").unwrap();
+        std::fs::write(&helpers, "module Helpers exposing (helper)\n").unwrap();
+        let main_uri = Url::from_file_path(&main).unwrap();
+        let helpers_uri = Url::from_file_path(&helpers).unwrap();
+
+        let rename = TextEdit::new(
+            Range::new(Position::new(2, 7), Position::new(2, 13)),
+            "compute".to_string(),
+        );
+        let past_the_end = TextEdit::new(
+            Range::new(Position::new(40, 0), Position::new(40, 6)),
+            "compute".to_string(),
+        );
+        let edit = WorkspaceEdit::new(HashMap::from([
+            (main_uri.clone(), vec![rename.clone()]),
+            (helpers_uri, vec![past_the_end]),
+        ]));
+        assert!(apply_workspace_edit(&edit).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&main).unwrap(),
+            "module Main exposing (main)\n\nmain = helper 1\n"
+        );
+
+        let edit = WorkspaceEdit::new(HashMap::from([(main_uri, vec![rename])]));
+        assert_eq!(apply_workspace_edit(&edit).unwrap(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&main).unwrap(),
+            "module Main exposing (main)\n\nmain = compute 1\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,23 +1,82 @@
-use gren_lsp_core::Workspace;
+use crate::compile_scheduler::CompileScheduler;
+use crate::deadlines::RequestDeadlines;
+use crate::load_shedding::RequestLimiter;
+use crate::open_burst::{OpenAction, OpenBurst, BURST_WINDOW};
+use crate::readiness::ServerReadiness;
+use crate::recompile_queue::RecompileQueue;
+use crate::trace_control::{TraceControl, DEFAULT_DIRECTIVES};
+use anyhow::Context;
+use gren_lsp_core::declaration_order::{self, declaration_at};
+use gren_lsp_core::edit_journal::edited_files;
+use gren_lsp_core::formatter::whole_document_edit;
+use gren_lsp_core::index_freshness::PENDING_EXPIRY;
+use gren_lsp_core::manifest;
+use gren_lsp_core::source_file::{
+    decode_source, restore_line_endings, restore_text_edit_ending, with_line_ending,
+};
+use gren_lsp_core::string_literals::{rename_string_literals, string_literal_at};
+use gren_lsp_core::telemetry::uri_hash;
+use gren_lsp_core::{
+    CachedDiagnostics, CompileReport, DocumentKind, IndexStats, MoveDirection, Parser, ScanSummary,
+    SkipReason, SymbolStoreBackend, Workspace, PROJECT_CONFIG_FILE,
+};
+use gren_lsp_protocol::completion_cache::SharedCompletionCache;
+use gren_lsp_protocol::exposing;
 use gren_lsp_protocol::handlers::Handlers;
+use gren_lsp_protocol::lsp_ext::{
+    ActiveDocumentParams, AffectedFile, CapabilitiesReportResult, CompileReportNotification,
+    DependencyCommandArguments, DeprecatedSymbolUsages, DocSearchResult, EditExposingArguments,
+    FileTodos, FindValuesOfTypeParams, IndexFreshnessParams, IndexFreshnessResult,
+    IndexStatusNotification, IndexStatusParams, InlineCompletionItem, InlineCompletionParams,
+    MetricsParams, ModuleExportsParams, ModuleExportsResult, ModuleMatch, ModuleMetricsReport,
+    ModuleRenameImpactParams, MoveDeclarationArguments, OpenModuleParams, PartialResult,
+    PartialResultParams, RenameStringLiteralArguments, RequestMethodStats, RuntimeLogParams,
+    SearchDocsParams, SetSubsystemTraceParams, ValueOfType, ADD_DEPENDENCY_COMMAND,
+    CHECK_FORMATTING_COMMAND, EDIT_EXPOSING_COMMAND, FORMAT_WORKSPACE_COMMAND,
+    MOVE_DECLARATION_COMMAND, REMOVE_DEPENDENCY_COMMAND, RENAME_STRING_LITERAL_COMMAND,
+    SOURCE_MOVE_DECLARATION, SOURCE_SORT_DECLARATIONS, UNDO_LAST_SERVER_EDIT_COMMAND,
+};
+use gren_lsp_protocol::package_docs::{forget_dependency_versions, package_module_for_path};
+use gren_lsp_protocol::semantic_tokens::{self, SharedSemanticTokens};
+use gren_lsp_protocol::settings::CompileTrigger;
+use gren_lsp_protocol::ServerSettings;
 use lsp_types::*;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tokio::time::sleep;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::{Client, LanguageServer};
-use tracing::{error, info, warn};
+use tracing::{error, info, info_span, instrument, warn, Instrument};
 
+/// Progress token for diagnosing the documents of an open burst
+const OPEN_BURST_PROGRESS_TOKEN: &str = "gren/openBurst";
+
+/// Every field is shared, so a clone is a handle on the same server for work that runs
+/// in the background, like the initial workspace index
+#[derive(Clone)]
 pub struct GrenLanguageServer {
     client: Client,
     workspace: Arc<RwLock<Workspace>>,
-    client_capabilities: Arc<RwLock<Option<ClientCapabilities>>>,
+    readiness: Arc<ServerReadiness>,
+    settings: Arc<RwLock<ServerSettings>>,
     debug_export_dir: Option<PathBuf>,
-    // Debouncing mechanism for real-time compilation
-    pending_diagnostics: Arc<RwLock<HashMap<Url, Instant>>>,
+    /// Compiles of documents being edited
+    compile_scheduler: Arc<Mutex<CompileScheduler>>,
+    completion_cache: SharedCompletionCache,
+    semantic_tokens: SharedSemanticTokens,
+    open_burst: Arc<Mutex<OpenBurst>>,
+    /// Dependents of saved modules waiting to be compiled again
+    recompile_queue: Arc<Mutex<RecompileQueue>>,
+    trace_control: Arc<Mutex<TraceControl>>,
+    /// URI schemes the user was already told aren't supported
+    unsupported_schemes: Arc<Mutex<HashSet<String>>>,
+    /// Sheds language feature requests once too many are in flight
+    request_limiter: RequestLimiter,
+    /// Cuts off language feature requests running past their method's deadline
+    deadlines: RequestDeadlines,
 }
 
 impl GrenLanguageServer {
@@ -52,11 +111,26 @@ impl GrenLanguageServer {
         Self {
             client,
             workspace: Arc::new(RwLock::new(workspace)),
-            client_capabilities: Arc::new(RwLock::new(None)),
+            readiness: Arc::new(ServerReadiness::new()),
+            settings: Arc::new(RwLock::new(ServerSettings::default())),
             debug_export_dir,
-            pending_diagnostics: Arc::new(RwLock::new(HashMap::new())),
+            compile_scheduler: Arc::new(Mutex::new(CompileScheduler::new())),
+            completion_cache: SharedCompletionCache::default(),
+            semantic_tokens: SharedSemanticTokens::default(),
+            open_burst: Arc::new(Mutex::new(OpenBurst::new())),
+            recompile_queue: Arc::new(Mutex::new(RecompileQueue::new())),
+            trace_control: Arc::new(Mutex::new(TraceControl::detached(DEFAULT_DIRECTIVES))),
+            unsupported_schemes: Arc::new(Mutex::new(HashSet::new())),
+            request_limiter: RequestLimiter::new(),
+            deadlines: RequestDeadlines::new(),
         }
     }
+
+    /// Apply `$/setTrace` and `gren/setTrace` to the installed log filter
+    pub fn with_trace_control(mut self, trace_control: TraceControl) -> Self {
+        self.trace_control = Arc::new(Mutex::new(trace_control));
+        self
+    }
 }
 
 #[tower_lsp::async_trait]
@@ -65,9 +139,14 @@ impl LanguageServer for GrenLanguageServer {
         info!("Initialize request received");
 
         // Store client capabilities
-        {
-            let mut capabilities = self.client_capabilities.write().await;
-            *capabilities = Some(params.capabilities.clone());
+        self.readiness
+            .set_client_capabilities(params.capabilities.clone());
+
+        // Apply any settings passed along with the initialize request
+        if let Some(options) = &params.initialization_options {
+            let new_settings = ServerSettings::from_json(options);
+            info!("Applied initialization options: {:?}", new_settings);
+            self.apply_settings(new_settings).await;
         }
 
         // Set workspace root - prefer workspaceFolders, fallback to rootUri
@@ -84,6 +163,13 @@ impl LanguageServer for GrenLanguageServer {
                     });
                 }
                 info!("Set workspace root to: {}", folders[0].uri);
+
+                // The other folders get compilers of their own
+                for folder in &folders[1..] {
+                    if let Err(e) = workspace.add_folder(folder.uri.clone()) {
+                        warn!("No compiler for workspace folder {}: {}", folder.uri, e);
+                    }
+                }
             }
         } else if let Some(root_uri) = params.root_uri {
             let mut workspace = self.workspace.write().await;
@@ -96,42 +182,29 @@ impl LanguageServer for GrenLanguageServer {
             }
             info!("Set workspace root to: {}", root_uri);
         }
+        // Clients able to pull diagnostics, and to be told to pull them again, get them
+        // that way instead of having them pushed
+        let capabilities = &params.capabilities;
+        let pulls = capabilities
+            .text_document
+            .as_ref()
+            .is_some_and(|text| text.diagnostic.is_some());
+        let refreshes = capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.diagnostic.as_ref())
+            .and_then(|diagnostic| diagnostic.refresh_support)
+            == Some(true);
+        if pulls && refreshes {
+            info!("Client pulls diagnostics");
+            self.workspace.write().await.set_pull_diagnostics(true);
+        }
+
+        // The root's `.gren-lsp.toml` is loaded now, and overrides some of the settings
+        self.refresh_effective_settings().await;
 
         Ok(InitializeResult {
-            capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::INCREMENTAL,
-                )),
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                completion_provider: Some(CompletionOptions {
-                    trigger_characters: Some(vec![".".to_string()]),
-                    ..Default::default()
-                }),
-                definition_provider: Some(OneOf::Left(true)),
-                document_symbol_provider: Some(OneOf::Left(true)),
-                workspace_symbol_provider: Some(OneOf::Left(true)),
-                workspace: Some(WorkspaceServerCapabilities {
-                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
-                        supported: Some(true),
-                        change_notifications: Some(OneOf::Left(true)),
-                    }),
-                    ..Default::default()
-                }),
-                // TODO: Implement these features
-                references_provider: Some(OneOf::Left(true)),
-                code_action_provider: Some(CodeActionProviderCapability::Options(
-                    CodeActionOptions {
-                        code_action_kinds: Some(vec![
-                            CodeActionKind::QUICKFIX,
-                            CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
-                        ]),
-                        work_done_progress_options: WorkDoneProgressOptions::default(),
-                        resolve_provider: Some(false),
-                    },
-                )),
-                rename_provider: Some(OneOf::Left(true)),
-                ..Default::default()
-            },
+            capabilities: server_capabilities(),
             ..Default::default()
         })
     }
@@ -139,11 +212,36 @@ impl LanguageServer for GrenLanguageServer {
     async fn initialized(&self, _: InitializedParams) {
         info!("Server initialized");
 
+        let project_config_error = self
+            .workspace
+            .read()
+            .await
+            .project_config_error()
+            .map(str::to_string);
+        if let Some(error) = project_config_error {
+            self.client
+                .show_message(
+                    MessageType::WARNING,
+                    format!("Gren: ignoring project configuration. {}", error),
+                )
+                .await;
+        }
+        self.publish_project_config_diagnostics().await;
+
         // Register file watchers for Gren files according to LSP spec
         self.register_file_watchers().await;
-
-        // Index any existing Gren files in the workspace
-        self.index_workspace_files().await;
+        self.register_type_hierarchy().await;
+        self.register_inline_completion().await;
+
+        // Index any existing Gren files in the workspace in the background, so requests
+        // that only need open documents are answered straight away. Requests that need the
+        // whole index are turned away until it's done.
+        self.readiness.indexing_started();
+        let server = self.clone();
+        tokio::spawn(async move {
+            server.index_workspace_files().await;
+            server.readiness.indexing_finished();
+        });
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -154,85 +252,74 @@ impl LanguageServer for GrenLanguageServer {
     // Document synchronization
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         info!("Document opened: {}", params.text_document.uri);
-
-        let uri = params.text_document.uri.clone();
-        let mut workspace = self.workspace.write().await;
-
-        if let Err(e) = workspace.open_document(params.text_document) {
-            error!("Failed to open document: {}", e);
+        if DocumentKind::of(&params.text_document.uri) == DocumentKind::Unsupported {
+            self.report_unsupported_scheme(&params.text_document.uri)
+                .await;
             return;
         }
 
-        // Get comprehensive diagnostics and global errors for the newly opened document
-        let (diagnostics, global_errors) = match workspace
-            .get_document_diagnostics_with_global_errors(&uri)
-            .await
-        {
-            Ok((diags, global_errs)) => {
-                info!(
-                    "Found {} comprehensive diagnostics and {} global errors for document: {}",
-                    diags.len(),
-                    global_errs.len(),
-                    uri
-                );
-                (diags, global_errs)
+        let uri = {
+            let mut workspace = self.workspace.write().await;
+            let uri = workspace.normalize_uri(&params.text_document.uri);
+            if let Err(e) = workspace.open_document(params.text_document) {
+                error!("Failed to open document: {}", e);
+                return;
             }
-            Err(e) => {
-                warn!("Failed to get comprehensive diagnostics for {}: {}", uri, e);
-                // Fallback to syntax-only diagnostics
-                let syntax_diagnostics = workspace.get_diagnostics(&uri);
-                info!(
-                    "Fallback: {} syntax diagnostics for document: {}",
-                    syntax_diagnostics.len(),
-                    uri
-                );
-                (syntax_diagnostics, Vec::new())
+
+            // Export parse tree if debug mode is enabled
+            if let Some(ref debug_dir) = self.debug_export_dir {
+                if let Err(e) = workspace.export_parse_tree_for_document(&uri, debug_dir) {
+                    error!("Failed to export parse tree for {}: {}", uri, e);
+                }
             }
+            uri
         };
-
-        // Log workspace stats before we potentially drop the workspace lock
-        let stats = workspace.stats();
-
-        // Export parse tree if debug mode is enabled
-        if let Some(ref debug_dir) = self.debug_export_dir {
-            if let Err(e) = workspace.export_parse_tree_for_document(&uri, debug_dir) {
-                error!("Failed to export parse tree for {}: {}", uri, e);
+        send_index_status(&self.client, &self.workspace).await;
+        self.recompile_queue.lock().await.opened(uri.clone());
+
+        // Documents opened in a burst, like restored tabs, are compiled one after the
+        // other once the burst is over instead of all at once
+        let action = self
+            .open_burst
+            .lock()
+            .await
+            .record_open(uri.clone(), Instant::now());
+        match action {
+            OpenAction::DiagnoseNow => {
+                publish_open_diagnostics(&self.client, &self.workspace, uri).await;
+            }
+            OpenAction::Queued { start_drain } => {
+                info!("Queued {} until the burst of opened documents is over", uri);
+                if start_drain {
+                    tokio::spawn(drain_open_burst(
+                        self.client.clone(),
+                        self.workspace.clone(),
+                        self.open_burst.clone(),
+                        self.supports_work_done_progress(),
+                    ));
+                }
             }
-        }
-
-        // Send global error notifications if any were found
-        if !global_errors.is_empty() {
-            // Need to drop the workspace lock before calling async notification method
-            drop(workspace);
-            self.send_global_error_notifications(global_errors).await;
-        }
-
-        info!("Workspace stats: {} documents open", stats.document_count);
-
-        // Publish diagnostics with timeout to prevent hanging
-        let publish_result = tokio::time::timeout(
-            std::time::Duration::from_secs(5),
-            self.client.publish_diagnostics(uri.clone(), diagnostics, None)
-        ).await;
-        
-        match publish_result {
-            Ok(_) => {},
-            Err(_) => error!("❌ Timeout publishing diagnostics for: {}", uri),
         }
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri.clone();
         let version = params.text_document.version;
+        if DocumentKind::of(&uri) == DocumentKind::Unsupported {
+            return;
+        }
 
         info!("Document changed: {} (version {})", uri, version);
 
+        // A compile of the previous version would hold up taking the workspace lock
+        self.cancel_edit_compile(&uri).await;
         let mut workspace = self.workspace.write().await;
 
         if let Err(e) = workspace.update_document(params) {
             error!("Failed to update document {}: {}", uri, e);
             return;
         }
+        let uri = workspace.normalize_uri(&uri);
 
         // Check if this is a project configuration file that should invalidate cache
         if workspace.is_project_file(&uri) {
@@ -242,6 +329,7 @@ impl LanguageServer for GrenLanguageServer {
 
         // Release the workspace lock before calling debounced diagnostics
         drop(workspace);
+        send_index_status(&self.client, &self.workspace).await;
 
         // Export parse tree if debug mode is enabled
         if let Some(ref debug_dir) = self.debug_export_dir {
@@ -251,18 +339,28 @@ impl LanguageServer for GrenLanguageServer {
             }
         }
 
-        // Schedule debounced diagnostics update
-        self.schedule_debounced_diagnostics(uri).await;
+        // Schedule debounced diagnostics update, unless only saving compiles
+        // A client pulling diagnostics asks for them after the edit itself
+        let compile_on = self.settings.read().await.diagnostics.compile_on;
+        let push = self.workspace.read().await.pushes_diagnostics();
+        if push && compile_on == CompileTrigger::Change {
+            self.schedule_debounced_diagnostics(uri).await;
+        }
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        if DocumentKind::of(&params.text_document.uri) == DocumentKind::Unsupported {
+            return;
+        }
         info!(
             "🔍 Document save request received: {}",
             params.text_document.uri
         );
 
-        let uri = params.text_document.uri.clone();
+        // Saving compiles the document anyway
+        self.cancel_edit_compile(&params.text_document.uri).await;
         let mut workspace = self.workspace.write().await;
+        let uri = workspace.normalize_uri(&params.text_document.uri);
 
         // Force refresh diagnostics and global errors after save (bypasses cache)
         let (diagnostics, global_errors) = match workspace
@@ -284,161 +382,2017 @@ impl LanguageServer for GrenLanguageServer {
                 (workspace.get_diagnostics(&uri), Vec::new())
             }
         };
+        let compile_reports = workspace.take_compile_reports();
+        // Modules importing this one, directly or not, may be broken by the change
+        let dependents = workspace.module_dependents(&uri);
+        workspace.record_published_diagnostics(&uri, &diagnostics);
+        let push = workspace.pushes_diagnostics();
+        drop(workspace);
 
         // Send global error notifications if any were found
         if !global_errors.is_empty() {
-            // Need to drop the workspace lock before calling async notification method
-            drop(workspace);
-            self.send_global_error_notifications(global_errors).await;
+            send_global_error_notifications(&self.client, global_errors).await;
         }
 
         // Publish fresh diagnostics
-        self.client
-            .publish_diagnostics(uri, diagnostics, None)
-            .await;
+        if push {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        } else {
+            refresh_pulled_diagnostics(&self.client).await;
+        }
+        send_compile_reports(&self.client, compile_reports).await;
+
+        if !dependents.is_empty() {
+            info!("Recompiling {} dependent modules", dependents.len());
+            self.schedule_recompile(dependents).await;
+        }
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
         info!("Document closed: {}", params.text_document.uri);
+        if DocumentKind::of(&params.text_document.uri) == DocumentKind::Unsupported {
+            return;
+        }
 
-        let uri = params.text_document.uri.clone();
+        self.cancel_edit_compile(&params.text_document.uri).await;
         let mut workspace = self.workspace.write().await;
+        let uri = workspace.normalize_uri(&params.text_document.uri);
 
+        self.open_burst.lock().await.remove(&uri);
+        self.recompile_queue.lock().await.closed(&uri);
+        if let Ok(mut semantic_tokens) = self.semantic_tokens.lock() {
+            semantic_tokens.forget(&uri);
+        }
         if let Err(e) = workspace.close_document(params.text_document.uri) {
             error!("Failed to close document: {}", e);
         }
 
         // Clear diagnostics for closed document
-        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+        workspace.record_published_diagnostics(&uri, &[]);
+        if workspace.pushes_diagnostics() {
+            self.client.publish_diagnostics(uri, Vec::new(), None).await;
+        }
 
         // Log workspace stats
         let stats = workspace.stats();
         info!("Workspace stats: {} documents open", stats.document_count);
     }
 
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        info!("Diagnostics pulled for {}", params.text_document.uri);
+        let compile_on = self.settings.read().await.diagnostics.compile_on;
+        let mut workspace = self.workspace.write().await;
+        let uri = workspace.normalize_uri(&params.text_document.uri);
+
+        let cached = match workspace.cached_diagnostics(&uri) {
+            Some(cached) => cached.clone(),
+            None => {
+                // Compiling only on save, edits keep the diagnostics of the last save
+                let (diagnostics, global_errors) = match workspace.diagnostics_cache().latest(&uri)
+                {
+                    Some(latest) if compile_on == CompileTrigger::Save => {
+                        (latest.diagnostics.clone(), Vec::new())
+                    }
+                    _ if compile_on == CompileTrigger::Save => {
+                        (workspace.get_diagnostics(&uri), Vec::new())
+                    }
+                    _ => match workspace
+                        .get_document_diagnostics_with_global_errors(&uri)
+                        .await
+                    {
+                        Ok(result) => result,
+                        Err(e) => {
+                            warn!("Failed to get diagnostics for {}: {}", uri, e);
+                            (workspace.get_diagnostics(&uri), Vec::new())
+                        }
+                    },
+                };
+                workspace.record_published_diagnostics(&uri, &diagnostics);
+                let compile_reports = workspace.take_compile_reports();
+                if !global_errors.is_empty() {
+                    send_global_error_notifications(&self.client, global_errors).await;
+                }
+                send_compile_reports(&self.client, compile_reports).await;
+                match workspace.cached_diagnostics(&uri) {
+                    Some(cached) => cached.clone(),
+                    None => CachedDiagnostics {
+                        version: None,
+                        result_id: String::new(),
+                        diagnostics,
+                    },
+                }
+            }
+        };
+
+        Ok(DocumentDiagnosticReportResult::Report(
+            cached.report(params.previous_result_id.as_deref()),
+        ))
+    }
+
+    async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> Result<WorkspaceDiagnosticReportResult> {
+        let previous: HashMap<Url, String> = params
+            .previous_result_ids
+            .into_iter()
+            .map(|previous| (previous.uri, previous.value))
+            .collect();
+        let workspace = self.workspace.read().await;
+        let items = workspace
+            .diagnostics_cache()
+            .entries()
+            .map(|(uri, cached)| {
+                cached.workspace_report(uri, previous.get(uri).map(String::as_str))
+            })
+            .collect();
+        Ok(WorkspaceDiagnosticReportResult::Report(
+            WorkspaceDiagnosticReport { items },
+        ))
+    }
+
     // Language features
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let permit = match self.request_limiter.try_acquire("textDocument/hover") {
+            Some(permit) => permit,
+            None => return Ok(None),
+        };
         info!(
             "🔍 Hover request received for {}:{}",
             params.text_document_position_params.position.line,
             params.text_document_position_params.position.character
         );
 
-        let handlers = Handlers::new(self.workspace.clone());
-        let client_capabilities = self.client_capabilities.read().await;
-        let result = handlers
-            .hover_with_capabilities(params, client_capabilities.as_ref())
-            .await;
+        let span = request_span(
+            "textDocument/hover",
+            Some(&params.text_document_position_params.text_document.uri),
+        );
+        let capabilities = self.readiness.client_capabilities().cloned();
+        let handlers = self.handlers().await;
+        let hover = async move {
+            handlers
+                .hover_with_capabilities(params, capabilities.as_ref())
+                .await
+        }
+        .instrument(span);
+        let result = self
+            .deadlines
+            .within("textDocument/hover", permit, hover)
+            .await
+            .unwrap_or(Ok(None));
 
         info!("✅ Hover request completed");
         result
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
-        let handlers = Handlers::new(self.workspace.clone());
-        handlers.completion(params).await
+        // An incomplete empty list makes the client ask again as the user keeps typing, so
+        // it answers requests shed or run past their deadline
+        let retry = Ok(Some(CompletionResponse::List(CompletionList {
+            is_incomplete: true,
+            items: Vec::new(),
+        })));
+        let permit = match self.request_limiter.try_acquire("textDocument/completion") {
+            Some(permit) => permit,
+            None => return retry,
+        };
+        let span = request_span(
+            "textDocument/completion",
+            Some(&params.text_document_position.text_document.uri),
+        );
+        let uri = params.text_document_position.text_document.uri.clone();
+        let capabilities = self.readiness.client_capabilities().cloned();
+        let handlers = self.handlers().await;
+        let completion = async move {
+            handlers
+                .completion_with_capabilities(params, capabilities.as_ref())
+                .await
+        }
+        .instrument(span);
+        let mut response = self
+            .deadlines
+            .within("textDocument/completion", permit, completion)
+            .await
+            .unwrap_or(retry)?;
+        let ending = self.workspace.read().await.line_ending(&uri);
+        match &mut response {
+            Some(CompletionResponse::Array(items)) => {
+                restore_completion_line_endings(items, ending)
+            }
+            Some(CompletionResponse::List(list)) => {
+                restore_completion_line_endings(&mut list.items, ending)
+            }
+            None => {}
+        }
+        Ok(response)
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        let handlers = Handlers::new(self.workspace.clone());
-        handlers.goto_definition(params).await
+        let permit = self.request_limiter.acquire("textDocument/definition")?;
+        let span = request_span(
+            "textDocument/definition",
+            Some(&params.text_document_position_params.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/definition",
+                permit,
+                async move { handlers.goto_definition(params).await }.instrument(span),
+            )
+            .await
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let permit = match self
+            .request_limiter
+            .try_acquire("textDocument/signatureHelp")
+        {
+            Some(permit) => permit,
+            None => return Ok(None),
+        };
+        let span = request_span(
+            "textDocument/signatureHelp",
+            Some(&params.text_document_position_params.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        let signature_help = async move { handlers.signature_help(params).await }.instrument(span);
+        self.deadlines
+            .within("textDocument/signatureHelp", permit, signature_help)
+            .await
+            .unwrap_or(Ok(None))
     }
 
     async fn document_symbol(
         &self,
         params: DocumentSymbolParams,
     ) -> Result<Option<DocumentSymbolResponse>> {
-        let handlers = Handlers::new(self.workspace.clone());
-        handlers.document_symbols(params).await
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/documentSymbol")?;
+        let span = request_span(
+            "textDocument/documentSymbol",
+            Some(&params.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/documentSymbol",
+                permit,
+                async move { handlers.document_symbols(params).await }.instrument(span),
+            )
+            .await
     }
 
-    async fn symbol(
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let permit = self.request_limiter.acquire("textDocument/foldingRange")?;
+        let span = request_span("textDocument/foldingRange", Some(&params.text_document.uri));
+        let capabilities = self.readiness.client_capabilities().cloned();
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/foldingRange",
+                permit,
+                async move { handlers.folding_range(params, capabilities.as_ref()).await }
+                    .instrument(span),
+            )
+            .await
+    }
+
+    async fn selection_range(
         &self,
-        params: WorkspaceSymbolParams,
-    ) -> Result<Option<Vec<SymbolInformation>>> {
-        let handlers = Handlers::new(self.workspace.clone());
-        handlers.workspace_symbols(params).await
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/selectionRange")?;
+        let span = request_span(
+            "textDocument/selectionRange",
+            Some(&params.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/selectionRange",
+                permit,
+                async move { handlers.selection_range(params).await }.instrument(span),
+            )
+            .await
     }
 
-    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
-        let handlers = Handlers::new(self.workspace.clone());
-        handlers.find_references(params).await
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let permit = self.request_limiter.acquire("textDocument/inlayHint")?;
+        let span = request_span("textDocument/inlayHint", Some(&params.text_document.uri));
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/inlayHint",
+                permit,
+                async move { handlers.inlay_hint(params).await }.instrument(span),
+            )
+            .await
     }
 
-    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        let handlers = Handlers::new(self.workspace.clone());
-        handlers.code_action(params).await
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/semanticTokens/full")?;
+        let span = request_span(
+            "textDocument/semanticTokens/full",
+            Some(&params.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/semanticTokens/full",
+                permit,
+                async move { handlers.semantic_tokens_full(params).await }.instrument(span),
+            )
+            .await
     }
 
-    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
-        let handlers = Handlers::new(self.workspace.clone());
-        handlers.rename(params).await
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/semanticTokens/full/delta")?;
+        let span = request_span(
+            "textDocument/semanticTokens/full/delta",
+            Some(&params.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/semanticTokens/full/delta",
+                permit,
+                async move { handlers.semantic_tokens_full_delta(params).await }.instrument(span),
+            )
+            .await
     }
 
-    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
-        info!("Watched files changed: {} files", params.changes.len());
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let permit = self.request_limiter.acquire("textDocument/formatting")?;
+        let span = request_span("textDocument/formatting", Some(&params.text_document.uri));
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/formatting",
+                permit,
+                async move { handlers.formatting(params).await }.instrument(span),
+            )
+            .await
+    }
 
-        for change in params.changes {
-            info!("File {} changed: {:?}", change.uri, change.typ);
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/rangeFormatting")?;
+        let span = request_span(
+            "textDocument/rangeFormatting",
+            Some(&params.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/rangeFormatting",
+                permit,
+                async move { handlers.range_formatting(params).await }.instrument(span),
+            )
+            .await
+    }
 
-            match change.typ {
-                FileChangeType::CREATED | FileChangeType::CHANGED => {
-                    // For created or changed files, try to index them
-                    self.index_file(&change.uri).await;
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/onTypeFormatting")?;
+        let span = request_span(
+            "textDocument/onTypeFormatting",
+            Some(&params.text_document_position.text_document.uri),
+        );
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/onTypeFormatting",
+                permit,
+                async move { handlers.on_type_formatting(params).await }.instrument(span),
+            )
+            .await
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let permit = self.request_limiter.acquire("textDocument/codeLens")?;
+        let span = request_span("textDocument/codeLens", Some(&params.text_document.uri));
+        let capabilities = self.readiness.client_capabilities().cloned();
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/codeLens",
+                permit,
+                async move {
+                    handlers
+                        .code_lens_with_capabilities(params, capabilities.as_ref())
+                        .await
                 }
-                FileChangeType::DELETED => {
-                    // For deleted files, remove them completely from our index
-                    let mut workspace = self.workspace.write().await;
-                    if let Err(e) = workspace.remove_file(change.uri.clone()) {
-                        info!("Failed to remove file from index: {}", e);
-                    }
+                .instrument(span),
+            )
+            .await
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let permit = self.request_limiter.acquire("workspace/symbol")?;
+        self.readiness.require_index("workspace/symbol")?;
+        let capabilities = self.readiness.client_capabilities().cloned();
+        let handlers = self.handlers().await;
+        let partial_result_token = params.partial_result_params.partial_result_token.clone();
+        let (batch_size, max_results) = {
+            let settings = self.settings.read().await;
+            (
+                settings.workspace_symbols.batch_size.max(1),
+                settings.workspace_symbols.max_results,
+            )
+        };
+        let client = self.client.clone();
+
+        let symbols = async move {
+            let token = match partial_result_token {
+                Some(token) => token,
+                None => {
+                    return handlers
+                        .workspace_symbols_with_capabilities(params, capabilities.as_ref())
+                        .await
                 }
-                _ => {}
+            };
+
+            // Page through the index and send each page as soon as it's found, so the first
+            // results show while later ones are still being looked up
+            let mut offset = 0;
+            while offset < max_results {
+                let limit = batch_size.min(max_results - offset);
+                let (page, more) = handlers
+                    .workspace_symbols_page(&params.query, capabilities.as_ref(), offset, limit)
+                    .await;
+                if !page.is_empty() {
+                    let value = match serde_json::to_value(&page) {
+                        Ok(value) => value,
+                        Err(e) => {
+                            error!("Failed to serialize workspace symbol batch: {}", e);
+                            break;
+                        }
+                    };
+                    client
+                        .send_notification::<PartialResult>(PartialResultParams {
+                            token: token.clone(),
+                            value,
+                        })
+                        .await;
+                }
+                if !more {
+                    break;
+                }
+                offset += limit;
+
+                // tower-lsp handles $/cancelRequest by dropping this future, so give it
+                // a chance to do that between pages rather than only at the end
+                tokio::task::yield_now().await;
             }
+            info!(
+                "📋 Streamed workspace symbols for '{}' in pages of {}",
+                params.query, batch_size
+            );
+
+            // All results were delivered as partial results, so the final response is empty
+            Ok(Some(Vec::new()))
         }
+        .instrument(request_span("workspace/symbol", None));
+        self.deadlines
+            .enforce("workspace/symbol", permit, symbols)
+            .await
     }
 
-    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
-        info!(
-            "Workspace folders changed: {} added, {} removed",
-            params.event.added.len(),
-            params.event.removed.len()
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let permit = self.request_limiter.acquire("textDocument/references")?;
+        let span = request_span(
+            "textDocument/references",
+            Some(&params.text_document_position.text_document.uri),
         );
+        self.readiness.require_index("textDocument/references")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/references",
+                permit,
+                async move { handlers.find_references(params).await }.instrument(span),
+            )
+            .await
+    }
 
-        // Handle removed workspace folders
-        for removed_folder in &params.event.removed {
-            info!("Removing workspace folder: {}", removed_folder.uri);
-            self.cleanup_workspace_folder(&removed_folder.uri).await;
-        }
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/prepareCallHierarchy")?;
+        let span = request_span(
+            "textDocument/prepareCallHierarchy",
+            Some(&params.text_document_position_params.text_document.uri),
+        );
+        self.readiness
+            .require_index("textDocument/prepareCallHierarchy")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/prepareCallHierarchy",
+                permit,
+                async move { handlers.prepare_call_hierarchy(params).await }.instrument(span),
+            )
+            .await
+    }
 
-        // Handle added workspace folders
-        for added_folder in &params.event.added {
-            info!("Adding workspace folder: {}", added_folder.uri);
-            self.index_workspace_folder(&added_folder.uri).await;
-        }
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let permit = self
+            .request_limiter
+            .acquire("callHierarchy/incomingCalls")?;
+        let span = request_span("callHierarchy/incomingCalls", Some(&params.item.uri));
+        self.readiness
+            .require_index("callHierarchy/incomingCalls")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "callHierarchy/incomingCalls",
+                permit,
+                async move { handlers.incoming_calls(params).await }.instrument(span),
+            )
+            .await
+    }
 
-        info!("Workspace folder changes processed");
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let permit = self
+            .request_limiter
+            .acquire("callHierarchy/outgoingCalls")?;
+        let span = request_span("callHierarchy/outgoingCalls", Some(&params.item.uri));
+        self.readiness
+            .require_index("callHierarchy/outgoingCalls")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "callHierarchy/outgoingCalls",
+                permit,
+                async move { handlers.outgoing_calls(params).await }.instrument(span),
+            )
+            .await
     }
-}
 
-impl GrenLanguageServer {
-    /// Register file watchers for Gren files using LSP client capabilities
-    async fn register_file_watchers(&self) {
-        info!("Registering file watchers for *.gren files");
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/prepareTypeHierarchy")?;
+        let span = request_span(
+            "textDocument/prepareTypeHierarchy",
+            Some(&params.text_document_position_params.text_document.uri),
+        );
+        self.readiness
+            .require_index("textDocument/prepareTypeHierarchy")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/prepareTypeHierarchy",
+                permit,
+                async move { handlers.prepare_type_hierarchy(params).await }.instrument(span),
+            )
+            .await
+    }
 
-        let registration_params = RegistrationParams {
-            registrations: vec![Registration {
-                id: "gren-file-watcher".to_string(),
-                method: "workspace/didChangeWatchedFiles".to_string(),
-                register_options: Some(
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let permit = self.request_limiter.acquire("typeHierarchy/supertypes")?;
+        let span = request_span("typeHierarchy/supertypes", Some(&params.item.uri));
+        self.readiness.require_index("typeHierarchy/supertypes")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "typeHierarchy/supertypes",
+                permit,
+                async move { handlers.supertypes(params).await }.instrument(span),
+            )
+            .await
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let permit = self.request_limiter.acquire("typeHierarchy/subtypes")?;
+        let span = request_span("typeHierarchy/subtypes", Some(&params.item.uri));
+        self.readiness.require_index("typeHierarchy/subtypes")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "typeHierarchy/subtypes",
+                permit,
+                async move { handlers.subtypes(params).await }.instrument(span),
+            )
+            .await
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let permit = self.request_limiter.acquire("textDocument/codeAction")?;
+        let span = request_span("textDocument/codeAction", Some(&params.text_document.uri));
+        let capabilities = self.readiness.client_capabilities().cloned();
+        let handlers = self.handlers().await;
+        let mut actions = self
+            .deadlines
+            .enforce(
+                "textDocument/codeAction",
+                permit,
+                async move {
+                    handlers
+                        .code_action_with_capabilities(params, capabilities.as_ref())
+                        .await
+                }
+                .instrument(span),
+            )
+            .await?;
+        if let Some(actions) = &mut actions {
+            restore_action_line_endings(&*self.workspace.read().await, actions);
+        }
+        Ok(actions)
+    }
+
+    async fn code_action_resolve(&self, action: CodeAction) -> Result<CodeAction> {
+        let permit = self.request_limiter.acquire("codeAction/resolve")?;
+        let span = request_span("codeAction/resolve", None);
+        let handlers = self.handlers().await;
+        let mut action = self
+            .deadlines
+            .enforce(
+                "codeAction/resolve",
+                permit,
+                async move { handlers.code_action_resolve(action).await }.instrument(span),
+            )
+            .await?;
+        if let Some(edit) = &mut action.edit {
+            let workspace = self.workspace.read().await;
+            restore_line_endings(edit, |uri| workspace.line_ending(uri));
+        }
+        Ok(action)
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let permit = self.request_limiter.acquire("textDocument/prepareRename")?;
+        let span = request_span(
+            "textDocument/prepareRename",
+            Some(&params.text_document.uri),
+        );
+        self.readiness.require_index("textDocument/prepareRename")?;
+        let handlers = self.handlers().await;
+        self.deadlines
+            .enforce(
+                "textDocument/prepareRename",
+                permit,
+                async move { handlers.prepare_rename(params).await }.instrument(span),
+            )
+            .await
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let permit = self.request_limiter.acquire("textDocument/rename")?;
+        let span = request_span(
+            "textDocument/rename",
+            Some(&params.text_document_position.text_document.uri),
+        );
+        self.readiness.require_index("textDocument/rename")?;
+        let handlers = self.handlers().await;
+        let edit = self
+            .deadlines
+            .enforce(
+                "textDocument/rename",
+                permit,
+                async move { handlers.rename(params).await }.instrument(span),
+            )
+            .await?;
+
+        // The client applies the edit, files it doesn't have open only get re-indexed once
+        // their new content comes back
+        if let Some(edit) = &edit {
+            mark_index_pending(&self.client, &self.workspace, &edited_files(edit)).await;
+        }
+        Ok(edit)
+    }
+
+    /// Files changed outside the editor, by a `git checkout` or a code generator, are
+    /// indexed again and the modules importing them recompiled, so neither the index nor
+    /// the diagnostics go stale. Documents open in the editor are left alone: the editor's
+    /// buffer is their content, and it sends a change when it reloads the file.
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        info!("Watched files changed: {} files", params.changes.len());
+
+        let mut project_config_changed = false;
+        let mut manifest_changed = false;
+        let mut dependents: Vec<Url> = Vec::new();
+        for change in params.changes {
+            info!("File {} changed: {:?}", change.uri, change.typ);
+            if is_project_config(&change.uri) {
+                project_config_changed = true;
+                continue;
+            }
+            if is_manifest(&change.uri) {
+                if let Ok(path) = change.uri.to_file_path() {
+                    forget_dependency_versions(&path);
+                }
+                manifest_changed = true;
+                continue;
+            }
+
+            let uri = self.workspace.read().await.normalize_uri(&change.uri);
+            if self.recompile_queue.lock().await.is_open(&uri) {
+                info!("Not re-reading {}: the editor has it open", uri);
+                continue;
+            }
+
+            match change.typ {
+                FileChangeType::CREATED | FileChangeType::CHANGED => {
+                    if self.workspace.read().await.is_excluded(&change.uri) {
+                        info!(
+                            "Not indexing {}: excluded in {}",
+                            change.uri, PROJECT_CONFIG_FILE
+                        );
+                        continue;
+                    }
+                    // For created or changed files, try to index them
+                    if let Err(reason) = self.index_file(&change.uri).await {
+                        warn!("Not indexing {}: {}", change.uri, reason);
+                        continue;
+                    }
+                    dependents.extend(self.workspace.read().await.module_dependents(&uri));
+                }
+                FileChangeType::DELETED => {
+                    // Modules importing a deleted one are broken now
+                    dependents.extend(self.workspace.read().await.module_dependents(&uri));
+                    self.remove_deleted_file(&change.uri).await;
+                }
+                _ => {}
+            }
+        }
+        if project_config_changed {
+            self.workspace.write().await.reload_project_config();
+            self.refresh_effective_settings().await;
+            self.publish_project_config_diagnostics().await;
+        }
+        if manifest_changed {
+            // Dependencies or source directories may have changed, which can change any
+            // open document's diagnostics
+            info!("gren.json changed outside the editor, recompiling open documents");
+            {
+                let mut workspace = self.workspace.write().await;
+                workspace.refresh_projects();
+                workspace.invalidate_compiler_cache();
+            }
+            self.index_dependency_packages().await;
+            dependents.extend(self.recompile_queue.lock().await.open_documents());
+        }
+        send_index_status(&self.client, &self.workspace).await;
+
+        let mut seen = HashSet::new();
+        dependents.retain(|uri| seen.insert(uri.clone()));
+        if !dependents.is_empty() {
+            info!(
+                "Recompiling {} modules after external changes",
+                dependents.len()
+            );
+            self.schedule_recompile(dependents).await;
+        }
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // A change without usable settings leaves the current ones in place rather than
+        // resetting everything the user configured to the defaults
+        match ServerSettings::try_from_json(&params.settings) {
+            Some(new_settings) => {
+                info!("Configuration changed: {:?}", new_settings);
+                self.apply_settings(new_settings).await;
+            }
+            None => {
+                info!("Configuration change carried no usable settings, keeping the current ones")
+            }
+        }
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        info!(
+            "Workspace folders changed: {} added, {} removed",
+            params.event.added.len(),
+            params.event.removed.len()
+        );
+
+        // Handle removed workspace folders
+        for removed_folder in &params.event.removed {
+            info!("Removing workspace folder: {}", removed_folder.uri);
+            self.cleanup_workspace_folder(&removed_folder.uri).await;
+        }
+
+        // Handle added workspace folders
+        for added_folder in &params.event.added {
+            info!("Adding workspace folder: {}", added_folder.uri);
+            self.index_workspace_folder(&added_folder.uri).await;
+        }
+
+        info!("Workspace folder changes processed");
+    }
+
+    async fn did_rename_files(&self, params: RenameFilesParams) {
+        info!("Files renamed: {} entries", params.files.len());
+
+        let mut renamed = Vec::new();
+        for file_rename in params.files {
+            let (old_uri, new_uri) = match (
+                Url::parse(&file_rename.old_uri),
+                Url::parse(&file_rename.new_uri),
+            ) {
+                (Ok(old_uri), Ok(new_uri)) => (old_uri, new_uri),
+                _ => {
+                    warn!(
+                        "Ignoring rename with invalid URIs: {} -> {}",
+                        file_rename.old_uri, file_rename.new_uri
+                    );
+                    continue;
+                }
+            };
+
+            for (old_file, new_file) in self.expand_renamed_files(&old_uri, &new_uri).await {
+                info!("File {} renamed to {}", old_file, new_file);
+
+                // Move the document in the workspace so the new path is what gets analyzed
+                let moved = {
+                    let mut workspace = self.workspace.write().await;
+                    workspace.rename_file(&old_file, new_file.clone())
+                };
+                if let Err(e) = moved {
+                    info!("Renamed file was not open, indexing from disk: {}", e);
+                    if let Err(e) = self.workspace.write().await.remove_file(old_file) {
+                        info!("Failed to remove file from index: {}", e);
+                    }
+                    if let Err(reason) = self.index_file(&new_file).await {
+                        warn!("Not indexing {}: {}", new_file, reason);
+                    }
+                }
+                renamed.push(new_file);
+            }
+            // The old paths' diagnostics are gone from the project, not fixed
+            self.clear_stale_diagnostics(&old_uri).await;
+        }
+
+        self.republish_diagnostics(renamed.clone()).await;
+        self.offer_module_rename_sync(renamed).await;
+    }
+
+    async fn did_delete_files(&self, params: DeleteFilesParams) {
+        info!("Files deleted: {} entries", params.files.len());
+
+        for file in params.files {
+            match Url::parse(&file.uri) {
+                Ok(uri) => self.remove_deleted_file(&uri).await,
+                Err(_) => warn!("Ignoring delete with an invalid URI: {}", file.uri),
+            }
+        }
+        send_index_status(&self.client, &self.workspace).await;
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        let span = request_span("workspace/executeCommand", None);
+        let result = match params.command.as_str() {
+            ADD_DEPENDENCY_COMMAND | REMOVE_DEPENDENCY_COMMAND => {
+                let args: DependencyCommandArguments = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| "missing arguments".to_string())
+                    .and_then(|arg| serde_json::from_value(arg).map_err(|e| e.to_string()))
+                    .map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid {} arguments: {}",
+                            params.command, e
+                        ))
+                    })?;
+                self.edit_dependencies(&args, params.command == ADD_DEPENDENCY_COMMAND)
+                    .instrument(span)
+                    .await
+            }
+            UNDO_LAST_SERVER_EDIT_COMMAND => self.undo_last_server_edit().instrument(span).await,
+            MOVE_DECLARATION_COMMAND => {
+                let args: MoveDeclarationArguments = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| "missing arguments".to_string())
+                    .and_then(|arg| serde_json::from_value(arg).map_err(|e| e.to_string()))
+                    .map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid {} arguments: {}",
+                            params.command, e
+                        ))
+                    })?;
+                self.move_declaration(&args).instrument(span).await
+            }
+            EDIT_EXPOSING_COMMAND => {
+                let args: EditExposingArguments = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| "missing arguments".to_string())
+                    .and_then(|arg| serde_json::from_value(arg).map_err(|e| e.to_string()))
+                    .map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid {} arguments: {}",
+                            params.command, e
+                        ))
+                    })?;
+                self.edit_exposing(&args).instrument(span).await
+            }
+            CHECK_FORMATTING_COMMAND => self.check_formatting().instrument(span).await,
+            FORMAT_WORKSPACE_COMMAND => self.format_workspace().instrument(span).await,
+            RENAME_STRING_LITERAL_COMMAND => {
+                let args: RenameStringLiteralArguments = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| "missing arguments".to_string())
+                    .and_then(|arg| serde_json::from_value(arg).map_err(|e| e.to_string()))
+                    .map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid {} arguments: {}",
+                            params.command, e
+                        ))
+                    })?;
+                self.rename_string_literal(&args).instrument(span).await
+            }
+            other => {
+                return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                    "Unknown command: {}",
+                    other
+                )))
+            }
+        };
+
+        match result {
+            Ok(message) => {
+                self.client.show_message(MessageType::INFO, message).await;
+                Ok(None)
+            }
+            Err(e) => {
+                self.client
+                    .show_message(MessageType::ERROR, format!("{:#}", e))
+                    .await;
+                Err(tower_lsp::jsonrpc::Error {
+                    code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                    message: format!("{:#}", e).into(),
+                    data: None,
+                })
+            }
+        }
+    }
+}
+
+/// What the server offers. `gren-lsp methods` lists the methods these stand for.
+pub fn server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string()]),
+            ..Default::default()
+        }),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec![" ".to_string()]),
+            retrigger_characters: None,
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        // Only used by clients that pull diagnostics, the others still have them pushed
+        diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+            identifier: Some("gren".to_string()),
+            inter_file_dependencies: true,
+            workspace_diagnostics: true,
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        })),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+        inlay_hint_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+                legend: semantic_tokens::legend(),
+                range: None,
+                full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+            },
+        )),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            // Renames can break the module name <-> path correspondence, so we
+            // want to hear about both Gren files and folders containing them
+            file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                did_rename: Some(FileOperationRegistrationOptions {
+                    filters: vec![
+                        FileOperationFilter {
+                            scheme: Some("file".to_string()),
+                            pattern: FileOperationPattern {
+                                glob: "**/*.gren".to_string(),
+                                matches: Some(FileOperationPatternKind::File),
+                                options: None,
+                            },
+                        },
+                        FileOperationFilter {
+                            scheme: Some("file".to_string()),
+                            pattern: FileOperationPattern {
+                                glob: "**/*".to_string(),
+                                matches: Some(FileOperationPatternKind::Folder),
+                                options: None,
+                            },
+                        },
+                    ],
+                }),
+                // Deleted files' diagnostics are cleared, whether or not a watcher reports
+                // the deletion too
+                did_delete: Some(FileOperationRegistrationOptions {
+                    filters: vec![
+                        FileOperationFilter {
+                            scheme: Some("file".to_string()),
+                            pattern: FileOperationPattern {
+                                glob: "**/*.gren".to_string(),
+                                matches: Some(FileOperationPatternKind::File),
+                                options: None,
+                            },
+                        },
+                        FileOperationFilter {
+                            scheme: Some("file".to_string()),
+                            pattern: FileOperationPattern {
+                                glob: "**/*".to_string(),
+                                matches: Some(FileOperationPatternKind::Folder),
+                                options: None,
+                            },
+                        },
+                    ],
+                }),
+                ..Default::default()
+            }),
+        }),
+        // TODO: Implement these features
+        references_provider: Some(OneOf::Left(true)),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![
+                CodeActionKind::QUICKFIX,
+                CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                CodeActionKind::REFACTOR_REWRITE,
+                SOURCE_SORT_DECLARATIONS,
+                SOURCE_MOVE_DECLARATION,
+            ]),
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+            resolve_provider: Some(true),
+        })),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        })),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        // `in` and `is` end with `n` and `s`
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "\n".to_string(),
+            more_trigger_character: Some(vec!["n".to_string(), "s".to_string()]),
+        }),
+        code_lens_provider: Some(CodeLensOptions {
+            resolve_provider: Some(false),
+        }),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![
+                ADD_DEPENDENCY_COMMAND.to_string(),
+                REMOVE_DEPENDENCY_COMMAND.to_string(),
+                UNDO_LAST_SERVER_EDIT_COMMAND.to_string(),
+                MOVE_DECLARATION_COMMAND.to_string(),
+                RENAME_STRING_LITERAL_COMMAND.to_string(),
+                EDIT_EXPOSING_COMMAND.to_string(),
+                CHECK_FORMATTING_COMMAND.to_string(),
+                FORMAT_WORKSPACE_COMMAND.to_string(),
+            ],
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        }),
+        ..Default::default()
+    }
+}
+
+/// A project file and its contents as the formatter writes them
+struct FormattedFile {
+    uri: Url,
+    source: String,
+    formatted: String,
+}
+
+/// Whether `uri` is a project's `gren.json`
+fn is_manifest(uri: &Url) -> bool {
+    uri.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .is_some_and(|name| name == "gren.json")
+}
+
+/// Whether `uri` is a project's `.gren-lsp.toml`
+fn is_project_config(uri: &Url) -> bool {
+    uri.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .is_some_and(|name| name == PROJECT_CONFIG_FILE)
+}
+
+/// Span covering one request from receipt to response. Parse, index query and compile
+/// spans opened while handling it are nested underneath.
+fn request_span(method: &'static str, uri: Option<&Url>) -> tracing::Span {
+    info_span!(
+        "lsp.request",
+        method,
+        uri_hash = uri.map(uri_hash).unwrap_or_default()
+    )
+}
+
+/// Mark files about to be changed by an edit as waiting to be re-indexed, and tell the
+/// client. Files whose new content never arrives stop being reported after
+/// `PENDING_EXPIRY`, so the client is told again then.
+async fn mark_index_pending(client: &Client, workspace: &Arc<RwLock<Workspace>>, files: &[Url]) {
+    if files.is_empty() {
+        return;
+    }
+    workspace.write().await.mark_index_pending(files);
+    send_index_status(client, workspace).await;
+
+    let client = client.clone();
+    let workspace = workspace.clone();
+    tokio::spawn(async move {
+        sleep(PENDING_EXPIRY).await;
+        send_index_status(&client, &workspace).await;
+    });
+}
+
+/// Send a `gren/indexStatus` notification if the files waiting to be re-indexed changed
+/// since the last one
+async fn send_index_status(client: &Client, workspace: &RwLock<Workspace>) {
+    let status = {
+        let mut workspace = workspace.write().await;
+        workspace
+            .take_index_status_change()
+            .map(|pending_files| IndexStatusParams {
+                generation: workspace.index_generations().generation(),
+                pending_files,
+            })
+    };
+    if let Some(status) = status {
+        client
+            .send_notification::<IndexStatusNotification>(status)
+            .await;
+    }
+}
+
+/// Send a `gren/compileReport` notification for each compile since the last send
+async fn send_compile_reports(client: &Client, reports: Vec<CompileReport>) {
+    for report in reports {
+        client
+            .send_notification::<CompileReportNotification>(report)
+            .await;
+    }
+}
+
+/// Send global error notifications to the client
+async fn send_global_error_notifications(
+    client: &Client,
+    global_errors: Vec<gren_lsp_core::compiler::GlobalError>,
+) {
+    for global_error in global_errors {
+        let message = match global_error.title.as_str() {
+            "GREN VERSION MISMATCH" => {
+                // Extract version information from the message for a more actionable notification
+                format!("🚨 Gren Version Mismatch\n\n{}\n\nPlease update your Gren compiler or modify your gren.json file to match.", global_error.message)
+            }
+            _ => {
+                format!(
+                    "🚨 Compiler Error: {}\n\n{}",
+                    global_error.title, global_error.message
+                )
+            }
+        };
+
+        // Use MessageType::Error for critical issues like version mismatches
+        let message_type = match global_error.severity {
+            gren_lsp_core::compiler::DiagnosticSeverity::Error => MessageType::ERROR,
+            gren_lsp_core::compiler::DiagnosticSeverity::Warning => MessageType::WARNING,
+            gren_lsp_core::compiler::DiagnosticSeverity::Info => MessageType::INFO,
+        };
+
+        // Send notification to VS Code
+        info!(
+            "📢 Sending global error notification: {}",
+            global_error.title
+        );
+        client.show_message(message_type, message).await;
+    }
+}
+
+/// Compute and publish the diagnostics of a document that was just opened
+async fn publish_open_diagnostics(client: &Client, workspace: &RwLock<Workspace>, uri: Url) {
+    let mut workspace = workspace.write().await;
+
+    // Get comprehensive diagnostics and global errors for the newly opened document
+    let (diagnostics, global_errors) = match workspace
+        .get_document_diagnostics_with_global_errors(&uri)
+        .await
+    {
+        Ok((diags, global_errs)) => {
+            info!(
+                "Found {} comprehensive diagnostics and {} global errors for document: {}",
+                diags.len(),
+                global_errs.len(),
+                uri
+            );
+            (diags, global_errs)
+        }
+        Err(e) => {
+            warn!("Failed to get comprehensive diagnostics for {}: {}", uri, e);
+            // Fallback to syntax-only diagnostics
+            let syntax_diagnostics = workspace.get_diagnostics(&uri);
+            info!(
+                "Fallback: {} syntax diagnostics for document: {}",
+                syntax_diagnostics.len(),
+                uri
+            );
+            (syntax_diagnostics, Vec::new())
+        }
+    };
+
+    let stats = workspace.stats();
+    let compile_reports = workspace.take_compile_reports();
+    workspace.record_published_diagnostics(&uri, &diagnostics);
+    let push = workspace.pushes_diagnostics();
+    drop(workspace);
+
+    // Send global error notifications if any were found
+    send_global_error_notifications(client, global_errors).await;
+
+    info!("Workspace stats: {} documents open", stats.document_count);
+
+    // Publish diagnostics with timeout to prevent hanging. A client pulling diagnostics
+    // asks for them itself, and gets these from the cache.
+    if push {
+        let publish_result = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            client.publish_diagnostics(uri.clone(), diagnostics, None),
+        )
+        .await;
+
+        match publish_result {
+            Ok(_) => {}
+            Err(_) => error!("❌ Timeout publishing diagnostics for: {}", uri),
+        }
+    }
+
+    send_compile_reports(client, compile_reports).await;
+}
+
+/// Wait for a burst of opened documents to end, then diagnose the queued documents one at
+/// a time, reporting progress when the client supports it
+async fn drain_open_burst(
+    client: Client,
+    workspace: Arc<RwLock<Workspace>>,
+    open_burst: Arc<Mutex<OpenBurst>>,
+    supports_progress: bool,
+) {
+    loop {
+        sleep(BURST_WINDOW).await;
+        if open_burst.lock().await.is_settled(Instant::now()) {
+            break;
+        }
+    }
+    let queued = open_burst.lock().await.take_queued();
+    if queued.is_empty() {
+        return;
+    }
+    info!("Diagnosing {} documents opened in a burst", queued.len());
+
+    let token = NumberOrString::String(OPEN_BURST_PROGRESS_TOKEN.to_string());
+    let token = if supports_progress {
+        let created = client
+            .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await;
+        match created {
+            Ok(()) => Some(token),
+            Err(e) => {
+                warn!("Client refused the progress token: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let progress = |progress: WorkDoneProgress| {
+        let client = &client;
+        let token = token.clone();
+        async move {
+            if let Some(token) = token {
+                client
+                    .send_notification::<notification::Progress>(ProgressParams {
+                        token,
+                        value: ProgressParamsValue::WorkDone(progress),
+                    })
+                    .await;
+            }
+        }
+    };
+
+    let total = queued.len();
+    progress(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+        title: "Checking opened files".to_string(),
+        cancellable: Some(false),
+        message: Some(format!("0/{} files", total)),
+        percentage: Some(0),
+    }))
+    .await;
+
+    for (index, uri) in queued.into_iter().enumerate() {
+        // Skip documents closed since they were queued
+        if workspace.read().await.is_document_open(&uri) {
+            publish_open_diagnostics(&client, &workspace, uri).await;
+        }
+        progress(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(format!("{}/{} files", index + 1, total)),
+            percentage: Some(((index + 1) * 100 / total) as u32),
+        }))
+        .await;
+    }
+
+    progress(WorkDoneProgress::End(WorkDoneProgressEnd {
+        message: Some(format!("Checked {} files", total)),
+    }))
+    .await;
+}
+
+/// Compile the queued dependents of saved modules one at a time, publishing their
+/// diagnostics, until the queue is empty
+async fn drain_recompile_queue(
+    client: Client,
+    workspace: Arc<RwLock<Workspace>>,
+    queue: Arc<Mutex<RecompileQueue>>,
+) {
+    let mut pulled_changed = false;
+    loop {
+        let next = queue.lock().await.take_next();
+        let (priority, uri) = match next {
+            Some(next) => next,
+            None => break,
+        };
+        info!("Recompiling dependent {} ({:?})", uri, priority);
+
+        let (diagnostics, compile_reports, push) = {
+            let mut workspace = workspace.write().await;
+            // The file itself didn't change, so a cached result would be stale
+            let diagnostics = match workspace
+                .force_refresh_diagnostics_with_global_errors(&uri)
+                .await
+            {
+                Ok((diagnostics, _)) => diagnostics,
+                Err(e) => {
+                    warn!("Failed to recompile dependent {}: {}", uri, e);
+                    continue;
+                }
+            };
+            workspace.record_published_diagnostics(&uri, &diagnostics);
+            let push = workspace.pushes_diagnostics();
+            (diagnostics, workspace.take_compile_reports(), push)
+        };
+        if push {
+            client.publish_diagnostics(uri, diagnostics, None).await;
+        } else {
+            pulled_changed = true;
+        }
+        send_compile_reports(&client, compile_reports).await;
+    }
+    if pulled_changed {
+        refresh_pulled_diagnostics(&client).await;
+    }
+}
+
+/// Ask a client pulling diagnostics to pull them again, after they changed for a reason
+/// other than an edit of the document
+async fn refresh_pulled_diagnostics(client: &Client) {
+    if let Err(e) = client.workspace_diagnostic_refresh().await {
+        warn!("Failed to ask the client to pull diagnostics again: {}", e);
+    }
+}
+
+/// Give the edits of code `actions` the line endings of the files they change
+fn restore_action_line_endings(workspace: &Workspace, actions: &mut [CodeActionOrCommand]) {
+    for action in actions {
+        if let CodeActionOrCommand::CodeAction(CodeAction {
+            edit: Some(edit), ..
+        }) = action
+        {
+            restore_line_endings(edit, |uri| workspace.line_ending(uri));
+        }
+    }
+}
+
+/// Give the text completion `items` insert the line ending of their document
+fn restore_completion_line_endings(items: &mut [CompletionItem], ending: &str) {
+    for item in items {
+        if let Some(insert_text) = &item.insert_text {
+            item.insert_text = Some(with_line_ending(insert_text, ending).into_owned());
+        }
+        match &mut item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => restore_text_edit_ending(edit, ending),
+            Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+                edit.new_text = with_line_ending(&edit.new_text, ending).into_owned();
+            }
+            None => {}
+        }
+        for edit in item.additional_text_edits.iter_mut().flatten() {
+            restore_text_edit_ending(edit, ending);
+        }
+    }
+}
+
+/// Workspace edits worked out against the same files, as one edit. Each file's text edits
+/// go into a single document edit, as the client applies separate document edits of one
+/// file one after the other.
+fn merged_edit(edits: Vec<WorkspaceEdit>) -> WorkspaceEdit {
+    let mut merged: Vec<TextDocumentEdit> = Vec::new();
+    for edit in edits {
+        let document_edits = match edit.document_changes {
+            Some(DocumentChanges::Edits(document_edits)) => document_edits,
+            _ => Vec::new(),
+        };
+        for document_edit in document_edits {
+            let uri = &document_edit.text_document.uri;
+            match merged
+                .iter_mut()
+                .find(|known| &known.text_document.uri == uri)
+            {
+                Some(known) => known.edits.extend(document_edit.edits),
+                None => merged.push(document_edit),
+            }
+        }
+    }
+    for document_edit in &mut merged {
+        document_edit.edits.sort_by_key(|edit| {
+            let range = match edit {
+                OneOf::Left(edit) => edit.range,
+                OneOf::Right(edit) => edit.text_edit.range,
+            };
+            std::cmp::Reverse((range.start.line, range.start.character))
+        });
+    }
+    WorkspaceEdit {
+        changes: None,
+        document_changes: Some(DocumentChanges::Edits(merged)),
+        change_annotations: None,
+    }
+}
+
+/// Apply an edit through the client, recording it in the workspace's edit journal so
+/// `gren.undoLastServerEdit` can revert it
+async fn apply_server_edit(
+    client: &Client,
+    workspace: &Arc<RwLock<Workspace>>,
+    label: &str,
+    mut edit: WorkspaceEdit,
+) -> anyhow::Result<()> {
+    let files = edited_files(&edit);
+    let before: HashMap<Url, String> = {
+        let workspace = workspace.read().await;
+        restore_line_endings(&mut edit, |uri| workspace.line_ending(uri));
+        files
+            .iter()
+            .filter_map(|uri| Some((uri.clone(), workspace.file_contents(uri)?)))
+            .collect()
+    };
+
+    mark_index_pending(client, workspace, &files).await;
+
+    let response = client.apply_edit(edit.clone()).await?;
+    if !response.applied {
+        anyhow::bail!(
+            "Client declined the edit: {}",
+            response
+                .failure_reason
+                .unwrap_or_else(|| "no reason given".to_string())
+        );
+    }
+
+    if let Err(e) = workspace
+        .write()
+        .await
+        .record_server_edit(label, &edit, &before)
+    {
+        warn!("\"{}\" can't be undone: {:#}", label, e);
+    }
+    Ok(())
+}
+
+impl GrenLanguageServer {
+    /// Tell the user, once per scheme, that a document the client opened won't get any
+    /// language features. Its later changes, saves and closes are ignored without a word.
+    async fn report_unsupported_scheme(&self, uri: &Url) {
+        let scheme = uri.scheme().to_string();
+        if !self.unsupported_schemes.lock().await.insert(scheme.clone()) {
+            return;
+        }
+        warn!("Ignoring `{}:` documents, such as {}", scheme, uri);
+        self.client
+            .show_message(
+                MessageType::INFO,
+                format!(
+                    "Gren language features aren't available for `{}:` documents, only for files and untitled buffers",
+                    scheme
+                ),
+            )
+            .await;
+    }
+
+    /// Handle the `gren/searchDocs` custom request
+    pub async fn search_docs(&self, params: SearchDocsParams) -> Result<Vec<DocSearchResult>> {
+        self.readiness.require_index("gren/searchDocs")?;
+        let handlers = self.handlers().await;
+        handlers
+            .search_docs(params)
+            .instrument(request_span("gren/searchDocs", None))
+            .await
+    }
+
+    /// Handle the `gren/openModule` custom request
+    pub async fn open_module(&self, params: OpenModuleParams) -> Result<Vec<ModuleMatch>> {
+        self.readiness.require_index("gren/openModule")?;
+        let handlers = self.handlers().await;
+        handlers
+            .open_module(params)
+            .instrument(request_span("gren/openModule", None))
+            .await
+    }
+
+    /// Handle the `textDocument/inlineCompletion` request, served as a custom method since
+    /// tower-lsp doesn't know it
+    pub async fn inline_completion(
+        &self,
+        params: InlineCompletionParams,
+    ) -> Result<Option<Vec<InlineCompletionItem>>> {
+        let permit = self
+            .request_limiter
+            .acquire("textDocument/inlineCompletion")?;
+        let span = request_span(
+            "textDocument/inlineCompletion",
+            Some(&params.text_document_position.text_document.uri),
+        );
+        let uri = params.text_document_position.text_document.uri.clone();
+        let handlers = self.handlers().await;
+        let inline_completion =
+            async move { handlers.inline_completion(params).await }.instrument(span);
+        let mut items = self
+            .deadlines
+            .within("textDocument/inlineCompletion", permit, inline_completion)
+            .await
+            .unwrap_or(Ok(None))?;
+        let ending = self.workspace.read().await.line_ending(&uri);
+        for item in items.iter_mut().flatten() {
+            item.insert_text = with_line_ending(&item.insert_text, ending).into_owned();
+        }
+        Ok(items)
+    }
+
+    /// Handle the `gren/alternatePosition` custom request
+    pub async fn alternate_position(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<Location>> {
+        let span = request_span("gren/alternatePosition", Some(&params.text_document.uri));
+        let handlers = self.handlers().await;
+        handlers.alternate_position(params).instrument(span).await
+    }
+
+    /// Handle the `gren/findValuesOfType` custom request
+    pub async fn find_values_of_type(
+        &self,
+        params: FindValuesOfTypeParams,
+    ) -> Result<Vec<ValueOfType>> {
+        self.readiness.require_index("gren/findValuesOfType")?;
+        let handlers = self.handlers().await;
+        handlers
+            .find_values_of_type(params)
+            .instrument(request_span("gren/findValuesOfType", None))
+            .await
+    }
+
+    /// Handle the `gren/moduleExports` custom request
+    pub async fn module_exports(
+        &self,
+        params: ModuleExportsParams,
+    ) -> Result<Option<ModuleExportsResult>> {
+        self.readiness.require_index("gren/moduleExports")?;
+        let span = request_span("gren/moduleExports", Some(&params.text_document.uri));
+        let handlers = self.handlers().await;
+        handlers.module_exports(params).instrument(span).await
+    }
+
+    /// Handle the `gren/todos` custom request
+    pub async fn todos(&self) -> Result<Vec<FileTodos>> {
+        self.readiness.require_index("gren/todos")?;
+        let handlers = self.handlers().await;
+        handlers
+            .todos()
+            .instrument(request_span("gren/todos", None))
+            .await
+    }
+
+    /// Handle a `gren/runtimeLog` notification from a runtime harness
+    pub async fn runtime_log(&self, params: RuntimeLogParams) {
+        self.workspace
+            .write()
+            .await
+            .record_runtime_log(&params.tag, params.value);
+
+        if !self.settings.read().await.runtime_log.code_lens {
+            return;
+        }
+        let supports_refresh = self
+            .readiness
+            .client_capabilities()
+            .and_then(|caps| caps.workspace.as_ref())
+            .and_then(|workspace| workspace.code_lens.as_ref())
+            .and_then(|code_lens| code_lens.refresh_support)
+            .unwrap_or(false);
+        if supports_refresh {
+            if let Err(e) = self.client.code_lens_refresh().await {
+                warn!("Failed to refresh code lenses: {}", e);
+            }
+        }
+    }
+
+    /// Handle the `gren/activeDocument` notification
+    pub async fn active_document(&self, params: ActiveDocumentParams) {
+        let uri = self
+            .workspace
+            .read()
+            .await
+            .normalize_uri(&params.text_document.uri);
+        self.open_burst.lock().await.set_active(uri.clone());
+        self.recompile_queue.lock().await.set_active(uri);
+    }
+
+    /// Handle the `$/setTrace` notification
+    pub async fn set_trace(&self, params: SetTraceParams) {
+        if let Err(e) = self.trace_control.lock().await.set_trace(params.value) {
+            warn!("Failed to change the trace level: {:#}", e);
+        }
+    }
+
+    /// Handle the `gren/setTrace` notification
+    pub async fn set_subsystem_trace(&self, params: SetSubsystemTraceParams) {
+        let result = self
+            .trace_control
+            .lock()
+            .await
+            .set_subsystem(params.subsystem, params.enabled);
+        if let Err(e) = result {
+            warn!("Failed to change the trace level: {:#}", e);
+        }
+    }
+
+    /// Handle the `gren/indexFreshness` custom request
+    pub async fn index_freshness(
+        &self,
+        params: IndexFreshnessParams,
+    ) -> Result<IndexFreshnessResult> {
+        let workspace = self.workspace.read().await;
+        let uri = workspace.normalize_uri(&params.text_document.uri);
+        let generations = workspace.index_generations();
+        Ok(IndexFreshnessResult {
+            generation: generations.file_generation(&uri),
+            workspace_generation: generations.generation(),
+            pending: generations.is_pending(&uri),
+            pending_files: generations.pending_files(),
+            signatures_only: generations.is_signatures_only(&uri),
+        })
+    }
+
+    /// Handle the `gren/requestStats` custom request
+    pub async fn request_stats(&self) -> Result<Vec<RequestMethodStats>> {
+        Ok(self.deadlines.stats())
+    }
+
+    /// Handle the `gren/indexStats` custom request
+    pub async fn index_stats(&self) -> Result<IndexStats> {
+        let handlers = self.handlers().await;
+        handlers
+            .index_stats()
+            .instrument(request_span("gren/indexStats", None))
+            .await
+    }
+
+    /// Handle the `gren/compileHistory` custom request
+    pub async fn compile_history(&self) -> Result<Vec<CompileReport>> {
+        let handlers = self.handlers().await;
+        handlers
+            .compile_history()
+            .instrument(request_span("gren/compileHistory", None))
+            .await
+    }
+
+    /// Handle the `gren/deprecatedUsages` custom request
+    pub async fn deprecated_usages(&self) -> Result<Vec<DeprecatedSymbolUsages>> {
+        self.readiness.require_index("gren/deprecatedUsages")?;
+        let handlers = self.handlers().await;
+        handlers
+            .deprecated_usages()
+            .instrument(request_span("gren/deprecatedUsages", None))
+            .await
+    }
+
+    /// Handle the `gren/metrics` custom request
+    pub async fn metrics(&self, params: MetricsParams) -> Result<Vec<ModuleMetricsReport>> {
+        let uri = params.text_document.as_ref().map(|document| &document.uri);
+        let span = request_span("gren/metrics", uri);
+        let handlers = self.handlers().await;
+        handlers.metrics(params).instrument(span).await
+    }
+
+    /// Handle the `gren/moduleRenameImpact` custom request
+    ///
+    /// Importers come from the module graph, so only files that actually import the module
+    /// are scanned. Each scanned file is reported as progress and, when the client sent a
+    /// partial result token, streamed back straight away.
+    pub async fn module_rename_impact(
+        &self,
+        params: ModuleRenameImpactParams,
+    ) -> Result<Vec<AffectedFile>> {
+        let uri = params.text_document.uri;
+        let span = request_span("gren/moduleRenameImpact", Some(&uri));
+        self.readiness.require_index("gren/moduleRenameImpact")?;
+        let handlers = self.handlers().await;
+
+        let plan = handlers.module_rename_plan(&uri).instrument(span.clone());
+        let plan = match plan.await? {
+            Some(plan) => plan,
+            None => return Ok(Vec::new()),
+        };
+
+        let progress_token = params.work_done_progress_params.work_done_token;
+        let partial_result_token = params.partial_result_params.partial_result_token;
+        let total = plan.importers.len();
+
+        self.send_work_done_progress(
+            &progress_token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: format!("Finding files affected by renaming {}", plan.module_name),
+                cancellable: Some(true),
+                message: Some(format!("0/{} importing files", total)),
+                percentage: Some(0),
+            }),
+        )
+        .await;
+
+        let pending = self
+            .workspace
+            .read()
+            .await
+            .index_generations()
+            .pending_files();
+        let mut affected = vec![AffectedFile {
+            index_pending: pending.contains(&plan.declaration.uri),
+            uri: plan.declaration.uri,
+            occurrences: vec![plan.declaration.range],
+        }];
+        let mut streamed = 0;
+
+        for (index, importer) in plan.importers.into_iter().enumerate() {
+            let occurrences = handlers
+                .module_references_in_file(&importer, &plan.module_name)
+                .instrument(span.clone())
+                .await;
+            if !occurrences.is_empty() {
+                affected.push(AffectedFile {
+                    index_pending: pending.contains(&importer),
+                    uri: importer,
+                    occurrences,
+                });
+            }
+
+            if let Some(token) = &partial_result_token {
+                match serde_json::to_value(&affected[streamed..]) {
+                    Ok(value) => {
+                        self.client
+                            .send_notification::<PartialResult>(PartialResultParams {
+                                token: token.clone(),
+                                value,
+                            })
+                            .await;
+                        streamed = affected.len();
+                    }
+                    Err(e) => error!("Failed to serialize affected files: {}", e),
+                }
+            }
+
+            self.send_work_done_progress(
+                &progress_token,
+                WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: Some(true),
+                    message: Some(format!("{}/{} importing files", index + 1, total)),
+                    percentage: Some(((index + 1) * 100 / total) as u32),
+                }),
+            )
+            .await;
+
+            // tower-lsp handles $/cancelRequest by dropping this future, so give it a
+            // chance to do that between files
+            tokio::task::yield_now().await;
+        }
+
+        self.send_work_done_progress(
+            &progress_token,
+            WorkDoneProgress::End(WorkDoneProgressEnd {
+                message: Some(format!("{} affected files", affected.len())),
+            }),
+        )
+        .await;
+
+        // Anything already streamed as a partial result isn't repeated in the response
+        Ok(affected.split_off(streamed))
+    }
+
+    /// Send a work done progress notification if the client supplied a token for it
+    async fn send_work_done_progress(
+        &self,
+        token: &Option<ProgressToken>,
+        progress: WorkDoneProgress,
+    ) {
+        if let Some(token) = token {
+            self.client
+                .send_notification::<notification::Progress>(ProgressParams {
+                    token: token.clone(),
+                    value: ProgressParamsValue::WorkDone(progress),
+                })
+                .await;
+        }
+    }
+
+    /// Handle the `gren/capabilitiesReport` custom request
+    pub async fn capabilities_report(&self) -> Result<CapabilitiesReportResult> {
+        let handlers = self.handlers().await;
+        handlers
+            .capabilities_report()
+            .instrument(request_span("gren/capabilitiesReport", None))
+            .await
+    }
+
+    /// Create request handlers using the current settings
+    async fn handlers(&self) -> Handlers {
+        Handlers::with_settings(self.workspace.clone(), self.effective_settings().await)
+            .with_completion_cache(self.completion_cache.clone())
+            .with_semantic_tokens_cache(self.semantic_tokens.clone())
+    }
+
+    /// Store the client's settings and apply them, under the project configuration
+    async fn apply_settings(&self, new_settings: ServerSettings) {
+        let previous_backend = self.settings.read().await.symbol_store;
+        if new_settings.symbol_store != previous_backend {
+            self.switch_symbol_store(new_settings.symbol_store).await;
+        }
+        self.deadlines.set_overrides(new_settings.timeouts.clone());
+        *self.settings.write().await = new_settings;
+        self.refresh_effective_settings().await;
+    }
+
+    /// The client's settings with the project's `.gren-lsp.toml` taking precedence
+    async fn effective_settings(&self) -> ServerSettings {
+        let settings = self.settings.read().await.clone();
+        let workspace = self.workspace.read().await;
+        settings.with_project_config(workspace.project_config())
+    }
+
+    /// Hand the workspace the settings it lints and compiles with
+    async fn refresh_effective_settings(&self) {
+        let settings = self.effective_settings().await;
+        let mut workspace = self.workspace.write().await;
+        workspace.set_import_lint(settings.imports.lint_style());
+        workspace.set_metric_hints(settings.metrics.hint_thresholds());
+        workspace.set_lint_profile(settings.lints.profile);
+        workspace.set_compiler_settings(settings.compiler);
+    }
+
+    /// Show problems in each workspace folder's `.gren-lsp.toml` on the file, or clear them
+    async fn publish_project_config_diagnostics(&self) {
+        let configs = self.workspace.read().await.project_config_diagnostics();
+        for (uri, diagnostics) in configs {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
+    }
+
+    /// Move the workspace onto a different symbol store backend. The new store starts out
+    /// with only the open documents, so once the workspace has been indexed its files and
+    /// dependency packages are indexed into the new store again, in the background.
+    async fn switch_symbol_store(&self, backend: SymbolStoreBackend) {
+        info!("Switching symbol store backend to {:?}", backend);
+
+        let store = match backend.create() {
+            Ok(store) => store,
+            Err(e) => {
+                error!("Failed to create {:?} symbol store: {}", backend, e);
+                return;
+            }
+        };
+
+        if let Err(e) = self.workspace.write().await.set_symbol_store(store) {
+            warn!("Failed to re-index symbols into {:?} store: {}", backend, e);
+        }
+        if self.readiness.indexing_has_started() {
+            let server = self.clone();
+            tokio::spawn(async move {
+                server.index_workspace_files().await;
+            });
+        }
+    }
+
+    /// Register file watchers for Gren files using LSP client capabilities
+    async fn register_file_watchers(&self) {
+        info!(
+            "Registering file watchers for *.gren files, gren.json and {}",
+            PROJECT_CONFIG_FILE
+        );
+
+        let registration_params = RegistrationParams {
+            registrations: vec![Registration {
+                id: "gren-file-watcher".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: Some(
                     serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
-                        watchers: vec![FileSystemWatcher {
-                            glob_pattern: GlobPattern::String("**/*.gren".to_string()),
-                            kind: None, // Default to all kinds (CREATE | CHANGE | DELETE)
-                        }],
+                        watchers: vec![
+                            FileSystemWatcher {
+                                glob_pattern: GlobPattern::String("**/*.gren".to_string()),
+                                kind: None, // Default to all kinds (CREATE | CHANGE | DELETE)
+                            },
+                            FileSystemWatcher {
+                                glob_pattern: GlobPattern::String("**/gren.json".to_string()),
+                                kind: None,
+                            },
+                            FileSystemWatcher {
+                                glob_pattern: GlobPattern::String(format!(
+                                    "**/{}",
+                                    PROJECT_CONFIG_FILE
+                                )),
+                                kind: None,
+                            },
+                        ],
                     })
                     .unwrap(),
                 ),
@@ -456,21 +2410,73 @@ impl GrenLanguageServer {
         }
     }
 
+    /// Register the type hierarchy requests with clients able to take them. The
+    /// `ServerCapabilities` of the lsp-types version in use has no field advertising them.
+    async fn register_type_hierarchy(&self) {
+        let supported = self
+            .readiness
+            .client_capabilities()
+            .and_then(|caps| caps.text_document.as_ref())
+            .and_then(|text_document| text_document.type_hierarchy.as_ref())
+            .and_then(|type_hierarchy| type_hierarchy.dynamic_registration)
+            .unwrap_or(false);
+        if !supported {
+            return;
+        }
+
+        let options = TypeHierarchyRegistrationOptions {
+            text_document_registration_options: TextDocumentRegistrationOptions {
+                document_selector: Some(vec![DocumentFilter {
+                    language: Some("gren".to_string()),
+                    scheme: None,
+                    pattern: None,
+                }]),
+            },
+            ..Default::default()
+        };
+        let registration = Registration {
+            id: "gren-type-hierarchy".to_string(),
+            method: "textDocument/prepareTypeHierarchy".to_string(),
+            register_options: Some(serde_json::to_value(options).unwrap()),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            warn!("Failed to register type hierarchy: {}", e);
+        }
+    }
+
+    /// Register `textDocument/inlineCompletion`. lsp-types has no client capability
+    /// saying whether it's supported, so clients that don't know it are only told about
+    /// it, and their refusal is logged.
+    async fn register_inline_completion(&self) {
+        let options = TextDocumentRegistrationOptions {
+            document_selector: Some(vec![DocumentFilter {
+                language: Some("gren".to_string()),
+                scheme: None,
+                pattern: None,
+            }]),
+        };
+        let registration = Registration {
+            id: "gren-inline-completion".to_string(),
+            method: "textDocument/inlineCompletion".to_string(),
+            register_options: Some(serde_json::to_value(options).unwrap()),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            warn!("Failed to register inline completion: {}", e);
+        }
+    }
+
     /// Index all existing Gren files in the workspace
+    #[instrument(name = "index.workspace", skip_all)]
     async fn index_workspace_files(&self) {
         info!("Starting workspace indexing");
 
         // Create progress reporting
         let progress_token = self.create_progress("Indexing workspace").await;
 
-        let workspace_root = {
-            let workspace = self.workspace.read().await;
-            workspace.stats().root_uri.clone()
-        };
-
-        if let Some(root_uri) = workspace_root {
-            info!("Indexing workspace at: {}", root_uri);
+        let folders = self.workspace.read().await.folders().to_vec();
 
+        let mut summary = ScanSummary::default();
+        if !folders.is_empty() {
             if let Some(token) = &progress_token {
                 self.report_progress(token, "Discovering files...", Some(10))
                     .await;
@@ -478,26 +2484,70 @@ impl GrenLanguageServer {
 
             // Use the client's file search capabilities instead of filesystem crawling
             // This respects .gitignore and other editor exclusion rules
-            if let Ok(root_path) = root_uri.to_file_path() {
-                self.discover_and_index_files_with_progress(root_path, progress_token.as_deref())
+            for folder in folders {
+                info!("Indexing workspace at: {}", folder);
+                if let Ok(folder_path) = folder.to_file_path() {
+                    let folder_summary = self
+                        .discover_and_index_files_with_progress(
+                            folder_path,
+                            progress_token.as_deref(),
+                        )
+                        .await;
+                    summary.merge(folder_summary);
+                }
+            }
+            self.report_scan_summary(&summary).await;
+            if let Some(token) = &progress_token {
+                self.report_progress(token, "Indexing packages...", Some(95))
                     .await;
             }
+            self.index_dependency_packages().await;
         } else {
             info!("No workspace root set, skipping indexing");
         }
 
         // End progress reporting
         if let Some(token) = progress_token {
-            self.end_progress(&token, Some("Indexing completed")).await;
+            self.end_progress(&token, Some(&summary.message())).await;
+        }
+    }
+
+    /// Log each file the scan skipped and tell the user how many there were, since
+    /// anything defined in them is missing from completion, references and renames
+    async fn report_scan_summary(&self, summary: &ScanSummary) {
+        info!("{}", summary.message());
+        if summary.skipped.is_empty() {
+            return;
+        }
+
+        for skipped in &summary.skipped {
+            warn!("Skipped {}: {}", skipped.path.display(), skipped.reason);
         }
+        let first = &summary.skipped[0];
+        let others = match summary.skipped.len() - 1 {
+            0 => String::new(),
+            1 => " and 1 other".to_string(),
+            n => format!(" and {} others", n),
+        };
+        self.client
+            .show_message(
+                MessageType::WARNING,
+                format!(
+                    "Gren: couldn't index {} ({}){}. See the server log for details.",
+                    first.path.display(),
+                    first.reason,
+                    others
+                ),
+            )
+            .await;
     }
 
     /// Discover Gren files using basic filesystem traversal as fallback
     /// In a production implementation, we'd prefer to use workspace/symbol
     /// or other LSP client capabilities for file discovery
-    async fn discover_and_index_files(&self, root_path: std::path::PathBuf) {
+    async fn discover_and_index_files(&self, root_path: std::path::PathBuf) -> ScanSummary {
         self.discover_and_index_files_with_progress(root_path, None)
-            .await;
+            .await
     }
 
     /// Discover and index files with optional progress reporting
@@ -505,14 +2555,34 @@ impl GrenLanguageServer {
         &self,
         root_path: std::path::PathBuf,
         progress_token: Option<&str>,
-    ) {
+    ) -> ScanSummary {
         use std::path::Path;
         use tokio::fs;
 
-        async fn walk_dir(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
-            let mut entries = fs::read_dir(dir).await?;
+        /// Collect the Gren files under `dir`. A directory that can't be listed is skipped
+        /// rather than ending the scan.
+        async fn walk_dir(
+            dir: &Path,
+            files: &mut Vec<std::path::PathBuf>,
+            summary: &mut ScanSummary,
+        ) {
+            let mut entries = match fs::read_dir(dir).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    summary.skip(dir, SkipReason::from_io(&e));
+                    return;
+                }
+            };
 
-            while let Some(entry) = entries.next_entry().await? {
+            loop {
+                let entry = match entries.next_entry().await {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => break,
+                    Err(e) => {
+                        summary.skip(dir, SkipReason::from_io(&e));
+                        break;
+                    }
+                };
                 let path = entry.path();
 
                 if path.is_dir() {
@@ -525,12 +2595,11 @@ impl GrenLanguageServer {
                             continue;
                         }
                     }
-                    Box::pin(walk_dir(&path, files)).await?;
+                    Box::pin(walk_dir(&path, files, summary)).await;
                 } else if path.extension().and_then(|s| s.to_str()) == Some("gren") {
                     files.push(path);
                 }
             }
-            Ok(())
         }
 
         if let Some(token) = progress_token {
@@ -538,10 +2607,24 @@ impl GrenLanguageServer {
                 .await;
         }
 
+        // Only the source directories of the folder's projects hold modules
+        let source_roots = self
+            .workspace
+            .read()
+            .await
+            .projects()
+            .source_roots(&root_path);
         let mut gren_files = Vec::new();
-        if let Err(e) = walk_dir(&root_path, &mut gren_files).await {
-            error!("Failed to walk directory {}: {}", root_path.display(), e);
-            return;
+        let mut summary = ScanSummary::default();
+        for source_root in &source_roots {
+            walk_dir(source_root, &mut gren_files, &mut summary).await;
+        }
+        {
+            let workspace = self.workspace.read().await;
+            gren_files.retain(|path| match Url::from_file_path(path) {
+                Ok(uri) => !workspace.is_excluded(&uri),
+                Err(_) => true,
+            });
         }
 
         info!("Found {} Gren files to index", gren_files.len());
@@ -560,7 +2643,10 @@ impl GrenLanguageServer {
         // Index files with progress updates
         for (index, file_path) in gren_files.iter().enumerate() {
             if let Ok(uri) = Url::from_file_path(file_path) {
-                self.index_file(&uri).await;
+                match self.index_file(&uri).await {
+                    Ok(()) => summary.indexed += 1,
+                    Err(reason) => summary.skip(file_path, reason),
+                }
             }
 
             // Report progress every 10 files or for the last file
@@ -575,28 +2661,22 @@ impl GrenLanguageServer {
         }
 
         info!("Workspace indexing complete");
+        summary
     }
 
-    /// Index a single file by URI
-    async fn index_file(&self, uri: &Url) {
+    /// Index a single file by URI, or say why it can't be
+    async fn index_file(&self, uri: &Url) -> std::result::Result<(), SkipReason> {
         use tokio::fs;
 
         // Read file content
-        let file_path = match uri.to_file_path() {
-            Ok(path) => path,
-            Err(_) => {
-                error!("Invalid file path: {}", uri);
-                return;
-            }
-        };
+        let file_path = uri
+            .to_file_path()
+            .map_err(|_| SkipReason::Unreadable("not a local file".to_string()))?;
 
-        let content = match fs::read_to_string(&file_path).await {
-            Ok(content) => content,
-            Err(e) => {
-                error!("Failed to read file {}: {}", uri, e);
-                return;
-            }
-        };
+        let bytes = fs::read(&file_path)
+            .await
+            .map_err(|e| SkipReason::from_io(&e))?;
+        let content = decode_source(bytes)?;
 
         // Create a TextDocumentItem for the file
         let text_document = TextDocumentItem {
@@ -606,97 +2686,709 @@ impl GrenLanguageServer {
             text: content,
         };
 
-        // Add to workspace
+        // Parsing and indexing run on the blocking pool, keeping the runtime's threads free
+        // for the requests arriving meanwhile
+        let workspace = self.workspace.clone();
+        tokio::task::spawn_blocking(move || {
+            workspace
+                .blocking_write()
+                .open_document(text_document)
+                .map_err(|e| SkipReason::Unreadable(format!("{:#}", e)))
+        })
+        .await
+        .map_err(|e| SkipReason::Unreadable(format!("indexing stopped: {}", e)))??;
+        info!("Successfully indexed file: {}", uri);
+        Ok(())
+    }
+
+    /// Index the exposed modules of the project's dependencies from the compiler's package
+    /// cache, so definitions, hovers and completions reach into packages, and drop the
+    /// modules of packages the project no longer depends on
+    async fn index_dependency_packages(&self) {
+        let (packages, previous) = {
+            let workspace = self.workspace.read().await;
+            (workspace.dependency_packages(), workspace.package_files())
+        };
+
+        let mut indexed = HashSet::new();
+        for package in &packages {
+            for path in package.module_files() {
+                let uri = match Url::from_file_path(&path) {
+                    Ok(uri) => uri,
+                    Err(_) => continue,
+                };
+                if let Err(reason) = self.index_file(&uri).await {
+                    warn!("Not indexing {}: {}", path.display(), reason);
+                    continue;
+                }
+                let mut workspace = self.workspace.write().await;
+                workspace.mark_package_file(&uri);
+                indexed.insert(workspace.normalize_uri(&uri));
+            }
+        }
+
         let mut workspace = self.workspace.write().await;
-        if let Err(e) = workspace.open_document(text_document) {
-            error!("Failed to index file {}: {}", uri, e);
-        } else {
-            info!("Successfully indexed file: {}", uri);
+        for uri in previous.into_iter().filter(|uri| !indexed.contains(uri)) {
+            if let Err(e) = workspace.remove_file(uri.clone()) {
+                error!("Failed to remove package file {}: {}", uri, e);
+            }
         }
+        info!(
+            "Indexed {} modules from {} packages",
+            indexed.len(),
+            packages.len()
+        );
     }
 
     /// Index all files in a specific workspace folder
+    #[instrument(name = "index.workspace", skip_all)]
     async fn index_workspace_folder(&self, folder_uri: &Url) {
         info!("Indexing workspace folder: {}", folder_uri);
 
+        let added = self.workspace.write().await.add_folder(folder_uri.clone());
+        if let Err(e) = added {
+            warn!("No compiler for workspace folder {}: {}", folder_uri, e);
+        }
+        // Adding the first folder makes it the primary one, with its `.gren-lsp.toml`
+        self.refresh_effective_settings().await;
+        self.publish_project_config_diagnostics().await;
+
         // Create progress reporting for workspace folder indexing
         let progress_token = self
             .create_progress(&format!("Indexing folder: {}", folder_uri.path()))
             .await;
 
-        if let Ok(folder_path) = folder_uri.to_file_path() {
-            self.discover_and_index_files_with_progress(folder_path, progress_token.as_deref())
+        let message = if let Ok(folder_path) = folder_uri.to_file_path() {
+            let summary = self
+                .discover_and_index_files_with_progress(folder_path, progress_token.as_deref())
                 .await;
+            self.report_scan_summary(&summary).await;
+            summary.message()
         } else {
             error!("Invalid workspace folder path: {}", folder_uri);
-        }
+            "Folder indexing completed".to_string()
+        };
 
         // End progress reporting
         if let Some(token) = progress_token {
-            self.end_progress(&token, Some("Folder indexing completed"))
-                .await;
+            self.end_progress(&token, Some(&message)).await;
         }
     }
 
-    /// Clean up symbols and documents from a removed workspace folder
-    async fn cleanup_workspace_folder(&self, folder_uri: &Url) {
-        info!("Cleaning up workspace folder: {}", folder_uri);
+    /// Add or remove a dependency in the project's `gren.json`, have the compiler fetch
+    /// packages and bring the index in line, so completion knows about a new package
+    /// straight away. Returns the message to show the user.
+    async fn edit_dependencies(
+        &self,
+        args: &DependencyCommandArguments,
+        adding: bool,
+    ) -> anyhow::Result<String> {
+        let (project_dir, compiler, compiler_options) = {
+            let workspace = self.workspace.read().await;
+            let root_uri = workspace
+                .stats()
+                .root_uri
+                .ok_or_else(|| anyhow::anyhow!("No workspace folder is open"))?;
+            let project_dir = root_uri
+                .to_file_path()
+                .map_err(|_| anyhow::anyhow!("Workspace root isn't a local folder"))?;
+            (
+                project_dir,
+                workspace.compiler_path().map(Path::to_path_buf),
+                workspace.compiler_options_for(&root_uri),
+            )
+        };
 
-        let folder_path = match folder_uri.to_file_path() {
-            Ok(path) => path,
-            Err(_) => {
-                error!("Invalid workspace folder path: {}", folder_uri);
-                return;
+        let manifest_path = project_dir.join("gren.json");
+        let content = tokio::fs::read_to_string(&manifest_path)
+            .await
+            .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+        let edited = if adding {
+            let version = args
+                .version
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("A version is needed to add {}", args.package))?;
+            manifest::add_dependency(&content, &args.package, version)?
+        } else {
+            manifest::remove_dependency(&content, &args.package)?
+        };
+        tokio::fs::write(&manifest_path, edited)
+            .await
+            .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+        self.workspace.write().await.invalidate_compiler_cache();
+
+        let mut message = if adding {
+            format!("Added {} to gren.json", args.package)
+        } else {
+            format!("Removed {} from gren.json", args.package)
+        };
+        if args.fetch {
+            match compiler {
+                Some(compiler) => {
+                    manifest::fetch_packages(&compiler, &project_dir, &compiler_options).await?
+                }
+                None => message.push_str("; no Gren compiler found to fetch packages with"),
+            }
+        }
+
+        if adding {
+            self.index_workspace_files().await;
+        } else {
+            self.drop_package_files(&args.package).await;
+        }
+        Ok(message)
+    }
+
+    /// Swap the top-level declaration at the given position with its neighbour, through
+    /// the edit journal so it can be undone. Returns the message to show the user.
+    async fn move_declaration(&self, args: &MoveDeclarationArguments) -> anyhow::Result<String> {
+        let uri = &args.text_document.uri;
+        let source = self
+            .workspace
+            .read()
+            .await
+            .file_contents(uri)
+            .ok_or_else(|| anyhow::anyhow!("Can't read {}", uri))?;
+        let tree = Parser::new()?
+            .parse(&source)?
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse {}", uri))?;
+
+        let (direction, neighbour) = match args.direction {
+            MoveDirection::Up => ("up", "above"),
+            MoveDirection::Down => ("down", "below"),
+        };
+        let block = declaration_at(&tree, &source, args.position).ok_or_else(|| {
+            anyhow::anyhow!("There's no top-level declaration here that can be moved")
+        })?;
+        let edit =
+            declaration_order::move_declaration(&tree, &source, args.position, args.direction)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("There's no declaration {} `{}`", neighbour, block.name)
+                })?;
+
+        let label = format!("Move `{}` {}", block.name, direction);
+        let changes = HashMap::from([(uri.clone(), vec![edit])]);
+        apply_server_edit(
+            &self.client,
+            &self.workspace,
+            &label,
+            WorkspaceEdit::new(changes),
+        )
+        .await?;
+        Ok(format!("Moved `{}` {}", block.name, direction))
+    }
+
+    /// Add names to and remove names from a module's exposing list, and with `expose_used`
+    /// expose what importers use but the module doesn't. Returns the message to show the
+    /// user.
+    async fn edit_exposing(&self, args: &EditExposingArguments) -> anyhow::Result<String> {
+        let uri = &args.text_document.uri;
+        let source = self
+            .workspace
+            .read()
+            .await
+            .file_contents(uri)
+            .ok_or_else(|| anyhow::anyhow!("Can't read {}", uri))?;
+        let tree = Parser::new()?
+            .parse(&source)?
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse {}", uri))?;
+
+        let mut expose = args.expose.clone();
+        if args.expose_used {
+            let exports = self
+                .handlers()
+                .await
+                .module_exports(ModuleExportsParams {
+                    text_document: args.text_document.clone(),
+                })
+                .await
+                .map_err(|e| anyhow::anyhow!("{}", e.message))?
+                .ok_or_else(|| anyhow::anyhow!("{} has no module declaration", uri))?;
+            expose.extend(
+                exports
+                    .declarations
+                    .into_iter()
+                    .filter(|export| !export.exposed && export.external_usages > 0)
+                    .map(|export| export.name),
+            );
+        }
+
+        let edit = match exposing::edit_exposing(&tree, &source, &expose, &args.unexpose) {
+            Some(edit) => edit,
+            None if expose.is_empty() && !args.unexpose.is_empty() => {
+                anyhow::bail!("A module has to expose at least one declaration")
             }
+            None => return Ok("The exposing list is already up to date".to_string()),
         };
+        let changes = HashMap::from([(uri.clone(), vec![edit])]);
+        apply_server_edit(
+            &self.client,
+            &self.workspace,
+            "Edit exposing list",
+            WorkspaceEdit::new(changes),
+        )
+        .await?;
+        Ok(format!(
+            "Exposed {} and stopped exposing {} declarations",
+            expose.len(),
+            args.unexpose.len()
+        ))
+    }
+
+    /// Run the formatter over every project file without writing anything, giving the
+    /// unformatted ones a diagnostic. Returns the message to show the user.
+    async fn check_formatting(&self) -> anyhow::Result<String> {
+        let (checked, failed) = self.format_project_files().await?;
+        let unformatted: Vec<Url> = checked
+            .iter()
+            .filter(|file| file.source != file.formatted)
+            .map(|file| file.uri.clone())
+            .collect();
+        let uris: Vec<Url> = checked.iter().map(|file| file.uri.clone()).collect();
+        let changed = self
+            .workspace
+            .write()
+            .await
+            .set_unformatted(&uris, &unformatted);
+        self.republish_diagnostics(changed).await;
+
+        let mut message = format!(
+            "{} of {} files aren't formatted",
+            unformatted.len(),
+            checked.len()
+        );
+        if failed > 0 {
+            message.push_str(&format!(", {} couldn't be formatted", failed));
+        }
+        Ok(message)
+    }
+
+    /// Format every project file the formatter changes, in one edit. Returns the message to
+    /// show the user.
+    async fn format_workspace(&self) -> anyhow::Result<String> {
+        let (checked, failed) = self.format_project_files().await?;
+        let changes: HashMap<Url, Vec<TextEdit>> = checked
+            .iter()
+            .filter_map(|file| {
+                let edit = whole_document_edit(&file.source, &file.formatted)?;
+                Some((file.uri.clone(), vec![edit]))
+            })
+            .collect();
+        let formatted = changes.len();
+        if formatted > 0 {
+            apply_server_edit(
+                &self.client,
+                &self.workspace,
+                "Format workspace",
+                WorkspaceEdit::new(changes),
+            )
+            .await?;
+        }
 
-        let folder_path_str = folder_path.to_string_lossy();
+        let uris: Vec<Url> = checked.iter().map(|file| file.uri.clone()).collect();
+        let changed = self.workspace.write().await.set_unformatted(&uris, &[]);
+        self.republish_diagnostics(changed).await;
 
-        // Get list of documents that start with the folder path
-        let documents_to_remove = {
+        let mut message = format!("Formatted {} of {} files", formatted, checked.len());
+        if failed > 0 {
+            message.push_str(&format!(", {} couldn't be formatted", failed));
+        }
+        Ok(message)
+    }
+
+    /// Every project file with what the formatter makes of it, and how many files it
+    /// refused, usually because they don't parse
+    async fn format_project_files(&self) -> anyhow::Result<(Vec<FormattedFile>, usize)> {
+        let (formatter, sources) = {
             let workspace = self.workspace.read().await;
-            let stats = workspace.stats();
-            let mut docs = Vec::new();
-
-            // Find all documents in this workspace folder
-            // This is a simplified approach - in a more sophisticated implementation,
-            // we'd have better workspace folder tracking
-            for uri in stats.open_documents {
-                if let Ok(doc_path) = uri.to_file_path() {
-                    let doc_path_str = doc_path.to_string_lossy();
-                    if doc_path_str.starts_with(&*folder_path_str) {
-                        docs.push(uri);
-                    }
+            let formatter = workspace.formatter().ok_or_else(|| {
+                anyhow::anyhow!("Formatting needs the Gren compiler, which isn't available")
+            })?;
+            let sources: Vec<(Url, String)> = workspace
+                .project_module_files()
+                .into_iter()
+                .filter_map(|uri| {
+                    let source = workspace.file_contents(&uri)?;
+                    Some((uri, source))
+                })
+                .collect();
+            (formatter, sources)
+        };
+
+        let mut files = Vec::new();
+        let mut failed = 0;
+        for (uri, source) in sources {
+            match formatter.format(&source).await {
+                Ok(formatted) => files.push(FormattedFile {
+                    uri,
+                    source,
+                    formatted,
+                }),
+                Err(e) => {
+                    warn!("Failed to format {}: {}", uri, e);
+                    failed += 1;
+                }
+            }
+        }
+        Ok((files, failed))
+    }
+
+    /// Compile `uris` again in the background, the documents open in the editor first
+    async fn schedule_recompile(&self, uris: Vec<Url>) {
+        if self.recompile_queue.lock().await.schedule(uris) {
+            tokio::spawn(drain_recompile_queue(
+                self.client.clone(),
+                self.workspace.clone(),
+                self.recompile_queue.clone(),
+            ));
+        }
+    }
+
+    /// Remove a deleted file, or the files in a deleted folder, from the index and clear
+    /// the diagnostics the client still shows for them
+    async fn remove_deleted_file(&self, uri: &Url) {
+        self.clear_stale_diagnostics(uri).await;
+        let mut workspace = self.workspace.write().await;
+        if let Err(e) = workspace.remove_file(uri.clone()) {
+            info!("Failed to remove file from index: {}", e);
+        }
+    }
+
+    /// Publish empty diagnostics for `uri`, or the files in it, where diagnostics were
+    /// published before
+    async fn clear_stale_diagnostics(&self, uri: &Url) {
+        let (stale, push) = {
+            let mut workspace = self.workspace.write().await;
+            let stale = workspace.forget_published_diagnostics(uri);
+            (stale, workspace.pushes_diagnostics())
+        };
+        if !push {
+            if !stale.is_empty() {
+                refresh_pulled_diagnostics(&self.client).await;
+            }
+            return;
+        }
+        for stale_uri in stale {
+            info!(
+                "Clearing diagnostics of {}, which no longer exists",
+                stale_uri
+            );
+            self.client
+                .publish_diagnostics(stale_uri, Vec::new(), None)
+                .await;
+        }
+    }
+
+    /// Publish the diagnostics of files again after something other than an edit changed
+    /// them. Compile results are cached, so unchanged files aren't compiled again.
+    async fn republish_diagnostics(&self, uris: Vec<Url>) {
+        let push = self.workspace.read().await.pushes_diagnostics();
+        let refresh = !push && !uris.is_empty();
+        for uri in uris {
+            let result = {
+                let mut workspace = self.workspace.write().await;
+                let result = workspace
+                    .get_document_diagnostics_with_global_errors(&uri)
+                    .await;
+                if let Ok((diagnostics, _)) = &result {
+                    workspace.record_published_diagnostics(&uri, diagnostics);
+                }
+                result
+            };
+            match result {
+                Ok((diagnostics, _)) if push => {
+                    self.client
+                        .publish_diagnostics(uri, diagnostics, None)
+                        .await
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to refresh diagnostics of {}: {}", uri, e),
+            }
+        }
+        if refresh {
+            refresh_pulled_diagnostics(&self.client).await;
+        }
+    }
+
+    /// Replace every string literal in the project producing the same string as the one at
+    /// the given position. Returns the message to show the user.
+    async fn rename_string_literal(
+        &self,
+        args: &RenameStringLiteralArguments,
+    ) -> anyhow::Result<String> {
+        let uri = &args.text_document.uri;
+        let (source, files) = {
+            let workspace = self.workspace.read().await;
+            let source = workspace
+                .file_contents(uri)
+                .ok_or_else(|| anyhow::anyhow!("Can't read {}", uri))?;
+            let uri = workspace.normalize_uri(uri);
+            let mut files = workspace.project_module_files();
+            if !files.contains(&uri) {
+                files.push(uri.clone());
+            }
+            let files: Vec<(Url, String)> = files
+                .into_iter()
+                .filter_map(|file| {
+                    let content = if file == uri {
+                        source.clone()
+                    } else {
+                        workspace.file_contents(&file)?
+                    };
+                    Some((file, content))
+                })
+                .collect();
+            (source, files)
+        };
+
+        let mut parser = Parser::new()?;
+        let tree = parser
+            .parse(&source)?
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse {}", uri))?;
+        let literal = string_literal_at(&tree, &source, args.position)
+            .ok_or_else(|| anyhow::anyhow!("There's no string literal here"))?;
+        if literal.value.is_empty() {
+            anyhow::bail!("Empty strings can't be renamed across the project");
+        }
+        if literal.value == args.new_value {
+            return Ok(format!("The string is already {:?}", args.new_value));
+        }
+
+        let mut changes = HashMap::new();
+        let mut occurrences = 0;
+        for (file, content) in files {
+            let tree = match parser.parse(&content) {
+                Ok(Some(tree)) => tree,
+                _ => {
+                    warn!(
+                        "Not renaming string literals in {}, it failed to parse",
+                        file
+                    );
+                    continue;
                 }
+            };
+            let edits = rename_string_literals(&tree, &content, &literal.value, &args.new_value);
+            if !edits.is_empty() {
+                occurrences += edits.len();
+                changes.insert(file, edits);
             }
-            docs
+        }
+
+        let label = format!("Rename {:?} to {:?}", literal.value, args.new_value);
+        let file_count = changes.len();
+        apply_server_edit(
+            &self.client,
+            &self.workspace,
+            &label,
+            WorkspaceEdit::new(changes),
+        )
+        .await?;
+        Ok(format!(
+            "Replaced {} {} of {:?} in {} {}",
+            occurrences,
+            if occurrences == 1 {
+                "literal"
+            } else {
+                "literals"
+            },
+            literal.value,
+            file_count,
+            if file_count == 1 { "file" } else { "files" }
+        ))
+    }
+
+    /// Revert the most recent edit the server applied, unless a file it touched has
+    /// changed since. Returns the message to show the user.
+    async fn undo_last_server_edit(&self) -> anyhow::Result<String> {
+        let (entry, current) = {
+            let workspace = self.workspace.read().await;
+            let entry = match workspace.last_server_edit() {
+                Some(entry) => entry.clone(),
+                None => return Ok("No server edit to undo".to_string()),
+            };
+            let current: HashMap<Url, String> = entry
+                .files()
+                .into_iter()
+                .filter_map(|uri| Some((uri.clone(), workspace.file_contents(&uri)?)))
+                .collect();
+            (entry, current)
         };
+        entry.check_unchanged(&current)?;
+
+        mark_index_pending(&self.client, &self.workspace, &entry.files()).await;
+        let response = self.client.apply_edit(entry.undo.clone()).await?;
+        if !response.applied {
+            anyhow::bail!(
+                "Client declined to undo \"{}\": {}",
+                entry.label,
+                response
+                    .failure_reason
+                    .unwrap_or_else(|| "no reason given".to_string())
+            );
+        }
+        self.workspace.write().await.pop_server_edit();
+        Ok(format!("Undid \"{}\"", entry.label))
+    }
 
-        // Remove documents from the removed workspace folder
+    /// Remove the indexed source files of `package` (`author/name`)
+    async fn drop_package_files(&self, package: &str) {
         let mut workspace = self.workspace.write().await;
-        for uri in documents_to_remove {
-            info!("Removing document from workspace: {}", uri);
+        let package_files: Vec<Url> = workspace
+            .stats()
+            .open_documents
+            .into_iter()
+            .filter(|uri| {
+                uri.to_file_path()
+                    .ok()
+                    .and_then(|path| package_module_for_path(&path))
+                    .is_some_and(|module| format!("{}/{}", module.author, module.name) == package)
+            })
+            .collect();
+
+        for uri in package_files {
             if let Err(e) = workspace.remove_file(uri.clone()) {
-                error!("Failed to remove document {}: {}", uri, e);
+                error!("Failed to remove package file {}: {}", uri, e);
             }
         }
+    }
+
+    /// Clean up symbols and documents from a removed workspace folder
+    async fn cleanup_workspace_folder(&self, folder_uri: &Url) {
+        info!("Cleaning up workspace folder: {}", folder_uri);
+
+        self.clear_stale_diagnostics(folder_uri).await;
+        let removed = self.workspace.write().await.remove_folder(folder_uri);
+        for uri in &removed {
+            info!("Removed document from workspace: {}", uri);
+        }
+        // Removing the primary folder makes the next one primary
+        self.refresh_effective_settings().await;
 
         info!("Workspace folder cleanup completed: {}", folder_uri);
     }
 
-    /// Create a work done progress token and begin progress reporting
-    async fn create_progress(&self, title: &str) -> Option<String> {
-        // Check if client supports work done progress
-        let supports_progress = {
-            let capabilities = self.client_capabilities.read().await;
-            capabilities
-                .as_ref()
-                .and_then(|caps| caps.window.as_ref())
-                .and_then(|window| window.work_done_progress)
-                .unwrap_or(false)
+    /// Expand a rename entry into the Gren files it affects. A folder rename moves every
+    /// indexed or open Gren file below the old folder.
+    async fn expand_renamed_files(&self, old_uri: &Url, new_uri: &Url) -> Vec<(Url, Url)> {
+        if new_uri.path().ends_with(".gren") {
+            return vec![(old_uri.clone(), new_uri.clone())];
+        }
+
+        let old_prefix = format!("{}/", old_uri.as_str().trim_end_matches('/'));
+        let new_prefix = format!("{}/", new_uri.as_str().trim_end_matches('/'));
+
+        let workspace = self.workspace.read().await;
+        workspace
+            .files_below(old_uri)
+            .into_iter()
+            .filter(|uri| uri.path().ends_with(".gren"))
+            .filter_map(|uri| {
+                let relative = uri.as_str().strip_prefix(&old_prefix)?;
+                let moved = Url::parse(&format!("{}{}", new_prefix, relative)).ok()?;
+                Some((uri, moved))
+            })
+            .collect()
+    }
+
+    /// Ask the user whether to rewrite the module declarations of renamed files (and their
+    /// importers) so they match the new paths, applying the edit if they accept. A folder
+    /// rename asks once for all the modules in it.
+    async fn offer_module_rename_sync(&self, new_uris: Vec<Url>) {
+        const UPDATE_ACTION: &str = "Update module name";
+
+        let handlers = self.handlers().await;
+        let mut syncs = Vec::new();
+        for new_uri in &new_uris {
+            match handlers.module_rename_for_file_rename(new_uri).await {
+                Ok(Some(sync)) => syncs.push(sync),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to compute module rename for {}: {:?}", new_uri, e),
+            }
+        }
+
+        let (message, label) = match syncs.as_slice() {
+            [] => return,
+            [sync] if sync.importing_files == 0 => (
+                format!(
+                    "Module '{}' no longer matches its file path. Rename it to '{}'?",
+                    sync.old_module_name, sync.new_module_name
+                ),
+                format!(
+                    "Rename module '{}' to '{}'",
+                    sync.old_module_name, sync.new_module_name
+                ),
+            ),
+            [sync] => (
+                format!(
+                    "Module '{}' no longer matches its file path. Rename it to '{}' and update {} importing file(s)?",
+                    sync.old_module_name, sync.new_module_name, sync.importing_files
+                ),
+                format!(
+                    "Rename module '{}' to '{}'",
+                    sync.old_module_name, sync.new_module_name
+                ),
+            ),
+            syncs => (
+                format!(
+                    "{} modules no longer match their file paths. Rename them to match and update their importers?",
+                    syncs.len()
+                ),
+                format!("Rename {} modules", syncs.len()),
+            ),
         };
+        let names: Vec<String> = syncs
+            .iter()
+            .map(|sync| format!("'{}' -> '{}'", sync.old_module_name, sync.new_module_name))
+            .collect();
+        let edit = merged_edit(syncs.into_iter().map(|sync| sync.edit).collect());
+
+        // The prompt waits on the user, so don't hold up other notifications while it is open
+        let client = self.client.clone();
+        let workspace = self.workspace.clone();
+        tokio::spawn(async move {
+            let choice = client
+                .show_message_request(
+                    MessageType::WARNING,
+                    message,
+                    Some(vec![
+                        MessageActionItem {
+                            title: UPDATE_ACTION.to_string(),
+                            properties: HashMap::new(),
+                        },
+                        MessageActionItem {
+                            title: "Ignore".to_string(),
+                            properties: HashMap::new(),
+                        },
+                    ]),
+                )
+                .await;
 
-        if !supports_progress {
+            match choice {
+                Ok(Some(action)) if action.title == UPDATE_ACTION => {
+                    info!("📋 Applying module rename {}", names.join(", "));
+                    match apply_server_edit(&client, &workspace, &label, edit).await {
+                        Ok(()) => info!("✅ Module rename applied"),
+                        Err(e) => warn!("❌ Module rename edit not applied: {:#}", e),
+                    }
+                }
+                Ok(_) => info!("Module rename {} dismissed", names.join(", ")),
+                Err(e) => warn!("Module rename prompt failed: {}", e),
+            }
+        });
+    }
+
+    /// Whether the client accepts server-initiated work done progress
+    fn supports_work_done_progress(&self) -> bool {
+        self.readiness
+            .client_capabilities()
+            .and_then(|caps| caps.window.as_ref())
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false)
+    }
+
+    /// Create a work done progress token and begin progress reporting
+    async fn create_progress(&self, title: &str) -> Option<String> {
+        if !self.supports_work_done_progress() {
             info!("Client doesn't support work done progress, using log messages");
             return None;
         }
@@ -725,144 +3417,71 @@ impl GrenLanguageServer {
         }
     }
 
-    /// Schedule debounced diagnostics update for real-time feedback
+    /// Compile an edited document once typing pauses and publish its diagnostics,
+    /// cancelling the compile waiting or running for an earlier edit
     async fn schedule_debounced_diagnostics(&self, uri: Url) {
-        const DEBOUNCE_DURATION: Duration = Duration::from_millis(150); // 150ms debounce for faster feedback
-
-        // Record when this document was last changed
-        {
-            let mut pending = self.pending_diagnostics.write().await;
-            pending.insert(uri.clone(), Instant::now());
-        }
-
-        // Clone necessary data for the async task
-        let uri_clone = uri.clone();
+        let delay = Duration::from_millis(self.settings.read().await.diagnostics.debounce_ms);
         let client = self.client.clone();
         let workspace = self.workspace.clone();
-        let pending_diagnostics = self.pending_diagnostics.clone();
-
-        // Clone the client for global error notifications
-        let client_for_notifications = self.client.clone();
-
-        // Spawn a task to handle the debounced update
-        tokio::spawn(async move {
-            // Wait for the debounce period
-            sleep(DEBOUNCE_DURATION).await;
-
-            // Check if this is still the latest change for this document
-            let should_process = {
-                let pending = pending_diagnostics.read().await;
-                if let Some(last_change) = pending.get(&uri_clone) {
-                    // Only process if enough time has passed since the last change
-                    last_change.elapsed() >= DEBOUNCE_DURATION
-                } else {
-                    false // Document was removed from pending updates
-                }
+        let compile_scheduler = self.compile_scheduler.clone();
+
+        // Held until the task is recorded, so it can't finish before that
+        let mut scheduler = self.compile_scheduler.lock().await;
+        let ticket = scheduler.request(&uri);
+        let task_uri = uri.clone();
+        let task = tokio::spawn(async move {
+            sleep(delay).await;
+
+            // The compiler runs without the workspace lock held, so requests answered
+            // meanwhile don't wait for it
+            let compile = workspace.write().await.take_document_compile(&task_uri);
+            let compiled = match compile {
+                Some(compile) => Some(compile.run().await),
+                None => None,
             };
 
-            if should_process {
-                // Remove from pending updates
-                {
-                    let mut pending = pending_diagnostics.write().await;
-                    pending.remove(&uri_clone);
-                }
-
-                // Get comprehensive diagnostics and global errors (syntax + compiler)
-                let (diagnostics, global_errors) = {
-                    let mut workspace = workspace.write().await;
-                    match workspace
-                        .get_document_diagnostics_with_global_errors(&uri_clone)
-                        .await
-                    {
-                        Ok((diags, global_errs)) => (diags, global_errs),
-                        Err(e) => {
-                            warn!(
-                                "Failed to get real-time diagnostics for {}: {}",
-                                uri_clone, e
-                            );
-                            // Fallback to syntax-only diagnostics
-                            (workspace.get_diagnostics(&uri_clone), Vec::new())
-                        }
+            // Get comprehensive diagnostics and global errors (syntax + compiler)
+            let (diagnostics, global_errors, compile_reports) = {
+                let mut workspace = workspace.write().await;
+                let result = match compiled {
+                    Some(compiled) => Ok(workspace.finish_document_compile(compiled)),
+                    None => {
+                        workspace
+                            .get_document_diagnostics_with_global_errors(&task_uri)
+                            .await
                     }
                 };
-
-                // Send global error notifications if any were found
-                if !global_errors.is_empty() {
-                    for global_error in global_errors {
-                        let message = match global_error.title.as_str() {
-                            "GREN VERSION MISMATCH" => {
-                                format!("🚨 Gren Version Mismatch\n\n{}\n\nPlease update your Gren compiler or modify your gren.json file to match.", global_error.message)
-                            }
-                            _ => {
-                                format!(
-                                    "🚨 Compiler Error: {}\n\n{}",
-                                    global_error.title, global_error.message
-                                )
-                            }
-                        };
-
-                        let message_type = match global_error.severity {
-                            gren_lsp_core::compiler::DiagnosticSeverity::Error => {
-                                MessageType::ERROR
-                            }
-                            gren_lsp_core::compiler::DiagnosticSeverity::Warning => {
-                                MessageType::WARNING
-                            }
-                            gren_lsp_core::compiler::DiagnosticSeverity::Info => MessageType::INFO,
-                        };
-
-                        info!(
-                            "📢 Sending global error notification: {}",
-                            global_error.title
+                let (diagnostics, global_errors) = match result {
+                    Ok((diags, global_errs)) => (diags, global_errs),
+                    Err(e) => {
+                        warn!(
+                            "Failed to get real-time diagnostics for {}: {}",
+                            task_uri, e
                         );
-                        client_for_notifications
-                            .show_message(message_type, message)
-                            .await;
+                        // Fallback to syntax-only diagnostics
+                        (workspace.get_diagnostics(&task_uri), Vec::new())
                     }
-                }
+                };
+                workspace.record_published_diagnostics(&task_uri, &diagnostics);
+                (diagnostics, global_errors, workspace.take_compile_reports())
+            };
 
-                // Publish diagnostics
-                client
-                    .publish_diagnostics(uri_clone, diagnostics, None)
-                    .await;
-            } else {
-                info!("⚡ Skipping outdated diagnostic update for: {}", uri_clone);
+            if !global_errors.is_empty() {
+                send_global_error_notifications(&client, global_errors).await;
             }
+            client
+                .publish_diagnostics(task_uri.clone(), diagnostics, None)
+                .await;
+            send_compile_reports(&client, compile_reports).await;
+            compile_scheduler.lock().await.finished(&task_uri, ticket);
         });
+        scheduler.started(uri, ticket, task.abort_handle());
     }
 
-    /// Send global error notifications to the client
-    async fn send_global_error_notifications(
-        &self,
-        global_errors: Vec<gren_lsp_core::compiler::GlobalError>,
-    ) {
-        for global_error in global_errors {
-            let message = match global_error.title.as_str() {
-                "GREN VERSION MISMATCH" => {
-                    // Extract version information from the message for a more actionable notification
-                    format!("🚨 Gren Version Mismatch\n\n{}\n\nPlease update your Gren compiler or modify your gren.json file to match.", global_error.message)
-                }
-                _ => {
-                    format!(
-                        "🚨 Compiler Error: {}\n\n{}",
-                        global_error.title, global_error.message
-                    )
-                }
-            };
-
-            // Use MessageType::Error for critical issues like version mismatches
-            let message_type = match global_error.severity {
-                gren_lsp_core::compiler::DiagnosticSeverity::Error => MessageType::ERROR,
-                gren_lsp_core::compiler::DiagnosticSeverity::Warning => MessageType::WARNING,
-                gren_lsp_core::compiler::DiagnosticSeverity::Info => MessageType::INFO,
-            };
-
-            // Send notification to VS Code
-            info!(
-                "📢 Sending global error notification: {}",
-                global_error.title
-            );
-            self.client.show_message(message_type, message).await;
+    /// Cancel the compile waiting or running for an edit of `uri`, which stops its compiler
+    async fn cancel_edit_compile(&self, uri: &Url) {
+        if self.compile_scheduler.lock().await.cancel(uri) {
+            info!("⚡ Cancelled outdated compile of {}", uri);
         }
     }
 }
@@ -0,0 +1,56 @@
+//! OTLP export of request-handling spans, enabled with the `otel` feature.
+//!
+//! Spans for requests (`lsp.request`), parsing (`parse`), symbol index queries
+//! (`index.query`) and compilation (`compile`) are sent to a collector such as Jaeger or
+//! the OpenTelemetry Collector, carrying the request method, a hash of the document URI
+//! and busy/idle durations.
+
+use anyhow::Result;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Collector endpoint used when none is given on the command line
+pub const DEFAULT_OTLP_ENDPOINT: &str = "http://localhost:4317";
+
+/// Keeps the exporter alive; dropping it flushes any spans still buffered
+pub struct TelemetryGuard {
+    provider: TracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush OpenTelemetry spans: {}", e);
+        }
+    }
+}
+
+/// Build a tracing layer exporting spans over OTLP/gRPC to `endpoint`
+pub fn otlp_layer<S>(endpoint: &str) -> Result<(impl Layer<S>, TelemetryGuard)>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new(vec![
+            KeyValue::new("service.name", "gren-lsp"),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]))
+        .build();
+
+    let tracer = provider.tracer("gren-lsp");
+    let layer = tracing_opentelemetry::layer().with_tracer(tracer);
+
+    Ok((layer, TelemetryGuard { provider }))
+}
@@ -0,0 +1,160 @@
+//! Changing how much the server logs while it runs
+//!
+//! `$/setTrace` sets the overall verbosity: `off` goes back to the filter the server was
+//! started with (`RUST_LOG`), `messages` logs everything at debug level and `verbose` at
+//! trace level. `gren/setTrace` turns a single subsystem up to trace level on top of that,
+//! so one misbehaving feature can be followed without the log filling up with every other
+//! request. Subsystems are selected through the spans their work runs in, which also picks
+//! up what the core crate logs on their behalf.
+
+use anyhow::{Context, Result};
+use gren_lsp_protocol::lsp_ext::TraceSubsystem;
+use lsp_types::TraceValue;
+use std::collections::BTreeSet;
+use tracing::info;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Filter used when `RUST_LOG` isn't set
+pub const DEFAULT_DIRECTIVES: &str = "gren_lsp=debug";
+
+/// Handle swapping the filter of the installed subscriber
+pub type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// The filter the server logs with and what it was built from
+#[derive(Debug)]
+pub struct TraceControl {
+    startup_directives: String,
+    trace: TraceValue,
+    subsystems: BTreeSet<TraceSubsystem>,
+    handle: Option<FilterHandle>,
+}
+
+impl TraceControl {
+    /// Control the filter behind `handle`, which was created from `startup_directives`
+    pub fn new(startup_directives: impl Into<String>, handle: FilterHandle) -> Self {
+        Self {
+            handle: Some(handle),
+            ..Self::detached(startup_directives)
+        }
+    }
+
+    /// Track trace settings without a subscriber to apply them to, as in tests
+    pub fn detached(startup_directives: impl Into<String>) -> Self {
+        Self {
+            startup_directives: startup_directives.into(),
+            trace: TraceValue::Off,
+            subsystems: BTreeSet::new(),
+            handle: None,
+        }
+    }
+
+    /// Handle `$/setTrace`
+    pub fn set_trace(&mut self, trace: TraceValue) -> Result<()> {
+        self.trace = trace;
+        self.apply()
+    }
+
+    /// Handle `gren/setTrace`
+    pub fn set_subsystem(&mut self, subsystem: TraceSubsystem, enabled: bool) -> Result<()> {
+        if enabled {
+            self.subsystems.insert(subsystem);
+        } else {
+            self.subsystems.remove(&subsystem);
+        }
+        self.apply()
+    }
+
+    /// The filter directives for the current settings
+    pub fn directives(&self) -> String {
+        let base = match self.trace {
+            TraceValue::Off => self.startup_directives.as_str(),
+            TraceValue::Messages => "gren_lsp=debug",
+            TraceValue::Verbose => "gren_lsp=trace",
+        };
+        let mut directives = vec![base.to_string()];
+        for subsystem in &self.subsystems {
+            directives.extend(
+                subsystem_spans(*subsystem)
+                    .iter()
+                    .map(|span| format!("[{}]=trace", span)),
+            );
+        }
+        directives.join(",")
+    }
+
+    fn apply(&self) -> Result<()> {
+        let handle = match &self.handle {
+            Some(handle) => handle,
+            None => return Ok(()),
+        };
+        let directives = self.directives();
+        let filter = EnvFilter::try_new(&directives)
+            .with_context(|| format!("Invalid log filter `{}`", directives))?;
+        handle
+            .reload(filter)
+            .context("Failed to replace the log filter")?;
+        info!("Log filter is now `{}`", directives);
+        Ok(())
+    }
+}
+
+/// Spans a subsystem's work runs in, as `EnvFilter` span selectors
+fn subsystem_spans(subsystem: TraceSubsystem) -> &'static [&'static str] {
+    match subsystem {
+        TraceSubsystem::Completion => &["lsp.request{method=textDocument/completion}"],
+        TraceSubsystem::Index => &["index.workspace", "index.query"],
+        TraceSubsystem::Compile => &["compile"],
+        TraceSubsystem::Rename => &[
+            "lsp.request{method=textDocument/rename}",
+            "lsp.request{method=gren/moduleRenameImpact}",
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives_follow_settings() {
+        let mut control = TraceControl::detached("gren_lsp=info");
+        assert_eq!(control.directives(), "gren_lsp=info");
+
+        control
+            .set_subsystem(TraceSubsystem::Completion, true)
+            .unwrap();
+        control
+            .set_subsystem(TraceSubsystem::Compile, true)
+            .unwrap();
+        assert_eq!(
+            control.directives(),
+            "gren_lsp=info,[lsp.request{method=textDocument/completion}]=trace,[compile]=trace"
+        );
+
+        control.set_trace(TraceValue::Verbose).unwrap();
+        control
+            .set_subsystem(TraceSubsystem::Completion, false)
+            .unwrap();
+        assert_eq!(control.directives(), "gren_lsp=trace,[compile]=trace");
+
+        control.set_trace(TraceValue::Off).unwrap();
+        control
+            .set_subsystem(TraceSubsystem::Compile, false)
+            .unwrap();
+        assert_eq!(control.directives(), "gren_lsp=info");
+    }
+
+    #[test]
+    fn test_every_subsystem_builds_a_valid_filter() {
+        let mut control = TraceControl::detached(DEFAULT_DIRECTIVES);
+        for subsystem in [
+            TraceSubsystem::Completion,
+            TraceSubsystem::Index,
+            TraceSubsystem::Compile,
+            TraceSubsystem::Rename,
+        ] {
+            control.set_subsystem(subsystem, true).unwrap();
+        }
+        assert!(EnvFilter::try_new(control.directives()).is_ok());
+    }
+}